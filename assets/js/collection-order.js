@@ -0,0 +1,73 @@
+/**
+ * Drag-and-drop ordering of the bookmarks in a smart collection.
+ *
+ * A collection shown in its own order has its list wrapped in an element with a
+ * `data-reorder-url`. When a bookmark is dropped in a new place, the IDs of the bookmarks shown
+ * are sent there in their new order; if saving fails, they go back where they were.
+ */
+(function () {
+  const ITEMS = "[data-reorder-url] .bookmark-item";
+
+  let dragged = null;
+  let before = [];
+
+  // The list is swapped in by HTMX, so items are made draggable as they're loaded
+  document.addEventListener("htmx:load", (event) => {
+    event.detail.elt.querySelectorAll(ITEMS).forEach((item) => {
+      item.draggable = true;
+    });
+  });
+
+  document.addEventListener("dragstart", (event) => {
+    const item = event.target.closest && event.target.closest(ITEMS);
+    if (!item) return;
+    dragged = item;
+    before = [...item.parentElement.querySelectorAll(".bookmark-item")];
+    item.classList.add("bookmark-dragging");
+    event.dataTransfer.effectAllowed = "move";
+    event.dataTransfer.setData("text/plain", item.dataset.bookmarkId);
+  });
+
+  document.addEventListener("dragover", (event) => {
+    if (!dragged) return;
+    const item = event.target.closest && event.target.closest(".bookmark-item");
+    if (!item || item.parentElement !== dragged.parentElement) return;
+    event.preventDefault();
+    if (item === dragged) return;
+
+    // Grids run across, lists down
+    const rect = item.getBoundingClientRect();
+    const after = item.parentElement.classList.contains("bookmark-list-grid")
+      ? event.clientX > rect.left + rect.width / 2
+      : event.clientY > rect.top + rect.height / 2;
+    item.parentElement.insertBefore(dragged, after ? item.nextSibling : item);
+  });
+
+  document.addEventListener("drop", (event) => {
+    if (dragged) event.preventDefault();
+  });
+
+  document.addEventListener("dragend", async () => {
+    if (!dragged) return;
+    const item = dragged;
+    dragged = null;
+    item.classList.remove("bookmark-dragging");
+
+    const list = item.parentElement;
+    const items = [...list.querySelectorAll(".bookmark-item")];
+    if (items.every((other, index) => other === before[index])) return;
+
+    const reorder = item.closest("[data-reorder-url]");
+    const response = await fetch(reorder.dataset.reorderUrl, {
+      method: "PUT",
+      headers: { "Content-Type": "application/x-www-form-urlencoded" },
+      body: new URLSearchParams({ bookmark_ids: items.map((other) => other.dataset.bookmarkId).join(",") }),
+    }).catch(() => null);
+    if (!response || !response.ok) {
+      console.warn("Could not save the collection's order", { status: response && response.status });
+      // Put them back, ahead of whatever ends the list
+      const end = items[items.length - 1].nextSibling;
+      before.forEach((other) => list.insertBefore(other, end));
+    }
+  });
+})();
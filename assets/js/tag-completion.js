@@ -32,6 +32,7 @@ class TagCompletion {
     this.debounceTimeout = null;
     this.searchTimeout = null;
     this.committedTags = new Set(); // Track committed tags separately
+    this.sort = null; // Sort order restored from the page URL, if any
 
     // Prevent race conditions with operation locks
     this.isUpdatingTags = false;
@@ -49,11 +50,22 @@ class TagCompletion {
 
     if (!this.searchInput) return;
 
+    this.restoreStateFromUrl();
     this.bindEvents();
     this.setupTagColumnEventDelegation();
     this.setupBookmarkTagsEventDelegation();
   }
 
+  /**
+   * Restore committed tags and sort order from the page URL so that shared/bookmarked
+   * filter views come back in the same state. The server renders the matching results.
+   */
+  restoreStateFromUrl() {
+    const params = new URLSearchParams(window.location.search);
+    params.getAll('tags').forEach((tag) => this.committedTags.add(tag));
+    this.sort = params.get('sort');
+  }
+
   /**
    * Set up event delegation for tag column to handle clicks and keyboard interactions
    * This approach is more performant, prevents memory leaks, and supports accessibility
@@ -63,9 +75,10 @@ class TagCompletion {
 
     // Handle click events
     this.tagColumn.addEventListener('click', (e) => {
-      if (e.target.classList.contains('tag-list-item')) {
+      const tagElement = e.target.closest('.tag-list-item');
+      if (tagElement) {
         e.preventDefault();
-        this.toggleTagState(e.target);
+        this.toggleTagState(tagElement);
       }
     });
 
@@ -113,7 +126,7 @@ class TagCompletion {
    * @param {HTMLElement} tagElement - The tag element to toggle
    */
   toggleTagState(tagElement) {
-    const tagName = tagElement.textContent.trim();
+    const tagName = tagElement.dataset.tag ?? tagElement.textContent.trim();
 
     if (tagElement.classList.contains('tag-list-active')) {
       // Active tag clicked - remove it
@@ -573,8 +586,11 @@ class TagCompletion {
     if (searchTerms.length === 0 && tags.length === 0 && this.hasIncompleteTagSyntax(this.searchInput.value.trim())) return;
 
     // Use HTMX to make request with custom parameters
+    const values = { q: searchTerms, tags };
+    if (this.sort) values.sort = this.sort;
+
     htmx.ajax('GET', '/api/bookmarks', {
-      values: { q: searchTerms, tags },
+      values,
       target: '#bookmark-content',
       swap: 'innerHTML',
     });
@@ -590,10 +606,13 @@ class TagCompletion {
     if (typeof htmx === 'undefined') return;
 
     const tags = Array.from(this.committedTags);
+    const tagSearch = document.getElementById('tag-search');
+    const values = { tags };
+    if (tagSearch && tagSearch.value.trim()) values.tag_q = tagSearch.value.trim();
 
     // Use HTMX to refresh the tag column with current active tags
     htmx.ajax('GET', '/api/tags', {
-      values: { tags },
+      values,
       target: '#tag-column',
       swap: 'innerHTML',
     });
@@ -0,0 +1,141 @@
+//! Benchmarks for the hot query paths: the bookmark list, filtering by tag, searching, and the tags
+//! looked up for autocomplete.
+//!
+//! They run against a fixture database at `PAGEPOUCH_BENCH_DB`, by default `target/bench.db`, which
+//! is generated with the `large` seed profile the first time. For other volumes, make a fixture
+//! with e.g. `PAGEPOUCH_SEED=bookmarks=50000 cargo run --release -- --bench-db target/bench-50k.db`
+//! and point `PAGEPOUCH_BENCH_DB` at it.
+//!
+//! Run with `cargo bench`; criterion compares each run with the previous one.
+
+use std::path::PathBuf;
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use pagepouch_rs::{
+    db::{
+        self, Db,
+        bookmarks::{self, SortOrder},
+        seed::{self, SeedProfile},
+        tags,
+    },
+    search::SearchQuery,
+};
+use tokio::runtime::Runtime;
+use uuid::Uuid;
+
+const FIXTURE_NAME: &str = "PAGEPOUCH_BENCH_DB";
+const DEFAULT_FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/target/bench.db");
+/// Bookmarks on a page, as listed by the web UI.
+const PAGE_SIZE: i64 = 20;
+
+/// Searches as typed into the search box, by what they exercise.
+const SEARCHES: [(&str, &str); 5] = [
+    ("one_term", "rust"),
+    ("two_terms_or", "rust database"),
+    ("three_terms_and", "rust AND async AND server"),
+    ("phrase", "\"memory cache\""),
+    ("term_and_tag", "search #rust"),
+];
+
+struct Fixture {
+    db: Db,
+    user_id: Uuid,
+    /// The user's most used tag
+    top_tag: String,
+}
+
+/// Opens the fixture database, generating it first if there isn't one.
+async fn fixture() -> Fixture {
+    let path = PathBuf::from(std::env::var(FIXTURE_NAME).unwrap_or_else(|_| DEFAULT_FIXTURE.to_string()));
+    if !path.exists() {
+        seed::create_fixture(&path, SeedProfile::BENCH)
+            .await
+            .expect("the benchmark database can be created");
+    }
+    let db = db::connect(&format!("sqlite://{}", path.display()), None)
+        .await
+        .expect("the benchmark database opens");
+
+    let user_id: Uuid = sqlx::query_scalar("select user_id from users where username = 'admin'")
+        .fetch_one(&db.reader)
+        .await
+        .expect("the benchmark database has the admin user");
+    let top_tag = tags::get_user_tags(&db, user_id)
+        .await
+        .expect("tags can be read")
+        .into_iter()
+        .max_by_key(|tag| tag.count)
+        .expect("the benchmark database has tags")
+        .name;
+
+    Fixture { db, user_id, top_tag }
+}
+
+fn list(c: &mut Criterion, rt: &Runtime, fixture: &Fixture) {
+    let mut group = c.benchmark_group("list");
+    for (name, sort, offset) in [
+        ("newest", SortOrder::Newest, 0),
+        ("title", SortOrder::Title, 0),
+        ("newest_page_100", SortOrder::Newest, 100 * PAGE_SIZE),
+    ] {
+        group.bench_function(name, |b| {
+            b.to_async(rt)
+                .iter(|| bookmarks::get_user_bookmarks(&fixture.db, fixture.user_id, sort, PAGE_SIZE, offset));
+        });
+    }
+    group.finish();
+}
+
+fn tag_filter(c: &mut Criterion, rt: &Runtime, fixture: &Fixture) {
+    let mut group = c.benchmark_group("tag_filter");
+    let top_tag = SearchQuery::parse(&format!("#{}", fixture.top_tag));
+    group.bench_function("top_tag", |b| {
+        b.to_async(rt)
+            .iter(|| bookmarks::search_user_bookmarks_advanced(&fixture.db, fixture.user_id, &top_tag, SortOrder::Newest, PAGE_SIZE, 0));
+    });
+    let two_tags = SearchQuery::parse(&format!("#{} #rust", fixture.top_tag));
+    group.bench_function("two_tags", |b| {
+        b.to_async(rt)
+            .iter(|| bookmarks::search_user_bookmarks_advanced(&fixture.db, fixture.user_id, &two_tags, SortOrder::Newest, PAGE_SIZE, 0));
+    });
+    group.finish();
+}
+
+fn search(c: &mut Criterion, rt: &Runtime, fixture: &Fixture) {
+    let mut group = c.benchmark_group("search");
+    for (name, input) in SEARCHES {
+        let query = SearchQuery::parse(input);
+        group.bench_with_input(BenchmarkId::new(name, input), &query, |b, query| {
+            b.to_async(rt)
+                .iter(|| bookmarks::search_user_bookmarks_advanced(&fixture.db, fixture.user_id, query, SortOrder::Newest, PAGE_SIZE, 0));
+        });
+    }
+    group.finish();
+}
+
+fn autocomplete(c: &mut Criterion, rt: &Runtime, fixture: &Fixture) {
+    let mut group = c.benchmark_group("autocomplete");
+    group.bench_function("all_tags", |b| {
+        b.to_async(rt)
+            .iter(|| tags::get_tags_for_active_filters(&fixture.db, fixture.user_id, &[]));
+    });
+    let active = [fixture.top_tag.clone()];
+    group.bench_function("with_active_tag", |b| {
+        b.to_async(rt)
+            .iter(|| tags::get_tags_for_active_filters(&fixture.db, fixture.user_id, &active));
+    });
+    group.finish();
+}
+
+fn queries(c: &mut Criterion) {
+    let rt = Runtime::new().expect("the tokio runtime starts");
+    let fixture = rt.block_on(fixture());
+
+    list(c, &rt, &fixture);
+    tag_filter(c, &rt, &fixture);
+    search(c, &rt, &fixture);
+    autocomplete(c, &rt, &fixture);
+}
+
+criterion_group!(benches, queries);
+criterion_main!(benches);
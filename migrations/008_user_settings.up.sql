@@ -0,0 +1,7 @@
+create table if not exists user_settings (
+    user_id         blob primary key not null,
+    disable_fetch   boolean not null default false,         -- Never contact saved URLs to scrape title/description
+    updated_at      integer not null default (unixepoch()), -- UNIX timestamp
+
+    foreign key(user_id) references users(user_id) on delete cascade
+);
@@ -0,0 +1,6 @@
+alter table user_settings drop column show_favicon;
+alter table user_settings drop column show_date;
+alter table user_settings drop column show_domain;
+alter table user_settings drop column show_tags;
+alter table user_settings drop column show_description;
+alter table user_settings drop column list_density;
@@ -0,0 +1,6 @@
+alter table user_settings add column list_density text not null default 'comfortable'; -- 'comfortable' or 'compact'
+alter table user_settings add column show_description boolean not null default false;
+alter table user_settings add column show_tags boolean not null default true;
+alter table user_settings add column show_domain boolean not null default false;
+alter table user_settings add column show_date boolean not null default true;
+alter table user_settings add column show_favicon boolean not null default false;    -- Favicons are loaded from the saved sites
@@ -0,0 +1,2 @@
+drop index if exists idx_smart_collections_user_name;
+drop table if exists smart_collections;
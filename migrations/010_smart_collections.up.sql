@@ -0,0 +1,11 @@
+create table if not exists smart_collections (
+    collection_id   blob primary key not null default (randomblob(16)),
+    user_id         blob not null,
+    name            text not null,
+    query           text not null,                          -- Rule in search syntax, e.g. 'site:github.com #rust'
+    created_at      integer not null default (unixepoch()), -- UNIX timestamp
+
+    foreign key(user_id) references users(user_id) on delete cascade
+);
+
+create unique index idx_smart_collections_user_name on smart_collections(user_id, name);
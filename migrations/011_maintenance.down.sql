@@ -0,0 +1,2 @@
+drop table if exists maintenance_runs;
+alter table users drop column is_admin;
@@ -0,0 +1,15 @@
+alter table users add column is_admin boolean not null default false;   -- Can see the admin dashboard
+
+-- Whoever set up the instance (the first user) becomes its admin
+update users set is_admin = true
+where user_id = (select user_id from users order by created_at, rowid limit 1);
+
+create table if not exists maintenance_runs (
+    run_id              integer primary key autoincrement,
+    started_at          integer not null default (unixepoch()), -- UNIX timestamp
+    duration_ms         integer not null default 0,
+    sessions_deleted    integer not null default 0,
+    tags_deleted        integer not null default 0,
+    pages_freed         integer not null default 0,             -- Free pages returned to the OS by incremental vacuum
+    error               text                                    -- Set if any step failed
+);
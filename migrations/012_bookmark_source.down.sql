@@ -0,0 +1,3 @@
+drop index if exists idx_bookmarks_user_source;
+alter table bookmarks drop column source_detail;
+alter table bookmarks drop column source;
@@ -0,0 +1,4 @@
+alter table bookmarks add column source text not null default 'web'; -- How the bookmark was saved: 'web', 'api', 'import', ...
+alter table bookmarks add column source_detail text;                 -- Which token, file, etc. it came through, if any
+
+create index idx_bookmarks_user_source on bookmarks(user_id, source);
@@ -0,0 +1,8 @@
+drop trigger if exists bump_data_version_on_bookmark_tag_delete;
+drop trigger if exists bump_data_version_on_bookmark_tag_insert;
+drop trigger if exists bump_data_version_on_bookmark_delete;
+drop trigger if exists bump_data_version_on_bookmark_update;
+drop trigger if exists bump_data_version_on_bookmark_insert;
+
+alter table users drop column data_changed_at;
+alter table users drop column data_version;
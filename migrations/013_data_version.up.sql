@@ -0,0 +1,32 @@
+-- Bumped on every change to a user's bookmarks, so polling clients can cheaply tell whether anything changed
+alter table users add column data_version integer not null default 0;
+alter table users add column data_changed_at integer not null default 0;  -- UNIX timestamp of the last change
+
+update users set data_changed_at = unixepoch();
+
+create trigger bump_data_version_on_bookmark_insert after insert on bookmarks
+begin
+    update users set data_version = data_version + 1, data_changed_at = unixepoch() where user_id = new.user_id;
+end;
+
+create trigger bump_data_version_on_bookmark_update after update on bookmarks
+begin
+    update users set data_version = data_version + 1, data_changed_at = unixepoch() where user_id = new.user_id;
+end;
+
+create trigger bump_data_version_on_bookmark_delete after delete on bookmarks
+begin
+    update users set data_version = data_version + 1, data_changed_at = unixepoch() where user_id = old.user_id;
+end;
+
+create trigger bump_data_version_on_bookmark_tag_insert after insert on bookmark_tags
+begin
+    update users set data_version = data_version + 1, data_changed_at = unixepoch()
+    where user_id = (select user_id from bookmarks where bookmark_id = new.bookmark_id);
+end;
+
+create trigger bump_data_version_on_bookmark_tag_delete after delete on bookmark_tags
+begin
+    update users set data_version = data_version + 1, data_changed_at = unixepoch()
+    where user_id = (select user_id from bookmarks where bookmark_id = old.bookmark_id);
+end;
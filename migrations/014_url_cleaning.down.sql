@@ -0,0 +1,3 @@
+drop index if exists idx_url_cleaning_rules_user_rule;
+drop table if exists url_cleaning_rules;
+alter table bookmarks drop column original_url;
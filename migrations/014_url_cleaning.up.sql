@@ -0,0 +1,13 @@
+alter table bookmarks add column original_url text;    -- The URL as saved, if URL cleaning changed it
+
+create table if not exists url_cleaning_rules (
+    rule_id         blob primary key not null default (randomblob(16)),
+    user_id         blob not null,
+    domain          text not null,                          -- Applies to this host and its subdomains
+    pattern         text not null,                          -- Regex; query parameters with matching names are removed
+    created_at      integer not null default (unixepoch()), -- UNIX timestamp
+
+    foreign key(user_id) references users(user_id) on delete cascade
+);
+
+create unique index idx_url_cleaning_rules_user_rule on url_cleaning_rules(user_id, domain, pattern);
@@ -0,0 +1 @@
+drop table if exists admin_search_log;
@@ -0,0 +1,9 @@
+create table if not exists admin_search_log (
+    search_id       integer primary key autoincrement,
+    admin_user_id   blob not null,                          -- Not a foreign key, so the log outlives the account
+    admin_username  text not null,
+    query           text not null,
+    reason          text not null,                          -- Why the admin needed to search other users' bookmarks
+    result_count    integer not null,
+    searched_at     integer not null default (unixepoch())  -- UNIX timestamp
+);
@@ -0,0 +1,8 @@
+create table if not exists instance_settings (
+    id                          integer primary key not null check (id = 1),   -- Only ever one row
+    announcement                text,                                           -- Banner shown on every page, if set
+    announcement_expires_at     integer,                                        -- UNIX timestamp; shown indefinitely if null
+    updated_at                  integer not null default (unixepoch())          -- UNIX timestamp
+);
+
+insert into instance_settings (id) values (1);
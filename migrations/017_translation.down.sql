@@ -0,0 +1,5 @@
+drop trigger if exists bookmark_translations_outdated;
+drop table if exists bookmark_translations;
+drop index if exists idx_bookmarks_user_language;
+alter table user_settings drop column preferred_language;
+alter table bookmarks drop column language;
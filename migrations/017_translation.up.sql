@@ -0,0 +1,22 @@
+alter table bookmarks add column language text;                 -- Content language detected when the page was fetched, e.g. 'de'
+alter table user_settings add column preferred_language text;   -- Bookmarks in other languages can be translated into it
+
+create index idx_bookmarks_user_language on bookmarks(user_id, language);
+
+create table if not exists bookmark_translations (
+    bookmark_id     blob not null,
+    language        text not null,                          -- Language translated into
+    title           text not null,
+    description     text,
+    created_at      integer not null default (unixepoch()), -- UNIX timestamp
+
+    primary key (bookmark_id, language),
+    foreign key(bookmark_id) references bookmarks(bookmark_id) on delete cascade
+);
+
+-- Translations of an old title or description are no use
+create trigger if not exists bookmark_translations_outdated
+after update of title, description on bookmarks
+begin
+    delete from bookmark_translations where bookmark_id = new.bookmark_id;
+end;
@@ -0,0 +1,7 @@
+create table if not exists user_feature_flags (
+    user_id         blob not null references users(user_id) on delete cascade,
+    feature         text not null,                          -- Name of the feature, e.g. 'archiving'
+    enabled         boolean not null,                       -- Overrides the instance default either way
+    updated_at      integer not null default (unixepoch()), -- UNIX timestamp
+    primary key (user_id, feature)
+);
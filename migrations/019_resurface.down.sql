@@ -0,0 +1 @@
+alter table user_settings drop column resurface;
@@ -0,0 +1 @@
+alter table user_settings add column resurface boolean not null default false;    -- Show old bookmarks saved around this time on the home page
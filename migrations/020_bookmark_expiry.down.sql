@@ -0,0 +1,3 @@
+drop index if exists idx_bookmarks_expires_at;
+alter table maintenance_runs drop column bookmarks_expired;
+alter table bookmarks drop column expires_at;
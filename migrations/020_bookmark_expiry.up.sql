@@ -0,0 +1,4 @@
+alter table bookmarks add column expires_at integer;                        -- UNIX timestamp after which a temporary bookmark is deleted
+alter table maintenance_runs add column bookmarks_expired integer not null default 0;
+
+create index idx_bookmarks_expires_at on bookmarks(expires_at) where expires_at is not null;
@@ -0,0 +1,12 @@
+create table if not exists blocked_domains (
+    block_id        blob primary key not null default (randomblob(16)),
+    user_id         blob,                                   -- Null for the instance-wide blocklist, set by the admin
+    domain          text not null,                          -- Applies to this host and its subdomains
+    action          text not null check (action in ('block', 'archive')), -- Refuse to save, or save straight to the archive
+    created_at      integer not null default (unixepoch()), -- UNIX timestamp
+
+    foreign key(user_id) references users(user_id) on delete cascade
+);
+
+-- One entry per domain in each list; the instance list's null user ID counts as a value here
+create unique index idx_blocked_domains_list_domain on blocked_domains(ifnull(user_id, x''), domain);
@@ -0,0 +1,4 @@
+drop index if exists idx_bookmarks_user_author;
+alter table bookmarks drop column published_at;
+alter table bookmarks drop column site_name;
+alter table bookmarks drop column author;
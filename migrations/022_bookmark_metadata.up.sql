@@ -0,0 +1,5 @@
+alter table bookmarks add column author text;           -- Who wrote the page, from its metadata when it was fetched
+alter table bookmarks add column site_name text;        -- Name of the publication or site, e.g. 'The Guardian'
+alter table bookmarks add column published_at integer; -- UNIX timestamp the page says it was published
+
+create index idx_bookmarks_user_author on bookmarks(user_id, author) where author is not null;
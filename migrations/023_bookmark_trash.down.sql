@@ -0,0 +1,3 @@
+drop index if exists idx_bookmarks_deleted_at;
+alter table maintenance_runs drop column bookmarks_purged;
+alter table bookmarks drop column deleted_at;
@@ -0,0 +1,4 @@
+alter table bookmarks add column deleted_at integer;    -- UNIX timestamp it was moved to the trash, purged for good 30 days later
+alter table maintenance_runs add column bookmarks_purged integer not null default 0;
+
+create index idx_bookmarks_deleted_at on bookmarks(deleted_at) where deleted_at is not null;
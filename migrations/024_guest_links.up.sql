@@ -0,0 +1,12 @@
+create table if not exists guest_links (
+    link_id         blob primary key not null default (randomblob(16)),
+    user_id         blob not null,
+    collection_id   blob,                                   -- Null to share all of the user's bookmarks
+    expires_at      integer not null,                       -- UNIX timestamp
+    created_at      integer not null default (unixepoch()), -- UNIX timestamp
+
+    foreign key(user_id) references users(user_id) on delete cascade,
+    foreign key(collection_id) references smart_collections(collection_id) on delete cascade
+);
+
+create index idx_guest_links_user on guest_links(user_id, expires_at);
@@ -0,0 +1,14 @@
+-- API tokens are hashed with HMAC-SHA256 under the instance's root key rather than plain SHA-256, so a copy of the
+-- database alone isn't enough to check guessed tokens against
+create table if not exists user_api_tokens (
+    token_id        blob primary key not null default (randomblob(16)),
+    user_id         blob not null,
+    name            text not null,                          -- What it's for, e.g. 'phone' or 'backup script'
+    token_hash      text unique not null,                   -- HMAC-SHA256 of the token under the root key, hex; the token itself is only shown once
+    created_at      integer not null default (unixepoch()), -- UNIX timestamp
+    last_used_at    integer,                                -- UNIX timestamp, to the minute; null if never used
+
+    foreign key(user_id) references users(user_id) on delete cascade
+);
+
+create index idx_user_api_tokens_user on user_api_tokens(user_id);
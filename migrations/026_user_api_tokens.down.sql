@@ -0,0 +1 @@
+-- Nothing to do: 025_api_tokens creates user_api_tokens with keyed hashes. Kept so later migrations keep their numbers.
@@ -0,0 +1,11 @@
+-- Requests made with each API token, counted per day and endpoint, so users can notice a leaked token or a
+-- runaway script
+create table if not exists api_token_usage (
+    token_id        blob not null,
+    day             text not null,                          -- UTC date, YYYY-MM-DD
+    endpoint        text not null,                          -- Method and route, e.g. 'GET /api/v1/bookmarks/{bookmark_id}'
+    requests        integer not null default 0,
+
+    primary key(token_id, day, endpoint),
+    foreign key(token_id) references user_api_tokens(token_id) on delete cascade
+);
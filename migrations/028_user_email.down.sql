@@ -0,0 +1,2 @@
+drop index if exists idx_users_email;
+alter table users drop column email;
@@ -0,0 +1,5 @@
+-- An optional email address, which users can log in with instead of their username
+alter table users add column email text collate nocase;
+
+-- Unique when set; SQLite allows any number of nulls
+create unique index idx_users_email on users(email);
@@ -0,0 +1,2 @@
+drop index if exists idx_bookmarks_user_created_id;
+alter table user_settings drop column infinite_scroll;
@@ -0,0 +1,4 @@
+alter table user_settings add column infinite_scroll boolean not null default false;    -- Load more bookmarks when scrolling to the end of the list, instead of pages
+
+-- Scrolling through the list picks up after the last bookmark shown, in saved order with the ID breaking ties
+create index idx_bookmarks_user_created_id on bookmarks(user_id, created_at, bookmark_id);
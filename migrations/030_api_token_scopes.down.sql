@@ -0,0 +1,2 @@
+alter table user_api_tokens drop column tags;
+alter table user_api_tokens drop column scope;
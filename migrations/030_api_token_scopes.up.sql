@@ -0,0 +1,5 @@
+-- What each API token may do: 'read' (GET requests), 'create' (POST requests) or 'full'
+alter table user_api_tokens add column scope text not null default 'full';
+-- Tags the token is limited to, comma-separated: it only sees and changes bookmarks with one of them. Null for
+-- all bookmarks
+alter table user_api_tokens add column tags text;
@@ -0,0 +1,10 @@
+-- Tags are shared by everyone's bookmarks, so each user picks their own colors for them
+create table if not exists tag_colors (
+    user_id         blob not null,
+    tag_id          blob not null,
+    color           text not null check (color glob '#[0-9a-f][0-9a-f][0-9a-f][0-9a-f][0-9a-f][0-9a-f]'), -- e.g. #3b82f6
+
+    primary key (user_id, tag_id),
+    foreign key(user_id) references users(user_id) on delete cascade,
+    foreign key(tag_id) references tags(tag_id) on delete cascade
+);
@@ -0,0 +1,4 @@
+alter table user_settings drop column default_sort;
+alter table user_settings drop column fuzzy_search;
+alter table user_settings drop column search_include_archived;
+alter table user_settings drop column search_logic;
@@ -0,0 +1,4 @@
+alter table user_settings add column search_logic text not null default 'or' check (search_logic in ('or', 'and'));   -- Between terms of queries without AND or OR
+alter table user_settings add column search_include_archived boolean not null default false;                         -- Search the archive along with the other bookmarks
+alter table user_settings add column fuzzy_search boolean not null default false;                                    -- Match words with their letters in order
+alter table user_settings add column default_sort text not null default 'newest' check (default_sort in ('newest', 'oldest', 'title'));
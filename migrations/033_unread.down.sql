@@ -0,0 +1,2 @@
+drop index if exists idx_bookmarks_unread;
+alter table bookmarks drop column is_unread;
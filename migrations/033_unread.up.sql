@@ -0,0 +1,4 @@
+-- Bookmarks saved to read later, until they're marked as read
+alter table bookmarks add column is_unread boolean not null default false;
+
+create index idx_bookmarks_unread on bookmarks(user_id, created_at) where is_unread = true;
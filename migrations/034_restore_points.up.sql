@@ -0,0 +1,13 @@
+-- Snapshots of a user's bookmarks, tags and smart collections to roll back to, e.g. after an import gone wrong
+create table if not exists restore_points (
+    restore_point_id    blob primary key not null default (randomblob(16)),
+    user_id             blob not null,
+    label               text not null,                          -- What it was made for, e.g. 'Before importing Bookmarks'
+    bookmark_count      integer not null,                       -- Outside the trash, for the list on the settings page
+    snapshot            blob not null,                          -- LZ4-compressed JSON, see src/restore.rs
+    created_at          integer not null default (unixepoch()), -- UNIX timestamp
+
+    foreign key(user_id) references users(user_id) on delete cascade
+);
+
+create index idx_restore_points_user on restore_points(user_id, created_at desc);
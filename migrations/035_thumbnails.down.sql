@@ -0,0 +1,2 @@
+alter table bookmarks drop column has_thumbnail;
+drop table if exists bookmark_thumbnails;
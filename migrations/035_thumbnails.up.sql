@@ -0,0 +1,12 @@
+-- Small copies of the preview image pages name in og:image, for the grid view of the bookmark list
+create table if not exists bookmark_thumbnails (
+    bookmark_id     blob primary key not null,
+    image           blob not null,                          -- JPEG, at most 400x300, see src/thumbnail.rs
+    source_url      text not null,                          -- Where it was downloaded from
+    created_at      integer not null default (unixepoch()), -- UNIX timestamp
+
+    foreign key(bookmark_id) references bookmarks(bookmark_id) on delete cascade
+);
+
+-- Kept on the bookmark so the list doesn't have to look in the thumbnails table
+alter table bookmarks add column has_thumbnail boolean not null default false;
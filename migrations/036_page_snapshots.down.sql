@@ -0,0 +1,2 @@
+alter table bookmarks drop column has_snapshot;
+drop table if exists bookmark_snapshots;
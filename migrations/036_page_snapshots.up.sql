@@ -0,0 +1,13 @@
+-- Self-contained copies of saved pages, kept against link rot; see src/snapshot.rs
+create table if not exists bookmark_snapshots (
+    bookmark_id     blob primary key not null,
+    content         blob not null,                          -- LZ4-compressed HTML with everything inlined
+    size            integer not null,                       -- Bytes of HTML, uncompressed
+    source_url      text not null,                          -- Where the page was fetched from, after redirects
+    created_at      integer not null default (unixepoch()), -- UNIX timestamp
+
+    foreign key(bookmark_id) references bookmarks(bookmark_id) on delete cascade
+);
+
+-- Kept on the bookmark so the list doesn't have to look in the snapshots table
+alter table bookmarks add column has_snapshot boolean not null default false;
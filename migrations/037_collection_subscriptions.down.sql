@@ -0,0 +1 @@
+drop table if exists collection_subscriptions;
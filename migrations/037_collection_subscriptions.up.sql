@@ -0,0 +1,13 @@
+-- Stable links serving the latest export of a smart collection, for other tools to pull
+create table if not exists collection_subscriptions (
+    subscription_id blob primary key not null default (randomblob(16)),
+    user_id         blob not null,
+    collection_id   blob not null unique,                   -- One link per collection
+    created_at      integer not null default (unixepoch()), -- UNIX timestamp
+    last_pulled_at  integer,                                -- UNIX timestamp, null until first used
+
+    foreign key(user_id) references users(user_id) on delete cascade,
+    foreign key(collection_id) references smart_collections(collection_id) on delete cascade
+);
+
+create index idx_collection_subscriptions_user on collection_subscriptions(user_id);
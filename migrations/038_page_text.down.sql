@@ -0,0 +1,6 @@
+alter table user_settings drop column search_page_text;
+drop trigger if exists bookmark_page_text_delete;
+drop trigger if exists bookmark_page_text_update;
+drop trigger if exists bookmark_page_text_insert;
+drop table if exists bookmark_page_text_index;
+drop table if exists bookmark_page_text;
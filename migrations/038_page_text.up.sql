@@ -0,0 +1,35 @@
+-- Readable text of saved pages, for searching their content; see src/page_text.rs
+create table if not exists bookmark_page_text (
+    bookmark_id     blob primary key not null,
+    content         text not null,                          -- Main text of the page, boilerplate left out
+    extracted_at    integer not null default (unixepoch()), -- UNIX timestamp
+
+    foreign key(bookmark_id) references bookmarks(bookmark_id) on delete cascade
+);
+
+-- Trigram index of the text, so `like` searches of it don't have to read every page
+create virtual table if not exists bookmark_page_text_index using fts5(
+    content,
+    content = 'bookmark_page_text',
+    content_rowid = 'rowid',
+    tokenize = 'trigram'
+);
+
+create trigger if not exists bookmark_page_text_insert after insert on bookmark_page_text
+begin
+    insert into bookmark_page_text_index (rowid, content) values (new.rowid, new.content);
+end;
+
+create trigger if not exists bookmark_page_text_update after update of content on bookmark_page_text
+begin
+    insert into bookmark_page_text_index (bookmark_page_text_index, rowid, content) values ('delete', old.rowid, old.content);
+    insert into bookmark_page_text_index (rowid, content) values (new.rowid, new.content);
+end;
+
+-- Also when a bookmark is deleted, through the foreign key
+create trigger if not exists bookmark_page_text_delete after delete on bookmark_page_text
+begin
+    insert into bookmark_page_text_index (bookmark_page_text_index, rowid, content) values ('delete', old.rowid, old.content);
+end;
+
+alter table user_settings add column search_page_text boolean not null default false;   -- Words also match in the text of saved pages
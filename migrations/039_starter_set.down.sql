@@ -0,0 +1,2 @@
+alter table users drop column onboarded_at;
+alter table instance_settings drop column starter_set;
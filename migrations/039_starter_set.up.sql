@@ -0,0 +1,5 @@
+alter table instance_settings add column starter_set boolean not null default false;   -- Whether new users get the starter set
+alter table users add column onboarded_at integer;                                      -- UNIX timestamp of the first sign-in; null until then
+
+-- Everyone who already has an account is past onboarding
+update users set onboarded_at = unixepoch();
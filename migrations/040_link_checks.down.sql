@@ -0,0 +1,3 @@
+drop trigger if exists bookmark_link_checks_url;
+drop index if exists idx_link_checks_checked;
+drop table if exists bookmark_link_checks;
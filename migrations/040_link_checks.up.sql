@@ -0,0 +1,19 @@
+-- Results of the scheduled dead-link checker; see src/link_check.rs
+create table if not exists bookmark_link_checks (
+    bookmark_id     blob primary key not null,
+    status          integer,                                -- HTTP status of the last check, null if there was no response
+    error           text,                                   -- Why there was no response, if there wasn't
+    is_broken       boolean not null,                       -- Whether the last check found the link broken
+    checked_at      integer not null default (unixepoch()), -- UNIX timestamp
+
+    foreign key(bookmark_id) references bookmarks(bookmark_id) on delete cascade
+);
+
+create index idx_link_checks_checked on bookmark_link_checks(checked_at);
+
+-- A re-pointed bookmark hasn't been checked yet
+create trigger if not exists bookmark_link_checks_url after update of url on bookmarks
+when old.url != new.url
+begin
+    delete from bookmark_link_checks where bookmark_id = new.bookmark_id;
+end;
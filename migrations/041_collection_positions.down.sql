@@ -0,0 +1,2 @@
+drop index if exists idx_collection_positions_bookmark;
+drop table if exists collection_positions;
@@ -0,0 +1,13 @@
+-- Where bookmarks have been placed in a smart collection's own order; bookmarks without a
+-- position come after the placed ones, newest first
+create table if not exists collection_positions (
+    collection_id   blob not null,
+    bookmark_id     blob not null,
+    position        integer not null,                       -- Place in the collection, from 0
+
+    primary key(collection_id, bookmark_id),
+    foreign key(collection_id) references smart_collections(collection_id) on delete cascade,
+    foreign key(bookmark_id) references bookmarks(bookmark_id) on delete cascade
+);
+
+create index idx_collection_positions_bookmark on collection_positions(bookmark_id);
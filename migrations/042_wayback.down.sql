@@ -0,0 +1 @@
+alter table bookmark_link_checks drop column archived_url;
@@ -0,0 +1,2 @@
+-- Copies of broken links in the Wayback Machine; see src/wayback.rs
+alter table bookmark_link_checks add column archived_url text; -- Closest copy found when the link was broken, if any
@@ -0,0 +1,12 @@
+-- Links to reset a forgotten password, sent by email; see src/mail.rs
+create table if not exists password_resets (
+    reset_id        blob primary key not null default (randomblob(16)),
+    user_id         blob not null,
+    expires_at      integer not null,                       -- UNIX timestamp
+    used_at         integer,                                -- UNIX timestamp the password was reset at, null until then
+    created_at      integer not null default (unixepoch()), -- UNIX timestamp
+
+    foreign key(user_id) references users(user_id) on delete cascade
+);
+
+create index idx_password_resets_user on password_resets(user_id);
@@ -0,0 +1,3 @@
+drop trigger if exists bookmark_fetch_failures_url;
+drop index if exists idx_fetch_failures_retry;
+drop table if exists bookmark_fetch_failures;
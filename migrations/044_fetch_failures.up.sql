@@ -0,0 +1,19 @@
+-- Bookmarks whose page couldn't be fetched when they were saved, to be tried again; see src/fetch_retry.rs
+create table if not exists bookmark_fetch_failures (
+    bookmark_id     blob primary key not null,
+    error           text not null,                          -- Why the last try failed, e.g. "Timed out"
+    attempts        integer not null,                       -- Tries so far, counting the one at save time
+    retry_at        integer,                                -- UNIX timestamp of the next try, null once given up
+    failed_at       integer not null default (unixepoch()), -- UNIX timestamp of the last try
+
+    foreign key(bookmark_id) references bookmarks(bookmark_id) on delete cascade
+);
+
+create index idx_fetch_failures_retry on bookmark_fetch_failures(retry_at);
+
+-- A re-pointed bookmark's page is another one
+create trigger if not exists bookmark_fetch_failures_url after update of url on bookmarks
+when old.url != new.url
+begin
+    delete from bookmark_fetch_failures where bookmark_id = new.bookmark_id;
+end;
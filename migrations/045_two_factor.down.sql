@@ -0,0 +1,3 @@
+drop index if exists idx_totp_recovery_codes_user;
+drop table if exists totp_recovery_codes;
+drop table if exists user_totp;
@@ -0,0 +1,22 @@
+-- TOTP two-factor authentication, a second step when signing in; see src/totp.rs
+create table if not exists user_totp (
+    user_id         blob primary key not null,
+    secret          blob not null,                          -- Shared with the user's authenticator app
+    enabled_at      integer,                                -- UNIX timestamp the first code was confirmed at, null while setting up
+    last_used_step  integer,                                -- Time step of the last code used, so none is used twice
+    created_at      integer not null default (unixepoch()), -- UNIX timestamp
+
+    foreign key(user_id) references users(user_id) on delete cascade
+);
+
+-- Codes that sign in once each when the authenticator app is lost
+create table if not exists totp_recovery_codes (
+    code_hash       text primary key not null,              -- Keyed hash, like API tokens
+    user_id         blob not null,
+    used_at         integer,                                -- UNIX timestamp, null until used
+    created_at      integer not null default (unixepoch()), -- UNIX timestamp
+
+    foreign key(user_id) references users(user_id) on delete cascade
+);
+
+create index idx_totp_recovery_codes_user on totp_recovery_codes(user_id);
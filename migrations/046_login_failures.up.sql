@@ -0,0 +1,11 @@
+-- Failed sign-ins per login name and per IP address, for locking out password guessing; see src/lockout.rs
+create table if not exists login_failures (
+    failure_id      integer primary key autoincrement,
+    kind            text not null,                          -- 'login' or 'ip'
+    key             text not null,                          -- Username or email as typed in, lower case, or IP address
+    failures        integer not null,                       -- In a row, each within a day of the one before
+    last_failed_at  integer not null,                       -- UNIX timestamp
+    locked_until    integer,                                -- UNIX timestamp, null if not locked out
+
+    unique (kind, key)
+);
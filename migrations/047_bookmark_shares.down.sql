@@ -0,0 +1,2 @@
+drop index if exists idx_bookmark_shares_user;
+drop table if exists bookmark_shares;
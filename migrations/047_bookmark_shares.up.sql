@@ -0,0 +1,13 @@
+-- Public links to a single bookmark, viewable without an account until revoked
+create table if not exists bookmark_shares (
+    share_id        blob primary key not null default (randomblob(16)),
+    user_id         blob not null,
+    bookmark_id     blob not null unique,                   -- One link per bookmark
+    created_at      integer not null default (unixepoch()), -- UNIX timestamp
+    last_viewed_at  integer,                                -- UNIX timestamp, null until first opened
+
+    foreign key(user_id) references users(user_id) on delete cascade,
+    foreign key(bookmark_id) references bookmarks(bookmark_id) on delete cascade
+);
+
+create index idx_bookmark_shares_user on bookmark_shares(user_id);
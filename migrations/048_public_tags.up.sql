@@ -0,0 +1,10 @@
+-- Tags a user has made public: anyone can see that user's bookmarks with the tag, without an account
+create table if not exists public_tags (
+    user_id         blob not null,
+    tag_id          blob not null,
+    created_at      integer not null default (unixepoch()), -- UNIX timestamp
+
+    primary key (user_id, tag_id),
+    foreign key(user_id) references users(user_id) on delete cascade,
+    foreign key(tag_id) references tags(tag_id) on delete cascade
+);
@@ -0,0 +1,2 @@
+-- Private bookmarks are left out of guest links, public tags, subscriptions and share links
+alter table bookmarks add column visibility text not null default 'public' check (visibility in ('public', 'private'));
@@ -0,0 +1,2 @@
+alter table user_settings drop column digest_sent_at;
+alter table user_settings drop column digest;
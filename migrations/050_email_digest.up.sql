@@ -0,0 +1,2 @@
+alter table user_settings add column digest text not null default 'off' check (digest in ('off', 'daily', 'weekly')); -- Email a summary of new bookmarks
+alter table user_settings add column digest_sent_at integer; -- UNIX timestamp of the last digest, null if none was sent yet
@@ -0,0 +1,2 @@
+delete from public_tags where unpublished_at is not null;
+alter table public_tags drop column unpublished_at;
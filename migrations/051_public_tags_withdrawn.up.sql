@@ -0,0 +1 @@
+alter table public_tags add column unpublished_at integer; -- UNIX timestamp the tag was made private again, null while public
@@ -0,0 +1,111 @@
+//! Counts of the requests made with each API token, per day and endpoint.
+//!
+//! Shown on the settings page, so users can notice a leaked token or a runaway script. Requests are
+//! counted in memory and written to the database once a minute (see
+//! [`crate::jobs::spawn_api_usage_flush`]), so busy clients don't queue up a write per request.
+
+use std::{collections::HashMap, sync::Mutex};
+
+use chrono::{NaiveDate, TimeDelta};
+use uuid::Uuid;
+
+use crate::db::api_tokens::TokenUsage;
+
+/// Days of usage shown on the settings page and kept in the database.
+pub const USAGE_DAYS: i64 = 30;
+/// The endpoint counted for requests that don't match a route.
+pub const UNKNOWN_ENDPOINT: &str = "(no such endpoint)";
+/// How days are stored in the database.
+const DAY_FORMAT: &str = "%Y-%m-%d";
+
+/// Requests counted since the last flush.
+#[derive(Default)]
+pub struct ApiUsage {
+    counts: Mutex<HashMap<(Uuid, NaiveDate, String), i64>>,
+}
+
+impl ApiUsage {
+    /// Counts a request made with a token to an endpoint, like `GET /api/v1/tags`, on `day`.
+    pub fn record(&self, token_id: Uuid, day: NaiveDate, endpoint: &str) {
+        let mut counts = self.counts.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+        *counts.entry((token_id, day, endpoint.to_string())).or_default() += 1;
+    }
+
+    /// Takes the requests counted since the last call.
+    pub fn take(&self) -> Vec<TokenUsage> {
+        let counts = std::mem::take(&mut *self.counts.lock().unwrap_or_else(std::sync::PoisonError::into_inner));
+        counts
+            .into_iter()
+            .map(|((token_id, day, endpoint), requests)| TokenUsage {
+                token_id,
+                day: day_string(day),
+                endpoint,
+                requests,
+            })
+            .collect()
+    }
+
+    /// Puts back requests that couldn't be written, so they're written with the next ones.
+    pub fn restore(&self, usage: Vec<TokenUsage>) {
+        let mut counts = self.counts.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+        for TokenUsage {
+            token_id,
+            day,
+            endpoint,
+            requests,
+        } in usage
+        {
+            if let Ok(day) = NaiveDate::parse_from_str(&day, DAY_FORMAT) {
+                *counts.entry((token_id, day, endpoint)).or_default() += requests;
+            }
+        }
+    }
+}
+
+/// A day as stored in the database, YYYY-MM-DD.
+#[must_use]
+pub fn day_string(day: NaiveDate) -> String {
+    day.format(DAY_FORMAT).to_string()
+}
+
+/// The first day of usage that's shown and kept, when today is `today`.
+#[must_use]
+pub fn first_usage_day(today: NaiveDate) -> String {
+    day_string(today - TimeDelta::days(USAGE_DAYS - 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usage_counts() {
+        let usage = ApiUsage::default();
+        let token_id = Uuid::new_v4();
+        let day = NaiveDate::from_ymd_opt(2025, 3, 1).unwrap();
+        usage.record(token_id, day, "GET /api/v1/tags");
+        usage.record(token_id, day, "GET /api/v1/tags");
+        usage.record(token_id, day.succ_opt().unwrap(), "GET /api/v1/tags");
+
+        let mut taken = usage.take();
+        taken.sort_by(|a, b| a.day.cmp(&b.day));
+        assert_eq!(
+            taken.iter().map(|u| (u.day.as_str(), u.requests)).collect::<Vec<_>>(),
+            [("2025-03-01", 2), ("2025-03-02", 1)]
+        );
+        assert!(usage.take().is_empty());
+
+        // Restored counts are added to the ones counted since
+        usage.record(token_id, day, "GET /api/v1/tags");
+        usage.restore(taken);
+        let restored = usage.take();
+        assert_eq!(restored.len(), 2);
+        assert!(restored.iter().any(|u| u.day == "2025-03-01" && u.requests == 3));
+    }
+
+    #[test]
+    fn test_first_usage_day() {
+        let today = NaiveDate::from_ymd_opt(2025, 3, 1).unwrap();
+        assert_eq!(first_usage_day(today), "2025-01-31");
+    }
+}
@@ -0,0 +1,155 @@
+//! Domain blocklists.
+//!
+//! Keeps bookmarks from unwanted sites out, e.g. on shared instances with a content policy. The
+//! admin keeps a blocklist for the whole instance and every user can keep their own. Each entry
+//! either refuses bookmarks from a domain (and its subdomains) or saves them straight to the
+//! archive. The add form, the API and imports all check new bookmarks against both lists.
+
+use std::str::FromStr;
+
+use anyhow::{Result, anyhow};
+use reqwest::Url;
+use uuid::Uuid;
+
+use crate::{
+    clean::is_on_domain,
+    db::{self, Db, blocked_domains::BlockedDomain, bookmarks::NewBookmark},
+};
+
+/// What happens to a bookmark from a blocked domain.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BlockAction {
+    /// Not saved at all
+    Block,
+    /// Saved, but straight to the archive
+    Archive,
+}
+
+impl BlockAction {
+    /// The value stored in the database and sent by the settings forms.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BlockAction::Block => "block",
+            BlockAction::Archive => "archive",
+        }
+    }
+}
+
+impl FromStr for BlockAction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "block" => Ok(BlockAction::Block),
+            "archive" => Ok(BlockAction::Archive),
+            _ => Err(anyhow!("Unknown block action '{s}'")),
+        }
+    }
+}
+
+/// The instance-wide blocklist plus one user's own.
+#[derive(Clone, Debug, Default)]
+pub struct Blocklist {
+    entries: Vec<(BlockAction, BlockedDomain)>,
+}
+
+impl Blocklist {
+    pub fn new(entries: Vec<BlockedDomain>) -> Self {
+        let entries = entries
+            .into_iter()
+            .filter_map(|entry| Some((entry.action.parse().ok()?, entry)))
+            .collect();
+        Self { entries }
+    }
+
+    /// Loads the blocklists a user's new bookmarks are checked against.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the blocklists cannot be loaded.
+    pub async fn for_user(db: &Db, user_id: Uuid) -> Result<Self> {
+        Ok(Self::new(db::blocked_domains::get_blocklist(db, Some(user_id)).await?))
+    }
+
+    /// The entry that applies to `url`, if any. Refusing wins over archiving, and the instance-wide
+    /// list over the user's own.
+    fn find(&self, url: &str) -> Option<(BlockAction, &BlockedDomain)> {
+        let host = Url::parse(url).ok()?.host_str()?.to_lowercase();
+        self.entries
+            .iter()
+            .filter(|(_, entry)| is_on_domain(&host, &entry.domain))
+            .min_by_key(|(action, entry)| (*action, !entry.is_instance))
+            .map(|(action, entry)| (*action, entry))
+    }
+
+    /// Checks a new bookmark, archiving it if its domain is to be archived.
+    ///
+    /// # Errors
+    ///
+    /// Returns a message for the user if its domain is blocked.
+    pub fn apply(&self, mut bookmark: NewBookmark) -> Result<NewBookmark, String> {
+        match self.find(&bookmark.url) {
+            Some((BlockAction::Block, entry)) if entry.is_instance => {
+                Err(format!("Bookmarks from {} can't be saved on this server.", entry.domain))
+            }
+            Some((BlockAction::Block, entry)) => Err(format!("{} is on your blocklist.", entry.domain)),
+            Some((BlockAction::Archive, _)) => {
+                bookmark.is_archived = true;
+                Ok(bookmark)
+            }
+            None => Ok(bookmark),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(domain: &str, action: BlockAction, is_instance: bool) -> BlockedDomain {
+        BlockedDomain {
+            block_id: Uuid::new_v4(),
+            domain: domain.to_string(),
+            action: action.as_str().to_string(),
+            is_instance,
+        }
+    }
+
+    fn bookmark(url: &str) -> NewBookmark {
+        NewBookmark {
+            url: url.to_string(),
+            ..NewBookmark::default()
+        }
+    }
+
+    #[test]
+    fn test_apply() {
+        let blocklist = Blocklist::new(vec![
+            entry("spam.example", BlockAction::Block, true),
+            entry("news.example", BlockAction::Archive, false),
+            entry("ads.example", BlockAction::Block, false),
+        ]);
+
+        let err = blocklist.apply(bookmark("https://www.spam.example/offer")).unwrap_err();
+        assert_eq!(err, "Bookmarks from spam.example can't be saved on this server.");
+        let err = blocklist.apply(bookmark("https://ADS.example/")).unwrap_err();
+        assert_eq!(err, "ads.example is on your blocklist.");
+
+        assert!(blocklist.apply(bookmark("https://daily.news.example/a")).unwrap().is_archived);
+        let kept = blocklist.apply(bookmark("https://notspam.example/")).unwrap();
+        assert!(!kept.is_archived);
+        assert!(blocklist.apply(bookmark("not a url")).is_ok());
+    }
+
+    #[test]
+    fn test_blocking_wins_over_archiving() {
+        let blocklist = Blocklist::new(vec![
+            entry("example.com", BlockAction::Archive, true),
+            entry("example.com", BlockAction::Block, false),
+        ]);
+        assert_eq!(
+            blocklist.apply(bookmark("https://example.com/")).unwrap_err(),
+            "example.com is on your blocklist."
+        );
+    }
+}
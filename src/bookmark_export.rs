@@ -0,0 +1,221 @@
+//! Bookmark export as JSON, Netscape bookmark HTML or CSV.
+//!
+//! The HTML is the `bookmarks.html` format browsers and most bookmark services import. Exports are
+//! written a bookmark at a time, between a header and a footer, so they can be streamed to the
+//! user without holding every bookmark in memory.
+
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::db::bookmarks::ExportedBookmark;
+
+/// Version written to JSON exports.
+pub const BOOKMARK_EXPORT_VERSION: u32 = 1;
+
+/// File format of a bookmark export.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Json,
+    Html,
+    Csv,
+}
+
+/// A bookmark as it appears in a JSON export.
+#[derive(Serialize)]
+struct JsonBookmark<'a> {
+    url: &'a str,
+    title: &'a str,
+    description: Option<&'a str>,
+    tags: &'a [String],
+    /// UNIX timestamp
+    created_at: i64,
+    /// UNIX timestamp
+    updated_at: i64,
+    language: Option<&'a str>,
+    archived: bool,
+}
+
+impl ExportFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Json => "application/json",
+            ExportFormat::Html => "text/html; charset=utf-8",
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+        }
+    }
+
+    pub fn file_name(self) -> &'static str {
+        match self {
+            ExportFormat::Json => "pagepouch-bookmarks.json",
+            ExportFormat::Html => "pagepouch-bookmarks.html",
+            ExportFormat::Csv => "pagepouch-bookmarks.csv",
+        }
+    }
+
+    /// Everything before the first bookmark. `exported_at` is a UNIX timestamp.
+    pub fn header(self, exported_at: i64) -> String {
+        match self {
+            ExportFormat::Json => {
+                format!("{{\"version\":{BOOKMARK_EXPORT_VERSION},\"exported_at\":{exported_at},\"bookmarks\":[")
+            }
+            ExportFormat::Html => "<!DOCTYPE NETSCAPE-Bookmark-file-1>\n\
+                 <!-- This is an automatically generated file.\n     It will be read and overwritten.\n     DO NOT EDIT! -->\n\
+                 <META HTTP-EQUIV=\"Content-Type\" CONTENT=\"text/html; charset=UTF-8\">\n\
+                 <TITLE>Bookmarks</TITLE>\n\
+                 <H1>Bookmarks</H1>\n\
+                 <DL><p>\n"
+                .to_string(),
+            ExportFormat::Csv => "url,title,description,tags,created_at,updated_at,language,archived\r\n".to_string(),
+        }
+    }
+
+    /// One bookmark; `first` is whether it's the first one written.
+    pub fn entry(self, bookmark: &ExportedBookmark, first: bool) -> String {
+        match self {
+            ExportFormat::Json => {
+                let json = serde_json::to_string(&JsonBookmark {
+                    url: &bookmark.url,
+                    title: &bookmark.title,
+                    description: bookmark.description.as_deref(),
+                    tags: &bookmark.tags,
+                    created_at: bookmark.created_at,
+                    updated_at: bookmark.updated_at,
+                    language: bookmark.language.as_deref(),
+                    archived: bookmark.is_archived,
+                })
+                .expect("bookmarks serialize to JSON");
+                format!("{}\n{json}", if first { "" } else { "," })
+            }
+            ExportFormat::Html => {
+                let description = bookmark
+                    .description
+                    .as_deref()
+                    .map(|description| format!("    <DD>{}\n", escape_html(description)))
+                    .unwrap_or_default();
+                format!(
+                    "    <DT><A HREF=\"{}\" ADD_DATE=\"{}\" LAST_MODIFIED=\"{}\" TAGS=\"{}\"{}>{}</A>\n{description}",
+                    escape_html(&bookmark.url),
+                    bookmark.created_at,
+                    bookmark.updated_at,
+                    escape_html(&bookmark.tags.join(",")),
+                    if bookmark.is_archived { " ARCHIVED=\"1\"" } else { "" },
+                    escape_html(&bookmark.title)
+                )
+            }
+            ExportFormat::Csv => {
+                let fields = [
+                    bookmark.url.as_str(),
+                    &bookmark.title,
+                    bookmark.description.as_deref().unwrap_or_default(),
+                    &bookmark.tags.join(","),
+                    &format_date(bookmark.created_at),
+                    &format_date(bookmark.updated_at),
+                    bookmark.language.as_deref().unwrap_or_default(),
+                    if bookmark.is_archived { "true" } else { "false" },
+                ];
+                let fields: Vec<String> = fields.into_iter().map(csv_field).collect();
+                format!("{}\r\n", fields.join(","))
+            }
+        }
+    }
+
+    /// Everything after the last bookmark.
+    pub fn footer(self) -> String {
+        match self {
+            ExportFormat::Json => "\n]}\n".to_string(),
+            ExportFormat::Html => "</DL><p>\n".to_string(),
+            ExportFormat::Csv => String::new(),
+        }
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Quotes a CSV field if it needs it, doubling any quotes inside.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn format_date(timestamp: i64) -> String {
+    DateTime::from_timestamp(timestamp, 0).map_or_else(String::new, |dt| dt.format("%Y-%m-%dT%H:%M:%SZ").to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bookmark() -> ExportedBookmark {
+        ExportedBookmark {
+            url: "https://example.com/?a=1&b=2".to_string(),
+            title: "Say \"hi\", <now>".to_string(),
+            description: Some("Two\nlines".to_string()),
+            tags: vec!["reading".to_string(), "rust".to_string()],
+            created_at: 1_700_000_000,
+            updated_at: 1_700_000_060,
+            language: Some("en".to_string()),
+            is_archived: true,
+        }
+    }
+
+    fn export(format: ExportFormat, bookmarks: &[ExportedBookmark]) -> String {
+        let mut export = format.header(1_800_000_000);
+        for (index, bookmark) in bookmarks.iter().enumerate() {
+            export.push_str(&format.entry(bookmark, index == 0));
+        }
+        export.push_str(&format.footer());
+        export
+    }
+
+    #[test]
+    fn test_json_export_is_valid() {
+        let plain = ExportedBookmark {
+            description: None,
+            language: None,
+            is_archived: false,
+            ..bookmark()
+        };
+        let json: serde_json::Value = serde_json::from_str(&export(ExportFormat::Json, &[bookmark(), plain])).unwrap();
+        assert_eq!(json["version"], BOOKMARK_EXPORT_VERSION);
+        assert_eq!(json["exported_at"], 1_800_000_000);
+        assert_eq!(json["bookmarks"][0]["title"], "Say \"hi\", <now>");
+        assert_eq!(json["bookmarks"][0]["tags"][1], "rust");
+        assert_eq!(json["bookmarks"][0]["archived"], true);
+        assert_eq!(json["bookmarks"][1]["description"], serde_json::Value::Null);
+
+        let empty: serde_json::Value = serde_json::from_str(&export(ExportFormat::Json, &[])).unwrap();
+        assert_eq!(empty["bookmarks"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn test_html_export() {
+        let html = export(ExportFormat::Html, &[bookmark()]);
+        assert!(html.starts_with("<!DOCTYPE NETSCAPE-Bookmark-file-1>\n"));
+        assert!(html.contains(
+            "<DT><A HREF=\"https://example.com/?a=1&amp;b=2\" ADD_DATE=\"1700000000\" LAST_MODIFIED=\"1700000060\" \
+             TAGS=\"reading,rust\" ARCHIVED=\"1\">Say &quot;hi&quot;, &lt;now&gt;</A>\n    <DD>Two\nlines\n"
+        ));
+        assert!(html.ends_with("</DL><p>\n"));
+    }
+
+    #[test]
+    fn test_csv_export() {
+        let csv = export(ExportFormat::Csv, &[bookmark()]);
+        assert_eq!(
+            csv,
+            "url,title,description,tags,created_at,updated_at,language,archived\r\n\
+             https://example.com/?a=1&b=2,\"Say \"\"hi\"\", <now>\",\"Two\nlines\",\"reading,rust\",\
+             2023-11-14T22:13:20Z,2023-11-14T22:14:20Z,en,true\r\n"
+        );
+    }
+}
@@ -0,0 +1,263 @@
+//! Bookmark import from browser bookmark files.
+//!
+//! Reads the files browsers keep their bookmarks in, for people who don't have an HTML export:
+//! - Chrome (and other Chromium browsers): the JSON `Bookmarks` file in the profile directory
+//! - Firefox: the `bookmarks-*.jsonlz4` backups in the profile's `bookmarkbackups` directory, or an
+//!   uncompressed JSON backup made from the Library window
+//!
+//! Each bookmark keeps the path of folders it was in, so the folders can be carried over.
+
+use anyhow::{Context as _, Result, anyhow, bail};
+use serde_json::Value;
+
+/// Header of Mozilla's LZ4 container, followed by the decompressed size as a little-endian `u32`.
+const MOZLZ4_MAGIC: &[u8] = b"mozLz40\0";
+/// Largest decompressed file accepted, to keep a small upload from expanding without bound.
+const MAX_DECOMPRESSED_SIZE: usize = 256 * 1024 * 1024;
+/// Microseconds between the Windows epoch (1601-01-01) Chrome counts from and the UNIX epoch.
+const CHROME_EPOCH_OFFSET_MICROS: i64 = 11_644_473_600_000_000;
+
+/// Which browser a bookmark file came from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BrowserFormat {
+    Chrome,
+    Firefox,
+}
+
+impl BrowserFormat {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BrowserFormat::Chrome => "Chrome",
+            BrowserFormat::Firefox => "Firefox",
+        }
+    }
+}
+
+/// A bookmark read from a browser file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImportedBookmark {
+    pub url: String,
+    pub title: String,
+    /// Folders the bookmark was in, outermost first, not counting the browser's own top-level folders
+    pub folders: Vec<String>,
+    /// UNIX timestamp of when it was bookmarked
+    pub added_at: Option<i64>,
+}
+
+/// Reads a Chrome or Firefox bookmark file, decompressing Firefox's `jsonlz4` backups first.
+///
+/// # Errors
+///
+/// Returns an error if the file isn't in either format.
+pub fn parse(bytes: &[u8]) -> Result<(BrowserFormat, Vec<ImportedBookmark>)> {
+    let decompressed;
+    let json = if bytes.starts_with(MOZLZ4_MAGIC) {
+        decompressed = decompress_mozlz4(bytes)?;
+        &decompressed[..]
+    } else {
+        bytes
+    };
+    let root: Value = serde_json::from_slice(json).context("Not a JSON bookmark file")?;
+
+    let mut bookmarks = Vec::new();
+    if let Some(roots) = root.get("roots").and_then(Value::as_object) {
+        // Chrome: the top-level folders (bookmarks bar, other, mobile) aren't part of the path
+        for child in roots.values().flat_map(children) {
+            walk_chrome(child, &mut Vec::new(), &mut bookmarks);
+        }
+        Ok((BrowserFormat::Chrome, bookmarks))
+    } else if root.get("type").and_then(Value::as_str) == Some("text/x-moz-place-container") {
+        walk_firefox(&root, &mut Vec::new(), &mut bookmarks);
+        Ok((BrowserFormat::Firefox, bookmarks))
+    } else {
+        bail!("Not a Chrome or Firefox bookmark file")
+    }
+}
+
+/// Decompresses a Mozilla `mozLz4` file: the magic header, the decompressed size, then one LZ4 block.
+fn decompress_mozlz4(bytes: &[u8]) -> Result<Vec<u8>> {
+    let size_bytes = bytes
+        .get(MOZLZ4_MAGIC.len()..MOZLZ4_MAGIC.len() + 4)
+        .ok_or_else(|| anyhow!("Truncated jsonlz4 header"))?;
+    let size = u32::from_le_bytes(size_bytes.try_into()?) as usize;
+    if size > MAX_DECOMPRESSED_SIZE {
+        bail!("Bookmark backup is too large ({size} bytes uncompressed)");
+    }
+    lz4_flex::block::decompress(&bytes[MOZLZ4_MAGIC.len() + 4..], size).context("Could not decompress jsonlz4 backup")
+}
+
+fn walk_chrome(node: &Value, folders: &mut Vec<String>, bookmarks: &mut Vec<ImportedBookmark>) {
+    let name = node.get("name").and_then(Value::as_str).unwrap_or_default();
+    match node.get("type").and_then(Value::as_str) {
+        Some("url") => {
+            if let Some(url) = node.get("url").and_then(Value::as_str) {
+                let added_at = node
+                    .get("date_added")
+                    .and_then(Value::as_str)
+                    .and_then(|micros| micros.parse::<i64>().ok())
+                    .filter(|micros| *micros > CHROME_EPOCH_OFFSET_MICROS)
+                    .map(|micros| (micros - CHROME_EPOCH_OFFSET_MICROS) / 1_000_000);
+                bookmarks.push(ImportedBookmark {
+                    url: url.to_string(),
+                    title: name.to_string(),
+                    folders: folders.clone(),
+                    added_at,
+                });
+            }
+        }
+        Some("folder") => {
+            folders.push(name.to_string());
+            for child in children(node) {
+                walk_chrome(child, folders, bookmarks);
+            }
+            folders.pop();
+        }
+        _ => {}
+    }
+}
+
+fn walk_firefox(node: &Value, folders: &mut Vec<String>, bookmarks: &mut Vec<ImportedBookmark>) {
+    let title = node.get("title").and_then(Value::as_str).unwrap_or_default();
+    match node.get("type").and_then(Value::as_str) {
+        Some("text/x-moz-place") => {
+            if let Some(url) = node.get("uri").and_then(Value::as_str) {
+                bookmarks.push(ImportedBookmark {
+                    url: url.to_string(),
+                    title: title.to_string(),
+                    folders: folders.clone(),
+                    added_at: node.get("dateAdded").and_then(Value::as_i64).map(|micros| micros / 1_000_000),
+                });
+            }
+        }
+        Some("text/x-moz-place-container") => {
+            // Built-in folders (menu, toolbar, other bookmarks, mobile) have a `root` name
+            let is_root = node.get("root").is_some();
+            if !is_root {
+                folders.push(title.to_string());
+            }
+            for child in children(node) {
+                walk_firefox(child, folders, bookmarks);
+            }
+            if !is_root {
+                folders.pop();
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The child nodes of a folder, in both formats.
+fn children(node: &Value) -> impl Iterator<Item = &Value> {
+    node.get("children").and_then(Value::as_array).into_iter().flatten()
+}
+
+/// The tag for a folder path: each folder name slugified, nested with `/`, e.g. `reading/rust-async`.
+///
+/// Returns `None` for bookmarks outside any folder.
+pub fn folder_tag(folders: &[String]) -> Option<String> {
+    let segments: Vec<String> = folders
+        .iter()
+        .map(|folder| {
+            folder
+                .to_lowercase()
+                .split(|c: char| !c.is_alphanumeric())
+                .filter(|part| !part.is_empty())
+                .collect::<Vec<_>>()
+                .join("-")
+        })
+        .filter(|segment| !segment.is_empty())
+        .collect();
+    (!segments.is_empty()).then(|| segments.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHROME: &str = r#"{
+        "checksum": "abc",
+        "roots": {
+            "bookmark_bar": {
+                "id": "1", "name": "Bookmarks bar", "type": "folder", "children": [
+                    {"id": "4", "name": "Rust", "type": "url", "url": "https://www.rust-lang.org/", "date_added": "13300000000000000"},
+                    {"id": "5", "name": "Reading", "type": "folder", "children": [
+                        {"id": "6", "name": "Async Rust", "type": "folder", "children": [
+                            {"id": "7", "name": "Tokio", "type": "url", "url": "https://tokio.rs/"}
+                        ]}
+                    ]}
+                ]
+            },
+            "other": {"id": "2", "name": "Other bookmarks", "type": "folder", "children": []}
+        },
+        "version": 1
+    }"#;
+
+    const FIREFOX: &str = r#"{
+        "guid": "root________", "title": "", "root": "placesRoot", "type": "text/x-moz-place-container", "children": [
+            {"guid": "toolbar_____", "title": "toolbar", "root": "toolbarFolder", "type": "text/x-moz-place-container", "children": [
+                {"title": "Docs", "type": "text/x-moz-place-container", "children": [
+                    {"title": "docs.rs", "type": "text/x-moz-place", "uri": "https://docs.rs/", "dateAdded": 1700000000000000},
+                    {"type": "text/x-moz-place-separator"}
+                ]}
+            ]},
+            {"guid": "unfiled_____", "title": "unfiled", "root": "unfiledBookmarksFolder", "type": "text/x-moz-place-container", "children": [
+                {"title": "Crates", "type": "text/x-moz-place", "uri": "https://crates.io/"}
+            ]}
+        ]
+    }"#;
+
+    #[test]
+    fn test_parse_chrome() {
+        let (format, bookmarks) = parse(CHROME.as_bytes()).unwrap();
+        assert_eq!(format, BrowserFormat::Chrome);
+        assert_eq!(bookmarks.len(), 2);
+        assert_eq!(bookmarks[0].url, "https://www.rust-lang.org/");
+        assert!(bookmarks[0].folders.is_empty());
+        // 13300000000000000 µs since 1601 is 2022-06-18
+        assert_eq!(bookmarks[0].added_at, Some(1_655_526_400));
+        assert_eq!(bookmarks[1].title, "Tokio");
+        assert_eq!(bookmarks[1].folders, ["Reading", "Async Rust"]);
+        assert_eq!(bookmarks[1].added_at, None);
+    }
+
+    #[test]
+    fn test_parse_firefox() {
+        let (format, bookmarks) = parse(FIREFOX.as_bytes()).unwrap();
+        assert_eq!(format, BrowserFormat::Firefox);
+        assert_eq!(bookmarks.len(), 2);
+        assert_eq!(bookmarks[0].folders, ["Docs"]);
+        assert_eq!(bookmarks[0].added_at, Some(1_700_000_000));
+        assert_eq!(bookmarks[1].url, "https://crates.io/");
+        assert!(bookmarks[1].folders.is_empty());
+    }
+
+    #[test]
+    fn test_parse_firefox_jsonlz4() {
+        let mut file = MOZLZ4_MAGIC.to_vec();
+        file.extend(u32::try_from(FIREFOX.len()).unwrap().to_le_bytes());
+        file.extend(lz4_flex::block::compress(FIREFOX.as_bytes()));
+
+        let (format, bookmarks) = parse(&file).unwrap();
+        assert_eq!(format, BrowserFormat::Firefox);
+        assert_eq!(bookmarks.len(), 2);
+
+        assert!(parse(&file[..10]).is_err());
+    }
+
+    #[test]
+    fn test_parse_rejects_other_files() {
+        assert!(parse(b"<!DOCTYPE NETSCAPE-Bookmark-file-1>").is_err());
+        assert!(parse(br#"{"version": 1}"#).is_err());
+    }
+
+    #[test]
+    fn test_folder_tag() {
+        assert_eq!(
+            folder_tag(&["Reading".to_string(), "Async Rust!".to_string()]),
+            Some("reading/async-rust".to_string())
+        );
+        assert_eq!(folder_tag(&["C++ & 'Tools'".to_string()]), Some("c-tools".to_string()));
+        assert_eq!(folder_tag(&[]), None);
+        assert_eq!(folder_tag(&["???".to_string()]), None);
+    }
+}
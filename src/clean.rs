@@ -0,0 +1,205 @@
+//! URL cleaning.
+//!
+//! Strips tracking parameters from URLs before they're saved, so the same page saved from a
+//! newsletter and from a search result ends up as the same bookmark. Built-in rules remove common
+//! trackers everywhere; users can add their own rules for specific sites. A rule removes every query
+//! parameter whose name matches its regex.
+
+use std::sync::LazyLock;
+
+use regex::{Regex, RegexBuilder};
+use reqwest::Url;
+use tracing::{error, warn};
+use uuid::Uuid;
+
+use crate::db::{self, Db, bookmarks::NewBookmark, url_rules::UrlCleaningRule};
+
+/// Tracking parameters removed from URLs on every site.
+const BUILT_IN_PATTERNS: &[&str] = &["^utm_", "^fbclid$", "^gclid$"];
+/// Longest allowed user rule pattern.
+pub const MAX_PATTERN_LENGTH: usize = 200;
+/// Upper bound on the compiled size of a user rule, see [`RegexBuilder::size_limit`].
+const PATTERN_SIZE_LIMIT: usize = 64 * 1024;
+
+static BUILT_IN_RULES: LazyLock<Vec<Rule>> = LazyLock::new(|| {
+    BUILT_IN_PATTERNS
+        .iter()
+        .map(|pattern| Rule {
+            domain: None,
+            pattern: Regex::new(pattern).expect("built-in URL cleaning patterns are valid"),
+        })
+        .collect()
+});
+
+/// A compiled cleaning rule.
+#[derive(Clone, Debug)]
+struct Rule {
+    /// Only applies to this host and its subdomains; `None` applies everywhere
+    domain: Option<String>,
+    pattern: Regex,
+}
+
+impl Rule {
+    fn applies_to(&self, host: &str) -> bool {
+        self.domain.as_deref().is_none_or(|domain| is_on_domain(host, domain))
+    }
+}
+
+/// Whether `host` is `domain` or one of its subdomains.
+pub fn is_on_domain(host: &str, domain: &str) -> bool {
+    host == domain || host.strip_suffix(domain).is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+/// Compiles a user's rule pattern, rejecting ones that are too long or too expensive.
+///
+/// # Errors
+///
+/// Returns a message for the user if the pattern is invalid.
+pub fn compile_pattern(pattern: &str) -> Result<Regex, String> {
+    if pattern.is_empty() || pattern.chars().count() > MAX_PATTERN_LENGTH {
+        return Err(format!("Patterns must be between 1 and {MAX_PATTERN_LENGTH} characters."));
+    }
+    RegexBuilder::new(pattern)
+        .size_limit(PATTERN_SIZE_LIMIT)
+        .build()
+        .map_err(|err| format!("Invalid pattern: {err}"))
+}
+
+/// Normalizes a rule's domain the same way `site:` search filters are: lowercased, without a
+/// leading `www.` or trailing `/`. Returns `None` if nothing is left.
+pub fn normalize_domain(domain: &str) -> Option<String> {
+    let domain = domain.trim().to_lowercase();
+    let domain = domain.strip_prefix("www.").unwrap_or(&domain).trim_end_matches('/');
+    (!domain.is_empty()).then(|| domain.to_string())
+}
+
+/// The built-in rules plus one user's own.
+#[derive(Clone, Debug, Default)]
+pub struct UrlCleaner {
+    user_rules: Vec<Rule>,
+}
+
+impl UrlCleaner {
+    /// Builds a cleaner from a user's rules, skipping (and logging) any that no longer compile.
+    pub fn new(rules: &[UrlCleaningRule]) -> Self {
+        let user_rules = rules
+            .iter()
+            .filter_map(|rule| match compile_pattern(&rule.pattern) {
+                Ok(pattern) => Some(Rule {
+                    domain: Some(rule.domain.clone()),
+                    pattern,
+                }),
+                Err(err) => {
+                    warn!(rule_id = %rule.rule_id, err, "Skipping invalid URL cleaning rule.");
+                    None
+                }
+            })
+            .collect();
+        Self { user_rules }
+    }
+
+    /// Loads the cleaner for a user. Falls back to the built-in rules only if their rules can't be loaded.
+    pub async fn for_user(db: &Db, user_id: Uuid) -> Self {
+        match db::url_rules::get_user_rules(db, user_id).await {
+            Ok(rules) => Self::new(&rules),
+            Err(err) => {
+                error!(?err, "🚨 Could not load URL cleaning rules, using built-in rules only.");
+                Self::default()
+            }
+        }
+    }
+
+    /// Removes matching query parameters from `url`. Returns the cleaned URL, or `None` if nothing
+    /// was removed (including when `url` isn't a valid absolute URL).
+    pub fn clean(&self, url: &str) -> Option<String> {
+        let mut parsed = Url::parse(url).ok()?;
+        let host = parsed.host_str()?.to_lowercase();
+        let rules: Vec<&Rule> = BUILT_IN_RULES
+            .iter()
+            .chain(&self.user_rules)
+            .filter(|rule| rule.applies_to(&host))
+            .collect();
+
+        let params: Vec<(String, String)> = parsed.query_pairs().into_owned().collect();
+        let kept: Vec<&(String, String)> = params
+            .iter()
+            .filter(|(name, _)| !rules.iter().any(|rule| rule.pattern.is_match(name)))
+            .collect();
+        if kept.len() == params.len() {
+            return None;
+        }
+
+        if kept.is_empty() {
+            parsed.set_query(None);
+        } else {
+            parsed.query_pairs_mut().clear().extend_pairs(kept);
+        }
+        Some(parsed.to_string())
+    }
+
+    /// Cleans a new bookmark's URL, keeping the URL as given in `original_url` if it changed.
+    pub fn apply(&self, mut bookmark: NewBookmark) -> NewBookmark {
+        if let Some(cleaned) = self.clean(&bookmark.url) {
+            bookmark.original_url = Some(std::mem::replace(&mut bookmark.url, cleaned));
+        }
+        bookmark
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user_rule(domain: &str, pattern: &str) -> UrlCleaningRule {
+        UrlCleaningRule {
+            rule_id: Uuid::new_v4(),
+            domain: domain.to_string(),
+            pattern: pattern.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_built_in_rules() {
+        let cleaner = UrlCleaner::default();
+        assert_eq!(
+            cleaner.clean("https://example.com/post?id=7&utm_source=news&utm_medium=email&fbclid=abc"),
+            Some("https://example.com/post?id=7".to_string())
+        );
+        assert_eq!(
+            cleaner.clean("https://example.com/?gclid=xyz#top"),
+            Some("https://example.com/#top".to_string())
+        );
+        // Nothing to remove
+        assert_eq!(cleaner.clean("https://example.com/?q=utm_source"), None);
+        assert_eq!(cleaner.clean("not a url"), None);
+    }
+
+    #[test]
+    fn test_user_rules_apply_to_their_domain() {
+        let cleaner = UrlCleaner::new(&[user_rule("example.com", "^(ref|src)$")]);
+        assert_eq!(
+            cleaner.clean("https://shop.example.com/item?ref=home&size=m"),
+            Some("https://shop.example.com/item?size=m".to_string())
+        );
+        assert_eq!(
+            cleaner.clean("https://example.com/?src=x"),
+            Some("https://example.com/".to_string())
+        );
+        assert_eq!(cleaner.clean("https://notexample.com/?ref=home"), None);
+        assert_eq!(cleaner.clean("https://other.org/?ref=home"), None);
+    }
+
+    #[test]
+    fn test_invalid_user_rules_are_skipped() {
+        let cleaner = UrlCleaner::new(&[user_rule("example.com", "(unclosed")]);
+        assert_eq!(cleaner.clean("https://example.com/?unclosed=1"), None);
+        assert!(compile_pattern("").is_err());
+        assert!(compile_pattern(&"a".repeat(MAX_PATTERN_LENGTH + 1)).is_err());
+    }
+
+    #[test]
+    fn test_normalize_domain() {
+        assert_eq!(normalize_domain(" WWW.Example.com/ "), Some("example.com".to_string()));
+        assert_eq!(normalize_domain("www."), None);
+    }
+}
@@ -0,0 +1,103 @@
+//! Instance-wide bookmark search for admins, and the audit log every such search is recorded in.
+
+use anyhow::Result;
+use uuid::Uuid;
+
+use crate::db::{Db, timing::QueryTimer};
+
+/// A bookmark found by an admin search, with the account it belongs to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AdminSearchResult {
+    pub username: String,
+    pub url: String,
+    pub title: String,
+    /// UNIX timestamp
+    pub created_at: i64,
+}
+
+/// An entry in the admin search audit log.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AdminSearchLogEntry {
+    pub admin_username: String,
+    pub query: String,
+    pub reason: String,
+    pub result_count: i64,
+    /// UNIX timestamp
+    pub searched_at: i64,
+}
+
+/// Searches the URL, title and description of every user's bookmarks, newest first.
+///
+/// Only for the audited admin search; everything else must stay scoped to one user.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn search_all_users(db: &Db, query: &str, limit: i64) -> Result<Vec<AdminSearchResult>> {
+    let _timer = QueryTimer::start("search_all_users");
+    let pattern = format!("%{query}%");
+    let results = sqlx::query_as!(
+        AdminSearchResult,
+        r#"
+        select u.username, b.url, b.title, b.created_at
+        from bookmarks b
+        join users u on b.user_id = u.user_id
+        where b.url like $1 or b.title like $1 or b.description like $1
+        order by b.created_at desc
+        limit $2
+        "#,
+        pattern,
+        limit
+    )
+    .fetch_all(&db.reader)
+    .await?;
+
+    Ok(results)
+}
+
+/// Adds a search to the audit log.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn record_search(db: &Db, admin_user_id: Uuid, admin_username: &str, query: &str, reason: &str, result_count: i64) -> Result<()> {
+    let _timer = QueryTimer::start("record_search");
+    sqlx::query!(
+        r#"
+        insert into admin_search_log (admin_user_id, admin_username, query, reason, result_count)
+        values (?, ?, ?, ?, ?)
+        "#,
+        admin_user_id,
+        admin_username,
+        query,
+        reason,
+        result_count
+    )
+    .execute(&db.writer)
+    .await?;
+
+    Ok(())
+}
+
+/// Retrieves the most recent admin searches, newest first.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn get_recent_searches(db: &Db, limit: i64) -> Result<Vec<AdminSearchLogEntry>> {
+    let _timer = QueryTimer::start("get_recent_searches");
+    let entries = sqlx::query_as!(
+        AdminSearchLogEntry,
+        r#"
+        select admin_username, query, reason, result_count, searched_at
+        from admin_search_log
+        order by search_id desc
+        limit ?
+        "#,
+        limit
+    )
+    .fetch_all(&db.reader)
+    .await?;
+
+    Ok(entries)
+}
@@ -0,0 +1,378 @@
+//! API token database operations.
+//!
+//! API tokens let scripts and apps use the JSON API without a browser session, by sending
+//! `Authorization: Bearer <token>`. Only a keyed hash of each token is stored (see
+//! [`crate::encryption::EncryptionProvider::hash_api_token`]); the token itself is shown to the user
+//! once, when it's created.
+//!
+//! Each token has a [`TokenScope`] limiting which requests it can make, and can be limited to
+//! bookmarks with some tags, so a token in a shell alias or on a shared device can't read or delete
+//! everything.
+
+use std::str::FromStr;
+
+use anyhow::{Result, anyhow};
+use axum::http::Method;
+use uuid::Uuid;
+
+use crate::db::{Db, timing::QueryTimer, users::User};
+
+/// What an API token may do.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum TokenScope {
+    /// Only read bookmarks, tags and authors (`GET` requests)
+    Read,
+    /// Only save new bookmarks (`POST` requests)
+    Create,
+    /// Everything the JSON API offers
+    #[default]
+    Full,
+}
+
+impl TokenScope {
+    /// The value stored in the database and sent by the settings form.
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TokenScope::Read => "read",
+            TokenScope::Create => "create",
+            TokenScope::Full => "full",
+        }
+    }
+
+    /// Name shown to users.
+    #[must_use]
+    pub fn label(self) -> &'static str {
+        match self {
+            TokenScope::Read => "Read only",
+            TokenScope::Create => "Create only",
+            TokenScope::Full => "Full access",
+        }
+    }
+
+    /// Whether a token with this scope can make requests with `method`.
+    #[must_use]
+    pub fn allows(self, method: &Method) -> bool {
+        match self {
+            TokenScope::Read => matches!(*method, Method::GET | Method::HEAD),
+            TokenScope::Create => *method == Method::POST,
+            TokenScope::Full => true,
+        }
+    }
+}
+
+impl FromStr for TokenScope {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "read" => Ok(TokenScope::Read),
+            "create" => Ok(TokenScope::Create),
+            "full" => Ok(TokenScope::Full),
+            _ => Err(anyhow!("Unknown token scope '{s}'")),
+        }
+    }
+}
+
+/// An API token, as listed on the settings page.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ApiToken {
+    pub token_id: Uuid,
+    /// What it's for, e.g. "phone"
+    pub name: String,
+    pub scope: TokenScope,
+    /// Tags it's limited to; empty if it isn't
+    pub tags: Vec<String>,
+    /// UNIX timestamp
+    pub created_at: i64,
+    /// UNIX timestamp, to the minute; `None` if it was never used
+    pub last_used_at: Option<i64>,
+}
+
+/// The token a request was authenticated with, added to the request next to the [`User`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct TokenAuth {
+    pub token_id: Uuid,
+    pub name: String,
+    pub scope: TokenScope,
+    /// Tags it's limited to: it only sees and changes bookmarks with at least one of them. Empty if
+    /// it isn't limited.
+    pub tags: Vec<String>,
+}
+
+impl TokenAuth {
+    /// Whether the token can see and change a bookmark with these tags.
+    #[must_use]
+    pub fn can_access<'a>(&self, mut tag_names: impl Iterator<Item = &'a str>) -> bool {
+        self.tags.is_empty() || tag_names.any(|tag_name| self.tags.iter().any(|tag| tag == tag_name))
+    }
+}
+
+/// Reads a scope stored in the database. Values this version doesn't know get the narrowest scope.
+fn stored_scope(scope: &str) -> TokenScope {
+    scope.parse().unwrap_or(TokenScope::Read)
+}
+
+/// Reads the tags a token is limited to, stored comma-separated.
+fn stored_tags(tags: Option<&str>) -> Vec<String> {
+    tags.unwrap_or_default()
+        .split(',')
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Requests made with an API token to one endpoint on one day.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TokenUsage {
+    pub token_id: Uuid,
+    /// UTC date, YYYY-MM-DD
+    pub day: String,
+    /// Method and route, e.g. `GET /api/v1/bookmarks/{bookmark_id}`
+    pub endpoint: String,
+    pub requests: i64,
+}
+
+/// Retrieves a user's API tokens, newest first.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn get_api_tokens(db: &Db, user_id: Uuid) -> Result<Vec<ApiToken>> {
+    let _timer = QueryTimer::start("get_api_tokens");
+    let records = sqlx::query!(
+        r#"
+        select token_id as "token_id!: Uuid", name, scope, tags, created_at, last_used_at
+        from user_api_tokens
+        where user_id = ?
+        order by created_at desc, name
+        "#,
+        user_id
+    )
+    .fetch_all(&db.reader)
+    .await?;
+
+    Ok(records
+        .into_iter()
+        .map(|record| ApiToken {
+            token_id: record.token_id,
+            name: record.name,
+            scope: stored_scope(&record.scope),
+            tags: stored_tags(record.tags.as_deref()),
+            created_at: record.created_at,
+            last_used_at: record.last_used_at,
+        })
+        .collect())
+}
+
+/// Stores a new API token for a user by its hash, returning its ID. The token is limited to
+/// bookmarks with one of `tags` (lowercase names), unless there are none.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn create_api_token(db: &Db, user_id: Uuid, name: &str, token_hash: &str, scope: TokenScope, tags: &[String]) -> Result<Uuid> {
+    let _timer = QueryTimer::start("create_api_token");
+    let scope = scope.as_str();
+    let tags = (!tags.is_empty()).then(|| tags.join(","));
+    let record = sqlx::query!(
+        r#"
+        insert into user_api_tokens (user_id, name, token_hash, scope, tags)
+        values (?, ?, ?, ?, ?)
+        returning token_id as "token_id!: Uuid"
+        "#,
+        user_id,
+        name,
+        token_hash,
+        scope,
+        tags
+    )
+    .fetch_one(&db.writer)
+    .await?;
+
+    Ok(record.token_id)
+}
+
+/// Revokes one of a user's API tokens. Returns whether anything was deleted.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn delete_api_token(db: &Db, user_id: Uuid, token_id: Uuid) -> Result<bool> {
+    let _timer = QueryTimer::start("delete_api_token");
+    let result = sqlx::query!("delete from user_api_tokens where user_id = ? and token_id = ?", user_id, token_id)
+        .execute(&db.writer)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Looks up the user a token belongs to by the token's hash, or `None` if there's no such token or
+/// its user has been revoked.
+///
+/// Also records that the token was used, at most once a minute so busy clients don't queue up
+/// writes.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn get_token_user(db: &Db, token_hash: &str) -> Result<Option<(User, TokenAuth)>> {
+    let _timer = QueryTimer::start("get_token_user");
+    let Some(record) = sqlx::query!(
+        r#"
+        select
+            t.token_id as "token_id!: Uuid",
+            t.name,
+            t.scope,
+            t.tags,
+            t.last_used_at,
+            u.user_id as "user_id!: Uuid",
+            u.username,
+            u.is_revoked,
+            u.is_admin
+        from user_api_tokens t
+        join users u on u.user_id = t.user_id
+        where t.token_hash = ? and not u.is_revoked
+        "#,
+        token_hash
+    )
+    .fetch_optional(&db.reader)
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    let now = chrono::Utc::now().timestamp();
+    if record.last_used_at.is_none_or(|last_used_at| now - last_used_at >= 60) {
+        sqlx::query!(
+            "update user_api_tokens set last_used_at = ? where token_id = ?",
+            now,
+            record.token_id
+        )
+        .execute(&db.writer)
+        .await?;
+    }
+
+    Ok(Some((
+        User {
+            user_id: record.user_id,
+            username: record.username,
+            is_revoked: record.is_revoked,
+            is_admin: record.is_admin,
+        },
+        TokenAuth {
+            token_id: record.token_id,
+            name: record.name,
+            scope: stored_scope(&record.scope),
+            tags: stored_tags(record.tags.as_deref()),
+        },
+    )))
+}
+
+/// Adds requests to the usage counts of API tokens. Counts for tokens revoked in the meantime are
+/// dropped.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn record_token_usage(db: &Db, usage: &[TokenUsage]) -> Result<()> {
+    let _timer = QueryTimer::start("record_token_usage");
+    let mut tx = db.writer.begin().await?;
+    for TokenUsage {
+        token_id,
+        day,
+        endpoint,
+        requests,
+    } in usage
+    {
+        sqlx::query!(
+            r#"
+            insert into api_token_usage (token_id, day, endpoint, requests)
+            select token_id, ?, ?, ? from user_api_tokens where token_id = ?
+            on conflict (token_id, day, endpoint) do update set requests = requests + excluded.requests
+            "#,
+            day,
+            endpoint,
+            requests,
+            token_id
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Retrieves the usage counts of a user's API tokens from `since_day` (YYYY-MM-DD) on, newest day
+/// first and the busiest endpoints first within a day.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn get_token_usage(db: &Db, user_id: Uuid, since_day: &str) -> Result<Vec<TokenUsage>> {
+    let _timer = QueryTimer::start("get_token_usage");
+    let usage = sqlx::query_as!(
+        TokenUsage,
+        r#"
+        select u.token_id as "token_id!: Uuid", u.day, u.endpoint, u.requests
+        from api_token_usage u
+        join user_api_tokens t on t.token_id = u.token_id
+        where t.user_id = ? and u.day >= ?
+        order by u.day desc, u.requests desc, u.endpoint
+        "#,
+        user_id,
+        since_day
+    )
+    .fetch_all(&db.reader)
+    .await?;
+
+    Ok(usage)
+}
+
+/// Deletes API token usage counts from before `before_day` (YYYY-MM-DD), returning how many were
+/// deleted.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn delete_old_token_usage(db: &Db, before_day: &str) -> Result<u64> {
+    let _timer = QueryTimer::start("delete_old_token_usage");
+    let result = sqlx::query!("delete from api_token_usage where day < ?", before_day)
+        .execute(&db.writer)
+        .await?;
+
+    Ok(result.rows_affected())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scope_allows() {
+        assert!(TokenScope::Read.allows(&Method::GET));
+        assert!(!TokenScope::Read.allows(&Method::POST));
+        assert!(TokenScope::Create.allows(&Method::POST));
+        assert!(!TokenScope::Create.allows(&Method::GET));
+        assert!(!TokenScope::Create.allows(&Method::DELETE));
+        assert!(TokenScope::Full.allows(&Method::DELETE));
+        assert_eq!(stored_scope("admin"), TokenScope::Read);
+    }
+
+    #[test]
+    fn test_can_access() {
+        let mut token = TokenAuth {
+            token_id: Uuid::nil(),
+            name: "shell".to_string(),
+            scope: TokenScope::Full,
+            tags: stored_tags(None),
+        };
+        assert!(token.can_access(std::iter::empty()));
+
+        token.tags = stored_tags(Some("inbox,reading"));
+        assert!(token.can_access(["rust", "reading"].into_iter()));
+        assert!(!token.can_access(["rust", "read"].into_iter()));
+        assert!(!token.can_access(std::iter::empty()));
+    }
+}
@@ -0,0 +1,88 @@
+//! Domain blocklist database operations.
+//!
+//! Each user has their own blocklist, and the admin keeps one for the whole instance (stored with
+//! no user). How they're applied lives in [`crate::blocklist`].
+
+use anyhow::Result;
+use uuid::Uuid;
+
+use crate::db::{Db, timing::QueryTimer};
+
+/// Bookmarks from `domain` and its subdomains are refused, or archived as they're saved.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlockedDomain {
+    pub block_id: Uuid,
+    pub domain: String,
+    /// `block` or `archive`
+    pub action: String,
+    /// Whether this is on the instance-wide list rather than the user's own
+    pub is_instance: bool,
+}
+
+/// Retrieves the blocklist `user_id` is subject to: the instance-wide entries followed by the
+/// user's own, each ordered by domain. Only the instance-wide list if `user_id` is `None`.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn get_blocklist(db: &Db, user_id: Option<Uuid>) -> Result<Vec<BlockedDomain>> {
+    let _timer = QueryTimer::start("get_blocklist");
+    let entries = sqlx::query_as!(
+        BlockedDomain,
+        r#"
+        select
+            block_id as "block_id!: Uuid",
+            domain,
+            action,
+            (user_id is null) as "is_instance!: bool"
+        from blocked_domains
+        where user_id is null or user_id = ?
+        order by user_id is not null, domain
+        "#,
+        user_id
+    )
+    .fetch_all(&db.reader)
+    .await?;
+
+    Ok(entries)
+}
+
+/// Adds a domain to a user's blocklist, or the instance-wide one if `user_id` is `None`. Returns
+/// its ID, or `None` if the domain is already on that list.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn create_blocked_domain(db: &Db, user_id: Option<Uuid>, domain: &str, action: &str) -> Result<Option<Uuid>> {
+    let _timer = QueryTimer::start("create_blocked_domain");
+    let record = sqlx::query!(
+        r#"
+        insert into blocked_domains (user_id, domain, action)
+        values (?, ?, ?)
+        on conflict do nothing
+        returning block_id as "block_id!: Uuid"
+        "#,
+        user_id,
+        domain,
+        action
+    )
+    .fetch_optional(&db.writer)
+    .await?;
+
+    Ok(record.map(|record| record.block_id))
+}
+
+/// Removes a domain from a user's blocklist, or the instance-wide one if `user_id` is `None`.
+/// Returns whether anything was deleted.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn delete_blocked_domain(db: &Db, user_id: Option<Uuid>, block_id: Uuid) -> Result<bool> {
+    let _timer = QueryTimer::start("delete_blocked_domain");
+    let result = sqlx::query!("delete from blocked_domains where user_id is ? and block_id = ?", user_id, block_id)
+        .execute(&db.writer)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
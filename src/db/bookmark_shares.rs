@@ -0,0 +1,170 @@
+//! Bookmark share database operations.
+//!
+//! A share is a public link to one of a user's bookmarks, showing its title, URL, description and
+//! tags to anyone who has it. Like a subscription, the URL carries a signed [`ShareToken`] and the
+//! row here is what makes it valid; it doesn't expire, but works until it's revoked or the bookmark
+//! is deleted.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::db::{
+    Db,
+    bookmarks::{BookmarkId, get_created_ago},
+    timing::QueryTimer,
+};
+
+/// Token in a share's URL, converted to a JWT and signed.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+pub struct ShareToken {
+    pub share_id: Uuid,
+}
+
+/// A shared bookmark, as listed on the settings page.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BookmarkShare {
+    pub share_id: Uuid,
+    pub bookmark_id: BookmarkId,
+    pub title: String,
+    /// When it was last opened, e.g. "2 hours ago"
+    pub last_viewed_ago: Option<String>,
+    /// The bookmark was made private since, so the link doesn't work until it's public again
+    pub is_private: bool,
+}
+
+/// What a valid share gives access to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShareAccess {
+    pub share_id: Uuid,
+    /// Whose bookmark it is
+    pub user_id: Uuid,
+    pub username: String,
+    pub bookmark_id: BookmarkId,
+}
+
+/// Retrieves a user's shared bookmarks that aren't in the trash, most recently shared first.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn get_shares(db: &Db, user_id: Uuid) -> Result<Vec<BookmarkShare>> {
+    let _timer = QueryTimer::start("get_bookmark_shares");
+    let records = sqlx::query!(
+        r#"
+        select
+            s.share_id as "share_id!: Uuid",
+            s.bookmark_id as "bookmark_id!: BookmarkId",
+            b.title,
+            s.last_viewed_at,
+            b.visibility = 'private' as "is_private!: bool"
+        from bookmark_shares s
+        join bookmarks b on b.bookmark_id = s.bookmark_id
+        where s.user_id = ? and b.deleted_at is null
+        order by s.created_at desc
+        "#,
+        user_id
+    )
+    .fetch_all(&db.reader)
+    .await?;
+
+    Ok(records
+        .into_iter()
+        .map(|record| BookmarkShare {
+            share_id: record.share_id,
+            bookmark_id: record.bookmark_id,
+            title: record.title,
+            last_viewed_ago: record.last_viewed_at.map(get_created_ago),
+            is_private: record.is_private,
+        })
+        .collect())
+}
+
+/// Shares one of a user's bookmarks, or returns the share it already has. Returns `None` if the
+/// user has no such public bookmark outside the trash.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn create_share(db: &Db, user_id: Uuid, bookmark_id: BookmarkId) -> Result<Option<Uuid>> {
+    let _timer = QueryTimer::start("create_bookmark_share");
+    let share_id = sqlx::query_scalar!(
+        r#"
+        insert into bookmark_shares (user_id, bookmark_id)
+        select user_id, bookmark_id from bookmarks
+        where user_id = ? and bookmark_id = ? and deleted_at is null and visibility = 'public'
+        on conflict (bookmark_id) do update set bookmark_id = excluded.bookmark_id
+        returning share_id as "share_id!: Uuid"
+        "#,
+        user_id,
+        bookmark_id
+    )
+    .fetch_optional(&db.writer)
+    .await?;
+
+    Ok(share_id)
+}
+
+/// Stops sharing one of a user's bookmarks, revoking its link. Returns whether it was shared.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn delete_share(db: &Db, user_id: Uuid, bookmark_id: BookmarkId) -> Result<bool> {
+    let _timer = QueryTimer::start("delete_bookmark_share");
+    let result = sqlx::query!(
+        "delete from bookmark_shares where user_id = ? and bookmark_id = ?",
+        user_id,
+        bookmark_id
+    )
+    .execute(&db.writer)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Looks up what a share gives access to, or `None` if it has been revoked, its bookmark made
+/// private, or its user revoked.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn get_share_access(db: &Db, share_id: Uuid) -> Result<Option<ShareAccess>> {
+    let _timer = QueryTimer::start("get_share_access");
+    let access = sqlx::query_as!(
+        ShareAccess,
+        r#"
+        select
+            s.share_id as "share_id!: Uuid",
+            s.user_id as "user_id!: Uuid",
+            u.username,
+            s.bookmark_id as "bookmark_id!: BookmarkId"
+        from bookmark_shares s
+        join users u on u.user_id = s.user_id
+        join bookmarks b on b.bookmark_id = s.bookmark_id
+        where s.share_id = ? and not u.is_revoked and b.visibility = 'public'
+        "#,
+        share_id
+    )
+    .fetch_optional(&db.reader)
+    .await?;
+
+    Ok(access)
+}
+
+/// Notes that a share was just opened.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn mark_viewed(db: &Db, share_id: Uuid) -> Result<()> {
+    let _timer = QueryTimer::start("mark_share_viewed");
+    sqlx::query!(
+        "update bookmark_shares set last_viewed_at = unixepoch() where share_id = ?",
+        share_id
+    )
+    .execute(&db.writer)
+    .await?;
+
+    Ok(())
+}
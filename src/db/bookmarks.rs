@@ -1,23 +1,257 @@
 //! Bookmark database operations.
 
-use std::ops::Deref;
+use std::{collections::HashSet, fmt, ops::Deref, str::FromStr};
 
-use anyhow::Result;
-use sqlx::{SqlitePool, prelude::FromRow};
+use anyhow::{Context as _, Result};
+use base64::{Engine as _, prelude::BASE64_URL_SAFE_NO_PAD};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sqlx::{QueryBuilder, Sqlite, SqliteConnection, prelude::FromRow};
 use uuid::Uuid;
 
 use crate::{
-    db,
-    search::{SearchLogic, SearchQuery, SearchTerm},
+    db::{
+        self, Db,
+        fetch_failures::{FetchFailure, FetchFailures},
+        link_checks::ArchivedCopies,
+        tags::TagColors,
+        timing::QueryTimer,
+    },
+    search::{DateRange, FieldTerm, HasFilter, SearchField, SearchLogic, SearchQuery, SearchTerm, StateFilter},
+    suggest::domain_of,
 };
 
+/// Entry point a bookmark was saved through, searchable with `source:`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum BookmarkSource {
+    /// The add bookmark form
+    #[default]
+    Web,
+    /// The JSON API
+    Api,
+    /// A file imported from another browser or service
+    Import,
+    /// A file dropped into the watched directory
+    Folder,
+    /// The starter set new users are given, see [`crate::onboarding`]
+    Starter,
+    /// The bookmarklet that saves the open page right away, see [`crate::handler::quick_add`]
+    Bookmarklet,
+}
+
+impl BookmarkSource {
+    /// The value stored in the database and matched by `source:` searches.
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BookmarkSource::Web => "web",
+            BookmarkSource::Api => "api",
+            BookmarkSource::Import => "import",
+            BookmarkSource::Folder => "folder",
+            BookmarkSource::Starter => "starter",
+            BookmarkSource::Bookmarklet => "bookmarklet",
+        }
+    }
+}
+
+/// Who can see a bookmark besides its owner.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Visibility {
+    /// Shown in guest links, public tags, subscriptions and share links that include it
+    #[default]
+    Public,
+    /// Only ever shown to its owner
+    Private,
+}
+
+impl Visibility {
+    /// The value stored in the database.
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Visibility::Public => "public",
+            Visibility::Private => "private",
+        }
+    }
+
+    /// The visibility picked with a "Private" checkbox.
+    #[must_use]
+    pub fn private_if(is_private: bool) -> Self {
+        if is_private { Visibility::Private } else { Visibility::Public }
+    }
+}
+
+/// Where a new bookmark came from: its source, plus details such as which API token or import
+/// file, when there are any.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BookmarkOrigin {
+    pub source: BookmarkSource,
+    pub detail: Option<String>,
+}
+
+impl From<BookmarkSource> for BookmarkOrigin {
+    fn from(source: BookmarkSource) -> Self {
+        Self { source, detail: None }
+    }
+}
+
+/// Sort order for bookmark listings.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    /// Most recently saved first
+    #[default]
+    Newest,
+    /// Least recently saved first
+    Oldest,
+    /// Alphabetical by title
+    Title,
+}
+
+impl SortOrder {
+    /// The value used for this order in URLs and static queries.
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SortOrder::Newest => "newest",
+            SortOrder::Oldest => "oldest",
+            SortOrder::Title => "title",
+        }
+    }
+
+    /// The `order by` clause for dynamically built queries, which alias bookmarks as `b`.
+    fn order_by_clause(self) -> &'static str {
+        match self {
+            SortOrder::Newest => "b.created_at desc",
+            SortOrder::Oldest => "b.created_at asc",
+            SortOrder::Title => "b.title collate nocase, b.created_at desc",
+        }
+    }
+}
+
+/// Stable public identifier of a bookmark.
+///
+/// Stored as a UUID blob, but rendered in URLs, templates and JSON as 22 characters of URL-safe
+/// base64 so it can be dropped into a path without escaping. Parsing also accepts the usual
+/// hyphenated UUID form.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, sqlx::Type)]
+#[sqlx(transparent)]
+pub struct BookmarkId(pub Uuid);
+
+impl BookmarkId {
+    /// Encodes the ID as URL-safe base64 without padding.
+    #[must_use]
+    pub fn encode(&self) -> String {
+        BASE64_URL_SAFE_NO_PAD.encode(self.0.as_bytes())
+    }
+}
+
+impl fmt::Display for BookmarkId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.encode())
+    }
+}
+
+impl FromStr for BookmarkId {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if let Ok(uuid) = Uuid::parse_str(s) {
+            return Ok(Self(uuid));
+        }
+        let bytes = BASE64_URL_SAFE_NO_PAD
+            .decode(s)
+            .with_context(|| format!("Invalid bookmark ID '{s}'"))?;
+        let uuid = Uuid::from_slice(&bytes).with_context(|| format!("Invalid bookmark ID '{s}'"))?;
+        Ok(Self(uuid))
+    }
+}
+
+impl Serialize for BookmarkId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.encode())
+    }
+}
+
+impl<'de> Deserialize<'de> for BookmarkId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Where a page of the bookmark list ends, for picking up after it: when the last bookmark shown was
+/// saved, and its ID to break ties between bookmarks saved in the same second.
+///
+/// Written in URLs as `<created_at>.<bookmark ID>`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BookmarkCursor {
+    /// UNIX timestamp
+    pub created_at: i64,
+    pub id: BookmarkId,
+}
+
+impl fmt::Display for BookmarkCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.created_at, self.id)
+    }
+}
+
+impl FromStr for BookmarkCursor {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (created_at, id) = s.split_once('.').with_context(|| format!("Invalid list cursor '{s}'"))?;
+        Ok(Self {
+            created_at: created_at.parse().with_context(|| format!("Invalid list cursor '{s}'"))?,
+            id: id.parse()?,
+        })
+    }
+}
+
+/// A page of bookmarks, and where the next one starts if there may be more.
+#[derive(Default)]
+pub struct BookmarkPage {
+    pub bookmarks: BookmarkList,
+    pub next: Option<BookmarkCursor>,
+}
+
 /// Represents a bookmark with its associated tags for display.
-#[derive(Clone, Debug)]
+#[allow(clippy::struct_excessive_bools)] // independent flags of a bookmark
+#[derive(Clone, Debug, Serialize)]
 pub struct BookmarkItem {
+    pub id: BookmarkId,
     pub url: String,
     pub title: String,
+    pub description: Option<String>,
+    /// Host of the URL without a leading `www.`, for display next to the title
+    pub domain: Option<String>,
     pub created_ago: String,
+    /// Language of the page's content, e.g. `de`, if it was detected when the page was fetched
+    pub language: Option<String>,
+    /// When a temporary bookmark will be deleted, e.g. "in 3 days"
+    pub expires_in: Option<String>,
+    /// Who wrote the page, if its metadata said when it was fetched
+    pub author: Option<String>,
+    /// Name of the publication or site, e.g. `The Guardian`, if its metadata said
+    pub site_name: Option<String>,
+    /// Moved out of the main list to the archive
+    pub is_archived: bool,
+    /// Saved to read later and not marked as read yet
+    pub is_unread: bool,
+    /// Whether a thumbnail of the page's preview image was saved, see [`crate::thumbnail`]
+    pub has_thumbnail: bool,
+    /// Whether a copy of the page was saved, see [`crate::snapshot`]
+    pub has_snapshot: bool,
+    /// Left out of everything shown to people other than its owner, see [`Visibility`]
+    pub is_private: bool,
     pub tags: Vec<TagInfo>,
+    /// Copy of the page in the Wayback Machine, if the link is broken; only set where it's shown
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub archived_copy: Option<String>,
+    /// Why the page couldn't be fetched, if it couldn't, see [`crate::fetch_retry`]; only set where
+    /// it's shown
+    #[serde(skip)]
+    pub fetch_failure: Option<FetchFailure>,
 }
 
 #[derive(Default)]
@@ -52,23 +286,79 @@ impl Deref for BookmarkList {
     }
 }
 
+impl BookmarkList {
+    /// Colors the bookmarks' tags with the colors the user picked.
+    #[must_use]
+    pub fn with_tag_colors(mut self, colors: &TagColors) -> Self {
+        for bookmark in &mut self.0 {
+            bookmark.color_tags(colors);
+        }
+        self
+    }
+
+    /// Offers the copies in the Wayback Machine of the bookmarks whose links are broken.
+    #[must_use]
+    pub fn with_archived_copies(mut self, copies: &ArchivedCopies) -> Self {
+        for bookmark in &mut self.0 {
+            bookmark.archived_copy = copies.get(&bookmark.id).cloned();
+        }
+        self
+    }
+
+    /// Shows why the pages of the bookmarks that couldn't be fetched couldn't be.
+    #[must_use]
+    pub fn with_fetch_failures(mut self, failures: &FetchFailures) -> Self {
+        for bookmark in &mut self.0 {
+            bookmark.fetch_failure = failures.get(&bookmark.id).cloned();
+        }
+        self
+    }
+}
+
+impl BookmarkItem {
+    /// Colors the bookmark's tags with the colors the user picked.
+    pub fn color_tags(&mut self, colors: &TagColors) {
+        for tag in &mut self.tags {
+            tag.color = colors.get(&tag.name).cloned();
+        }
+    }
+}
+
 /// Tag information for bookmarks.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct TagInfo {
     pub name: String,
+    /// The color the user picked for the tag, e.g. `#3b82f6`; only set where it's shown
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
 }
 
 impl From<&str> for TagInfo {
     fn from(value: &str) -> Self {
-        Self { name: value.to_string() }
+        Self {
+            name: value.to_string(),
+            color: None,
+        }
     }
 }
 
+#[allow(clippy::struct_excessive_bools)] // independent flags of a bookmark
 #[derive(FromRow)]
 struct BookmarkRecord {
+    bookmark_id: BookmarkId,
     url: String,
     title: String,
+    description: Option<String>,
     created_at: i64,
+    language: Option<String>,
+    expires_at: Option<i64>,
+    author: Option<String>,
+    site_name: Option<String>,
+    is_archived: bool,
+    is_unread: bool,
+    has_thumbnail: bool,
+    has_snapshot: bool,
+    is_private: bool,
     tags_string: Option<String>,
 }
 
@@ -82,12 +372,32 @@ impl From<Vec<BookmarkRecord>> for BookmarkList {
             let tags = tags.into_iter().map(TagInfo::from).collect();
 
             let created_ago = get_created_ago(bookmark.created_at);
+            let expires_in = bookmark
+                .expires_at
+                .map(|expires_at| get_expires_in(expires_at, chrono::Utc::now().timestamp()));
+
+            let domain = domain_of(&bookmark.url);
+            let description = bookmark.description.filter(|d| !d.trim().is_empty());
 
             result.push(BookmarkItem {
+                id: bookmark.bookmark_id,
+                domain,
                 url: bookmark.url,
                 title: bookmark.title,
+                description,
                 created_ago,
+                language: bookmark.language,
+                expires_in,
+                author: bookmark.author,
+                site_name: bookmark.site_name,
+                is_archived: bookmark.is_archived,
+                is_unread: bookmark.is_unread,
+                has_thumbnail: bookmark.has_thumbnail,
+                has_snapshot: bookmark.has_snapshot,
+                is_private: bookmark.is_private,
                 tags,
+                archived_copy: None,
+                fetch_failure: None,
             });
         }
         Self(result)
@@ -100,477 +410,824 @@ impl From<Vec<BookmarkRecord>> for BookmarkList {
 /// # Errors
 ///
 /// Returns an error if database query fails.
-pub async fn get_user_bookmarks(pool: &SqlitePool, user_id: Uuid, limit: i64, offset: i64) -> Result<BookmarkList> {
+pub async fn get_user_bookmarks(db: &Db, user_id: Uuid, sort: SortOrder, limit: i64, offset: i64) -> Result<BookmarkList> {
+    let _timer = QueryTimer::start("get_user_bookmarks");
+    let sort = sort.as_str();
     let bookmarks = sqlx::query_as!(
         BookmarkRecord,
         r#"
         select
+            bookmark_id as "bookmark_id!: BookmarkId",
             url,
             title,
+            description,
             created_at,
+            language,
+            expires_at,
+            author,
+            site_name,
+            is_archived as "is_archived!: bool",
+            is_unread as "is_unread!: bool",
+            has_thumbnail as "has_thumbnail!: bool",
+            has_snapshot as "has_snapshot!: bool",
+            visibility = 'private' as "is_private!: bool",
             tags_string
         from bookmark_with_tags
         where
             user_id = $1
-            and is_archived = 0
-        order by created_at desc
+            and is_archived = 0 and deleted_at is null
+        order by
+            case when $4 = 'title' then title end collate nocase,
+            case when $4 = 'oldest' then created_at end,
+            created_at desc
         limit $2 offset $3
         "#,
         user_id,
         limit,
-        offset
+        offset,
+        sort
     )
-    .fetch_all(pool)
+    .fetch_all(&db.reader)
     .await?;
 
     Ok(bookmarks.into())
 }
 
-/// Retrieves bookmarks filtered by tag.
+/// Retrieves the page of a user's unarchived bookmarks that follows `after` in the order they were
+/// saved, newest or oldest first, or the first page without a cursor.
+///
+/// Unlike [`get_user_bookmarks`], this picks up where the previous page ended instead of skipping
+/// an offset, so later pages are as quick as the first on big collections. Any other sort order is
+/// treated as newest first.
 ///
 /// # Errors
 ///
 /// Returns an error if database query fails.
-pub async fn get_user_bookmarks_by_tag(pool: &SqlitePool, user_id: Uuid, tag_name: &str, limit: i64, offset: i64) -> Result<BookmarkList> {
-    let bookmarks = sqlx::query_as!(
-        BookmarkRecord,
-        r#"
-        select
-            url,
-            title,
+pub async fn get_user_bookmarks_after(
+    db: &Db,
+    user_id: Uuid,
+    sort: SortOrder,
+    after: Option<BookmarkCursor>,
+    limit: i64,
+) -> Result<BookmarkPage> {
+    let _timer = QueryTimer::start("get_user_bookmarks_after");
+    let records = if sort == SortOrder::Oldest {
+        let (created_at, id) = after.map_or((i64::MIN, BookmarkId(Uuid::nil())), |after| (after.created_at, after.id));
+        sqlx::query_as!(
+            BookmarkRecord,
+            r#"
+            select
+                bookmark_id as "bookmark_id!: BookmarkId",
+                url,
+                title,
+                description,
+                created_at,
+                language,
+                expires_at,
+                author,
+                site_name,
+                is_archived as "is_archived!: bool",
+                is_unread as "is_unread!: bool",
+                has_thumbnail as "has_thumbnail!: bool",
+                has_snapshot as "has_snapshot!: bool",
+                visibility = 'private' as "is_private!: bool",
+                (select group_concat(t.name) from bookmark_tags bt join tags t on bt.tag_id = t.tag_id where bt.bookmark_id = b.bookmark_id) as "tags_string: String"
+            from bookmarks b
+            where
+                user_id = $1
+                and is_archived = 0 and deleted_at is null
+                and (created_at > $2 or (created_at = $2 and bookmark_id > $3))
+            order by created_at, bookmark_id
+            limit $4
+            "#,
+            user_id,
             created_at,
-            tags_string
-        from bookmark_with_tags
-        where
-            user_id = $1
-            and is_archived = 0
-            and bookmark_id in (
-                select bt.bookmark_id
-                from bookmark_tags bt
-                join tags t on bt.tag_id = t.tag_id
-                where t.name = $2
-            )
-        order by created_at desc
-        limit $3 offset $4
-        "#,
-        user_id,
-        tag_name,
-        limit,
-        offset
-    )
-    .fetch_all(pool)
-    .await?;
+            id,
+            limit
+        )
+        .fetch_all(&db.reader)
+        .await?
+    } else {
+        let (created_at, id) = after.map_or((i64::MAX, BookmarkId(Uuid::max())), |after| (after.created_at, after.id));
+        sqlx::query_as!(
+            BookmarkRecord,
+            r#"
+            select
+                bookmark_id as "bookmark_id!: BookmarkId",
+                url,
+                title,
+                description,
+                created_at,
+                language,
+                expires_at,
+                author,
+                site_name,
+                is_archived as "is_archived!: bool",
+                is_unread as "is_unread!: bool",
+                has_thumbnail as "has_thumbnail!: bool",
+                has_snapshot as "has_snapshot!: bool",
+                visibility = 'private' as "is_private!: bool",
+                (select group_concat(t.name) from bookmark_tags bt join tags t on bt.tag_id = t.tag_id where bt.bookmark_id = b.bookmark_id) as "tags_string: String"
+            from bookmarks b
+            where
+                user_id = $1
+                and is_archived = 0 and deleted_at is null
+                and (created_at < $2 or (created_at = $2 and bookmark_id < $3))
+            order by created_at desc, bookmark_id desc
+            limit $4
+            "#,
+            user_id,
+            created_at,
+            id,
+            limit
+        )
+        .fetch_all(&db.reader)
+        .await?
+    };
 
-    Ok(bookmarks.into())
+    let next = records
+        .last()
+        .filter(|_| i64::try_from(records.len()).unwrap_or(i64::MAX) >= limit)
+        .map(|last| BookmarkCursor {
+            created_at: last.created_at,
+            id: last.bookmark_id,
+        });
+    Ok(BookmarkPage {
+        bookmarks: records.into(),
+        next,
+    })
 }
 
 /// Searches bookmarks using advanced query parsing with OR/AND logic and phrases.
 ///
+/// Everything in the query is applied in one SQL query (see [`search_condition`]), so pages stay
+/// full however many terms, tags and filters there are.
+///
 /// # Errors
 ///
 /// Returns an error if database query fails.
 pub async fn search_user_bookmarks_advanced(
-    pool: &SqlitePool,
+    db: &Db,
     user_id: Uuid,
     query: &SearchQuery,
+    sort: SortOrder,
     limit: i64,
     offset: i64,
 ) -> Result<BookmarkList> {
-    if query.is_empty() {
-        return get_user_bookmarks(pool, user_id, limit, offset).await;
-    }
-
-    // Handle tag-only queries
-    if query.general_terms.is_empty() && !query.tag_filters.is_empty() {
-        return search_by_tags_only(pool, user_id, &query.tag_filters, limit, offset).await;
+    let _timer = QueryTimer::start("search_user_bookmarks_advanced");
+    // The plain list leaves the archive out, so it's only the same as the search without it, which
+    // is what [`count_user_bookmarks`] counts
+    if query.is_empty() && !query.include_archived {
+        return get_user_bookmarks(db, user_id, sort, limit, offset).await;
     }
 
-    // Handle general terms with optional tag filtering
-    let base_results = match (query.logic, query.general_terms.len()) {
-        (SearchLogic::Or, 1) => search_single_term(pool, user_id, &query.general_terms[0], limit, offset).await?,
-        (SearchLogic::Or, 2) => search_two_terms_or(pool, user_id, &query.general_terms, limit, offset).await?,
-        (SearchLogic::And, _) if query.general_terms.len() >= 2 => {
-            search_multiple_terms_and(pool, user_id, &query.general_terms, limit, offset).await?
-        }
-        // For more complex queries, fall back to the original search
-        _ => {
-            tracing::warn!("Complex search query not fully optimized, falling back to simple search");
-            let search_term = query
-                .general_terms
-                .iter()
-                .map(std::string::ToString::to_string)
-                .collect::<Vec<_>>()
-                .join(" ");
-            // TODO: this is bullshit
-            search_user_bookmarks(pool, user_id, &search_term, limit, offset).await?
-        }
-    };
+    let mut builder = search_query_builder(user_id, &search_condition(query), ListOrder::Sorted(sort), limit, offset);
+    let bookmarks = builder.build_query_as::<BookmarkRecord>().fetch_all(&db.reader).await?;
 
-    // Filter by tags if any are specified
-    if query.tag_filters.is_empty() {
-        Ok(base_results)
-    } else {
-        Ok(filter_bookmarks_by_tags(base_results, &query.tag_filters))
-    }
+    Ok(bookmarks.into())
 }
 
-/// Searches bookmarks by title, description, URL, or tags (legacy function).
+/// Searches bookmarks like [`search_user_bookmarks_advanced`], in a smart collection's own order:
+/// the bookmarks placed in it first (see [`db::collections::set_collection_order`]), then the
+/// rest, newest first.
 ///
 /// # Errors
 ///
 /// Returns an error if database query fails.
-pub async fn search_user_bookmarks(pool: &SqlitePool, user_id: Uuid, search_term: &str, limit: i64, offset: i64) -> Result<BookmarkList> {
-    let search_pattern = format!("%{search_term}%");
-
-    let bookmarks = sqlx::query_as!(
-        BookmarkRecord,
-        r#"
-        select
-            url,
-            title,
-            created_at,
-            tags_string
-        from bookmark_with_tags bwt
-        where user_id = $1
-        and (
-            title like $2 or
-            description like $3 or
-            url like $4 or
-            exists (
-                select 1 from bookmark_tags bt
-                join tags t on bt.tag_id = t.tag_id
-                where bt.bookmark_id = bwt.bookmark_id
-                and t.name like $5
-            )
-        )
-        order by created_at desc
-        limit $6 offset $7
-        "#,
+pub async fn search_collection_bookmarks(
+    db: &Db,
+    user_id: Uuid,
+    collection_id: Uuid,
+    query: &SearchQuery,
+    limit: i64,
+    offset: i64,
+) -> Result<BookmarkList> {
+    let _timer = QueryTimer::start("search_collection_bookmarks");
+    let mut builder = search_query_builder(
         user_id,
-        search_pattern,
-        search_pattern,
-        search_pattern,
-        search_pattern,
+        &search_condition(query),
+        ListOrder::Collection(collection_id),
         limit,
-        offset
-    )
-    .fetch_all(pool)
-    .await?;
+        offset,
+    );
+    let bookmarks = builder.build_query_as::<BookmarkRecord>().fetch_all(&db.reader).await?;
 
     Ok(bookmarks.into())
 }
 
-/// Searches for bookmarks with a single search term.
-async fn search_single_term(pool: &SqlitePool, user_id: Uuid, term: &SearchTerm, limit: i64, offset: i64) -> Result<BookmarkList> {
-    match term {
-        SearchTerm::Word(word) => search_single_word(pool, user_id, word, limit, offset).await,
-        SearchTerm::Phrase(phrase) => search_single_phrase(pool, user_id, phrase, limit, offset).await,
-    }
-}
-
-async fn search_single_word(pool: &SqlitePool, user_id: Uuid, word: &str, limit: i64, offset: i64) -> Result<BookmarkList> {
-    let search_pattern = format!("%{word}%");
-    let res = sqlx::query_as!(
-        BookmarkRecord,
-        r#"
-        select
-            url,
-            title,
-            created_at,
-            tags_string
-        from bookmark_with_tags bwt
-        where user_id = $1
-        and (
-            title like $2
-            or description like $3
-            or url like $4
-            or exists (
-                select 1 from bookmark_tags bt
-                join tags t on bt.tag_id = t.tag_id
-                where
-                    bt.bookmark_id = bwt.bookmark_id
-                    and t.name like $5
-            )
-        )
-        order by created_at desc
-        limit $6 offset $7
-        "#,
-        user_id,
-        search_pattern,
-        search_pattern,
-        search_pattern,
-        search_pattern,
-        limit,
-        offset
-    )
-    .fetch_all(pool)
-    .await?;
-
-    Ok(res.into())
+/// Finds the IDs of all of a user's bookmarks in a smart collection, whose rule is `query`, in the
+/// collection's own order, see [`search_collection_bookmarks`].
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn search_collection_bookmark_ids(db: &Db, user_id: Uuid, collection_id: Uuid, query: &SearchQuery) -> Result<Vec<BookmarkId>> {
+    let _timer = QueryTimer::start("search_collection_bookmark_ids");
+    let order = ListOrder::Collection(collection_id);
+    let mut builder = QueryBuilder::new("select b.bookmark_id from bookmarks b");
+    order.push_join(&mut builder);
+    builder.push(" where b.user_id = ");
+    builder.push_bind(user_id);
+    builder.push(" and b.deleted_at is null and ");
+    search_condition(query).push_to(&mut builder);
+    builder.push(" order by ");
+    builder.push(order.order_by_clause());
+    builder.push(", b.bookmark_id");
+    let ids = builder.build_query_scalar::<BookmarkId>().fetch_all(&db.reader).await?;
+
+    Ok(ids)
 }
 
-async fn search_single_phrase(pool: &SqlitePool, user_id: Uuid, phrase: &str, limit: i64, offset: i64) -> Result<BookmarkList> {
-    let res = sqlx::query_as!(
-        BookmarkRecord,
-        r#"
-        select
-            url,
-            title,
-            created_at,
-            tags_string
-        from bookmark_with_tags bwt
-        where
-            user_id = ?
-            and is_archived = 0
-            and (
-                instr(title, ?) > 0
-                or instr(description, ?) > 0
-                or instr(url, ?) > 0
-                or exists (
-                    select 1 from bookmark_tags bt
-                    join tags t on bt.tag_id = t.tag_id
-                    where
-                        bt.bookmark_id = bwt.bookmark_id
-                        and instr(t.name, ?) > 0
-                )
-            )
-        order by created_at desc
-        limit ? offset ?
-        "#,
+/// Searches bookmarks like [`search_user_bookmarks_advanced`], but only those with at least one of
+/// the tags (exact, lowercase names), e.g. for an API token limited to them.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn search_user_bookmarks_tagged(
+    db: &Db,
+    user_id: Uuid,
+    query: &SearchQuery,
+    tag_names: &[String],
+    sort: SortOrder,
+    limit: i64,
+    offset: i64,
+) -> Result<BookmarkList> {
+    let _timer = QueryTimer::start("search_user_bookmarks_tagged");
+    let mut builder = search_query_builder(
         user_id,
-        phrase,
-        phrase,
-        phrase,
-        phrase,
+        &tagged_search_condition(query, tag_names),
+        ListOrder::Sorted(sort),
         limit,
-        offset
-    )
-    .fetch_all(pool)
-    .await?;
-
-    Ok(res.into())
-}
-
-/// Searches for bookmarks with two terms using OR logic.
-async fn search_two_terms_or(pool: &SqlitePool, user_id: Uuid, terms: &[SearchTerm], limit: i64, offset: i64) -> Result<BookmarkList> {
-    // Build the WHERE conditions based on term types
-    let condition1 = match &terms[0] {
-        SearchTerm::Word(_) => "(b.title like ? or b.description like ? or b.url like ? or t_search.name like ?)",
-        SearchTerm::Phrase(_) => {
-            "(instr(b.title, ?) > 0 or instr(b.description, ?) > 0 or instr(b.url, ?) > 0 or instr(t_search.name, ?) > 0)"
-        }
-    };
-    let condition2 = match &terms[1] {
-        SearchTerm::Word(_) => "(b.title like ? or b.description like ? or b.url like ? or t_search.name like ?)",
-        SearchTerm::Phrase(_) => {
-            "(instr(b.title, ?) > 0 or instr(b.description, ?) > 0 or instr(b.url, ?) > 0 or instr(t_search.name, ?) > 0)"
-        }
-    };
-
-    let pattern1 = match &terms[0] {
-        SearchTerm::Word(word) => format!("%{word}%"),
-        SearchTerm::Phrase(phrase) => phrase.clone(),
-    };
-    let pattern2 = match &terms[1] {
-        SearchTerm::Word(word) => format!("%{word}%"),
-        SearchTerm::Phrase(phrase) => phrase.clone(),
-    };
-
-    let sql = format!(
-        r"
-        select
-            b.url,
-            b.title,
-            b.created_at,
-            GROUP_CONCAT(distinct t2.name) as tags_string
-        from bookmarks b
-        left join bookmark_tags bt_search on b.bookmark_id = bt_search.bookmark_id
-        left join tags t_search on bt_search.tag_id = t_search.tag_id
-        left join bookmark_tags bt on b.bookmark_id = bt.bookmark_id
-        left join tags t2 on bt.tag_id = t2.tag_id
-        where b.user_id = ? and b.is_archived = 0
-        and ({condition1} or {condition2})
-        group by b.bookmark_id, b.url, b.title, b.created_at
-        order by b.created_at desc
-        limit ? offset ?
-        "
+        offset,
     );
-
-    // TODO: querybuilder instead?
-    // TODO: query_as for all these
-    let bookmarks: Vec<BookmarkRecord> = sqlx::query_as(&sql)
-        .bind(user_id)
-        .bind(&pattern1)
-        .bind(&pattern1)
-        .bind(&pattern1)
-        .bind(&pattern1)
-        .bind(&pattern2)
-        .bind(&pattern2)
-        .bind(&pattern2)
-        .bind(&pattern2)
-        .bind(limit)
-        .bind(offset)
-        .fetch_all(pool)
-        .await?;
+    let bookmarks = builder.build_query_as::<BookmarkRecord>().fetch_all(&db.reader).await?;
 
     Ok(bookmarks.into())
 }
 
-/// Searches for bookmarks with multiple terms using AND logic.
-async fn search_multiple_terms_and(
-    pool: &SqlitePool,
+/// Finds the IDs of a user's bookmarks matching a search like [`search_user_bookmarks_advanced`],
+/// only those with at least one of the tags unless there are none. They're in the sort order, then
+/// by ID, so pages don't overlap or skip bookmarks saved at the same time.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn search_user_bookmark_ids(
+    db: &Db,
     user_id: Uuid,
-    terms: &[SearchTerm],
+    query: &SearchQuery,
+    tag_names: &[String],
+    sort: SortOrder,
     limit: i64,
     offset: i64,
-) -> Result<BookmarkList> {
-    if terms.is_empty() {
-        return get_user_bookmarks(pool, user_id, limit, offset).await;
+) -> Result<Vec<BookmarkId>> {
+    let _timer = QueryTimer::start("search_user_bookmark_ids");
+    let mut builder = QueryBuilder::new("select b.bookmark_id from bookmarks b where b.user_id = ");
+    builder.push_bind(user_id);
+    builder.push(" and b.deleted_at is null and ");
+    limited_search_condition(query, tag_names).push_to(&mut builder);
+    builder.push(" order by ");
+    builder.push(sort.order_by_clause());
+    builder.push(", b.bookmark_id limit ");
+    builder.push_bind(limit);
+    builder.push(" offset ");
+    builder.push_bind(offset);
+    let ids = builder.build_query_scalar::<BookmarkId>().fetch_all(&db.reader).await?;
+
+    Ok(ids)
+}
+
+/// Counts a user's bookmarks matching a search like [`search_user_bookmarks_advanced`], only those
+/// with at least one of the tags unless there are none, for paging through them.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn count_user_bookmarks(db: &Db, user_id: Uuid, query: &SearchQuery, tag_names: &[String]) -> Result<i64> {
+    let _timer = QueryTimer::start("count_user_bookmarks");
+    let mut builder = QueryBuilder::new("select count(*) from bookmarks b where b.user_id = ");
+    builder.push_bind(user_id);
+    builder.push(" and b.deleted_at is null and ");
+    limited_search_condition(query, tag_names).push_to(&mut builder);
+    let count = builder.build_query_scalar::<i64>().fetch_one(&db.reader).await?;
+
+    Ok(count)
+}
+
+/// The condition for bookmarks matching `query`, only those with at least one of the tags unless
+/// there are none.
+fn limited_search_condition(query: &SearchQuery, tag_names: &[String]) -> Condition {
+    if tag_names.is_empty() {
+        search_condition(query)
+    } else {
+        tagged_search_condition(query, tag_names)
     }
+}
+
+/// The condition for bookmarks matching `query` that have at least one of the tags (exact,
+/// lowercase names).
+fn tagged_search_condition(query: &SearchQuery, tag_names: &[String]) -> Condition {
+    let placeholders = vec!["?"; tag_names.len()].join(", ");
+    let has_any_tag = Condition::text(
+        format!(
+            "exists (select 1 from bookmark_tags bt join tags t on bt.tag_id = t.tag_id where bt.bookmark_id = b.bookmark_id and t.name in ({placeholders}))"
+        ),
+        tag_names.iter().cloned(),
+    );
+    Condition::All(vec![search_condition(query), has_any_tag])
+}
 
-    // Build conditions for each term
-    let mut bookmark_conditions = Vec::new();
-    let mut tag_conditions = Vec::new();
-    let mut patterns = Vec::new();
+/// How a page of search results is ordered.
+#[derive(Clone, Copy, Debug)]
+enum ListOrder {
+    Sorted(SortOrder),
+    /// A smart collection's own order, see [`search_collection_bookmarks`]
+    Collection(Uuid),
+}
 
-    for term in terms {
-        let (bookmark_cond, tag_cond, pattern) = match term {
-            SearchTerm::Word(word) => (
-                "(b.title like ? or b.description like ? or b.url like ?)",
-                "t.name like ?",
-                format!("%{word}%"),
-            ),
-            SearchTerm::Phrase(phrase) => (
-                "(instr(b.title, ?) > 0 or instr(b.description, ?) > 0 or instr(b.url, ?) > 0)",
-                "instr(t.name, ?) > 0",
-                phrase.clone(),
-            ),
-        };
-        bookmark_conditions.push(bookmark_cond);
-        tag_conditions.push(tag_cond);
-        patterns.push(pattern);
+impl ListOrder {
+    /// Joins what's needed to order by onto bookmarks (aliased as `b`).
+    fn push_join(self, builder: &mut QueryBuilder<'static, Sqlite>) {
+        if let ListOrder::Collection(collection_id) = self {
+            builder.push(" left join collection_positions cp on cp.bookmark_id = b.bookmark_id and cp.collection_id = ");
+            builder.push_bind(collection_id);
+        }
     }
 
-    // Build the AND clauses using EXISTS subqueries
-    let mut and_clauses = Vec::new();
-    for i in 0..terms.len() {
-        let clause = format!(
-            "({} or exists (select 1 from bookmark_tags bt{} join tags t on bt{}.tag_id = t.tag_id where bt{}.bookmark_id = b.bookmark_id and {}))",
-            bookmark_conditions[i], i, i, i, tag_conditions[i]
-        );
-        and_clauses.push(clause);
+    fn order_by_clause(self) -> &'static str {
+        match self {
+            ListOrder::Sorted(sort) => sort.order_by_clause(),
+            ListOrder::Collection(_) => "cp.position is null, cp.position, b.created_at desc",
+        }
     }
+}
 
-    let sql = format!(
+/// The query for a page of a user's bookmarks matching `condition`.
+fn search_query_builder(user_id: Uuid, condition: &Condition, order: ListOrder, limit: i64, offset: i64) -> QueryBuilder<'static, Sqlite> {
+    let mut builder = QueryBuilder::new(
         r"
         select
+            b.bookmark_id,
             b.url,
             b.title,
+            b.description,
             b.created_at,
+            b.language,
+            b.expires_at,
+            b.author,
+            b.site_name,
+            b.is_archived,
+            b.is_unread,
+            b.has_thumbnail,
+            b.has_snapshot,
+            b.visibility = 'private' as is_private,
             GROUP_CONCAT(distinct t_result.name) as tags_string
         from bookmarks b
-        left join bookmark_tags bt on b.bookmark_id = bt.bookmark_id
-        left join tags t_result on bt.tag_id = t_result.tag_id
-        where b.user_id = ? and b.is_archived = 0
-        and {}
-        group by b.bookmark_id, b.url, b.title, b.created_at
-        order by b.created_at desc
-        limit ? offset ?
-        ",
-        and_clauses.join(" and ")
+        left join bookmark_tags bt_result on b.bookmark_id = bt_result.bookmark_id
+        left join tags t_result on bt_result.tag_id = t_result.tag_id",
     );
+    order.push_join(&mut builder);
+    builder.push(" where b.user_id = ");
+    builder.push_bind(user_id);
+    builder.push(" and b.deleted_at is null and ");
+    condition.push_to(&mut builder);
+    builder.push(
+        r"
+        group by b.bookmark_id, b.url, b.title, b.description, b.created_at, b.language, b.expires_at, b.author, b.site_name, b.is_archived, b.is_unread, b.has_thumbnail, b.has_snapshot, b.visibility
+        order by ",
+    );
+    builder.push(order.order_by_clause());
+    builder.push(" limit ");
+    builder.push_bind(limit);
+    builder.push(" offset ");
+    builder.push_bind(offset);
+    builder
+}
+
+/// A value bound to a search query.
+#[derive(Clone, Debug, PartialEq)]
+enum SqlValue {
+    Text(String),
+    Integer(i64),
+}
 
-    let mut query_builder = sqlx::query_as(&sql).bind(user_id);
+/// A condition on bookmarks (aliased as `b`) in a search, built up from the parts of the query and
+/// then pushed to a [`QueryBuilder`] with its values bound.
+#[derive(Clone, Debug, PartialEq)]
+enum Condition {
+    /// SQL with a `?` for each of the values, in order
+    Sql(String, Vec<SqlValue>),
+    /// All of the conditions; true if there are none
+    All(Vec<Condition>),
+    /// Any of the conditions; false if there are none
+    Any(Vec<Condition>),
+}
 
-    // Bind parameters for each term (4 binds per term: 3 for bookmark fields + 1 for tag)
-    for pattern in &patterns {
-        query_builder = query_builder
-            .bind(pattern) // bookmark title
-            .bind(pattern) // bookmark description
-            .bind(pattern) // bookmark url
-            .bind(pattern); // tag name
+impl Condition {
+    fn sql(sql: impl Into<String>) -> Self {
+        Self::Sql(sql.into(), Vec::new())
     }
 
-    query_builder = query_builder.bind(limit).bind(offset);
+    fn text(sql: impl Into<String>, values: impl IntoIterator<Item = String>) -> Self {
+        Self::Sql(sql.into(), values.into_iter().map(SqlValue::Text).collect())
+    }
 
-    let bookmarks: Vec<BookmarkRecord> = query_builder.fetch_all(pool).await?;
+    /// Whether a column of bookmarks contains a term: a word regardless of (ASCII) case, a phrase
+    /// exactly. Fuzzy words only need their letters in order.
+    fn contains(column: &str, term: &SearchTerm, fuzzy: bool) -> Self {
+        let (sql, value) = contains_sql(column, term, fuzzy);
+        Self::text(sql, [value])
+    }
 
-    Ok(bookmarks.into())
-}
+    /// Whether a bookmark has a tag with exactly this name, regardless of case, so `#rust` doesn't
+    /// also find bookmarks tagged `trust` or `rust-async`.
+    fn has_tag(name: &str) -> Self {
+        Self::text(
+            "exists (select 1 from bookmark_tags bt join tags t on bt.tag_id = t.tag_id where bt.bookmark_id = b.bookmark_id and t.name = ?)",
+            [name.to_lowercase()],
+        )
+    }
 
-/// Searches bookmarks by tags only (no general search terms).
-pub async fn search_by_tags_only(pool: &SqlitePool, user_id: Uuid, tag_names: &[String], limit: i64, offset: i64) -> Result<BookmarkList> {
-    if tag_names.is_empty() {
-        return get_user_bookmarks(pool, user_id, limit, offset).await;
+    /// Whether a bookmark has a tag whose name contains a term.
+    fn has_tag_containing(term: &SearchTerm, fuzzy: bool) -> Self {
+        let (tag_condition, value) = contains_sql("t.name", term, fuzzy);
+        Self::text(
+            format!(
+                "exists (select 1 from bookmark_tags bt join tags t on bt.tag_id = t.tag_id where bt.bookmark_id = b.bookmark_id and {tag_condition})"
+            ),
+            [value],
+        )
     }
 
-    // For single tag, use the existing optimized function
-    if tag_names.len() == 1 {
-        return get_user_bookmarks_by_tag(pool, user_id, &tag_names[0], limit, offset).await;
+    /// Whether the text of a bookmark's saved page contains a term, matched like
+    /// [`Condition::contains`] but through the text's trigram index, which only serves `like` and
+    /// `glob`: phrases are matched exactly with `glob` rather than `instr`.
+    fn page_text_contains(term: &SearchTerm, fuzzy: bool) -> Self {
+        let (operator, pattern) = match term {
+            SearchTerm::Word(_) => ("like", contains_sql("content", term, fuzzy).1),
+            SearchTerm::Phrase(phrase) => ("glob", format!("*{}*", glob_escape(phrase))),
+        };
+        Self::text(
+            format!(
+                "b.bookmark_id in (select pt.bookmark_id from bookmark_page_text pt where pt.rowid in (select rowid from bookmark_page_text_index where content {operator} ?))"
+            ),
+            [pattern],
+        )
     }
 
-    // For multiple tags, find bookmarks that have ALL specified tags (using LIKE for fuzzy matching)
-    let like_conditions = tag_names.iter().map(|_| "t.name like ?").collect::<Vec<_>>().join(" OR ");
-    let sql = format!(
-        r"
-        select
-            b.url,
-            b.title,
-            b.created_at,
-            GROUP_CONCAT(distinct t_result.name) as tags_string
-        from bookmarks b
-        left join bookmark_tags bt_result on b.bookmark_id = bt_result.bookmark_id
-        left join tags t_result on bt_result.tag_id = t_result.tag_id
-        where b.user_id = ? and b.is_archived = 0
-        and b.bookmark_id in (
-            select bt.bookmark_id
-            from bookmark_tags bt
-            join tags t on bt.tag_id = t.tag_id
-            where ({like_conditions})
-            group by bt.bookmark_id
-            having count(distinct t.tag_id) >= ?
-        )
-        group by b.bookmark_id, b.url, b.title, b.created_at
-        order by b.created_at desc
-        limit ? offset ?
-        "
+    /// Writes the condition to the query, binding its values.
+    fn push_to(&self, builder: &mut QueryBuilder<'_, Sqlite>) {
+        match self {
+            Self::Sql(sql, values) => {
+                debug_assert_eq!(sql.matches('?').count(), values.len(), "{sql}");
+                let mut parts = sql.split('?');
+                builder.push(parts.next().unwrap_or_default());
+                for (value, part) in values.iter().zip(parts) {
+                    match value {
+                        SqlValue::Text(text) => builder.push_bind(text.clone()),
+                        SqlValue::Integer(integer) => builder.push_bind(*integer),
+                    };
+                    builder.push(part);
+                }
+            }
+            Self::All(conditions) | Self::Any(conditions) => {
+                let (joiner, empty) = if matches!(self, Self::All(_)) {
+                    (" and ", "1")
+                } else {
+                    (" or ", "0")
+                };
+                if conditions.is_empty() {
+                    builder.push(empty);
+                    return;
+                }
+                builder.push("(");
+                for (i, condition) in conditions.iter().enumerate() {
+                    if i > 0 {
+                        builder.push(joiner);
+                    }
+                    condition.push_to(builder);
+                }
+                builder.push(")");
+            }
+        }
+    }
+}
+
+/// The condition for bookmarks matching a search.
+///
+/// Matches the host exactly or with a `www.` prefix, so `site:github.com` doesn't match `gist.github.com`.
+/// A bookmark must come from any of the sites, any of the sources, any of the authors and any of the
+/// site names (matching part of the name), be published and saved in all of the periods, and be in all
+/// of the states. Tags must all be present, matching the whole name, and terms, including those
+/// restricted to one field, are combined with the query's AND/OR logic. Only archived bookmarks are
+/// searched for `is:archived`, and only the others otherwise unless the archive is included.
+fn search_condition(query: &SearchQuery) -> Condition {
+    // Either the archive or the main list, and all of the other states
+    let mut all = state_conditions(&query.state_filters, query.include_archived);
+
+    // All of the things it must have
+    all.extend(query.has_filters.iter().map(|has| Condition::sql(has_condition(*has))));
+
+    // Any of the sites
+    if !query.site_filters.is_empty() {
+        all.push(Condition::Any(
+            query
+                .site_filters
+                .iter()
+                .map(|site| {
+                    Condition::text(
+                        "(b.url like ? or b.url like ? or b.url like ? or b.url like ?)",
+                        [
+                            format!("%://{site}"),
+                            format!("%://{site}/%"),
+                            format!("%://www.{site}"),
+                            format!("%://www.{site}/%"),
+                        ],
+                    )
+                })
+                .collect(),
+        ));
+    }
+
+    // Any of the sources, and any of the languages
+    for (column, values) in [("b.source", &query.source_filters), ("b.language", &query.language_filters)] {
+        if !values.is_empty() {
+            let placeholders = vec!["?"; values.len()].join(", ");
+            all.push(Condition::text(format!("{column} in ({placeholders})"), values.iter().cloned()));
+        }
+    }
+
+    // Any of the authors, and any of the site names
+    for (column, names) in [("b.author", &query.author_filters), ("b.site_name", &query.site_name_filters)] {
+        if !names.is_empty() {
+            all.push(Condition::Any(
+                names
+                    .iter()
+                    .map(|name| Condition::contains(column, &SearchTerm::Word(name.clone()), false))
+                    .collect(),
+            ));
+        }
+    }
+
+    // All of the publication periods, and all of the periods saved in
+    all.extend(
+        query
+            .published_filters
+            .iter()
+            .flat_map(|published| date_conditions("b.published_at", published)),
+    );
+    all.extend(
+        query
+            .created_filters
+            .iter()
+            .flat_map(|created| date_conditions("b.created_at", created)),
     );
 
-    let mut query = sqlx::query_as(&sql).bind(user_id);
-    for tag_name in tag_names {
-        let tag_pattern = format!("%{tag_name}%");
-        query = query.bind(tag_pattern);
+    // All of the tags
+    all.extend(query.tag_filters.iter().map(|tag_name| Condition::has_tag(tag_name)));
+
+    // Terms, joined by the query's logic
+    let terms = query
+        .general_terms
+        .iter()
+        .map(|term| {
+            let mut any = vec![
+                Condition::contains("b.title", term, query.fuzzy),
+                Condition::contains("b.description", term, query.fuzzy),
+                Condition::contains("b.url", term, query.fuzzy),
+                Condition::has_tag_containing(term, query.fuzzy),
+            ];
+            if query.page_text {
+                any.push(Condition::page_text_contains(term, query.fuzzy));
+            }
+            Condition::Any(any)
+        })
+        .chain(
+            query
+                .field_terms
+                .iter()
+                .map(|FieldTerm { field, term }| field_condition(*field, term, query.fuzzy)),
+        )
+        .collect::<Vec<_>>();
+    if !terms.is_empty() {
+        all.push(match query.logic {
+            SearchLogic::And => Condition::All(terms),
+            SearchLogic::Or => Condition::Any(terms),
+        });
     }
-    query = query.bind(i64::try_from(tag_names.len()).unwrap_or(0)).bind(limit).bind(offset);
 
-    let bookmarks: Vec<BookmarkRecord> = query.fetch_all(pool).await?;
+    Condition::All(all)
+}
 
-    Ok(bookmarks.into())
+/// The SQL for a column containing a term, and the value to bind. Fuzzy words match with anything
+/// between their letters, e.g. `%r%s%t%` for `rst`; phrases are always exact.
+fn contains_sql(column: &str, term: &SearchTerm, fuzzy: bool) -> (String, String) {
+    match term {
+        SearchTerm::Word(word) if fuzzy => {
+            let pattern = word.chars().fold(String::from("%"), |mut pattern, c| {
+                pattern.push(c);
+                pattern.push('%');
+                pattern
+            });
+            (format!("{column} like ?"), pattern)
+        }
+        SearchTerm::Word(word) => (format!("{column} like ?"), format!("%{word}%")),
+        SearchTerm::Phrase(phrase) => (format!("instr({column}, ?) > 0"), phrase.clone()),
+    }
+}
+
+/// Escapes the characters `glob` treats specially, so the text only matches itself.
+fn glob_escape(text: &str) -> String {
+    text.chars().fold(String::with_capacity(text.len()), |mut escaped, c| {
+        if matches!(c, '*' | '?' | '[') {
+            escaped.push('[');
+            escaped.push(c);
+            escaped.push(']');
+        } else {
+            escaped.push(c);
+        }
+        escaped
+    })
 }
 
-/// Filters bookmark results to only include those with all specified tags (fuzzy matching).
-fn filter_bookmarks_by_tags(bookmarks: BookmarkList, required_tags: &[String]) -> BookmarkList {
-    if required_tags.is_empty() {
-        return bookmarks;
+/// The condition for bookmarks (aliased as `b`) whose field contains a term.
+fn field_condition(field: SearchField, term: &SearchTerm, fuzzy: bool) -> Condition {
+    match field {
+        SearchField::Title => Condition::contains("b.title", term, fuzzy),
+        SearchField::Url => Condition::contains("b.url", term, fuzzy),
+        SearchField::Description => Condition::contains("b.description", term, fuzzy),
+        SearchField::Content => Condition::page_text_contains(term, fuzzy),
     }
+}
 
-    bookmarks
-        .into_iter()
-        .filter(|bookmark| {
-            // Check if bookmark has all required tags using fuzzy LIKE matching
-            required_tags.iter().all(|required_tag| {
-                bookmark
-                    .tags
-                    .iter()
-                    .any(|tag| tag.name.to_lowercase().contains(&required_tag.to_lowercase()))
-            })
-        })
-        .collect::<Vec<_>>()
-        .into()
+/// The SQL condition for bookmarks (aliased as `b`) in the given state.
+fn state_condition(state: StateFilter) -> &'static str {
+    match state {
+        StateFilter::Untagged => "not exists (select 1 from bookmark_tags bt where bt.bookmark_id = b.bookmark_id)",
+        StateFilter::Undescribed => "(b.description is null or trim(b.description) = '')",
+        StateFilter::Duplicate => {
+            "exists (
+                select 1 from bookmarks d
+                where d.user_id = b.user_id and d.bookmark_id != b.bookmark_id and d.is_archived = 0 and d.deleted_at is null
+                and rtrim(substr(d.url, instr(d.url, '://') + 3), '/') = rtrim(substr(b.url, instr(b.url, '://') + 3), '/')
+            )"
+        }
+        // Within the next 7 days
+        StateFilter::Expiring => "b.expires_at < unixepoch() + 7 * 86400",
+        StateFilter::Archived => "b.is_archived",
+        StateFilter::Unread => "b.is_unread",
+        StateFilter::Read => "not b.is_unread",
+        StateFilter::Broken => "exists (select 1 from bookmark_link_checks lc where lc.bookmark_id = b.bookmark_id and lc.is_broken)",
+        StateFilter::Public => "b.visibility = 'public'",
+        StateFilter::Private => "b.visibility = 'private'",
+    }
+}
+
+/// The SQL condition for bookmarks (aliased as `b`) having something.
+fn has_condition(has: HasFilter) -> &'static str {
+    match has {
+        HasFilter::Description => "(b.description is not null and trim(b.description) != '')",
+        HasFilter::Snapshot => "b.has_snapshot",
+        HasFilter::Thumbnail => "b.has_thumbnail",
+        HasFilter::Text => "exists (select 1 from bookmark_page_text pt where pt.bookmark_id = b.bookmark_id)",
+    }
+}
+
+/// The conditions for bookmarks in all of the given states: only archived bookmarks if the archive
+/// is one of them, all bookmarks if the archive is included, and only the others otherwise.
+fn state_conditions(states: &[StateFilter], include_archived: bool) -> Vec<Condition> {
+    let archived = state_condition(StateFilter::Archived);
+    let list = if states.contains(&StateFilter::Archived) {
+        Some(archived.to_string())
+    } else if include_archived {
+        None
+    } else {
+        Some(format!("not {archived}"))
+    };
+    list.into_iter()
+        .map(Condition::sql)
+        .chain(
+            states
+                .iter()
+                .filter(|state| **state != StateFilter::Archived)
+                .map(|state| Condition::sql(state_condition(*state))),
+        )
+        .collect()
+}
+
+/// The conditions for a timestamp column of bookmarks being in the given period.
+fn date_conditions(column: &str, range: &DateRange) -> Vec<Condition> {
+    let start = range
+        .start
+        .map(|start| Condition::Sql(format!("{column} >= ?"), vec![SqlValue::Integer(start)]));
+    let end = range
+        .end
+        .map(|end| Condition::Sql(format!("{column} < ?"), vec![SqlValue::Integer(end)]));
+    start.into_iter().chain(end).collect()
+}
+
+/// Counts of a user's bookmarks that could use some tidying up.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CollectionHealth {
+    pub total: i64,
+    pub untagged: i64,
+    pub undescribed: i64,
+    /// Bookmarks sharing their URL with another one, so a pair counts as two
+    pub duplicates: i64,
+    /// Temporary bookmarks that will be deleted within a week
+    pub expiring: i64,
+    /// Bookmarks whose links were found broken
+    pub broken: i64,
+}
+
+/// Counts a user's bookmarks in each of the states that can be tidied up, matching `is:` searches.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn get_collection_health(db: &Db, user_id: Uuid) -> Result<CollectionHealth> {
+    let _timer = QueryTimer::start("get_collection_health");
+    let sql = format!(
+        r"
+        select
+            count(*),
+            coalesce(sum({}), 0),
+            coalesce(sum({}), 0),
+            coalesce(sum({}), 0),
+            coalesce(sum({}), 0),
+            coalesce(sum({}), 0)
+        from bookmarks b
+        where b.user_id = ? and b.is_archived = 0 and b.deleted_at is null
+        ",
+        state_condition(StateFilter::Untagged),
+        state_condition(StateFilter::Undescribed),
+        state_condition(StateFilter::Duplicate),
+        state_condition(StateFilter::Expiring),
+        state_condition(StateFilter::Broken),
+    );
+    let (total, untagged, undescribed, duplicates, expiring, broken): (i64, i64, i64, i64, i64, i64) =
+        sqlx::query_as(&sql).bind(user_id).fetch_one(&db.reader).await?;
+
+    Ok(CollectionHealth {
+        total,
+        untagged,
+        undescribed,
+        duplicates,
+        expiring,
+        broken,
+    })
+}
+
+/// The part of a URL duplicates are found by, matching `is:duplicate` searches: the URL without its
+/// scheme or trailing slashes.
+#[must_use]
+pub fn duplicate_key(url: &str) -> &str {
+    url.split_once("://").map_or(url, |(_, rest)| rest).trim_end_matches('/')
+}
+
+/// Retrieves the [`duplicate_key`] of every bookmark a user has, archived ones included, to find
+/// out which URLs are already saved.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn get_duplicate_keys(db: &Db, user_id: Uuid) -> Result<HashSet<String>> {
+    let _timer = QueryTimer::start("get_duplicate_keys");
+    let urls = sqlx::query_scalar!("select url from bookmarks where user_id = ? and deleted_at is null", user_id)
+        .fetch_all(&db.reader)
+        .await?;
+
+    Ok(urls.iter().map(|url| duplicate_key(url).to_string()).collect())
+}
+
+/// Finds the user's most recently saved bookmark with a URL, archived or not, going by the
+/// [`duplicate_key`] so the scheme and trailing slashes don't matter.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn find_bookmark_by_url(db: &Db, user_id: Uuid, url: &str) -> Result<Option<BookmarkId>> {
+    let _timer = QueryTimer::start("find_bookmark_by_url");
+    let key = duplicate_key(url);
+    let bookmark_id = sqlx::query_scalar!(
+        r#"
+        select bookmark_id as "bookmark_id!: BookmarkId" from bookmarks
+        where user_id = ? and deleted_at is null and rtrim(substr(url, instr(url, '://') + 3), '/') = ?
+        order by created_at desc
+        limit 1
+        "#,
+        user_id,
+        key
+    )
+    .fetch_optional(&db.reader)
+    .await?;
+
+    Ok(bookmark_id)
 }
 
 /// Formats a Unix timestamp into a human-readable "time ago" string.
-fn get_created_ago(timestamp: i64) -> String {
+pub(crate) fn get_created_ago(timestamp: i64) -> String {
     use chrono::{DateTime, Utc};
 
     let dt = DateTime::from_timestamp(timestamp, 0).unwrap_or_else(Utc::now);
@@ -615,47 +1272,380 @@ fn get_created_ago(timestamp: i64) -> String {
     "now".to_string()
 }
 
-/// Creates a new bookmark for a user.
+/// Formats how long until a temporary bookmark expires (UNIX timestamps), e.g. "in 3 days".
+pub(crate) fn get_expires_in(expires_at: i64, now: i64) -> String {
+    let days = (expires_at - now) / 86400;
+    match days {
+        ..=0 => "within a day".to_string(),
+        1 => "in 1 day".to_string(),
+        days => format!("in {days} days"),
+    }
+}
+
+/// Retrieves a user's bookmarks saved within any of the `(start, end)` periods (UNIX timestamps,
+/// end exclusive), newest first, along with when each was saved. Archived bookmarks are left out.
 ///
 /// # Errors
 ///
-/// Returns an error if database operations fail.
-pub async fn create_bookmark(
-    pool: &SqlitePool,
+/// Returns an error if database query fails.
+pub async fn get_saved_between(db: &Db, user_id: Uuid, periods: &[(i64, i64)], limit: i64) -> Result<Vec<(i64, BookmarkItem)>> {
+    let _timer = QueryTimer::start("get_saved_between");
+    if periods.is_empty() {
+        return Ok(Vec::new());
+    }
+    let conditions = vec!["(created_at >= ? and created_at < ?)"; periods.len()].join(" or ");
+    let sql = format!(
+        "
+        select bookmark_id, url, title, description, created_at, language, expires_at, author, site_name, is_archived, is_unread, has_thumbnail, has_snapshot,
+            visibility = 'private' as is_private, tags_string
+        from bookmark_with_tags
+        where user_id = ? and is_archived = 0 and deleted_at is null and ({conditions})
+        order by created_at desc
+        limit ?
+        "
+    );
+
+    let mut query = sqlx::query_as(&sql).bind(user_id);
+    for (start, end) in periods {
+        query = query.bind(start).bind(end);
+    }
+    let records: Vec<BookmarkRecord> = query.bind(limit).fetch_all(&db.reader).await?;
+
+    let created_at: Vec<i64> = records.iter().map(|record| record.created_at).collect();
+    Ok(created_at.into_iter().zip(BookmarkList::from(records)).collect())
+}
+
+/// A bookmark with everything about it that's worth keeping in an export.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExportedBookmark {
+    pub url: String,
+    pub title: String,
+    pub description: Option<String>,
+    /// Tag names, sorted
+    pub tags: Vec<String>,
+    /// UNIX timestamp
+    pub created_at: i64,
+    /// UNIX timestamp
+    pub updated_at: i64,
+    pub language: Option<String>,
+    pub is_archived: bool,
+}
+
+/// Retrieves all of a user's bookmarks, archived ones included, oldest first.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn get_all_bookmarks(db: &Db, user_id: Uuid) -> Result<Vec<ExportedBookmark>> {
+    let _timer = QueryTimer::start("get_all_bookmarks");
+    let records = sqlx::query!(
+        r#"
+        select
+            url as "url!",
+            title as "title!",
+            description,
+            created_at as "created_at!",
+            updated_at as "updated_at!",
+            language,
+            is_archived as "is_archived!: bool",
+            tags_string
+        from bookmark_with_tags
+        where user_id = ? and deleted_at is null
+        order by created_at, bookmark_id
+        "#,
+        user_id
+    )
+    .fetch_all(&db.reader)
+    .await?;
+
+    Ok(records
+        .into_iter()
+        .map(|record| ExportedBookmark {
+            url: record.url,
+            title: record.title,
+            description: record.description.filter(|d| !d.trim().is_empty()),
+            tags: exported_tags(record.tags_string),
+            created_at: record.created_at,
+            updated_at: record.updated_at,
+            language: record.language,
+            is_archived: record.is_archived,
+        })
+        .collect())
+}
+
+/// Where a page of [`get_export_page`] left off: the last bookmark's creation time and ID.
+pub type ExportCursor = (i64, Uuid);
+
+/// Retrieves up to `limit` of a user's bookmarks, archived ones included, oldest first, starting
+/// after `after`, along with where to continue from. Like [`get_all_bookmarks`], but a page at a
+/// time, so exports can be streamed.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn get_export_page(
+    db: &Db,
     user_id: Uuid,
-    url: &str,
-    title: &str,
-    description: Option<&str>,
-    tag_names: &[String],
-) -> Result<Vec<u8>> {
+    after: Option<ExportCursor>,
+    limit: i64,
+) -> Result<(Vec<ExportedBookmark>, Option<ExportCursor>)> {
+    let _timer = QueryTimer::start("get_export_page");
+    let (after_created_at, after_id) = after.unzip();
+    let records = sqlx::query!(
+        r#"
+        select
+            bookmark_id as "bookmark_id!: Uuid",
+            url as "url!",
+            title as "title!",
+            description,
+            created_at as "created_at!",
+            updated_at as "updated_at!",
+            language,
+            is_archived as "is_archived!: bool",
+            tags_string
+        from bookmark_with_tags
+        where user_id = ? and deleted_at is null and (?2 is null or (created_at, bookmark_id) > (?2, ?3))
+        order by created_at, bookmark_id
+        limit ?4
+        "#,
+        user_id,
+        after_created_at,
+        after_id,
+        limit
+    )
+    .fetch_all(&db.reader)
+    .await?;
+
+    let cursor = records.last().map(|record| (record.created_at, record.bookmark_id));
+    let bookmarks = records
+        .into_iter()
+        .map(|record| ExportedBookmark {
+            url: record.url,
+            title: record.title,
+            description: record.description.filter(|d| !d.trim().is_empty()),
+            tags: exported_tags(record.tags_string),
+            created_at: record.created_at,
+            updated_at: record.updated_at,
+            language: record.language,
+            is_archived: record.is_archived,
+        })
+        .collect();
+
+    Ok((bookmarks, cursor))
+}
+
+/// A row of [`search_export_page`].
+#[derive(FromRow)]
+struct ExportRecord {
+    bookmark_id: Uuid,
+    url: String,
+    title: String,
+    description: Option<String>,
+    created_at: i64,
+    updated_at: i64,
+    language: Option<String>,
+    is_archived: bool,
+    tags_string: Option<String>,
+}
+
+/// Retrieves a page of a user's bookmarks matching a search, like [`get_export_page`] does for all
+/// of them, e.g. to export a smart collection.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn search_export_page(
+    db: &Db,
+    user_id: Uuid,
+    query: &SearchQuery,
+    after: Option<ExportCursor>,
+    limit: i64,
+) -> Result<(Vec<ExportedBookmark>, Option<ExportCursor>)> {
+    let _timer = QueryTimer::start("search_export_page");
+    let mut builder = QueryBuilder::new(
+        r"
+        select
+            b.bookmark_id,
+            b.url,
+            b.title,
+            b.description,
+            b.created_at,
+            b.updated_at,
+            b.language,
+            b.is_archived,
+            GROUP_CONCAT(distinct t_result.name) as tags_string
+        from bookmarks b
+        left join bookmark_tags bt_result on b.bookmark_id = bt_result.bookmark_id
+        left join tags t_result on bt_result.tag_id = t_result.tag_id
+        where b.user_id = ",
+    );
+    builder.push_bind(user_id);
+    builder.push(" and b.deleted_at is null and ");
+    search_condition(query).push_to(&mut builder);
+    if let Some((after_created_at, after_id)) = after {
+        builder.push(" and (b.created_at, b.bookmark_id) > (");
+        builder.push_bind(after_created_at);
+        builder.push(", ");
+        builder.push_bind(after_id);
+        builder.push(")");
+    }
+    builder.push(" group by b.bookmark_id order by b.created_at, b.bookmark_id limit ");
+    builder.push_bind(limit);
+    let records = builder.build_query_as::<ExportRecord>().fetch_all(&db.reader).await?;
+
+    let cursor = records.last().map(|record| (record.created_at, record.bookmark_id));
+    let bookmarks = records
+        .into_iter()
+        .map(|record| ExportedBookmark {
+            url: record.url,
+            title: record.title,
+            description: record.description.filter(|d| !d.trim().is_empty()),
+            tags: exported_tags(record.tags_string),
+            created_at: record.created_at,
+            updated_at: record.updated_at,
+            language: record.language,
+            is_archived: record.is_archived,
+        })
+        .collect();
+
+    Ok((bookmarks, cursor))
+}
+
+/// Sorted tag names from the view's comma-separated `tags_string`.
+fn exported_tags(tags_string: Option<String>) -> Vec<String> {
+    let mut tags: Vec<String> = tags_string
+        .unwrap_or_default()
+        .split(',')
+        .filter(|t| !t.is_empty())
+        .map(str::to_string)
+        .collect();
+    tags.sort_unstable();
+    tags
+}
+
+/// Creates a new bookmark for a user, recording where it was saved from.
+///
+/// # Errors
+///
+/// Returns an error if database operations fail.
+pub async fn create_bookmark(db: &Db, user_id: Uuid, bookmark: &NewBookmark, origin: &BookmarkOrigin) -> Result<BookmarkId> {
+    let _timer = QueryTimer::start("create_bookmark");
     // Begin transaction to ensure atomicity
-    let mut tx = pool.begin().await?;
+    let mut tx = db.writer.begin().await?;
+
+    let bookmark_id = insert_bookmark(&mut tx, user_id, bookmark, origin).await?;
+
+    // Commit the transaction
+    tx.commit().await?;
+
+    Ok(bookmark_id)
+}
+
+/// A bookmark to be created.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NewBookmark {
+    pub url: String,
+    /// The URL as it was given, if URL cleaning changed it
+    pub original_url: Option<String>,
+    pub title: String,
+    pub description: Option<String>,
+    pub tag_names: Vec<String>,
+    /// UNIX timestamp, for bookmarks carried over from elsewhere; defaults to now
+    pub created_at: Option<i64>,
+    /// Language of the page's content, e.g. `de`, if known
+    pub language: Option<String>,
+    /// UNIX timestamp after which a temporary bookmark is deleted; kept forever if not set
+    pub expires_at: Option<i64>,
+    /// Saved straight to the archive
+    pub is_archived: bool,
+    /// Saved to read later
+    pub is_unread: bool,
+    /// Who wrote the page, from its metadata
+    pub author: Option<String>,
+    /// Name of the publication or site the page is from, from its metadata
+    pub site_name: Option<String>,
+    /// UNIX timestamp the page says it was published
+    pub published_at: Option<i64>,
+    pub visibility: Visibility,
+}
+
+/// Creates several bookmarks for a user in a single transaction, returning their IDs in order.
+///
+/// Either all of them are saved or, if anything fails, none are.
+///
+/// # Errors
+///
+/// Returns an error if database operations fail.
+pub async fn create_bookmarks(db: &Db, user_id: Uuid, bookmarks: &[NewBookmark], origin: &BookmarkOrigin) -> Result<Vec<BookmarkId>> {
+    let _timer = QueryTimer::start("create_bookmarks");
+    let mut tx = db.writer.begin().await?;
+
+    let mut bookmark_ids = Vec::with_capacity(bookmarks.len());
+    for bookmark in bookmarks {
+        let bookmark_id = insert_bookmark(&mut tx, user_id, bookmark, origin).await?;
+        bookmark_ids.push(bookmark_id);
+    }
+
+    tx.commit().await?;
+
+    Ok(bookmark_ids)
+}
+
+/// Inserts a bookmark and links its tags, creating any that don't exist yet.
+pub(crate) async fn insert_bookmark(
+    tx: &mut SqliteConnection,
+    user_id: Uuid,
+    bookmark: &NewBookmark,
+    origin: &BookmarkOrigin,
+) -> Result<BookmarkId> {
+    let source = origin.source.as_str();
+    let visibility = bookmark.visibility.as_str();
 
     // Insert bookmark and get the generated bookmark_id
     let bookmark_result = sqlx::query!(
         r#"
-        insert into bookmarks (user_id, url, title, description)
-        values (?, ?, ?, ?)
-        returning bookmark_id
+        insert into bookmarks (
+            user_id, url, original_url, title, description, source, source_detail, created_at, language, expires_at, is_archived,
+            is_unread, author, site_name, published_at, visibility
+        )
+        values (?, ?, ?, ?, ?, ?, ?, coalesce(?, unixepoch()), ?, ?, ?, ?, ?, ?, ?, ?)
+        returning bookmark_id as "bookmark_id!: BookmarkId"
         "#,
         user_id,
-        url,
-        title,
-        description
+        bookmark.url,
+        bookmark.original_url,
+        bookmark.title,
+        bookmark.description,
+        source,
+        origin.detail,
+        bookmark.created_at,
+        bookmark.language,
+        bookmark.expires_at,
+        bookmark.is_archived,
+        bookmark.is_unread,
+        bookmark.author,
+        bookmark.site_name,
+        bookmark.published_at,
+        visibility
     )
     .fetch_one(&mut *tx)
     .await?;
 
-    let bookmark_id = &bookmark_result.bookmark_id;
+    let bookmark_id = bookmark_result.bookmark_id;
+    link_tags(tx, bookmark_id, &bookmark.tag_names).await?;
+
+    Ok(bookmark_id)
+}
 
-    // Process and link tags
+/// Links tags to a bookmark, creating any that don't exist yet.
+pub(crate) async fn link_tags(tx: &mut SqliteConnection, bookmark_id: BookmarkId, tag_names: &[String]) -> Result<()> {
     for tag_name in tag_names {
         if tag_name.trim().is_empty() {
             continue;
         }
 
         // Get or create the tag within the transaction
-        let tag_id = db::tags::get_or_create_tag(&mut tx, tag_name).await?;
+        let tag_id = db::tags::get_or_create_tag(tx, tag_name).await?;
 
         // Link bookmark to tag
         sqlx::query!("insert into bookmark_tags (bookmark_id, tag_id) values (?, ?)", bookmark_id, tag_id)
@@ -663,8 +1653,565 @@ pub async fn create_bookmark(
             .await?;
     }
 
-    // Commit the transaction
+    Ok(())
+}
+
+/// An author along with how many of a user's bookmarks they wrote.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct AuthorCount {
+    pub name: String,
+    pub count: i64,
+}
+
+/// Retrieves the authors of a user's bookmarks with their bookmark counts, most bookmarks first.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn get_authors(db: &Db, user_id: Uuid) -> Result<Vec<AuthorCount>> {
+    let _timer = QueryTimer::start("get_authors");
+    let authors = sqlx::query_as!(
+        AuthorCount,
+        r#"
+        select author as "name!: String", count(*) as "count!: i64"
+        from bookmarks
+        where user_id = ? and is_archived = 0 and deleted_at is null and author is not null
+        group by author collate nocase
+        order by count(*) desc, author collate nocase
+        "#,
+        user_id
+    )
+    .fetch_all(&db.reader)
+    .await?;
+
+    Ok(authors)
+}
+
+/// Retrieves all of a user's bookmarks by an author, newest first. Bookmarks with several authors
+/// match any of them, as do those where only part of the name is given.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn get_bookmarks_by_author(db: &Db, user_id: Uuid, author: &str) -> Result<BookmarkList> {
+    let _timer = QueryTimer::start("get_bookmarks_by_author");
+    let pattern = format!("%{}%", author.trim());
+    let bookmarks = sqlx::query_as!(
+        BookmarkRecord,
+        r#"
+        select
+            bookmark_id as "bookmark_id!: BookmarkId",
+            url,
+            title,
+            description,
+            created_at,
+            language,
+            expires_at,
+            author,
+            site_name,
+            is_archived as "is_archived!: bool",
+            is_unread as "is_unread!: bool",
+            has_thumbnail as "has_thumbnail!: bool",
+            has_snapshot as "has_snapshot!: bool",
+            visibility = 'private' as "is_private!: bool",
+            tags_string as "tags_string?: String"
+        from bookmark_with_tags
+        where user_id = ? and is_archived = 0 and deleted_at is null and author like ?
+        order by created_at desc
+        "#,
+        user_id,
+        pattern
+    )
+    .fetch_all(&db.reader)
+    .await?;
+
+    Ok(bookmarks.into())
+}
+
+/// Retrieves one of a user's bookmarks, whether archived or not, unless it's in the trash.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn get_bookmark(db: &Db, user_id: Uuid, bookmark_id: BookmarkId) -> Result<Option<BookmarkItem>> {
+    let _timer = QueryTimer::start("get_bookmark");
+    let bookmark = sqlx::query_as!(
+        BookmarkRecord,
+        r#"
+        select
+            bookmark_id as "bookmark_id!: BookmarkId",
+            url,
+            title,
+            description,
+            created_at,
+            language,
+            expires_at,
+            author,
+            site_name,
+            is_archived as "is_archived!: bool",
+            is_unread as "is_unread!: bool",
+            has_thumbnail as "has_thumbnail!: bool",
+            has_snapshot as "has_snapshot!: bool",
+            visibility = 'private' as "is_private!: bool",
+            tags_string as "tags_string?: String"
+        from bookmark_with_tags
+        where user_id = ? and bookmark_id = ? and deleted_at is null
+        "#,
+        user_id,
+        bookmark_id
+    )
+    .fetch_optional(&db.reader)
+    .await?;
+
+    Ok(bookmark.and_then(|bookmark| BookmarkList::from(vec![bookmark]).into_iter().next()))
+}
+
+/// Changes a bookmark's URL, title, description and tags, replacing all of its tags. Only archives
+/// it if `bookmark.is_archived` is set, never unarchives it. Returns `false` if the user has no
+/// such bookmark.
+///
+/// # Errors
+///
+/// Returns an error if database operations fail.
+pub async fn update_bookmark(db: &Db, user_id: Uuid, bookmark_id: BookmarkId, bookmark: &NewBookmark) -> Result<bool> {
+    let _timer = QueryTimer::start("update_bookmark");
+    let mut tx = db.writer.begin().await?;
+
+    // The URL as first entered is only replaced if the URL itself changes
+    let result = sqlx::query!(
+        r#"
+        update bookmarks
+        set
+            original_url = case when url = ? then original_url else ? end,
+            url = ?,
+            title = ?,
+            description = ?,
+            is_archived = is_archived or ?,
+            updated_at = unixepoch()
+        where bookmark_id = ? and user_id = ? and deleted_at is null
+        "#,
+        bookmark.url,
+        bookmark.original_url,
+        bookmark.url,
+        bookmark.title,
+        bookmark.description,
+        bookmark.is_archived,
+        bookmark_id,
+        user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Ok(false);
+    }
+
+    sqlx::query!("delete from bookmark_tags where bookmark_id = ?", bookmark_id)
+        .execute(&mut *tx)
+        .await?;
+    link_tags(&mut tx, bookmark_id, &bookmark.tag_names).await?;
+
     tx.commit().await?;
 
-    Ok(bookmark_id.clone())
+    Ok(true)
+}
+
+/// How long deleted bookmarks stay in the trash before they're purged for good.
+pub const TRASH_RETENTION_DAYS: i64 = 30;
+
+/// Moves one of a user's bookmarks to the archive, or back to the main list. Returns `false` if the
+/// user has no such bookmark outside the trash.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn set_archived(db: &Db, user_id: Uuid, bookmark_id: BookmarkId, is_archived: bool) -> Result<bool> {
+    let _timer = QueryTimer::start("set_archived");
+    let result = sqlx::query!(
+        r#"
+        update bookmarks
+        set is_archived = ?, updated_at = unixepoch()
+        where bookmark_id = ? and user_id = ? and deleted_at is null
+        "#,
+        is_archived,
+        bookmark_id,
+        user_id
+    )
+    .execute(&db.writer)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Marks one of a user's bookmarks to read later, or as read. Returns `false` if the user has no
+/// such bookmark outside the trash.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn set_unread(db: &Db, user_id: Uuid, bookmark_id: BookmarkId, is_unread: bool) -> Result<bool> {
+    let _timer = QueryTimer::start("set_unread");
+    let result = sqlx::query!(
+        r#"
+        update bookmarks
+        set is_unread = ?, updated_at = unixepoch()
+        where bookmark_id = ? and user_id = ? and deleted_at is null
+        "#,
+        is_unread,
+        bookmark_id,
+        user_id
+    )
+    .execute(&db.writer)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Makes one of a user's bookmarks private, or public again. Returns `false` if the user has no
+/// such bookmark outside the trash.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn set_visibility(db: &Db, user_id: Uuid, bookmark_id: BookmarkId, visibility: Visibility) -> Result<bool> {
+    let _timer = QueryTimer::start("set_visibility");
+    let visibility = visibility.as_str();
+    let result = sqlx::query!(
+        r#"
+        update bookmarks
+        set visibility = ?, updated_at = unixepoch()
+        where bookmark_id = ? and user_id = ? and deleted_at is null
+        "#,
+        visibility,
+        bookmark_id,
+        user_id
+    )
+    .execute(&db.writer)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// A bookmark in the trash.
+#[derive(Clone, Debug)]
+pub struct TrashedBookmark {
+    pub id: BookmarkId,
+    pub url: String,
+    pub title: String,
+    /// When it was deleted, e.g. "3 days ago"
+    pub deleted_ago: String,
+    /// When it will be purged, e.g. "in 27 days"
+    pub purged_in: String,
+}
+
+/// Moves one of a user's bookmarks to the trash. Returns `false` if the user has no such bookmark
+/// outside the trash.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn trash_bookmark(db: &Db, user_id: Uuid, bookmark_id: BookmarkId) -> Result<bool> {
+    let _timer = QueryTimer::start("trash_bookmark");
+    let result = sqlx::query!(
+        "update bookmarks set deleted_at = unixepoch() where bookmark_id = ? and user_id = ? and deleted_at is null",
+        bookmark_id,
+        user_id
+    )
+    .execute(&db.writer)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Takes one of a user's bookmarks back out of the trash. Returns `false` if there's no such
+/// bookmark in their trash.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn restore_bookmark(db: &Db, user_id: Uuid, bookmark_id: BookmarkId) -> Result<bool> {
+    let _timer = QueryTimer::start("restore_bookmark");
+    let result = sqlx::query!(
+        "update bookmarks set deleted_at = null where bookmark_id = ? and user_id = ? and deleted_at is not null",
+        bookmark_id,
+        user_id
+    )
+    .execute(&db.writer)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Permanently deletes a bookmark from a user's trash, or all of them if `bookmark_id` is `None`.
+/// Returns how many were deleted.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn purge_bookmarks(db: &Db, user_id: Uuid, bookmark_id: Option<BookmarkId>) -> Result<u64> {
+    let _timer = QueryTimer::start("purge_bookmarks");
+    let result = sqlx::query!(
+        "delete from bookmarks where user_id = ? and deleted_at is not null and (? is null or bookmark_id = ?)",
+        user_id,
+        bookmark_id,
+        bookmark_id
+    )
+    .execute(&db.writer)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Retrieves the bookmarks in a user's trash, most recently deleted first.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn get_trashed_bookmarks(db: &Db, user_id: Uuid) -> Result<Vec<TrashedBookmark>> {
+    let _timer = QueryTimer::start("get_trashed_bookmarks");
+    let records = sqlx::query!(
+        r#"
+        select bookmark_id as "bookmark_id!: BookmarkId", url, title, deleted_at as "deleted_at!: i64"
+        from bookmarks
+        where user_id = ? and deleted_at is not null
+        order by deleted_at desc
+        "#,
+        user_id
+    )
+    .fetch_all(&db.reader)
+    .await?;
+
+    let now = chrono::Utc::now().timestamp();
+    Ok(records
+        .into_iter()
+        .map(|record| TrashedBookmark {
+            id: record.bookmark_id,
+            url: record.url,
+            title: record.title,
+            deleted_ago: get_created_ago(record.deleted_at),
+            purged_in: get_expires_in(record.deleted_at + TRASH_RETENTION_DAYS * 86400, now),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::seed::{self, SeedProfile, Volumes},
+        search::SearchDefaults,
+    };
+
+    #[test]
+    fn test_bookmark_id_encoding_round_trip() {
+        let id = BookmarkId(Uuid::parse_str("67e55044-10b1-426f-9247-bb680e5fe0c8").unwrap());
+        let encoded = id.to_string();
+        assert_eq!(encoded.len(), 22);
+        assert!(encoded.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+        assert_eq!(encoded.parse::<BookmarkId>().unwrap(), id);
+        assert_eq!("67e55044-10b1-426f-9247-bb680e5fe0c8".parse::<BookmarkId>().unwrap(), id);
+    }
+
+    #[test]
+    fn test_bookmark_id_rejects_garbage() {
+        assert!("".parse::<BookmarkId>().is_err());
+        assert!("not an id".parse::<BookmarkId>().is_err());
+        assert!("AAAA".parse::<BookmarkId>().is_err());
+    }
+
+    #[test]
+    fn test_bookmark_cursor_round_trip() {
+        let cursor = BookmarkCursor {
+            created_at: 1_700_000_000,
+            id: BookmarkId(Uuid::parse_str("67e55044-10b1-426f-9247-bb680e5fe0c8").unwrap()),
+        };
+        let written = cursor.to_string();
+        assert_eq!(written, format!("1700000000.{}", cursor.id));
+        assert_eq!(written.parse::<BookmarkCursor>().unwrap(), cursor);
+
+        assert!("".parse::<BookmarkCursor>().is_err());
+        assert!("1700000000".parse::<BookmarkCursor>().is_err());
+        assert!("soon.AAAAAAAAAAAAAAAAAAAAAA".parse::<BookmarkCursor>().is_err());
+        assert!("1700000000.not-an-id".parse::<BookmarkCursor>().is_err());
+    }
+
+    #[test]
+    fn test_expires_in() {
+        let now = 1_700_000_000;
+        assert_eq!(get_expires_in(now - 60, now), "within a day");
+        assert_eq!(get_expires_in(now + 3600, now), "within a day");
+        assert_eq!(get_expires_in(now + 86400 + 3600, now), "in 1 day");
+        assert_eq!(get_expires_in(now + 7 * 86400, now), "in 7 days");
+    }
+
+    #[test]
+    fn test_bookmark_id_serde() {
+        let id = BookmarkId(Uuid::nil());
+        let json = serde_json::to_string(&id).unwrap();
+        assert_eq!(json, "\"AAAAAAAAAAAAAAAAAAAAAA\"");
+        assert_eq!(serde_json::from_str::<BookmarkId>(&json).unwrap(), id);
+    }
+
+    #[test]
+    fn test_duplicate_key() {
+        assert_eq!(duplicate_key("https://example.com/a/"), "example.com/a");
+        assert_eq!(duplicate_key("http://example.com/a"), "example.com/a");
+        assert_eq!(duplicate_key("https://example.com/"), "example.com");
+        assert_eq!(duplicate_key("example.com//"), "example.com");
+    }
+
+    #[test]
+    fn test_search_condition_sql() {
+        let sql = |input: &str| {
+            let mut builder = QueryBuilder::<Sqlite>::new("");
+            search_condition(&SearchQuery::parse(input)).push_to(&mut builder);
+            builder.sql().to_string()
+        };
+        let matches = |column: &str| format!("{column} like ?");
+        let term = format!(
+            "({} or {} or {} or exists (select 1 from bookmark_tags bt join tags t on bt.tag_id = t.tag_id where bt.bookmark_id = b.bookmark_id and t.name like ?))",
+            matches("b.title"),
+            matches("b.description"),
+            matches("b.url")
+        );
+
+        // Any number of terms, joined by the query's logic
+        assert_eq!(
+            sql("rust axum tokio"),
+            format!("(not b.is_archived and ({term} or {term} or {term}))")
+        );
+        assert_eq!(
+            sql("rust AND axum AND tokio"),
+            format!("(not b.is_archived and ({term} and {term} and {term}))")
+        );
+        // Phrases, field terms and tags alongside them
+        assert_eq!(
+            sql("\"web dev\" title:axum #rust"),
+            "(not b.is_archived and exists (select 1 from bookmark_tags bt join tags t on bt.tag_id = t.tag_id where bt.bookmark_id = b.bookmark_id and t.name = ?) \
+             and ((instr(b.title, ?) > 0 or instr(b.description, ?) > 0 or instr(b.url, ?) > 0 or exists (select 1 from bookmark_tags bt join tags t on bt.tag_id = t.tag_id where bt.bookmark_id = b.bookmark_id and instr(t.name, ?) > 0)) \
+             or b.title like ?))"
+        );
+        // Dates are bound rather than written into the SQL
+        assert_eq!(sql("is:archived after:2024"), "(b.is_archived and b.created_at >= ?)");
+        assert_eq!(
+            sql("is:unread has:snapshot has:notes"),
+            "(not b.is_archived and b.is_unread and b.has_snapshot and (b.description is not null and trim(b.description) != ''))"
+        );
+    }
+
+    #[test]
+    fn test_pages_add_up_to_count_with_archive() {
+        let path = std::env::temp_dir().join(format!("pagepouch-test-{}.db", Uuid::new_v4()));
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let volumes = Volumes {
+                users: 1,
+                bookmarks: 200,
+                tags: 20,
+            };
+            seed::create_fixture(&path, SeedProfile::Generated(volumes)).await.unwrap();
+            let db = crate::db::connect(&format!("sqlite://{}", path.display()), None).await.unwrap();
+            let user_id: Uuid = sqlx::query_scalar("select user_id from users where username = 'admin'")
+                .fetch_one(&db.reader)
+                .await
+                .unwrap();
+
+            for include_archived in [false, true] {
+                let query = SearchQuery {
+                    include_archived,
+                    ..SearchQuery::default()
+                };
+                let total = count_user_bookmarks(&db, user_id, &query, &[]).await.unwrap();
+                let mut listed = Vec::new();
+                for offset in (0..total).step_by(30) {
+                    let page = search_user_bookmarks_advanced(&db, user_id, &query, SortOrder::Newest, 30, offset)
+                        .await
+                        .unwrap();
+                    assert!(!page.is_empty(), "page at {offset} of {total} is empty");
+                    listed.extend(page.into_iter().map(|bookmark| bookmark.is_archived));
+                }
+                assert_eq!(i64::try_from(listed.len()).unwrap(), total);
+                assert_eq!(listed.contains(&true), include_archived);
+            }
+        });
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{suffix}", path.display()));
+        }
+    }
+
+    #[test]
+    fn test_tag_filters_match_whole_names() {
+        let tag = |name: &str| {
+            Condition::text(
+                "exists (select 1 from bookmark_tags bt join tags t on bt.tag_id = t.tag_id where bt.bookmark_id = b.bookmark_id and t.name = ?)",
+                [name.to_string()],
+            )
+        };
+        let tags = |input: &str| {
+            let Condition::All(all) = search_condition(&SearchQuery::parse_complete(input)) else {
+                panic!("a search is all of its conditions");
+            };
+            all[1..].to_vec()
+        };
+
+        // One tag or several, `#rust` doesn't find `trust` or `rust-async`
+        assert_eq!(tags("#rust"), vec![tag("rust")]);
+        assert_eq!(tags("#Rust #web"), vec![tag("rust"), tag("web")]);
+    }
+
+    #[test]
+    fn test_search_defaults_sql() {
+        let defaults = SearchDefaults {
+            include_archived: true,
+            ..SearchDefaults::default()
+        };
+        let sql = |input: &str| {
+            let mut builder = QueryBuilder::<Sqlite>::new("");
+            search_condition(&SearchQuery::parse_with(input, defaults)).push_to(&mut builder);
+            builder.sql().to_string()
+        };
+        assert_eq!(sql("after:2024"), "(b.created_at >= ?)");
+        assert_eq!(sql("is:archived after:2024"), "(b.is_archived and b.created_at >= ?)");
+
+        let word = SearchTerm::Word("rst".to_string());
+        assert_eq!(
+            contains_sql("b.title", &word, true),
+            ("b.title like ?".to_string(), "%r%s%t%".to_string())
+        );
+        assert_eq!(contains_sql("b.title", &word, false).1, "%rst%");
+        let phrase = SearchTerm::Phrase("rst".to_string());
+        assert_eq!(contains_sql("b.title", &phrase, true).1, "rst");
+    }
+
+    #[test]
+    fn test_page_text_sql() {
+        let page_text = "b.bookmark_id in (select pt.bookmark_id from bookmark_page_text pt where pt.rowid in (select rowid from bookmark_page_text_index where content";
+        let condition = |input: &str, defaults: SearchDefaults| search_condition(&SearchQuery::parse_with(input, defaults));
+
+        // Phrases are matched exactly, whatever they contain
+        let Condition::All(all) = condition("content:\"async [rust]?\"", SearchDefaults::default()) else {
+            panic!("a search is all of its conditions");
+        };
+        let Some(Condition::Any(terms)) = all.last() else {
+            panic!("terms are joined by the query's logic");
+        };
+        assert_eq!(
+            terms,
+            &[Condition::text(
+                format!("{page_text} glob ?))"),
+                ["*async [[]rust][?]*".to_string()]
+            )]
+        );
+        // Other terms only match in it when the user asked for that
+        let mut builder = QueryBuilder::<Sqlite>::new("");
+        condition("rust", SearchDefaults::default()).push_to(&mut builder);
+        assert!(!builder.sql().contains("bookmark_page_text"));
+
+        let defaults = SearchDefaults {
+            page_text: true,
+            ..SearchDefaults::default()
+        };
+        let mut builder = QueryBuilder::<Sqlite>::new("");
+        condition("rust", defaults).push_to(&mut builder);
+        assert!(builder.sql().contains(&format!(" or {page_text} like ?))")));
+    }
 }
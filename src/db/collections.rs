@@ -0,0 +1,166 @@
+//! Smart collection database operations.
+//!
+//! A smart collection is a named rule written in the search syntax (e.g. `site:github.com #rust`).
+//! Only the rule is stored; its bookmarks are found by running the rule as a search each time, so
+//! collections stay up to date as bookmarks are added. Bookmarks can also be put in order within a
+//! collection, see [`set_collection_order`].
+
+use anyhow::Result;
+use uuid::Uuid;
+
+use crate::db::{Db, bookmarks::BookmarkId, timing::QueryTimer};
+
+/// A saved, named search rule shown alongside the tags in navigation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SmartCollection {
+    pub collection_id: Uuid,
+    pub name: String,
+    /// Rule in search syntax
+    pub query: String,
+}
+
+/// Retrieves all of a user's smart collections, ordered by name.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn get_user_collections(db: &Db, user_id: Uuid) -> Result<Vec<SmartCollection>> {
+    let _timer = QueryTimer::start("get_user_collections");
+    let collections = sqlx::query_as!(
+        SmartCollection,
+        r#"
+        select
+            collection_id as "collection_id!: Uuid",
+            name,
+            query
+        from smart_collections
+        where user_id = ?
+        order by name collate nocase
+        "#,
+        user_id
+    )
+    .fetch_all(&db.reader)
+    .await?;
+
+    Ok(collections)
+}
+
+/// Retrieves one of a user's smart collections, or `None` if it doesn't exist or belongs to someone else.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn get_collection(db: &Db, user_id: Uuid, collection_id: Uuid) -> Result<Option<SmartCollection>> {
+    let _timer = QueryTimer::start("get_collection");
+    let collection = sqlx::query_as!(
+        SmartCollection,
+        r#"
+        select
+            collection_id as "collection_id!: Uuid",
+            name,
+            query
+        from smart_collections
+        where user_id = ? and collection_id = ?
+        "#,
+        user_id,
+        collection_id
+    )
+    .fetch_optional(&db.reader)
+    .await?;
+
+    Ok(collection)
+}
+
+/// Creates a smart collection, returning its ID, or `None` if the user already has one with that name.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn create_collection(db: &Db, user_id: Uuid, name: &str, query: &str) -> Result<Option<Uuid>> {
+    let _timer = QueryTimer::start("create_collection");
+    let record = sqlx::query!(
+        r#"
+        insert into smart_collections (user_id, name, query)
+        values (?, ?, ?)
+        on conflict (user_id, name) do nothing
+        returning collection_id as "collection_id!: Uuid"
+        "#,
+        user_id,
+        name,
+        query
+    )
+    .fetch_optional(&db.writer)
+    .await?;
+
+    Ok(record.map(|record| record.collection_id))
+}
+
+/// Renames one of a user's smart collections and changes its rule. Returns `false` if the user has
+/// another collection with the new name.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn update_collection(db: &Db, user_id: Uuid, collection_id: Uuid, name: &str, query: &str) -> Result<bool> {
+    let _timer = QueryTimer::start("update_collection");
+    let result = sqlx::query!(
+        r#"
+        update smart_collections set name = $1, query = $2
+        where user_id = $3 and collection_id = $4
+        and not exists (select 1 from smart_collections where user_id = $3 and name = $1 and collection_id != $4)
+        "#,
+        name,
+        query,
+        user_id,
+        collection_id
+    )
+    .execute(&db.writer)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Deletes one of a user's smart collections. Returns whether anything was deleted.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn delete_collection(db: &Db, user_id: Uuid, collection_id: Uuid) -> Result<bool> {
+    let _timer = QueryTimer::start("delete_collection");
+    let result = sqlx::query!(
+        "delete from smart_collections where user_id = ? and collection_id = ?",
+        user_id,
+        collection_id
+    )
+    .execute(&db.writer)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Sets a smart collection's own order, placing its bookmarks in the order given. Bookmarks left
+/// out come after them, newest first.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn set_collection_order(db: &Db, collection_id: Uuid, bookmark_ids: &[BookmarkId]) -> Result<()> {
+    let _timer = QueryTimer::start("set_collection_order");
+    let mut tx = db.writer.begin().await?;
+    sqlx::query!("delete from collection_positions where collection_id = ?", collection_id)
+        .execute(&mut *tx)
+        .await?;
+    for (position, bookmark_id) in (0_i64..).zip(bookmark_ids) {
+        sqlx::query!(
+            "insert into collection_positions (collection_id, bookmark_id, position) values (?, ?, ?)",
+            collection_id,
+            bookmark_id,
+            position
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+
+    Ok(())
+}
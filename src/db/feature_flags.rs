@@ -0,0 +1,108 @@
+//! Per-user feature flag overrides.
+//!
+//! Which features exist, and the instance defaults these override, live in [`crate::features`].
+
+use anyhow::Result;
+use uuid::Uuid;
+
+use crate::db::{Db, timing::QueryTimer};
+
+/// A feature turned on or off for one user, regardless of the instance default.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FeatureOverride {
+    pub feature: String,
+    pub enabled: bool,
+}
+
+/// A user's override, as listed for the admin.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UserFeatureOverride {
+    pub username: String,
+    pub feature: String,
+    pub enabled: bool,
+}
+
+/// Retrieves a user's feature overrides.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn get_user_overrides(db: &Db, user_id: Uuid) -> Result<Vec<FeatureOverride>> {
+    let _timer = QueryTimer::start("get_user_overrides");
+    let overrides = sqlx::query_as!(
+        FeatureOverride,
+        r#"
+        select feature, enabled as "enabled!: bool"
+        from user_feature_flags
+        where user_id = ?
+        "#,
+        user_id
+    )
+    .fetch_all(&db.reader)
+    .await?;
+
+    Ok(overrides)
+}
+
+/// Retrieves every user's feature overrides, ordered by username.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn get_all_overrides(db: &Db) -> Result<Vec<UserFeatureOverride>> {
+    let _timer = QueryTimer::start("get_all_overrides");
+    let overrides = sqlx::query_as!(
+        UserFeatureOverride,
+        r#"
+        select u.username, f.feature, f.enabled as "enabled!: bool"
+        from user_feature_flags f
+        join users u on u.user_id = f.user_id
+        order by u.username, f.feature
+        "#
+    )
+    .fetch_all(&db.reader)
+    .await?;
+
+    Ok(overrides)
+}
+
+/// Turns a feature on or off for a user, or back to the instance default if `enabled` is `None`.
+/// Returns `false` if there is no user with that name.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn set_override(db: &Db, username: &str, feature: &str, enabled: Option<bool>) -> Result<bool> {
+    let _timer = QueryTimer::start("set_override");
+    let Some(user) = sqlx::query!(r#"select user_id as "user_id!: Uuid" from users where username = ?"#, username)
+        .fetch_optional(&db.reader)
+        .await?
+    else {
+        return Ok(false);
+    };
+
+    if let Some(enabled) = enabled {
+        sqlx::query!(
+            r#"
+            insert into user_feature_flags (user_id, feature, enabled)
+            values (?, ?, ?)
+            on conflict (user_id, feature) do update set enabled = excluded.enabled, updated_at = unixepoch()
+            "#,
+            user.user_id,
+            feature,
+            enabled
+        )
+        .execute(&db.writer)
+        .await?;
+    } else {
+        sqlx::query!(
+            "delete from user_feature_flags where user_id = ? and feature = ?",
+            user.user_id,
+            feature
+        )
+        .execute(&db.writer)
+        .await?;
+    }
+
+    Ok(true)
+}
@@ -0,0 +1,188 @@
+//! Bookmarks whose page couldn't be fetched, to be tried again, see [`crate::fetch_retry`].
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use uuid::Uuid;
+
+use crate::db::{Db, bookmarks::BookmarkId, timing::QueryTimer};
+
+/// The failed fetches of a user's bookmarks, by bookmark.
+pub type FetchFailures = HashMap<BookmarkId, FetchFailure>;
+
+/// Why a bookmark's page couldn't be fetched, and when it'll be tried again.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FetchFailure {
+    /// Why the last try failed, e.g. "Timed out" or "HTTP 403 Forbidden"
+    pub error: String,
+    /// Tries so far, counting the one when the bookmark was saved
+    pub attempts: i64,
+    /// UNIX timestamp of the next try, or `None` once given up
+    pub retry_at: Option<i64>,
+}
+
+/// A bookmark whose page is due to be fetched again.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DueFetch {
+    pub bookmark_id: BookmarkId,
+    pub url: String,
+    pub attempts: i64,
+}
+
+/// What fetching a bookmark's page found, to fill in what the bookmark is missing.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FetchedDetails {
+    pub title: String,
+    pub description: Option<String>,
+    pub language: Option<String>,
+    pub author: Option<String>,
+    pub site_name: Option<String>,
+    /// UNIX timestamp
+    pub published_at: Option<i64>,
+}
+
+/// Saves why a bookmark's page couldn't be fetched, replacing the last failure.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn set_fetch_failure(db: &Db, bookmark_id: BookmarkId, failure: &FetchFailure) -> Result<()> {
+    let _timer = QueryTimer::start("set_fetch_failure");
+    sqlx::query!(
+        r#"
+        insert or replace into bookmark_fetch_failures (bookmark_id, error, attempts, retry_at)
+        values (?, ?, ?, ?)
+        "#,
+        bookmark_id,
+        failure.error,
+        failure.attempts,
+        failure.retry_at
+    )
+    .execute(&db.writer)
+    .await?;
+
+    Ok(())
+}
+
+/// Retrieves why a bookmark's page couldn't be fetched, if it couldn't.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn get_fetch_failure(db: &Db, bookmark_id: BookmarkId) -> Result<Option<FetchFailure>> {
+    let _timer = QueryTimer::start("get_fetch_failure");
+    let failure = sqlx::query_as!(
+        FetchFailure,
+        "select error, attempts, retry_at from bookmark_fetch_failures where bookmark_id = ?",
+        bookmark_id
+    )
+    .fetch_optional(&db.reader)
+    .await?;
+
+    Ok(failure)
+}
+
+/// Retrieves the failed fetches of a user's bookmarks.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn get_fetch_failures(db: &Db, user_id: Uuid) -> Result<FetchFailures> {
+    let _timer = QueryTimer::start("get_fetch_failures");
+    let records = sqlx::query!(
+        r#"
+        select ff.bookmark_id as "bookmark_id!: BookmarkId", ff.error, ff.attempts, ff.retry_at
+        from bookmark_fetch_failures ff
+        join bookmarks b on b.bookmark_id = ff.bookmark_id
+        where b.user_id = ?
+        "#,
+        user_id
+    )
+    .fetch_all(&db.reader)
+    .await?;
+
+    Ok(records
+        .into_iter()
+        .map(|record| {
+            (
+                record.bookmark_id,
+                FetchFailure {
+                    error: record.error,
+                    attempts: record.attempts,
+                    retry_at: record.retry_at,
+                },
+            )
+        })
+        .collect())
+}
+
+/// Finds bookmarks whose page is due to be fetched again at `now` (a UNIX timestamp), the longest
+/// due first. Bookmarks in the trash and of users who opted out of fetching are left alone.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn get_due_fetches(db: &Db, now: i64, limit: i64) -> Result<Vec<DueFetch>> {
+    let _timer = QueryTimer::start("get_due_fetches");
+    let due = sqlx::query_as!(
+        DueFetch,
+        r#"
+        select b.bookmark_id as "bookmark_id!: BookmarkId", b.url, ff.attempts
+        from bookmark_fetch_failures ff
+        join bookmarks b on b.bookmark_id = ff.bookmark_id
+        left join user_settings us on us.user_id = b.user_id
+        where ff.retry_at <= ?
+            and b.deleted_at is null
+            and not coalesce(us.disable_fetch, false)
+        order by ff.retry_at
+        limit ?
+        "#,
+        now,
+        limit
+    )
+    .fetch_all(&db.reader)
+    .await?;
+
+    Ok(due)
+}
+
+/// Fills in what a bookmark is missing from its fetched page, and forgets that fetching it
+/// failed. The title is only replaced if it's still the URL, as it's left when the page can't be
+/// fetched, and the rest only where the bookmark has none.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn complete_fetch(db: &Db, bookmark_id: BookmarkId, details: &FetchedDetails) -> Result<()> {
+    let _timer = QueryTimer::start("complete_fetch");
+    let mut tx = db.writer.begin().await?;
+    sqlx::query!(
+        r#"
+        update bookmarks
+        set
+            title = case when trim(title) = '' or title = url then ? else title end,
+            description = case when description is null or trim(description) = '' then ? else description end,
+            language = coalesce(language, ?),
+            author = coalesce(author, ?),
+            site_name = coalesce(site_name, ?),
+            published_at = coalesce(published_at, ?),
+            updated_at = unixepoch()
+        where bookmark_id = ?
+        "#,
+        details.title,
+        details.description,
+        details.language,
+        details.author,
+        details.site_name,
+        details.published_at,
+        bookmark_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!("delete from bookmark_fetch_failures where bookmark_id = ?", bookmark_id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    Ok(())
+}
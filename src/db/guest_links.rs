@@ -0,0 +1,156 @@
+//! Guest link database operations.
+//!
+//! A guest link lets someone without an account look through a user's bookmarks (or one of their
+//! smart collections) until it expires. The URL carries a signed [`GuestToken`], but it's the row
+//! here that makes the link valid, so deleting it revokes the link early.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::db::{Db, bookmarks::get_expires_in, timing::QueryTimer};
+
+/// Token in a guest link's URL.
+///
+/// Converted to a JWT and signed, like the session cookie. It also carries the expiry, so expired
+/// links are turned away without looking them up.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+pub struct GuestToken {
+    pub link_id: Uuid,
+    /// UNIX timestamp
+    pub expires_at: i64,
+}
+
+/// A guest link, as listed on the settings page.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GuestLink {
+    pub link_id: Uuid,
+    /// UNIX timestamp
+    pub expires_at: i64,
+    /// e.g. "in 3 days"
+    pub expires_in: String,
+    /// The smart collection it's limited to, if any
+    pub collection_name: Option<String>,
+}
+
+/// What a valid guest link gives access to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GuestAccess {
+    pub link_id: Uuid,
+    /// Whose bookmarks are shared
+    pub user_id: Uuid,
+    pub username: String,
+    /// The smart collection it's limited to, if any
+    pub collection_id: Option<Uuid>,
+    pub collection_name: Option<String>,
+    /// UNIX timestamp
+    pub expires_at: i64,
+}
+
+/// Retrieves a user's guest links that haven't expired yet, soonest to expire first.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn get_guest_links(db: &Db, user_id: Uuid) -> Result<Vec<GuestLink>> {
+    let _timer = QueryTimer::start("get_guest_links");
+    let records = sqlx::query!(
+        r#"
+        select
+            g.link_id as "link_id!: Uuid",
+            g.expires_at,
+            c.name as "collection_name?: String"
+        from guest_links g
+        left join smart_collections c on c.collection_id = g.collection_id
+        where g.user_id = ? and g.expires_at > unixepoch()
+        order by g.expires_at
+        "#,
+        user_id
+    )
+    .fetch_all(&db.reader)
+    .await?;
+
+    let now = chrono::Utc::now().timestamp();
+    Ok(records
+        .into_iter()
+        .map(|record| GuestLink {
+            link_id: record.link_id,
+            expires_at: record.expires_at,
+            expires_in: get_expires_in(record.expires_at, now),
+            collection_name: record.collection_name,
+        })
+        .collect())
+}
+
+/// Creates a guest link to a user's bookmarks, or only those in one of their smart collections,
+/// returning its ID. The user's expired links are cleaned up along the way.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn create_guest_link(db: &Db, user_id: Uuid, collection_id: Option<Uuid>, expires_at: i64) -> Result<Uuid> {
+    let _timer = QueryTimer::start("create_guest_link");
+    sqlx::query!("delete from guest_links where user_id = ? and expires_at <= unixepoch()", user_id)
+        .execute(&db.writer)
+        .await?;
+
+    let record = sqlx::query!(
+        r#"
+        insert into guest_links (user_id, collection_id, expires_at)
+        values (?, ?, ?)
+        returning link_id as "link_id!: Uuid"
+        "#,
+        user_id,
+        collection_id,
+        expires_at
+    )
+    .fetch_one(&db.writer)
+    .await?;
+
+    Ok(record.link_id)
+}
+
+/// Revokes one of a user's guest links. Returns whether anything was deleted.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn delete_guest_link(db: &Db, user_id: Uuid, link_id: Uuid) -> Result<bool> {
+    let _timer = QueryTimer::start("delete_guest_link");
+    let result = sqlx::query!("delete from guest_links where user_id = ? and link_id = ?", user_id, link_id)
+        .execute(&db.writer)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Looks up what a guest link gives access to, or `None` if it has expired, been revoked, or its
+/// user has been revoked.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn get_guest_access(db: &Db, link_id: Uuid) -> Result<Option<GuestAccess>> {
+    let _timer = QueryTimer::start("get_guest_access");
+    let access = sqlx::query_as!(
+        GuestAccess,
+        r#"
+        select
+            g.link_id as "link_id!: Uuid",
+            g.user_id as "user_id!: Uuid",
+            u.username,
+            g.collection_id as "collection_id?: Uuid",
+            c.name as "collection_name?: String",
+            g.expires_at
+        from guest_links g
+        join users u on u.user_id = g.user_id
+        left join smart_collections c on c.collection_id = g.collection_id
+        where g.link_id = ? and g.expires_at > unixepoch() and not u.is_revoked
+        "#,
+        link_id
+    )
+    .fetch_optional(&db.reader)
+    .await?;
+
+    Ok(access)
+}
@@ -0,0 +1,41 @@
+//! Bookmark import history database operations.
+
+use anyhow::Result;
+use uuid::Uuid;
+
+use crate::db::{Db, timing::QueryTimer};
+
+/// Outcome of importing one file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImportRecord {
+    /// File name or other description of where the bookmarks came from
+    pub source_name: String,
+    pub total_count: i64,
+    pub success_count: i64,
+    /// Entries that couldn't be imported, such as links that aren't web pages
+    pub error_count: i64,
+}
+
+/// Saves the outcome of a finished import.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn record_import(db: &Db, user_id: Uuid, record: &ImportRecord) -> Result<()> {
+    let _timer = QueryTimer::start("record_import");
+    sqlx::query!(
+        r#"
+        insert into bookmark_imports (user_id, source_name, total_count, success_count, error_count, completed_at)
+        values (?, ?, ?, ?, ?, unixepoch())
+        "#,
+        user_id,
+        record.source_name,
+        record.total_count,
+        record.success_count,
+        record.error_count
+    )
+    .execute(&db.writer)
+    .await?;
+
+    Ok(())
+}
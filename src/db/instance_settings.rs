@@ -0,0 +1,89 @@
+//! Instance-wide settings, changed by the admin. Stored in a single row.
+
+use anyhow::Result;
+
+use crate::db::{Db, timing::QueryTimer};
+
+/// A message shown in a banner on every page.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Announcement {
+    pub message: String,
+    /// UNIX timestamp after which it's no longer shown; shown until cleared if `None`
+    pub expires_at: Option<i64>,
+    /// UNIX timestamp; changes with every edit, so a dismissed banner comes back when it's changed
+    pub updated_at: i64,
+}
+
+/// Loads the announcement, if there is one that hasn't expired.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn get_announcement(db: &Db) -> Result<Option<Announcement>> {
+    let _timer = QueryTimer::start("get_announcement");
+    let announcement = sqlx::query_as!(
+        Announcement,
+        r#"
+        select announcement as "message!", announcement_expires_at as expires_at, updated_at
+        from instance_settings
+        where announcement is not null
+            and (announcement_expires_at is null or announcement_expires_at > unixepoch())
+        "#
+    )
+    .fetch_optional(&db.reader)
+    .await?;
+
+    Ok(announcement)
+}
+
+/// Sets the announcement, or removes it if `message` is `None`.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn set_announcement(db: &Db, message: Option<&str>, expires_at: Option<i64>) -> Result<()> {
+    let _timer = QueryTimer::start("set_announcement");
+    sqlx::query!(
+        r#"
+        update instance_settings
+        set announcement = ?, announcement_expires_at = ?, updated_at = unixepoch()
+        where id = 1
+        "#,
+        message,
+        expires_at
+    )
+    .execute(&db.writer)
+    .await?;
+
+    Ok(())
+}
+
+/// Whether new users are given the starter set, see [`crate::onboarding`].
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn get_starter_set(db: &Db) -> Result<bool> {
+    let _timer = QueryTimer::start("get_starter_set");
+    let enabled = sqlx::query_scalar!(r#"select starter_set as "starter_set: bool" from instance_settings"#)
+        .fetch_one(&db.reader)
+        .await?;
+
+    Ok(enabled)
+}
+
+/// Sets whether new users are given the starter set.
+///
+/// Doesn't touch `updated_at`, which would bring back dismissed announcements.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn set_starter_set(db: &Db, enabled: bool) -> Result<()> {
+    let _timer = QueryTimer::start("set_starter_set");
+    sqlx::query!("update instance_settings set starter_set = ? where id = 1", enabled)
+        .execute(&db.writer)
+        .await?;
+
+    Ok(())
+}
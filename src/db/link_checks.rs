@@ -0,0 +1,112 @@
+//! Results of the scheduled dead-link checker, see [`crate::link_check`].
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use uuid::Uuid;
+
+use crate::db::{Db, bookmarks::BookmarkId, timing::QueryTimer};
+
+/// The copies in the Wayback Machine of a user's broken links, by bookmark.
+pub type ArchivedCopies = HashMap<BookmarkId, String>;
+
+/// What checking a bookmark's link found.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LinkCheck {
+    /// HTTP status, if there was a response
+    pub status: Option<u16>,
+    /// Why there was no response, if there wasn't
+    pub error: Option<String>,
+    pub is_broken: bool,
+    /// Closest copy in the Wayback Machine, if the link is broken and one was found, see
+    /// [`crate::wayback`]
+    pub archived_url: Option<String>,
+}
+
+/// A bookmark whose link is due to be checked.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DueLink {
+    pub bookmark_id: BookmarkId,
+    pub url: String,
+}
+
+/// Finds bookmarks whose links haven't been checked since `checked_before` (a UNIX timestamp), the
+/// never checked ones first. Bookmarks in the trash, of users who opted out of fetching, and with
+/// links other than http(s) aren't checked.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn get_due_links(db: &Db, checked_before: i64, limit: i64) -> Result<Vec<DueLink>> {
+    let _timer = QueryTimer::start("get_due_links");
+    let links = sqlx::query_as!(
+        DueLink,
+        r#"
+        select b.bookmark_id as "bookmark_id!: BookmarkId", b.url
+        from bookmarks b
+        left join bookmark_link_checks lc on lc.bookmark_id = b.bookmark_id
+        left join user_settings us on us.user_id = b.user_id
+        where b.deleted_at is null
+            and (b.url like 'http://%' or b.url like 'https://%')
+            and not coalesce(us.disable_fetch, false)
+            and (lc.checked_at is null or lc.checked_at < ?)
+        order by lc.checked_at nulls first
+        limit ?
+        "#,
+        checked_before,
+        limit
+    )
+    .fetch_all(&db.reader)
+    .await?;
+
+    Ok(links)
+}
+
+/// Saves what checking a bookmark's link found, replacing the last check.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn set_link_check(db: &Db, bookmark_id: BookmarkId, check: &LinkCheck) -> Result<()> {
+    let _timer = QueryTimer::start("set_link_check");
+    sqlx::query!(
+        r#"
+        insert or replace into bookmark_link_checks (bookmark_id, status, error, is_broken, archived_url)
+        values (?, ?, ?, ?, ?)
+        "#,
+        bookmark_id,
+        check.status,
+        check.error,
+        check.is_broken,
+        check.archived_url
+    )
+    .execute(&db.writer)
+    .await?;
+
+    Ok(())
+}
+
+/// Retrieves the copies in the Wayback Machine found for a user's broken links.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn get_archived_copies(db: &Db, user_id: Uuid) -> Result<ArchivedCopies> {
+    let _timer = QueryTimer::start("get_archived_copies");
+    let records = sqlx::query!(
+        r#"
+        select lc.bookmark_id as "bookmark_id!: BookmarkId", lc.archived_url as "archived_url!"
+        from bookmark_link_checks lc
+        join bookmarks b on b.bookmark_id = lc.bookmark_id
+        where b.user_id = ? and lc.is_broken and lc.archived_url is not null
+        "#,
+        user_id
+    )
+    .fetch_all(&db.reader)
+    .await?;
+
+    Ok(records
+        .into_iter()
+        .map(|record| (record.bookmark_id, record.archived_url))
+        .collect())
+}
@@ -0,0 +1,162 @@
+//! Failed sign-in database operations.
+//!
+//! Failures are counted per login name as typed in and per IP address, so password guessing gets
+//! locked out (see [`crate::lockout`]) whether it's aimed at one account or spread over many. The
+//! admin can see and lift lockouts on the dashboard.
+
+use anyhow::Result;
+
+use crate::db::{Db, timing::QueryTimer};
+
+/// A login name or IP address that's locked out, as shown on the admin dashboard.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lockout {
+    pub failure_id: i64,
+    /// `login` or `ip`
+    pub kind: String,
+    pub key: String,
+    pub failures: i64,
+    /// UNIX timestamp
+    pub locked_until: i64,
+}
+
+/// When the lockout of the login name `login`, or of the IP address `ip`, ends, whichever is
+/// later, if either is locked out.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn get_locked_until(db: &Db, login: &str, ip: Option<&str>) -> Result<Option<i64>> {
+    let _timer = QueryTimer::start("get_locked_until");
+    let locked_until = sqlx::query_scalar!(
+        r#"
+        select max(locked_until) as "locked_until: i64" from login_failures
+        where locked_until > unixepoch()
+        and ((kind = 'login' and key = ?) or (kind = 'ip' and key = ?))
+        "#,
+        login,
+        ip
+    )
+    .fetch_one(&db.reader)
+    .await?;
+
+    Ok(locked_until)
+}
+
+/// Counts a failed sign-in for `key` of `kind`, starting over if the last one was more than
+/// `window_secs` ago, and returns how many there have been in a row.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn record_failure(db: &Db, kind: &str, key: &str, window_secs: i64) -> Result<i64> {
+    let _timer = QueryTimer::start("record_login_failure");
+    let failures = sqlx::query_scalar!(
+        r#"
+        insert into login_failures (kind, key, failures, last_failed_at) values ($1, $2, 1, unixepoch())
+        on conflict (kind, key) do update set
+            failures = case when last_failed_at < unixepoch() - $3 then 1 else failures + 1 end,
+            last_failed_at = unixepoch()
+        returning failures
+        "#,
+        kind,
+        key,
+        window_secs
+    )
+    .fetch_one(&db.writer)
+    .await?;
+
+    Ok(failures)
+}
+
+/// Locks `key` of `kind` out until `locked_until`.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn lock(db: &Db, kind: &str, key: &str, locked_until: i64) -> Result<()> {
+    let _timer = QueryTimer::start("lock_login");
+    sqlx::query!(
+        "update login_failures set locked_until = ? where kind = ? and key = ?",
+        locked_until,
+        kind,
+        key
+    )
+    .execute(&db.writer)
+    .await?;
+
+    Ok(())
+}
+
+/// Forgets the failed sign-ins of the login name `login`, after a successful one.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn clear_login(db: &Db, login: &str) -> Result<()> {
+    let _timer = QueryTimer::start("clear_login_failures");
+    sqlx::query!("delete from login_failures where kind = 'login' and key = ?", login)
+        .execute(&db.writer)
+        .await?;
+
+    Ok(())
+}
+
+/// Retrieves every current lockout, the ones ending last first.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn get_lockouts(db: &Db) -> Result<Vec<Lockout>> {
+    let _timer = QueryTimer::start("get_lockouts");
+    let lockouts = sqlx::query_as!(
+        Lockout,
+        r#"
+        select failure_id as "failure_id!", kind, key, failures, locked_until as "locked_until!"
+        from login_failures
+        where locked_until > unixepoch()
+        order by locked_until desc
+        "#
+    )
+    .fetch_all(&db.reader)
+    .await?;
+
+    Ok(lockouts)
+}
+
+/// Lifts a lockout and forgets the failed sign-ins behind it. Returns `false` if there's no such
+/// record.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn unlock(db: &Db, failure_id: i64) -> Result<bool> {
+    let _timer = QueryTimer::start("unlock_login");
+    let result = sqlx::query!("delete from login_failures where failure_id = ?", failure_id)
+        .execute(&db.writer)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Deletes records of failed sign-ins that are no longer counted or locked out, returning how many
+/// were deleted.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn delete_stale_failures(db: &Db, window_secs: i64) -> Result<i64> {
+    let _timer = QueryTimer::start("delete_stale_login_failures");
+    let result = sqlx::query!(
+        r#"
+        delete from login_failures
+        where last_failed_at < unixepoch() - ?
+        and (locked_until is null or locked_until <= unixepoch())
+        "#,
+        window_secs
+    )
+    .execute(&db.writer)
+    .await?;
+
+    Ok(i64::try_from(result.rows_affected()).unwrap_or(i64::MAX))
+}
@@ -0,0 +1,187 @@
+//! Database housekeeping: the individual maintenance steps and the record of past runs.
+//!
+//! Everything here goes through the writer pool, since even `PRAGMA optimize` may write.
+
+use anyhow::Result;
+
+use crate::db::{Db, bookmarks::TRASH_RETENTION_DAYS, timing::QueryTimer};
+
+/// Full-text search tables whose index segments are merged during maintenance.
+const FTS_TABLES: &[&str] = &[];
+/// Work limit per FTS merge step, see <https://sqlite.org/fts5.html#the_merge_command>.
+const FTS_MERGE_PAGES: i64 = 500;
+/// `PRAGMA auto_vacuum` value for incremental mode.
+const AUTO_VACUUM_INCREMENTAL: i64 = 2;
+
+/// Outcome of one maintenance run, as shown on the admin dashboard.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MaintenanceRun {
+    /// UNIX timestamp
+    pub started_at: i64,
+    pub duration_ms: i64,
+    pub sessions_deleted: i64,
+    /// Temporary bookmarks deleted because they expired
+    pub bookmarks_expired: i64,
+    /// Bookmarks deleted for good after their time in the trash
+    pub bookmarks_purged: i64,
+    pub tags_deleted: i64,
+    /// Free database pages returned to the file system
+    pub pages_freed: i64,
+    /// Errors from any steps that failed; the other steps still run
+    pub error: Option<String>,
+}
+
+/// Lets `SQLite` update its query planner statistics where it thinks they're stale.
+///
+/// # Errors
+///
+/// Returns an error if the pragma fails.
+pub async fn optimize(db: &Db) -> Result<()> {
+    let _timer = QueryTimer::start("optimize");
+    sqlx::query("pragma optimize").execute(&db.writer).await?;
+    Ok(())
+}
+
+/// Returns free pages to the file system, returning how many were freed.
+///
+/// Databases created before incremental auto-vacuum was enabled are switched over first, which
+/// needs a one-off full `VACUUM`.
+///
+/// # Errors
+///
+/// Returns an error if a pragma or the vacuum fails.
+pub async fn incremental_vacuum(db: &Db) -> Result<i64> {
+    let _timer = QueryTimer::start("incremental_vacuum");
+    let free_before: i64 = sqlx::query_scalar("pragma freelist_count").fetch_one(&db.writer).await?;
+    let auto_vacuum: i64 = sqlx::query_scalar("pragma auto_vacuum").fetch_one(&db.writer).await?;
+
+    if auto_vacuum == AUTO_VACUUM_INCREMENTAL {
+        sqlx::query("pragma incremental_vacuum").execute(&db.writer).await?;
+    } else {
+        sqlx::query("pragma auto_vacuum = incremental").execute(&db.writer).await?;
+        sqlx::query("vacuum").execute(&db.writer).await?;
+    }
+
+    let free_after: i64 = sqlx::query_scalar("pragma freelist_count").fetch_one(&db.writer).await?;
+    Ok(free_before - free_after)
+}
+
+/// Merges full-text index segments so searches have fewer to look through.
+///
+/// # Errors
+///
+/// Returns an error if a merge fails.
+pub async fn merge_fts_indexes(db: &Db) -> Result<()> {
+    let _timer = QueryTimer::start("merge_fts_indexes");
+    for table in FTS_TABLES {
+        sqlx::query(&format!("insert into {table}({table}, rank) values ('merge', ?)"))
+            .bind(FTS_MERGE_PAGES)
+            .execute(&db.writer)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Deletes temporary bookmarks whose expiry has passed, returning how many were deleted.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn delete_expired_bookmarks(db: &Db) -> Result<i64> {
+    let _timer = QueryTimer::start("delete_expired_bookmarks");
+    let result = sqlx::query!("delete from bookmarks where expires_at <= unixepoch()")
+        .execute(&db.writer)
+        .await?;
+
+    Ok(i64::try_from(result.rows_affected()).unwrap_or(i64::MAX))
+}
+
+/// Deletes bookmarks that have been in the trash for longer than [`TRASH_RETENTION_DAYS`], returning
+/// how many were deleted.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn purge_trashed_bookmarks(db: &Db) -> Result<i64> {
+    let _timer = QueryTimer::start("purge_trashed_bookmarks");
+    let retention_secs = TRASH_RETENTION_DAYS * 86400;
+    let result = sqlx::query!("delete from bookmarks where deleted_at <= unixepoch() - ?", retention_secs)
+        .execute(&db.writer)
+        .await?;
+
+    Ok(i64::try_from(result.rows_affected()).unwrap_or(i64::MAX))
+}
+
+/// Deletes tags no bookmark uses anymore, returning how many were deleted.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn delete_orphan_tags(db: &Db) -> Result<i64> {
+    let _timer = QueryTimer::start("delete_orphan_tags");
+    let result = sqlx::query!(
+        r#"
+        delete from tags
+        where not exists (
+            select 1 from bookmark_tags bt
+            where bt.tag_id = tags.tag_id
+        )
+        "#
+    )
+    .execute(&db.writer)
+    .await?;
+
+    Ok(i64::try_from(result.rows_affected()).unwrap_or(i64::MAX))
+}
+
+/// Saves the outcome of a maintenance run.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn record_run(db: &Db, run: &MaintenanceRun) -> Result<()> {
+    let _timer = QueryTimer::start("record_run");
+    sqlx::query!(
+        r#"
+        insert into maintenance_runs (
+            started_at, duration_ms, sessions_deleted, bookmarks_expired, bookmarks_purged, tags_deleted, pages_freed, error
+        )
+        values (?, ?, ?, ?, ?, ?, ?, ?)
+        "#,
+        run.started_at,
+        run.duration_ms,
+        run.sessions_deleted,
+        run.bookmarks_expired,
+        run.bookmarks_purged,
+        run.tags_deleted,
+        run.pages_freed,
+        run.error
+    )
+    .execute(&db.writer)
+    .await?;
+
+    Ok(())
+}
+
+/// Retrieves the most recent maintenance runs, newest first.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn get_recent_runs(db: &Db, limit: i64) -> Result<Vec<MaintenanceRun>> {
+    let _timer = QueryTimer::start("get_recent_runs");
+    let runs = sqlx::query_as!(
+        MaintenanceRun,
+        r#"
+        select started_at, duration_ms, sessions_deleted, bookmarks_expired, bookmarks_purged, tags_deleted, pages_freed, error
+        from maintenance_runs
+        order by run_id desc
+        limit ?
+        "#,
+        limit
+    )
+    .fetch_all(&db.reader)
+    .await?;
+
+    Ok(runs)
+}
@@ -3,60 +3,163 @@
 //! This module provides database connection pooling, automatic migrations,
 //! and submodules for specific database operations.
 
+pub mod admin_search;
+pub mod api_tokens;
+pub mod blocked_domains;
+pub mod bookmark_shares;
 pub mod bookmarks;
+pub mod collections;
+pub mod feature_flags;
+pub mod fetch_failures;
+pub mod guest_links;
+pub mod imports;
+pub mod instance_settings;
+pub mod link_checks;
+pub mod login_failures;
+pub mod maintenance;
+pub mod onboarding;
+pub mod page_text;
+pub mod password_resets;
+#[cfg(feature = "redis")]
+pub mod redis_sessions;
+pub mod restore_points;
+pub mod seed;
+pub mod settings;
+pub mod snapshots;
+pub mod subscriptions;
 pub mod tags;
+pub mod thumbnails;
+pub mod timing;
+pub mod translations;
+pub mod two_factor;
+pub mod url_rules;
 pub mod user_session;
 pub mod users;
+use std::{str::FromStr as _, time::Duration};
+
 use anyhow::{Context as _, Result};
-use sqlx::{SqlitePool, sqlite::SqlitePoolOptions};
+use sqlx::{
+    SqlitePool,
+    sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteSynchronous},
+};
+
+const MAX_READ_CONNECTIONS: u32 = 10;
+/// How long a connection waits on a lock held by another connection before giving up.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
 
-const MAX_CONNECTIONS: u32 = 10;
+/// Database handle with separate pools for reading and writing.
+///
+/// `SQLite` allows only one writer at a time, so writes go through a single-connection pool and
+/// queue there, instead of many connections fighting over the write lock and timing out. In WAL
+/// mode readers don't block on the writer, so reads get their own larger pool.
+///
+/// The db layer functions pick the right pool; handlers only pass `&Db` along.
+#[derive(Clone, Debug)]
+pub struct Db {
+    /// Read-only connections for queries.
+    pub reader: SqlitePool,
+    /// Single connection for anything that writes.
+    pub writer: SqlitePool,
+}
 
-/// Establishes a connection pool to the `SQLite` database.
+/// Establishes the reader and writer connection pools to the `SQLite` database.
+///
+/// If a `key` is given, the database is opened with it as a `SQLCipher` encrypted database, which
+/// needs a build with the `sqlcipher` feature.
 ///
 /// This function:
-/// 1. Creates a connection pool with configured limits
-/// 2. Runs any pending database migrations
+/// 1. Creates the single-connection writer pool with WAL mode enabled
+/// 2. Checks that the database is encrypted, if there's a key
+/// 3. Runs any pending database migrations
 /// 3. In debug mode, creates a default admin user if needed
+/// 4. Creates the read-only reader pool
 ///
 /// # Errors
 ///
 /// Returns an error if:
-/// - Database connection fails
+/// - Database connection fails, e.g. because the key is wrong
+/// - There's a key but this build doesn't support `SQLCipher`
 /// - Migrations fail to run
 /// - Debug initialization fails
-pub async fn connect(pool_uri: &str) -> Result<SqlitePool> {
-    let pool = SqlitePoolOptions::new()
-        .max_connections(MAX_CONNECTIONS)
-        .connect(pool_uri)
+pub async fn connect(pool_uri: &str, key: Option<&str>) -> Result<Db> {
+    let mut options = SqliteConnectOptions::from_str(pool_uri)
+        .with_context(|| format!("🔥 Invalid database URL '{pool_uri}'"))?
+        .journal_mode(SqliteJournalMode::Wal)
+        .synchronous(SqliteSynchronous::Normal)
+        .busy_timeout(BUSY_TIMEOUT);
+    if let Some(key) = key {
+        // sqlx always runs the key pragma first, as SQLCipher requires
+        options = options.pragma("key", format!("'{}'", key.replace('\'', "''")));
+    }
+
+    let writer = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect_with(options.clone())
         .await
-        .context("Error: 🔥 unable to connect to the database!")?;
+        .with_context(|| {
+            if key.is_some() {
+                "Error: 🔥 unable to connect to the database! Check that the database key is right."
+            } else {
+                "Error: 🔥 unable to connect to the database!"
+            }
+        })?;
+
+    if key.is_some() {
+        // Plain SQLite silently ignores the key pragma, which would leave the database unencrypted
+        let cipher_version: Option<String> = sqlx::query_scalar("pragma cipher_version").fetch_optional(&writer).await?;
+        let Some(cipher_version) = cipher_version else {
+            anyhow::bail!("🔥 A database key is set, but this build has no SQLCipher support. Build with `--features sqlcipher`.");
+        };
+        println!("🔐 Database is encrypted with SQLCipher {cipher_version}.");
+    }
 
     sqlx::migrate!()
-        .run(&pool)
+        .run(&writer)
         .await
         .with_context(|| format!("🚨 Could not run database migrations for database at '{pool_uri}'"))?;
 
-    #[cfg(debug_assertions)]
-    init_for_dev(&pool).await?;
+    // Load testing builds seed their own fixtures, see `crate::loadtest`
+    #[cfg(all(debug_assertions, not(feature = "loadtest")))]
+    init_for_dev(&writer, seed::SeedProfile::Demo).await?;
+
+    let reader = SqlitePoolOptions::new()
+        .max_connections(MAX_READ_CONNECTIONS)
+        .connect_with(options.read_only(true))
+        .await
+        .context("Error: 🔥 unable to open read connections to the database!")?;
 
     println!("✅ Successfully connected to database!");
-    Ok(pool)
+    Ok(Db { reader, writer })
 }
 
+/// Password hash of the development admin user, for the password `admin123`; seeded users share it.
+const DEV_ADMIN_PASSWORD_HASH: &str = "$argon2id$v=19$m=19456,t=2,p=1$LL8PlWjHaOuA6gLK2+x1fQ$LY791mB/ymrCS/HgwSHqj4Mc9eEnOcZB/OT5bu9+GFY";
+
 /// Initializes development-specific database data.
 ///
-/// Creates a default admin user for testing purposes in debug builds.
+/// Creates a default admin user for testing purposes in debug builds and load testing builds, then
+/// seeds an empty database as set with `PAGEPOUCH_SEED` (see [`seed`]), or else with `profile`.
+///
+/// # Errors
+///
+/// Returns an error if database operations fail.
+#[cfg(any(debug_assertions, feature = "loadtest"))]
+pub(crate) async fn init_for_dev(pool: &SqlitePool, profile: seed::SeedProfile) -> Result<()> {
+    add_dev_admin(pool).await?;
+    seed::seed(pool, seed::SeedProfile::from_env(profile)?, DEV_ADMIN_PASSWORD_HASH).await?;
+
+    Ok(())
+}
+
+/// Adds the development admin user if it doesn't exist yet.
 /// Username: admin
 /// Password: admin123 (pre-hashed with Argon2)
 ///
 /// # Errors
 ///
 /// Returns an error if database operations fail.
-#[cfg(debug_assertions)]
-async fn init_for_dev(pool: &SqlitePool) -> Result<()> {
+async fn add_dev_admin(pool: &SqlitePool) -> Result<()> {
     const ADMIN_USERNAME: &str = "admin";
-    const ADMIN_PASSWORD_HASH: &str = "$argon2id$v=19$m=19456,t=2,p=1$LL8PlWjHaOuA6gLK2+x1fQ$LY791mB/ymrCS/HgwSHqj4Mc9eEnOcZB/OT5bu9+GFY";
     let has_admin = sqlx::query!(
         r#"
             select user_id from users
@@ -73,197 +176,18 @@ async fn init_for_dev(pool: &SqlitePool) -> Result<()> {
             r#"
                 insert into users (
                     username,
-                    password_hash
+                    password_hash,
+                    is_admin
                 )
-                values (?, ?)
+                values (?, ?, true)
             "#,
             ADMIN_USERNAME,
-            ADMIN_PASSWORD_HASH
+            DEV_ADMIN_PASSWORD_HASH
         )
         .execute(pool)
         .await?;
         println!("✨ Added dev user: admin");
     }
 
-    // Add dummy data for development
-    populate_dummy_data(pool).await?;
-
-    Ok(())
-}
-
-/// Populates the database with dummy bookmarks and tags for development.
-///
-/// Only runs once - checks if data already exists before adding.
-///
-/// # Errors
-///
-/// Returns an error if database operations fail.
-#[cfg(debug_assertions)]
-#[allow(clippy::too_many_lines)] // Dev-only function with extensive test data - splitting would hurt readability
-async fn populate_dummy_data(pool: &SqlitePool) -> Result<()> {
-    // Check if we already have bookmarks data
-    let existing_bookmarks = sqlx::query!("select count(*) as count from bookmarks").fetch_one(pool).await?;
-
-    if existing_bookmarks.count > 0 {
-        return Ok(()); // Data already exists
-    }
-
-    println!("📚 Adding dummy bookmarks and tags for development...");
-
-    // Get admin user ID
-    let admin_user = sqlx::query!("select user_id from users where username = 'admin'")
-        .fetch_one(pool)
-        .await?;
-
-    // Create tags first
-    let tag_data = [
-        "rust",
-        "programming",
-        "web-dev",
-        "javascript",
-        "htmx",
-        "css",
-        "web-framework",
-        "axum",
-        "systems",
-        "tokio",
-        "framework",
-        "simple",
-        "backend",
-        "database",
-        "sqlite",
-        "authentication",
-        "api",
-        "async",
-        "http",
-        "server",
-        "middleware",
-        "routing",
-        "templates",
-        "bookmarks",
-        "self-hosted",
-    ];
-
-    // Insert tags
-    for name in &tag_data {
-        sqlx::query!("insert or ignore into tags (name) values (?)", name)
-            .execute(pool)
-            .await?;
-    }
-
-    // Create bookmarks with associated tags
-    let bookmark_data = [
-        (
-            "https://rust-lang.org",
-            "The Rust Programming Language",
-            "A systems programming language that is blazingly fast, memory-safe, and thread-safe.",
-            &["rust", "programming", "systems"][..],
-        ),
-        (
-            "https://htmx.org",
-            "HTMX - High Power Tools for HTML",
-            "htmx allows you to access modern browser features directly from HTML, rather than using JavaScript.",
-            &["web-dev", "javascript", "htmx"],
-        ),
-        (
-            "https://github.com/tokio-rs/axum",
-            "Axum Web Framework for Rust",
-            "Axum is a web application framework that focuses on ergonomics and modularity.",
-            &[
-                "rust",
-                "web-framework",
-                "axum",
-                "tokio",
-                "async",
-                "http",
-                "server",
-                "middleware",
-                "routing",
-            ],
-        ),
-        (
-            "https://simplecss.org",
-            "Simple.css - A CSS Framework for Semantic HTML",
-            "A CSS framework for developers who want their websites to look good without the complexity.",
-            &["css", "framework", "simple"],
-        ),
-        (
-            "https://sqlite.org",
-            "SQLite Database Engine",
-            "SQLite is a C library that provides a lightweight disk-based database.",
-            &["database", "sqlite", "backend"],
-        ),
-        (
-            "https://docs.rs/askama/latest/askama/",
-            "Askama Template Engine",
-            "Type-safe, compiled Jinja-like templates for Rust.",
-            &["rust", "templates", "web-dev"],
-        ),
-        (
-            "https://github.com/launchbadge/sqlx",
-            "SQLx - Rust SQL Toolkit",
-            "The Rust SQL toolkit. An async, pure Rust SQL crate featuring compile-time checked queries.",
-            &["rust", "database", "async", "api"],
-        ),
-        (
-            "https://developer.mozilla.org/en-US/docs/Web/API/Fetch_API",
-            "Fetch API - Web APIs | MDN",
-            "The Fetch API provides an interface for fetching resources.",
-            &["javascript", "web-dev", "api", "http"],
-        ),
-        (
-            "https://github.com/SergioBenitez/Rocket",
-            "Rocket - Web Framework for Rust",
-            "A web framework for Rust that makes it simple to write fast, secure web applications.",
-            &["rust", "web-framework", "server", "api"],
-        ),
-        (
-            "https://tailwindcss.com",
-            "Tailwind CSS",
-            "A utility-first CSS framework for rapidly building custom user interfaces.",
-            &["css", "framework", "web-dev"],
-        ),
-    ];
-
-    // Insert bookmarks and link to tags
-    for (url, title, description, tag_names) in &bookmark_data {
-        // Insert bookmark and get the generated bookmark_id
-        let bookmark_result = sqlx::query!(
-            r#"
-            insert into bookmarks (user_id, url, title, description)
-            values (?, ?, ?, ?)
-            returning bookmark_id
-            "#,
-            admin_user.user_id,
-            url,
-            title,
-            description
-        )
-        .fetch_one(pool)
-        .await?;
-
-        let bookmark_id = &bookmark_result.bookmark_id;
-
-        // Link bookmark to tags
-        for tag_name in *tag_names {
-            let tag = sqlx::query!("select tag_id from tags where name = ?", tag_name)
-                .fetch_one(pool)
-                .await?;
-
-            sqlx::query!(
-                "insert into bookmark_tags (bookmark_id, tag_id) values (?, ?)",
-                bookmark_id,
-                tag.tag_id
-            )
-            .execute(pool)
-            .await?;
-        }
-    }
-
-    println!(
-        "✨ Added {} tags and {} bookmarks for development!",
-        tag_data.len(),
-        bookmark_data.len()
-    );
     Ok(())
 }
@@ -0,0 +1,52 @@
+//! Onboarding users at their first sign-in, see [`crate::onboarding`].
+
+use anyhow::Result;
+use uuid::Uuid;
+
+use crate::db::{
+    Db,
+    bookmarks::{BookmarkOrigin, BookmarkSource, NewBookmark, insert_bookmark},
+    timing::QueryTimer,
+};
+
+/// Marks a user as past their first sign-in, giving them the given bookmarks and smart collections
+/// (as name and rule) along with it. Returns `false`, adding nothing, if they already were.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn onboard_user(db: &Db, user_id: Uuid, bookmarks: &[NewBookmark], collections: &[(&str, &str)]) -> Result<bool> {
+    let _timer = QueryTimer::start("onboard_user");
+    let mut tx = db.writer.begin().await?;
+    let result = sqlx::query!(
+        "update users set onboarded_at = unixepoch() where user_id = ? and onboarded_at is null",
+        user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Ok(false);
+    }
+
+    let origin = BookmarkOrigin::from(BookmarkSource::Starter);
+    for bookmark in bookmarks {
+        insert_bookmark(&mut tx, user_id, bookmark, &origin).await?;
+    }
+    for (name, query) in collections {
+        sqlx::query!(
+            r#"
+            insert into smart_collections (user_id, name, query)
+            values (?, ?, ?)
+            on conflict (user_id, name) do nothing
+            "#,
+            user_id,
+            name,
+            query
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+
+    Ok(true)
+}
@@ -0,0 +1,30 @@
+//! Readable text of bookmarked pages, searched with `content:` terms, see [`crate::page_text`].
+
+use anyhow::Result;
+
+use crate::db::{Db, bookmarks::BookmarkId, timing::QueryTimer};
+
+/// Saves the text of a bookmark's page, replacing any earlier one.
+///
+/// An upsert rather than `insert or replace`, so the search index is kept in step by its update
+/// trigger.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn set_page_text(db: &Db, bookmark_id: BookmarkId, content: &str) -> Result<()> {
+    let _timer = QueryTimer::start("set_page_text");
+    sqlx::query!(
+        r#"
+        insert into bookmark_page_text (bookmark_id, content)
+        values (?, ?)
+        on conflict (bookmark_id) do update set content = excluded.content, extracted_at = unixepoch()
+        "#,
+        bookmark_id,
+        content
+    )
+    .execute(&db.writer)
+    .await?;
+
+    Ok(())
+}
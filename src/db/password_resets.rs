@@ -0,0 +1,175 @@
+//! Password reset database operations.
+//!
+//! Someone who forgot their password can have a link to reset it emailed to the address on their
+//! account (see [`crate::mail`]). The link carries a signed [`PasswordResetToken`], but it's the
+//! row here that makes it valid: it works once and until it expires, and resetting the password
+//! uses up any other links the user was sent.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::db::{Db, timing::QueryTimer, users::User};
+
+/// Token in a password reset link.
+///
+/// Converted to a JWT and signed, like the session cookie. It also carries the expiry, so expired
+/// links are turned away without looking them up.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+pub struct PasswordResetToken {
+    pub reset_id: Uuid,
+    /// UNIX timestamp
+    pub expires_at: i64,
+}
+
+/// A user a password reset link can be sent to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResetRecipient {
+    pub user_id: Uuid,
+    pub username: String,
+    pub email: String,
+}
+
+/// Looks up the user with `login` as their username or email, either case-insensitive, if they
+/// have an email address to send a reset link to and aren't revoked. If one user's username is
+/// another's email, the username wins, as when signing in.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn get_reset_recipient(db: &Db, login: &str) -> Result<Option<ResetRecipient>> {
+    let _timer = QueryTimer::start("get_reset_recipient");
+    let recipient = sqlx::query_as!(
+        ResetRecipient,
+        r#"
+        select user_id as "user_id!: Uuid", username, email as "email!: String"
+        from (
+            select user_id, username, email, is_revoked
+            from users where username = $1 or email = $1
+            order by username = $1 desc
+            limit 1
+        )
+        where email is not null and not is_revoked
+        "#,
+        login
+    )
+    .fetch_optional(&db.reader)
+    .await?;
+
+    Ok(recipient)
+}
+
+/// Creates a password reset for a user, working until `expires_at`, returning its ID.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn create_password_reset(db: &Db, user_id: Uuid, expires_at: i64) -> Result<Uuid> {
+    let _timer = QueryTimer::start("create_password_reset");
+    let reset_id = sqlx::query_scalar!(
+        r#"
+        insert into password_resets (user_id, expires_at)
+        values (?, ?)
+        returning reset_id as "reset_id!: Uuid"
+        "#,
+        user_id,
+        expires_at
+    )
+    .fetch_one(&db.writer)
+    .await?;
+
+    Ok(reset_id)
+}
+
+/// Whether a password reset can still be used: it hasn't been, or expired, and its user hasn't
+/// been revoked.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn is_password_reset_valid(db: &Db, reset_id: Uuid) -> Result<bool> {
+    let _timer = QueryTimer::start("is_password_reset_valid");
+    let valid = sqlx::query_scalar!(
+        r#"
+        select exists (
+            select 1 from password_resets r
+            join users u on u.user_id = r.user_id
+            where r.reset_id = ? and r.used_at is null and r.expires_at > unixepoch() and not u.is_revoked
+        ) as "valid!: bool"
+        "#,
+        reset_id
+    )
+    .fetch_one(&db.reader)
+    .await?;
+
+    Ok(valid)
+}
+
+/// Uses up a password reset, setting its user's password to the one hashed as `password_hash`
+/// (see [`crate::db::users::create_hash`]), along with the user's other resets. Returns the user,
+/// or `None` if the reset can't be used, see [`is_password_reset_valid`].
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn complete_password_reset(db: &Db, reset_id: Uuid, password_hash: &str) -> Result<Option<User>> {
+    let _timer = QueryTimer::start("complete_password_reset");
+    let mut tx = db.writer.begin().await?;
+    let Some(user_id) = sqlx::query_scalar!(
+        r#"
+        update password_resets set used_at = unixepoch()
+        where reset_id = ? and used_at is null and expires_at > unixepoch()
+        returning user_id as "user_id!: Uuid"
+        "#,
+        reset_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    let Some(user) = sqlx::query_as!(
+        User,
+        r#"
+        update users set password_hash = ?, updated_at = unixepoch()
+        where user_id = ? and not is_revoked
+        returning
+            user_id as "user_id!: Uuid",
+            username,
+            is_revoked,
+            is_admin
+        "#,
+        password_hash,
+        user_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    sqlx::query!(
+        "update password_resets set used_at = unixepoch() where user_id = ? and used_at is null",
+        user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(Some(user))
+}
+
+/// Deletes password resets that were used or have expired, returning how many were deleted.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn delete_finished_resets(db: &Db) -> Result<i64> {
+    let _timer = QueryTimer::start("delete_finished_resets");
+    let result = sqlx::query!("delete from password_resets where used_at is not null or expires_at <= unixepoch()")
+        .execute(&db.writer)
+        .await?;
+
+    Ok(i64::try_from(result.rows_affected()).unwrap_or(i64::MAX))
+}
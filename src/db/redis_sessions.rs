@@ -0,0 +1,161 @@
+//! Sessions kept in Redis, for builds with the `redis` feature and `PAGEPOUCH_REDIS_URL` set.
+//!
+//! Each session is a key holding its user's ID, set to expire with the session, so Redis drops
+//! expired sessions on its own and extending one is an `EXPIREAT` rather than a write to the
+//! database file.
+
+use anyhow::{Context as _, Result};
+use redis::{AsyncCommands as _, Client, aio::MultiplexedConnection};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use crate::db::user_session::{SessionFuture, SessionStore, SessionToken, StoredSession};
+
+/// Prefix of the keys sessions are kept under, so the Redis database can be shared.
+const KEY_PREFIX: &str = "pagepouch:session:";
+
+/// Keeps sessions in Redis.
+pub struct RedisSessionStore {
+    client: Client,
+    /// Shared by all requests; replaced after the connection breaks, e.g. when Redis restarts
+    connection: Mutex<Option<MultiplexedConnection>>,
+}
+
+impl RedisSessionStore {
+    /// Connects to the Redis server at `url`, e.g. `redis://127.0.0.1:6379/0`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the URL is invalid or the server can't be reached.
+    pub async fn connect(url: &str) -> Result<Self> {
+        let client = Client::open(url).with_context(|| "🔥 Redis URL is not valid")?;
+        let connection = client
+            .get_multiplexed_async_connection()
+            .await
+            .with_context(|| "🔥 Could not connect to Redis")?;
+
+        Ok(Self {
+            client,
+            connection: Mutex::new(Some(connection)),
+        })
+    }
+
+    async fn connection(&self) -> Result<MultiplexedConnection> {
+        let mut connection = self.connection.lock().await;
+        if let Some(connection) = connection.as_ref() {
+            return Ok(connection.clone());
+        }
+        let new = self
+            .client
+            .get_multiplexed_async_connection()
+            .await
+            .with_context(|| "Could not reconnect to Redis")?;
+        *connection = Some(new.clone());
+        Ok(new)
+    }
+
+    /// Passes on the result of a command, dropping the connection if it broke so the next command
+    /// reconnects.
+    async fn checked<T>(&self, result: redis::RedisResult<T>) -> Result<T> {
+        if let Err(err) = &result
+            && err.is_unrecoverable_error()
+        {
+            *self.connection.lock().await = None;
+        }
+        Ok(result?)
+    }
+}
+
+fn key(session_token: SessionToken) -> String {
+    format!("{KEY_PREFIX}{}", session_token.0)
+}
+
+impl SessionStore for RedisSessionStore {
+    fn create(&self, user_id: Uuid, expires_at: i64) -> SessionFuture<'_, SessionToken> {
+        Box::pin(async move {
+            let session_token = SessionToken(Uuid::new_v4());
+            let key = key(session_token);
+            let mut connection = self.connection().await?;
+            let result = redis::pipe()
+                .atomic()
+                .set(&key, user_id.to_string())
+                .ignore()
+                .expire_at(&key, expires_at)
+                .ignore()
+                .query_async::<()>(&mut connection)
+                .await;
+            self.checked(result).await?;
+
+            Ok(session_token)
+        })
+    }
+
+    fn get(&self, session_token: SessionToken) -> SessionFuture<'_, Option<StoredSession>> {
+        Box::pin(async move {
+            let key = key(session_token);
+            let mut connection = self.connection().await?;
+            let result = redis::pipe()
+                .atomic()
+                .get(&key)
+                .ttl(&key)
+                .query_async::<(Option<String>, i64)>(&mut connection)
+                .await;
+            let (user_id, ttl) = self.checked(result).await?;
+
+            // A key without an expiry (TTL -1) wasn't set by us
+            let Some(user_id) = user_id.filter(|_| ttl >= 0) else {
+                return Ok(None);
+            };
+            Ok(Some(StoredSession {
+                user_id: user_id.parse().with_context(|| format!("Session {key} has an invalid user ID"))?,
+                expires_at: chrono::Utc::now().timestamp() + ttl,
+            }))
+        })
+    }
+
+    fn set_expiry(&self, session_token: SessionToken, expires_at: i64) -> SessionFuture<'_, bool> {
+        Box::pin(async move {
+            let mut connection = self.connection().await?;
+            let result = connection.expire_at::<_, bool>(key(session_token), expires_at).await;
+            self.checked(result).await
+        })
+    }
+
+    fn remove(&self, session_token: SessionToken) -> SessionFuture<'_, ()> {
+        Box::pin(async move {
+            let mut connection = self.connection().await?;
+            let result = connection.del::<_, ()>(key(session_token)).await;
+            self.checked(result).await
+        })
+    }
+
+    fn remove_for_user(&self, user_id: Uuid) -> SessionFuture<'_, u64> {
+        Box::pin(async move {
+            let mut connection = self.connection().await?;
+            // Sessions are keyed by token, so finding a user's means going through them all
+            let mut keys = Vec::new();
+            {
+                let result = connection.scan_match::<_, String>(format!("{KEY_PREFIX}*")).await;
+                let mut iter = self.checked(result).await?;
+                while let Some(key) = iter.next_item().await {
+                    keys.push(key);
+                }
+            }
+            let user_id = user_id.to_string();
+            let mut removed = 0;
+            for key in keys {
+                let result = connection.get::<_, Option<String>>(&key).await;
+                if self.checked(result).await?.as_deref() == Some(user_id.as_str()) {
+                    let result = connection.del::<_, u64>(&key).await;
+                    removed += self.checked(result).await?;
+                }
+            }
+            Ok(removed)
+        })
+    }
+
+    fn remove_expired(&self, _now: i64) -> SessionFuture<'_, u64> {
+        // Redis drops sessions when their keys expire
+        Box::pin(async { Ok(0) })
+    }
+}
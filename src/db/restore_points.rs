@@ -0,0 +1,416 @@
+//! Restore point database operations.
+//!
+//! Taking a restore point reads a user's bookmarks, tags and smart collections into a
+//! [`Snapshot`]; rolling back to one writes only what differs from the current state, in a single
+//! transaction, after saving the current state as another restore point so the rollback can be
+//! undone too.
+
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Result;
+use sqlx::SqliteConnection;
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        Db,
+        bookmarks::{BookmarkId, get_created_ago, link_tags},
+        tags::get_or_create_tag,
+        timing::QueryTimer,
+    },
+    restore::{MAX_RESTORE_POINTS, SNAPSHOT_VERSION, Snapshot, SnapshotBookmark, SnapshotCollection},
+};
+
+/// A restore point, as listed on the settings page.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RestorePoint {
+    pub restore_point_id: Uuid,
+    /// What it was made for, e.g. "Before importing Bookmarks"
+    pub label: String,
+    /// Bookmarks outside the trash at the time
+    pub bookmark_count: i64,
+    /// e.g. "3 days ago"
+    pub created_ago: String,
+}
+
+/// Retrieves a user's restore points, newest first.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn get_restore_points(db: &Db, user_id: Uuid) -> Result<Vec<RestorePoint>> {
+    let _timer = QueryTimer::start("get_restore_points");
+    let records = sqlx::query!(
+        r#"
+        select restore_point_id as "restore_point_id!: Uuid", label, bookmark_count, created_at
+        from restore_points
+        where user_id = ?
+        order by created_at desc, rowid desc
+        "#,
+        user_id
+    )
+    .fetch_all(&db.reader)
+    .await?;
+
+    Ok(records
+        .into_iter()
+        .map(|record| RestorePoint {
+            restore_point_id: record.restore_point_id,
+            label: record.label,
+            bookmark_count: record.bookmark_count,
+            created_ago: get_created_ago(record.created_at),
+        })
+        .collect())
+}
+
+/// Saves the current state of a user's bookmarks as a restore point, dropping the oldest beyond
+/// [`MAX_RESTORE_POINTS`]. Returns its ID.
+///
+/// # Errors
+///
+/// Returns an error if database operations fail.
+pub async fn create_restore_point(db: &Db, user_id: Uuid, label: &str) -> Result<Uuid> {
+    let _timer = QueryTimer::start("create_restore_point");
+    let mut tx = db.writer.begin().await?;
+
+    let snapshot = take_snapshot(&mut tx, user_id).await?;
+    let restore_point_id = insert_restore_point(&mut tx, user_id, label, &snapshot).await?;
+    prune_restore_points(&mut tx, user_id, restore_point_id).await?;
+
+    tx.commit().await?;
+
+    Ok(restore_point_id)
+}
+
+/// Deletes one of a user's restore points. Returns whether anything was deleted.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn delete_restore_point(db: &Db, user_id: Uuid, restore_point_id: Uuid) -> Result<bool> {
+    let _timer = QueryTimer::start("delete_restore_point");
+    let result = sqlx::query!(
+        "delete from restore_points where user_id = ? and restore_point_id = ?",
+        user_id,
+        restore_point_id
+    )
+    .execute(&db.writer)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Puts a user's bookmarks, tags and smart collections back the way they were at a restore point,
+/// first saving how they are now as a new one. Returns the restore point's label, or `None` if the
+/// user has no such restore point.
+///
+/// Bookmarks saved since are deleted outright rather than moved to the trash, as the new restore
+/// point still has them.
+///
+/// # Errors
+///
+/// Returns an error if the restore point can't be read or database operations fail; nothing is
+/// changed then.
+pub async fn roll_back_to(db: &Db, user_id: Uuid, restore_point_id: Uuid) -> Result<Option<String>> {
+    let _timer = QueryTimer::start("roll_back_to");
+    let mut tx = db.writer.begin().await?;
+
+    let Some(record) = sqlx::query!(
+        "select label, snapshot from restore_points where user_id = ? and restore_point_id = ?",
+        user_id,
+        restore_point_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    else {
+        return Ok(None);
+    };
+    let target = Snapshot::decode(&record.snapshot)?;
+
+    let current = take_snapshot(&mut tx, user_id).await?;
+    let label = format!("Before restoring \u{201c}{}\u{201d}", record.label);
+    insert_restore_point(&mut tx, user_id, &label, &current).await?;
+    // Keep the one restored from, too, even if it's the oldest
+    prune_restore_points(&mut tx, user_id, restore_point_id).await?;
+
+    restore_bookmarks(&mut tx, user_id, &current, &target).await?;
+    restore_collections(&mut tx, user_id, &current.collections, &target.collections).await?;
+    if current.tag_colors != target.tag_colors {
+        sqlx::query!("delete from tag_colors where user_id = ?", user_id)
+            .execute(&mut *tx)
+            .await?;
+        for (tag_name, color) in &target.tag_colors {
+            let tag_id = get_or_create_tag(&mut tx, tag_name).await?;
+            sqlx::query!(
+                "insert into tag_colors (user_id, tag_id, color) values (?, ?, ?)",
+                user_id,
+                tag_id,
+                color
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+
+    tx.commit().await?;
+
+    Ok(Some(record.label))
+}
+
+/// Reads everything a restore point keeps about a user.
+async fn take_snapshot(tx: &mut SqliteConnection, user_id: Uuid) -> Result<Snapshot> {
+    let bookmarks = sqlx::query_as!(
+        SnapshotBookmark,
+        r#"
+        select
+            bookmark_id as "bookmark_id!: BookmarkId",
+            url,
+            original_url,
+            title,
+            description,
+            source,
+            source_detail,
+            created_at,
+            updated_at,
+            is_archived as "is_archived!: bool",
+            is_private as "is_private!: bool",
+            is_unread as "is_unread!: bool",
+            language,
+            expires_at,
+            author,
+            site_name,
+            published_at,
+            deleted_at
+        from bookmarks
+        where user_id = ?
+        order by created_at, bookmark_id
+        "#,
+        user_id
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let tag_rows = sqlx::query!(
+        r#"
+        select bt.bookmark_id as "bookmark_id!: BookmarkId", t.name
+        from bookmark_tags bt
+        join bookmarks b on bt.bookmark_id = b.bookmark_id
+        join tags t on bt.tag_id = t.tag_id
+        where b.user_id = ?
+        order by t.name
+        "#,
+        user_id
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    let mut tags: HashMap<BookmarkId, Vec<String>> = HashMap::new();
+    for row in tag_rows {
+        tags.entry(row.bookmark_id).or_default().push(row.name);
+    }
+
+    let collections = sqlx::query_as!(
+        SnapshotCollection,
+        r#"
+        select collection_id as "collection_id!: Uuid", name, query
+        from smart_collections
+        where user_id = ?
+        order by name collate nocase
+        "#,
+        user_id
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let tag_colors = sqlx::query!(
+        r#"
+        select t.name, c.color
+        from tag_colors c
+        join tags t on c.tag_id = t.tag_id
+        where c.user_id = ?
+        "#,
+        user_id
+    )
+    .fetch_all(&mut *tx)
+    .await?
+    .into_iter()
+    .map(|row| (row.name, row.color))
+    .collect();
+
+    Ok(Snapshot {
+        version: SNAPSHOT_VERSION,
+        bookmarks,
+        tags,
+        collections,
+        tag_colors,
+    })
+}
+
+async fn insert_restore_point(tx: &mut SqliteConnection, user_id: Uuid, label: &str, snapshot: &Snapshot) -> Result<Uuid> {
+    let data = snapshot.encode()?;
+    let bookmark_count = i64::try_from(snapshot.bookmark_count()).unwrap_or(i64::MAX);
+    let record = sqlx::query!(
+        r#"
+        insert into restore_points (user_id, label, bookmark_count, snapshot)
+        values (?, ?, ?, ?)
+        returning restore_point_id as "restore_point_id!: Uuid"
+        "#,
+        user_id,
+        label,
+        bookmark_count,
+        data
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    Ok(record.restore_point_id)
+}
+
+/// Drops a user's oldest restore points beyond [`MAX_RESTORE_POINTS`], never the one to `keep`.
+async fn prune_restore_points(tx: &mut SqliteConnection, user_id: Uuid, keep: Uuid) -> Result<()> {
+    sqlx::query!(
+        r#"
+        delete from restore_points
+        where user_id = $1 and restore_point_id != $2 and restore_point_id not in (
+            select restore_point_id from restore_points
+            where user_id = $1
+            order by restore_point_id = $2 desc, created_at desc, rowid desc
+            limit $3
+        )
+        "#,
+        user_id,
+        keep,
+        MAX_RESTORE_POINTS
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Deletes the bookmarks that weren't there yet, and writes back those that changed along with
+/// their tags.
+async fn restore_bookmarks(tx: &mut SqliteConnection, user_id: Uuid, current: &Snapshot, target: &Snapshot) -> Result<()> {
+    let wanted: HashSet<BookmarkId> = target.bookmarks.iter().map(|bookmark| bookmark.bookmark_id).collect();
+    for bookmark in current.bookmarks.iter().filter(|bookmark| !wanted.contains(&bookmark.bookmark_id)) {
+        sqlx::query!(
+            "delete from bookmarks where user_id = ? and bookmark_id = ?",
+            user_id,
+            bookmark.bookmark_id
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    let existing: HashMap<BookmarkId, &SnapshotBookmark> =
+        current.bookmarks.iter().map(|bookmark| (bookmark.bookmark_id, bookmark)).collect();
+    for bookmark in &target.bookmarks {
+        let bookmark_id = bookmark.bookmark_id;
+        if existing.get(&bookmark_id) != Some(&bookmark) {
+            upsert_bookmark(tx, user_id, bookmark).await?;
+        }
+
+        let tags = target.tags_of(bookmark_id);
+        if current.tags_of(bookmark_id) != tags {
+            sqlx::query!("delete from bookmark_tags where bookmark_id = ?", bookmark_id)
+                .execute(&mut *tx)
+                .await?;
+            link_tags(tx, bookmark_id, tags).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes a bookmark back as it was, keeping its ID.
+async fn upsert_bookmark(tx: &mut SqliteConnection, user_id: Uuid, bookmark: &SnapshotBookmark) -> Result<()> {
+    sqlx::query!(
+        r#"
+        insert into bookmarks (
+            bookmark_id, user_id, url, original_url, title, description, source, source_detail, created_at, updated_at,
+            is_archived, is_private, is_unread, language, expires_at, author, site_name, published_at, deleted_at
+        )
+        values (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        on conflict (bookmark_id) do update set
+            url = excluded.url,
+            original_url = excluded.original_url,
+            title = excluded.title,
+            description = excluded.description,
+            source = excluded.source,
+            source_detail = excluded.source_detail,
+            created_at = excluded.created_at,
+            updated_at = excluded.updated_at,
+            is_archived = excluded.is_archived,
+            is_private = excluded.is_private,
+            is_unread = excluded.is_unread,
+            language = excluded.language,
+            expires_at = excluded.expires_at,
+            author = excluded.author,
+            site_name = excluded.site_name,
+            published_at = excluded.published_at,
+            deleted_at = excluded.deleted_at
+        where bookmarks.user_id = excluded.user_id
+        "#,
+        bookmark.bookmark_id,
+        user_id,
+        bookmark.url,
+        bookmark.original_url,
+        bookmark.title,
+        bookmark.description,
+        bookmark.source,
+        bookmark.source_detail,
+        bookmark.created_at,
+        bookmark.updated_at,
+        bookmark.is_archived,
+        bookmark.is_private,
+        bookmark.is_unread,
+        bookmark.language,
+        bookmark.expires_at,
+        bookmark.author,
+        bookmark.site_name,
+        bookmark.published_at,
+        bookmark.deleted_at
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Deletes the smart collections made since, and writes back those that changed. Guest links to
+/// collections that are kept keep working.
+async fn restore_collections(
+    tx: &mut SqliteConnection,
+    user_id: Uuid,
+    current: &[SnapshotCollection],
+    target: &[SnapshotCollection],
+) -> Result<()> {
+    let wanted: HashSet<Uuid> = target.iter().map(|collection| collection.collection_id).collect();
+    for collection in current.iter().filter(|collection| !wanted.contains(&collection.collection_id)) {
+        sqlx::query!(
+            "delete from smart_collections where user_id = ? and collection_id = ?",
+            user_id,
+            collection.collection_id
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    for collection in target.iter().filter(|collection| !current.contains(collection)) {
+        sqlx::query!(
+            r#"
+            insert into smart_collections (collection_id, user_id, name, query)
+            values (?, ?, ?, ?)
+            on conflict (collection_id) do update set name = excluded.name, query = excluded.query
+            where smart_collections.user_id = excluded.user_id
+            "#,
+            collection.collection_id,
+            user_id,
+            collection.name,
+            collection.query
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,572 @@
+//! Development and benchmark data seeding.
+//!
+//! In debug builds, `PAGEPOUCH_SEED` picks what an empty database is filled with:
+//! - `demo` (the default): a handful of hand-written bookmarks and tags for the admin
+//! - `large`: 10,000 bookmarks for each of 3 users, with 500 tags
+//! - `none`: nothing
+//! - custom volumes, e.g. `bookmarks=50000,tags=1000,users=5`; unset ones are taken from `large`
+//!
+//! Generated data is random but repeatable, and roughly shaped like real collections: a few tags
+//! and sites account for most bookmarks, most bookmarks have a couple of tags, and recent months
+//! are busier than older ones. That's what makes search and pagination performance work tested
+//! locally mean something.
+//!
+//! Seeding only ever happens when there are no bookmarks at all.
+//!
+//! The benchmarks run against a fixture database made by [`create_fixture`], which uses the same
+//! generator; `pagepouch-rs --bench-db <path>` makes one, sized by `PAGEPOUCH_SEED` or `large`.
+
+use std::{path::Path, str::FromStr as _};
+
+use anyhow::{Context as _, Result};
+use rand::{
+    Rng as _, SeedableRng as _,
+    distr::{Distribution as _, weighted::WeightedIndex},
+    rngs::StdRng,
+    seq::IndexedRandom as _,
+};
+use sqlx::{
+    SqlitePool,
+    sqlite::{SqliteConnectOptions, SqliteJournalMode},
+};
+use uuid::Uuid;
+
+const SEED_NAME: &str = "PAGEPOUCH_SEED";
+/// Seed for generated data, so every run generates the same bookmarks.
+const RNG_SEED: u64 = 0x5EED;
+/// Generated bookmarks are spread over this many days before now.
+const HISTORY_DAYS: f64 = 3.0 * 365.0;
+/// Share of generated bookmarks with a description.
+const DESCRIPTION_RATE: f64 = 0.6;
+/// Share of generated bookmarks that are archived.
+const ARCHIVED_RATE: f64 = 0.05;
+/// Weights for how many tags a generated bookmark has, from none up.
+const TAG_COUNT_WEIGHTS: [u32; 7] = [10, 25, 25, 18, 10, 7, 5];
+/// Different sites generated bookmarks are saved from.
+const DOMAIN_COUNT: usize = 300;
+
+const WORDS: [&str; 64] = [
+    "rust",
+    "async",
+    "database",
+    "search",
+    "index",
+    "query",
+    "cache",
+    "server",
+    "client",
+    "browser",
+    "layout",
+    "design",
+    "typography",
+    "color",
+    "memory",
+    "thread",
+    "network",
+    "protocol",
+    "security",
+    "crypto",
+    "privacy",
+    "compiler",
+    "parser",
+    "syntax",
+    "macro",
+    "testing",
+    "deploy",
+    "docker",
+    "linux",
+    "kernel",
+    "shell",
+    "editor",
+    "vim",
+    "emacs",
+    "git",
+    "review",
+    "release",
+    "version",
+    "api",
+    "schema",
+    "graph",
+    "vector",
+    "stream",
+    "queue",
+    "events",
+    "logging",
+    "metrics",
+    "tracing",
+    "cooking",
+    "travel",
+    "music",
+    "history",
+    "science",
+    "physics",
+    "math",
+    "finance",
+    "health",
+    "garden",
+    "photo",
+    "video",
+    "audio",
+    "games",
+    "books",
+    "writing",
+];
+const TLDS: [&str; 6] = ["com", "org", "dev", "io", "net", "blog"];
+
+/// Amounts of generated data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Volumes {
+    /// Users besides the admin are named `dev2`, `dev3`, etc., with the admin's password
+    pub users: usize,
+    /// Bookmarks for each user
+    pub bookmarks: usize,
+    /// Tags shared between all users
+    pub tags: usize,
+}
+
+impl Volumes {
+    pub const LARGE: Self = Self {
+        users: 3,
+        bookmarks: 10_000,
+        tags: 500,
+    };
+}
+
+/// What an empty development database is filled with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SeedProfile {
+    None,
+    Demo,
+    Generated(Volumes),
+}
+
+impl SeedProfile {
+    /// What benchmark fixtures are filled with, unless `PAGEPOUCH_SEED` says otherwise.
+    pub const BENCH: Self = Self::Generated(Volumes::LARGE);
+
+    /// Reads the profile from `PAGEPOUCH_SEED`, or returns `default` if it isn't set.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the variable is set but isn't a profile.
+    pub fn from_env(default: Self) -> Result<Self> {
+        match std::env::var(SEED_NAME) {
+            Ok(value) => Self::parse(&value).with_context(|| format!("🔥 Could not parse '{SEED_NAME}' value '{value}'.")),
+            Err(_) => Ok(default),
+        }
+    }
+
+    fn parse(value: &str) -> Result<Self> {
+        match value.trim() {
+            "" | "demo" => Ok(Self::Demo),
+            "none" => Ok(Self::None),
+            "large" => Ok(Self::Generated(Volumes::LARGE)),
+            custom => {
+                let mut volumes = Volumes::LARGE;
+                for setting in custom.split(',') {
+                    let (key, amount) = setting
+                        .split_once('=')
+                        .with_context(|| format!("'{setting}' isn't a profile or a volume like 'bookmarks=10000'"))?;
+                    let amount: usize = amount.trim().parse().with_context(|| format!("'{amount}' isn't a number"))?;
+                    match key.trim() {
+                        "users" => volumes.users = amount,
+                        "bookmarks" => volumes.bookmarks = amount,
+                        "tags" => volumes.tags = amount,
+                        key => anyhow::bail!("Unknown volume '{key}'; use users, bookmarks or tags"),
+                    }
+                }
+                anyhow::ensure!(volumes.users > 0, "There must be at least one user");
+                Ok(Self::Generated(volumes))
+            }
+        }
+    }
+}
+
+/// Fills the database according to `profile`, unless it already has bookmarks. The admin user must
+/// already exist.
+///
+/// # Errors
+///
+/// Returns an error if database operations fail.
+pub async fn seed(pool: &SqlitePool, profile: SeedProfile, password_hash: &str) -> Result<()> {
+    let existing_bookmarks = sqlx::query!("select count(*) as count from bookmarks").fetch_one(pool).await?;
+    if existing_bookmarks.count > 0 {
+        return Ok(());
+    }
+
+    match profile {
+        SeedProfile::None => Ok(()),
+        SeedProfile::Demo => seed_demo(pool).await,
+        SeedProfile::Generated(volumes) => seed_generated(pool, volumes, password_hash).await,
+    }
+}
+
+/// Creates a database at `path` for the benchmarks, with the development admin user and data
+/// generated for `profile`.
+///
+/// # Errors
+///
+/// Returns an error if there's already a file at `path`, or database operations fail.
+pub async fn create_fixture(path: &Path, profile: SeedProfile) -> Result<()> {
+    anyhow::ensure!(
+        !path.exists(),
+        "🔥 '{}' already exists; remove it to make a new fixture.",
+        path.display()
+    );
+    let options = SqliteConnectOptions::from_str(&format!("sqlite://{}", path.display()))?
+        .create_if_missing(true)
+        .journal_mode(SqliteJournalMode::Wal);
+    let pool = SqlitePool::connect_with(options)
+        .await
+        .with_context(|| format!("🔥 Could not create a database at '{}'", path.display()))?;
+    sqlx::migrate!().run(&pool).await?;
+    super::add_dev_admin(&pool).await?;
+    seed(&pool, profile, super::DEV_ADMIN_PASSWORD_HASH).await?;
+    // Gather the statistics the query planner uses, as the nightly maintenance would have by now
+    sqlx::query("analyze").execute(&pool).await?;
+    pool.close().await;
+
+    println!("✅ Created benchmark database at '{}'.", path.display());
+    Ok(())
+}
+
+async fn admin_user_id(pool: &SqlitePool) -> Result<Uuid> {
+    let admin = sqlx::query!(r#"select user_id as "user_id!: Uuid" from users where username = 'admin'"#)
+        .fetch_one(pool)
+        .await?;
+    Ok(admin.user_id)
+}
+
+/// Adds a tag if it doesn't exist yet, returning its ID.
+async fn tag_id(conn: &mut sqlx::SqliteConnection, name: &str) -> Result<Uuid> {
+    sqlx::query!("insert or ignore into tags (name) values (?)", name)
+        .execute(&mut *conn)
+        .await?;
+    let tag = sqlx::query!(r#"select tag_id as "tag_id!: Uuid" from tags where name = ?"#, name)
+        .fetch_one(&mut *conn)
+        .await?;
+    Ok(tag.tag_id)
+}
+
+/// A bookmark to insert.
+struct SeedBookmark<'a> {
+    url: &'a str,
+    title: &'a str,
+    description: Option<&'a str>,
+    created_at: i64,
+    updated_at: i64,
+    is_archived: bool,
+}
+
+async fn insert_bookmark(conn: &mut sqlx::SqliteConnection, user_id: Uuid, bookmark: &SeedBookmark<'_>, tag_ids: &[Uuid]) -> Result<()> {
+    let inserted = sqlx::query!(
+        r#"
+        insert into bookmarks (user_id, url, title, description, created_at, updated_at, is_archived)
+        values (?, ?, ?, ?, ?, ?, ?)
+        returning bookmark_id as "bookmark_id!: Uuid"
+        "#,
+        user_id,
+        bookmark.url,
+        bookmark.title,
+        bookmark.description,
+        bookmark.created_at,
+        bookmark.updated_at,
+        bookmark.is_archived
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    for tag_id in tag_ids {
+        sqlx::query!(
+            "insert or ignore into bookmark_tags (bookmark_id, tag_id) values (?, ?)",
+            inserted.bookmark_id,
+            tag_id
+        )
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Adds the hand-written demo bookmarks and tags for the admin.
+async fn seed_demo(pool: &SqlitePool) -> Result<()> {
+    println!("📚 Adding dummy bookmarks and tags for development...");
+    let user_id = admin_user_id(pool).await?;
+    let now = chrono::Utc::now().timestamp();
+    let mut tx = pool.begin().await?;
+
+    let mut tags = std::collections::HashSet::new();
+    for (url, title, description, tag_names) in DEMO_BOOKMARKS {
+        let mut tag_ids = Vec::new();
+        for name in tag_names {
+            tag_ids.push(tag_id(&mut tx, name).await?);
+            tags.insert(*name);
+        }
+        let bookmark = SeedBookmark {
+            url,
+            title,
+            description: Some(description),
+            created_at: now,
+            updated_at: now,
+            is_archived: false,
+        };
+        insert_bookmark(&mut tx, user_id, &bookmark, &tag_ids).await?;
+    }
+    tx.commit().await?;
+
+    println!(
+        "✨ Added {} tags and {} bookmarks for development!",
+        tags.len(),
+        DEMO_BOOKMARKS.len()
+    );
+    Ok(())
+}
+
+/// Adds the extra users, then generated tags and bookmarks for every user.
+async fn seed_generated(pool: &SqlitePool, volumes: Volumes, password_hash: &str) -> Result<()> {
+    println!(
+        "📚 Generating {} bookmarks for each of {} users, with {} tags...",
+        volumes.bookmarks, volumes.users, volumes.tags
+    );
+    let started = std::time::Instant::now();
+    let mut rng = StdRng::seed_from_u64(RNG_SEED);
+    let now = chrono::Utc::now().timestamp();
+
+    let mut user_ids = vec![admin_user_id(pool).await?];
+    for number in 2..=volumes.users {
+        let username = format!("dev{number}");
+        let user = sqlx::query!(
+            r#"insert into users (username, password_hash) values (?, ?) returning user_id as "user_id!: Uuid""#,
+            username,
+            password_hash
+        )
+        .fetch_one(pool)
+        .await?;
+        user_ids.push(user.user_id);
+    }
+
+    let mut tx = pool.begin().await?;
+    let mut tag_ids = Vec::with_capacity(volumes.tags);
+    for name in tag_names(volumes.tags) {
+        tag_ids.push(tag_id(&mut tx, &name).await?);
+    }
+    let domains = domain_names(DOMAIN_COUNT);
+    // A few tags and sites account for most bookmarks, as in real collections
+    let tag_popularity = zipf(tag_ids.len());
+    let domain_popularity = zipf(domains.len()).context("No domains to generate bookmarks from")?;
+    let tag_counts = WeightedIndex::new(TAG_COUNT_WEIGHTS)?;
+
+    for user_id in user_ids {
+        for number in 0..volumes.bookmarks {
+            let domain = &domains[domain_popularity.sample(&mut rng)];
+            let words = sentence(&mut rng, 3..=8);
+            let url = format!("https://{domain}/{}-{number}", words.join("-"));
+            let title = title_case(&words);
+            let description = rng.random_bool(DESCRIPTION_RATE).then(|| {
+                let mut description = title_case(&sentence(&mut rng, 8..=25));
+                description.push('.');
+                description
+            });
+            // Squaring skews towards recent days: users save more now than they did years ago
+            #[allow(clippy::cast_possible_truncation)] // at most a few years of seconds
+            let age = (rng.random::<f64>().powi(2) * HISTORY_DAYS * 86_400.0) as i64;
+            let created_at = now - age;
+            let updated_at = if rng.random_bool(0.2) {
+                created_at + rng.random_range(0..=age)
+            } else {
+                created_at
+            };
+            let mut bookmark_tags = Vec::new();
+            if let Some(tag_popularity) = &tag_popularity {
+                for _ in 0..tag_counts.sample(&mut rng) {
+                    bookmark_tags.push(tag_ids[tag_popularity.sample(&mut rng)]);
+                }
+            }
+
+            let bookmark = SeedBookmark {
+                url: &url,
+                title: &title,
+                description: description.as_deref(),
+                created_at,
+                updated_at,
+                is_archived: rng.random_bool(ARCHIVED_RATE),
+            };
+            insert_bookmark(&mut tx, user_id, &bookmark, &bookmark_tags).await?;
+        }
+    }
+    tx.commit().await?;
+
+    println!(
+        "✨ Generated {} bookmarks and {} tags in {:.1}s!",
+        volumes.bookmarks * volumes.users,
+        volumes.tags,
+        started.elapsed().as_secs_f64()
+    );
+    Ok(())
+}
+
+/// Weights the first items most, falling off like word frequencies do, or `None` if there are none.
+fn zipf(count: usize) -> Option<WeightedIndex<f64>> {
+    #[allow(clippy::cast_precision_loss)] // counts are far below 2^52
+    WeightedIndex::new((1..=count).map(|rank| 1.0 / rank as f64)).ok()
+}
+
+/// `count` distinct tag names: single words first, then pairs of them.
+fn tag_names(count: usize) -> Vec<String> {
+    let pairs = WORDS.iter().flat_map(|first| {
+        WORDS
+            .iter()
+            .filter(move |second| second != &first)
+            .map(move |second| format!("{first}-{second}"))
+    });
+    let numbered = (2..).flat_map(|number| WORDS.iter().map(move |word| format!("{word}-{number}")));
+    WORDS
+        .iter()
+        .map(ToString::to_string)
+        .chain(pairs)
+        .chain(numbered)
+        .take(count)
+        .collect()
+}
+
+/// `count` distinct domain names.
+fn domain_names(count: usize) -> Vec<String> {
+    (0..count)
+        .map(|index| {
+            let word = WORDS[index % WORDS.len()];
+            let tld = TLDS[(index / WORDS.len()) % TLDS.len()];
+            match index / (WORDS.len() * TLDS.len()) {
+                0 => format!("{word}.{tld}"),
+                round => format!("{word}{round}.{tld}"),
+            }
+        })
+        .collect()
+}
+
+fn sentence(rng: &mut StdRng, length: std::ops::RangeInclusive<usize>) -> Vec<&'static str> {
+    let length = rng.random_range(length);
+    (0..length).filter_map(|_| WORDS.choose(rng).copied()).collect()
+}
+
+fn title_case(words: &[&str]) -> String {
+    let mut words = words.iter();
+    let mut title = String::new();
+    if let Some(first) = words.next() {
+        let mut chars = first.chars();
+        title.extend(chars.next().map(|c| c.to_ascii_uppercase()));
+        title.push_str(chars.as_str());
+    }
+    for word in words {
+        title.push(' ');
+        title.push_str(word);
+    }
+    title
+}
+
+const DEMO_BOOKMARKS: [(&str, &str, &str, &[&str]); 10] = [
+    (
+        "https://rust-lang.org",
+        "The Rust Programming Language",
+        "A systems programming language that is blazingly fast, memory-safe, and thread-safe.",
+        &["rust", "programming", "systems"],
+    ),
+    (
+        "https://htmx.org",
+        "HTMX - High Power Tools for HTML",
+        "htmx allows you to access modern browser features directly from HTML, rather than using JavaScript.",
+        &["web-dev", "javascript", "htmx"],
+    ),
+    (
+        "https://github.com/tokio-rs/axum",
+        "Axum Web Framework for Rust",
+        "Axum is a web application framework that focuses on ergonomics and modularity.",
+        &[
+            "rust",
+            "web-framework",
+            "axum",
+            "tokio",
+            "async",
+            "http",
+            "server",
+            "middleware",
+            "routing",
+        ],
+    ),
+    (
+        "https://simplecss.org",
+        "Simple.css - A CSS Framework for Semantic HTML",
+        "A CSS framework for developers who want their websites to look good without the complexity.",
+        &["css", "framework", "simple"],
+    ),
+    (
+        "https://sqlite.org",
+        "SQLite Database Engine",
+        "SQLite is a C library that provides a lightweight disk-based database.",
+        &["database", "sqlite", "backend"],
+    ),
+    (
+        "https://docs.rs/askama/latest/askama/",
+        "Askama Template Engine",
+        "Type-safe, compiled Jinja-like templates for Rust.",
+        &["rust", "templates", "web-dev"],
+    ),
+    (
+        "https://github.com/launchbadge/sqlx",
+        "SQLx - Rust SQL Toolkit",
+        "The Rust SQL toolkit. An async, pure Rust SQL crate featuring compile-time checked queries.",
+        &["rust", "database", "async", "api"],
+    ),
+    (
+        "https://developer.mozilla.org/en-US/docs/Web/API/Fetch_API",
+        "Fetch API - Web APIs | MDN",
+        "The Fetch API provides an interface for fetching resources.",
+        &["javascript", "web-dev", "api", "http"],
+    ),
+    (
+        "https://github.com/SergioBenitez/Rocket",
+        "Rocket - Web Framework for Rust",
+        "A web framework for Rust that makes it simple to write fast, secure web applications.",
+        &["rust", "web-framework", "server", "api"],
+    ),
+    (
+        "https://tailwindcss.com",
+        "Tailwind CSS",
+        "A utility-first CSS framework for rapidly building custom user interfaces.",
+        &["css", "framework", "web-dev"],
+    ),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_profile() {
+        assert_eq!(SeedProfile::parse("").unwrap(), SeedProfile::Demo);
+        assert_eq!(SeedProfile::parse("none").unwrap(), SeedProfile::None);
+        assert_eq!(SeedProfile::parse("large").unwrap(), SeedProfile::Generated(Volumes::LARGE));
+        assert_eq!(
+            SeedProfile::parse("bookmarks=50000, users=5").unwrap(),
+            SeedProfile::Generated(Volumes {
+                users: 5,
+                bookmarks: 50_000,
+                tags: 500,
+            })
+        );
+        assert!(SeedProfile::parse("huge").is_err());
+        assert!(SeedProfile::parse("bookmarks=lots").is_err());
+        assert!(SeedProfile::parse("folders=3").is_err());
+        assert!(SeedProfile::parse("users=0").is_err());
+    }
+
+    #[test]
+    fn test_generated_names_are_distinct() {
+        let tags = tag_names(10_000);
+        assert_eq!(tags.len(), 10_000);
+        assert_eq!(tags.iter().collect::<std::collections::HashSet<_>>().len(), tags.len());
+        assert_eq!(tags[0], "rust");
+
+        let domains = domain_names(DOMAIN_COUNT);
+        assert_eq!(domains.iter().collect::<std::collections::HashSet<_>>().len(), DOMAIN_COUNT);
+    }
+}
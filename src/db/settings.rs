@@ -0,0 +1,351 @@
+//! Per-user settings persistence.
+//!
+//! Users without a row in `user_settings` get the defaults, so a row is only written once a
+//! user changes something.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    db::{Db, bookmarks::SortOrder, timing::QueryTimer},
+    search::{SearchDefaults, SearchLogic},
+};
+
+/// Settings a user can change from the settings page.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct UserSettings {
+    /// Never fetch saved URLs to fill in title and description.
+    pub disable_fetch: bool,
+    /// Which fields the bookmark list shows, and how tightly it is laid out.
+    pub list: ListDisplay,
+    /// Language to translate bookmarks in other languages into, e.g. `en`; no translation if `None`.
+    pub preferred_language: Option<String>,
+    /// Show a few bookmarks saved months or years ago around this time on the home page.
+    pub resurface: bool,
+    /// How searches are read when the query doesn't say.
+    pub search: SearchDefaults,
+    /// Order of the bookmark list when none is picked.
+    pub default_sort: SortOrder,
+    /// How often to email a summary of new bookmarks, see [`crate::digest`].
+    pub digest: DigestFrequency,
+}
+
+/// How tightly bookmark list rows are laid out.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ListDensity {
+    #[default]
+    Comfortable,
+    Compact,
+    /// Cards in columns, with the page's thumbnail where one was saved
+    Grid,
+}
+
+impl ListDensity {
+    /// The value stored in the database and used in forms and CSS classes.
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ListDensity::Comfortable => "comfortable",
+            ListDensity::Compact => "compact",
+            ListDensity::Grid => "grid",
+        }
+    }
+
+    /// Parses a stored value, falling back to the default for anything unknown.
+    fn from_db(value: &str) -> Self {
+        match value {
+            "compact" => ListDensity::Compact,
+            "grid" => ListDensity::Grid,
+            _ => ListDensity::Comfortable,
+        }
+    }
+}
+
+/// How often a user gets the email digest of their new bookmarks.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DigestFrequency {
+    #[default]
+    Off,
+    Daily,
+    Weekly,
+}
+
+impl DigestFrequency {
+    /// The value stored in the database and used in forms.
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DigestFrequency::Off => "off",
+            DigestFrequency::Daily => "daily",
+            DigestFrequency::Weekly => "weekly",
+        }
+    }
+
+    /// Parses a stored value, falling back to the default for anything unknown.
+    fn from_db(value: &str) -> Self {
+        match value {
+            "daily" => DigestFrequency::Daily,
+            "weekly" => DigestFrequency::Weekly,
+            _ => DigestFrequency::Off,
+        }
+    }
+
+    /// Seconds between digests, or `None` if they're off.
+    #[must_use]
+    pub fn period_secs(self) -> Option<i64> {
+        match self {
+            DigestFrequency::Off => None,
+            DigestFrequency::Daily => Some(86400),
+            DigestFrequency::Weekly => Some(7 * 86400),
+        }
+    }
+}
+
+/// Fields shown in each bookmark list row. The title is always shown.
+#[allow(clippy::struct_excessive_bools)] // one independent toggle per field
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct ListDisplay {
+    pub density: ListDensity,
+    pub show_description: bool,
+    pub show_tags: bool,
+    pub show_domain: bool,
+    pub show_date: bool,
+    /// Favicons are loaded by the browser straight from the saved site.
+    pub show_favicon: bool,
+    /// Load more bookmarks when the end of the list is scrolled into view, instead of showing pages.
+    /// Only the unfiltered list sorted by date scrolls; searches and other orders keep their pages.
+    pub infinite_scroll: bool,
+}
+
+impl Default for ListDisplay {
+    fn default() -> Self {
+        Self {
+            density: ListDensity::default(),
+            show_description: false,
+            show_tags: true,
+            show_domain: false,
+            show_date: true,
+            show_favicon: false,
+            infinite_scroll: false,
+        }
+    }
+}
+
+/// Loads a user's settings, falling back to defaults if they never changed any.
+///
+/// # Errors
+///
+/// Returns an error if the database query fails.
+pub async fn get_user_settings(db: &Db, user_id: Uuid) -> Result<UserSettings> {
+    let _timer = QueryTimer::start("get_user_settings");
+    let record = sqlx::query!(
+        r#"
+            select
+                disable_fetch,
+                list_density,
+                show_description,
+                show_tags,
+                show_domain,
+                show_date,
+                show_favicon,
+                infinite_scroll,
+                preferred_language,
+                resurface,
+                search_logic,
+                search_include_archived,
+                fuzzy_search,
+                search_page_text,
+                default_sort,
+                digest
+            from user_settings
+            where user_id = $1
+        "#,
+        user_id
+    )
+    .fetch_optional(&db.reader)
+    .await?;
+
+    Ok(record.map_or_else(UserSettings::default, |record| UserSettings {
+        disable_fetch: record.disable_fetch,
+        list: ListDisplay {
+            density: ListDensity::from_db(&record.list_density),
+            show_description: record.show_description,
+            show_tags: record.show_tags,
+            show_domain: record.show_domain,
+            show_date: record.show_date,
+            show_favicon: record.show_favicon,
+            infinite_scroll: record.infinite_scroll,
+        },
+        preferred_language: record.preferred_language,
+        resurface: record.resurface,
+        search: SearchDefaults {
+            logic: logic_from_db(&record.search_logic),
+            include_archived: record.search_include_archived,
+            fuzzy: record.fuzzy_search,
+            page_text: record.search_page_text,
+        },
+        default_sort: sort_from_db(&record.default_sort),
+        digest: DigestFrequency::from_db(&record.digest),
+    }))
+}
+
+/// Parses a stored search logic, falling back to the default for anything unknown.
+fn logic_from_db(value: &str) -> SearchLogic {
+    match value {
+        "and" => SearchLogic::And,
+        _ => SearchLogic::Or,
+    }
+}
+
+/// Parses a stored sort order, falling back to the default for anything unknown.
+fn sort_from_db(value: &str) -> SortOrder {
+    match value {
+        "oldest" => SortOrder::Oldest,
+        "title" => SortOrder::Title,
+        _ => SortOrder::Newest,
+    }
+}
+
+/// Saves a user's settings, creating their settings row if needed.
+///
+/// # Errors
+///
+/// Returns an error if the database query fails.
+pub async fn update_user_settings(db: &Db, user_id: Uuid, settings: &UserSettings) -> Result<()> {
+    let _timer = QueryTimer::start("update_user_settings");
+    let list_density = settings.list.density.as_str();
+    let search_logic = settings.search.logic.as_str();
+    let default_sort = settings.default_sort.as_str();
+    let digest = settings.digest.as_str();
+    sqlx::query!(
+        r#"
+            insert into user_settings (
+                user_id,
+                disable_fetch,
+                list_density,
+                show_description,
+                show_tags,
+                show_domain,
+                show_date,
+                show_favicon,
+                infinite_scroll,
+                preferred_language,
+                resurface,
+                search_logic,
+                search_include_archived,
+                fuzzy_search,
+                search_page_text,
+                default_sort,
+                digest
+            )
+            values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17)
+            on conflict (user_id) do update set
+                disable_fetch = excluded.disable_fetch,
+                list_density = excluded.list_density,
+                show_description = excluded.show_description,
+                show_tags = excluded.show_tags,
+                show_domain = excluded.show_domain,
+                show_date = excluded.show_date,
+                show_favicon = excluded.show_favicon,
+                infinite_scroll = excluded.infinite_scroll,
+                preferred_language = excluded.preferred_language,
+                resurface = excluded.resurface,
+                search_logic = excluded.search_logic,
+                search_include_archived = excluded.search_include_archived,
+                fuzzy_search = excluded.fuzzy_search,
+                search_page_text = excluded.search_page_text,
+                default_sort = excluded.default_sort,
+                digest = excluded.digest,
+                updated_at = unixepoch()
+        "#,
+        user_id,
+        settings.disable_fetch,
+        list_density,
+        settings.list.show_description,
+        settings.list.show_tags,
+        settings.list.show_domain,
+        settings.list.show_date,
+        settings.list.show_favicon,
+        settings.list.infinite_scroll,
+        settings.preferred_language,
+        settings.resurface,
+        search_logic,
+        settings.search.include_archived,
+        settings.search.fuzzy,
+        settings.search.page_text,
+        default_sort,
+        digest
+    )
+    .execute(&db.writer)
+    .await?;
+
+    Ok(())
+}
+
+/// A user whose email digest is due.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DueDigest {
+    pub user_id: Uuid,
+    pub username: String,
+    pub email: String,
+    pub frequency: DigestFrequency,
+}
+
+/// Retrieves the users with an email address whose daily digest was last sent before
+/// `daily_before`, or weekly digest before `weekly_before` (UNIX timestamps), or never.
+///
+/// # Errors
+///
+/// Returns an error if the database query fails.
+pub async fn get_due_digests(db: &Db, daily_before: i64, weekly_before: i64) -> Result<Vec<DueDigest>> {
+    let _timer = QueryTimer::start("get_due_digests");
+    let records = sqlx::query!(
+        r#"
+            select
+                s.user_id as "user_id!: Uuid",
+                u.username,
+                u.email as "email!",
+                s.digest
+            from user_settings s
+            join users u on u.user_id = s.user_id
+            where u.email is not null and not u.is_revoked
+                and (
+                    (s.digest = 'daily' and coalesce(s.digest_sent_at, 0) < $1)
+                    or (s.digest = 'weekly' and coalesce(s.digest_sent_at, 0) < $2)
+                )
+        "#,
+        daily_before,
+        weekly_before
+    )
+    .fetch_all(&db.reader)
+    .await?;
+
+    Ok(records
+        .into_iter()
+        .map(|record| DueDigest {
+            user_id: record.user_id,
+            username: record.username,
+            email: record.email,
+            frequency: DigestFrequency::from_db(&record.digest),
+        })
+        .collect())
+}
+
+/// Notes that a user's digest was sent at `sent_at` (UNIX timestamp), or that there was nothing to
+/// send, so the next one is due a period later.
+///
+/// # Errors
+///
+/// Returns an error if the database query fails.
+pub async fn mark_digest_sent(db: &Db, user_id: Uuid, sent_at: i64) -> Result<()> {
+    let _timer = QueryTimer::start("mark_digest_sent");
+    sqlx::query!("update user_settings set digest_sent_at = $1 where user_id = $2", sent_at, user_id)
+        .execute(&db.writer)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,70 @@
+//! Saved copies of bookmarked pages, see [`crate::snapshot`].
+
+use anyhow::Result;
+use uuid::Uuid;
+
+use crate::db::{Db, bookmarks::BookmarkId, timing::QueryTimer};
+
+/// A saved copy of a page, as stored.
+#[derive(Clone, Debug)]
+pub struct StoredSnapshot {
+    /// LZ4-compressed HTML
+    pub content: Vec<u8>,
+    /// Where the page was fetched from, after redirects
+    pub source_url: String,
+    /// UNIX timestamp
+    pub created_at: i64,
+}
+
+/// Saves a copy of a bookmark's page, replacing any earlier one, and marks the bookmark as having
+/// one.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn set_snapshot(db: &Db, bookmark_id: BookmarkId, content: &[u8], size: i64, source_url: &str) -> Result<()> {
+    let _timer = QueryTimer::start("set_snapshot");
+    let mut tx = db.writer.begin().await?;
+    sqlx::query!(
+        r#"
+        insert or replace into bookmark_snapshots (bookmark_id, content, size, source_url)
+        values (?, ?, ?, ?)
+        "#,
+        bookmark_id,
+        content,
+        size,
+        source_url
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!("update bookmarks set has_snapshot = true where bookmark_id = ?", bookmark_id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Loads the saved copy of one of a user's bookmarked pages.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn get_snapshot(db: &Db, user_id: Uuid, bookmark_id: BookmarkId) -> Result<Option<StoredSnapshot>> {
+    let _timer = QueryTimer::start("get_snapshot");
+    let snapshot = sqlx::query_as!(
+        StoredSnapshot,
+        r#"
+        select bs.content, bs.source_url, bs.created_at
+        from bookmark_snapshots bs
+        join bookmarks b on bs.bookmark_id = b.bookmark_id
+        where bs.bookmark_id = ? and b.user_id = ?
+        "#,
+        bookmark_id,
+        user_id
+    )
+    .fetch_optional(&db.reader)
+    .await?;
+
+    Ok(snapshot)
+}
@@ -0,0 +1,160 @@
+//! Collection subscription database operations.
+//!
+//! A subscription is a stable link serving the latest export of one of a user's smart collections,
+//! for static site generators, note apps and the like to pull on a schedule. Like a guest link, the
+//! URL carries a signed [`SubscriptionToken`] and the row here is what makes it valid, but it
+//! doesn't expire: it works until it's revoked or the collection is deleted.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::db::{Db, bookmarks::get_created_ago, timing::QueryTimer};
+
+/// Token in a subscription's URL, converted to a JWT and signed.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+pub struct SubscriptionToken {
+    pub subscription_id: Uuid,
+}
+
+/// A subscription, as listed on the settings page.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Subscription {
+    pub subscription_id: Uuid,
+    pub collection_name: String,
+    /// When it was last used, e.g. "2 hours ago"
+    pub last_pulled_ago: Option<String>,
+}
+
+/// What a valid subscription gives access to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SubscriptionAccess {
+    pub subscription_id: Uuid,
+    /// Whose collection it is
+    pub user_id: Uuid,
+    pub username: String,
+    pub collection_name: String,
+    /// The collection's rule in search syntax
+    pub collection_query: String,
+}
+
+/// Retrieves a user's subscriptions, ordered by collection name.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn get_subscriptions(db: &Db, user_id: Uuid) -> Result<Vec<Subscription>> {
+    let _timer = QueryTimer::start("get_subscriptions");
+    let records = sqlx::query!(
+        r#"
+        select
+            s.subscription_id as "subscription_id!: Uuid",
+            c.name as collection_name,
+            s.last_pulled_at
+        from collection_subscriptions s
+        join smart_collections c on c.collection_id = s.collection_id
+        where s.user_id = ?
+        order by c.name collate nocase
+        "#,
+        user_id
+    )
+    .fetch_all(&db.reader)
+    .await?;
+
+    Ok(records
+        .into_iter()
+        .map(|record| Subscription {
+            subscription_id: record.subscription_id,
+            collection_name: record.collection_name,
+            last_pulled_ago: record.last_pulled_at.map(get_created_ago),
+        })
+        .collect())
+}
+
+/// Creates a subscription to one of a user's smart collections, or returns the one it already has.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn create_subscription(db: &Db, user_id: Uuid, collection_id: Uuid) -> Result<Uuid> {
+    let _timer = QueryTimer::start("create_subscription");
+    let record = sqlx::query!(
+        r#"
+        insert into collection_subscriptions (user_id, collection_id)
+        values (?, ?)
+        on conflict (collection_id) do update set collection_id = excluded.collection_id
+        returning subscription_id as "subscription_id!: Uuid"
+        "#,
+        user_id,
+        collection_id
+    )
+    .fetch_one(&db.writer)
+    .await?;
+
+    Ok(record.subscription_id)
+}
+
+/// Revokes one of a user's subscriptions. Returns whether anything was deleted.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn delete_subscription(db: &Db, user_id: Uuid, subscription_id: Uuid) -> Result<bool> {
+    let _timer = QueryTimer::start("delete_subscription");
+    let result = sqlx::query!(
+        "delete from collection_subscriptions where user_id = ? and subscription_id = ?",
+        user_id,
+        subscription_id
+    )
+    .execute(&db.writer)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Looks up what a subscription gives access to, or `None` if it has been revoked or its user has
+/// been revoked.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn get_subscription_access(db: &Db, subscription_id: Uuid) -> Result<Option<SubscriptionAccess>> {
+    let _timer = QueryTimer::start("get_subscription_access");
+    let access = sqlx::query_as!(
+        SubscriptionAccess,
+        r#"
+        select
+            s.subscription_id as "subscription_id!: Uuid",
+            s.user_id as "user_id!: Uuid",
+            u.username,
+            c.name as collection_name,
+            c.query as collection_query
+        from collection_subscriptions s
+        join users u on u.user_id = s.user_id
+        join smart_collections c on c.collection_id = s.collection_id
+        where s.subscription_id = ? and not u.is_revoked
+        "#,
+        subscription_id
+    )
+    .fetch_optional(&db.reader)
+    .await?;
+
+    Ok(access)
+}
+
+/// Notes that a subscription was just used.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn mark_pulled(db: &Db, subscription_id: Uuid) -> Result<()> {
+    let _timer = QueryTimer::start("mark_subscription_pulled");
+    sqlx::query!(
+        "update collection_subscriptions set last_pulled_at = unixepoch() where subscription_id = ?",
+        subscription_id
+    )
+    .execute(&db.writer)
+    .await?;
+
+    Ok(())
+}
@@ -1,33 +1,98 @@
 //! Tag database operations.
 
+use std::collections::BTreeMap;
+
 use anyhow::Result;
-use sqlx::{Row, SqliteConnection, SqlitePool};
+use serde::Serialize;
+use sqlx::{Row, SqliteConnection};
+
+use crate::{
+    db::{Db, timing::QueryTimer},
+    suggest::CorpusEntry,
+};
+
+/// Maximum number of recent tagged bookmarks considered when suggesting tags.
+const SUGGESTION_CORPUS_LIMIT: i64 = 2000;
+
+/// A tag along with how many bookmarks in the current context use it.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TagCount {
+    pub name: String,
+    pub count: i64,
+    /// The color the user picked for the tag, e.g. `#3b82f6`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+}
 
-use crate::db::bookmarks::TagInfo;
+/// The colors a user picked for their tags, by tag name.
+pub type TagColors = BTreeMap<String, String>;
 
-/// Retrieves all tags used by a user's bookmarks, ordered by name.
+/// Retrieves all tags used by a user's bookmarks with their bookmark counts, ordered by name.
 ///
 /// # Errors
 ///
 /// Returns an error if database query fails.
-pub async fn get_user_tags(pool: &SqlitePool, user_id: uuid::Uuid) -> Result<Vec<TagInfo>> {
-    let tags = sqlx::query!(
+pub async fn get_user_tags(db: &Db, user_id: uuid::Uuid) -> Result<Vec<TagCount>> {
+    let tags = sqlx::query_as!(
+        TagCount,
         r#"
-        select distinct t.name
+        select
+            t.name,
+            count(distinct b.bookmark_id) as "count!: i64",
+            c.color as "color?: String"
         from tags t
         join bookmark_tags bt on t.tag_id = bt.tag_id
         join bookmarks b on bt.bookmark_id = b.bookmark_id
-        where b.user_id = ? and b.is_archived = 0
+        left join tag_colors c on c.tag_id = t.tag_id and c.user_id = b.user_id
+        where b.user_id = ? and b.is_archived = 0 and b.deleted_at is null
+        group by t.name
         order by t.name
         "#,
         user_id
     )
-    .fetch_all(pool)
+    .fetch_all(&db.reader)
     .await?;
 
-    let result = tags.into_iter().map(|tag| TagInfo { name: tag.name }).collect();
+    Ok(tags)
+}
+
+/// Retrieves the user's most recent tagged bookmarks as a corpus for tag suggestions.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn get_suggestion_corpus(db: &Db, user_id: uuid::Uuid) -> Result<Vec<CorpusEntry>> {
+    let _timer = QueryTimer::start("get_suggestion_corpus");
+    let rows = sqlx::query!(
+        r#"
+        select
+            url,
+            title,
+            tags_string as "tags_string!: String"
+        from bookmark_with_tags
+        where
+            user_id = ?
+            and is_archived = 0 and deleted_at is null
+            and tags_string is not null
+        order by created_at desc
+        limit ?
+        "#,
+        user_id,
+        SUGGESTION_CORPUS_LIMIT
+    )
+    .fetch_all(&db.reader)
+    .await?;
 
-    Ok(result)
+    let corpus = rows
+        .into_iter()
+        .map(|row| CorpusEntry {
+            url: row.url,
+            title: row.title,
+            tags: row.tags_string.split(',').filter(|t| !t.is_empty()).map(str::to_string).collect(),
+        })
+        .collect();
+
+    Ok(corpus)
 }
 
 /// Gets or creates a tag by name.
@@ -55,58 +120,67 @@ pub async fn get_or_create_tag(tx: &mut SqliteConnection, name: &str) -> Result<
     Ok(result.tag_id)
 }
 
-/// Gets tags that are present in bookmarks matching the specified tag filters.
-/// If no tag filters are provided, returns all user tags.
+/// Gets tags that are present in bookmarks matching the specified tag filters, each with the
+/// number of matching bookmarks that use it. If no tag filters are provided, returns all user tags.
 ///
 /// # Errors
 ///
 /// Returns an error if database query fails.
-pub async fn get_tags_for_active_filters(pool: &SqlitePool, user_id: uuid::Uuid, active_tag_filters: &[String]) -> Result<Vec<TagInfo>> {
+pub async fn get_tags_for_active_filters(db: &Db, user_id: uuid::Uuid, active_tag_filters: &[String]) -> Result<Vec<TagCount>> {
+    let _timer = QueryTimer::start("get_tags_for_active_filters");
     if active_tag_filters.is_empty() {
         // No active tags, return all user tags
-        return get_user_tags(pool, user_id).await;
+        return get_user_tags(db, user_id).await;
     }
 
     // Get tags from bookmarks that match the active tag filters
     if active_tag_filters.len() == 1 {
         // Single tag filter - simpler query
         let tag_pattern = format!("%{}%", active_tag_filters[0]);
-        let tags = sqlx::query!(
+        let tags = sqlx::query_as!(
+            TagCount,
             r#"
-            select distinct t2.name
+            select
+                t2.name,
+                count(distinct b2.bookmark_id) as "count!: i64",
+                c.color as "color?: String"
             from tags t2
             join bookmark_tags bt2 on t2.tag_id = bt2.tag_id
             join bookmarks b2 on bt2.bookmark_id = b2.bookmark_id
-            where b2.user_id = ? and b2.is_archived = 0
+            left join tag_colors c on c.tag_id = t2.tag_id and c.user_id = b2.user_id
+            where b2.user_id = ? and b2.is_archived = 0 and b2.deleted_at is null
             and b2.bookmark_id in (
                 select distinct b.bookmark_id
                 from bookmarks b
                 join bookmark_tags bt on b.bookmark_id = bt.bookmark_id
                 join tags t on bt.tag_id = t.tag_id
-                where b.user_id = ? and b.is_archived = 0 and t.name like ?
+                where b.user_id = ? and b.is_archived = 0 and b.deleted_at is null and t.name like ?
             )
+            group by t2.name
             order by t2.name
             "#,
             user_id,
             user_id,
             tag_pattern
         )
-        .fetch_all(pool)
+        .fetch_all(&db.reader)
         .await?;
 
-        let result = tags.into_iter().map(|tag| TagInfo { name: tag.name }).collect();
-
-        Ok(result)
+        Ok(tags)
     } else {
         // Multiple tag filters - find bookmarks that have ALL specified tags
         let like_conditions = active_tag_filters.iter().map(|_| "t.name like ?").collect::<Vec<_>>().join(" OR ");
         let sql = format!(
             r"
-            select distinct t2.name
+            select
+                t2.name,
+                count(distinct b2.bookmark_id) as count,
+                c.color
             from tags t2
             join bookmark_tags bt2 on t2.tag_id = bt2.tag_id
             join bookmarks b2 on bt2.bookmark_id = b2.bookmark_id
-            where b2.user_id = ? and b2.is_archived = 0
+            left join tag_colors c on c.tag_id = t2.tag_id and c.user_id = b2.user_id
+            where b2.user_id = ? and b2.is_archived = 0 and b2.deleted_at is null
             and b2.bookmark_id in (
                 select bt.bookmark_id
                 from bookmark_tags bt
@@ -115,6 +189,7 @@ pub async fn get_tags_for_active_filters(pool: &SqlitePool, user_id: uuid::Uuid,
                 group by bt.bookmark_id
                 having count(distinct t.tag_id) >= ?
             )
+            group by t2.name
             order by t2.name
             "
         );
@@ -126,9 +201,257 @@ pub async fn get_tags_for_active_filters(pool: &SqlitePool, user_id: uuid::Uuid,
         }
         query = query.bind(i64::try_from(active_tag_filters.len()).unwrap_or(0));
 
-        let rows = query.fetch_all(pool).await?;
-        let result = rows.into_iter().map(|row| TagInfo { name: row.get("name") }).collect();
+        let rows = query.fetch_all(&db.reader).await?;
+        let result = rows
+            .into_iter()
+            .map(|row| TagCount {
+                name: row.get("name"),
+                count: row.get("count"),
+                color: row.get("color"),
+            })
+            .collect();
 
         Ok(result)
     }
 }
+
+/// Reads a color as sent by a color picker, `#rrggbb`, in lowercase.
+#[must_use]
+pub fn parse_color(color: &str) -> Option<String> {
+    let hex = color.trim().strip_prefix('#')?;
+    (hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit())).then(|| format!("#{}", hex.to_ascii_lowercase()))
+}
+
+/// Retrieves the colors the user picked for their tags.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn get_tag_colors(db: &Db, user_id: uuid::Uuid) -> Result<TagColors> {
+    let _timer = QueryTimer::start("get_tag_colors");
+    let rows = sqlx::query!(
+        r#"
+        select t.name, c.color
+        from tag_colors c
+        join tags t on c.tag_id = t.tag_id
+        where c.user_id = ?
+        "#,
+        user_id
+    )
+    .fetch_all(&db.reader)
+    .await?;
+
+    Ok(rows.into_iter().map(|row| (row.name, row.color)).collect())
+}
+
+/// Sets the color of one of the user's tags, or removes it with `None`. The color must be
+/// `#rrggbb` in lowercase, see [`parse_color`].
+///
+/// Returns false if none of the user's bookmarks have the tag.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn set_tag_color(db: &Db, user_id: uuid::Uuid, tag_name: &str, color: Option<&str>) -> Result<bool> {
+    let _timer = QueryTimer::start("set_tag_color");
+    let Some(tag_id) = get_user_tag_id(db, user_id, tag_name).await? else {
+        return Ok(false);
+    };
+
+    if let Some(color) = color {
+        sqlx::query!(
+            r#"
+            insert into tag_colors (user_id, tag_id, color)
+            values (?, ?, ?)
+            on conflict (user_id, tag_id) do update set color = excluded.color
+            "#,
+            user_id,
+            tag_id,
+            color
+        )
+        .execute(&db.writer)
+        .await?;
+    } else {
+        sqlx::query!("delete from tag_colors where user_id = ? and tag_id = ?", user_id, tag_id)
+            .execute(&db.writer)
+            .await?;
+    }
+
+    Ok(true)
+}
+
+/// Retrieves the names of the tags the user made public, in order.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn get_public_tags(db: &Db, user_id: uuid::Uuid) -> Result<Vec<String>> {
+    let _timer = QueryTimer::start("get_public_tags");
+    let names = sqlx::query_scalar!(
+        r#"
+        select t.name
+        from public_tags p
+        join tags t on p.tag_id = t.tag_id
+        where p.user_id = ? and p.unpublished_at is null
+        order by t.name
+        "#,
+        user_id
+    )
+    .fetch_all(&db.reader)
+    .await?;
+
+    Ok(names)
+}
+
+/// Makes one of the user's tags public, or private again with `public` false. Tags made private
+/// are remembered, so their page can tell they were withdrawn.
+///
+/// Returns false if none of the user's bookmarks have the tag.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn set_tag_public(db: &Db, user_id: uuid::Uuid, tag_name: &str, public: bool) -> Result<bool> {
+    let _timer = QueryTimer::start("set_tag_public");
+    let Some(tag_id) = get_user_tag_id(db, user_id, tag_name).await? else {
+        return Ok(false);
+    };
+
+    if public {
+        sqlx::query!(
+            r#"
+            insert into public_tags (user_id, tag_id) values (?, ?)
+            on conflict (user_id, tag_id) do update set unpublished_at = null
+            "#,
+            user_id,
+            tag_id
+        )
+        .execute(&db.writer)
+        .await?;
+    } else {
+        sqlx::query!(
+            r#"
+            update public_tags set unpublished_at = unixepoch()
+            where user_id = ? and tag_id = ? and unpublished_at is null
+            "#,
+            user_id,
+            tag_id
+        )
+        .execute(&db.writer)
+        .await?;
+    }
+
+    Ok(true)
+}
+
+/// Whose public tag a public tag page shows.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PublicTagOwner {
+    /// The tag is public; the ID of its user
+    Public(uuid::Uuid),
+    /// The tag was public, but made private again
+    Withdrawn,
+}
+
+/// Looks up whose public tag a public tag page shows: the user with this username, if they made
+/// the tag public, or once did, and haven't been revoked.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn get_public_tag_owner(db: &Db, username: &str, tag_name: &str) -> Result<Option<PublicTagOwner>> {
+    let _timer = QueryTimer::start("get_public_tag_owner");
+    let row = sqlx::query!(
+        r#"
+        select u.user_id as "user_id!: uuid::Uuid", p.unpublished_at
+        from public_tags p
+        join users u on p.user_id = u.user_id
+        join tags t on p.tag_id = t.tag_id
+        where u.username = ? and t.name = ? and not u.is_revoked
+        "#,
+        username,
+        tag_name
+    )
+    .fetch_optional(&db.reader)
+    .await?;
+
+    Ok(row.map(|row| match row.unpublished_at {
+        None => PublicTagOwner::Public(row.user_id),
+        Some(_) => PublicTagOwner::Withdrawn,
+    }))
+}
+
+/// Finds a tag used by at least one of the user's bookmarks.
+async fn get_user_tag_id(db: &Db, user_id: uuid::Uuid, tag_name: &str) -> Result<Option<Vec<u8>>> {
+    let tag_id = sqlx::query_scalar!(
+        r#"
+        select t.tag_id
+        from tags t
+        where t.name = ? and exists (
+            select 1 from bookmark_tags bt join bookmarks b on bt.bookmark_id = b.bookmark_id
+            where bt.tag_id = t.tag_id and b.user_id = ?
+        )
+        "#,
+        tag_name,
+        user_id
+    )
+    .fetch_optional(&db.reader)
+    .await?;
+
+    Ok(tag_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::seed::{self, SeedProfile, Volumes};
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(parse_color("#3B82F6").as_deref(), Some("#3b82f6"));
+        assert_eq!(parse_color(" #00ff00 ").as_deref(), Some("#00ff00"));
+        assert_eq!(parse_color("3b82f6"), None);
+        assert_eq!(parse_color("#fff"), None);
+        assert_eq!(parse_color("#12345g"), None);
+        assert_eq!(parse_color("red"), None);
+    }
+
+    #[test]
+    fn test_withdrawn_public_tags_are_remembered() {
+        let path = std::env::temp_dir().join(format!("pagepouch-test-{}.db", uuid::Uuid::new_v4()));
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let volumes = Volumes {
+                users: 1,
+                bookmarks: 20,
+                tags: 5,
+            };
+            seed::create_fixture(&path, SeedProfile::Generated(volumes)).await.unwrap();
+            let db = crate::db::connect(&format!("sqlite://{}", path.display()), None).await.unwrap();
+            let user_id: uuid::Uuid = sqlx::query_scalar("select user_id from users where username = 'admin'")
+                .fetch_one(&db.reader)
+                .await
+                .unwrap();
+            let tag: String = sqlx::query_scalar("select t.name from tags t join bookmark_tags bt on t.tag_id = bt.tag_id limit 1")
+                .fetch_one(&db.reader)
+                .await
+                .unwrap();
+            let owner = async || get_public_tag_owner(&db, "admin", &tag).await.unwrap();
+
+            assert_eq!(owner().await, None);
+            assert!(set_tag_public(&db, user_id, &tag, true).await.unwrap());
+            assert_eq!(owner().await, Some(PublicTagOwner::Public(user_id)));
+            assert_eq!(get_public_tags(&db, user_id).await.unwrap(), [tag.clone()]);
+
+            assert!(set_tag_public(&db, user_id, &tag, false).await.unwrap());
+            assert_eq!(owner().await, Some(PublicTagOwner::Withdrawn));
+            assert!(get_public_tags(&db, user_id).await.unwrap().is_empty());
+
+            assert!(set_tag_public(&db, user_id, &tag, true).await.unwrap());
+            assert_eq!(owner().await, Some(PublicTagOwner::Public(user_id)));
+        });
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{suffix}", path.display()));
+        }
+    }
+}
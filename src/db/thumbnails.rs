@@ -0,0 +1,56 @@
+//! Thumbnails of bookmarked pages' preview images, see [`crate::thumbnail`].
+
+use anyhow::Result;
+use uuid::Uuid;
+
+use crate::db::{Db, bookmarks::BookmarkId, timing::QueryTimer};
+
+/// Saves a bookmark's thumbnail, replacing any earlier one, and marks the bookmark as having one.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn set_thumbnail(db: &Db, bookmark_id: BookmarkId, image: &[u8], source_url: &str) -> Result<()> {
+    let _timer = QueryTimer::start("set_thumbnail");
+    let mut tx = db.writer.begin().await?;
+    sqlx::query!(
+        r#"
+        insert or replace into bookmark_thumbnails (bookmark_id, image, source_url)
+        values (?, ?, ?)
+        "#,
+        bookmark_id,
+        image,
+        source_url
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!("update bookmarks set has_thumbnail = true where bookmark_id = ?", bookmark_id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Loads the thumbnail of one of a user's bookmarks, as a JPEG.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn get_thumbnail(db: &Db, user_id: Uuid, bookmark_id: BookmarkId) -> Result<Option<Vec<u8>>> {
+    let _timer = QueryTimer::start("get_thumbnail");
+    let image = sqlx::query_scalar!(
+        r#"
+        select bt.image
+        from bookmark_thumbnails bt
+        join bookmarks b on bt.bookmark_id = b.bookmark_id
+        where bt.bookmark_id = ? and b.user_id = ?
+        "#,
+        bookmark_id,
+        user_id
+    )
+    .fetch_optional(&db.reader)
+    .await?;
+
+    Ok(image)
+}
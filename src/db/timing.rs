@@ -0,0 +1,68 @@
+//! Slow query logging.
+//!
+//! Database functions start a [`QueryTimer`] named after themselves; if it is still alive past
+//! the configured threshold when dropped, a warning is logged with the name and duration. This
+//! is much quieter than sqlx's statement logging, which logs every query.
+//!
+//! Each timer also holds a `db.query` span for the same time, so exported traces show every
+//! query (see [`crate::trace`]).
+
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use tracing::{Span, trace_span, warn};
+
+/// Queries taking at least this many milliseconds are logged as slow.
+static SLOW_QUERY_THRESHOLD_MS: AtomicU64 = AtomicU64::new(250);
+/// Number of slow queries seen since startup.
+static SLOW_QUERY_COUNT: AtomicU64 = AtomicU64::new(0);
+
+/// Sets the duration above which queries are logged as slow.
+pub fn set_slow_query_threshold(threshold: Duration) {
+    let millis = u64::try_from(threshold.as_millis()).unwrap_or(u64::MAX);
+    SLOW_QUERY_THRESHOLD_MS.store(millis, Ordering::Relaxed);
+}
+
+/// Number of slow queries seen since startup.
+pub fn slow_query_count() -> u64 {
+    SLOW_QUERY_COUNT.load(Ordering::Relaxed)
+}
+
+/// Times a database operation from creation until drop, logging it if it was slow.
+#[must_use = "the timer measures until it is dropped"]
+pub struct QueryTimer {
+    name: &'static str,
+    started: Instant,
+    /// Open until the timer is dropped; never entered, so it doesn't hide the caller's span.
+    _span: Span,
+}
+
+impl QueryTimer {
+    /// Starts timing the named operation.
+    pub fn start(name: &'static str) -> Self {
+        Self {
+            name,
+            started: Instant::now(),
+            _span: trace_span!("db.query", query = name),
+        }
+    }
+}
+
+impl Drop for QueryTimer {
+    fn drop(&mut self) {
+        let elapsed = self.started.elapsed();
+        let threshold = Duration::from_millis(SLOW_QUERY_THRESHOLD_MS.load(Ordering::Relaxed));
+        if elapsed >= threshold {
+            let total = SLOW_QUERY_COUNT.fetch_add(1, Ordering::Relaxed) + 1;
+            warn!(
+                query = self.name,
+                duration_ms = elapsed.as_millis(),
+                threshold_ms = threshold.as_millis(),
+                slow_queries_total = total,
+                "🐢 Slow database query."
+            );
+        }
+    }
+}
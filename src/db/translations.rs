@@ -0,0 +1,86 @@
+//! Cached translations of bookmark titles and descriptions.
+
+use anyhow::Result;
+use uuid::Uuid;
+
+use crate::db::{Db, bookmarks::BookmarkId, timing::QueryTimer};
+
+/// A bookmark's title and description, in its own language or translated.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BookmarkText {
+    pub title: String,
+    pub description: Option<String>,
+    /// Language of the text, if known
+    pub language: Option<String>,
+}
+
+/// Loads the text of one of a user's bookmarks, to be translated.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn get_bookmark_text(db: &Db, user_id: Uuid, bookmark_id: BookmarkId) -> Result<Option<BookmarkText>> {
+    let _timer = QueryTimer::start("get_bookmark_text");
+    let text = sqlx::query_as!(
+        BookmarkText,
+        r#"
+        select title, description, language
+        from bookmarks
+        where bookmark_id = ? and user_id = ? and deleted_at is null
+        "#,
+        bookmark_id,
+        user_id
+    )
+    .fetch_optional(&db.reader)
+    .await?;
+
+    Ok(text)
+}
+
+/// Loads a cached translation of one of a user's bookmarks.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn get_translation(db: &Db, user_id: Uuid, bookmark_id: BookmarkId, language: &str) -> Result<Option<BookmarkText>> {
+    let _timer = QueryTimer::start("get_translation");
+    let translation = sqlx::query_as!(
+        BookmarkText,
+        r#"
+        select bt.title, bt.description, bt.language as "language?"
+        from bookmark_translations bt
+        join bookmarks b on bt.bookmark_id = b.bookmark_id
+        where bt.bookmark_id = ? and b.user_id = ? and bt.language = ?
+        "#,
+        bookmark_id,
+        user_id,
+        language
+    )
+    .fetch_optional(&db.reader)
+    .await?;
+
+    Ok(translation)
+}
+
+/// Caches a translation, replacing any earlier one into the same language.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn save_translation(db: &Db, bookmark_id: BookmarkId, language: &str, title: &str, description: Option<&str>) -> Result<()> {
+    let _timer = QueryTimer::start("save_translation");
+    sqlx::query!(
+        r#"
+        insert or replace into bookmark_translations (bookmark_id, language, title, description)
+        values (?, ?, ?, ?)
+        "#,
+        bookmark_id,
+        language,
+        title,
+        description
+    )
+    .execute(&db.writer)
+    .await?;
+
+    Ok(())
+}
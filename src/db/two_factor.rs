@@ -0,0 +1,241 @@
+//! Two-factor authentication database operations.
+//!
+//! A user who sets up two-factor authentication has a TOTP secret here (see [`crate::totp`]). It's
+//! stored when setup starts, but only asked for when signing in once a first code from the
+//! authenticator app confirmed it, which also gives the user their recovery codes. Only keyed hashes
+//! of those are stored, like API tokens.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sqlx::SqliteConnection;
+use uuid::Uuid;
+
+use crate::db::{Db, timing::QueryTimer};
+
+/// Token carried from the password step of signing in to the code step.
+///
+/// Converted to a JWT and signed, like the session cookie, so the code step knows whose password
+/// was right without a session. It expires quickly.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+pub struct TwoFactorToken {
+    pub user_id: Uuid,
+    /// UNIX timestamp
+    pub expires_at: i64,
+}
+
+/// A user's TOTP secret.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UserTotp {
+    pub secret: Vec<u8>,
+    /// Whether setup was confirmed, so codes are asked for when signing in
+    pub enabled: bool,
+    /// Time step of the last code used
+    pub last_used_step: Option<i64>,
+}
+
+/// Retrieves a user's TOTP secret, if they set one up or are setting it up.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn get_totp(db: &Db, user_id: Uuid) -> Result<Option<UserTotp>> {
+    let _timer = QueryTimer::start("get_totp");
+    let totp = sqlx::query_as!(
+        UserTotp,
+        r#"
+        select secret, enabled_at is not null as "enabled!: bool", last_used_step
+        from user_totp where user_id = ?
+        "#,
+        user_id
+    )
+    .fetch_optional(&db.reader)
+    .await?;
+
+    Ok(totp)
+}
+
+/// Whether a user has to give a code when signing in.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn is_totp_enabled(db: &Db, user_id: Uuid) -> Result<bool> {
+    let _timer = QueryTimer::start("is_totp_enabled");
+    let enabled = sqlx::query_scalar!(
+        r#"select exists (select 1 from user_totp where user_id = ? and enabled_at is not null) as "enabled!: bool""#,
+        user_id
+    )
+    .fetch_one(&db.reader)
+    .await?;
+
+    Ok(enabled)
+}
+
+/// Starts setting up two-factor authentication with a new secret, replacing one from an earlier
+/// setup that wasn't confirmed. Returns `false` if it's already set up.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn start_totp_setup(db: &Db, user_id: Uuid, secret: &[u8]) -> Result<bool> {
+    let _timer = QueryTimer::start("start_totp_setup");
+    let result = sqlx::query!(
+        r#"
+        insert into user_totp (user_id, secret) values (?, ?)
+        on conflict (user_id) do update set secret = excluded.secret, last_used_step = null, created_at = unixepoch()
+        where enabled_at is null
+        "#,
+        user_id,
+        secret
+    )
+    .execute(&db.writer)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Finishes setting up two-factor authentication once the code of time step `step` confirmed the
+/// secret, giving the user the recovery codes hashed as `code_hashes`. Returns `false` if there's no
+/// setup to finish.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn enable_totp(db: &Db, user_id: Uuid, step: i64, code_hashes: &[String]) -> Result<bool> {
+    let _timer = QueryTimer::start("enable_totp");
+    let mut tx = db.writer.begin().await?;
+    let result = sqlx::query!(
+        "update user_totp set enabled_at = unixepoch(), last_used_step = ? where user_id = ? and enabled_at is null",
+        step,
+        user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Ok(false);
+    }
+    set_recovery_codes(&mut tx, user_id, code_hashes).await?;
+    tx.commit().await?;
+
+    Ok(true)
+}
+
+/// Records the code of time step `step` as used. Returns `false` if a code of that or a later step
+/// was already used, e.g. by a request racing this one.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn use_totp_step(db: &Db, user_id: Uuid, step: i64) -> Result<bool> {
+    let _timer = QueryTimer::start("use_totp_step");
+    let result = sqlx::query!(
+        r#"
+        update user_totp set last_used_step = $1
+        where user_id = $2 and enabled_at is not null and (last_used_step is null or last_used_step < $1)
+        "#,
+        step,
+        user_id
+    )
+    .execute(&db.writer)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Uses up the recovery code hashed as `code_hash`. Returns `false` if the user has no such code,
+/// or it was already used.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn use_recovery_code(db: &Db, user_id: Uuid, code_hash: &str) -> Result<bool> {
+    let _timer = QueryTimer::start("use_recovery_code");
+    let result = sqlx::query!(
+        "update totp_recovery_codes set used_at = unixepoch() where code_hash = ? and user_id = ? and used_at is null",
+        code_hash,
+        user_id
+    )
+    .execute(&db.writer)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Replaces a user's recovery codes, used or not, with the ones hashed as `code_hashes`. Returns
+/// `false` if two-factor authentication isn't set up.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn replace_recovery_codes(db: &Db, user_id: Uuid, code_hashes: &[String]) -> Result<bool> {
+    let _timer = QueryTimer::start("replace_recovery_codes");
+    let mut tx = db.writer.begin().await?;
+    let enabled = sqlx::query_scalar!(
+        r#"select exists (select 1 from user_totp where user_id = ? and enabled_at is not null) as "enabled!: bool""#,
+        user_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    if !enabled {
+        return Ok(false);
+    }
+    set_recovery_codes(&mut tx, user_id, code_hashes).await?;
+    tx.commit().await?;
+
+    Ok(true)
+}
+
+/// Replaces a user's recovery codes with the ones hashed as `code_hashes`.
+async fn set_recovery_codes(conn: &mut SqliteConnection, user_id: Uuid, code_hashes: &[String]) -> Result<()> {
+    sqlx::query!("delete from totp_recovery_codes where user_id = ?", user_id)
+        .execute(&mut *conn)
+        .await?;
+    for code_hash in code_hashes {
+        sqlx::query!(
+            "insert into totp_recovery_codes (code_hash, user_id) values (?, ?)",
+            code_hash,
+            user_id
+        )
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Counts a user's recovery codes that weren't used yet.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn count_recovery_codes(db: &Db, user_id: Uuid) -> Result<i64> {
+    let _timer = QueryTimer::start("count_recovery_codes");
+    let count = sqlx::query_scalar!(
+        r#"select count(*) as "count!: i64" from totp_recovery_codes where user_id = ? and used_at is null"#,
+        user_id
+    )
+    .fetch_one(&db.reader)
+    .await?;
+
+    Ok(count)
+}
+
+/// Turns two-factor authentication off for a user, or cancels setting it up, forgetting the secret
+/// and recovery codes. Returns `false` if there was nothing to turn off.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn disable_totp(db: &Db, user_id: Uuid) -> Result<bool> {
+    let _timer = QueryTimer::start("disable_totp");
+    let mut tx = db.writer.begin().await?;
+    sqlx::query!("delete from totp_recovery_codes where user_id = ?", user_id)
+        .execute(&mut *tx)
+        .await?;
+    let result = sqlx::query!("delete from user_totp where user_id = ?", user_id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    Ok(result.rows_affected() > 0)
+}
@@ -0,0 +1,81 @@
+//! URL cleaning rule database operations.
+//!
+//! These are the user's own rules; the built-in ones live in [`crate::clean`].
+
+use anyhow::Result;
+use uuid::Uuid;
+
+use crate::db::{Db, timing::QueryTimer};
+
+/// Removes query parameters matching `pattern` from URLs on `domain` and its subdomains.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UrlCleaningRule {
+    pub rule_id: Uuid,
+    pub domain: String,
+    /// Regex matched against query parameter names
+    pub pattern: String,
+}
+
+/// Retrieves all of a user's URL cleaning rules, ordered by domain.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn get_user_rules(db: &Db, user_id: Uuid) -> Result<Vec<UrlCleaningRule>> {
+    let _timer = QueryTimer::start("get_user_rules");
+    let rules = sqlx::query_as!(
+        UrlCleaningRule,
+        r#"
+        select
+            rule_id as "rule_id!: Uuid",
+            domain,
+            pattern
+        from url_cleaning_rules
+        where user_id = ?
+        order by domain, pattern
+        "#,
+        user_id
+    )
+    .fetch_all(&db.reader)
+    .await?;
+
+    Ok(rules)
+}
+
+/// Creates a URL cleaning rule, returning its ID, or `None` if the user already has the same rule.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn create_rule(db: &Db, user_id: Uuid, domain: &str, pattern: &str) -> Result<Option<Uuid>> {
+    let _timer = QueryTimer::start("create_rule");
+    let record = sqlx::query!(
+        r#"
+        insert into url_cleaning_rules (user_id, domain, pattern)
+        values (?, ?, ?)
+        on conflict (user_id, domain, pattern) do nothing
+        returning rule_id as "rule_id!: Uuid"
+        "#,
+        user_id,
+        domain,
+        pattern
+    )
+    .fetch_optional(&db.writer)
+    .await?;
+
+    Ok(record.map(|record| record.rule_id))
+}
+
+/// Deletes one of a user's URL cleaning rules. Returns whether anything was deleted.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn delete_rule(db: &Db, user_id: Uuid, rule_id: Uuid) -> Result<bool> {
+    let _timer = QueryTimer::start("delete_rule");
+    let result = sqlx::query!("delete from url_cleaning_rules where user_id = ? and rule_id = ?", user_id, rule_id)
+        .execute(&db.writer)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
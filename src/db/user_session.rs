@@ -1,14 +1,20 @@
 //! User session management and persistence.
+//!
+//! Sessions are kept in a [`SessionStore`]: by default [`SqliteSessionStore`], next to everything
+//! else in the database. Larger deployments can keep them in Redis instead, with a build with the
+//! `redis` feature (see `db::redis_sessions`): expired sessions vanish on their own there, and
+//! extending a session doesn't write to the database file.
+
+use std::{future::Future, pin::Pin, sync::Arc};
 
 use anyhow::anyhow;
 use chrono::TimeDelta;
 use serde::{Deserialize, Serialize};
-use sqlx::SqlitePool;
 use tracing::{debug, error, warn};
 use uuid::Uuid;
 
 use crate::{
-    db::{self, users::User},
+    db::{self, Db, timing::QueryTimer, users::User},
     error::AppError,
 };
 
@@ -29,163 +35,360 @@ pub struct Session {
 
 impl Session {
     /// Returns a clone of the session token.
+    #[must_use]
     pub fn session_token(&self) -> SessionToken {
         self.sid
     }
 }
 
+/// Future returned by [`SessionStore`] methods.
+pub type SessionFuture<'a, T> = Pin<Box<dyn Future<Output = anyhow::Result<T>> + Send + 'a>>;
+
+/// A session as kept in a [`SessionStore`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StoredSession {
+    pub user_id: Uuid,
+    /// UNIX timestamp
+    pub expires_at: i64,
+}
+
+/// Where sessions are kept.
+pub trait SessionStore: Send + Sync {
+    /// Stores a new session for a user, expiring at `expires_at` (UNIX timestamp), and returns its
+    /// token.
+    fn create(&self, user_id: Uuid, expires_at: i64) -> SessionFuture<'_, SessionToken>;
+
+    /// Looks up a session, or `None` if there's no such session. It may have expired.
+    fn get(&self, session_token: SessionToken) -> SessionFuture<'_, Option<StoredSession>>;
+
+    /// Moves a session's expiry to `expires_at` (UNIX timestamp). Returns whether there was such a
+    /// session.
+    fn set_expiry(&self, session_token: SessionToken, expires_at: i64) -> SessionFuture<'_, bool>;
+
+    /// Removes a session, if there is one.
+    fn remove(&self, session_token: SessionToken) -> SessionFuture<'_, ()>;
+
+    /// Removes all of a user's sessions, signing them out everywhere, returning how many.
+    fn remove_for_user(&self, user_id: Uuid) -> SessionFuture<'_, u64>;
+
+    /// Removes the sessions that expired before `now` (UNIX timestamp), returning how many.
+    fn remove_expired(&self, now: i64) -> SessionFuture<'_, u64>;
+}
+
+/// Keeps sessions in the `user_sessions` table.
+pub struct SqliteSessionStore {
+    db: Db,
+}
+
+impl SqliteSessionStore {
+    #[must_use]
+    pub fn new(db: Db) -> Self {
+        Self { db }
+    }
+}
+
+impl SessionStore for SqliteSessionStore {
+    fn create(&self, user_id: Uuid, expires_at: i64) -> SessionFuture<'_, SessionToken> {
+        Box::pin(async move {
+            let record = sqlx::query!(
+                r#"
+                    insert into user_sessions (
+                        user_id,
+                        expires_at
+                    )
+                    values (?, ?)
+                    returning token_id as "token_id: Uuid"
+                "#,
+                user_id,
+                expires_at
+            )
+            .fetch_one(&self.db.writer)
+            .await?;
+
+            Ok(SessionToken(record.token_id))
+        })
+    }
+
+    fn get(&self, session_token: SessionToken) -> SessionFuture<'_, Option<StoredSession>> {
+        Box::pin(async move {
+            let session = sqlx::query_as!(
+                StoredSession,
+                r#"
+                select
+                    user_id as "user_id: Uuid",
+                    expires_at
+                from user_sessions
+                where token_id = $1
+                "#,
+                session_token.0,
+            )
+            .fetch_optional(&self.db.reader)
+            .await?;
+
+            Ok(session)
+        })
+    }
+
+    fn set_expiry(&self, session_token: SessionToken, expires_at: i64) -> SessionFuture<'_, bool> {
+        Box::pin(async move {
+            let result = sqlx::query!(
+                r#"
+                update user_sessions
+                set expires_at = $1
+                where token_id = $2
+                "#,
+                expires_at,
+                session_token.0,
+            )
+            .execute(&self.db.writer)
+            .await?;
+
+            Ok(result.rows_affected() > 0)
+        })
+    }
+
+    fn remove(&self, session_token: SessionToken) -> SessionFuture<'_, ()> {
+        Box::pin(async move {
+            let res = sqlx::query!(
+                r#"
+                    delete from user_sessions
+                    where token_id = ?
+                "#,
+                session_token.0
+            )
+            .execute(&self.db.writer)
+            .await?;
+
+            debug!(rows = res.rows_affected(), "Removed user session.");
+            Ok(())
+        })
+    }
+
+    fn remove_for_user(&self, user_id: Uuid) -> SessionFuture<'_, u64> {
+        Box::pin(async move {
+            let res = sqlx::query!("delete from user_sessions where user_id = ?", user_id)
+                .execute(&self.db.writer)
+                .await?;
+
+            Ok(res.rows_affected())
+        })
+    }
+
+    fn remove_expired(&self, now: i64) -> SessionFuture<'_, u64> {
+        Box::pin(async move {
+            let res = sqlx::query!(
+                r#"
+                    delete
+                    from user_sessions
+                    where expires_at < ?
+                "#,
+                now
+            )
+            .execute(&self.db.writer)
+            .await?;
+
+            Ok(res.rows_affected())
+        })
+    }
+}
+
+/// Builds the session store: Redis if `redis_url` is set, otherwise the database.
+///
+/// # Errors
+///
+/// Returns an error if `redis_url` is set but Redis can't be reached, or this build has no Redis
+/// support.
+#[cfg_attr(not(feature = "redis"), allow(clippy::unused_async))]
+pub async fn session_store_from_config(db: &Db, redis_url: Option<&str>) -> anyhow::Result<Arc<dyn SessionStore>> {
+    let Some(redis_url) = redis_url else {
+        return Ok(Arc::new(SqliteSessionStore::new(db.clone())));
+    };
+    #[cfg(feature = "redis")]
+    {
+        let store = db::redis_sessions::RedisSessionStore::connect(redis_url).await?;
+        println!("🧰 Sessions are kept in Redis.");
+        Ok(Arc::new(store))
+    }
+    #[cfg(not(feature = "redis"))]
+    {
+        let _ = redis_url;
+        anyhow::bail!("🔥 A Redis URL is set, but this build has no Redis support. Build with `--features redis`.");
+    }
+}
+
 /// Creates a new session for a user.
 ///
 /// This function:
 /// 1. Checks if the user is revoked
-/// 2. Cleans expired sessions from the database
+/// 2. Cleans expired sessions from the store
 /// 3. Creates a new session with a 60-minute expiration
 ///
 /// # Errors
 ///
 /// Returns `AppError::unauthorized` if the user is revoked.
-/// Returns database errors if session creation fails.
-pub async fn make_user_session(pool: &SqlitePool, user: &User) -> Result<Session, AppError> {
+/// Returns store errors if session creation fails.
+///
+/// # Panics
+///
+/// Panics if the session expiry is out of range for a date, which it isn't until the far future.
+pub async fn make_user_session(sessions: &dyn SessionStore, user: &User) -> Result<Session, AppError> {
+    let _timer = QueryTimer::start("make_user_session");
     if user.is_revoked {
         return Err(AppError::unauthorized(anyhow::anyhow!("User is revoked")));
     }
 
-    if let Err(err) = clean_expired_sessions(pool).await {
+    if let Err(err) = clean_expired_sessions(sessions).await {
         // Log it, but don't prevent making a new session
         error!(error = ?err, "Could not clean expired user sessions.");
     }
 
     let now = chrono::Utc::now();
     let expires_at = now.checked_add_signed(DEFAULT_SESSION_DURATION).unwrap().timestamp();
-    let record = sqlx::query!(
-        r#"
-            insert into user_sessions (
-                user_id,
-                expires_at
-            )
-            values (?, ?)
-            returning token_id as "token_id: Uuid"
-        "#,
-        user.user_id,
-        expires_at
-    )
-    .fetch_one(pool)
-    .await?;
+    let sid = sessions.create(user.user_id, expires_at).await?;
 
     debug!(username = user.username, "Created new user session.");
-    Ok(Session {
-        sid: SessionToken(record.token_id),
-    })
+    Ok(Session { sid })
 }
 
-/// Removes a session from the database.
+/// Removes a session from the store.
 ///
 /// Used for logout functionality.
 ///
 /// # Errors
 ///
-/// Returns database errors if deletion fails.
-pub async fn remove_session(pool: &SqlitePool, session_token: &SessionToken) -> Result<(), AppError> {
-    let res = sqlx::query!(
-        r#"
-                delete from user_sessions
-                where token_id = ?
-            "#,
-        session_token.0
-    )
-    .execute(pool)
-    .await?;
-
-    let rows = res.rows_affected();
-    debug!(rows, "Removed user session.");
+/// Returns store errors if deletion fails.
+pub async fn remove_session(sessions: &dyn SessionStore, session_token: &SessionToken) -> Result<(), AppError> {
+    let _timer = QueryTimer::start("remove_session");
+    sessions.remove(*session_token).await?;
     Ok(())
 }
 
+/// Removes all of a user's sessions from the store, e.g. after their password changed.
+///
+/// # Errors
+///
+/// Returns store errors if deletion fails.
+pub async fn remove_user_sessions(sessions: &dyn SessionStore, user_id: Uuid) -> Result<u64, AppError> {
+    let _timer = QueryTimer::start("remove_user_sessions");
+    Ok(sessions.remove_for_user(user_id).await?)
+}
+
 /// Result of a session lookup operation.
 ///
 /// Contains the user, their session, and the signed token.
 pub struct SessionLookup {
     pub user: User,
+    pub session_token: SessionToken,
     pub signed_token: String,
+    /// UNIX timestamp
+    pub expires_at: i64,
 }
 
 /// Looks up a session and associated user from a session token.
 ///
+/// This doesn't extend the session; see [`renew_session`].
+///
 /// # Errors
 ///
-/// Returns `AppError::unauthorized` if the session doesn't exist.
-/// Returns database errors if queries fail.
-pub async fn from_token(pool: &SqlitePool, session_token: SessionToken, signed_token: String) -> Result<SessionLookup, AppError> {
-    let mut tx = pool.begin().await?;
+/// Returns `AppError::unauthorized` if the session doesn't exist or has expired.
+/// Returns store or database errors if queries fail.
+pub async fn from_token(
+    db: &Db,
+    sessions: &dyn SessionStore,
+    session_token: SessionToken,
+    signed_token: String,
+) -> Result<SessionLookup, AppError> {
+    let _timer = QueryTimer::start("from_token");
     let now = chrono::Utc::now();
-    let new_expires = now
-        .checked_add_signed(DEFAULT_SESSION_DURATION)
-        .expect("It's not the year 2000...")
-        .timestamp();
-    let record = sqlx::query!(
-        r#"
-        update user_sessions
-        set expires_at = $1
-        where token_id = $2
-        returning
-            user_id as "user_id: Uuid",
-            expires_at
-        "#,
-        new_expires,
-        session_token.0,
-    )
-    .fetch_optional(&mut *tx)
-    .await?;
-
-    let Some(record) = record else {
+    let Some(session) = sessions.get(session_token).await? else {
         return Err(AppError::unauthorized(anyhow!("No user session found.")));
     };
 
-    if record.expires_at < now.timestamp() {
-        let _ignore = sqlx::query!(
-            r#"
-                delete from user_sessions
-                where token_id = $1
-            "#,
-            session_token.0
-        )
-        .execute(&mut *tx)
-        .await
-        .inspect_err(|e| warn!(error = ?e, "Failed to delete expired session, continuing."));
+    if session.expires_at < now.timestamp() {
+        let _ignore = sessions
+            .remove(session_token)
+            .await
+            .inspect_err(|e| warn!(error = ?e, "Failed to delete expired session, continuing."));
 
         return Err(AppError::unauthorized(anyhow!("User session expired")));
     }
 
-    let user = db::users::get_by_id(&mut tx, record.user_id).await?;
-    tx.commit().await?;
-    Ok(SessionLookup { user, signed_token })
+    let mut conn = db.reader.acquire().await?;
+    let user = db::users::get_by_id(&mut conn, session.user_id).await?;
+    Ok(SessionLookup {
+        user,
+        session_token,
+        signed_token,
+        expires_at: session.expires_at,
+    })
+}
+
+/// Whether a session expiring at `expires_at` is due to be extended at `now` (both UNIX timestamps).
+///
+/// Sessions are only extended once less than [`SESSION_RENEWAL_THRESHOLD`] is left, rather than on
+/// every request, so that most requests don't need a write and a fresh cookie.
+#[must_use]
+pub fn needs_renewal(expires_at: i64, now: i64) -> bool {
+    expires_at - now < SESSION_RENEWAL_THRESHOLD.num_seconds()
+}
+
+/// Extends a session to the full session duration from now, returning the new expiry as a UNIX
+/// timestamp. The session cookie must be re-issued to match.
+///
+/// # Errors
+///
+/// Returns `AppError::unauthorized` if the session no longer exists.
+/// Returns store errors if the update fails.
+///
+/// # Panics
+///
+/// Panics if the session expiry is out of range for a date, which it isn't until the far future.
+pub async fn renew_session(sessions: &dyn SessionStore, session_token: SessionToken) -> Result<i64, AppError> {
+    let _timer = QueryTimer::start("renew_session");
+    let new_expires = chrono::Utc::now()
+        .checked_add_signed(DEFAULT_SESSION_DURATION)
+        .expect("It's not the year 2000...")
+        .timestamp();
+    if !sessions.set_expiry(session_token, new_expires).await? {
+        return Err(AppError::unauthorized(anyhow!("No user session found.")));
+    }
+    Ok(new_expires)
 }
 
 pub const DEFAULT_SESSION_MINUTES: i64 = 60;
 const DEFAULT_SESSION_DURATION: TimeDelta = TimeDelta::minutes(DEFAULT_SESSION_MINUTES);
+/// Sessions with less than this left are extended (with a new cookie) on the next request.
+pub const SESSION_RENEWAL_THRESHOLD: TimeDelta = TimeDelta::minutes(DEFAULT_SESSION_MINUTES / 2);
 
-/// Removes expired sessions from the database.
+/// Removes expired sessions from the store, returning how many were removed.
 ///
-/// Called automatically when creating new sessions to prevent
-/// accumulation of expired session records.
+/// Called automatically when creating new sessions, and by the nightly maintenance job, to
+/// prevent accumulation of expired session records.
 ///
 /// # Errors
 ///
-/// Returns database errors if deletion fails.
-async fn clean_expired_sessions(pool: &SqlitePool) -> Result<(), AppError> {
-    let now = chrono::Utc::now().timestamp();
-    let res = sqlx::query!(
-        r#"
-            delete
-            from user_sessions
-            where expires_at < ?
-        "#,
-        now
-    )
-    .execute(pool)
-    .await?;
-
-    debug!(sessions_deleted = res.rows_affected(), "Deleted expired user sessions.");
+/// Returns store errors if deletion fails.
+pub async fn clean_expired_sessions(sessions: &dyn SessionStore) -> Result<u64, AppError> {
+    let deleted = sessions.remove_expired(chrono::Utc::now().timestamp()).await?;
+    debug!(sessions_deleted = deleted, "Deleted expired user sessions.");
 
-    Ok(())
+    Ok(deleted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_needs_renewal() {
+        let now = 1_700_000_000;
+        let threshold = SESSION_RENEWAL_THRESHOLD.num_seconds();
+
+        // Freshly created or renewed
+        assert!(!needs_renewal(now + DEFAULT_SESSION_DURATION.num_seconds(), now));
+        assert!(!needs_renewal(now + threshold, now));
+        // Past the threshold
+        assert!(needs_renewal(now + threshold - 1, now));
+        assert!(needs_renewal(now, now));
+    }
 }
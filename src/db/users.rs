@@ -4,10 +4,13 @@ use argon2::{
     Argon2, PasswordHash, PasswordVerifier as _,
     password_hash::{PasswordHasher, SaltString, rand_core::OsRng},
 };
-use sqlx::{SqliteConnection, SqlitePool};
+use sqlx::SqliteConnection;
 use uuid::Uuid;
 
-use crate::error::AppError;
+use crate::{
+    db::{Db, timing::QueryTimer},
+    error::AppError,
+};
 
 /// Represents a user in the system. Its claims should only be treated as valid
 /// and correct for the lifetime of the single request in which it was generated.
@@ -17,47 +20,127 @@ pub struct User {
     pub user_id: Uuid,
     pub username: String,
     pub is_revoked: bool,
+    /// Can see the admin dashboard and run instance maintenance
+    pub is_admin: bool,
 }
 
-const TIMING_PASSWORD_HASH: &str = "ThisIsNotARealPasswordNorAHash";
+/// Verified against when there's no such user, so a failed login takes as long either way and doesn't
+/// tell which usernames or emails exist. Must be a valid hash with the default parameters, or the
+/// verification would fail right away.
+const TIMING_PASSWORD_HASH: &str = "$argon2id$v=19$m=19456,t=2,p=1$dGltaW5nLW9ubHktc2FsdA$vORUGKlnuJa7HCZTyQ/eA1vhQkcKwhb/e9BWBNxMJ2g";
+
+/// Longest email address accepted, as limited by SMTP.
+pub const MAX_EMAIL_LENGTH: usize = 254;
 
-/// Authenticates a user by verifying their username and password.
+/// Retrieves every user who isn't revoked, ordered by username.
 ///
 /// # Errors
 ///
-/// Returns an error if:
-/// - User doesn't exist
-/// - Password is incorrect
-/// - Password hash verification fails
-pub async fn check_username_password(pool: &SqlitePool, username: String, password: String) -> Result<User, AppError> {
-    let user = sqlx::query!(
+/// Returns an error if database query fails.
+pub async fn get_active_users(db: &Db) -> Result<Vec<User>, AppError> {
+    let _timer = QueryTimer::start("get_active_users");
+    let users = sqlx::query_as!(
+        User,
         r#"
-            select user_id as "user_id: Uuid", username, password_hash, is_revoked
-            from users where username = $1
+            select
+                user_id as "user_id!: Uuid",
+                username,
+                is_revoked,
+                is_admin
+            from users
+            where not is_revoked
+            order by username
+        "#
+    )
+    .fetch_all(&db.reader)
+    .await?;
+
+    Ok(users)
+}
+
+/// Authenticates a user by verifying their username or email, either case-insensitive, and password.
+///
+/// If one user's username is another's email, the username wins.
+///
+/// # Errors
+///
+/// Returns `AppError::bad_login` if there's no such user, the password is wrong or the user is
+/// revoked, the same way for each, see [`verify_login`]. Returns other errors if the database query
+/// or password hash verification fails.
+pub async fn check_username_password(db: &Db, login: String, password: String) -> Result<User, AppError> {
+    let login = login.trim();
+    let record = sqlx::query!(
+        r#"
+            select user_id as "user_id: Uuid", username, password_hash, is_revoked, is_admin
+            from users where username = $1 or email = $1
+            order by username = $1 desc
+            limit 1
         "#,
-        username
+        login
     )
-    .fetch_optional(pool)
+    .fetch_optional(&db.reader)
     .await?
-    .ok_or_else(|| AppError::bad_login(anyhow!("Username '{username}' not found")));
+    .map(|record| {
+        (
+            User {
+                user_id: record.user_id,
+                username: record.username,
+                is_revoked: record.is_revoked,
+                is_admin: record.is_admin,
+            },
+            record.password_hash,
+        )
+    });
 
-    let user = match user {
-        Ok(user) => {
-            verify_hash(&password, &user.password_hash)?;
-            user
-        }
-        Err(e) => {
-            // Eliminate timing attack by calculating a password hash anyway even though we know it will fail
-            let _ = verify_hash(&password, TIMING_PASSWORD_HASH);
-            return Err(e);
-        }
+    verify_login(record, login, &password)
+}
+
+/// Checks a password against the user found for a login and their password hash, if one was found.
+///
+/// Whether there's no such user, the password is wrong or the user is revoked, it fails with the same
+/// `AppError::bad_login`, after verifying a password hash, so neither the response nor how long it
+/// takes tells which accounts exist or which passwords are right.
+fn verify_login(record: Option<(User, String)>, login: &str, password: &str) -> Result<User, AppError> {
+    let Some((user, password_hash)) = record else {
+        let _ = verify_hash(password, TIMING_PASSWORD_HASH);
+        return Err(AppError::bad_login(anyhow!("No user with the username or email '{login}'")));
     };
+    verify_hash(password, &password_hash)?;
+    if user.is_revoked {
+        return Err(AppError::bad_login(anyhow!("User '{}' is revoked", user.username)));
+    }
 
-    Ok(User {
-        user_id: user.user_id,
-        username: user.username,
-        is_revoked: user.is_revoked,
-    })
+    Ok(user)
+}
+
+/// Creates a user who can sign in with `username` and the password hashed as `password_hash`, see
+/// [`create_hash`]. The first user of an instance without an admin is made its admin. Returns
+/// `None` if the username is taken, regardless of case.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn create_user(db: &Db, username: &str, password_hash: &str) -> Result<Option<User>, AppError> {
+    let _timer = QueryTimer::start("create_user");
+    let user = sqlx::query_as!(
+        User,
+        r#"
+            insert into users (username, password_hash, is_admin)
+            values (?, ?, not exists (select 1 from users where is_admin))
+            on conflict (username) do nothing
+            returning
+                user_id as "user_id!: Uuid",
+                username,
+                is_revoked,
+                is_admin
+        "#,
+        username,
+        password_hash
+    )
+    .fetch_optional(&db.writer)
+    .await?;
+
+    Ok(user)
 }
 
 /// Retrieves a user by their ID.
@@ -68,12 +151,14 @@ pub async fn check_username_password(pool: &SqlitePool, username: String, passwo
 /// - User doesn't exist (`SqliteError::RowNotFound`)
 /// - Database query fails
 pub async fn get_by_id(pool: &mut SqliteConnection, user_id: Uuid) -> Result<User, AppError> {
+    let _timer = QueryTimer::start("get_by_id");
     let record = sqlx::query!(
         r#"
             select
                 user_id as "user_id: Uuid",
                 username,
-                is_revoked
+                is_revoked,
+                is_admin
             from users
             where user_id = ?
         "#,
@@ -86,9 +171,57 @@ pub async fn get_by_id(pool: &mut SqliteConnection, user_id: Uuid) -> Result<Use
         user_id: record.user_id,
         username: record.username,
         is_revoked: record.is_revoked,
+        is_admin: record.is_admin,
     })
 }
 
+/// Retrieves a user's email address, if they've set one.
+///
+/// # Errors
+///
+/// Returns an error if the user doesn't exist or the database query fails.
+pub async fn get_email(db: &Db, user_id: Uuid) -> Result<Option<String>, AppError> {
+    let _timer = QueryTimer::start("get_email");
+    let email = sqlx::query_scalar!("select email from users where user_id = ?", user_id)
+        .fetch_one(&db.reader)
+        .await?;
+
+    Ok(email)
+}
+
+/// Sets or, with `None`, removes a user's email address. Returns `false` if another user already
+/// has the address.
+///
+/// # Errors
+///
+/// Returns an error if database query fails.
+pub async fn set_email(db: &Db, user_id: Uuid, email: Option<&str>) -> Result<bool, AppError> {
+    let _timer = QueryTimer::start("set_email");
+    let result = sqlx::query!(
+        r#"
+            update users set email = $1, updated_at = unixepoch()
+            where user_id = $2
+            and not exists (select 1 from users where email = $1 and user_id != $2)
+        "#,
+        email,
+        user_id
+    )
+    .execute(&db.writer)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Whether `email` looks like an email address: something, an `@`, and a domain with a dot.
+#[must_use]
+pub fn is_valid_email(email: &str) -> bool {
+    email.len() <= MAX_EMAIL_LENGTH
+        && !email.contains(char::is_whitespace)
+        && email.rsplit_once('@').is_some_and(|(local, domain)| {
+            !local.is_empty() && domain.split('.').count() > 1 && domain.split('.').all(|part| !part.is_empty())
+        })
+}
+
 /// Verifies a plaintext password against an Argon2 hash.
 ///
 /// # Errors
@@ -108,10 +241,123 @@ pub fn verify_hash(plaintext: &str, hash: &str) -> Result<(), AppError> {
 /// # Errors
 ///
 /// Returns `AppError::internal` if hashing fails.
-#[allow(unused)]
 pub fn create_hash(plaintext: &[u8]) -> Result<String, AppError> {
     let hash = PasswordHasher::hash_password(&argon2::Argon2::default(), plaintext, &SaltString::generate(&mut OsRng))
         .map_err(|e| AppError::internal(anyhow!("Failed to hash password: {e}")))?
         .to_string();
     Ok(hash)
 }
+
+/// How often a user's bookmarks have changed, and when they last did.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DataVersion {
+    /// Incremented by database triggers on every change to the user's bookmarks or their tags
+    pub version: i64,
+    /// UNIX timestamp
+    pub changed_at: i64,
+}
+
+/// Retrieves the current version of a user's bookmark data.
+///
+/// # Errors
+///
+/// Returns an error if the user doesn't exist or the database query fails.
+pub async fn get_data_version(db: &Db, user_id: Uuid) -> Result<DataVersion, AppError> {
+    let _timer = QueryTimer::start("get_data_version");
+    let data_version = sqlx::query_as!(
+        DataVersion,
+        r#"
+            select data_version as version, data_changed_at as changed_at
+            from users
+            where user_id = ?
+        "#,
+        user_id
+    )
+    .fetch_one(&db.reader)
+    .await?;
+
+    Ok(data_version)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use axum::response::IntoResponse as _;
+
+    use super::*;
+    use crate::error::ErrorMessage;
+
+    fn user(is_revoked: bool) -> User {
+        User {
+            user_id: Uuid::new_v4(),
+            username: "ann".to_string(),
+            is_revoked,
+            is_admin: false,
+        }
+    }
+
+    /// The status and message of a failed login, and how long it took.
+    fn failed_login(record: Option<(User, String)>, password: &str) -> (u16, String, Duration) {
+        let started = Instant::now();
+        let err = verify_login(record, "ann", password).expect_err("the login fails");
+        let elapsed = started.elapsed();
+        let response = err.into_response();
+        let message = response
+            .extensions()
+            .get::<ErrorMessage>()
+            .expect("the response has a message")
+            .0
+            .clone();
+        (response.status().as_u16(), message, elapsed)
+    }
+
+    #[test]
+    fn test_login_failures_are_uniform() {
+        let hash = create_hash(b"correct horse").unwrap();
+        assert_eq!(
+            verify_login(Some((user(false), hash.clone())), "ann", "correct horse")
+                .unwrap()
+                .username,
+            "ann"
+        );
+
+        let failures = [
+            // No such user
+            failed_login(None, "correct horse"),
+            // Wrong password
+            failed_login(Some((user(false), hash.clone())), "wrong horse"),
+            // Right password, but revoked
+            failed_login(Some((user(true), hash)), "correct horse"),
+        ];
+        for (status, message, _) in &failures {
+            assert_eq!(*status, 401);
+            assert_eq!(message, &failures[0].1);
+        }
+        // Each verifies a hash, rather than failing fast; the bound is loose so busy test runners don't fail it
+        let slowest = failures.iter().map(|(_, _, elapsed)| *elapsed).max().unwrap();
+        for (_, _, elapsed) in &failures {
+            assert!(*elapsed * 4 > slowest, "{elapsed:?} is much faster than {slowest:?}");
+        }
+    }
+
+    #[test]
+    fn test_timing_password_hash() {
+        // Parses, so verifying against it takes as long as against a real user's hash
+        let hash = PasswordHash::new(TIMING_PASSWORD_HASH).unwrap();
+        assert_eq!(hash.params, Argon2::default().params().try_into().unwrap());
+        assert!(verify_hash("admin123", TIMING_PASSWORD_HASH).is_err());
+    }
+
+    #[test]
+    fn test_is_valid_email() {
+        assert!(is_valid_email("ann@example.com"));
+        assert!(is_valid_email("ann+bookmarks@mail.example.co.uk"));
+        assert!(!is_valid_email("ann"));
+        assert!(!is_valid_email("@example.com"));
+        assert!(!is_valid_email("ann@localhost"));
+        assert!(!is_valid_email("ann@example."));
+        assert!(!is_valid_email("ann @example.com"));
+        assert!(!is_valid_email(&format!("{}@example.com", "a".repeat(MAX_EMAIL_LENGTH))));
+    }
+}
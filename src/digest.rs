@@ -0,0 +1,191 @@
+//! Email digests of new bookmarks.
+//!
+//! Users who opt in get an email every day or week listing the bookmarks they saved in that time,
+//! grouped by tag, as a reminder of what they meant to come back to. The job in [`crate::jobs`]
+//! looks for due digests every hour, so a digest goes out at about the time of day it was turned
+//! on. When nothing was saved no email is sent, but the digest still counts as sent, so the next
+//! one covers a whole period again.
+
+use std::collections::BTreeMap;
+
+use crate::{
+    db::{bookmarks::BookmarkItem, settings::DigestFrequency},
+    mail::Email,
+};
+
+/// Most bookmarks listed in one digest, the most recently saved ones.
+pub const DIGEST_LIMIT: i64 = 100;
+/// Heading of the bookmarks without tags, after all the tags.
+const UNTAGGED: &str = "Untagged";
+
+/// A bookmark as it's listed in a digest.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DigestEntry {
+    pub title: String,
+    pub url: String,
+    pub tags: Vec<String>,
+}
+
+impl From<BookmarkItem> for DigestEntry {
+    fn from(bookmark: BookmarkItem) -> Self {
+        Self {
+            title: bookmark.title,
+            url: bookmark.url,
+            tags: bookmark.tags.into_iter().map(|tag| tag.name).collect(),
+        }
+    }
+}
+
+/// Groups entries by tag, with the tags in alphabetical order and the untagged entries last. An
+/// entry with several tags is listed under each of them.
+fn group_by_tag(entries: &[DigestEntry]) -> Vec<(&str, Vec<&DigestEntry>)> {
+    let mut tagged: BTreeMap<&str, Vec<&DigestEntry>> = BTreeMap::new();
+    let mut untagged = Vec::new();
+    for entry in entries {
+        if entry.tags.is_empty() {
+            untagged.push(entry);
+        }
+        for tag in &entry.tags {
+            tagged.entry(tag.as_str()).or_default().push(entry);
+        }
+    }
+
+    let mut groups: Vec<_> = tagged.into_iter().collect();
+    if !untagged.is_empty() {
+        groups.push((UNTAGGED, untagged));
+    }
+    groups
+}
+
+/// The digest email listing the bookmarks a user saved in the last day or week, or `None` if they
+/// saved none or digests are off. `more` says there were more than [`DIGEST_LIMIT`], which aren't
+/// listed; `public_url` is where the instance is reached, for links back to it.
+pub fn digest_email(
+    to: &str,
+    username: &str,
+    frequency: DigestFrequency,
+    entries: &[DigestEntry],
+    more: bool,
+    public_url: &str,
+) -> Option<Email> {
+    let period = match frequency {
+        DigestFrequency::Off => return None,
+        DigestFrequency::Daily => "day",
+        DigestFrequency::Weekly => "week",
+    };
+    if entries.is_empty() {
+        return None;
+    }
+
+    let count = if entries.len() == 1 {
+        "1 new bookmark".to_string()
+    } else if more {
+        format!("{}+ new bookmarks", entries.len())
+    } else {
+        format!("{} new bookmarks", entries.len())
+    };
+    let mut sections = vec![format!("Here's what you saved to PagePouch in the last {period}, {username}.")];
+    for (tag, entries) in group_by_tag(entries) {
+        let lines: Vec<String> = entries.iter().map(|entry| format!("- {}\n  {}", entry.title, entry.url)).collect();
+        sections.push(format!("{tag}\n{}", lines.join("\n")));
+    }
+    if more {
+        sections.push(format!("Only the {DIGEST_LIMIT} most recent are listed."));
+    }
+    sections.push(format!(
+        "See all your bookmarks: {public_url}/\n\
+         To get this email less often or not at all, change the digest in your settings: {public_url}/settings"
+    ));
+
+    Some(Email {
+        to: to.to_string(),
+        subject: format!("Your {} PagePouch digest: {count}", frequency.as_str()),
+        body: sections.join("\n\n") + "\n",
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(title: &str, tags: &[&str]) -> DigestEntry {
+        DigestEntry {
+            title: title.to_string(),
+            url: format!("https://example.com/{title}"),
+            tags: tags.iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    #[test]
+    fn test_group_by_tag() {
+        let entries = [entry("a", &["rust", "async"]), entry("b", &[]), entry("c", &["rust"])];
+        let groups: Vec<(&str, Vec<&str>)> = group_by_tag(&entries)
+            .into_iter()
+            .map(|(tag, entries)| (tag, entries.iter().map(|entry| entry.title.as_str()).collect()))
+            .collect();
+        assert_eq!(
+            groups,
+            vec![("async", vec!["a"]), ("rust", vec!["a", "c"]), ("Untagged", vec!["b"])]
+        );
+    }
+
+    #[test]
+    fn test_digest_email() {
+        let entries = [entry("a", &["rust"]), entry("b", &[])];
+        let email = digest_email(
+            "ann@example.com",
+            "ann",
+            DigestFrequency::Weekly,
+            &entries,
+            false,
+            "https://pp.example.com",
+        )
+        .unwrap();
+        assert_eq!(email.to, "ann@example.com");
+        assert_eq!(email.subject, "Your weekly PagePouch digest: 2 new bookmarks");
+        assert!(email.body.contains("in the last week, ann."));
+        assert!(email.body.contains("\nrust\n- a\n  https://example.com/a\n"));
+        assert!(email.body.contains("\nUntagged\n- b\n  https://example.com/b\n"));
+        assert!(email.body.contains("https://pp.example.com/settings"));
+        assert!(!email.body.contains("most recent"));
+
+        let email = digest_email(
+            "ann@example.com",
+            "ann",
+            DigestFrequency::Daily,
+            &entries,
+            true,
+            "https://pp.example.com",
+        )
+        .unwrap();
+        assert_eq!(email.subject, "Your daily PagePouch digest: 2+ new bookmarks");
+        assert!(email.body.contains("Only the 100 most recent are listed."));
+    }
+
+    #[test]
+    fn test_no_digest_email() {
+        let entries = [entry("a", &[])];
+        assert!(
+            digest_email(
+                "ann@example.com",
+                "ann",
+                DigestFrequency::Daily,
+                &[],
+                false,
+                "https://pp.example.com"
+            )
+            .is_none()
+        );
+        assert!(
+            digest_email(
+                "ann@example.com",
+                "ann",
+                DigestFrequency::Off,
+                &entries,
+                false,
+                "https://pp.example.com"
+            )
+            .is_none()
+        );
+    }
+}
@@ -1,19 +1,33 @@
 //! Encryption and token management utilities.
 
-use aes_gcm::{Aes256Gcm, KeyInit as _, aead::OsRng};
+use aes_gcm::{
+    Aes256Gcm, KeyInit as _,
+    aead::{OsRng, rand_core::RngCore as _},
+};
 use anyhow::anyhow;
+use base64::{Engine as _, prelude::BASE64_URL_SAFE_NO_PAD};
 use hmac::Hmac;
 use jwt::{SignWithKey, VerifyWithKey as _};
 use serde::Deserialize;
 use sha2::Sha256;
 
-use crate::error::AppError;
+use crate::{error::AppError, totp::normalize_recovery_code};
 
 /// Generates a cryptographically secure 256-bit encryption key.
 pub fn generate_root_key() -> Vec<u8> {
     Aes256Gcm::generate_key(OsRng).to_vec()
 }
 
+/// Prefix of API tokens, so they're easy to recognize, e.g. by secret scanners.
+pub const API_TOKEN_PREFIX: &str = "pp_";
+
+/// Generates a new API token: the prefix and 256 random bits as URL-safe base64.
+pub fn generate_api_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    format!("{API_TOKEN_PREFIX}{}", BASE64_URL_SAFE_NO_PAD.encode(bytes))
+}
+
 /// Provider for encryption and token operations.
 pub struct EncryptionProvider {
     /// The root encryption key used for HMAC operations.
@@ -48,6 +62,34 @@ impl EncryptionProvider {
             .map_err(|err| AppError::internal(anyhow!("Failed to sign token with HMAC: {err}")))
     }
 
+    /// Hashes an API token for storage and lookup, as hex-encoded HMAC-SHA256 under the provider's
+    /// key.
+    ///
+    /// Tokens are random and long, so a fast hash is enough; keying it means a copy of the database
+    /// alone isn't enough to check guessed tokens against.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if HMAC key initialization fails.
+    pub fn hash_api_token(&self, token: &str) -> Result<String, AppError> {
+        use hmac::Mac;
+
+        let mut hmac = <Hmac<Sha256> as Mac>::new_from_slice(self.key.as_ref())
+            .map_err(|err| AppError::internal(anyhow!("Failed to create HMAC: {err}")))?;
+        hmac.update(token.as_bytes());
+        Ok(format!("{:x}", hmac.finalize().into_bytes()))
+    }
+
+    /// Hashes a two-factor recovery code for storage and lookup, the way API tokens are (see
+    /// [`Self::hash_api_token`]), after [`normalize_recovery_code`] so it can be typed in loosely.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if HMAC key initialization fails.
+    pub fn hash_recovery_code(&self, code: &str) -> Result<String, AppError> {
+        self.hash_api_token(&normalize_recovery_code(code))
+    }
+
     /// Verifies and decodes a JWT token.
     ///
     /// This method:
@@ -77,3 +119,25 @@ impl EncryptionProvider {
         Ok(claims)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_api_tokens() {
+        let token = generate_api_token();
+        assert!(token.starts_with(API_TOKEN_PREFIX));
+        assert_eq!(token.len(), API_TOKEN_PREFIX.len() + 43);
+        assert_ne!(token, generate_api_token());
+
+        let encryption = EncryptionProvider::new(generate_root_key());
+        let hash = encryption.hash_api_token(&token).unwrap();
+        assert_eq!(hash.len(), 64);
+        assert_eq!(hash, encryption.hash_api_token(&token).unwrap());
+        assert_ne!(hash, encryption.hash_api_token(&generate_api_token()).unwrap());
+        // Keyed: another instance's hash of the same token doesn't match
+        let other = EncryptionProvider::new(generate_root_key());
+        assert_ne!(hash, other.hash_api_token(&token).unwrap());
+    }
+}
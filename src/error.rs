@@ -6,7 +6,10 @@ use askama::Template;
 use axum::{http::StatusCode, response::IntoResponse};
 use tracing::{error, warn};
 
-use crate::handler::HtmlTemplate;
+use crate::{
+    handler::HtmlTemplate,
+    report::{ErrorKind, ReportableError},
+};
 
 /// Application-wide error type that encapsulates both internal and user-facing errors.
 ///
@@ -27,6 +30,11 @@ pub struct AppError {
 /// Type alias for Results that use `AppError` as the error type.
 pub type AppResult<T> = Result<T, AppError>;
 
+/// The message shown to the user for an error, kept on the response so it can be given in
+/// another format, see [`crate::handler::middlewares::json_errors_middleware`].
+#[derive(Clone, Debug)]
+pub struct ErrorMessage(pub String);
+
 impl AppError {
     /// Creates an unauthorized (401) error.
     ///
@@ -47,7 +55,7 @@ impl AppError {
         Self {
             status: StatusCode::UNAUTHORIZED,
             internal: internal_msg.into(),
-            user_message: Some("Incorrect username or password.".to_string()),
+            user_message: Some("Incorrect username, email or password.".to_string()),
         }
     }
 
@@ -91,6 +99,12 @@ impl AppError {
         }
     }
 
+    /// The HTTP status code the error answers with.
+    #[must_use]
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
     /// Adds a user-facing message to the error.
     ///
     /// This message will be displayed to the user instead of the default
@@ -150,13 +164,22 @@ impl IntoResponse for AppError {
 
         let template = ErrorTemplate {
             title: "Error",
-            message,
+            message: message.clone(),
             status_code: self.status.as_u16(),
         };
 
         let headers = [("hx-retarget", "#error-container"), ("hx-reswap", "innerHTML")];
 
-        (self.status, headers, HtmlTemplate(template)).into_response()
+        let mut response = (self.status, headers, HtmlTemplate(template)).into_response();
+        response.extensions_mut().insert(ErrorMessage(message));
+        if self.status.is_server_error() {
+            // Picked up by the error reporting middleware, which knows the request context
+            response.extensions_mut().insert(ReportableError {
+                kind: ErrorKind::Internal,
+                message: format!("{:#}", self.internal),
+            });
+        }
+        response
     }
 }
 
@@ -0,0 +1,161 @@
+//! Account export and import as JSON.
+//!
+//! The export document carries everything about an account besides the bookmarks themselves that
+//! is needed to pick up where you left off on another instance: user settings and smart
+//! collections. It's versioned so that older exports keep importing as more is added; any field
+//! missing from an import keeps its default.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db::{
+        bookmarks::SortOrder,
+        collections::SmartCollection,
+        settings::{DigestFrequency, ListDisplay, UserSettings},
+    },
+    search::SearchDefaults,
+    translate::normalize_language,
+};
+
+/// Version written to new exports. Imports from newer versions are refused.
+pub const EXPORT_VERSION: u32 = 1;
+
+/// An exported account.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct AccountExport {
+    pub version: u32,
+    /// UNIX timestamp of when the export was made
+    #[serde(default)]
+    pub exported_at: i64,
+    #[serde(default)]
+    pub settings: ExportedSettings,
+    #[serde(default)]
+    pub smart_collections: Vec<ExportedCollection>,
+}
+
+/// User settings as they appear in an export.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct ExportedSettings {
+    pub disable_fetch: bool,
+    pub list: ListDisplay,
+    pub preferred_language: Option<String>,
+    pub resurface: bool,
+    pub search: SearchDefaults,
+    pub default_sort: SortOrder,
+    pub digest: DigestFrequency,
+}
+
+impl Default for ExportedSettings {
+    fn default() -> Self {
+        UserSettings::default().into()
+    }
+}
+
+impl From<UserSettings> for ExportedSettings {
+    fn from(settings: UserSettings) -> Self {
+        Self {
+            disable_fetch: settings.disable_fetch,
+            list: settings.list,
+            preferred_language: settings.preferred_language,
+            resurface: settings.resurface,
+            search: settings.search,
+            default_sort: settings.default_sort,
+            digest: settings.digest,
+        }
+    }
+}
+
+impl From<ExportedSettings> for UserSettings {
+    fn from(settings: ExportedSettings) -> Self {
+        Self {
+            disable_fetch: settings.disable_fetch,
+            list: settings.list,
+            preferred_language: settings.preferred_language.as_deref().and_then(normalize_language),
+            resurface: settings.resurface,
+            search: settings.search,
+            default_sort: settings.default_sort,
+            digest: settings.digest,
+        }
+    }
+}
+
+/// A smart collection as it appears in an export; IDs are not carried over.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ExportedCollection {
+    pub name: String,
+    pub query: String,
+}
+
+impl From<SmartCollection> for ExportedCollection {
+    fn from(collection: SmartCollection) -> Self {
+        Self {
+            name: collection.name,
+            query: collection.query,
+        }
+    }
+}
+
+impl AccountExport {
+    /// Builds an export of the given settings and collections.
+    pub fn new(settings: UserSettings, collections: Vec<SmartCollection>) -> Self {
+        Self {
+            version: EXPORT_VERSION,
+            exported_at: chrono::Utc::now().timestamp(),
+            settings: settings.into(),
+            smart_collections: collections.into_iter().map(ExportedCollection::from).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::db::settings::ListDensity;
+
+    #[test]
+    fn test_export_round_trip() {
+        let settings = UserSettings {
+            disable_fetch: true,
+            list: ListDisplay {
+                density: ListDensity::Compact,
+                show_favicon: true,
+                ..ListDisplay::default()
+            },
+            preferred_language: Some("en".to_string()),
+            resurface: true,
+            search: SearchDefaults {
+                fuzzy: true,
+                ..SearchDefaults::default()
+            },
+            default_sort: SortOrder::Title,
+            digest: DigestFrequency::Weekly,
+        };
+        let collections = vec![SmartCollection {
+            collection_id: Uuid::new_v4(),
+            name: "GitHub Rust".to_string(),
+            query: "site:github.com #rust".to_string(),
+        }];
+
+        let export = AccountExport::new(settings.clone(), collections);
+        let json = serde_json::to_string(&export).unwrap();
+        assert!(json.contains("\"density\":\"compact\""));
+        assert!(!json.contains("collection_id"));
+
+        let imported: AccountExport = serde_json::from_str(&json).unwrap();
+        assert_eq!(imported, export);
+        assert_eq!(UserSettings::from(imported.settings), settings);
+    }
+
+    #[test]
+    fn test_import_fills_in_missing_fields() {
+        let imported: AccountExport = serde_json::from_str(r#"{"version": 1, "settings": {"list": {"show_domain": true}}}"#).unwrap();
+        let settings = UserSettings::from(imported.settings);
+        assert!(!settings.disable_fetch);
+        assert!(settings.list.show_domain);
+        assert!(settings.list.show_tags);
+        assert!(imported.smart_collections.is_empty());
+    }
+}
@@ -0,0 +1,157 @@
+//! Feature flags for dark-launching big new subsystems.
+//!
+//! Each [`Feature`] is off unless the instance turns it on in `PAGEPOUCH_FEATURES`. The admin can
+//! also turn a feature on (or off) for individual users, e.g. to try it out on their own account
+//! before enabling it for everyone; a user's override always wins over the instance default.
+
+use std::str::FromStr;
+
+use anyhow::{Result, anyhow};
+use uuid::Uuid;
+
+use crate::db::{self, Db, feature_flags::FeatureOverride};
+
+/// A subsystem that can be switched on per instance or per user.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Feature {
+    Archiving,
+    SyncApi,
+    ActivityPub,
+}
+
+impl Feature {
+    /// Every feature, in the order they're listed to the admin.
+    pub const ALL: [Feature; 3] = [Feature::Archiving, Feature::SyncApi, Feature::ActivityPub];
+
+    /// Name used in `PAGEPOUCH_FEATURES` and stored with user overrides.
+    pub fn name(self) -> &'static str {
+        match self {
+            Feature::Archiving => "archiving",
+            Feature::SyncApi => "sync_api",
+            Feature::ActivityPub => "activitypub",
+        }
+    }
+
+    /// Name shown to users.
+    pub fn label(self) -> &'static str {
+        match self {
+            Feature::Archiving => "Archiving",
+            Feature::SyncApi => "Sync API",
+            Feature::ActivityPub => "ActivityPub",
+        }
+    }
+}
+
+impl FromStr for Feature {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Feature::ALL
+            .into_iter()
+            .find(|feature| feature.name().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| anyhow!("Unknown feature '{}'", s.trim()))
+    }
+}
+
+/// Parses a comma-separated list of feature names, e.g. `archiving, sync_api`.
+///
+/// # Errors
+///
+/// Returns an error naming the first unknown feature.
+pub fn parse_list(list: &str) -> Result<Vec<Feature>> {
+    let mut features: Vec<Feature> = list
+        .split(',')
+        .filter(|name| !name.trim().is_empty())
+        .map(str::parse)
+        .collect::<Result<_>>()?;
+    features.sort_unstable();
+    features.dedup();
+    Ok(features)
+}
+
+/// The features enabled for the whole instance, which users' overrides are applied on top of.
+#[derive(Clone, Debug, Default)]
+pub struct FeatureFlags {
+    defaults: Vec<Feature>,
+}
+
+impl FeatureFlags {
+    pub fn new(defaults: Vec<Feature>) -> Self {
+        Self { defaults }
+    }
+
+    /// Whether a feature is on for users without an override.
+    pub fn is_default(&self, feature: Feature) -> bool {
+        self.defaults.contains(&feature)
+    }
+
+    /// The features enabled for a user.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the user's overrides cannot be loaded.
+    pub async fn enabled_for(&self, db: &Db, user_id: Uuid) -> Result<Vec<Feature>> {
+        let overrides = db::feature_flags::get_user_overrides(db, user_id).await?;
+        Ok(Feature::ALL
+            .into_iter()
+            .filter(|feature| self.resolve(*feature, &overrides))
+            .collect())
+    }
+
+    /// Whether a feature is enabled for a user.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the user's overrides cannot be loaded.
+    pub async fn is_enabled(&self, db: &Db, user_id: Uuid, feature: Feature) -> Result<bool> {
+        let overrides = db::feature_flags::get_user_overrides(db, user_id).await?;
+        Ok(self.resolve(feature, &overrides))
+    }
+
+    /// Applies a user's overrides to the instance default. Overrides for features that no longer
+    /// exist are ignored.
+    fn resolve(&self, feature: Feature, overrides: &[FeatureOverride]) -> bool {
+        overrides
+            .iter()
+            .find(|o| o.feature == feature.name())
+            .map_or_else(|| self.is_default(feature), |o| o.enabled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feature_override(feature: &str, enabled: bool) -> FeatureOverride {
+        FeatureOverride {
+            feature: feature.to_string(),
+            enabled,
+        }
+    }
+
+    #[test]
+    fn test_parse_list() {
+        assert_eq!(
+            parse_list(" Sync_API, archiving,,sync_api ").unwrap(),
+            [Feature::Archiving, Feature::SyncApi]
+        );
+        assert!(parse_list("").unwrap().is_empty());
+        assert!(parse_list("archiving, teleportation").is_err());
+    }
+
+    #[test]
+    fn test_overrides_win_over_defaults() {
+        let flags = FeatureFlags::new(vec![Feature::Archiving]);
+        assert!(flags.resolve(Feature::Archiving, &[]));
+        assert!(!flags.resolve(Feature::SyncApi, &[]));
+
+        let overrides = [
+            feature_override("archiving", false),
+            feature_override("sync_api", true),
+            feature_override("removed_feature", true),
+        ];
+        assert!(!flags.resolve(Feature::Archiving, &overrides));
+        assert!(flags.resolve(Feature::SyncApi, &overrides));
+        assert!(!flags.resolve(Feature::ActivityPub, &overrides));
+    }
+}
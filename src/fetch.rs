@@ -0,0 +1,582 @@
+//! Outbound fetching of saved URLs.
+//!
+//! Everything that contacts a user's saved URLs goes through here, so that the instance-wide
+//! [`FetchConfig`] (timeouts, disable switch, proxy) applies consistently.
+
+use std::time::Duration;
+
+use anyhow::{Context as _, Result, bail};
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use reqwest::{
+    Client, Proxy, Response, Url,
+    header::{FROM, HeaderMap, HeaderValue},
+};
+use serde_json::Value;
+use tl::VDom;
+use tracing::debug;
+use uuid::Uuid;
+
+use crate::{
+    config::FetchConfig,
+    db::{Db, settings},
+    translate::normalize_language,
+};
+
+/// Overall cap on a single fetch, so a slow-dripping server can't hold a request open forever.
+const MAX_TOTAL_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Builds the shared HTTP client used for fetching saved URLs.
+///
+/// Requests identify the instance by the configured `User-Agent`, followed by the contact the way
+/// crawlers usually give theirs, e.g. `PagePouch/1.0 (+https://example.com/about)`. A contact email
+/// address is also sent as the `From` header.
+///
+/// # Errors
+///
+/// Returns an error if the proxy URL or identification is invalid, or the TLS backend cannot be
+/// initialized.
+pub fn build_client(config: &FetchConfig) -> Result<Client> {
+    let mut builder = Client::builder()
+        .connect_timeout(config.connect_timeout)
+        .read_timeout(config.read_timeout)
+        .timeout(MAX_TOTAL_TIMEOUT)
+        .user_agent(user_agent(config));
+
+    if let Some(email) = config.contact.as_deref().filter(|contact| !contact.contains("://")) {
+        let from = HeaderValue::from_str(email).with_context(|| "🔥 Invalid outbound fetch contact")?;
+        builder = builder.default_headers(HeaderMap::from_iter([(FROM, from)]));
+    }
+
+    if let Some(proxy) = &config.proxy {
+        let proxy = Proxy::all(proxy).with_context(|| "🔥 Invalid outbound fetch proxy URL")?;
+        builder = builder.proxy(proxy);
+    }
+
+    Ok(builder.build()?)
+}
+
+/// The `User-Agent` sent when fetching, with the contact if there is one.
+fn user_agent(config: &FetchConfig) -> String {
+    match &config.contact {
+        Some(contact) => format!("{} (+{contact})", config.user_agent),
+        None => config.user_agent.clone(),
+    }
+}
+
+/// Checks that a URL may be contacted under the instance's fetch configuration.
+///
+/// `.onion` addresses are refused unless explicitly allowed, since without a Tor proxy the lookup
+/// would fail and leak the hostname to the local resolver.
+///
+/// # Errors
+///
+/// Returns an error if the URL cannot be parsed or its destination is not allowed.
+pub fn check_destination(config: &FetchConfig, url: &str) -> Result<()> {
+    let url = Url::parse(url).with_context(|| format!("Invalid URL '{url}'"))?;
+    // Hosts are already lowercased by the URL parser
+    let is_onion = url.host_str().unwrap_or_default().trim_end_matches('.').rsplit('.').next() == Some("onion");
+    if is_onion && !config.allow_onion {
+        bail!("Fetching .onion URLs is not enabled on this instance");
+    }
+    Ok(())
+}
+
+/// Checks whether saved URLs may be fetched on behalf of this user.
+///
+/// Fetching must be enabled for the instance, and the user must not have opted out.
+///
+/// # Errors
+///
+/// Returns an error if the user's settings cannot be loaded.
+pub async fn is_fetch_allowed(config: &FetchConfig, db: &Db, user_id: Uuid) -> Result<bool> {
+    if !config.enabled {
+        return Ok(false);
+    }
+    let user_settings = settings::get_user_settings(db, user_id).await?;
+    Ok(!user_settings.disable_fetch)
+}
+
+/// Reads a response's body, giving up once it's larger than `limit` bytes.
+///
+/// # Errors
+///
+/// Returns an error if reading the body fails or it's too large.
+pub async fn read_body(mut response: Response, limit: usize) -> Result<Vec<u8>> {
+    if response.content_length().is_some_and(|length| length > limit as u64) {
+        bail!("Response is larger than {limit} bytes");
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if body.len() + chunk.len() > limit {
+            bail!("Response is larger than {limit} bytes");
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+/// Title and description scraped from a page.
+#[derive(Debug)]
+pub struct LinkScrapeResult {
+    pub description: Option<String>,
+    pub title: String,
+    pub final_url: String,
+    /// Language of the page's content, e.g. `de`, if it declares one
+    pub language: Option<String>,
+    pub metadata: PageMetadata,
+    /// Absolute URL of the image the page wants shown in link previews, if it names one
+    pub image_url: Option<String>,
+}
+
+/// Who wrote a page, where and when, as far as its metadata says.
+#[derive(Debug, Default, PartialEq)]
+pub struct PageMetadata {
+    pub author: Option<String>,
+    /// Name of the publication or site, e.g. `The Guardian`
+    pub site_name: Option<String>,
+    /// UNIX timestamp
+    pub published_at: Option<i64>,
+}
+
+/// Fetches the title from a webpage
+///
+/// # Errors
+///
+/// Returns an error if the HTTP request fails or HTML parsing fails.
+#[tracing::instrument(level = "trace", name = "http.fetch", skip_all, fields(url = url))]
+pub async fn scrape_title_description(client: &Client, config: &FetchConfig, url: &str) -> Result<LinkScrapeResult> {
+    let default_title = url.to_string();
+    let mut url = match url {
+        url if url.starts_with("http://") || url.starts_with("https://") => url.to_string(),
+        no_proto => format!("http://{no_proto}"),
+    };
+    check_destination(config, &url)?;
+
+    // Fetch the page
+    let mut response = client.get(&url).send().await?;
+
+    // Check if response is successful
+    if !response.status().is_success() {
+        // If not, try https instead
+        debug!("Fetch failed with https, trying http");
+        url = url.replace("https://", "http://");
+        response = client.get(&url).send().await?.error_for_status()?;
+    }
+
+    let html = response.text().await?;
+
+    // Parse HTML and extract title
+    let dom = tl::parse(&html, tl::ParserOptions::default())?;
+    let parser = dom.parser();
+    let title = dom
+        .query_selector("title")
+        .and_then(|mut iter| iter.next())
+        .and_then(|node| node.get(parser))
+        .map(|node| decode_html_entities(&node.inner_text(parser)).trim().to_string())
+        .filter(|title| !title.is_empty())
+        .unwrap_or(default_title);
+
+    let description = get_meta_description(&dom);
+    let language = get_language(&dom);
+    let metadata = get_page_metadata(&dom);
+    let image_url = get_preview_image(&dom, &url);
+
+    Ok(LinkScrapeResult {
+        description,
+        title,
+        final_url: url,
+        language,
+        metadata,
+        image_url,
+    })
+}
+
+/// Why fetching a page failed, briefly but specifically enough to do something about it, e.g.
+/// "Timed out", "HTTP 403 Forbidden" or "TLS error: invalid peer certificate: Expired".
+pub fn failure_reason(err: &anyhow::Error) -> String {
+    let Some(err) = err.downcast_ref::<reqwest::Error>() else {
+        return err.to_string();
+    };
+    // The innermost cause says the most, e.g. which certificate problem it was
+    let causes: Vec<String> = std::iter::successors(std::error::Error::source(err), |cause| cause.source())
+        .map(ToString::to_string)
+        .collect();
+    let cause = causes.last().map_or("unknown cause", String::as_str);
+    // Connecting to an https URL fails in the TLS handshake if it got past the TCP connection
+    let is_tls = err.is_connect()
+        && err.url().is_some_and(|url| url.scheme() == "https")
+        && !causes
+            .iter()
+            .any(|cause| cause.starts_with("tcp connect error") || cause.starts_with("dns error"));
+    if let Some(status) = err.status() {
+        format!("HTTP {status}")
+    } else if err.is_timeout() {
+        "Timed out".to_string()
+    } else if is_tls {
+        format!("TLS error: {cause}")
+    } else if err.is_connect() {
+        format!("Could not connect: {cause}")
+    } else if err.is_redirect() {
+        "Too many redirects".to_string()
+    } else if err.is_body() || err.is_decode() {
+        "Could not read the page".to_string()
+    } else {
+        "Request failed".to_string()
+    }
+}
+
+fn get_meta_description(dom: &VDom<'_>) -> Option<String> {
+    let parser = dom.parser();
+
+    // Try Open Graph description first
+    if let Some(og_desc) = dom
+        .query_selector("meta[property=\"og:description\"]")
+        .and_then(|mut iter| iter.next())
+        .and_then(|node| node.get(parser))
+        .and_then(|node| node.as_tag())
+        .and_then(|tag| tag.attributes().get("content")?)
+        .map(|content| content.as_utf8_str().to_string())
+    {
+        Some(og_desc)
+    } else {
+        // Fall back to standard meta description
+        dom.query_selector("meta[name=\"description\"]")
+            .and_then(|mut iter| iter.next())
+            .and_then(|node| node.get(parser))
+            .and_then(|node| node.as_tag())
+            .and_then(|tag| tag.attributes().get("content")?)
+            .map(|content| content.as_utf8_str().to_string())
+    }
+    .map(|text| decode_html_entities(&text))
+}
+
+/// Reads the page's declared language from `<html lang>`, falling back to a `Content-Language` meta tag.
+fn get_language(dom: &VDom<'_>) -> Option<String> {
+    get_attribute(dom, "html", "lang")
+        .and_then(|lang| normalize_language(&lang))
+        .or_else(|| get_attribute(dom, "meta[http-equiv=\"content-language\"]", "content").and_then(|lang| normalize_language(&lang)))
+}
+
+/// Reads the author, site name and publication date from schema.org JSON-LD, Open Graph and
+/// Highwire Press (`citation_*`, used by academic publishers) meta tags and microformats, preferring
+/// them in that order.
+fn get_page_metadata(dom: &VDom<'_>) -> PageMetadata {
+    let linked_data = get_linked_data(dom);
+    let meta = |name: &str| get_attribute(dom, &format!("meta[name=\"{name}\"]"), "content");
+    let meta_property = |property: &str| get_attribute(dom, &format!("meta[property=\"{property}\"]"), "content");
+
+    // Academic papers list each author in their own tag
+    let citation_authors: Vec<String> = dom
+        .query_selector("meta[name=\"citation_author\"]")
+        .into_iter()
+        .flatten()
+        .filter_map(|node| node.get(dom.parser())?.as_tag()?.attributes().get("content")?)
+        .map(|content| content.as_utf8_str().trim().to_string())
+        .filter(|author| !author.is_empty())
+        .collect();
+
+    let author = linked_data
+        .iter()
+        .find_map(|item| item.get("author").and_then(person_name))
+        .or_else(|| meta("author"))
+        .or_else(|| (!citation_authors.is_empty()).then(|| citation_authors.join(", ")))
+        // Often a link to the author's profile rather than a name
+        .or_else(|| meta_property("article:author").filter(|author| !author.starts_with("http")))
+        .or_else(|| get_text(dom, ".p-author"));
+
+    let site_name = meta_property("og:site_name")
+        .or_else(|| {
+            linked_data
+                .iter()
+                .find_map(|item| item.get("publisher")?.get("name")?.as_str().map(str::to_string))
+        })
+        .or_else(|| meta("citation_journal_title"))
+        .or_else(|| meta("application-name"));
+
+    let published_at = linked_data
+        .iter()
+        .find_map(|item| item.get("datePublished")?.as_str().map(str::to_string))
+        .into_iter()
+        .chain(meta_property("article:published_time"))
+        .chain(meta("citation_publication_date"))
+        .chain(meta("citation_date"))
+        .chain(meta("date"))
+        .chain(get_attribute(dom, ".dt-published", "datetime"))
+        .chain(get_attribute(dom, "time[itemprop=\"datePublished\"]", "datetime"))
+        .find_map(|date| parse_published_date(&date));
+
+    let clean = |text: String| {
+        let text = decode_html_entities(&text).split_whitespace().collect::<Vec<_>>().join(" ");
+        (!text.is_empty()).then_some(text)
+    };
+    PageMetadata {
+        author: author.and_then(clean),
+        site_name: site_name.and_then(clean),
+        published_at,
+    }
+}
+
+/// Every schema.org item described in the page's JSON-LD scripts, including those nested in `@graph`.
+fn get_linked_data(dom: &VDom<'_>) -> Vec<Value> {
+    fn flatten(value: Value, items: &mut Vec<Value>) {
+        match value {
+            Value::Array(values) => values.into_iter().for_each(|value| flatten(value, items)),
+            Value::Object(mut object) => {
+                if let Some(graph) = object.remove("@graph") {
+                    flatten(graph, items);
+                }
+                items.push(Value::Object(object));
+            }
+            _ => {}
+        }
+    }
+
+    let parser = dom.parser();
+    let mut items = Vec::new();
+    for node in dom
+        .query_selector("script[type=\"application/ld+json\"]")
+        .into_iter()
+        .flatten()
+        .filter_map(|node| node.get(parser))
+    {
+        if let Ok(value) = serde_json::from_str(&node.inner_text(parser)) {
+            flatten(value, &mut items);
+        }
+    }
+    items
+}
+
+/// The name of a schema.org person or organization, or of several joined by commas.
+fn person_name(value: &Value) -> Option<String> {
+    match value {
+        Value::String(name) => Some(name.clone()),
+        Value::Object(object) => object.get("name")?.as_str().map(str::to_string),
+        Value::Array(values) => {
+            let names: Vec<String> = values.iter().filter_map(person_name).collect();
+            (!names.is_empty()).then(|| names.join(", "))
+        }
+        _ => None,
+    }
+}
+
+/// Parses the many ways pages give their publication date: RFC 3339, a date and time without a
+/// time zone (taken as UTC), a date with dashes or slashes, or just a year and month or a year.
+fn parse_published_date(date: &str) -> Option<i64> {
+    let date = date.trim();
+    if let Ok(published) = DateTime::parse_from_rfc3339(date) {
+        return Some(published.timestamp());
+    }
+    if let Ok(published) = NaiveDateTime::parse_from_str(date, "%Y-%m-%dT%H:%M:%S%.f") {
+        return Some(published.and_utc().timestamp());
+    }
+
+    // Fall back to just the date
+    let day = date.get(..10).unwrap_or(date).replace('/', "-");
+    let day = match day.split('-').count() {
+        1 => format!("{day}-01-01"),
+        2 => format!("{day}-01"),
+        _ => day,
+    };
+    let published = NaiveDate::parse_from_str(&day, "%Y-%m-%d").ok()?;
+    Some(published.and_hms_opt(0, 0, 0)?.and_utc().timestamp())
+}
+
+/// The value of an attribute on the first element matching a selector.
+fn get_attribute(dom: &VDom<'_>, selector: &str, name: &str) -> Option<String> {
+    dom.query_selector(selector)
+        .and_then(|mut iter| iter.next())
+        .and_then(|node| node.get(dom.parser()))
+        .and_then(|node| node.as_tag())
+        .and_then(|tag| tag.attributes().get(name)?)
+        .map(|value| value.as_utf8_str().to_string())
+}
+
+/// Reads the image the page wants shown in link previews from Open Graph or Twitter card meta tags,
+/// resolved against the page's URL. Only `http` and `https` images are returned.
+fn get_preview_image(dom: &VDom<'_>, page_url: &str) -> Option<String> {
+    let image = get_attribute(dom, "meta[property=\"og:image\"]", "content")
+        .or_else(|| get_attribute(dom, "meta[property=\"og:image:url\"]", "content"))
+        .or_else(|| get_attribute(dom, "meta[name=\"twitter:image\"]", "content"))?;
+    let url = Url::parse(page_url).ok()?.join(decode_html_entities(&image).trim()).ok()?;
+    matches!(url.scheme(), "http" | "https").then(|| url.to_string())
+}
+
+/// The text inside the first element matching a selector, if there is any.
+fn get_text(dom: &VDom<'_>, selector: &str) -> Option<String> {
+    let parser = dom.parser();
+    dom.query_selector(selector)
+        .and_then(|mut iter| iter.next())
+        .and_then(|node| node.get(parser))
+        .map(|node| node.inner_text(parser).trim().to_string())
+        .filter(|text| !text.is_empty())
+}
+
+/// Decodes common HTML entities in text.
+pub(crate) fn decode_html_entities(text: &str) -> String {
+    text.replace("&amp;", "&")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&apos;", "'")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_onion_destinations_need_opt_in() {
+        let mut config = FetchConfig::default();
+        assert!(check_destination(&config, "https://example.com/page").is_ok());
+        assert!(check_destination(&config, "http://duckduckgogg42xjoc72x3sjasowoarfbgcmvfimaftt6twagswzczad.onion/").is_err());
+        assert!(check_destination(&config, "http://EXAMPLE.onion./").is_err());
+
+        config.allow_onion = true;
+        assert!(check_destination(&config, "http://example.onion/").is_ok());
+    }
+
+    #[test]
+    fn test_get_language() {
+        let language = |html: &str| get_language(&tl::parse(html, tl::ParserOptions::default()).unwrap());
+        assert_eq!(language(r#"<html lang="de-AT"><head></head></html>"#), Some("de".to_string()));
+        assert_eq!(
+            language(r#"<html><head><meta http-equiv="content-language" content="fr"></head></html>"#),
+            Some("fr".to_string())
+        );
+        assert_eq!(language("<html><head><title>No language</title></head></html>"), None);
+    }
+
+    #[test]
+    fn test_get_preview_image() {
+        let image = |html: &str| {
+            get_preview_image(
+                &tl::parse(html, tl::ParserOptions::default()).unwrap(),
+                "https://example.com/blog/post",
+            )
+        };
+        assert_eq!(
+            image(r#"<html><head><meta property="og:image" content="https://cdn.example.com/a.jpg?w=1200&amp;h=630"></head></html>"#),
+            Some("https://cdn.example.com/a.jpg?w=1200&h=630".to_string())
+        );
+        assert_eq!(
+            image(r#"<html><head><meta name="twitter:image" content="/images/card.png"></head></html>"#),
+            Some("https://example.com/images/card.png".to_string())
+        );
+        assert_eq!(
+            image(r#"<html><head><meta property="og:image" content="data:image/png;base64,AAAA"></head></html>"#),
+            None
+        );
+        assert_eq!(image("<html><head><title>No image</title></head></html>"), None);
+    }
+
+    #[test]
+    fn test_get_page_metadata() {
+        let metadata = |html: &str| get_page_metadata(&tl::parse(html, tl::ParserOptions::default()).unwrap());
+
+        let json_ld = r#"<html><head>
+            <meta property="og:site_name" content="The Example Times">
+            <script type="application/ld+json">{"@context": "https://schema.org", "@graph": [
+                {"@type": "WebSite", "name": "Example"},
+                {"@type": "NewsArticle", "datePublished": "2024-03-05T10:00:00+01:00",
+                 "author": [{"@type": "Person", "name": "Jane Doe"}, {"@type": "Person", "name": "John Roe"}]}
+            ]}</script>
+            <meta name="author" content="Someone Else">
+        </head></html>"#;
+        assert_eq!(
+            metadata(json_ld),
+            PageMetadata {
+                author: Some("Jane Doe, John Roe".to_string()),
+                site_name: Some("The Example Times".to_string()),
+                published_at: Some(1_709_629_200),
+            }
+        );
+
+        let paper = r#"<html><head>
+            <meta name="citation_author" content="Knuth, Donald E.">
+            <meta name="citation_author" content="Lamport, Leslie">
+            <meta name="citation_journal_title" content="Journal of Examples">
+            <meta name="citation_publication_date" content="1984/05">
+        </head></html>"#;
+        assert_eq!(
+            metadata(paper),
+            PageMetadata {
+                author: Some("Knuth, Donald E., Lamport, Leslie".to_string()),
+                site_name: Some("Journal of Examples".to_string()),
+                // 1984-05-01
+                published_at: Some(452_217_600),
+            }
+        );
+
+        let blog = r#"<html><body><article class="h-entry">
+            <a class="p-author h-card" href="/">  Ann
+              Author </a>
+            <time class="dt-published" datetime="2023-11-14">14 November</time>
+            <meta property="article:author" content="https://example.com/ann">
+        </article></body></html>"#;
+        assert_eq!(
+            metadata(blog),
+            PageMetadata {
+                author: Some("Ann Author".to_string()),
+                site_name: None,
+                published_at: Some(1_699_920_000),
+            }
+        );
+
+        assert_eq!(
+            metadata("<html><head><title>Nothing</title></head></html>"),
+            PageMetadata::default()
+        );
+    }
+
+    #[test]
+    fn test_parse_published_date() {
+        assert_eq!(parse_published_date("2023-11-14T22:13:20Z"), Some(1_700_000_000));
+        assert_eq!(parse_published_date("2023-11-14T22:13:20.123"), Some(1_700_000_000));
+        assert_eq!(parse_published_date(" 2023-11-14 "), Some(1_699_920_000));
+        assert_eq!(parse_published_date("2023/11/14"), Some(1_699_920_000));
+        assert_eq!(parse_published_date("2023"), Some(1_672_531_200));
+        assert_eq!(parse_published_date("last Tuesday"), None);
+        assert_eq!(parse_published_date(""), None);
+    }
+
+    #[test]
+    fn test_build_client_rejects_invalid_proxy() {
+        let config = FetchConfig {
+            proxy: Some("not a proxy url".to_string()),
+            ..FetchConfig::default()
+        };
+        assert!(build_client(&config).is_err());
+
+        let config = FetchConfig {
+            proxy: Some("socks5h://127.0.0.1:9050".to_string()),
+            ..FetchConfig::default()
+        };
+        assert!(build_client(&config).is_ok());
+    }
+
+    #[test]
+    fn test_failure_reason() {
+        let invalid = Client::new().get("not a url").build().unwrap_err();
+        assert_eq!(failure_reason(&invalid.into()), "Request failed");
+        assert_eq!(
+            failure_reason(&anyhow::anyhow!("Fetching .onion URLs is not enabled on this instance")),
+            "Fetching .onion URLs is not enabled on this instance"
+        );
+    }
+
+    #[test]
+    fn test_user_agent_names_the_contact() {
+        let mut config = FetchConfig::default();
+        assert_eq!(user_agent(&config), "PagePouch/1.0");
+
+        config.user_agent = "ExampleBookmarks/2.1".to_string();
+        config.contact = Some("https://bookmarks.example.com/about".to_string());
+        assert_eq!(user_agent(&config), "ExampleBookmarks/2.1 (+https://bookmarks.example.com/about)");
+        assert!(build_client(&config).is_ok());
+
+        config.contact = Some("admin@example.com".to_string());
+        assert_eq!(user_agent(&config), "ExampleBookmarks/2.1 (+admin@example.com)");
+        assert!(build_client(&config).is_ok());
+    }
+}
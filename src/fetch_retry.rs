@@ -0,0 +1,75 @@
+//! Trying again to fetch the pages of bookmarks that couldn't be fetched when they were saved.
+//!
+//! When the Add Bookmark page can't fetch a page's title, the bookmark is saved anyway, with why
+//! it couldn't be (e.g. "Timed out", "HTTP 403 Forbidden") recorded on it. It's then tried again
+//! by [`crate::jobs::spawn_fetch_retry`], waiting longer each time (15 minutes, an hour, then 4
+//! hours) and giving up after [`MAX_FETCH_ATTEMPTS`] tries in all. Meanwhile the reason is shown
+//! on the bookmark, which can be tried again right away. Once fetched, the bookmark gets the
+//! page's title, if it still has its URL as title, and whatever else it's missing.
+
+use reqwest::Client;
+
+use crate::{
+    config::FetchConfig,
+    db::fetch_failures::{FetchFailure, FetchedDetails},
+    fetch,
+};
+
+/// Tries in all, counting the one when the bookmark was saved, before giving up.
+const MAX_FETCH_ATTEMPTS: i64 = 4;
+/// Seconds to wait after the first failed try.
+const FIRST_RETRY_DELAY: i64 = 15 * 60;
+/// How many times longer to wait after each further failed try.
+const BACKOFF_FACTOR: i64 = 4;
+
+/// Seconds to wait after `attempts` failed tries before the next, or `None` once it's time to give
+/// up.
+fn retry_delay(attempts: i64) -> Option<i64> {
+    let retries = u32::try_from(attempts - 1).ok()?;
+    (attempts < MAX_FETCH_ATTEMPTS).then(|| FIRST_RETRY_DELAY * BACKOFF_FACTOR.pow(retries))
+}
+
+/// The failure to record after `attempts` tries at `now` (a UNIX timestamp), the last one failing
+/// with `error`.
+pub fn failure_after(attempts: i64, error: String, now: i64) -> FetchFailure {
+    FetchFailure {
+        error,
+        attempts,
+        retry_at: retry_delay(attempts).map(|delay| now + delay),
+    }
+}
+
+/// Fetches a bookmark's page, answering with why it couldn't if it couldn't.
+///
+/// # Errors
+///
+/// Returns the reason fetching failed, see [`fetch::failure_reason`].
+pub async fn fetch_details(client: &Client, config: &FetchConfig, url: &str) -> Result<FetchedDetails, String> {
+    let scraped = fetch::scrape_title_description(client, config, url)
+        .await
+        .map_err(|err| fetch::failure_reason(&err))?;
+    Ok(FetchedDetails {
+        title: scraped.title,
+        description: scraped.description,
+        language: scraped.language,
+        author: scraped.metadata.author,
+        site_name: scraped.metadata.site_name,
+        published_at: scraped.metadata.published_at,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failure_after() {
+        let now = 1_700_000_000;
+        assert_eq!(failure_after(1, "Timed out".to_string(), now).retry_at, Some(now + 15 * 60));
+        assert_eq!(failure_after(2, "Timed out".to_string(), now).retry_at, Some(now + 3600));
+        assert_eq!(failure_after(3, "Timed out".to_string(), now).retry_at, Some(now + 4 * 3600));
+        // Given up
+        assert_eq!(failure_after(MAX_FETCH_ATTEMPTS, "Timed out".to_string(), now).retry_at, None);
+        assert_eq!(failure_after(0, "Timed out".to_string(), now).retry_at, None);
+    }
+}
@@ -0,0 +1,555 @@
+//! Admin dashboard handlers and templates.
+
+use anyhow::anyhow;
+use askama::Template;
+use axum::{
+    Extension, Form, Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use chrono::DateTime;
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use crate::{
+    ApiState, AppState,
+    db::{
+        self, Db,
+        admin_search::{AdminSearchLogEntry, AdminSearchResult},
+        blocked_domains::BlockedDomain,
+        feature_flags::UserFeatureOverride,
+        instance_settings::Announcement,
+        login_failures::Lockout,
+        maintenance::MaintenanceRun,
+        users::User,
+    },
+    error::{AppError, AppResult},
+    features::{Feature, FeatureFlags},
+    handler::{AuthState, HtmlTemplate},
+    jobs,
+    metrics::{MetricsSummary, RateView},
+    update_check::{CURRENT_VERSION, Release},
+};
+
+/// Number of past maintenance runs shown on the dashboard.
+const RECENT_RUNS: i64 = 14;
+/// Number of past admin searches shown on the admin search page.
+const RECENT_SEARCHES: i64 = 50;
+/// Most bookmarks shown for one admin search.
+const ADMIN_SEARCH_LIMIT: i64 = 200;
+/// Shortest reason accepted for an admin search, to rule out placeholders like "x".
+const MIN_REASON_LENGTH: usize = 10;
+/// Longest announcement accepted, to keep the banner to a line or two.
+const MAX_ANNOUNCEMENT_LENGTH: usize = 500;
+
+#[derive(Template)]
+#[template(path = "pages/admin.html")]
+#[allow(clippy::struct_excessive_bools)] // independent flags for the template
+pub struct AdminTemplate<'a> {
+    pub title: &'a str,
+    pub auth_state: AuthState,
+    pub is_error: bool,
+    /// Slow queries logged since the server started
+    pub slow_query_count: u64,
+    pub runs: Vec<MaintenanceRunView>,
+    /// Routes that answered with server errors since the server started, most first
+    pub failing_routes: Vec<RateView>,
+    /// Scheduled jobs' runs since the server started
+    pub jobs: Vec<RateView>,
+    pub alerts: Option<AlertsView>,
+    pub admin_search_enabled: bool,
+    pub announcement: AnnouncementView,
+    pub lockouts: Vec<LockoutView>,
+    /// Whether new users are given the starter set
+    pub starter_set: bool,
+    /// Whether anyone can sign up for an account
+    pub registration_enabled: bool,
+    pub features: Vec<FeatureView>,
+    pub overrides: Vec<FeatureOverrideView>,
+    pub blocked_domains: Vec<BlockedDomain>,
+    /// Always true here; the blocklist component is shared with the settings page
+    pub instance_blocklist: bool,
+    /// The running version
+    pub version: &'static str,
+    pub update_status: UpdateStatus,
+}
+
+/// When the admin is alerted about failures.
+pub struct AlertsView {
+    pub error_percent: u64,
+    pub min_requests: u64,
+    pub job_failure_percent: u64,
+    pub window_minutes: u64,
+}
+
+/// What the update check has found.
+pub enum UpdateStatus {
+    /// The check is turned off
+    Off,
+    UpToDate,
+    Available(Release),
+}
+
+/// Instance feature defaults and per-user overrides, re-rendered after a change.
+#[derive(Template)]
+#[template(path = "components/feature_settings.html")]
+pub struct FeatureSettingsTemplate {
+    pub features: Vec<FeatureView>,
+    pub overrides: Vec<FeatureOverrideView>,
+}
+
+/// A feature and whether it's enabled for the whole instance.
+pub struct FeatureView {
+    pub name: &'static str,
+    pub label: &'static str,
+    pub enabled: bool,
+}
+
+/// A user's override formatted for display.
+pub struct FeatureOverrideView {
+    pub username: String,
+    /// The feature's label, or its stored name if it no longer exists
+    pub feature: String,
+    pub enabled: bool,
+}
+
+impl From<UserFeatureOverride> for FeatureOverrideView {
+    fn from(o: UserFeatureOverride) -> Self {
+        Self {
+            username: o.username,
+            feature: o
+                .feature
+                .parse::<Feature>()
+                .map_or(o.feature, |feature| feature.label().to_string()),
+            enabled: o.enabled,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct FeatureOverrideForm {
+    pub username: String,
+    pub feature: String,
+    /// `on`, `off`, or `default` to remove the override
+    pub setting: String,
+}
+
+/// Announcement form, re-rendered after a change.
+#[derive(Template)]
+#[template(path = "components/announcement_settings.html")]
+pub struct AnnouncementSettingsTemplate {
+    pub announcement: AnnouncementView,
+}
+
+/// The current announcement formatted for display.
+#[derive(Default)]
+pub struct AnnouncementView {
+    pub message: String,
+    /// When it stops being shown, if it expires
+    pub expires: Option<String>,
+}
+
+impl From<Option<Announcement>> for AnnouncementView {
+    fn from(announcement: Option<Announcement>) -> Self {
+        announcement.map_or_else(Self::default, |announcement| Self {
+            message: announcement.message,
+            expires: announcement.expires_at.map(format_timestamp),
+        })
+    }
+}
+
+#[derive(Deserialize)]
+pub struct AnnouncementForm {
+    #[serde(default)]
+    pub message: String,
+    /// Hours until the announcement expires; never if empty
+    #[serde(default)]
+    pub expires_in_hours: String,
+}
+
+#[derive(Deserialize)]
+pub struct StarterSetForm {
+    /// Checkbox value, only sent when checked
+    pub starter_set: Option<String>,
+}
+
+/// Current sign-in lockouts, re-rendered after one is lifted.
+#[derive(Template)]
+#[template(path = "components/lockout_list.html")]
+pub struct LockoutListTemplate {
+    pub lockouts: Vec<LockoutView>,
+}
+
+/// A lockout formatted for display.
+pub struct LockoutView {
+    pub locked_until: String,
+    pub lockout: Lockout,
+}
+
+impl From<Lockout> for LockoutView {
+    fn from(lockout: Lockout) -> Self {
+        Self {
+            locked_until: format_timestamp(lockout.locked_until),
+            lockout,
+        }
+    }
+}
+
+/// Table of recent maintenance runs, re-rendered after a manual run.
+#[derive(Template)]
+#[template(path = "components/maintenance_runs.html")]
+pub struct MaintenanceRunsTemplate {
+    pub runs: Vec<MaintenanceRunView>,
+}
+
+/// A maintenance run formatted for display.
+pub struct MaintenanceRunView {
+    pub started: String,
+    pub run: MaintenanceRun,
+}
+
+impl From<MaintenanceRun> for MaintenanceRunView {
+    fn from(run: MaintenanceRun) -> Self {
+        Self {
+            started: format_timestamp(run.started_at),
+            run,
+        }
+    }
+}
+
+/// The new release banner, or nothing if there's no newer release, it was dismissed, or the user
+/// isn't the admin.
+#[derive(Template)]
+#[template(path = "components/update_notice.html")]
+pub struct UpdateNoticeTemplate {
+    pub release: Option<Release>,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateNoticeQuery {
+    /// Version of the release the browser last dismissed the banner for
+    #[serde(default)]
+    pub dismissed: String,
+}
+
+/// Searching all users' bookmarks, with the audit log of past searches.
+#[derive(Template)]
+#[template(path = "pages/admin_search.html")]
+pub struct AdminSearchTemplate<'a> {
+    pub title: &'a str,
+    pub auth_state: AuthState,
+    pub is_error: bool,
+    pub log: Vec<AdminSearchLogView>,
+}
+
+/// Bookmarks found by an admin search.
+#[derive(Template)]
+#[template(path = "components/admin_search_results.html")]
+pub struct AdminSearchResultsTemplate {
+    pub query: String,
+    pub results: Vec<AdminSearchResultView>,
+}
+
+/// An admin search result formatted for display.
+pub struct AdminSearchResultView {
+    pub created: String,
+    pub result: AdminSearchResult,
+}
+
+/// An audit log entry formatted for display.
+pub struct AdminSearchLogView {
+    pub searched: String,
+    pub entry: AdminSearchLogEntry,
+}
+
+fn format_timestamp(timestamp: i64) -> String {
+    DateTime::from_timestamp(timestamp, 0).map_or_else(String::new, |dt| dt.format("%Y-%m-%d %H:%M UTC").to_string())
+}
+
+#[derive(Deserialize)]
+pub struct AdminSearchForm {
+    pub query: String,
+    pub reason: String,
+}
+
+/// Rejects users who aren't admins.
+pub(crate) fn require_admin(user: &User) -> AppResult<()> {
+    if user.is_admin {
+        Ok(())
+    } else {
+        Err(AppError::with_status(
+            anyhow!("User '{}' is not an admin", user.username),
+            StatusCode::FORBIDDEN,
+        ))
+    }
+}
+
+/// Rejects users who aren't admins, and everyone if admin search isn't enabled on this instance.
+///
+/// Answers "not found" when it's disabled, so the pages look like they don't exist.
+fn require_admin_search(state: &AppState, user: &User) -> AppResult<()> {
+    if !state.admin_search_enabled {
+        return Err(AppError::with_status(anyhow!("Admin search is not enabled"), StatusCode::NOT_FOUND));
+    }
+    require_admin(user)
+}
+
+async fn feature_settings(db: &Db, flags: &FeatureFlags) -> AppResult<FeatureSettingsTemplate> {
+    let overrides = db::feature_flags::get_all_overrides(db).await?;
+    Ok(FeatureSettingsTemplate {
+        features: Feature::ALL
+            .into_iter()
+            .map(|feature| FeatureView {
+                name: feature.name(),
+                label: feature.label(),
+                enabled: flags.is_default(feature),
+            })
+            .collect(),
+        overrides: overrides.into_iter().map(FeatureOverrideView::from).collect(),
+    })
+}
+
+async fn current_lockouts(db: &Db) -> AppResult<Vec<LockoutView>> {
+    let lockouts = db::login_failures::get_lockouts(db).await?;
+    Ok(lockouts.into_iter().map(LockoutView::from).collect())
+}
+
+async fn recent_runs(db: &Db) -> AppResult<Vec<MaintenanceRunView>> {
+    let runs = db::maintenance::get_recent_runs(db, RECENT_RUNS).await?;
+    Ok(runs.into_iter().map(MaintenanceRunView::from).collect())
+}
+
+/// Handler for the admin dashboard
+pub async fn admin_handler(State(state): ApiState, Extension(user): Extension<User>) -> AppResult<impl IntoResponse> {
+    require_admin(&user)?;
+
+    let FeatureSettingsTemplate { features, overrides } = feature_settings(&state.db, &state.features).await?;
+    let MetricsSummary { routes, jobs } = state.metrics.summary();
+    Ok(HtmlTemplate(AdminTemplate {
+        title: "Admin",
+        auth_state: AuthState::Authenticated,
+        is_error: false,
+        slow_query_count: db::timing::slow_query_count(),
+        runs: recent_runs(&state.db).await?,
+        failing_routes: routes.into_iter().filter(|route| route.tally.failed > 0).collect(),
+        jobs,
+        alerts: state.alerts.as_ref().map(|alerts| AlertsView {
+            error_percent: alerts.error_percent,
+            min_requests: alerts.min_requests,
+            job_failure_percent: alerts.job_failure_percent,
+            window_minutes: alerts.window.as_secs() / 60,
+        }),
+        admin_search_enabled: state.admin_search_enabled,
+        announcement: db::instance_settings::get_announcement(&state.db).await?.into(),
+        lockouts: current_lockouts(&state.db).await?,
+        starter_set: db::instance_settings::get_starter_set(&state.db).await?,
+        registration_enabled: state.registration_enabled,
+        features,
+        overrides,
+        blocked_domains: db::blocked_domains::get_blocklist(&state.db, None).await?,
+        instance_blocklist: true,
+        version: CURRENT_VERSION,
+        update_status: match &state.update_check {
+            None => UpdateStatus::Off,
+            Some(update_check) => update_check.available().map_or(UpdateStatus::UpToDate, UpdateStatus::Available),
+        },
+    }))
+}
+
+/// Handler for the requests and scheduled job runs counted since the server started, as JSON
+///
+/// For monitoring tools to scrape; the dashboard shows the failures.
+pub async fn metrics_handler(State(state): ApiState, Extension(user): Extension<User>) -> AppResult<impl IntoResponse> {
+    require_admin(&user)?;
+    Ok(Json(state.metrics.summary()))
+}
+
+/// Handler for the new release banner fragment, loaded by every page once signed in
+///
+/// Only the admin sees it; everyone else gets nothing.
+pub async fn update_notice_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Query(params): Query<UpdateNoticeQuery>,
+) -> impl IntoResponse {
+    let release = state
+        .update_check
+        .as_ref()
+        .filter(|_| user.is_admin)
+        .and_then(|update_check| update_check.available())
+        .filter(|release| release.version != params.dismissed);
+
+    HtmlTemplate(UpdateNoticeTemplate { release })
+}
+
+/// API handler for turning a feature on or off for one user, or back to the instance default
+pub async fn update_feature_override_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Form(form): Form<FeatureOverrideForm>,
+) -> AppResult<impl IntoResponse> {
+    require_admin(&user)?;
+
+    let feature: Feature = form.feature.parse().map_err(|_| AppError::bad_request("Choose a feature."))?;
+    let enabled = match form.setting.as_str() {
+        "on" => Some(true),
+        "off" => Some(false),
+        "default" => None,
+        _ => return Err(AppError::bad_request("Choose on, off or the instance default.")),
+    };
+    let username = form.username.trim();
+    if !db::feature_flags::set_override(&state.db, username, feature.name(), enabled).await? {
+        return Err(AppError::bad_request(format!("There is no user named '{username}'.")));
+    }
+    info!(
+        user = user.username,
+        target_user = username,
+        feature = feature.name(),
+        ?enabled,
+        "🚩 Feature override updated by admin."
+    );
+
+    Ok(HtmlTemplate(feature_settings(&state.db, &state.features).await?))
+}
+
+/// API handler for setting or clearing the announcement banner shown on every page
+pub async fn update_announcement_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Form(form): Form<AnnouncementForm>,
+) -> AppResult<impl IntoResponse> {
+    require_admin(&user)?;
+
+    let message = form.message.trim();
+    if message.chars().count() > MAX_ANNOUNCEMENT_LENGTH {
+        return Err(AppError::bad_request(format!(
+            "Announcements can be at most {MAX_ANNOUNCEMENT_LENGTH} characters."
+        )));
+    }
+    let expires_at = match form.expires_in_hours.trim() {
+        "" => None,
+        hours => match hours.parse::<u32>() {
+            Ok(hours) if hours > 0 => Some(chrono::Utc::now().timestamp() + i64::from(hours) * 3600),
+            _ => return Err(AppError::bad_request("Expiry must be a whole number of hours.")),
+        },
+    };
+
+    let message = (!message.is_empty()).then_some(message);
+    db::instance_settings::set_announcement(&state.db, message, expires_at).await?;
+    info!(
+        user = user.username,
+        cleared = message.is_none(),
+        "📢 Announcement updated by admin."
+    );
+
+    Ok(HtmlTemplate(AnnouncementSettingsTemplate {
+        announcement: db::instance_settings::get_announcement(&state.db).await?.into(),
+    }))
+}
+
+/// API handler for lifting a sign-in lockout before it ends
+pub async fn unlock_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Path(failure_id): Path<i64>,
+) -> AppResult<impl IntoResponse> {
+    require_admin(&user)?;
+
+    if !db::login_failures::unlock(&state.db, failure_id).await? {
+        return Err(AppError::with_status(anyhow!("No lockout {failure_id}"), StatusCode::NOT_FOUND));
+    }
+    info!(user = user.username, failure_id, "🔓 Sign-in lockout lifted by admin.");
+
+    Ok(HtmlTemplate(LockoutListTemplate {
+        lockouts: current_lockouts(&state.db).await?,
+    }))
+}
+
+/// API handler for turning the starter set for new users on or off
+pub async fn update_starter_set_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Form(form): Form<StarterSetForm>,
+) -> AppResult<impl IntoResponse> {
+    require_admin(&user)?;
+
+    let enabled = form.starter_set.is_some();
+    db::instance_settings::set_starter_set(&state.db, enabled).await?;
+    info!(user = user.username, enabled, "🌱 Starter set for new users updated by admin.");
+
+    Ok("OK")
+}
+
+/// API handler for running database maintenance now instead of waiting for the nightly run
+pub async fn run_maintenance_handler(State(state): ApiState, Extension(user): Extension<User>) -> AppResult<impl IntoResponse> {
+    require_admin(&user)?;
+    info!(user = user.username, "🧹 Database maintenance started by admin.");
+
+    jobs::run_maintenance(&state.db, state.sessions.as_ref()).await;
+
+    Ok(HtmlTemplate(MaintenanceRunsTemplate {
+        runs: recent_runs(&state.db).await?,
+    }))
+}
+
+/// Handler for the admin search page
+pub async fn admin_search_page_handler(State(state): ApiState, Extension(user): Extension<User>) -> AppResult<impl IntoResponse> {
+    require_admin_search(&state, &user)?;
+
+    let log = db::admin_search::get_recent_searches(&state.db, RECENT_SEARCHES).await?;
+    Ok(HtmlTemplate(AdminSearchTemplate {
+        title: "Admin search",
+        auth_state: AuthState::Authenticated,
+        is_error: false,
+        log: log
+            .into_iter()
+            .map(|entry| AdminSearchLogView {
+                searched: format_timestamp(entry.searched_at),
+                entry,
+            })
+            .collect(),
+    }))
+}
+
+/// API handler searching every user's bookmarks
+///
+/// Each search must give a reason, and is logged with it before any results are shown. If the log
+/// can't be written, no results are shown.
+pub async fn admin_search_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Form(form): Form<AdminSearchForm>,
+) -> AppResult<impl IntoResponse> {
+    require_admin_search(&state, &user)?;
+
+    let query = form.query.trim();
+    let reason = form.reason.trim();
+    if query.is_empty() {
+        return Err(AppError::bad_request("Enter something to search for."));
+    }
+    if reason.chars().count() < MIN_REASON_LENGTH {
+        return Err(AppError::bad_request(format!(
+            "Give a reason for this search (at least {MIN_REASON_LENGTH} characters)."
+        )));
+    }
+
+    let results = db::admin_search::search_all_users(&state.db, query, ADMIN_SEARCH_LIMIT).await?;
+    let result_count = i64::try_from(results.len()).unwrap_or(i64::MAX);
+    db::admin_search::record_search(&state.db, user.user_id, &user.username, query, reason, result_count).await?;
+    warn!(
+        user = user.username,
+        query, reason, result_count, "🔎 Admin searched all users' bookmarks."
+    );
+
+    Ok(HtmlTemplate(AdminSearchResultsTemplate {
+        query: query.to_string(),
+        results: results
+            .into_iter()
+            .map(|result| AdminSearchResultView {
+                created: format_timestamp(result.created_at),
+                result,
+            })
+            .collect(),
+    }))
+}
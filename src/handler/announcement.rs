@@ -0,0 +1,41 @@
+//! Instance announcement banner shown on every page.
+
+use askama::Template;
+use axum::{
+    extract::{Query, State},
+    response::IntoResponse,
+};
+use serde::Deserialize;
+
+use crate::{
+    ApiState,
+    db::{self, instance_settings::Announcement},
+    error::AppResult,
+    handler::HtmlTemplate,
+};
+
+/// The banner, or nothing if there's no announcement or it was dismissed.
+#[derive(Template)]
+#[template(path = "components/announcement.html")]
+pub struct AnnouncementTemplate {
+    pub announcement: Option<Announcement>,
+}
+
+#[derive(Deserialize)]
+pub struct AnnouncementQuery {
+    /// Version (`updated_at`) of the announcement the browser last dismissed
+    #[serde(default)]
+    pub dismissed: String,
+}
+
+/// Handler for the announcement banner fragment, loaded by every page
+///
+/// Doesn't need a session, so notices also reach the login page.
+pub async fn announcement_handler(State(state): ApiState, Query(params): Query<AnnouncementQuery>) -> AppResult<impl IntoResponse> {
+    let dismissed = params.dismissed.parse::<i64>().ok();
+    let announcement = db::instance_settings::get_announcement(&state.db)
+        .await?
+        .filter(|announcement| Some(announcement.updated_at) != dismissed);
+
+    Ok(HtmlTemplate(AnnouncementTemplate { announcement }))
+}
@@ -0,0 +1,800 @@
+//! JSON API for scripts, apps and browser extensions, under `/api/v1`.
+//!
+//! Requests are authenticated by the browser session like the rest of the app, or by an API token
+//! sent as `Authorization: Bearer <token>` (see [`crate::db::api_tokens`]). Tokens limited to some
+//! tags only see and change bookmarks with one of them, which the handlers check here; their scope
+//! is checked by [`crate::handler::middlewares::check_api_token`]. Errors are answered as
+//! `{"error": "..."}`, and lists a page at a time, alongside `total`, `page`, `per_page` and links
+//! to the `next` and `prev` pages (see [`crate::handler::pagination::PageMeta`]).
+
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use axum::{
+    Extension, Json,
+    extract::{Path, State},
+    http::{
+        HeaderMap, HeaderValue, Method, StatusCode,
+        header::{CACHE_CONTROL, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+    },
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{
+    ApiState, AppState,
+    blocklist::Blocklist,
+    clean::UrlCleaner,
+    db::{
+        api_tokens::TokenAuth,
+        bookmarks::{self, AuthorCount, BookmarkId, BookmarkItem, BookmarkOrigin, BookmarkSource, NewBookmark},
+        tags::{self, TagCount},
+        users::{self, User},
+    },
+    error::{AppError, AppResult},
+    handler::{
+        bookmarks::{BookmarkQuery, find_bookmark, search_for},
+        extract::TolerantQuery,
+        pagination::{Page, PageMeta, PageQuery},
+    },
+    page_text,
+    search::SearchDefaults,
+    snapshot,
+    translate::normalize_language,
+    wayback,
+};
+
+/// Most bookmarks accepted in one batch request.
+pub const MAX_BATCH_SIZE: usize = 100;
+/// Bookmarks per page of the bookmark list.
+const LIST_LIMIT: i64 = 50;
+/// Bookmark IDs per page of the ID list.
+const ID_LIST_LIMIT: i64 = 1000;
+/// Tags or authors per page of their lists.
+const NAME_LIST_LIMIT: i64 = 1000;
+
+#[derive(Serialize)]
+pub struct BookmarkListResponse {
+    /// Version of the user's data the list was loaded at; it changes whenever any bookmark does
+    pub version: i64,
+    pub bookmarks: Vec<BookmarkItem>,
+    #[serde(flatten)]
+    pub page: PageMeta,
+}
+
+/// API handler listing bookmarks as JSON, with the same filters as the web list
+///
+/// Responses carry an `ETag` and `Last-Modified` from the user's data version, so clients polling for
+/// changes get an empty `304 Not Modified` until something changes.
+pub async fn list_bookmarks_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    token: Option<Extension<TokenAuth>>,
+    headers: HeaderMap,
+    TolerantQuery(params): TolerantQuery<BookmarkQuery>,
+) -> AppResult<Response> {
+    let data_version = users::get_data_version(&state.db, user.user_id).await?;
+    let etag = format!("W/\"{}\"", data_version.version);
+
+    let mut response = if is_not_modified(&headers, &etag, data_version.changed_at) {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        let sort = params.sort.unwrap_or_default();
+        // Scripts get the same results whatever the user picked as their search defaults
+        let search_query = search_for(&state.db, user.user_id, &params, SearchDefaults::default()).await?;
+        let tag_names = token_tags(token.as_deref());
+        let total = bookmarks::count_user_bookmarks(&state.db, user.user_id, &search_query, tag_names).await?;
+        let page = Page::new(params.page, LIST_LIMIT, total);
+        let bookmarks = match tag_names {
+            [] => {
+                bookmarks::search_user_bookmarks_advanced(&state.db, user.user_id, &search_query, sort, LIST_LIMIT, page.offset()).await?
+            }
+            tag_names => {
+                bookmarks::search_user_bookmarks_tagged(&state.db, user.user_id, &search_query, tag_names, sort, LIST_LIMIT, page.offset())
+                    .await?
+            }
+        };
+        Json(BookmarkListResponse {
+            version: data_version.version,
+            bookmarks: bookmarks.into(),
+            page: page.meta(|number| format!("/api/v1/bookmarks{}", params.to_query_string(number))),
+        })
+        .into_response()
+    };
+
+    let response_headers = response.headers_mut();
+    response_headers.insert(CACHE_CONTROL, HeaderValue::from_static("private, no-cache"));
+    if let Ok(value) = HeaderValue::from_str(&etag) {
+        response_headers.insert(ETAG, value);
+    }
+    if let Some(changed_at) = DateTime::from_timestamp(data_version.changed_at, 0)
+        && let Ok(value) = HeaderValue::from_str(&changed_at.format("%a, %d %b %Y %H:%M:%S GMT").to_string())
+    {
+        response_headers.insert(LAST_MODIFIED, value);
+    }
+
+    Ok(response)
+}
+
+#[derive(Serialize)]
+pub struct BookmarkIdsResponse {
+    pub ids: Vec<BookmarkId>,
+    #[serde(flatten)]
+    pub page: PageMeta,
+}
+
+/// API handler listing only the IDs of the bookmarks matching the same filters as the bookmark list,
+/// for scripts that pass them on to other endpoints
+///
+/// Pages are in a stable order, so going through them all finds each matching bookmark once as long
+/// as nothing changes in between.
+pub async fn list_bookmark_ids_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    token: Option<Extension<TokenAuth>>,
+    TolerantQuery(params): TolerantQuery<BookmarkQuery>,
+) -> AppResult<Json<BookmarkIdsResponse>> {
+    let sort = params.sort.unwrap_or_default();
+    let search_query = search_for(&state.db, user.user_id, &params, SearchDefaults::default()).await?;
+    let tag_names = token_tags(token.as_deref());
+    let total = bookmarks::count_user_bookmarks(&state.db, user.user_id, &search_query, tag_names).await?;
+    let page = Page::new(params.page, ID_LIST_LIMIT, total);
+    let ids = bookmarks::search_user_bookmark_ids(
+        &state.db,
+        user.user_id,
+        &search_query,
+        tag_names,
+        sort,
+        ID_LIST_LIMIT,
+        page.offset(),
+    )
+    .await?;
+
+    Ok(Json(BookmarkIdsResponse {
+        ids,
+        page: page.meta(|number| format!("/api/v1/bookmarks/ids{}", params.to_query_string(number))),
+    }))
+}
+
+#[derive(Serialize)]
+pub struct AuthorListResponse {
+    pub authors: Vec<AuthorCount>,
+    #[serde(flatten)]
+    pub page: PageMeta,
+}
+
+/// API handler listing the authors of the user's bookmarks, with how many bookmarks each wrote
+///
+/// Not available to tokens limited to some tags, as the counts include all bookmarks.
+pub async fn list_authors_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    token: Option<Extension<TokenAuth>>,
+    TolerantQuery(params): TolerantQuery<PageQuery>,
+) -> AppResult<Json<AuthorListResponse>> {
+    if !token_tags(token.as_deref()).is_empty() {
+        return Err(
+            AppError::with_status(anyhow!("Tag-limited API token listed authors"), StatusCode::FORBIDDEN)
+                .user_message("Tokens limited to some tags can't list authors."),
+        );
+    }
+    let (page, authors) = Page::of(params.page, NAME_LIST_LIMIT, bookmarks::get_authors(&state.db, user.user_id).await?);
+    Ok(Json(AuthorListResponse {
+        authors,
+        page: page.meta(|number| format!("/api/v1/authors?page={number}")),
+    }))
+}
+
+#[derive(Serialize)]
+pub struct AuthorBookmarksResponse {
+    pub author: String,
+    pub bookmarks: Vec<BookmarkItem>,
+    #[serde(flatten)]
+    pub page: PageMeta,
+}
+
+/// API handler listing everything the user has saved by one author
+pub async fn author_bookmarks_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    token: Option<Extension<TokenAuth>>,
+    Path(author): Path<String>,
+    TolerantQuery(params): TolerantQuery<PageQuery>,
+) -> AppResult<Json<AuthorBookmarksResponse>> {
+    if author.trim().is_empty() {
+        return Err(AppError::bad_request("Author is required"));
+    }
+    let bookmarks: Vec<BookmarkItem> = bookmarks::get_bookmarks_by_author(&state.db, user.user_id, &author)
+        .await?
+        .into_iter()
+        .filter(|bookmark| can_access(token.as_deref(), bookmark))
+        .collect();
+    let (page, bookmarks) = Page::of(params.page, LIST_LIMIT, bookmarks);
+    let mut url = Url::parse("http://localhost/api/v1/authors/").expect("static URL is valid");
+    url.path_segments_mut().expect("static URL has a path").pop_if_empty().push(&author);
+    Ok(Json(AuthorBookmarksResponse {
+        page: page.meta(|number| format!("{}?page={number}", url.path())),
+        author,
+        bookmarks,
+    }))
+}
+
+/// The tags the request's API token is limited to; empty if it isn't, or the request was made with
+/// the browser session.
+fn token_tags(token: Option<&TokenAuth>) -> &[String] {
+    token.map_or(&[], |token| &token.tags)
+}
+
+/// Whether the request can see and change a bookmark, going by the tags its API token is limited to.
+fn can_access(token: Option<&TokenAuth>, bookmark: &BookmarkItem) -> bool {
+    token.is_none_or(|token| token.can_access(bookmark.tags.iter().map(|tag| tag.name.as_str())))
+}
+
+/// Finds one of the user's bookmarks, as if it didn't exist if the request's API token can't see it.
+async fn find_accessible_bookmark(
+    state: &AppState,
+    user_id: Uuid,
+    token: Option<&TokenAuth>,
+    bookmark_id: BookmarkId,
+) -> AppResult<BookmarkItem> {
+    let bookmark = find_bookmark(&state.db, user_id, bookmark_id).await?;
+    if !can_access(token, &bookmark) {
+        return Err(AppError::with_status(
+            anyhow!("Bookmark {bookmark_id} is outside the API token's tags"),
+            StatusCode::NOT_FOUND,
+        ));
+    }
+    Ok(bookmark)
+}
+
+/// Checks that a new or changed bookmark keeps one of the tags the request's API token is limited
+/// to, returning why not otherwise.
+fn check_token_tags(token: Option<&TokenAuth>, bookmark: &NewBookmark) -> Result<(), String> {
+    match token {
+        Some(token) if !token.can_access(bookmark.tag_names.iter().map(String::as_str)) => Err(format!(
+            "This token can only save bookmarks tagged with one of: {}",
+            token.tags.join(", ")
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// [`check_token_tags`] as a 403 error.
+fn require_token_tags(token: Option<&TokenAuth>, bookmark: &NewBookmark) -> AppResult<()> {
+    check_token_tags(token, bookmark)
+        .map_err(|message| AppError::with_status(anyhow!("{message}"), StatusCode::FORBIDDEN).user_message(message))
+}
+
+/// Where bookmarks saved through the API came from: the API, and which token if it was one.
+fn api_origin(token: Option<&TokenAuth>) -> BookmarkOrigin {
+    BookmarkOrigin {
+        source: BookmarkSource::Api,
+        detail: token.map(|token| token.name.clone()),
+    }
+}
+
+/// API handler for saving one bookmark, answering with it as saved
+pub async fn create_bookmark_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    token: Option<Extension<TokenAuth>>,
+    Json(request): Json<BatchBookmark>,
+) -> AppResult<impl IntoResponse> {
+    let bookmark_id = save_bookmark(&state, &user, token.as_deref(), request, &api_origin(token.as_deref())).await?;
+    info!(user = user.username, %bookmark_id, "📥 Created bookmark through the API.");
+
+    Ok((
+        StatusCode::CREATED,
+        Json(find_bookmark(&state.db, user.user_id, bookmark_id).await?),
+    ))
+}
+
+/// Checks, cleans and saves one bookmark, then starts saving its page's text, a copy of the page and
+/// its submission to the Wayback Machine in the background.
+pub(crate) async fn save_bookmark(
+    state: &Arc<AppState>,
+    user: &User,
+    token: Option<&TokenAuth>,
+    request: BatchBookmark,
+    origin: &BookmarkOrigin,
+) -> AppResult<BookmarkId> {
+    let bookmark = request.validate().map_err(AppError::bad_request)?;
+    require_token_tags(token, &bookmark)?;
+    let bookmark = UrlCleaner::for_user(&state.db, user.user_id).await.apply(bookmark);
+    let bookmark = Blocklist::for_user(&state.db, user.user_id)
+        .await?
+        .apply(bookmark)
+        .map_err(AppError::bad_request)?;
+
+    let bookmark_id = bookmarks::create_bookmark(&state.db, user.user_id, &bookmark, origin).await?;
+    tokio::spawn(page_text::save_page_text(
+        state.clone(),
+        user.user_id,
+        bookmark_id,
+        bookmark.url.clone(),
+    ));
+    tokio::spawn(wayback::submit_new_bookmark(state.clone(), user.user_id, bookmark.url.clone()));
+    tokio::spawn(snapshot::save_new_snapshot(state.clone(), user.user_id, bookmark_id, bookmark.url));
+
+    Ok(bookmark_id)
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct CheckQuery {
+    pub url: String,
+}
+
+/// Whether a URL is saved, with the bookmark if it is.
+#[derive(Serialize)]
+pub struct BookmarkCheckResponse {
+    pub saved: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bookmark: Option<BookmarkItem>,
+}
+
+/// The bookmark a quick save answers with, and whether it was saved just now or already.
+#[derive(Serialize)]
+pub struct QuickSaveResponse {
+    pub created: bool,
+    pub bookmark_id: BookmarkId,
+    pub bookmark: BookmarkItem,
+}
+
+/// Finds the bookmark saved for a URL, cleaning the URL the way it would be cleaned when saved,
+/// whether or not the request's API token can see it.
+async fn find_saved_url(state: &AppState, user_id: Uuid, url: &str) -> AppResult<Option<BookmarkItem>> {
+    let url = UrlCleaner::for_user(&state.db, user_id)
+        .await
+        .clean(url)
+        .unwrap_or_else(|| url.to_string());
+    let Some(bookmark_id) = bookmarks::find_bookmark_by_url(&state.db, user_id, &url).await? else {
+        return Ok(None);
+    };
+    Ok(bookmarks::get_bookmark(&state.db, user_id, bookmark_id).await?)
+}
+
+/// Whether the request can read a bookmark with these tags: its API token, if any, can make `GET`
+/// requests and isn't limited to other tags.
+fn can_read<'a>(token: Option<&TokenAuth>, tag_names: impl Iterator<Item = &'a str>) -> bool {
+    token.is_none_or(|token| token.scope.allows(&Method::GET) && token.can_access(tag_names))
+}
+
+/// API handler for whether a URL is saved already, answering with its bookmark if it is, e.g. for a
+/// browser extension to show on the page it's opened on
+pub async fn check_bookmark_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    token: Option<Extension<TokenAuth>>,
+    TolerantQuery(params): TolerantQuery<CheckQuery>,
+) -> AppResult<Json<BookmarkCheckResponse>> {
+    let url = params.url.trim();
+    if url.is_empty() {
+        return Err(AppError::bad_request("Give the URL to check as 'url'."));
+    }
+    let bookmark = find_saved_url(&state, user.user_id, url)
+        .await?
+        .filter(|bookmark| can_access(token.as_deref(), bookmark));
+
+    Ok(Json(BookmarkCheckResponse {
+        saved: bookmark.is_some(),
+        bookmark,
+    }))
+}
+
+/// API handler for saving a page in one click, e.g. from a browser extension, answering with the
+/// bookmark already saved for its URL instead of saving it twice
+///
+/// The page isn't fetched first, so it answers as fast as the bookmark is saved; its text, a copy
+/// and the Wayback Machine submission follow in the background as for any bookmark. A URL that's
+/// saved already is never saved again: if the API token can't read its bookmark, e.g. it can only
+/// create bookmarks or is limited to other tags, it's a 409 error without the bookmark.
+pub async fn quick_save_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    token: Option<Extension<TokenAuth>>,
+    Json(request): Json<BatchBookmark>,
+) -> AppResult<impl IntoResponse> {
+    let token = token.as_deref();
+    if let Some(bookmark) = find_saved_url(&state, user.user_id, request.url.trim()).await? {
+        if !can_read(token, bookmark.tags.iter().map(|tag| tag.name.as_str())) {
+            return Err(AppError::with_status(
+                anyhow!("Quick save of {} with an API token that can't read it", bookmark.id),
+                StatusCode::CONFLICT,
+            )
+            .user_message("This URL is saved already."));
+        }
+        return Ok((
+            StatusCode::OK,
+            Json(QuickSaveResponse {
+                created: false,
+                bookmark_id: bookmark.id,
+                bookmark,
+            }),
+        ));
+    }
+
+    let bookmark_id = save_bookmark(&state, &user, token, request, &api_origin(token)).await?;
+    info!(user = user.username, %bookmark_id, "📥 Quick-saved bookmark through the API.");
+
+    Ok((
+        StatusCode::CREATED,
+        Json(QuickSaveResponse {
+            created: true,
+            bookmark_id,
+            bookmark: find_bookmark(&state.db, user.user_id, bookmark_id).await?,
+        }),
+    ))
+}
+
+/// API handler for one bookmark, archived or not
+pub async fn get_bookmark_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    token: Option<Extension<TokenAuth>>,
+    Path(bookmark_id): Path<BookmarkId>,
+) -> AppResult<Json<BookmarkItem>> {
+    Ok(Json(
+        find_accessible_bookmark(&state, user.user_id, token.as_deref(), bookmark_id).await?,
+    ))
+}
+
+/// A bookmark's new URL, title, description and tags. The title defaults to the URL.
+#[derive(Deserialize)]
+pub struct BookmarkUpdate {
+    pub url: String,
+    #[serde(default)]
+    pub title: String,
+    pub description: Option<String>,
+    /// Replaces all of the bookmark's tags
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// API handler for changing a bookmark, answering with it as saved
+pub async fn update_bookmark_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    token: Option<Extension<TokenAuth>>,
+    Path(bookmark_id): Path<BookmarkId>,
+    Json(request): Json<BookmarkUpdate>,
+) -> AppResult<Json<BookmarkItem>> {
+    let bookmark = BatchBookmark {
+        url: request.url,
+        title: request.title,
+        description: request.description,
+        tags: request.tags,
+        language: None,
+        expires_at: None,
+        unread: false,
+    }
+    .validate()
+    .map_err(AppError::bad_request)?;
+    find_accessible_bookmark(&state, user.user_id, token.as_deref(), bookmark_id).await?;
+    require_token_tags(token.as_deref(), &bookmark)?;
+    let bookmark = UrlCleaner::for_user(&state.db, user.user_id).await.apply(bookmark);
+    let bookmark = Blocklist::for_user(&state.db, user.user_id)
+        .await?
+        .apply(bookmark)
+        .map_err(AppError::bad_request)?;
+
+    if !bookmarks::update_bookmark(&state.db, user.user_id, bookmark_id, &bookmark).await? {
+        return Err(AppError::with_status(
+            anyhow!("Bookmark {bookmark_id} not found for user"),
+            StatusCode::NOT_FOUND,
+        ));
+    }
+    info!(user = user.username, %bookmark_id, "✏️ Updated bookmark through the API.");
+
+    Ok(Json(find_bookmark(&state.db, user.user_id, bookmark_id).await?))
+}
+
+/// API handler for deleting a bookmark, moving it to the trash like the web app does
+pub async fn delete_bookmark_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    token: Option<Extension<TokenAuth>>,
+    Path(bookmark_id): Path<BookmarkId>,
+) -> AppResult<StatusCode> {
+    find_accessible_bookmark(&state, user.user_id, token.as_deref(), bookmark_id).await?;
+    if !bookmarks::trash_bookmark(&state.db, user.user_id, bookmark_id).await? {
+        return Err(AppError::with_status(
+            anyhow!("Bookmark {bookmark_id} not found for user"),
+            StatusCode::NOT_FOUND,
+        ));
+    }
+    info!(user = user.username, %bookmark_id, "🗑️ Moved bookmark to the trash through the API.");
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Serialize)]
+pub struct TagListResponse {
+    pub tags: Vec<TagCount>,
+    #[serde(flatten)]
+    pub page: PageMeta,
+}
+
+/// API handler listing the tags on the user's bookmarks outside the archive, with how many
+/// bookmarks have each. Tokens limited to some tags only see those.
+pub async fn list_tags_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    token: Option<Extension<TokenAuth>>,
+    TolerantQuery(params): TolerantQuery<PageQuery>,
+) -> AppResult<Json<TagListResponse>> {
+    let mut tags = tags::get_user_tags(&state.db, user.user_id).await?;
+    let token_tags = token_tags(token.as_deref());
+    if !token_tags.is_empty() {
+        tags.retain(|tag| token_tags.contains(&tag.name));
+    }
+    let (page, tags) = Page::of(params.page, NAME_LIST_LIMIT, tags);
+    Ok(Json(TagListResponse {
+        tags,
+        page: page.meta(|number| format!("/api/v1/tags?page={number}")),
+    }))
+}
+
+/// Whether the client's cached copy is still current, going by `If-None-Match` or, failing that,
+/// `If-Modified-Since`.
+///
+/// Prefer the `ETag`: `If-Modified-Since` only has one-second resolution, so a change in the same
+/// second as the previous response goes unnoticed.
+fn is_not_modified(headers: &HeaderMap, etag: &str, changed_at: i64) -> bool {
+    // Weak comparison, as both sides may be weak tags
+    let opaque_tag = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+
+    if let Some(if_none_match) = headers.get(IF_NONE_MATCH) {
+        return if_none_match
+            .to_str()
+            .is_ok_and(|tags| tags.split(',').any(|tag| tag.trim() == "*" || opaque_tag(tag) == opaque_tag(etag)));
+    }
+
+    headers
+        .get(IF_MODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| DateTime::parse_from_rfc2822(value).ok())
+        .is_some_and(|since| changed_at <= since.timestamp())
+}
+
+#[derive(Deserialize)]
+pub struct BatchCreateRequest {
+    pub bookmarks: Vec<BatchBookmark>,
+}
+
+/// A bookmark in a batch request. Only the URL is required; the title defaults to the URL.
+#[derive(Default, Deserialize)]
+pub struct BatchBookmark {
+    pub url: String,
+    #[serde(default)]
+    pub title: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Language of the page's content, e.g. `de`
+    pub language: Option<String>,
+    /// UNIX timestamp after which the bookmark is deleted, for temporary saves
+    pub expires_at: Option<i64>,
+    /// Save it to read later
+    #[serde(default)]
+    pub unread: bool,
+}
+
+/// Outcome for one bookmark of a batch, in the same position as in the request.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum BatchItemResult {
+    Created { bookmark_id: BookmarkId },
+    Error { error: String },
+}
+
+#[derive(Serialize)]
+pub struct BatchCreateResponse {
+    pub created: usize,
+    pub failed: usize,
+    pub results: Vec<BatchItemResult>,
+}
+
+impl BatchBookmark {
+    /// Checks and normalizes the bookmark, returning why it can't be saved if it's invalid.
+    fn validate(self) -> Result<NewBookmark, String> {
+        let url = self.url.trim();
+        match Url::parse(url) {
+            Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => {}
+            Ok(_) => return Err(format!("Only http and https URLs can be saved: '{url}'")),
+            Err(err) => return Err(format!("Invalid URL '{url}': {err}")),
+        }
+
+        if self.expires_at.is_some_and(|expires_at| expires_at <= Utc::now().timestamp()) {
+            return Err("The expiry time is in the past".to_string());
+        }
+
+        let title = match self.title.trim() {
+            "" => url,
+            title => title,
+        };
+
+        let mut tag_names: Vec<String> = Vec::new();
+        for tag in self.tags {
+            let tag = tag.trim().to_lowercase();
+            if !tag.is_empty() && !tag_names.contains(&tag) {
+                tag_names.push(tag);
+            }
+        }
+
+        Ok(NewBookmark {
+            url: url.to_string(),
+            title: title.to_string(),
+            description: self.description.map(|d| d.trim().to_string()).filter(|d| !d.is_empty()),
+            tag_names,
+            language: self.language.as_deref().and_then(normalize_language),
+            expires_at: self.expires_at,
+            is_unread: self.unread,
+            ..NewBookmark::default()
+        })
+    }
+}
+
+/// API handler for creating many bookmarks at once
+///
+/// Invalid bookmarks are reported in the results without stopping the rest, which are all saved in
+/// one transaction.
+pub async fn batch_create_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    token: Option<Extension<TokenAuth>>,
+    Json(request): Json<BatchCreateRequest>,
+) -> AppResult<impl IntoResponse> {
+    if request.bookmarks.is_empty() || request.bookmarks.len() > MAX_BATCH_SIZE {
+        return Err(AppError::bad_request(format!(
+            "A batch must have between 1 and {MAX_BATCH_SIZE} bookmarks."
+        )));
+    }
+
+    let cleaner = UrlCleaner::for_user(&state.db, user.user_id).await;
+    let blocklist = Blocklist::for_user(&state.db, user.user_id).await?;
+    let validated: Vec<Result<NewBookmark, String>> = request
+        .bookmarks
+        .into_iter()
+        .map(|item| {
+            item.validate()
+                .and_then(|bookmark| check_token_tags(token.as_deref(), &bookmark).map(|()| bookmark))
+                .and_then(|bookmark| blocklist.apply(cleaner.apply(bookmark)))
+        })
+        .collect();
+    let valid: Vec<NewBookmark> = validated.iter().filter_map(|item| item.as_ref().ok()).cloned().collect();
+
+    let mut bookmark_ids = bookmarks::create_bookmarks(&state.db, user.user_id, &valid, &api_origin(token.as_deref()))
+        .await?
+        .into_iter();
+
+    let results: Vec<BatchItemResult> = validated
+        .into_iter()
+        .map(|item| match item {
+            Ok(_) => bookmark_ids.next().map_or_else(
+                || BatchItemResult::Error {
+                    error: "Bookmark was not saved".to_string(),
+                },
+                |bookmark_id| BatchItemResult::Created { bookmark_id },
+            ),
+            Err(error) => BatchItemResult::Error { error },
+        })
+        .collect();
+
+    let created = valid.len();
+    let failed = results.len() - created;
+    info!(user = user.username, created, failed, "📥 Created bookmarks from batch.");
+
+    Ok(Json(BatchCreateResponse { created, failed, results }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::api_tokens::TokenScope;
+
+    fn batch_bookmark(url: &str) -> BatchBookmark {
+        BatchBookmark {
+            url: url.to_string(),
+            title: String::new(),
+            description: None,
+            tags: Vec::new(),
+            language: None,
+            expires_at: None,
+            unread: false,
+        }
+    }
+
+    #[test]
+    fn test_validate_normalizes_fields() {
+        let bookmark = BatchBookmark {
+            title: "  ".to_string(),
+            description: Some(" ".to_string()),
+            tags: vec![" Rust ".to_string(), "rust".to_string(), String::new(), "web".to_string()],
+            ..batch_bookmark(" https://www.rust-lang.org/ ")
+        }
+        .validate()
+        .unwrap();
+
+        assert_eq!(bookmark.url, "https://www.rust-lang.org/");
+        assert_eq!(bookmark.title, "https://www.rust-lang.org/");
+        assert_eq!(bookmark.description, None);
+        assert_eq!(bookmark.tag_names, ["rust", "web"]);
+    }
+
+    fn headers(name: axum::http::HeaderName, value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, HeaderValue::from_static(value));
+        headers
+    }
+
+    fn token(scope: TokenScope, tags: &[&str]) -> TokenAuth {
+        TokenAuth {
+            token_id: Uuid::nil(),
+            name: "extension".to_string(),
+            scope,
+            tags: tags.iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    #[test]
+    fn test_can_read_saved_bookmark() {
+        let tags = || ["rust", "web"].into_iter();
+        // The browser session, and tokens that can read it
+        assert!(can_read(None, tags()));
+        assert!(can_read(Some(&token(TokenScope::Full, &[])), tags()));
+        assert!(can_read(Some(&token(TokenScope::Read, &["web"])), tags()));
+        // Tokens limited to other tags, even with full access
+        assert!(!can_read(Some(&token(TokenScope::Full, &["cooking"])), tags()));
+        assert!(!can_read(Some(&token(TokenScope::Full, &["cooking"])), std::iter::empty()));
+        // Tokens that can only create bookmarks, whatever their tags
+        assert!(!can_read(Some(&token(TokenScope::Create, &[])), tags()));
+        assert!(!can_read(Some(&token(TokenScope::Create, &["rust"])), tags()));
+    }
+
+    #[test]
+    fn test_not_modified_by_etag() {
+        assert!(!is_not_modified(&HeaderMap::new(), "W/\"7\"", 1000));
+        assert!(is_not_modified(&headers(IF_NONE_MATCH, "W/\"7\""), "W/\"7\"", 1000));
+        assert!(is_not_modified(&headers(IF_NONE_MATCH, "\"3\", \"7\""), "W/\"7\"", 1000));
+        assert!(is_not_modified(&headers(IF_NONE_MATCH, "*"), "W/\"7\"", 1000));
+        assert!(!is_not_modified(&headers(IF_NONE_MATCH, "W/\"6\""), "W/\"7\"", 1000));
+    }
+
+    #[test]
+    fn test_not_modified_by_date() {
+        // 1000000000 is Sun, 09 Sep 2001 01:46:40 GMT
+        let since = headers(IF_MODIFIED_SINCE, "Sun, 09 Sep 2001 01:46:40 GMT");
+        assert!(is_not_modified(&since, "W/\"7\"", 1_000_000_000));
+        assert!(!is_not_modified(&since, "W/\"7\"", 1_000_000_001));
+        assert!(!is_not_modified(&headers(IF_MODIFIED_SINCE, "yesterday"), "W/\"7\"", 0));
+    }
+
+    #[test]
+    fn test_validate_rejects_bad_urls() {
+        assert!(batch_bookmark("not a url").validate().is_err());
+        assert!(batch_bookmark("javascript:alert(1)").validate().is_err());
+        assert!(batch_bookmark("ftp://example.com/file").validate().is_err());
+        assert!(batch_bookmark("http://example.com").validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_rejects_past_expiry() {
+        let expired = BatchBookmark {
+            expires_at: Some(1_000_000_000),
+            ..batch_bookmark("https://example.com")
+        };
+        assert!(expired.validate().is_err());
+
+        let expires_at = Utc::now().timestamp() + 3600;
+        let temporary = BatchBookmark {
+            expires_at: Some(expires_at),
+            ..batch_bookmark("https://example.com")
+        };
+        assert_eq!(temporary.validate().unwrap().expires_at, Some(expires_at));
+    }
+}
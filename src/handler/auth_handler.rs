@@ -1,29 +1,56 @@
 //! Authentication handlers for login, logout, and session management.
 //!
 //! This module provides handlers for user authentication operations,
-//! including login form display, login processing, and logout.
+//! including login form display, login processing, and logout. When the instance allows it
+//! (`PAGEPOUCH_REGISTRATION_ENABLED`), anyone can also sign up for an account, and when it can send
+//! email (see [`crate::mail`]), users who forgot their password can have a link to reset it sent to
+//! them. Users who set up two-factor authentication (see [`crate::totp`]) are asked for a code from
+//! their authenticator app, or a recovery code, after their password. Repeated failures at either
+//! step lock the login name or IP address out for a while, see [`crate::lockout`].
 
+use std::sync::Arc;
+
+use anyhow::anyhow;
 use axum::{
     Form,
-    extract::State,
-    http::StatusCode,
+    extract::{Path, State},
+    http::{StatusCode, header},
     response::{IntoResponse, Response},
 };
 use axum_extra::extract::{CookieJar, cookie::Cookie};
 use serde::Deserialize;
-use tracing::warn;
+use tracing::{error, info, warn};
+use uuid::Uuid;
 
 use crate::{
-    ApiState,
+    ApiState, AppState,
     db::{
-        self,
-        user_session::{DEFAULT_SESSION_MINUTES, SessionToken, make_user_session},
-        users::check_username_password,
+        self, collections,
+        password_resets::{self, PasswordResetToken},
+        settings,
+        two_factor::{self as two_factor_db, TwoFactorToken},
+        user_session::{self, DEFAULT_SESSION_MINUTES, SessionToken, make_user_session},
+        users::{self, User, check_username_password},
+    },
+    error::{AppError, AppResult},
+    handler::{
+        AuthState, ForgotPasswordTemplate, HomeTemplate, HtmlTemplate, HxRequest, LoginTemplate, RegisterTemplate, ResetPasswordTemplate,
+        TwoFactorTemplate, extract::ClientIp, middlewares::check_session_cookie, page_or_redirect, two_factor,
     },
-    error::AppResult,
-    handler::{AuthState, HomeTemplate, HtmlTemplate, middlewares::check_session_cookie},
+    lockout,
+    mail::{self, Mailer},
+    onboarding,
 };
 
+/// Usernames are between these many characters long.
+const USERNAME_LENGTH: std::ops::RangeInclusive<usize> = 3..=32;
+/// Passwords are between these many characters long.
+const PASSWORD_LENGTH: std::ops::RangeInclusive<usize> = 8..=128;
+/// How long a password reset link works for, in minutes.
+const PASSWORD_RESET_MINUTES: i64 = 60;
+/// How long after the password step the two-factor code has to be given, in minutes.
+const TWO_FACTOR_MINUTES: i64 = 5;
+
 /// Serves the login page template.
 ///
 /// Returns home page if the user is already authenticated.
@@ -35,10 +62,12 @@ pub async fn login_page_handler(State(state): ApiState, jar: CookieJar) -> impl
         return axum::response::Redirect::to("/").into_response();
     }
 
-    HtmlTemplate(crate::handler::LoginTemplate {
+    HtmlTemplate(LoginTemplate {
         title: "Login",
         auth_state: AuthState::LoginPage,
         is_error: false,
+        registration_enabled: state.registration_enabled,
+        password_reset_enabled: state.mailer.is_some(),
     })
     .into_response()
 }
@@ -96,46 +125,441 @@ pub(super) fn clear_session(jar: CookieJar) -> CookieJar {
 /// Handles the POST request for user login.
 ///
 /// This function:
-/// 1. Validates username and password
-/// 2. Creates a new session in the database
-/// 3. Signs a session token with JWT
-/// 4. Sets the session cookie
-/// 5. Returns the home page directly
+/// 1. Turns the attempt away if the login name or IP address is locked out, see [`lockout`]
+/// 2. Validates username and password, counting a failure towards a lockout
+/// 3. Asks for a two-factor code instead of going on, if the user set that up, see
+///    [`two_factor_handler`]
+/// 4. Onboards the user if it's their first sign-in, see [`onboarding`]
+/// 5. Creates a new session in the database
+/// 6. Signs a session token with JWT
+/// 7. Sets the session cookie
+/// 8. Returns the home page directly for HTMX, or redirects to it for a plain form post
 ///
 /// # Errors
 ///
 /// Returns `AppError::bad_login` if credentials are invalid.
+/// Returns a 429 error if the login name or IP address is locked out.
 /// Returns database errors if session creation fails.
-pub async fn login_user_handler(State(state): ApiState, jar: CookieJar, Form(form_data): Form<LoginUserSchema>) -> AppResult<Response> {
+pub async fn login_user_handler(
+    State(state): ApiState,
+    ClientIp(ip): ClientIp,
+    jar: CookieJar,
+    hx: HxRequest,
+    Form(form_data): Form<LoginUserSchema>,
+) -> AppResult<Response> {
     let LoginUserSchema { username, password } = form_data;
-    let user = check_username_password(&state.pool, username, password).await?;
-    let session = make_user_session(&state.pool, &user).await?;
+    lockout::check(&state.db, &username, ip.as_deref()).await?;
+    let user = match check_username_password(&state.db, username.clone(), password).await {
+        Ok(user) => user,
+        Err(err) => {
+            if err.status() == StatusCode::UNAUTHORIZED {
+                lockout::record_failure(&state.db, &username, ip.as_deref()).await;
+            }
+            return Err(err);
+        }
+    };
+    // Failures under the username itself are cleared once signed in, see `sign_in`, so they still
+    // count towards a lockout at the two-factor step
+    if lockout::login_key(&username) != lockout::login_key(&user.username) {
+        lockout::record_success(&state.db, &username).await;
+    }
+    sign_in_or_ask_code(&state, jar, hx, &user).await
+}
+
+/// Signs a user whose password was right in, see [`sign_in`], or if they set up two-factor
+/// authentication, answers with the page asking for a code, carrying a [`TwoFactorToken`] on to
+/// [`two_factor_handler`].
+///
+/// # Errors
+///
+/// Returns database errors if looking up the user's two-factor setup or creating the session fails.
+async fn sign_in_or_ask_code(state: &AppState, jar: CookieJar, hx: HxRequest, user: &User) -> AppResult<Response> {
+    if !two_factor_db::is_totp_enabled(&state.db, user.user_id).await? {
+        return sign_in(state, jar, hx, user).await;
+    }
+
+    let token = state.encryption.sign_token(TwoFactorToken {
+        user_id: user.user_id,
+        expires_at: chrono::Utc::now().timestamp() + TWO_FACTOR_MINUTES * 60,
+    })?;
+    info!(user = user.username, "🔐 Asking for a two-factor code.");
+    Ok(HtmlTemplate(TwoFactorTemplate {
+        title: "Two-Factor Authentication",
+        auth_state: AuthState::LoginPage,
+        is_error: false,
+        token,
+    })
+    .into_response())
+}
+
+/// Form data structure for the two-factor step of signing in.
+#[derive(Debug, Deserialize)]
+pub struct TwoFactorSchema {
+    /// Signed [`TwoFactorToken`] from the password step
+    pub token: String,
+    /// Code from the authenticator app, or a recovery code
+    pub code: String,
+}
+
+/// Handles the POST request of the two-factor step of signing in: checks the code from the
+/// authenticator app, or uses up a recovery code, and signs the user in. Wrong codes count towards
+/// a lockout of the username, see [`lockout`].
+///
+/// # Errors
+///
+/// Returns a 401 error if the password step's token isn't valid or has expired, or the code is
+/// wrong or was already used, a 429 error if the username or IP address is locked out, and database
+/// errors if checking the code or creating the session fails.
+pub async fn two_factor_handler(
+    State(state): ApiState,
+    ClientIp(ip): ClientIp,
+    jar: CookieJar,
+    hx: HxRequest,
+    Form(form_data): Form<TwoFactorSchema>,
+) -> AppResult<Response> {
+    let expired = || {
+        AppError::unauthorized(anyhow!("Two-factor token is invalid or expired"))
+            .user_message("This took too long. Log in again to get a new chance.")
+    };
+    let token = match state.encryption.verify_token_sig::<TwoFactorToken>(&form_data.token) {
+        Ok(token) if token.expires_at > chrono::Utc::now().timestamp() => token,
+        Ok(_) => return Err(expired()),
+        Err(err) => {
+            warn!(?err, "Token signature invalid on two-factor step.");
+            return Err(expired());
+        }
+    };
+
+    let mut conn = state.db.reader.acquire().await?;
+    let user = users::get_by_id(&mut conn, token.user_id).await?;
+    drop(conn);
+    if user.is_revoked {
+        return Err(AppError::bad_login(anyhow!("User '{}' is revoked", user.username)));
+    }
+    lockout::check(&state.db, &user.username, ip.as_deref()).await?;
+    let Some(user_totp) = two_factor_db::get_totp(&state.db, user.user_id).await?.filter(|totp| totp.enabled) else {
+        // Turned off since the password step, so there's nothing left to ask
+        return sign_in(&state, jar, hx, &user).await;
+    };
+
+    if !two_factor::check_code(&state, user.user_id, &user_totp, &form_data.code).await? {
+        lockout::record_failure(&state.db, &user.username, ip.as_deref()).await;
+        return Err(AppError::unauthorized(anyhow!("Wrong two-factor code for '{}'", user.username))
+            .user_message("That code isn't right, or was already used."));
+    }
+    sign_in(&state, jar, hx, &user).await
+}
+
+/// Signs a user in: forgets their username's failed sign-ins (see [`lockout`]), onboards them if
+/// it's their first sign-in (see [`onboarding`]), starts a session, sets its cookie, and answers
+/// with the home page.
+///
+/// # Errors
+///
+/// Returns database errors if session creation fails.
+async fn sign_in(state: &AppState, jar: CookieJar, hx: HxRequest, user: &User) -> AppResult<Response> {
+    lockout::record_success(&state.db, &user.username).await;
+    onboarding::onboard(&state.db, user).await;
+    let session = make_user_session(state.sessions.as_ref(), user).await?;
 
     let signed_token = state.encryption.sign_token(session.session_token())?;
+    let collections = collections::get_user_collections(&state.db, user.user_id).await.unwrap_or_default();
+    let resurface = settings::get_user_settings(&state.db, user.user_id)
+        .await
+        .is_ok_and(|settings| settings.resurface);
 
-    // Return home page directly instead of redirect
+    // Return home page directly instead of redirect when we can
     Ok((
         set_session(jar, signed_token),
-        [("HX-Push-Url", "/")],
-        HtmlTemplate(HomeTemplate {
-            title: "Home",
-            auth_state: AuthState::Authenticated,
+        page_or_redirect(
+            hx,
+            "/",
+            HomeTemplate {
+                title: "Home",
+                auth_state: AuthState::Authenticated,
+                is_error: false,
+                collections,
+                resurface,
+                ..Default::default()
+            },
+        ),
+    )
+        .into_response())
+}
+
+/// Serves the sign-up page, if anyone can sign up.
+///
+/// Returns home page if the user is already authenticated.
+///
+/// # Errors
+///
+/// Returns a 404 error if signing up is turned off.
+pub async fn register_page_handler(State(state): ApiState, jar: CookieJar) -> AppResult<Response> {
+    require_registration(&state)?;
+    if check_session_cookie(&state, &jar).await.is_ok() {
+        return Ok(axum::response::Redirect::to("/").into_response());
+    }
+
+    Ok(HtmlTemplate(RegisterTemplate {
+        title: "Sign Up",
+        auth_state: AuthState::LoginPage,
+        is_error: false,
+    })
+    .into_response())
+}
+
+/// Form data structure for sign-up requests.
+#[derive(Debug, Deserialize)]
+pub struct RegisterUserSchema {
+    pub username: String,
+    pub password: String,
+    /// The password again, to catch typos
+    pub confirm_password: String,
+}
+
+/// Handles the POST request for signing up: creates the account and signs it in.
+///
+/// The first account on an instance without an admin becomes its admin.
+///
+/// # Errors
+///
+/// Returns a 404 error if signing up is turned off, a 400 error if the username or password isn't
+/// valid or the username is taken, and database errors if creating the account or session fails.
+pub async fn register_handler(
+    State(state): ApiState,
+    jar: CookieJar,
+    hx: HxRequest,
+    Form(form_data): Form<RegisterUserSchema>,
+) -> AppResult<Response> {
+    require_registration(&state)?;
+    let username = form_data.username.trim();
+    validate_registration(username, &form_data.password, &form_data.confirm_password)
+        .map_err(|reason| registration_failed(anyhow!(reason)))?;
+
+    let password_hash = users::create_hash(form_data.password.as_bytes())?;
+    let Some(user) = users::create_user(&state.db, username, &password_hash).await? else {
+        return Err(registration_failed(anyhow!("Username '{username}' is taken")));
+    };
+    info!(user = user.username, is_admin = user.is_admin, "📝 Signed up a new user.");
+    sign_in(&state, jar, hx, &user).await
+}
+
+/// The error for an account that couldn't be created, the same whether the username or password
+/// didn't follow the rules or the username is taken, so signing up doesn't tell which accounts
+/// exist.
+fn registration_failed(internal: anyhow::Error) -> AppError {
+    AppError::with_status(internal, StatusCode::BAD_REQUEST).user_message(format!(
+        "Couldn't create that account. Usernames are {}-{} letters, digits, '.', '-' or '_' and can't be in use already; \
+         passwords are {}-{} characters, typed the same twice.",
+        USERNAME_LENGTH.start(),
+        USERNAME_LENGTH.end(),
+        PASSWORD_LENGTH.start(),
+        PASSWORD_LENGTH.end()
+    ))
+}
+
+/// Fails with a 404 error unless anyone can sign up.
+fn require_registration(state: &AppState) -> AppResult<()> {
+    if state.registration_enabled {
+        Ok(())
+    } else {
+        Err(AppError::with_status(anyhow!("Signing up is turned off"), StatusCode::NOT_FOUND))
+    }
+}
+
+/// Checks a new account's username and password: a username of letters, digits, `.`, `-` and `_`,
+/// so it can't be mistaken for an email address when signing in, and a password as
+/// [`validate_password`] wants it.
+fn validate_registration(username: &str, password: &str, confirm_password: &str) -> Result<(), String> {
+    if !USERNAME_LENGTH.contains(&username.chars().count()) {
+        return Err(format!(
+            "Usernames must be between {} and {} characters.",
+            USERNAME_LENGTH.start(),
+            USERNAME_LENGTH.end()
+        ));
+    }
+    if !username.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_')) {
+        return Err("Usernames can only have letters, digits, '.', '-' and '_'.".to_string());
+    }
+    validate_password(password, confirm_password)
+}
+
+/// Checks a new password: long enough, not too long, and typed the same twice.
+fn validate_password(password: &str, confirm_password: &str) -> Result<(), String> {
+    if !PASSWORD_LENGTH.contains(&password.chars().count()) {
+        return Err(format!(
+            "Passwords must be between {} and {} characters.",
+            PASSWORD_LENGTH.start(),
+            PASSWORD_LENGTH.end()
+        ));
+    }
+    if password != confirm_password {
+        return Err("The passwords don't match.".to_string());
+    }
+    Ok(())
+}
+
+/// Serves the page to ask for a password reset link, if they can be emailed.
+///
+/// Returns home page if the user is already authenticated.
+///
+/// # Errors
+///
+/// Returns a 404 error if no SMTP server is configured.
+pub async fn forgot_password_page_handler(State(state): ApiState, jar: CookieJar) -> AppResult<Response> {
+    require_mailer(&state)?;
+    if check_session_cookie(&state, &jar).await.is_ok() {
+        return Ok(axum::response::Redirect::to("/").into_response());
+    }
+
+    Ok(HtmlTemplate(ForgotPasswordTemplate {
+        title: "Forgot Password",
+        auth_state: AuthState::LoginPage,
+        is_error: false,
+        sent: false,
+    })
+    .into_response())
+}
+
+/// Form data structure for password reset link requests.
+#[derive(Debug, Deserialize)]
+pub struct ForgotPasswordSchema {
+    /// Username or email, as when signing in
+    pub login: String,
+}
+
+/// Handles the POST request for a password reset link: emails one to the account's address, if it
+/// has one and isn't revoked.
+///
+/// The answer is the same either way, and doesn't wait for the email to be sent, so it doesn't
+/// tell which accounts exist or have an email address.
+///
+/// # Errors
+///
+/// Returns a 404 error if no SMTP server is configured, and database errors if creating the reset
+/// fails.
+pub async fn forgot_password_handler(State(state): ApiState, Form(form_data): Form<ForgotPasswordSchema>) -> AppResult<Response> {
+    let mailer = require_mailer(&state)?.clone();
+    if let Some(recipient) = password_resets::get_reset_recipient(&state.db, form_data.login.trim()).await? {
+        let expires_at = chrono::Utc::now().timestamp() + PASSWORD_RESET_MINUTES * 60;
+        let reset_id = password_resets::create_password_reset(&state.db, recipient.user_id, expires_at).await?;
+        let token = state.encryption.sign_token(PasswordResetToken { reset_id, expires_at })?;
+        let link = format!("{}/reset-password/{token}", state.public_url.as_deref().unwrap_or_default());
+        let email = mail::password_reset_email(&recipient.email, &recipient.username, &link, PASSWORD_RESET_MINUTES);
+        tokio::spawn(async move {
+            match mailer.send(email).await {
+                Ok(()) => info!(user = recipient.username, "📧 Sent a password reset link."),
+                Err(err) => error!(user = recipient.username, ?err, "🚨 Could not send a password reset link."),
+            }
+        });
+    }
+
+    Ok(HtmlTemplate(ForgotPasswordTemplate {
+        title: "Forgot Password",
+        auth_state: AuthState::LoginPage,
+        is_error: false,
+        sent: true,
+    })
+    .into_response())
+}
+
+/// Serves the page a password reset link opens, with the form for a new password if the link can
+/// still be used.
+///
+/// # Errors
+///
+/// Returns a 404 error if no SMTP server is configured, and database errors if looking up the
+/// reset fails.
+pub async fn reset_password_page_handler(State(state): ApiState, Path(token): Path<String>) -> AppResult<Response> {
+    require_mailer(&state)?;
+    let usable = match verify_reset_token(&state, &token) {
+        Some(reset_id) => password_resets::is_password_reset_valid(&state.db, reset_id).await?,
+        None => false,
+    };
+
+    // Keep the token out of the Referer header of anything the page loads
+    Ok((
+        [(header::REFERRER_POLICY, "no-referrer")],
+        HtmlTemplate(ResetPasswordTemplate {
+            title: "Reset Password",
+            auth_state: AuthState::LoginPage,
             is_error: false,
+            token: usable.then_some(token),
         }),
     )
         .into_response())
 }
 
+/// Form data structure for choosing a new password.
+#[derive(Debug, Deserialize)]
+pub struct ResetPasswordSchema {
+    pub password: String,
+    /// The password again, to catch typos
+    pub confirm_password: String,
+}
+
+/// Handles the POST request of a password reset link: sets the new password, signs the user out
+/// everywhere else, and signs them in, after a two-factor code if they set that up.
+///
+/// # Errors
+///
+/// Returns a 404 error if no SMTP server is configured, a 400 error if the password isn't valid, a
+/// 410 error if the link has expired or was used, and database errors if changing the password or
+/// the sessions fails.
+pub async fn reset_password_handler(
+    State(state): ApiState,
+    jar: CookieJar,
+    hx: HxRequest,
+    Path(token): Path<String>,
+    Form(form_data): Form<ResetPasswordSchema>,
+) -> AppResult<Response> {
+    require_mailer(&state)?;
+    validate_password(&form_data.password, &form_data.confirm_password).map_err(AppError::bad_request)?;
+    let unusable = || {
+        AppError::with_status(anyhow!("Password reset link can't be used"), StatusCode::GONE)
+            .user_message("This link has expired or was already used. Ask for a new one.")
+    };
+    let reset_id = verify_reset_token(&state, &token).ok_or_else(unusable)?;
+
+    let password_hash = users::create_hash(form_data.password.as_bytes())?;
+    let user = password_resets::complete_password_reset(&state.db, reset_id, &password_hash)
+        .await?
+        .ok_or_else(unusable)?;
+    let signed_out = user_session::remove_user_sessions(state.sessions.as_ref(), user.user_id).await?;
+    info!(user = user.username, signed_out, "🔑 Reset a forgotten password.");
+    sign_in_or_ask_code(&state, jar, hx, &user).await
+}
+
+/// Fails with a 404 error unless password reset links can be emailed, and gives the mailer if they can.
+fn require_mailer(state: &AppState) -> AppResult<&Arc<dyn Mailer>> {
+    state
+        .mailer
+        .as_ref()
+        .ok_or_else(|| AppError::with_status(anyhow!("No SMTP server is configured"), StatusCode::NOT_FOUND))
+}
+
+/// The password reset a link's token is for, if it's signed by this instance and hasn't expired.
+fn verify_reset_token(state: &AppState, token: &str) -> Option<Uuid> {
+    match state.encryption.verify_token_sig::<PasswordResetToken>(token) {
+        Ok(token) if token.expires_at > chrono::Utc::now().timestamp() => Some(token.reset_id),
+        Ok(_) => None,
+        Err(err) => {
+            warn!(?err, "Token signature invalid on password reset link.");
+            None
+        }
+    }
+}
+
 /// Handles user logout.
 ///
 /// This function:
 /// 1. Validates the session token
 /// 2. Removes the session from the database
 /// 3. Clears the session cookie
-/// 4. Returns the login page directly
+/// 4. Returns the login page directly for HTMX, or redirects to it otherwise
 ///
 /// Returns `UNAUTHORIZED` if no valid session exists.
-pub async fn logout_handler(State(state): ApiState, jar: CookieJar) -> impl IntoResponse {
+pub async fn logout_handler(State(state): ApiState, jar: CookieJar, hx: HxRequest) -> impl IntoResponse {
     let Some(token) = jar.get(SESSION_COOKIE).map(Cookie::value) else {
         return StatusCode::UNAUTHORIZED.into_response();
     };
@@ -146,18 +570,23 @@ pub async fn logout_handler(State(state): ApiState, jar: CookieJar) -> impl Into
             return (clear_session(jar), StatusCode::UNAUTHORIZED).into_response();
         }
     };
-    if let Err(err) = db::user_session::remove_session(&state.pool, &signed_token).await {
+    if let Err(err) = db::user_session::remove_session(state.sessions.as_ref(), &signed_token).await {
         warn!(err = ?err, "Error invalidating user session, but clearing session cookie anyway.");
     }
 
     (
         clear_session(jar),
-        [("HX-Push-Url", "/login")],
-        HtmlTemplate(crate::handler::LoginTemplate {
-            title: "Login",
-            auth_state: AuthState::LoginPage,
-            is_error: false,
-        }),
+        page_or_redirect(
+            hx,
+            "/login",
+            LoginTemplate {
+                title: "Login",
+                auth_state: AuthState::LoginPage,
+                is_error: false,
+                registration_enabled: state.registration_enabled,
+                password_reset_enabled: state.mailer.is_some(),
+            },
+        ),
     )
         .into_response()
 }
@@ -171,3 +600,67 @@ pub async fn session_check_handler() -> impl IntoResponse {
     // If we reach here, middleware has already validated the session
     axum::http::StatusCode::OK
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{db::user_session::SESSION_RENEWAL_THRESHOLD, error::ErrorMessage};
+
+    #[test]
+    fn test_session_cookie_lasts_as_long_as_the_session() {
+        let cookie = session_cookie("token");
+        let max_age = cookie.max_age().unwrap();
+        assert_eq!(max_age.whole_minutes(), DEFAULT_SESSION_MINUTES);
+        // A renewal re-issues the cookie before the browser would drop it
+        assert!(max_age.whole_seconds() > SESSION_RENEWAL_THRESHOLD.num_seconds());
+    }
+
+    /// The status and message a failed sign-up answers with.
+    fn failed_registration(err: AppError) -> (u16, String) {
+        let response = err.into_response();
+        let message = response
+            .extensions()
+            .get::<ErrorMessage>()
+            .expect("the response has a message")
+            .0
+            .clone();
+        (response.status().as_u16(), message)
+    }
+
+    #[test]
+    fn test_registration_failures_are_uniform() {
+        let invalid = validate_registration("an", "correct horse", "correct horse").unwrap_err();
+        let mistyped = validate_registration("ann", "correct horse", "correct hose").unwrap_err();
+        let failures = [
+            // Username against the rules
+            failed_registration(registration_failed(anyhow!(invalid))),
+            // Passwords that don't match
+            failed_registration(registration_failed(anyhow!(mistyped))),
+            // Taken username
+            failed_registration(registration_failed(anyhow!("Username 'ann' is taken"))),
+        ];
+        for (status, message) in &failures {
+            assert_eq!(*status, 400);
+            assert_eq!(message, &failures[0].1);
+            assert!(!message.contains("taken"));
+        }
+    }
+
+    #[test]
+    fn test_validate_registration() {
+        assert!(validate_registration("ann", "correct horse", "correct horse").is_ok());
+        assert!(validate_registration("ann.lee-2_b", "12345678", "12345678").is_ok());
+        // Too short or long, or could be an email
+        assert!(validate_registration("an", "correct horse", "correct horse").is_err());
+        assert!(validate_registration(&"a".repeat(33), "correct horse", "correct horse").is_err());
+        assert!(validate_registration("ann@example.com", "correct horse", "correct horse").is_err());
+        assert!(validate_registration("ann lee", "correct horse", "correct horse").is_err());
+        // Passwords too short, too long, or mistyped
+        assert!(validate_registration("ann", "1234567", "1234567").is_err());
+        assert!(validate_registration("ann", &"a".repeat(129), &"a".repeat(129)).is_err());
+        assert_eq!(
+            validate_registration("ann", "correct horse", "correct hose"),
+            Err("The passwords don't match.".to_string())
+        );
+    }
+}
@@ -0,0 +1,133 @@
+//! Domain blocklist handlers and templates, for users' own blocklists and the instance-wide one.
+
+use anyhow::anyhow;
+use askama::Template;
+use axum::{
+    Extension, Form,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use serde::Deserialize;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{
+    ApiState,
+    blocklist::BlockAction,
+    clean,
+    db::{
+        Db,
+        blocked_domains::{self, BlockedDomain},
+        users::User,
+    },
+    error::{AppError, AppResult},
+    handler::{HtmlTemplate, admin::require_admin},
+};
+
+/// A blocklist on the settings page or the admin dashboard, re-rendered after each change.
+#[derive(Template)]
+#[template(path = "components/blocklist_settings.html")]
+pub struct BlocklistSettingsTemplate {
+    /// On the settings page, the instance-wide entries too, which only the admin can change
+    pub blocked_domains: Vec<BlockedDomain>,
+    /// Whether this is the admin's instance-wide blocklist rather than a user's own
+    pub instance_blocklist: bool,
+}
+
+#[derive(Deserialize)]
+pub struct BlockedDomainForm {
+    pub domain: String,
+    /// `block` or `archive`
+    pub action: String,
+}
+
+/// Adds a domain to a user's blocklist, or the instance-wide one if `user_id` is `None`.
+async fn add_entry(db: &Db, user_id: Option<Uuid>, form: &BlockedDomainForm) -> AppResult<String> {
+    let domain = clean::normalize_domain(&form.domain).ok_or_else(|| AppError::bad_request("Enter the domain to block."))?;
+    let action: BlockAction = form
+        .action
+        .parse()
+        .map_err(|_| AppError::bad_request("Choose whether to block or archive bookmarks from it."))?;
+
+    if blocked_domains::create_blocked_domain(db, user_id, &domain, action.as_str())
+        .await?
+        .is_none()
+    {
+        return Err(AppError::bad_request(format!("{domain} is already on the blocklist.")));
+    }
+    Ok(domain)
+}
+
+/// Removes a domain from a user's blocklist, or the instance-wide one if `user_id` is `None`.
+async fn remove_entry(db: &Db, user_id: Option<Uuid>, block_id: Uuid) -> AppResult<()> {
+    if !blocked_domains::delete_blocked_domain(db, user_id, block_id).await? {
+        return Err(AppError::with_status(
+            anyhow!("Blocked domain {block_id} not found"),
+            StatusCode::NOT_FOUND,
+        ));
+    }
+    Ok(())
+}
+
+async fn blocklist_settings(db: &Db, user_id: Option<Uuid>) -> AppResult<BlocklistSettingsTemplate> {
+    Ok(BlocklistSettingsTemplate {
+        blocked_domains: blocked_domains::get_blocklist(db, user_id).await?,
+        instance_blocklist: user_id.is_none(),
+    })
+}
+
+/// API handler for adding a domain to the user's blocklist
+pub async fn create_blocked_domain_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Form(form): Form<BlockedDomainForm>,
+) -> AppResult<impl IntoResponse> {
+    let domain = add_entry(&state.db, Some(user.user_id), &form).await?;
+    info!(user = user.username, domain, action = form.action, "⛔ Blocked domain.");
+
+    Ok(HtmlTemplate(blocklist_settings(&state.db, Some(user.user_id)).await?))
+}
+
+/// API handler for removing a domain from the user's blocklist
+pub async fn delete_blocked_domain_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Path(block_id): Path<Uuid>,
+) -> AppResult<impl IntoResponse> {
+    remove_entry(&state.db, Some(user.user_id), block_id).await?;
+    info!(user = user.username, %block_id, "⛔ Unblocked domain.");
+
+    Ok(HtmlTemplate(blocklist_settings(&state.db, Some(user.user_id)).await?))
+}
+
+/// API handler for adding a domain to the instance-wide blocklist
+pub async fn create_instance_blocked_domain_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Form(form): Form<BlockedDomainForm>,
+) -> AppResult<impl IntoResponse> {
+    require_admin(&user)?;
+    let domain = add_entry(&state.db, None, &form).await?;
+    info!(
+        user = user.username,
+        domain,
+        action = form.action,
+        "⛔ Domain blocked for everyone by admin."
+    );
+
+    Ok(HtmlTemplate(blocklist_settings(&state.db, None).await?))
+}
+
+/// API handler for removing a domain from the instance-wide blocklist
+pub async fn delete_instance_blocked_domain_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Path(block_id): Path<Uuid>,
+) -> AppResult<impl IntoResponse> {
+    require_admin(&user)?;
+    remove_entry(&state.db, None, block_id).await?;
+    info!(user = user.username, %block_id, "⛔ Domain unblocked for everyone by admin.");
+
+    Ok(HtmlTemplate(blocklist_settings(&state.db, None).await?))
+}
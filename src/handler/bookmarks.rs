@@ -1,109 +1,474 @@
 //! Bookmark-related handlers and templates.
 
+use anyhow::anyhow;
 use askama::Template;
-use axum::{Extension, Form, Json, extract::State, http::StatusCode, response::IntoResponse};
-use axum_extra::extract::Query;
-use reqwest::Client;
+use axum::{
+    Extension, Form, Json,
+    extract::{Path, State},
+    http::{
+        HeaderMap, HeaderValue, StatusCode,
+        header::{CACHE_CONTROL, CONTENT_TYPE},
+    },
+    response::{IntoResponse, Response},
+};
+use chrono::{NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
-use tl::VDom;
-use tracing::{debug, error, warn};
+use tracing::{debug, error, info, warn};
+use uuid::Uuid;
 
 use crate::{
-    ApiState,
+    ApiState, AppState,
+    blocklist::Blocklist,
+    clean::UrlCleaner,
     db::{
-        bookmarks::{self, BookmarkItem},
+        Db,
+        bookmarks::{self, BookmarkCursor, BookmarkId, BookmarkItem, BookmarkList, BookmarkSource, NewBookmark, SortOrder, Visibility},
+        collections,
+        fetch_failures::{self, FetchFailure, FetchFailures},
+        link_checks::{self, ArchivedCopies},
+        settings::{self, ListDisplay, UserSettings},
+        tags::{self, TagColors},
+        thumbnails,
         users::User,
     },
-    handler::{AuthState, HomeTemplate, HtmlTemplate},
-    search::SearchQuery,
+    error::{AppError, AppResult},
+    fetch::{self, LinkScrapeResult},
+    fetch_retry,
+    handler::{
+        AuthState, HomeTemplate, HtmlTemplate, HxRequest,
+        extract::TolerantQuery,
+        page_or_redirect,
+        pagination::{Page, Pagination},
+        render_fragment,
+    },
+    highlight::Highlighter,
+    page_text,
+    search::{SearchDefaults, SearchQuery, StateFilter},
+    snapshot, thumbnail,
+    translate::normalize_language,
+    wayback,
 };
 
-#[derive(Clone)]
-pub struct Pagination {
-    pub has_prev: bool,
-    pub has_next: bool,
-    pub page_links: Vec<PageLink>,
-}
-
-#[derive(Clone)]
-pub struct PageLink {
-    pub number: i64,
-    pub is_current: bool,
-    pub is_ellipsis: bool,
-}
-
+#[allow(clippy::struct_excessive_bools)] // independent flags for the template
 #[derive(Template)]
 #[template(path = "components/bookmark_content.html")]
 pub struct BookmarkContentTemplate {
     pub bookmarks: Vec<BookmarkItem>,
     pub pagination: Option<Pagination>,
+    /// The user's choice of which fields to show in each row
+    pub list: ListDisplay,
+    /// Language bookmarks in other languages can be translated into, if translation is available
+    pub translate_to: Option<String>,
+    /// Whether copies of the pages can be saved, see [`crate::snapshot`]
+    pub archiving: bool,
+    /// Whether this is the archive rather than the main list
+    pub archived: bool,
+    /// Whether this is the reading list of unread bookmarks
+    pub unread: bool,
+    /// The search shown, as a rule it can be saved as a smart collection with
+    pub search_rule: Option<String>,
+    /// Marks the searched-for terms in each bookmark
+    pub highlight: Highlighter,
+    /// Whether more bookmarks are loaded at the end of the list instead of pages
+    pub scrolling: bool,
+    /// Loads the bookmarks after the last one shown, when scrolling
+    pub more_url: Option<String>,
+    /// Where the bookmarks' new order is sent when they're dragged, if this is a smart collection in
+    /// its own order
+    pub reorder_url: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+/// The bookmarks loaded when scrolling to the end of the list, appended in place of its end.
+#[derive(Template)]
+#[template(path = "components/bookmark_page.html")]
+pub struct BookmarkPageTemplate {
+    pub bookmarks: Vec<BookmarkItem>,
+    pub list: ListDisplay,
+    pub translate_to: Option<String>,
+    pub archiving: bool,
+    pub highlight: Highlighter,
+    pub more_url: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
 pub struct BookmarkQuery {
     pub q: Option<String>, // Search query
     /// Filter by tags - complete/committed tags will not be part of the `q`, only partial tags that need
     /// auto-complete, or invalid tags (non-existing tags followed by whitespace) which should be ignored
     #[serde(default)]
     pub tags: Option<Vec<String>>,
+    pub sort: Option<SortOrder>,
     pub page: Option<i64>, // Page number
+    /// Show the bookmarks matching this smart collection's rule
+    pub collection: Option<Uuid>,
+    /// Show the archive instead of the main list
+    #[serde(default)]
+    pub archived: bool,
+    /// Only show bookmarks saved to read later
+    #[serde(default)]
+    pub unread: bool,
+    /// Show the bookmarks after this cursor (see [`BookmarkCursor`]) instead of a page
+    pub after: Option<String>,
+}
+
+impl BookmarkQuery {
+    /// Serializes the full filter state into a canonical query string (including the leading `?`),
+    /// omitting anything left at its default so that equivalent views share one URL. A picked sort
+    /// order is always kept, as users can change which order is their default.
+    ///
+    /// Returns an empty string when there is no filter state at all.
+    pub fn to_query_string(&self, page: i64) -> String {
+        let mut url = reqwest::Url::parse("http://localhost/").expect("static URL is valid");
+        {
+            let mut pairs = url.query_pairs_mut();
+            if let Some(collection) = self.collection {
+                pairs.append_pair("collection", &collection.to_string());
+            }
+            if self.archived {
+                pairs.append_pair("archived", "true");
+            }
+            if self.unread {
+                pairs.append_pair("unread", "true");
+            }
+            if let Some(q) = self.q.as_deref().map(str::trim).filter(|q| !q.is_empty()) {
+                pairs.append_pair("q", q);
+            }
+            for tag in self.tags.iter().flatten() {
+                pairs.append_pair("tags", tag);
+            }
+            if let Some(sort) = self.sort {
+                pairs.append_pair("sort", sort.as_str());
+            }
+            if page > 1 {
+                pairs.append_pair("page", &page.to_string());
+            }
+        }
+
+        match url.query() {
+            Some(query) if !query.is_empty() => format!("?{query}"),
+            _ => String::new(),
+        }
+    }
+
+    /// The search and sidebar tags as a smart collection rule, e.g. `site:github.com #rust`, or `None`
+    /// if nothing is searched for, or this already is a collection, the archive or the reading list.
+    pub fn search_rule(&self) -> Option<String> {
+        if self.collection.is_some() || self.archived || self.unread {
+            return None;
+        }
+        let q = self.q.as_deref().map(str::trim).filter(|q| !q.is_empty());
+        let tags = self.tags.iter().flatten().map(|tag| format!("#{tag}"));
+        let rule = q.map(ToString::to_string).into_iter().chain(tags).collect::<Vec<_>>().join(" ");
+        (!SearchQuery::parse_complete(&rule).is_empty()).then_some(rule)
+    }
+
+    /// Whether this is the plain list of bookmarks in an order it can be scrolled through by cursor,
+    /// see [`bookmarks::get_user_bookmarks_after`].
+    fn is_scrollable(&self, sort: SortOrder) -> bool {
+        self.collection.is_none()
+            && !self.archived
+            && !self.unread
+            && self.tags.iter().flatten().next().is_none()
+            && self.q.as_deref().is_none_or(|q| q.trim().is_empty())
+            && matches!(sort, SortOrder::Newest | SortOrder::Oldest)
+    }
+
+    /// The URL loading the bookmarks after `cursor`, for scrolling on from the end of the list.
+    pub fn scroll_url(&self, cursor: BookmarkCursor) -> String {
+        let query = self.to_query_string(1);
+        let separator = if query.is_empty() { '?' } else { '&' };
+        format!("/api/bookmarks{query}{separator}after={cursor}")
+    }
+
+    /// Marks the terms searched for in `q`, if [`find_bookmarks`] searches for them rather than
+    /// showing a collection or only filtering by sidebar tags.
+    pub fn highlighter(&self) -> Highlighter {
+        let searched = self.collection.is_none() && (self.archived || self.unread || self.tags.iter().flatten().next().is_none());
+        match self.q.as_deref() {
+            Some(q) if searched => Highlighter::new(&SearchQuery::parse(q)),
+            _ => Highlighter::default(),
+        }
+    }
+}
+
+/// Checks whether the page HTMX reports as current (`HX-Current-URL`) is already showing `location`.
+fn is_current_url(headers: &HeaderMap, location: &str) -> bool {
+    headers
+        .get("HX-Current-URL")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| reqwest::Url::parse(value).ok())
+        .is_some_and(|current| {
+            let current_location = match current.query() {
+                Some(query) if !query.is_empty() => format!("{}?{query}", current.path()),
+                _ => current.path().to_string(),
+            };
+            current_location == location
+        })
 }
 
 const DEFAULT_LIMIT: i64 = 20;
+/// Longest reason for a failed fetch taken from the Add Bookmark form.
+const FETCH_ERROR_LENGTH: usize = 200;
 /// API handler for bookmark content (HTMX lazy loading)
+///
+/// Shows pages, or with the user's infinite scroll setting (or an `after` cursor), the bookmarks
+/// after a cursor with a link at the end of the list that loads more.
 pub async fn bookmark_content_handler(
     State(state): ApiState,
     Extension(user): Extension<User>,
-    Query(params): Query<BookmarkQuery>,
-) -> impl IntoResponse {
+    hx: HxRequest,
+    headers: HeaderMap,
+    TolerantQuery(params): TolerantQuery<BookmarkQuery>,
+) -> AppResult<Response> {
     debug!(?params, "Bookmark content handler queried");
 
-    let page = params.page.unwrap_or(1);
-    let offset = (page - 1) * DEFAULT_LIMIT;
+    let user_settings = settings::get_user_settings(&state.db, user.user_id).await.unwrap_or_else(|err| {
+        error!(?err, "🚨 Could not load list display settings, using defaults.");
+        UserSettings::default()
+    });
+    let sort = params.sort.unwrap_or(user_settings.default_sort);
+    let translate_to = user_settings.preferred_language.filter(|_| state.translator.is_some());
+    let archiving = snapshot::is_archiving_allowed(&state, user.user_id).await.unwrap_or_else(|err| {
+        error!(?err, "🚨 Could not load archiving settings, not offering snapshots.");
+        false
+    });
+    let tag_colors = tags::get_tag_colors(&state.db, user.user_id).await.unwrap_or_else(|err| {
+        error!(?err, "🚨 Could not load tag colors, showing tags without them.");
+        TagColors::default()
+    });
+    let archived_copies = archived_copies(&state, user.user_id).await;
+    let fetch_failures = failed_fetches(&state, user.user_id).await;
+
+    let scrolling = params.is_scrollable(sort) && (user_settings.list.infinite_scroll || params.after.is_some());
+    if scrolling {
+        let after = match params.after.as_deref() {
+            Some(after) => Some(
+                after
+                    .parse::<BookmarkCursor>()
+                    .map_err(|_| AppError::bad_request("Invalid list cursor"))?,
+            ),
+            None => None,
+        };
+        let bookmark_page = bookmarks::get_user_bookmarks_after(&state.db, user.user_id, sort, after, DEFAULT_LIMIT).await?;
+        let more_url = bookmark_page.next.map(|next| params.scroll_url(next));
+        if after.is_some() {
+            // Only the next bookmarks, appended to the list already shown
+            return Ok(HtmlTemplate(BookmarkPageTemplate {
+                bookmarks: bookmark_page
+                    .bookmarks
+                    .with_tag_colors(&tag_colors)
+                    .with_archived_copies(&archived_copies)
+                    .with_fetch_failures(&fetch_failures)
+                    .into(),
+                list: user_settings.list,
+                translate_to,
+                archiving,
+                highlight: Highlighter::default(),
+                more_url,
+            })
+            .into_response());
+        }
+
+        let response = render_fragment(
+            hx,
+            "Bookmarks",
+            BookmarkContentTemplate {
+                bookmarks: bookmark_page
+                    .bookmarks
+                    .with_tag_colors(&tag_colors)
+                    .with_archived_copies(&archived_copies)
+                    .with_fetch_failures(&fetch_failures)
+                    .into(),
+                pagination: None,
+                list: user_settings.list,
+                translate_to,
+                archiving,
+                archived: false,
+                unread: false,
+                search_rule: None,
+                highlight: Highlighter::default(),
+                scrolling,
+                more_url,
+                reorder_url: None,
+            },
+        );
+        return Ok(push_canonical_url(response, hx, &headers, &params, 1));
+    }
+
+    let (db_bookmarks, page) = find_bookmarks(&state.db, user.user_id, &params, user_settings.search, sort, DEFAULT_LIMIT).await?;
+    let response = render_fragment(
+        hx,
+        "Bookmarks",
+        BookmarkContentTemplate {
+            bookmarks: db_bookmarks
+                .with_tag_colors(&tag_colors)
+                .with_archived_copies(&archived_copies)
+                .with_fetch_failures(&fetch_failures)
+                .into(),
+            pagination: page.links(|number| format!("/api/bookmarks{}", params.to_query_string(number))),
+            list: user_settings.list,
+            translate_to,
+            archiving,
+            archived: params.archived,
+            unread: params.unread,
+            search_rule: params.search_rule(),
+            highlight: params.highlighter(),
+            scrolling,
+            more_url: None,
+            reorder_url: params
+                .collection
+                .filter(|_| params.sort.is_none())
+                .map(|collection_id| format!("/api/collections/{collection_id}/order")),
+        },
+    );
+
+    Ok(push_canonical_url(response, hx, &headers, &params, page.number))
+}
+
+/// The copies in the Wayback Machine of the user's broken links, if links are checked.
+async fn archived_copies(state: &AppState, user_id: Uuid) -> ArchivedCopies {
+    if !state.link_check_enabled {
+        return ArchivedCopies::default();
+    }
+    link_checks::get_archived_copies(&state.db, user_id).await.unwrap_or_else(|err| {
+        error!(?err, "🚨 Could not load archived copies of broken links, not offering them.");
+        ArchivedCopies::default()
+    })
+}
+
+/// Why the pages of the user's bookmarks that couldn't be fetched couldn't be.
+async fn failed_fetches(state: &AppState, user_id: Uuid) -> FetchFailures {
+    fetch_failures::get_fetch_failures(&state.db, user_id).await.unwrap_or_else(|err| {
+        error!(?err, "🚨 Could not load failed fetches, not showing them.");
+        FetchFailures::default()
+    })
+}
+
+/// Keeps the address bar in sync with the filters so the view can be bookmarked and shared.
+fn push_canonical_url(mut response: Response, hx: HxRequest, headers: &HeaderMap, params: &BookmarkQuery, page: i64) -> Response {
+    let canonical_url = format!("/{}", params.to_query_string(page));
+    if hx.0
+        && !is_current_url(headers, &canonical_url)
+        && let Ok(value) = HeaderValue::from_str(&canonical_url)
+    {
+        response.headers_mut().insert("HX-Push-Url", value);
+    }
+    response
+}
+
+/// Loads the page `params` asks for, `per_page` to a page, of the user's bookmarks matching its
+/// filters, see [`search_for`], in the `sort` order. A smart collection is in its own order
+/// instead, unless `params` picks one.
+///
+/// # Errors
+///
+/// Returns a 404 error if the smart collection doesn't exist, or an error if loading it fails.
+pub(crate) async fn find_bookmarks(
+    db: &Db,
+    user_id: Uuid,
+    params: &BookmarkQuery,
+    defaults: SearchDefaults,
+    sort: SortOrder,
+    per_page: i64,
+) -> AppResult<(BookmarkList, Page)> {
+    let search_query = search_for(db, user_id, params, defaults).await?;
+    load_page(db, user_id, params, &search_query, sort, per_page).await
+}
 
-    // Parse search query to extract tags and determine search type
-    let tags: Vec<String> = params.tags.unwrap_or_default();
-    let db_bookmarks = if !tags.is_empty() {
+/// Loads the page `params` asks for like [`find_bookmarks`], for people other than the user: only
+/// their public bookmarks, newest first, with the search read the default way.
+///
+/// # Errors
+///
+/// Returns a 404 error if the smart collection doesn't exist, or an error if loading it fails.
+pub(crate) async fn find_public_bookmarks(
+    db: &Db,
+    user_id: Uuid,
+    params: &BookmarkQuery,
+    per_page: i64,
+) -> AppResult<(BookmarkList, Page)> {
+    let search_query = search_for(db, user_id, params, SearchDefaults::default()).await?.public_only();
+    load_page(db, user_id, params, &search_query, SortOrder::default(), per_page).await
+}
+
+/// Loads the page `params` asks for of the user's bookmarks matching `search_query`.
+async fn load_page(
+    db: &Db,
+    user_id: Uuid,
+    params: &BookmarkQuery,
+    search_query: &SearchQuery,
+    sort: SortOrder,
+    per_page: i64,
+) -> AppResult<(BookmarkList, Page)> {
+    let total = bookmarks::count_user_bookmarks(db, user_id, search_query, &[])
+        .await
+        .unwrap_or_default();
+    let page = Page::new(params.page, per_page, total);
+    let bookmarks = match params.collection {
+        Some(collection_id) if params.sort.is_none() => {
+            bookmarks::search_collection_bookmarks(db, user_id, collection_id, search_query, per_page, page.offset()).await
+        }
+        _ => bookmarks::search_user_bookmarks_advanced(db, user_id, search_query, sort, per_page, page.offset()).await,
+    };
+    Ok((bookmarks.unwrap_or_default(), page))
+}
+
+/// The search for the filters in `params`: a smart collection, the archive or reading list, sidebar
+/// tags or a search, in that order of precedence, read with the user's search `defaults`. Without any, it
+/// finds every bookmark outside the archive.
+///
+/// # Errors
+///
+/// Returns a 404 error if the smart collection doesn't exist, or an error if loading it fails.
+pub(crate) async fn search_for(db: &Db, user_id: Uuid, params: &BookmarkQuery, defaults: SearchDefaults) -> AppResult<SearchQuery> {
+    let collection = match params.collection {
+        Some(collection_id) => Some(
+            collections::get_collection(db, user_id, collection_id)
+                .await?
+                .ok_or_else(|| AppError::with_status(anyhow!("Collection {collection_id} not found for user"), StatusCode::NOT_FOUND))?,
+        ),
+        None => None,
+    };
+
+    let tags: Vec<String> = params.tags.clone().unwrap_or_default();
+    let search_query = if let Some(collection) = collection {
+        // Smart collections are evaluated fresh each time; sidebar tags narrow them down further
+        let mut search_query = SearchQuery::parse_complete_with(&collection.query, defaults);
+        search_query.tag_filters.extend(tags);
+        debug!(name = collection.name, ?search_query, "Evaluating smart collection");
+        search_query
+    } else if params.archived || params.unread {
+        // The archive and reading list can be searched and narrowed down by sidebar tags like the main list
+        let mut search_query = SearchQuery::parse_with(params.q.as_deref().unwrap_or_default(), defaults);
+        search_query.tag_filters.extend(tags);
+        if params.archived && !search_query.state_filters.contains(&StateFilter::Archived) {
+            search_query.state_filters.push(StateFilter::Archived);
+        }
+        if params.unread && !search_query.state_filters.contains(&StateFilter::Unread) {
+            search_query.state_filters.push(StateFilter::Unread);
+        }
+        search_query
+    } else if !tags.is_empty() {
         // TODO: we should be able to search by both tags and regular query
         // Committed tags from new tag completion system
-        bookmarks::search_by_tags_only(&state.pool, user.user_id, &tags, DEFAULT_LIMIT, offset)
-            .await
-            .unwrap_or_default()
+        SearchQuery {
+            tag_filters: tags,
+            include_archived: defaults.include_archived,
+            ..SearchQuery::default()
+        }
     } else if let Some(ref search_query_str) = params.q {
-        // Parse the search query and use advanced search
-        let search_query = SearchQuery::parse(search_query_str);
+        let search_query = SearchQuery::parse_with(search_query_str, defaults);
         debug!("Parsed search query: {:?}", search_query);
-
-        bookmarks::search_user_bookmarks_advanced(&state.pool, user.user_id, &search_query, DEFAULT_LIMIT, offset)
-            .await
-            .unwrap_or_default()
+        search_query
     } else {
         // No filters
-        bookmarks::get_user_bookmarks(&state.pool, user.user_id, DEFAULT_LIMIT, offset)
-            .await
-            .unwrap_or_default()
-    };
-
-    // Convert database results to template format
-    // TODO: Implement proper pagination based on total count
-    let pagination = if i64::try_from(db_bookmarks.len()).unwrap_or(0) == DEFAULT_LIMIT {
-        Some(Pagination {
-            has_prev: page > 1,
-            has_next: true, // Assume there might be more
-            page_links: vec![PageLink {
-                number: page,
-                is_current: true,
-                is_ellipsis: false,
-            }],
-        })
-    } else {
-        None // No pagination needed
+        SearchQuery {
+            include_archived: defaults.include_archived,
+            ..SearchQuery::default()
+        }
     };
 
-    HtmlTemplate(BookmarkContentTemplate {
-        bookmarks: db_bookmarks.into(),
-        pagination,
-    })
+    Ok(search_query)
 }
 
 #[derive(Template)]
@@ -112,6 +477,21 @@ pub struct BookmarkNewTemplate<'a> {
     pub title: &'a str,
     pub auth_state: AuthState,
     pub is_error: bool,
+    /// Whether the title and description may be fetched from the entered URL
+    pub fetch_enabled: bool,
+    pub prefill: NewBookmarkPrefill,
+    /// Opened by the bookmarklet in a window of its own, which closes once the bookmark is saved
+    pub popup: bool,
+}
+
+/// What the add bookmark form is filled in with, e.g. the page the bookmarklet was clicked on.
+#[derive(Clone, Debug, Default)]
+pub struct NewBookmarkPrefill {
+    pub url: String,
+    pub title: String,
+    pub description: String,
+    /// Comma separated
+    pub tags: String,
 }
 
 #[derive(Deserialize)]
@@ -120,6 +500,40 @@ pub struct BookmarkForm {
     pub title: String,
     pub description: Option<String>,
     pub tags: Option<String>,
+    /// Language of the page, as detected when its title was fetched
+    pub language: Option<String>,
+    /// Last day (`YYYY-MM-DD`) to keep a temporary bookmark; empty to keep it forever
+    pub keep_until: Option<String>,
+    /// Author, site name and publication date (UNIX timestamp) from the page's metadata, as found
+    /// when its title was fetched
+    pub author: Option<String>,
+    pub site_name: Option<String>,
+    pub published_at: Option<String>,
+    /// Save it to read later
+    #[serde(default)]
+    pub unread: bool,
+    /// Leave it out of everything shown to others
+    #[serde(default)]
+    pub private: bool,
+    /// The page's preview image, as found when its title was fetched, to make a thumbnail of
+    pub image_url: Option<String>,
+    /// Why the page's title couldn't be fetched, if it couldn't, so it's tried again later
+    pub fetch_error: Option<String>,
+}
+
+/// Turns the form's "keep until" date into when the bookmark expires: the end of that day (UTC).
+fn expiry_from_keep_until(keep_until: Option<&str>, today: NaiveDate) -> Result<Option<i64>, &'static str> {
+    let Some(keep_until) = keep_until.map(str::trim).filter(|date| !date.is_empty()) else {
+        return Ok(None);
+    };
+    let date = NaiveDate::parse_from_str(keep_until, "%Y-%m-%d").map_err(|_| "Invalid keep-until date")?;
+    if date < today {
+        return Err("The keep-until date is in the past");
+    }
+    Ok(date
+        .succ_opt()
+        .and_then(|end| end.and_hms_opt(0, 0, 0))
+        .map(|end| end.and_utc().timestamp()))
 }
 
 #[derive(Deserialize)]
@@ -132,14 +546,31 @@ pub struct FetchTitleResponse {
     pub title: Option<String>,
     pub description: Option<String>,
     pub corrected_url: String,
+    pub language: Option<String>,
+    pub author: Option<String>,
+    pub site_name: Option<String>,
+    pub published_at: Option<i64>,
+    pub image_url: Option<String>,
+    /// Why the page couldn't be fetched, if it couldn't, e.g. "Timed out"
+    pub error: Option<String>,
 }
 
 /// Handler for displaying the bookmark creation form
-pub async fn bookmark_new_handler() -> impl IntoResponse {
+pub async fn bookmark_new_handler(State(state): ApiState, Extension(user): Extension<User>) -> impl IntoResponse {
+    let fetch_enabled = fetch::is_fetch_allowed(&state.fetch_config, &state.db, user.user_id)
+        .await
+        .unwrap_or_else(|err| {
+            error!(?err, "🚨 Could not load fetch setting, not fetching titles.");
+            false
+        });
+
     HtmlTemplate(BookmarkNewTemplate {
         title: "Add Bookmark",
         auth_state: AuthState::Authenticated,
         is_error: false,
+        fetch_enabled,
+        prefill: NewBookmarkPrefill::default(),
+        popup: false,
     })
 }
 
@@ -147,37 +578,72 @@ pub async fn bookmark_new_handler() -> impl IntoResponse {
 pub async fn bookmark_create_handler(
     State(state): ApiState,
     Extension(user): Extension<User>,
+    hx: HxRequest,
     Form(form): Form<BookmarkForm>,
 ) -> impl IntoResponse {
-    // Parse tags from comma-separated string
-    let tag_names: Vec<String> = form
-        .tags
-        .as_ref()
-        .map(|tags| {
-            tags.split(',')
-                .map(|tag| tag.trim().to_lowercase())
-                .filter(|tag| !tag.is_empty())
-                .collect()
-        })
-        .unwrap_or_default();
+    let tag_names = parse_tags(form.tags.as_deref().unwrap_or_default());
+
+    let expires_at = match expiry_from_keep_until(form.keep_until.as_deref(), Utc::now().date_naive()) {
+        Ok(expires_at) => expires_at,
+        Err(message) => return (StatusCode::BAD_REQUEST, message).into_response(),
+    };
+
+    // Strip tracking parameters, keeping the URL as entered for reference
+    let bookmark = UrlCleaner::for_user(&state.db, user.user_id).await.apply(NewBookmark {
+        url: form.url.trim().to_string(),
+        title: form.title,
+        description: form.description,
+        tag_names,
+        language: form.language.as_deref().and_then(normalize_language),
+        expires_at,
+        author: form.author.filter(|author| !author.trim().is_empty()),
+        site_name: form.site_name.filter(|site_name| !site_name.trim().is_empty()),
+        published_at: form.published_at.and_then(|published_at| published_at.parse().ok()),
+        is_unread: form.unread,
+        visibility: Visibility::private_if(form.private),
+        ..NewBookmark::default()
+    });
+
+    let bookmark = match Blocklist::for_user(&state.db, user.user_id).await {
+        Ok(blocklist) => match blocklist.apply(bookmark) {
+            Ok(bookmark) => bookmark,
+            Err(message) => return (StatusCode::BAD_REQUEST, message).into_response(),
+        },
+        Err(err) => {
+            error!(?err, "🚨 Could not load domain blocklists.");
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to create bookmark").into_response();
+        }
+    };
 
     // Create the bookmark in the database
-    match bookmarks::create_bookmark(
-        &state.pool,
-        user.user_id,
-        &form.url,
-        &form.title,
-        form.description.as_deref(),
-        &tag_names,
-    )
-    .await
-    {
-        Ok(_bookmark_id) => HtmlTemplate(HomeTemplate {
-            title: "Home",
-            auth_state: AuthState::Authenticated,
-            is_error: false,
-        })
-        .into_response(),
+    match bookmarks::create_bookmark(&state.db, user.user_id, &bookmark, &BookmarkSource::Web.into()).await {
+        Ok(bookmark_id) => {
+            if let Some(error) = form.fetch_error.filter(|error| !error.trim().is_empty()) {
+                record_fetch_failure(&state, bookmark_id, error).await;
+            }
+            if let Some(image_url) = form.image_url.filter(|image_url| !image_url.trim().is_empty()) {
+                tokio::spawn(thumbnail::save_thumbnail(state.clone(), user.user_id, bookmark_id, image_url));
+            }
+            tokio::spawn(page_text::save_page_text(
+                state.clone(),
+                user.user_id,
+                bookmark_id,
+                bookmark.url.clone(),
+            ));
+            tokio::spawn(wayback::submit_new_bookmark(state.clone(), user.user_id, bookmark.url.clone()));
+            tokio::spawn(snapshot::save_new_snapshot(state.clone(), user.user_id, bookmark_id, bookmark.url));
+            page_or_redirect(
+                hx,
+                "/",
+                HomeTemplate {
+                    title: "Home",
+                    auth_state: AuthState::Authenticated,
+                    is_error: false,
+                    collections: collections::get_user_collections(&state.db, user.user_id).await.unwrap_or_default(),
+                    ..Default::default()
+                },
+            )
+        }
         Err(err) => {
             error!("🚨 Failed to create bookmark: {}", err);
             (StatusCode::INTERNAL_SERVER_ERROR, "Failed to create bookmark").into_response()
@@ -185,28 +651,371 @@ pub async fn bookmark_create_handler(
     }
 }
 
+/// Parses comma-separated tags, lowercased and without repeats.
+pub(crate) fn parse_tags(tags: &str) -> Vec<String> {
+    let mut tag_names: Vec<String> = Vec::new();
+    for tag in tags.split(',').map(|tag| tag.trim().to_lowercase()) {
+        if !tag.is_empty() && !tag_names.contains(&tag) {
+            tag_names.push(tag);
+        }
+    }
+    tag_names
+}
+
+/// A single bookmark as shown in the list, re-rendered after an edit. Opened directly, it's the
+/// bookmark's own page.
+#[derive(Template)]
+#[template(path = "components/bookmark_item.html")]
+pub struct BookmarkItemTemplate {
+    pub bookmark: BookmarkItem,
+    pub list: ListDisplay,
+    pub translate_to: Option<String>,
+    pub archiving: bool,
+    /// Marks nothing, as a single bookmark is shown again after an edit rather than for a search
+    pub highlight: Highlighter,
+}
+
+/// Form for editing a bookmark in place of its row in the list.
+#[derive(Template)]
+#[template(path = "components/bookmark_edit.html")]
+pub struct BookmarkEditTemplate {
+    pub bookmark: BookmarkItem,
+    /// The bookmark's tags, comma separated
+    pub tags: String,
+}
+
+#[derive(Deserialize)]
+pub struct BookmarkEditForm {
+    pub url: String,
+    pub title: String,
+    pub description: Option<String>,
+    pub tags: Option<String>,
+    /// Leave it out of everything shown to others
+    #[serde(default)]
+    pub private: bool,
+}
+
+pub(crate) async fn find_bookmark(db: &Db, user_id: Uuid, bookmark_id: BookmarkId) -> AppResult<BookmarkItem> {
+    bookmarks::get_bookmark(db, user_id, bookmark_id)
+        .await?
+        .ok_or_else(|| AppError::with_status(anyhow!("Bookmark {bookmark_id} not found for user"), StatusCode::NOT_FOUND))
+}
+
+pub(crate) async fn item_template(state: &AppState, user_id: Uuid, mut bookmark: BookmarkItem) -> AppResult<BookmarkItemTemplate> {
+    let user_settings = settings::get_user_settings(&state.db, user_id).await?;
+    bookmark.color_tags(&tags::get_tag_colors(&state.db, user_id).await?);
+    bookmark.archived_copy = archived_copies(state, user_id).await.remove(&bookmark.id);
+    bookmark.fetch_failure = fetch_failures::get_fetch_failure(&state.db, bookmark.id).await?;
+    Ok(BookmarkItemTemplate {
+        bookmark,
+        list: user_settings.list,
+        translate_to: user_settings.preferred_language.filter(|_| state.translator.is_some()),
+        archiving: snapshot::is_archiving_allowed(state, user_id).await?,
+        highlight: Highlighter::default(),
+    })
+}
+
+/// Handler for a single bookmark, e.g. to show it again when editing is cancelled
+pub async fn bookmark_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    hx: HxRequest,
+    Path(bookmark_id): Path<BookmarkId>,
+) -> AppResult<Response> {
+    let bookmark = find_bookmark(&state.db, user.user_id, bookmark_id).await?;
+    let title = bookmark.title.clone();
+    Ok(render_fragment(hx, &title, item_template(&state, user.user_id, bookmark).await?))
+}
+
+/// Records why a new bookmark's page couldn't be fetched on the Add Bookmark page, so it's tried
+/// again later, see [`crate::fetch_retry`]. Failing to is only logged, as the bookmark is saved.
+async fn record_fetch_failure(state: &AppState, bookmark_id: BookmarkId, error: String) {
+    // Comes from the form, so keep it to the length of the reasons actually given
+    let error: String = error.trim().chars().take(FETCH_ERROR_LENGTH).collect();
+    let failure = fetch_retry::failure_after(1, error, Utc::now().timestamp());
+    if let Err(err) = fetch_failures::set_fetch_failure(&state.db, bookmark_id, &failure).await {
+        error!(?err, %bookmark_id, "🚨 Could not record a failed fetch, it won't be tried again.");
+    }
+}
+
+/// API handler fetching a bookmark's page again now, filling in the title and whatever else the
+/// bookmark is missing, see [`crate::fetch_retry`]
+///
+/// A try on request doesn't count towards the ones made before giving up.
+pub async fn retry_fetch_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Path(bookmark_id): Path<BookmarkId>,
+) -> AppResult<impl IntoResponse> {
+    if !fetch::is_fetch_allowed(&state.fetch_config, &state.db, user.user_id).await? {
+        return Err(
+            AppError::with_status(anyhow!("Fetching is turned off for {}", user.username), StatusCode::CONFLICT)
+                .user_message("Fetching pages is turned off, on the Settings page or for this instance."),
+        );
+    }
+    let bookmark = find_bookmark(&state.db, user.user_id, bookmark_id).await?;
+
+    match fetch_retry::fetch_details(&state.http_client, &state.fetch_config, &bookmark.url).await {
+        Ok(details) => {
+            fetch_failures::complete_fetch(&state.db, bookmark_id, &details).await?;
+            info!(user = user.username, %bookmark_id, "🌐 Fetched a page again on request.");
+        }
+        Err(error) => {
+            warn!(%bookmark_id, url = bookmark.url, error, "🌐 Could not fetch a page again on request.");
+            let failure = match fetch_failures::get_fetch_failure(&state.db, bookmark_id).await? {
+                Some(failure) => FetchFailure {
+                    error: error.clone(),
+                    ..failure
+                },
+                None => fetch_retry::failure_after(1, error.clone(), Utc::now().timestamp()),
+            };
+            fetch_failures::set_fetch_failure(&state.db, bookmark_id, &failure).await?;
+            return Err(
+                AppError::with_status(anyhow!("Could not fetch {}: {error}", bookmark.url), StatusCode::BAD_GATEWAY)
+                    .user_message(format!("The page could not be fetched: {error}.")),
+            );
+        }
+    }
+
+    // Shown again with what was fetched
+    let bookmark = find_bookmark(&state.db, user.user_id, bookmark_id).await?;
+    Ok(HtmlTemplate(item_template(&state, user.user_id, bookmark).await?))
+}
+
+/// Handler for a bookmark's thumbnail, see [`crate::thumbnail`]
+pub async fn bookmark_thumbnail_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Path(bookmark_id): Path<BookmarkId>,
+) -> AppResult<impl IntoResponse> {
+    let image = thumbnails::get_thumbnail(&state.db, user.user_id, bookmark_id)
+        .await?
+        .ok_or_else(|| AppError::with_status(anyhow!("No thumbnail for bookmark {bookmark_id}"), StatusCode::NOT_FOUND))?;
+    // Thumbnails are never changed once saved
+    Ok(([(CONTENT_TYPE, "image/jpeg"), (CACHE_CONTROL, "private, max-age=604800")], image))
+}
+
+/// Handler for the form editing a bookmark
+pub async fn bookmark_edit_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Path(bookmark_id): Path<BookmarkId>,
+) -> AppResult<impl IntoResponse> {
+    let bookmark = find_bookmark(&state.db, user.user_id, bookmark_id).await?;
+    let tags = bookmark.tags.iter().map(|tag| tag.name.as_str()).collect::<Vec<_>>().join(", ");
+    Ok(HtmlTemplate(BookmarkEditTemplate { bookmark, tags }))
+}
+
+/// API handler for saving changes to a bookmark
+///
+/// The new URL is cleaned and checked against the blocklists, just like a new bookmark's.
+pub async fn bookmark_update_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Path(bookmark_id): Path<BookmarkId>,
+    Form(form): Form<BookmarkEditForm>,
+) -> AppResult<impl IntoResponse> {
+    let url = form.url.trim();
+    if !reqwest::Url::parse(url).is_ok_and(|url| matches!(url.scheme(), "http" | "https")) {
+        return Err(AppError::bad_request("Enter a web address starting with http:// or https://."));
+    }
+    let title = form.title.trim();
+    if title.is_empty() {
+        return Err(AppError::bad_request("Enter a title."));
+    }
+
+    let bookmark = UrlCleaner::for_user(&state.db, user.user_id).await.apply(NewBookmark {
+        url: url.to_string(),
+        title: title.to_string(),
+        description: form.description.map(|d| d.trim().to_string()).filter(|d| !d.is_empty()),
+        tag_names: parse_tags(form.tags.as_deref().unwrap_or_default()),
+        ..NewBookmark::default()
+    });
+    let bookmark = Blocklist::for_user(&state.db, user.user_id)
+        .await?
+        .apply(bookmark)
+        .map_err(AppError::bad_request)?;
+
+    if !bookmarks::update_bookmark(&state.db, user.user_id, bookmark_id, &bookmark).await? {
+        return Err(AppError::with_status(
+            anyhow!("Bookmark {bookmark_id} not found for user"),
+            StatusCode::NOT_FOUND,
+        ));
+    }
+    bookmarks::set_visibility(&state.db, user.user_id, bookmark_id, Visibility::private_if(form.private)).await?;
+    info!(user = user.username, %bookmark_id, "✏️ Updated bookmark.");
+
+    let bookmark = find_bookmark(&state.db, user.user_id, bookmark_id).await?;
+    Ok(HtmlTemplate(item_template(&state, user.user_id, bookmark).await?))
+}
+
+/// API handler for deleting a bookmark, moving it to the trash
+///
+/// Responds with nothing, so the bookmark disappears from the list it was deleted from.
+pub async fn bookmark_delete_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Path(bookmark_id): Path<BookmarkId>,
+) -> AppResult<impl IntoResponse> {
+    if !bookmarks::trash_bookmark(&state.db, user.user_id, bookmark_id).await? {
+        return Err(AppError::with_status(
+            anyhow!("Bookmark {bookmark_id} not found for user"),
+            StatusCode::NOT_FOUND,
+        ));
+    }
+    info!(user = user.username, %bookmark_id, "🗑️ Moved bookmark to the trash.");
+
+    Ok(StatusCode::OK)
+}
+
+/// API handler for moving a bookmark out of the main list into the archive
+///
+/// Responds with nothing, so the bookmark disappears from the list it was archived from.
+pub async fn bookmark_archive_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Path(bookmark_id): Path<BookmarkId>,
+) -> AppResult<impl IntoResponse> {
+    if !bookmarks::set_archived(&state.db, user.user_id, bookmark_id, true).await? {
+        return Err(AppError::with_status(
+            anyhow!("Bookmark {bookmark_id} not found for user"),
+            StatusCode::NOT_FOUND,
+        ));
+    }
+    info!(user = user.username, %bookmark_id, "📦 Archived bookmark.");
+
+    Ok(StatusCode::OK)
+}
+
+/// API handler for moving a bookmark from the archive back to the main list
+///
+/// Responds with nothing, so the bookmark disappears from the archive.
+pub async fn bookmark_unarchive_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Path(bookmark_id): Path<BookmarkId>,
+) -> AppResult<impl IntoResponse> {
+    if !bookmarks::set_archived(&state.db, user.user_id, bookmark_id, false).await? {
+        return Err(AppError::with_status(
+            anyhow!("Bookmark {bookmark_id} not found for user"),
+            StatusCode::NOT_FOUND,
+        ));
+    }
+    info!(user = user.username, %bookmark_id, "📤 Unarchived bookmark.");
+
+    Ok(StatusCode::OK)
+}
+
+/// API handler for marking a bookmark to read later
+///
+/// Responds with the bookmark as it's now shown in the list.
+pub async fn bookmark_unread_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Path(bookmark_id): Path<BookmarkId>,
+) -> AppResult<impl IntoResponse> {
+    set_unread(&state, &user, bookmark_id, true).await
+}
+
+/// API handler for marking a bookmark as read, taking it off the reading list
+///
+/// Responds with the bookmark as it's now shown in the list.
+pub async fn bookmark_read_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Path(bookmark_id): Path<BookmarkId>,
+) -> AppResult<impl IntoResponse> {
+    set_unread(&state, &user, bookmark_id, false).await
+}
+
+/// Marks a bookmark to read later or as read, rendering it again to show the change.
+async fn set_unread(
+    state: &AppState,
+    user: &User,
+    bookmark_id: BookmarkId,
+    is_unread: bool,
+) -> AppResult<HtmlTemplate<BookmarkItemTemplate>> {
+    if !bookmarks::set_unread(&state.db, user.user_id, bookmark_id, is_unread).await? {
+        return Err(AppError::with_status(
+            anyhow!("Bookmark {bookmark_id} not found for user"),
+            StatusCode::NOT_FOUND,
+        ));
+    }
+    if is_unread {
+        info!(user = user.username, %bookmark_id, "🔖 Marked bookmark to read later.");
+    } else {
+        info!(user = user.username, %bookmark_id, "✅ Marked bookmark as read.");
+    }
+
+    let bookmark = find_bookmark(&state.db, user.user_id, bookmark_id).await?;
+    Ok(HtmlTemplate(item_template(state, user.user_id, bookmark).await?))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SearchExplainQuery {
+    #[serde(default)]
+    pub q: String,
+}
+
+/// API handler explaining how a search query is interpreted (terms, phrases, tags, logic, warnings)
+/// with the user's search defaults
+pub async fn search_explain_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    TolerantQuery(params): TolerantQuery<SearchExplainQuery>,
+) -> AppResult<impl IntoResponse> {
+    let user_settings = settings::get_user_settings(&state.db, user.user_id).await?;
+    Ok(Json(SearchQuery::explain_with(&params.q, user_settings.search)))
+}
+
 /// Handler for fetching page title & description from URL
-pub async fn scrape_site_handler(State(state): ApiState, Form(request): Form<FetchTitleRequest>) -> impl IntoResponse {
-    if request.url.len() < 3 {
+pub async fn scrape_site_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Form(request): Form<FetchTitleRequest>,
+) -> impl IntoResponse {
+    let fetch_allowed = fetch::is_fetch_allowed(&state.fetch_config, &state.db, user.user_id)
+        .await
+        .unwrap_or_else(|err| {
+            error!(?err, "🚨 Could not load fetch setting, not fetching title.");
+            false
+        });
+
+    if !fetch_allowed || request.url.len() < 3 {
         return Json(FetchTitleResponse {
             title: None,
             description: None,
             corrected_url: request.url,
+            language: None,
+            author: None,
+            site_name: None,
+            published_at: None,
+            image_url: None,
+            error: None,
         });
     }
 
-    match scrape_title_description(&state.http_client, &request.url).await {
+    match fetch::scrape_title_description(&state.http_client, &state.fetch_config, &request.url).await {
         Ok(result) => {
             let LinkScrapeResult {
                 description,
                 title,
                 final_url,
+                language,
+                metadata,
+                image_url,
             } = result;
-            debug!(final_url, title, description, "Scraped input site.");
+            debug!(final_url, title, description, language, ?metadata, image_url, "Scraped input site.");
             Json(FetchTitleResponse {
                 title: Some(title),
                 description,
                 corrected_url: final_url,
+                language,
+                author: metadata.author,
+                site_name: metadata.site_name,
+                published_at: metadata.published_at,
+                image_url,
+                error: None,
             })
         }
         Err(err) => {
@@ -216,97 +1025,119 @@ pub async fn scrape_site_handler(State(state): ApiState, Form(request): Form<Fet
                 title: None,
                 description: None,
                 corrected_url: request.url,
+                language: None,
+                author: None,
+                site_name: None,
+                published_at: None,
+                image_url: None,
+                error: Some(fetch::failure_reason(&err)),
             })
         }
     }
 }
 
-#[derive(Debug)]
-struct LinkScrapeResult {
-    description: Option<String>,
-    title: String,
-    final_url: String,
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-/// Fetches the title from a webpage
-///
-/// # Errors
-///
-/// Returns an error if the HTTP request fails or HTML parsing fails.
-async fn scrape_title_description(client: &Client, url: &str) -> anyhow::Result<LinkScrapeResult> {
-    let default_title = url.to_string();
-    let mut url = match url {
-        url if url.starts_with("http://") || url.starts_with("https://") => url.to_string(),
-        no_proto => format!("http://{no_proto}"),
-    };
+    #[test]
+    fn test_parse_tags() {
+        assert_eq!(parse_tags(" Rust, web ,,rust, "), ["rust", "web"]);
+        assert!(parse_tags("").is_empty());
+    }
 
-    // Fetch the page
-    let mut response = client.get(&url).send().await?;
-
-    // Check if response is successful
-    if !response.status().is_success() {
-        // If not, try https instead
-        debug!("Fetch failed with https, trying http");
-        url = url.replace("https://", "http://");
-        response = client.get(&url).send().await?;
-        if !response.status().is_success() {
-            return Err(anyhow::anyhow!("HTTP error: {}", response.status()));
+    #[test]
+    fn test_search_for_uses_defaults_without_filters() {
+        let path = std::env::temp_dir().join(format!("pagepouch-test-{}.db", Uuid::new_v4()));
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let db = crate::db::connect(&format!("sqlite://{}?mode=rwc", path.display()), None)
+                .await
+                .unwrap();
+            for include_archived in [false, true] {
+                let defaults = SearchDefaults {
+                    include_archived,
+                    ..SearchDefaults::default()
+                };
+                let query = search_for(&db, Uuid::new_v4(), &BookmarkQuery::default(), defaults).await.unwrap();
+                assert!(query.is_empty());
+                assert_eq!(query.include_archived, include_archived);
+            }
+        });
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{suffix}", path.display()));
         }
     }
 
-    let html = response.text().await?;
-
-    // Parse HTML and extract title
-    let dom = tl::parse(&html, tl::ParserOptions::default())?;
-    let parser = dom.parser();
-    let title = dom
-        .query_selector("title")
-        .and_then(|mut iter| iter.next())
-        .and_then(|node| node.get(parser))
-        .map(|node| decode_html_entities(&node.inner_text(parser)).trim().to_string())
-        .filter(|title| !title.is_empty())
-        .unwrap_or(default_title);
-
-    let description = get_meta_description(&dom);
-
-    Ok(LinkScrapeResult {
-        description,
-        title,
-        final_url: url,
-    })
-}
+    #[test]
+    fn test_search_rule() {
+        let params = BookmarkQuery {
+            q: Some(" site:github.com ".to_string()),
+            tags: Some(vec!["rust".to_string(), "web".to_string()]),
+            ..Default::default()
+        };
+        assert_eq!(params.search_rule().as_deref(), Some("site:github.com #rust #web"));
 
-fn get_meta_description(dom: &VDom<'_>) -> Option<String> {
-    let parser = dom.parser();
+        let tags_only = BookmarkQuery {
+            tags: Some(vec!["rust".to_string()]),
+            ..Default::default()
+        };
+        assert_eq!(tags_only.search_rule().as_deref(), Some("#rust"));
 
-    // Try Open Graph description first
-    if let Some(og_desc) = dom
-        .query_selector("meta[property=\"og:description\"]")
-        .and_then(|mut iter| iter.next())
-        .and_then(|node| node.get(parser))
-        .and_then(|node| node.as_tag())
-        .and_then(|tag| tag.attributes().get("content")?)
-        .map(|content| content.as_utf8_str().to_string())
-    {
-        Some(og_desc)
-    } else {
-        // Fall back to standard meta description
-        dom.query_selector("meta[name=\"description\"]")
-            .and_then(|mut iter| iter.next())
-            .and_then(|node| node.get(parser))
-            .and_then(|node| node.as_tag())
-            .and_then(|tag| tag.attributes().get("content")?)
-            .map(|content| content.as_utf8_str().to_string())
+        assert_eq!(BookmarkQuery::default().search_rule(), None);
+        let archived = BookmarkQuery { archived: true, ..params };
+        assert_eq!(archived.search_rule(), None);
+        let unread = BookmarkQuery {
+            archived: false,
+            unread: true,
+            ..archived
+        };
+        assert_eq!(unread.search_rule(), None);
     }
-    .map(|text| decode_html_entities(&text))
-}
 
-/// Decodes common HTML entities in text.
-fn decode_html_entities(text: &str) -> String {
-    text.replace("&amp;", "&")
-        .replace("&lt;", "<")
-        .replace("&gt;", ">")
-        .replace("&quot;", "\"")
-        .replace("&#39;", "'")
-        .replace("&apos;", "'")
+    #[test]
+    fn test_scrolling() {
+        assert!(BookmarkQuery::default().is_scrollable(SortOrder::Newest));
+        assert!(BookmarkQuery::default().is_scrollable(SortOrder::Oldest));
+        assert!(!BookmarkQuery::default().is_scrollable(SortOrder::Title));
+        let searched = BookmarkQuery {
+            q: Some("rust".to_string()),
+            ..Default::default()
+        };
+        assert!(!searched.is_scrollable(SortOrder::Newest));
+        let archived = BookmarkQuery {
+            archived: true,
+            ..Default::default()
+        };
+        assert!(!archived.is_scrollable(SortOrder::Newest));
+
+        let cursor = BookmarkCursor {
+            created_at: 1_700_000_000,
+            id: BookmarkId(Uuid::nil()),
+        };
+        assert_eq!(
+            BookmarkQuery::default().scroll_url(cursor),
+            "/api/bookmarks?after=1700000000.AAAAAAAAAAAAAAAAAAAAAA"
+        );
+        let oldest = BookmarkQuery {
+            sort: Some(SortOrder::Oldest),
+            after: Some("ignored".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            oldest.scroll_url(cursor),
+            "/api/bookmarks?sort=oldest&after=1700000000.AAAAAAAAAAAAAAAAAAAAAA"
+        );
+    }
+
+    #[test]
+    fn test_expiry_from_keep_until() {
+        let today = NaiveDate::from_ymd_opt(2025, 3, 1).unwrap();
+        assert_eq!(expiry_from_keep_until(None, today), Ok(None));
+        assert_eq!(expiry_from_keep_until(Some(" "), today), Ok(None));
+        // Kept through the whole day, until 2025-03-02T00:00:00Z
+        assert_eq!(expiry_from_keep_until(Some("2025-03-01"), today), Ok(Some(1_740_873_600)));
+        assert!(expiry_from_keep_until(Some("2025-02-28"), today).is_err());
+        assert!(expiry_from_keep_until(Some("next week"), today).is_err());
+    }
 }
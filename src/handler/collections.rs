@@ -0,0 +1,232 @@
+//! Smart collection handlers and templates.
+//!
+//! Collections are managed on the settings page, and the search shown in the bookmark list can be
+//! saved as one, which adds it to the sidebar. A collection's bookmarks can be dragged into an order
+//! of the user's own, which it's shown in unless another is picked.
+
+use std::collections::HashSet;
+
+use anyhow::anyhow;
+use askama::Template;
+use axum::{
+    Extension, Form,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use serde::Deserialize;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{
+    ApiState, AppState,
+    db::{
+        bookmarks::{self, BookmarkId},
+        collections::{self, SmartCollection},
+        settings,
+        users::User,
+    },
+    error::{AppError, AppResult},
+    handler::{
+        HtmlTemplate,
+        bookmarks::{BookmarkQuery, search_for},
+    },
+    search::SearchQuery,
+};
+
+/// Longest allowed collection name.
+const MAX_NAME_LENGTH: usize = 100;
+
+/// The list of collections on the settings page, re-rendered after each change.
+#[derive(Template)]
+#[template(path = "components/collection_settings.html")]
+pub struct CollectionSettingsTemplate {
+    pub collections: Vec<SmartCollection>,
+}
+
+/// The collections in the sidebar, re-rendered after a search is saved as one.
+#[derive(Template)]
+#[template(path = "components/collection_list.html")]
+pub struct CollectionListTemplate {
+    pub collections: Vec<SmartCollection>,
+}
+
+#[derive(Deserialize)]
+pub struct CollectionForm {
+    pub name: String,
+    /// Rule in search syntax
+    pub query: String,
+}
+
+/// API handler for creating a smart collection
+pub async fn create_collection_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Form(form): Form<CollectionForm>,
+) -> AppResult<impl IntoResponse> {
+    create_collection(&state, &user, &form).await?;
+
+    let collections = collections::get_user_collections(&state.db, user.user_id).await?;
+    Ok(HtmlTemplate(CollectionSettingsTemplate { collections }))
+}
+
+/// API handler for saving the search shown in the bookmark list as a smart collection
+pub async fn save_search_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Form(form): Form<CollectionForm>,
+) -> AppResult<impl IntoResponse> {
+    create_collection(&state, &user, &form).await?;
+
+    let collections = collections::get_user_collections(&state.db, user.user_id).await?;
+    Ok(HtmlTemplate(CollectionListTemplate { collections }))
+}
+
+/// Checks a collection's name and rule, returning them trimmed.
+fn validate_collection(form: &CollectionForm) -> AppResult<(&str, &str)> {
+    let name = form.name.trim();
+    let query = form.query.trim();
+    if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+        return Err(AppError::bad_request(format!(
+            "Collection names must be between 1 and {MAX_NAME_LENGTH} characters."
+        )));
+    }
+    if SearchQuery::parse_complete(query).is_empty() {
+        return Err(AppError::bad_request(
+            "The rule doesn't filter anything. Try something like: site:github.com #rust",
+        ));
+    }
+    Ok((name, query))
+}
+
+async fn create_collection(state: &AppState, user: &User, form: &CollectionForm) -> AppResult<()> {
+    let (name, query) = validate_collection(form)?;
+    if collections::create_collection(&state.db, user.user_id, name, query)
+        .await?
+        .is_none()
+    {
+        return Err(AppError::bad_request(format!("You already have a collection named '{name}'.")));
+    }
+    info!(user = user.username, name, query, "🗂️ Created smart collection.");
+    Ok(())
+}
+
+/// API handler for renaming a smart collection or changing its rule
+pub async fn update_collection_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Path(collection_id): Path<Uuid>,
+    Form(form): Form<CollectionForm>,
+) -> AppResult<impl IntoResponse> {
+    let (name, query) = validate_collection(&form)?;
+    if collections::get_collection(&state.db, user.user_id, collection_id).await?.is_none() {
+        return Err(AppError::with_status(
+            anyhow!("Collection {collection_id} not found for user"),
+            StatusCode::NOT_FOUND,
+        ));
+    }
+    if !collections::update_collection(&state.db, user.user_id, collection_id, name, query).await? {
+        return Err(AppError::bad_request(format!("You already have a collection named '{name}'.")));
+    }
+    info!(user = user.username, %collection_id, name, query, "🗂️ Updated smart collection.");
+
+    let collections = collections::get_user_collections(&state.db, user.user_id).await?;
+    Ok(HtmlTemplate(CollectionSettingsTemplate { collections }))
+}
+
+/// API handler for deleting a smart collection
+pub async fn delete_collection_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Path(collection_id): Path<Uuid>,
+) -> AppResult<impl IntoResponse> {
+    if !collections::delete_collection(&state.db, user.user_id, collection_id).await? {
+        return Err(AppError::with_status(
+            anyhow!("Collection {collection_id} not found for user"),
+            StatusCode::NOT_FOUND,
+        ));
+    }
+    info!(user = user.username, %collection_id, "🗂️ Deleted smart collection.");
+
+    let collections = collections::get_user_collections(&state.db, user.user_id).await?;
+    Ok(HtmlTemplate(CollectionSettingsTemplate { collections }))
+}
+
+#[derive(Deserialize)]
+pub struct CollectionOrderForm {
+    /// Comma-separated IDs of the bookmarks shown, in their new order
+    pub bookmark_ids: String,
+}
+
+/// API handler for putting the bookmarks shown from a smart collection in a new order
+///
+/// Only the page shown is sent, so the bookmarks on it are reordered among the places they take up
+/// in the whole collection, see [`reorder`].
+pub async fn reorder_collection_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Path(collection_id): Path<Uuid>,
+    Form(form): Form<CollectionOrderForm>,
+) -> AppResult<StatusCode> {
+    let moved = form
+        .bookmark_ids
+        .split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(str::parse::<BookmarkId>)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| AppError::bad_request("Invalid bookmark ID"))?;
+
+    let defaults = settings::get_user_settings(&state.db, user.user_id).await?.search;
+    let params = BookmarkQuery {
+        collection: Some(collection_id),
+        ..BookmarkQuery::default()
+    };
+    let search_query = search_for(&state.db, user.user_id, &params, defaults).await?;
+    let current = bookmarks::search_collection_bookmark_ids(&state.db, user.user_id, collection_id, &search_query).await?;
+    collections::set_collection_order(&state.db, collection_id, &reorder(&current, &moved)).await?;
+    info!(user = user.username, %collection_id, moved = moved.len(), "🗂️ Reordered smart collection.");
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// The collection's order `current` with the bookmarks in `moved` put in that order among
+/// themselves, in the places they took up. Bookmarks that aren't in the collection are ignored.
+fn reorder(current: &[BookmarkId], moved: &[BookmarkId]) -> Vec<BookmarkId> {
+    let in_collection: HashSet<&BookmarkId> = current.iter().collect();
+    let mut seen = HashSet::new();
+    let moved: Vec<BookmarkId> = moved
+        .iter()
+        .filter(|id| in_collection.contains(id) && seen.insert(**id))
+        .copied()
+        .collect();
+    let mut placed = moved.into_iter();
+    current
+        .iter()
+        .map(|id| if seen.contains(id) { placed.next().unwrap_or(*id) } else { *id })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reorder() {
+        let ids: Vec<BookmarkId> = (1..=5).map(|n| BookmarkId(Uuid::from_u128(n))).collect();
+        let [first, second, third, fourth, fifth] = ids[..] else {
+            unreachable!()
+        };
+        // The moved bookmarks swap places, the rest stay put
+        assert_eq!(reorder(&ids, &[fourth, second]), vec![first, fourth, third, second, fifth]);
+        // A page from the middle of the collection
+        assert_eq!(reorder(&ids, &[fourth, third, second]), vec![first, fourth, third, second, fifth]);
+        // Bookmarks that aren't in it, and repeats, are ignored
+        let other = BookmarkId(Uuid::from_u128(9));
+        assert_eq!(
+            reorder(&ids, &[other, fifth, first, fifth]),
+            vec![fifth, second, third, fourth, first]
+        );
+        assert_eq!(reorder(&ids, &[]), ids);
+    }
+}
@@ -0,0 +1,110 @@
+//! Bookmark export handlers, streaming the export as it's read from the database.
+
+use std::sync::Arc;
+
+use axum::{
+    Extension,
+    body::Body,
+    extract::State,
+    http::header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+    response::IntoResponse,
+};
+use serde::Deserialize;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::{
+    ApiState, AppState,
+    bookmark_export::ExportFormat,
+    db::{bookmarks, users::User},
+    handler::extract::TolerantQuery,
+    search::SearchQuery,
+};
+
+/// Bookmarks read from the database at a time while exporting.
+const EXPORT_PAGE_SIZE: i64 = 500;
+/// Pages written ahead of what has been sent to the user.
+const EXPORT_BUFFER_PAGES: usize = 2;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ExportQuery {
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+/// Handler for downloading all bookmarks, archived ones included, as JSON, Netscape bookmark HTML
+/// or CSV
+pub async fn export_bookmarks_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    TolerantQuery(params): TolerantQuery<ExportQuery>,
+) -> impl IntoResponse {
+    let format = params.format;
+    let headers = [
+        (CONTENT_TYPE, format.content_type().to_string()),
+        (CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", format.file_name())),
+    ];
+    (headers, stream_export(state, user.user_id, user.username, format, None))
+}
+
+/// Streams an export of a user's bookmarks, or only those matching a search, a page of bookmarks
+/// at a time. If reading one fails partway through, the response is cut off, so the download fails
+/// rather than looking complete.
+pub(crate) fn stream_export(
+    state: Arc<AppState>,
+    user_id: Uuid,
+    username: String,
+    format: ExportFormat,
+    search: Option<SearchQuery>,
+) -> Body {
+    let (sender, receiver) = mpsc::channel::<anyhow::Result<String>>(EXPORT_BUFFER_PAGES);
+
+    tokio::spawn(async move {
+        if sender.send(Ok(format.header(chrono::Utc::now().timestamp()))).await.is_err() {
+            return;
+        }
+        let mut after = None;
+        let mut count = 0;
+        loop {
+            let page = match &search {
+                Some(search) => bookmarks::search_export_page(&state.db, user_id, search, after, EXPORT_PAGE_SIZE).await,
+                None => bookmarks::get_export_page(&state.db, user_id, after, EXPORT_PAGE_SIZE).await,
+            };
+            let (page, cursor) = match page {
+                Ok(page) => page,
+                Err(err) => {
+                    error!(?err, user = username, "🚨 Could not read bookmarks to export.");
+                    let _ = sender.send(Err(err)).await;
+                    return;
+                }
+            };
+            let chunk: String = page
+                .iter()
+                .enumerate()
+                .map(|(index, bookmark)| format.entry(bookmark, count + index == 0))
+                .collect();
+            count += page.len();
+            // Stop early if the user went away
+            if sender.send(Ok(chunk)).await.is_err() {
+                return;
+            }
+            if i64::try_from(page.len()).unwrap_or(0) < EXPORT_PAGE_SIZE {
+                break;
+            }
+            after = cursor;
+        }
+        if sender.send(Ok(format.footer())).await.is_ok() {
+            info!(
+                user = username,
+                bookmarks = count,
+                ?format,
+                searched = search.is_some(),
+                "📦 Exported bookmarks."
+            );
+        }
+    });
+
+    Body::from_stream(ReceiverStream::new(receiver))
+}
@@ -0,0 +1,161 @@
+//! Custom request extractors.
+
+use std::convert::Infallible;
+
+use axum::{
+    extract::{FromRequestParts, Request},
+    http::request::Parts,
+};
+use axum_extra::extract::{Query, QueryRejection};
+use reqwest::Url;
+use serde::de::DeserializeOwned;
+use tower_governor::key_extractor::{KeyExtractor as _, SmartIpKeyExtractor};
+
+/// Query string extractor that tolerates the different ways clients encode multi-value params.
+///
+/// Behaves like [`axum_extra::extract::Query`], but first normalizes bracketed keys so that
+/// `tags=a`, `tags[]=b` and `tags[0]=c` all end up in the same `tags` list. Without this, mixing
+/// styles (e.g. a hand-written link and a JS-built request) silently drops values.
+#[derive(Clone, Debug, Default)]
+pub struct TolerantQuery<T>(pub T);
+
+impl<T, S> FromRequestParts<S> for TolerantQuery<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = QueryRejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let query = normalize_query(parts.uri.query().unwrap_or_default());
+        let (mut normalized_parts, ()) = Request::builder()
+            .uri(format!("/?{query}"))
+            .body(())
+            .unwrap_or_default()
+            .into_parts();
+        let Query(value) = Query::<T>::from_request_parts(&mut normalized_parts, state).await?;
+        Ok(Self(value))
+    }
+}
+
+/// The client's IP address, found the way the rate limiter finds it: from the `X-Forwarded-For`,
+/// `X-Real-IP` or `Forwarded` headers of a reverse proxy, or else from the connection. `None` if
+/// there's neither, e.g. in tests.
+#[derive(Clone, Debug, Default)]
+pub struct ClientIp(pub Option<String>);
+
+impl<S> FromRequestParts<S> for ClientIp
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let req = Request::from_parts(parts.clone(), ());
+        Ok(Self(SmartIpKeyExtractor.extract(&req).ok().map(|ip| ip.to_string())))
+    }
+}
+
+/// Rewrites a query string so that every multi-value key uses the plain repeated `key=value` style.
+fn normalize_query(query: &str) -> String {
+    let Ok(original) = Url::parse(&format!("http://localhost/?{query}")) else {
+        return query.to_string();
+    };
+    let mut url = Url::parse("http://localhost/").expect("static URL is valid");
+    {
+        let mut pairs = url.query_pairs_mut();
+        for (key, value) in original.query_pairs() {
+            pairs.append_pair(strip_index(&key), &value);
+        }
+    }
+    url.query().unwrap_or_default().to_string()
+}
+
+/// Strips a trailing `[]` or `[<index>]` from a query key.
+fn strip_index(key: &str) -> &str {
+    key.strip_suffix(']')
+        .and_then(|rest| rest.rsplit_once('['))
+        .filter(|(name, index)| !name.is_empty() && index.chars().all(|c| c.is_ascii_digit()))
+        .map_or(key, |(name, _)| name)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, Deserialize)]
+    struct TagsQuery {
+        q: Option<String>,
+        #[serde(default)]
+        tags: Option<Vec<String>>,
+    }
+
+    fn extract(query: &str) -> TagsQuery {
+        let (mut parts, ()) = Request::builder()
+            .uri(format!("/api/bookmarks?{query}"))
+            .body(())
+            .unwrap()
+            .into_parts();
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        let TolerantQuery(value) = runtime
+            .block_on(TolerantQuery::<TagsQuery>::from_request_parts(&mut parts, &()))
+            .unwrap();
+        value
+    }
+
+    #[test]
+    fn test_strip_index() {
+        assert_eq!(strip_index("tags"), "tags");
+        assert_eq!(strip_index("tags[]"), "tags");
+        assert_eq!(strip_index("tags[3]"), "tags");
+        assert_eq!(strip_index("tags[name]"), "tags[name]");
+        assert_eq!(strip_index("[]"), "[]");
+    }
+
+    #[test]
+    fn test_repeated_style() {
+        let query = extract("tags=rust&tags=web-dev");
+        assert_eq!(query.tags.unwrap(), ["rust", "web-dev"]);
+    }
+
+    #[test]
+    fn test_bracketed_style() {
+        let query = extract("tags%5B%5D=rust&tags[]=web-dev&q=axum");
+        assert_eq!(query.tags.unwrap(), ["rust", "web-dev"]);
+        assert_eq!(query.q.as_deref(), Some("axum"));
+
+        let query = extract("tags[0]=rust&tags[1]=web-dev");
+        assert_eq!(query.tags.unwrap(), ["rust", "web-dev"]);
+    }
+
+    #[test]
+    fn test_mixed_styles() {
+        let query = extract("tags=rust&tags[]=web-dev&tags%5B%5D=c%2B%2B");
+        assert_eq!(query.tags.unwrap(), ["rust", "web-dev", "c++"]);
+    }
+
+    #[test]
+    fn test_client_ip() {
+        let (mut parts, ()) = Request::builder()
+            .header("x-forwarded-for", "203.0.113.7, 10.0.0.1")
+            .body(())
+            .unwrap()
+            .into_parts();
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        let ClientIp(ip) = runtime.block_on(ClientIp::from_request_parts(&mut parts, &())).unwrap();
+        assert_eq!(ip.as_deref(), Some("203.0.113.7"));
+
+        let (mut parts, ()) = Request::builder().body(()).unwrap().into_parts();
+        let ClientIp(ip) = runtime.block_on(ClientIp::from_request_parts(&mut parts, &())).unwrap();
+        assert_eq!(ip, None);
+    }
+
+    #[test]
+    fn test_single_and_missing_values() {
+        assert_eq!(extract("tags[]=rust").tags.unwrap(), ["rust"]);
+        assert!(extract("q=hello+world").tags.is_none());
+        assert_eq!(extract("q=hello+world").q.as_deref(), Some("hello world"));
+    }
+}
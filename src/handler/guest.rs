@@ -0,0 +1,187 @@
+//! Guest link handlers and templates: read-only access to a user's bookmarks for a limited time,
+//! for people without an account.
+
+use anyhow::anyhow;
+use askama::Template;
+use axum::{
+    Extension, Form,
+    extract::{Path, State},
+    http::{StatusCode, header},
+    response::IntoResponse,
+};
+use serde::Deserialize;
+use tracing::{debug, info};
+use uuid::Uuid;
+
+use crate::{
+    ApiState, AppState,
+    db::{
+        bookmarks::{BookmarkItem, get_expires_in},
+        collections,
+        guest_links::{self, GuestAccess, GuestLink, GuestToken},
+        users::User,
+    },
+    error::{AppError, AppResult},
+    handler::{
+        AuthState, HtmlTemplate,
+        bookmarks::{BookmarkQuery, find_public_bookmarks},
+        extract::TolerantQuery,
+    },
+};
+
+/// How long a new guest link works for, in hours, as offered on the settings page.
+pub const GUEST_LINK_DURATIONS: [(i64, &str); 4] = [(1, "1 hour"), (24, "1 day"), (24 * 7, "1 week"), (24 * 30, "30 days")];
+/// Bookmarks per page shown to guests.
+const GUEST_PAGE_SIZE: i64 = 20;
+
+/// A guest link on the settings page, with the address to hand out.
+#[derive(Clone, Debug)]
+pub struct SharedGuestLink {
+    pub link: GuestLink,
+    /// Path of the guest page, including the signed token
+    pub path: String,
+}
+
+/// The list of guest links on the settings page, re-rendered after each change.
+#[derive(Template)]
+#[template(path = "components/guest_link_settings.html")]
+pub struct GuestLinkSettingsTemplate {
+    pub guest_links: Vec<SharedGuestLink>,
+}
+
+#[derive(Deserialize)]
+pub struct GuestLinkForm {
+    /// Smart collection to limit the link to; empty for all bookmarks
+    #[serde(default)]
+    pub collection: String,
+    /// One of [`GUEST_LINK_DURATIONS`]
+    pub hours: String,
+}
+
+/// Lists a user's guest links that still work, signing a token for each.
+pub(crate) async fn shared_guest_links(state: &AppState, user_id: Uuid) -> AppResult<Vec<SharedGuestLink>> {
+    guest_links::get_guest_links(&state.db, user_id)
+        .await?
+        .into_iter()
+        .map(|link| {
+            let token = state.encryption.sign_token(GuestToken {
+                link_id: link.link_id,
+                expires_at: link.expires_at,
+            })?;
+            Ok(SharedGuestLink {
+                link,
+                path: format!("/guest/{token}"),
+            })
+        })
+        .collect()
+}
+
+/// API handler for creating a guest link
+pub async fn create_guest_link_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Form(form): Form<GuestLinkForm>,
+) -> AppResult<impl IntoResponse> {
+    let hours = form
+        .hours
+        .trim()
+        .parse::<i64>()
+        .ok()
+        .filter(|hours| GUEST_LINK_DURATIONS.iter().any(|(duration, _)| duration == hours))
+        .ok_or_else(|| AppError::bad_request("Choose how long the link should work for."))?;
+    let collection_id = match form.collection.trim() {
+        "" => None,
+        collection => {
+            let collection = match collection.parse() {
+                Ok(collection_id) => collections::get_collection(&state.db, user.user_id, collection_id).await?,
+                Err(_) => None,
+            };
+            Some(
+                collection
+                    .ok_or_else(|| AppError::bad_request("That smart collection doesn't exist."))?
+                    .collection_id,
+            )
+        }
+    };
+
+    let expires_at = chrono::Utc::now().timestamp() + hours * 3600;
+    let link_id = guest_links::create_guest_link(&state.db, user.user_id, collection_id, expires_at).await?;
+    info!(user = user.username, %link_id, hours, "🔗 Created guest link.");
+
+    Ok(HtmlTemplate(GuestLinkSettingsTemplate {
+        guest_links: shared_guest_links(&state, user.user_id).await?,
+    }))
+}
+
+/// API handler for revoking a guest link before it expires
+pub async fn delete_guest_link_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Path(link_id): Path<Uuid>,
+) -> AppResult<impl IntoResponse> {
+    if !guest_links::delete_guest_link(&state.db, user.user_id, link_id).await? {
+        return Err(AppError::with_status(
+            anyhow!("Guest link {link_id} not found"),
+            StatusCode::NOT_FOUND,
+        ));
+    }
+    info!(user = user.username, %link_id, "🔗 Revoked guest link.");
+
+    Ok(HtmlTemplate(GuestLinkSettingsTemplate {
+        guest_links: shared_guest_links(&state, user.user_id).await?,
+    }))
+}
+
+#[derive(Template)]
+#[template(path = "pages/guest.html")]
+pub struct GuestTemplate<'a> {
+    pub title: &'a str,
+    pub auth_state: AuthState,
+    pub is_error: bool,
+    pub access: GuestAccess,
+    /// e.g. "in 3 days"
+    pub expires_in: String,
+    pub bookmarks: Vec<BookmarkItem>,
+    pub prev_url: Option<String>,
+    pub next_url: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct GuestQuery {
+    pub page: Option<i64>,
+}
+
+/// Handler for the read-only page a guest link opens
+///
+/// Only reachable through [`crate::handler::middlewares::guest_middleware`], which checks the link.
+pub async fn guest_handler(
+    State(state): ApiState,
+    Extension(access): Extension<GuestAccess>,
+    Path(token): Path<String>,
+    TolerantQuery(params): TolerantQuery<GuestQuery>,
+) -> AppResult<impl IntoResponse> {
+    debug!(link_id = %access.link_id, username = access.username, "Guest link opened");
+
+    let query = BookmarkQuery {
+        collection: access.collection_id,
+        page: params.page,
+        ..BookmarkQuery::default()
+    };
+    let (bookmarks, page) = find_public_bookmarks(&state.db, access.user_id, &query, GUEST_PAGE_SIZE).await?;
+    let expires_in = get_expires_in(access.expires_at, chrono::Utc::now().timestamp());
+
+    // Keep the token out of the Referer header of the pages guests go on to open
+    Ok((
+        [(header::REFERRER_POLICY, "no-referrer")],
+        HtmlTemplate(GuestTemplate {
+            title: "Shared bookmarks",
+            auth_state: AuthState::Anonymous,
+            is_error: false,
+            access,
+            expires_in,
+            bookmarks: bookmarks.into(),
+            prev_url: page.prev().map(|number| format!("/guest/{token}?page={number}")),
+            next_url: page.next().map(|number| format!("/guest/{token}?page={number}")),
+        }),
+    ))
+}
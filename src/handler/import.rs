@@ -0,0 +1,418 @@
+//! Bookmark import handlers.
+
+use std::collections::BTreeMap;
+
+use anyhow::anyhow;
+use axum::{
+    Extension, Form, Json,
+    body::Bytes,
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::{
+    ApiState, AppState,
+    blocklist::Blocklist,
+    browser_import::{self, BrowserFormat, ImportedBookmark},
+    clean::UrlCleaner,
+    db::{
+        bookmarks::{self, BookmarkOrigin, BookmarkSource, NewBookmark},
+        collections,
+        imports::{self, ImportRecord},
+        restore_points,
+        users::User,
+    },
+    error::{AppError, AppResult},
+    handler::bookmarks::parse_tags,
+    hypothesis,
+};
+
+/// Largest bookmark file accepted for import.
+pub const MAX_IMPORT_SIZE: usize = 32 * 1024 * 1024;
+/// Longest collection name created for a folder, matching what can be entered on the settings page.
+const MAX_COLLECTION_NAME_LENGTH: usize = 100;
+
+#[derive(Deserialize)]
+pub struct BrowserImportQuery {
+    /// Name of the uploaded file, recorded as where the bookmarks came from
+    #[serde(default)]
+    pub filename: String,
+    /// Whether each folder becomes a tag on the bookmarks in it
+    #[serde(default = "default_true")]
+    pub folder_tags: bool,
+    /// Whether each folder also gets a smart collection; needs `folder_tags`
+    #[serde(default = "default_true")]
+    pub collections: bool,
+    /// Whether to leave out bookmarks that are already saved, or listed earlier in the file
+    #[serde(default)]
+    pub skip_duplicates: bool,
+    /// Comma-separated tags for every imported bookmark, e.g. "from-chrome"
+    #[serde(default)]
+    pub tags: String,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Summary of what an import added
+#[derive(Serialize)]
+pub struct BrowserImportSummary {
+    pub format: &'static str,
+    pub imported: usize,
+    /// Entries skipped because they aren't web pages (e.g. `javascript:` bookmarklets)
+    pub skipped: usize,
+    /// Entries skipped because their domain is blocked
+    pub blocked: usize,
+    /// Entries skipped because they're already saved
+    pub duplicates: usize,
+    pub collections_added: usize,
+}
+
+/// What importing a file would do, shown before importing it so the options can be adjusted
+#[derive(Serialize)]
+pub struct BrowserImportPreview {
+    pub format: &'static str,
+    pub total: usize,
+    /// Bookmarks an import with these options would add
+    pub to_import: usize,
+    /// Entries that would be skipped because they aren't web pages
+    pub skipped: usize,
+    /// Entries that would be skipped because their domain is blocked
+    pub blocked: usize,
+    /// Bookmarks that are already saved, or listed earlier in the file; only skipped if asked to
+    pub duplicates: usize,
+    pub folders: Vec<FolderMapping>,
+    /// The first few bookmarks, as they'd be saved
+    pub sample: Vec<PreviewBookmark>,
+}
+
+/// What a folder in an imported file becomes
+#[derive(Serialize)]
+pub struct FolderMapping {
+    /// e.g. "Reading / Async Rust"
+    pub path: String,
+    /// Tag of the bookmarks directly in it, if folders become tags
+    pub tag: Option<String>,
+    /// Bookmarks in it, including those in its subfolders
+    pub bookmarks: usize,
+    /// Whether it would get a smart collection
+    pub collection: bool,
+}
+
+#[derive(Serialize)]
+pub struct PreviewBookmark {
+    pub url: String,
+    pub title: String,
+    pub tags: Vec<String>,
+    /// UNIX timestamp, if the file has it
+    pub added_at: Option<i64>,
+    /// Whether its domain is set to be archived
+    pub archived: bool,
+    pub duplicate: bool,
+}
+
+/// Bookmarks listed in a preview.
+const PREVIEW_SIZE: usize = 20;
+
+/// What importing a file with the given options does, worked out without saving anything.
+struct ImportPlan {
+    total: usize,
+    skipped: usize,
+    blocked: usize,
+    duplicates: usize,
+    /// Bookmarks to add, with whether each is a duplicate; without duplicates if they're skipped
+    bookmarks: Vec<(NewBookmark, bool)>,
+    /// Every folder enclosing a bookmark, with how many bookmarks are in it or its subfolders
+    folders: BTreeMap<Vec<String>, usize>,
+}
+
+impl ImportPlan {
+    /// Works out the import of parsed `entries` for a user, applying their URL cleaning rules and
+    /// blocklist.
+    async fn new(state: &AppState, user_id: Uuid, entries: Vec<ImportedBookmark>, options: &BrowserImportQuery) -> AppResult<Self> {
+        let total = entries.len();
+        let cleaner = UrlCleaner::for_user(&state.db, user_id).await;
+        let blocklist = Blocklist::for_user(&state.db, user_id).await?;
+        let mut saved = bookmarks::get_duplicate_keys(&state.db, user_id).await?;
+        let extra_tags = parse_tags(&options.tags);
+
+        let web_pages: Vec<ImportedBookmark> = entries
+            .into_iter()
+            .filter(|entry| Url::parse(&entry.url).is_ok_and(|url| matches!(url.scheme(), "http" | "https")))
+            .collect();
+        let web_page_count = web_pages.len();
+        let mut folders = BTreeMap::new();
+        let mut duplicates = 0;
+        let mut blocked = 0;
+        let mut planned = Vec::new();
+        for entry in web_pages {
+            let folder_path = entry.folders.clone();
+            let Ok(bookmark) = blocklist.apply(cleaner.apply(new_bookmark(entry, options.folder_tags, &extra_tags))) else {
+                blocked += 1;
+                continue;
+            };
+            // Also catches the same URL twice in the file
+            let duplicate = !saved.insert(bookmarks::duplicate_key(&bookmark.url).to_string());
+            if duplicate {
+                duplicates += 1;
+                if options.skip_duplicates {
+                    continue;
+                }
+            }
+            // Every enclosing folder counts the bookmark, as its collection would list it
+            for depth in 1..=folder_path.len() {
+                *folders.entry(folder_path[..depth].to_vec()).or_insert(0) += 1;
+            }
+            planned.push((bookmark, duplicate));
+        }
+
+        Ok(Self {
+            total,
+            skipped: total - web_page_count,
+            blocked,
+            duplicates,
+            bookmarks: planned,
+            folders,
+        })
+    }
+}
+
+/// Reads an uploaded browser bookmark file.
+fn parse_browser_file(body: &[u8]) -> AppResult<(BrowserFormat, Vec<ImportedBookmark>)> {
+    browser_import::parse(body).map_err(|err| {
+        AppError::bad_request(format!(
+            "Couldn't read this file ({err}). Use Chrome's Bookmarks file or a Firefox .json or .jsonlz4 backup."
+        ))
+    })
+}
+
+/// API handler previewing the import of a Chrome `Bookmarks` file or a Firefox bookmark backup, sent
+/// as the request body, with the same options as importing it. Nothing is saved.
+pub async fn preview_browser_import_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Query(options): Query<BrowserImportQuery>,
+    body: Bytes,
+) -> AppResult<impl IntoResponse> {
+    let (format, entries) = parse_browser_file(&body)?;
+    let plan = ImportPlan::new(&state, user.user_id, entries, &options).await?;
+
+    let folders = plan
+        .folders
+        .iter()
+        .map(|(folder, &bookmarks)| {
+            let tag = if options.folder_tags {
+                browser_import::folder_tag(folder)
+            } else {
+                None
+            };
+            FolderMapping {
+                path: folder.join(" / "),
+                collection: options.collections && tag.is_some(),
+                tag,
+                bookmarks,
+            }
+        })
+        .collect();
+    let sample = plan
+        .bookmarks
+        .iter()
+        .take(PREVIEW_SIZE)
+        .map(|(bookmark, duplicate)| PreviewBookmark {
+            url: bookmark.url.clone(),
+            title: bookmark.title.clone(),
+            tags: bookmark.tag_names.clone(),
+            added_at: bookmark.created_at,
+            archived: bookmark.is_archived,
+            duplicate: *duplicate,
+        })
+        .collect();
+
+    Ok(Json(BrowserImportPreview {
+        format: format.as_str(),
+        total: plan.total,
+        to_import: plan.bookmarks.len(),
+        skipped: plan.skipped,
+        blocked: plan.blocked,
+        duplicates: plan.duplicates,
+        folders,
+        sample,
+    }))
+}
+
+/// API handler for importing a Chrome `Bookmarks` file or a Firefox bookmark backup, sent as the request body
+///
+/// By default each folder becomes a tag on the bookmarks in it, and a smart collection listing them
+/// (including those in its subfolders); see [`BrowserImportQuery`] for the options.
+pub async fn import_browser_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Query(options): Query<BrowserImportQuery>,
+    body: Bytes,
+) -> AppResult<impl IntoResponse> {
+    let (format, entries) = parse_browser_file(&body)?;
+    let plan = ImportPlan::new(&state, user.user_id, entries, &options).await?;
+
+    let source_name = match options.filename.trim() {
+        "" => format!("{} bookmarks", format.as_str()),
+        filename => filename.to_string(),
+    };
+    let origin = BookmarkOrigin {
+        source: BookmarkSource::Import,
+        detail: Some(source_name.clone()),
+    };
+    let new_bookmarks: Vec<NewBookmark> = plan.bookmarks.into_iter().map(|(bookmark, _)| bookmark).collect();
+    if !new_bookmarks.is_empty() {
+        restore_points::create_restore_point(&state.db, user.user_id, &format!("Before importing {source_name}")).await?;
+    }
+    bookmarks::create_bookmarks(&state.db, user.user_id, &new_bookmarks, &origin).await?;
+
+    let mut collections_added = 0;
+    for folder in plan.folders.keys().filter(|_| options.folder_tags && options.collections) {
+        let Some(tag) = browser_import::folder_tag(folder) else {
+            continue;
+        };
+        let name: String = folder.join(" / ").chars().take(MAX_COLLECTION_NAME_LENGTH).collect();
+        if collections::create_collection(&state.db, user.user_id, &name, &format!("#{tag}"))
+            .await?
+            .is_some()
+        {
+            collections_added += 1;
+        }
+    }
+
+    let summary = BrowserImportSummary {
+        format: format.as_str(),
+        imported: new_bookmarks.len(),
+        skipped: plan.skipped,
+        blocked: plan.blocked,
+        duplicates: if options.skip_duplicates { plan.duplicates } else { 0 },
+        collections_added,
+    };
+    let record = ImportRecord {
+        source_name,
+        total_count: i64::try_from(plan.total).unwrap_or(i64::MAX),
+        success_count: i64::try_from(summary.imported).unwrap_or(i64::MAX),
+        error_count: i64::try_from(summary.skipped + summary.blocked).unwrap_or(i64::MAX),
+    };
+    if let Err(err) = imports::record_import(&state.db, user.user_id, &record).await {
+        error!(?err, "🚨 Could not record bookmark import.");
+    }
+    info!(
+        user = user.username,
+        format = summary.format,
+        imported = summary.imported,
+        skipped = summary.skipped,
+        blocked = summary.blocked,
+        duplicates = summary.duplicates,
+        collections_added,
+        "📥 Imported browser bookmarks."
+    );
+
+    Ok(Json(summary))
+}
+
+#[derive(Deserialize)]
+pub struct HypothesisImportForm {
+    /// Personal API token; only used for this import, never stored
+    pub api_token: String,
+}
+
+/// Summary of what a Hypothes.is import added
+#[derive(Serialize)]
+pub struct HypothesisImportSummary {
+    pub annotations: usize,
+    /// Bookmarks created, one per annotated page
+    pub imported: usize,
+    /// Annotated pages skipped because their domain is blocked
+    pub blocked: usize,
+}
+
+/// API handler for importing a user's Hypothes.is annotations
+///
+/// Each annotated web page becomes a bookmark with the highlights and notes in its description.
+pub async fn import_hypothesis_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Form(form): Form<HypothesisImportForm>,
+) -> AppResult<impl IntoResponse> {
+    let api_token = form.api_token.trim();
+    if api_token.is_empty() {
+        return Err(AppError::bad_request("Enter your Hypothes.is API token."));
+    }
+
+    let annotations = hypothesis::fetch_annotations(api_token).await.map_err(|err| {
+        error!(?err, "🚨 Could not download Hypothes.is annotations.");
+        AppError::with_status(anyhow!("Hypothes.is import failed: {err}"), StatusCode::BAD_GATEWAY)
+    })?;
+
+    let cleaner = UrlCleaner::for_user(&state.db, user.user_id).await;
+    let blocklist = Blocklist::for_user(&state.db, user.user_id).await?;
+    let pages = hypothesis::to_bookmarks(&annotations);
+    let page_count = pages.len();
+    let new_bookmarks: Vec<NewBookmark> = pages
+        .into_iter()
+        .filter_map(|bookmark| blocklist.apply(cleaner.apply(bookmark)).ok())
+        .collect();
+    let origin = BookmarkOrigin {
+        source: BookmarkSource::Import,
+        detail: Some("Hypothes.is".to_string()),
+    };
+    if !new_bookmarks.is_empty() {
+        restore_points::create_restore_point(&state.db, user.user_id, "Before importing from Hypothes.is").await?;
+    }
+    bookmarks::create_bookmarks(&state.db, user.user_id, &new_bookmarks, &origin).await?;
+
+    let summary = HypothesisImportSummary {
+        annotations: annotations.len(),
+        imported: new_bookmarks.len(),
+        blocked: page_count - new_bookmarks.len(),
+    };
+    let record = ImportRecord {
+        source_name: "Hypothes.is".to_string(),
+        total_count: i64::try_from(summary.annotations).unwrap_or(i64::MAX),
+        success_count: i64::try_from(summary.imported).unwrap_or(i64::MAX),
+        error_count: i64::try_from(summary.blocked).unwrap_or(i64::MAX),
+    };
+    if let Err(err) = imports::record_import(&state.db, user.user_id, &record).await {
+        error!(?err, "🚨 Could not record bookmark import.");
+    }
+    info!(
+        user = user.username,
+        annotations = summary.annotations,
+        imported = summary.imported,
+        blocked = summary.blocked,
+        "📥 Imported Hypothes.is annotations."
+    );
+
+    Ok(Json(summary))
+}
+
+fn new_bookmark(entry: ImportedBookmark, folder_tags: bool, extra_tags: &[String]) -> NewBookmark {
+    let title = match entry.title.trim() {
+        "" => entry.url.clone(),
+        title => title.to_string(),
+    };
+    let mut tag_names: Vec<String> = browser_import::folder_tag(&entry.folders)
+        .filter(|_| folder_tags)
+        .into_iter()
+        .collect();
+    for tag in extra_tags {
+        if !tag_names.contains(tag) {
+            tag_names.push(tag.clone());
+        }
+    }
+    NewBookmark {
+        tag_names,
+        url: entry.url,
+        title,
+        created_at: entry.added_at,
+        ..NewBookmark::default()
+    }
+}
@@ -1,47 +1,101 @@
 //! Middleware functions for authentication and session management.
 //!
 //! This module provides middleware that validates user sessions
-//! and enforces authentication requirements for protected routes.
+//! and enforces authentication requirements for protected routes,
+//! and the rate limiting key for routes behind it.
 
-use std::sync::Arc;
+use std::{net::IpAddr, sync::Arc};
 
+use anyhow::anyhow;
 use axum::{
-    extract::{Request, State},
-    http::StatusCode,
+    Json,
+    extract::{MatchedPath, Path, Request, State},
+    http::{
+        self, HeaderName, HeaderValue, Method, StatusCode,
+        header::{AUTHORIZATION, CACHE_CONTROL, WWW_AUTHENTICATE},
+    },
     middleware::Next,
-    response::IntoResponse,
+    response::{IntoResponse, Response},
 };
 use axum_extra::extract::CookieJar;
 use cookie::Cookie;
+use serde_json::json;
+use tower_governor::{
+    GovernorError,
+    key_extractor::{KeyExtractor, SmartIpKeyExtractor},
+};
 use tracing::warn;
+use uuid::Uuid;
 
 use super::auth_handler::clear_session;
 use crate::{
     ApiState, AppState,
+    api_usage::UNKNOWN_ENDPOINT,
     db::{
         self,
-        user_session::{SessionLookup, SessionToken},
+        api_tokens::TokenAuth,
+        bookmark_shares::ShareToken,
+        guest_links::GuestToken,
+        subscriptions::SubscriptionToken,
+        user_session::{self, SessionLookup, SessionToken},
+        users::User,
     },
+    error::{AppError, AppResult, ErrorMessage},
     handler::{
         AuthState, HtmlTemplate, LoginTemplate,
         auth_handler::{SESSION_COOKIE, set_session},
     },
 };
 
+/// Prefix of the JSON API's routes, the only ones API tokens work for.
+pub const JSON_API_PREFIX: &str = "/api/v1/";
+
 /// Authentication middleware that validates user sessions.
 ///
+/// Requests with an `Authorization: Bearer` header are authenticated with that API token instead,
+/// see [`check_api_token`], and counted in the token's usage.
+///
 /// This middleware:
 /// 1. Checks for a valid session cookie
 /// 2. Verifies the JWT signature
 /// 3. Looks up the session in the database
-/// 4. Adds the user to the request extensions if valid
-/// 5. Redirects to login if authentication fails
+/// 4. Extends the session and re-issues the cookie once it's past the renewal threshold
+/// 5. Adds the user to the request extensions if valid
+/// 6. Redirects to login if authentication fails
 ///
 /// Applied to routes that require authentication.
 pub async fn auth_user_middleware(State(state): ApiState, jar: CookieJar, mut req: Request, next: Next) -> impl IntoResponse {
+    if let Some(authorization) = req.headers().get(AUTHORIZATION) {
+        return match check_api_token(&state, authorization, req.method(), req.uri().path()).await {
+            Ok((user, token)) => {
+                let endpoint = req.extensions().get::<MatchedPath>().map_or(UNKNOWN_ENDPOINT, MatchedPath::as_str);
+                let endpoint = format!("{} {endpoint}", req.method());
+                state.api_usage.record(token.token_id, chrono::Utc::now().date_naive(), &endpoint);
+                req.extensions_mut().insert(user);
+                req.extensions_mut().insert(token);
+                next.run(req).await
+            }
+            Err(err) => {
+                let mut response = err.into_response();
+                response.headers_mut().insert(WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+                response
+            }
+        };
+    }
+
     let user = check_session_cookie(&state, &jar).await;
-    let SessionLookup { user, signed_token, .. } = match user {
+    let SessionLookup {
+        user,
+        session_token,
+        signed_token,
+        expires_at,
+    } = match user {
         Ok(user) => user,
+        Err(reason) if req.uri().path().starts_with(JSON_API_PREFIX) => {
+            return AppError::unauthorized(anyhow!("JSON API access attempted without a session or token: {reason}"))
+                .user_message("Sign in, or send an API token as 'Authorization: Bearer <token>'.")
+                .into_response();
+        }
         Err(_reason) => {
             warn!("API access attempted with no session cookie");
             // Redirect to login instead of returning 401
@@ -53,13 +107,27 @@ pub async fn auth_user_middleware(State(state): ApiState, jar: CookieJar, mut re
                     title: "Login",
                     auth_state: AuthState::LoginPage,
                     is_error: false,
+                    registration_enabled: state.registration_enabled,
+                    password_reset_enabled: state.mailer.is_some(),
                 }),
             )
                 .into_response();
         }
     };
 
-    let jar = set_session(jar, signed_token);
+    // Sliding expiry: the cookie's max-age is refreshed together with the session, so the browser
+    // keeps the cookie as long as the server keeps the session
+    let jar = if user_session::needs_renewal(expires_at, chrono::Utc::now().timestamp()) {
+        match user_session::renew_session(state.sessions.as_ref(), session_token).await {
+            Ok(_) => set_session(jar, signed_token),
+            Err(err) => {
+                warn!(?err, "Could not renew session, keeping the current expiry.");
+                jar
+            }
+        }
+    } else {
+        jar
+    };
 
     req.extensions_mut().insert(user);
 
@@ -68,6 +136,205 @@ pub async fn auth_user_middleware(State(state): ApiState, jar: CookieJar, mut re
     (jar, response).into_response()
 }
 
+/// Authenticates a request to the JSON API by the API token in its `Authorization` header.
+///
+/// # Errors
+///
+/// Returns 401 if the header isn't a bearer token, or the token doesn't exist (any more), and 403
+/// for a valid token used outside the JSON API, or for a request its scope doesn't allow.
+pub async fn check_api_token(state: &AppState, authorization: &HeaderValue, method: &Method, path: &str) -> AppResult<(User, TokenAuth)> {
+    let (user, token) = find_api_token(state, bearer_token(authorization)?).await?;
+    if !path.starts_with(JSON_API_PREFIX) {
+        return Err(AppError::with_status(
+            anyhow!("API token '{}' of '{}' used for {path}", token.name, user.username),
+            StatusCode::FORBIDDEN,
+        )
+        .user_message(format!("API tokens only work for the JSON API, under {JSON_API_PREFIX}.")));
+    }
+    if !token.scope.allows(method) {
+        return Err(AppError::with_status(
+            anyhow!(
+                "API token '{}' of '{}' with {} scope used for {method} {path}",
+                token.name,
+                user.username,
+                token.scope.as_str()
+            ),
+            StatusCode::FORBIDDEN,
+        )
+        .user_message(format!(
+            "This token's scope is {}, which doesn't allow {method} requests.",
+            token.scope.label().to_lowercase()
+        )));
+    }
+
+    Ok((user, token))
+}
+
+/// The API token sent as `Authorization: Bearer <token>`.
+///
+/// # Errors
+///
+/// Returns 401 if the header isn't a bearer token.
+pub fn bearer_token(authorization: &HeaderValue) -> AppResult<&str> {
+    authorization
+        .to_str()
+        .ok()
+        .and_then(|value| value.split_once(' '))
+        .filter(|(scheme, token)| scheme.eq_ignore_ascii_case("bearer") && !token.trim().is_empty())
+        .map(|(_, token)| token.trim())
+        .ok_or_else(|| {
+            AppError::unauthorized(anyhow!("Authorization header is not a bearer token"))
+                .user_message("Send the API token as 'Authorization: Bearer <token>'.")
+        })
+}
+
+/// Looks up the user an API token belongs to, without checking what it's used for.
+///
+/// # Errors
+///
+/// Returns 401 if the token doesn't exist (any more).
+pub async fn find_api_token(state: &AppState, token: &str) -> AppResult<(User, TokenAuth)> {
+    db::api_tokens::get_token_user(&state.db, &state.encryption.hash_api_token(token)?)
+        .await?
+        .ok_or_else(|| AppError::unauthorized(anyhow!("Unknown API token")).user_message("The API token isn't valid, or was revoked."))
+}
+
+/// Answers errors from the JSON API as JSON, `{"error": "..."}`, instead of the HTML error page.
+///
+/// Applies to the routes under [`JSON_API_PREFIX`]. Must wrap [`auth_user_middleware`], so
+/// authentication errors are JSON too.
+pub async fn json_errors_middleware(req: Request, next: Next) -> Response {
+    let is_json_api = req.uri().path().starts_with(JSON_API_PREFIX);
+    let mut response = next.run(req).await;
+    if !is_json_api {
+        return response;
+    }
+    let Some(ErrorMessage(message)) = response.extensions_mut().remove::<ErrorMessage>() else {
+        return response;
+    };
+
+    let mut json_response = (response.status(), Json(json!({ "error": message }))).into_response();
+    if let Some(challenge) = response.headers().get(WWW_AUTHENTICATE) {
+        json_response.headers_mut().insert(WWW_AUTHENTICATE, challenge.clone());
+    }
+    *json_response.extensions_mut() = std::mem::take(response.extensions_mut());
+    json_response
+}
+
+/// Guest link middleware for the read-only pages shared with people without an account.
+///
+/// This middleware:
+/// 1. Verifies the JWT signature of the token in the URL
+/// 2. Turns away expired links without a database lookup
+/// 3. Looks up the link in the database, so revoked links stop working
+/// 4. Adds the [`db::guest_links::GuestAccess`] to the request extensions if valid
+///
+/// Applied to the guest routes, which are outside the auth layer.
+pub async fn guest_middleware(State(state): ApiState, Path(token): Path<String>, mut req: Request, next: Next) -> AppResult<Response> {
+    let token = match state.encryption.verify_token_sig::<GuestToken>(&token) {
+        Ok(token) => token,
+        Err(err) => {
+            warn!(?err, "Token signature invalid on guest link.");
+            return Err(
+                AppError::with_status(anyhow!("Invalid guest link"), StatusCode::NOT_FOUND).user_message("This guest link isn't valid.")
+            );
+        }
+    };
+    if token.expires_at <= chrono::Utc::now().timestamp() {
+        return Err(
+            AppError::with_status(anyhow!("Guest link {} has expired", token.link_id), StatusCode::GONE)
+                .user_message("This guest link has expired."),
+        );
+    }
+    let Some(access) = db::guest_links::get_guest_access(&state.db, token.link_id).await? else {
+        return Err(
+            AppError::with_status(anyhow!("Guest link {} was revoked", token.link_id), StatusCode::GONE)
+                .user_message("This guest link has been revoked."),
+        );
+    };
+
+    req.extensions_mut().insert(access);
+
+    Ok(next.run(req).await)
+}
+
+/// Subscription middleware for the collection exports other tools pull.
+///
+/// Like [`guest_middleware`], but subscriptions don't expire: the token's signature is checked, and
+/// the subscription looked up so revoked ones stop working. Adds the
+/// [`db::subscriptions::SubscriptionAccess`] to the request extensions if valid.
+pub async fn subscription_middleware(
+    State(state): ApiState,
+    Path(token): Path<String>,
+    mut req: Request,
+    next: Next,
+) -> AppResult<Response> {
+    let token = match state.encryption.verify_token_sig::<SubscriptionToken>(&token) {
+        Ok(token) => token,
+        Err(err) => {
+            warn!(?err, "Token signature invalid on subscription.");
+            return Err(AppError::with_status(anyhow!("Invalid subscription"), StatusCode::NOT_FOUND)
+                .user_message("This subscription link isn't valid."));
+        }
+    };
+    let Some(access) = db::subscriptions::get_subscription_access(&state.db, token.subscription_id).await? else {
+        return Err(
+            AppError::with_status(anyhow!("Subscription {} was revoked", token.subscription_id), StatusCode::GONE)
+                .user_message("This subscription has been revoked."),
+        );
+    };
+
+    req.extensions_mut().insert(access);
+
+    Ok(next.run(req).await)
+}
+
+/// Share middleware for the public pages of shared bookmarks.
+///
+/// Like [`subscription_middleware`]: shares don't expire, so the token's signature is checked and
+/// the share looked up so revoked ones stop working. Adds the [`db::bookmark_shares::ShareAccess`]
+/// to the request extensions if valid.
+pub async fn share_middleware(State(state): ApiState, Path(token): Path<String>, mut req: Request, next: Next) -> AppResult<Response> {
+    let token = match state.encryption.verify_token_sig::<ShareToken>(&token) {
+        Ok(token) => token,
+        Err(err) => {
+            warn!(?err, "Token signature invalid on shared bookmark.");
+            return Err(
+                AppError::with_status(anyhow!("Invalid share link"), StatusCode::NOT_FOUND).user_message("This share link isn't valid.")
+            );
+        }
+    };
+    let Some(access) = db::bookmark_shares::get_share_access(&state.db, token.share_id).await? else {
+        return Err(
+            AppError::with_status(anyhow!("Share {} was revoked", token.share_id), StatusCode::GONE)
+                .user_message("This bookmark is no longer shared."),
+        );
+    };
+
+    req.extensions_mut().insert(access);
+
+    Ok(next.run(req).await)
+}
+
+/// Headers of the pages anyone with the link can open: they aren't kept in caches, so they're gone
+/// everywhere once revoked, or indexed by search engines.
+const PUBLIC_PAGE_HEADERS: [(HeaderName, &str); 2] = [(CACHE_CONTROL, "no-store"), (HeaderName::from_static("x-robots-tag"), "noindex")];
+
+/// Adds [`PUBLIC_PAGE_HEADERS`] to every response of the pages anyone with the link can open,
+/// including the errors for links that are revoked or were never valid.
+///
+/// Applied outside the middleware that checks the link, e.g. [`share_middleware`].
+pub async fn public_page_middleware(req: Request, next: Next) -> Response {
+    with_public_page_headers(next.run(req).await)
+}
+
+fn with_public_page_headers(mut response: Response) -> Response {
+    for (name, value) in PUBLIC_PAGE_HEADERS {
+        response.headers_mut().insert(name, HeaderValue::from_static(value));
+    }
+    response
+}
+
 /// Validates a session cookie and retrieves the associated user.
 ///
 /// This function performs a complete session validation:
@@ -96,7 +363,7 @@ pub async fn check_session_cookie(state: &Arc<AppState>, jar: &CookieJar) -> Res
         }
     };
 
-    let session_lookup = match db::user_session::from_token(&state.pool, session_token, token.to_string()).await {
+    let session_lookup = match db::user_session::from_token(&state.db, state.sessions.as_ref(), session_token, token.to_string()).await {
         Ok(user) => user,
         Err(err) => {
             warn!(?err, "Session is not valid.");
@@ -106,3 +373,101 @@ pub async fn check_session_cookie(state: &Arc<AppState>, jar: &CookieJar) -> Res
 
     Ok(session_lookup)
 }
+
+/// Who a rate limit budget belongs to.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum RateLimitKey {
+    User(Uuid),
+    Ip(IpAddr),
+}
+
+/// Rate limits each signed-in user separately, so people sharing an IP address (behind a NAT or a
+/// VPN) don't use up each other's budget, and one account can't spread its requests over several
+/// addresses. Falls back to the client IP when there's no user.
+///
+/// Must run inside [`auth_user_middleware`], which adds the user to the request.
+#[derive(Clone, Copy, Debug)]
+pub struct UserKeyExtractor;
+
+impl KeyExtractor for UserKeyExtractor {
+    type Key = RateLimitKey;
+
+    fn name(&self) -> &'static str {
+        "user"
+    }
+
+    fn extract<T>(&self, req: &http::Request<T>) -> Result<Self::Key, GovernorError> {
+        match req.extensions().get::<User>() {
+            Some(user) => Ok(RateLimitKey::User(user.user_id)),
+            None => SmartIpKeyExtractor.extract(req).map(RateLimitKey::Ip),
+        }
+    }
+
+    fn key_name(&self, key: &Self::Key) -> Option<String> {
+        match key {
+            RateLimitKey::User(user_id) => Some(user_id.to_string()),
+            RateLimitKey::Ip(ip) => Some(ip.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limit_key_prefers_user() {
+        let mut req = http::Request::builder().header("x-forwarded-for", "203.0.113.7").body(()).unwrap();
+        assert_eq!(
+            UserKeyExtractor.extract(&req).unwrap(),
+            RateLimitKey::Ip("203.0.113.7".parse().unwrap())
+        );
+
+        let user_id = Uuid::new_v4();
+        req.extensions_mut().insert(User {
+            user_id,
+            username: "someone".to_string(),
+            is_revoked: false,
+            is_admin: false,
+        });
+        assert_eq!(UserKeyExtractor.extract(&req).unwrap(), RateLimitKey::User(user_id));
+    }
+
+    #[test]
+    fn test_public_page_headers() {
+        let revoked = AppError::with_status(anyhow!("Share was revoked"), StatusCode::GONE)
+            .user_message("This bookmark is no longer shared.")
+            .into_response();
+        for response in [
+            with_public_page_headers(revoked),
+            with_public_page_headers("shared".into_response()),
+        ] {
+            assert_eq!(response.headers()[CACHE_CONTROL], "no-store");
+            assert_eq!(response.headers()["x-robots-tag"], "noindex");
+        }
+    }
+
+    #[test]
+    fn test_guest_token_must_be_signed() {
+        let encryption = crate::encryption::EncryptionProvider::new(crate::encryption::generate_root_key());
+        let token = GuestToken {
+            link_id: Uuid::new_v4(),
+            expires_at: 1_700_000_000,
+        };
+        let signed = encryption.sign_token(token).unwrap();
+        assert_eq!(encryption.verify_token_sig::<GuestToken>(&signed).unwrap(), token);
+
+        // Another instance's key, or a token with a changed payload, isn't accepted
+        let other = crate::encryption::EncryptionProvider::new(crate::encryption::generate_root_key());
+        assert!(other.verify_token_sig::<GuestToken>(&signed).is_err());
+        let mut parts: Vec<&str> = signed.split('.').collect();
+        let forged = other
+            .sign_token(GuestToken {
+                expires_at: i64::MAX,
+                ..token
+            })
+            .unwrap();
+        parts[1] = forged.split('.').nth(1).unwrap();
+        assert!(encryption.verify_token_sig::<GuestToken>(&parts.join(".")).is_err());
+    }
+}
@@ -1,20 +1,49 @@
 //! HTTP request handlers and response templates.
 
+pub mod admin;
+pub mod announcement;
+pub mod api;
 pub mod auth_handler;
+pub mod blocklist;
 pub mod bookmarks;
+pub mod collections;
+pub mod export;
+pub mod extract;
+pub mod guest;
+pub mod import;
 pub mod middlewares;
+pub mod pagination;
+pub mod public_tags;
+pub mod quick_add;
+pub mod report;
+pub mod restore_points;
+pub mod resurface;
 pub mod settings;
+pub mod shares;
+pub mod snapshots;
+pub mod subscriptions;
 pub mod tags;
+pub mod translations;
+pub mod trash;
+pub mod two_factor;
+pub mod url_rules;
+use std::convert::Infallible;
+
 use askama::Template;
 use axum::{
-    extract::State,
-    http::StatusCode,
-    response::{Html, IntoResponse},
+    Extension,
+    extract::{FromRequestParts, State},
+    http::{StatusCode, request::Parts},
+    response::{Html, IntoResponse, Redirect, Response},
 };
 use axum_extra::extract::CookieJar;
 use tracing::error;
 
-use crate::{ApiState, handler::middlewares::check_session_cookie};
+use crate::{
+    ApiState,
+    db::{self, collections::SmartCollection, users::User},
+    handler::{bookmarks::BookmarkQuery, extract::TolerantQuery, middlewares::check_session_cookie},
+};
 
 /// Authentication state for template rendering.
 ///
@@ -46,12 +75,88 @@ where
     }
 }
 
+/// Whether the request was made by HTMX, i.e. carries an `HX-Request: true` header.
+///
+/// Handlers use this to decide between returning a bare fragment (for HTMX swaps) and a
+/// full page (for a refresh, deep link, or plain form submission without JavaScript).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HxRequest(pub bool);
+
+impl<S> FromRequestParts<S> for HxRequest
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let is_htmx = parts.headers.get("HX-Request").is_some_and(|value| value.as_bytes() == b"true");
+        Ok(Self(is_htmx))
+    }
+}
+
+/// Full-page wrapper for a pre-rendered fragment.
+#[derive(Template)]
+#[template(path = "layout/fragment_page.html")]
+struct FragmentPageTemplate<'a> {
+    title: &'a str,
+    auth_state: AuthState,
+    is_error: bool,
+    content: String,
+}
+
+/// Renders an authenticated fragment, wrapping it in the full page layout unless the request came from HTMX.
+pub fn render_fragment<T>(hx: HxRequest, title: &str, fragment: T) -> Response
+where
+    T: Template,
+{
+    if hx.0 {
+        return HtmlTemplate(fragment).into_response();
+    }
+
+    match fragment.render() {
+        Ok(content) => HtmlTemplate(FragmentPageTemplate {
+            title,
+            auth_state: AuthState::Authenticated,
+            is_error: false,
+            content,
+        })
+        .into_response(),
+        Err(err) => {
+            error!(?err, "Failed to render template.");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to render template.").into_response()
+        }
+    }
+}
+
+/// Responds to a completed action with `page` for HTMX requests, or a redirect to `location` otherwise.
+///
+/// Plain (non-HTMX) form posts get a `303 See Other` so that refreshing the resulting page
+/// doesn't re-submit the form.
+pub fn page_or_redirect<T>(hx: HxRequest, location: &str, page: T) -> Response
+where
+    T: Template,
+{
+    if hx.0 {
+        ([("HX-Push-Url", location)], HtmlTemplate(page)).into_response()
+    } else {
+        Redirect::to(location).into_response()
+    }
+}
+
 #[derive(Default, Template)]
 #[template(path = "pages/home.html")]
 pub struct HomeTemplate<'a> {
     pub title: &'a str,
     pub auth_state: AuthState,
     pub is_error: bool,
+    /// Canonical query string (with leading `?`) of the filters the page was loaded with
+    pub initial_query: String,
+    /// Free-text search to pre-fill the search box with
+    pub search_text: String,
+    /// Smart collections listed in the sidebar
+    pub collections: Vec<SmartCollection>,
+    /// Whether to load old bookmarks to rediscover into the sidebar
+    pub resurface: bool,
 }
 
 #[derive(Default, Template)]
@@ -70,16 +175,77 @@ struct LoginTemplate<'a> {
     title: &'a str,
     auth_state: AuthState,
     is_error: bool,
+    /// Whether to link to the sign-up page
+    registration_enabled: bool,
+    /// Whether to link to the page for a password reset link
+    password_reset_enabled: bool,
+}
+
+#[derive(Default, Template)]
+#[template(path = "auth/register.html")]
+struct RegisterTemplate<'a> {
+    title: &'a str,
+    auth_state: AuthState,
+    is_error: bool,
+}
+
+#[derive(Default, Template)]
+#[template(path = "auth/forgot_password.html")]
+struct ForgotPasswordTemplate<'a> {
+    title: &'a str,
+    auth_state: AuthState,
+    is_error: bool,
+    /// Whether a link was asked for, rather than the form being shown
+    sent: bool,
+}
+
+#[derive(Default, Template)]
+#[template(path = "auth/reset_password.html")]
+struct ResetPasswordTemplate<'a> {
+    title: &'a str,
+    auth_state: AuthState,
+    is_error: bool,
+    /// The token of the reset link, or `None` if it can't be used (anymore)
+    token: Option<String>,
+}
+
+#[derive(Default, Template)]
+#[template(path = "auth/two_factor.html")]
+struct TwoFactorTemplate<'a> {
+    title: &'a str,
+    auth_state: AuthState,
+    is_error: bool,
+    /// Signed token of the password step, see [`crate::db::two_factor::TwoFactorToken`]
+    token: String,
 }
 
 /// Handler for the home page.
 ///
-/// Authentication is guaranteed by middleware.
-pub async fn home_handler() -> impl IntoResponse {
+/// Authentication is guaranteed by middleware. Any filter state in the query string (as pushed by
+/// the bookmark list) is restored, so filtered views can be bookmarked and shared.
+pub async fn home_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    TolerantQuery(params): TolerantQuery<BookmarkQuery>,
+) -> impl IntoResponse {
+    let collections = db::collections::get_user_collections(&state.db, user.user_id)
+        .await
+        .unwrap_or_else(|err| {
+            error!(?err, "🚨 Could not load smart collections for the sidebar.");
+            Vec::new()
+        });
+    let resurface = db::settings::get_user_settings(&state.db, user.user_id)
+        .await
+        .is_ok_and(|settings| settings.resurface);
+
     HtmlTemplate(HomeTemplate {
         title: "Home",
         auth_state: AuthState::Authenticated,
         is_error: false,
+        initial_query: params.to_query_string(params.page.unwrap_or(1)),
+        search_text: params.q.unwrap_or_default(),
+        collections,
+        resurface,
     })
     .into_response()
 }
@@ -0,0 +1,233 @@
+//! Paging through lists, shared by the web list and the JSON API so they agree on what's on each
+//! page and whether there are more.
+//!
+//! A [`Page`] is worked out from the page asked for and how many items there are in all. The web
+//! list shows it as [`Pagination`] links; the API answers with it as a [`PageMeta`] envelope, so
+//! clients can tell from `total` and `next` whether there's more without fetching an empty page.
+
+use serde::{Deserialize, Serialize};
+
+/// Pages shown on either side of the current one in the page links.
+const PAGES_AROUND: i64 = 2;
+
+/// Query parameters picking a page of a list that takes no other ones.
+#[derive(Debug, Default, Deserialize)]
+pub struct PageQuery {
+    pub page: Option<i64>,
+}
+
+/// One page of a list.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Page {
+    /// Which page, from 1
+    pub number: i64,
+    /// How many items there are to a page
+    pub size: i64,
+    /// How many items there are on all pages
+    pub total: i64,
+}
+
+impl Page {
+    /// The page numbered `number` (the first if it's missing or below 1) of a list of `total`
+    /// items, `per_page` to a page.
+    pub fn new(number: Option<i64>, per_page: i64, total: i64) -> Self {
+        Self {
+            number: number.unwrap_or(1).max(1),
+            size: per_page.max(1),
+            total: total.max(0),
+        }
+    }
+
+    /// The page numbered `number` of `items`, and the items on it.
+    pub fn of<T>(number: Option<i64>, per_page: i64, items: Vec<T>) -> (Self, Vec<T>) {
+        let page = Self::new(number, per_page, i64::try_from(items.len()).unwrap_or(i64::MAX));
+        let items = items
+            .into_iter()
+            .skip(usize::try_from(page.offset()).unwrap_or(usize::MAX))
+            .take(usize::try_from(page.size).unwrap_or(usize::MAX))
+            .collect();
+        (page, items)
+    }
+
+    /// How many items come before this page.
+    pub fn offset(&self) -> i64 {
+        (self.number - 1).saturating_mul(self.size)
+    }
+
+    /// The number of the last page; an empty list still has one.
+    pub fn last(&self) -> i64 {
+        ((self.total + self.size - 1) / self.size).max(1)
+    }
+
+    /// The page before this one, or the last one if this is past the end.
+    pub fn prev(&self) -> Option<i64> {
+        (self.number > 1).then(|| (self.number - 1).min(self.last()))
+    }
+
+    /// The page after this one, if this isn't the last.
+    pub fn next(&self) -> Option<i64> {
+        (self.number < self.last()).then_some(self.number + 1)
+    }
+
+    /// Where this page is, for a JSON list, with `url` giving the link to a page by its number.
+    pub fn meta(&self, url: impl Fn(i64) -> String) -> PageMeta {
+        PageMeta {
+            total: self.total,
+            page: self.number,
+            per_page: self.size,
+            next: self.next().map(&url),
+            prev: self.prev().map(&url),
+        }
+    }
+
+    /// Links to the pages around this one for the web list, with `url` giving the link to a page
+    /// by its number, or `None` if everything fits on one page.
+    pub fn links(&self, url: impl Fn(i64) -> String) -> Option<Pagination> {
+        (self.last() > 1 || self.number > 1).then(|| Pagination {
+            has_prev: self.prev().is_some(),
+            has_next: self.next().is_some(),
+            prev_url: self.prev().map(&url).unwrap_or_default(),
+            next_url: self.next().map(&url).unwrap_or_default(),
+            page_links: self
+                .numbers()
+                .into_iter()
+                .map(|number| match number {
+                    Some(number) => PageLink {
+                        number,
+                        url: url(number),
+                        is_current: number == self.number,
+                        is_ellipsis: false,
+                    },
+                    None => PageLink {
+                        number: 0,
+                        url: String::new(),
+                        is_current: false,
+                        is_ellipsis: true,
+                    },
+                })
+                .collect(),
+        })
+    }
+
+    /// The numbers of the pages to link to: the first and last, and those around this one, with
+    /// `None` where pages are left out.
+    fn numbers(&self) -> Vec<Option<i64>> {
+        let last = self.last();
+        let around = (self.number - PAGES_AROUND).max(1)..=(self.number.saturating_add(PAGES_AROUND)).min(last);
+        let mut numbers = Vec::new();
+        let mut previous = 0;
+        for number in std::iter::once(1).chain(around).chain(std::iter::once(last)) {
+            if number <= previous {
+                continue;
+            }
+            // Leaving out a single page saves no room
+            if number == previous + 2 {
+                numbers.push(Some(previous + 1));
+            } else if number > previous + 2 {
+                numbers.push(None);
+            }
+            numbers.push(Some(number));
+            previous = number;
+        }
+        numbers
+    }
+}
+
+/// Where a page of a JSON list is, sent alongside the items of every list the API answers with.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct PageMeta {
+    /// How many items there are on all pages
+    pub total: i64,
+    pub page: i64,
+    pub per_page: i64,
+    /// Link to the next page, if there is one
+    pub next: Option<String>,
+    /// Link to the previous page, if there is one
+    pub prev: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct Pagination {
+    pub has_prev: bool,
+    pub has_next: bool,
+    pub prev_url: String,
+    pub next_url: String,
+    pub page_links: Vec<PageLink>,
+}
+
+#[derive(Clone)]
+pub struct PageLink {
+    pub number: i64,
+    pub url: String,
+    pub is_current: bool,
+    pub is_ellipsis: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_bounds() {
+        let page = Page::new(Some(2), 20, 45);
+        assert_eq!((page.offset(), page.last()), (20, 3));
+        assert_eq!((page.prev(), page.next()), (Some(1), Some(3)));
+
+        let last = Page::new(Some(3), 20, 45);
+        assert_eq!((last.prev(), last.next()), (Some(2), None));
+
+        // Missing or nonsense page numbers are the first page, and an empty list has one page
+        let empty = Page::new(None, 20, 0);
+        assert_eq!((empty.number, empty.offset(), empty.last()), (1, 0, 1));
+        assert_eq!((empty.prev(), empty.next()), (None, None));
+        assert_eq!(Page::new(Some(-4), 20, 45).number, 1);
+
+        // Past the end, back to the last page
+        let past = Page::new(Some(9), 20, 45);
+        assert_eq!((past.prev(), past.next()), (Some(3), None));
+        assert_eq!(Page::new(Some(i64::MAX), 20, 45).offset(), i64::MAX);
+    }
+
+    #[test]
+    fn test_page_of_items() {
+        let (page, items) = Page::of(Some(2), 2, vec!['a', 'b', 'c']);
+        assert_eq!(items, ['c']);
+        assert_eq!((page.total, page.next()), (3, None));
+        assert!(Page::of(Some(5), 2, vec!['a']).1.is_empty());
+    }
+
+    #[test]
+    fn test_page_meta() {
+        let url = |number| format!("/list?page={number}");
+        assert_eq!(
+            Page::new(Some(2), 10, 25).meta(url),
+            PageMeta {
+                total: 25,
+                page: 2,
+                per_page: 10,
+                next: Some("/list?page=3".to_string()),
+                prev: Some("/list?page=1".to_string()),
+            }
+        );
+        let only = Page::new(None, 10, 5).meta(url);
+        assert_eq!((only.next, only.prev), (None, None));
+    }
+
+    #[test]
+    fn test_page_links() {
+        let numbers = |number, total| Page::new(Some(number), 10, total).numbers();
+        assert_eq!(numbers(1, 30), [Some(1), Some(2), Some(3)]);
+        assert_eq!(
+            numbers(10, 200),
+            [Some(1), None, Some(8), Some(9), Some(10), Some(11), Some(12), None, Some(20)]
+        );
+        // No gap left for a single page
+        assert_eq!(numbers(4, 60), [Some(1), Some(2), Some(3), Some(4), Some(5), Some(6)]);
+
+        assert!(Page::new(Some(1), 10, 10).links(|_| String::new()).is_none());
+        let links = Page::new(Some(2), 10, 11).links(|number| format!("?page={number}")).unwrap();
+        assert!(links.has_prev && !links.has_next);
+        assert_eq!(links.prev_url, "?page=1");
+        assert!(links.page_links[1].is_current);
+    }
+}
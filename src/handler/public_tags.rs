@@ -0,0 +1,179 @@
+//! Public tag handlers and templates: a read-only page of a user's bookmarks with a tag they made
+//! public, for anyone, without an account.
+
+use anyhow::anyhow;
+use askama::Template;
+use axum::{
+    Extension, Form,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use serde::Deserialize;
+use tracing::{debug, info};
+use uuid::Uuid;
+
+use crate::{
+    ApiState, AppState,
+    db::{
+        bookmarks::BookmarkItem,
+        tags::{self, PublicTagOwner},
+        users::User,
+    },
+    error::{AppError, AppResult},
+    handler::{
+        AuthState, HtmlTemplate,
+        bookmarks::{BookmarkQuery, find_public_bookmarks},
+        extract::TolerantQuery,
+        guest::GuestQuery,
+    },
+};
+
+/// Bookmarks per page of a public tag.
+const PUBLIC_TAG_PAGE_SIZE: i64 = 20;
+
+/// A public tag on the settings page, with the address of its page.
+#[derive(Clone, Debug)]
+pub struct PublicTag {
+    pub name: String,
+    /// Path of the public page
+    pub path: String,
+}
+
+/// The list of public tags on the settings page, re-rendered after each change.
+#[derive(Template)]
+#[template(path = "components/public_tag_settings.html")]
+pub struct PublicTagSettingsTemplate {
+    pub public_tags: Vec<PublicTag>,
+}
+
+#[derive(Deserialize)]
+pub struct PublicTagForm {
+    pub tag: String,
+}
+
+/// The path of the public page of a user's tag, e.g. `/u/alice/tags/rust-learning`.
+fn public_tag_path(username: &str, tag: &str) -> String {
+    let mut url = reqwest::Url::parse("http://localhost/").expect("static URL is valid");
+    url.path_segments_mut()
+        .expect("static URL has a path")
+        .extend(["u", username, "tags", tag]);
+    url.path().to_string()
+}
+
+/// Lists the tags a user made public, with the paths of their pages.
+pub(crate) async fn public_tags(state: &AppState, user: &User) -> AppResult<Vec<PublicTag>> {
+    Ok(tags::get_public_tags(&state.db, user.user_id)
+        .await?
+        .into_iter()
+        .map(|name| PublicTag {
+            path: public_tag_path(&user.username, &name),
+            name,
+        })
+        .collect())
+}
+
+/// API handler for making one of the user's tags public
+pub async fn publish_tag_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Form(form): Form<PublicTagForm>,
+) -> AppResult<impl IntoResponse> {
+    let tag = form.tag.trim().to_lowercase();
+    if !tags::set_tag_public(&state.db, user.user_id, &tag, true).await? {
+        return Err(AppError::bad_request(format!("None of your bookmarks are tagged '{tag}'.")));
+    }
+    info!(user = user.username, tag, "🔗 Made tag public.");
+
+    Ok(HtmlTemplate(PublicTagSettingsTemplate {
+        public_tags: public_tags(&state, &user).await?,
+    }))
+}
+
+/// API handler for making one of the user's public tags private again
+pub async fn unpublish_tag_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Path(tag): Path<String>,
+) -> AppResult<impl IntoResponse> {
+    if !tags::set_tag_public(&state.db, user.user_id, &tag, false).await? {
+        return Err(AppError::with_status(
+            anyhow!("Tag '{tag}' not found for user"),
+            StatusCode::NOT_FOUND,
+        ));
+    }
+    info!(user = user.username, tag, "🔗 Made tag private.");
+
+    Ok(HtmlTemplate(PublicTagSettingsTemplate {
+        public_tags: public_tags(&state, &user).await?,
+    }))
+}
+
+#[derive(Template)]
+#[template(path = "pages/public_tag.html")]
+pub struct PublicTagTemplate<'a> {
+    pub title: &'a str,
+    pub auth_state: AuthState,
+    pub is_error: bool,
+    pub username: String,
+    pub tag: String,
+    pub bookmarks: Vec<BookmarkItem>,
+    pub prev_url: Option<String>,
+    pub next_url: Option<String>,
+}
+
+/// Handler for the public page of a user's tag, listing their public bookmarks with it outside the
+/// archive
+pub async fn public_tag_handler(
+    State(state): ApiState,
+    Path((username, tag)): Path<(String, String)>,
+    TolerantQuery(params): TolerantQuery<GuestQuery>,
+) -> AppResult<impl IntoResponse> {
+    // Tags that were never public look the same as ones that don't exist
+    let user_id: Uuid = match tags::get_public_tag_owner(&state.db, &username, &tag).await? {
+        Some(PublicTagOwner::Public(user_id)) => user_id,
+        Some(PublicTagOwner::Withdrawn) => {
+            return Err(
+                AppError::with_status(anyhow!("Tag '{tag}' of {username} was made private"), StatusCode::GONE)
+                    .user_message("This tag is no longer public."),
+            );
+        }
+        None => {
+            return Err(
+                AppError::with_status(anyhow!("Tag '{tag}' of {username} isn't public"), StatusCode::NOT_FOUND)
+                    .user_message("There's no public tag here."),
+            );
+        }
+    };
+    debug!(username, tag, "Public tag opened");
+
+    let query = BookmarkQuery {
+        tags: Some(vec![tag.clone()]),
+        page: params.page,
+        ..BookmarkQuery::default()
+    };
+    let (bookmarks, page) = find_public_bookmarks(&state.db, user_id, &query, PUBLIC_TAG_PAGE_SIZE).await?;
+    let path = public_tag_path(&username, &tag);
+
+    Ok(HtmlTemplate(PublicTagTemplate {
+        title: "Public tag",
+        auth_state: AuthState::Anonymous,
+        is_error: false,
+        bookmarks: bookmarks.into(),
+        prev_url: page.prev().map(|number| format!("{path}?page={number}")),
+        next_url: page.next().map(|number| format!("{path}?page={number}")),
+        username,
+        tag,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_public_tag_path() {
+        assert_eq!(public_tag_path("alice", "rust-learning"), "/u/alice/tags/rust-learning");
+        assert_eq!(public_tag_path("bob smith", "c/c++"), "/u/bob%20smith/tags/c%2Fc++");
+    }
+}
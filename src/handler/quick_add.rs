@@ -0,0 +1,220 @@
+//! Quick-add handlers: saving the page open in the browser with a bookmarklet.
+//!
+//! `/add?url=…&title=…` opens the add bookmark form filled in with the page the bookmarklet was
+//! clicked on. With `save=true` it saves the page right away instead, without a session: the page
+//! it opens posts it back to `/add` with the API token kept in the browser, in the `Authorization`
+//! header, and the window closes itself. The token is never part of a URL, so it stays out of logs,
+//! traces and the browser history. The settings page shows both bookmarklets, made for the address
+//! the instance is reached at, and keeps the token.
+
+use anyhow::anyhow;
+use askama::Template;
+use axum::{
+    Json,
+    extract::State,
+    http::{HeaderMap, Method, StatusCode, header},
+    response::{IntoResponse, Redirect, Response},
+};
+use axum_extra::extract::CookieJar;
+use serde::Deserialize;
+use tracing::{error, info};
+
+use crate::{
+    ApiState,
+    db::bookmarks::{BookmarkOrigin, BookmarkSource},
+    error::{AppError, AppResult},
+    fetch,
+    handler::{
+        AuthState, HtmlTemplate,
+        api::{BatchBookmark, save_bookmark},
+        bookmarks::{BookmarkNewTemplate, NewBookmarkPrefill, find_bookmark},
+        extract::TolerantQuery,
+        middlewares::{bearer_token, check_session_cookie, find_api_token},
+    },
+};
+
+/// Where the settings page keeps the API token for [`Bookmarklets::save`], in the browser's local
+/// storage for the instance.
+pub const TOKEN_STORAGE_KEY: &str = "pagepouch-bookmarklet-token";
+
+/// The page a bookmarklet was clicked on, and whether to save it right away.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct QuickAddQuery {
+    pub url: String,
+    pub title: String,
+    /// Text selected on the page
+    pub description: Option<String>,
+    /// Comma separated
+    pub tags: Option<String>,
+    /// Saves the page with the API token kept in the browser instead of opening the form
+    pub save: bool,
+}
+
+/// Shown in the bookmarklet's window while the page is saved with the API token kept in the
+/// browser, closing it once it is.
+#[derive(Template)]
+#[template(path = "pages/quick_add_save.html")]
+pub struct QuickAddSaveTemplate<'a> {
+    pub title: &'a str,
+    pub auth_state: AuthState,
+    pub is_error: bool,
+    pub url: String,
+    pub bookmark_title: String,
+}
+
+/// The `javascript:` links to drag to the bookmarks bar, for an instance reached at an address.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bookmarklets {
+    /// Opens the add bookmark form for the current page
+    pub add: String,
+    /// Saves the current page right away, with the API token kept in the browser
+    pub save: String,
+}
+
+impl Bookmarklets {
+    /// The bookmarklets for the instance at `instance_url`, without a trailing slash.
+    pub fn new(instance_url: &str) -> Self {
+        // A JSON string is a valid JavaScript string literal, whatever the address has in it
+        let base = serde_json::to_string(instance_url).unwrap_or_default();
+        Self {
+            add: format!(
+                "javascript:(function(){{window.open({base}+'/add?url='+encodeURIComponent(location.href)\
+                 +'&title='+encodeURIComponent(document.title)+'&description='+encodeURIComponent(String(getSelection())),\
+                 'pagepouch','width=720,height=720');}})();"
+            ),
+            save: format!(
+                "javascript:(function(){{window.open({base}+'/add?save=true&url='+encodeURIComponent(location.href)\
+                 +'&title='+encodeURIComponent(document.title),'pagepouch','width=480,height=240');}})();"
+            ),
+        }
+    }
+}
+
+/// The address the instance is reached at: the configured public URL, or else the host the request
+/// was sent to, behind a proxy with the scheme it says.
+pub(crate) fn instance_url(public_url: Option<&str>, headers: &HeaderMap) -> String {
+    if let Some(public_url) = public_url {
+        return public_url.to_string();
+    }
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+    let host = header(header::HOST.as_str()).unwrap_or("localhost");
+    let scheme = header("x-forwarded-proto")
+        .and_then(|proto| proto.split(',').next())
+        .map_or("http", str::trim);
+    format!("{scheme}://{host}")
+}
+
+/// Handler for the bookmarklets: shows the add bookmark form filled in with the page, asking to log
+/// in first without a session, or the page that saves it right away with the API token
+pub async fn quick_add_handler(
+    State(state): ApiState,
+    jar: CookieJar,
+    TolerantQuery(params): TolerantQuery<QuickAddQuery>,
+) -> AppResult<Response> {
+    if params.save {
+        return Ok(HtmlTemplate(QuickAddSaveTemplate {
+            title: "Saving",
+            auth_state: AuthState::Anonymous,
+            is_error: false,
+            url: params.url,
+            bookmark_title: params.title,
+        })
+        .into_response());
+    }
+
+    let Ok(session) = check_session_cookie(&state, &jar).await else {
+        return Ok(Redirect::to("/login").into_response());
+    };
+    let fetch_enabled = fetch::is_fetch_allowed(&state.fetch_config, &state.db, session.user.user_id)
+        .await
+        .unwrap_or_else(|err| {
+            error!(?err, "🚨 Could not load fetch setting, not fetching titles.");
+            false
+        });
+
+    Ok(HtmlTemplate(BookmarkNewTemplate {
+        title: "Add Bookmark",
+        auth_state: AuthState::Authenticated,
+        is_error: false,
+        fetch_enabled,
+        prefill: NewBookmarkPrefill {
+            url: params.url,
+            title: params.title,
+            description: params.description.unwrap_or_default(),
+            tags: params.tags.unwrap_or_default(),
+        },
+        popup: true,
+    })
+    .into_response())
+}
+
+/// Handler for the bookmarklet that saves right away, with an API token that can create bookmarks
+/// in the `Authorization` header instead of a session
+pub async fn quick_add_save_handler(
+    State(state): ApiState,
+    headers: HeaderMap,
+    Json(request): Json<BatchBookmark>,
+) -> AppResult<impl IntoResponse> {
+    let authorization = headers.get(header::AUTHORIZATION).ok_or_else(|| {
+        AppError::unauthorized(anyhow!("No API token for the bookmarklet"))
+            .user_message("Keep an API token for the bookmarklet on the settings page first.")
+    })?;
+    let (user, token) = find_api_token(&state, bearer_token(authorization)?).await?;
+    if !token.scope.allows(&Method::POST) {
+        return Err(AppError::with_status(
+            anyhow!("API token '{}' of '{}' can't create bookmarks", token.name, user.username),
+            StatusCode::FORBIDDEN,
+        )
+        .user_message("This token can't save bookmarks. Use one that can create them."));
+    }
+    state.api_usage.record(token.token_id, chrono::Utc::now().date_naive(), "POST /add");
+
+    let origin = BookmarkOrigin {
+        source: BookmarkSource::Bookmarklet,
+        detail: Some(token.name.clone()),
+    };
+    let bookmark_id = save_bookmark(&state, &user, Some(&token), request, &origin).await?;
+    info!(user = user.username, %bookmark_id, "📥 Created bookmark with the bookmarklet.");
+
+    Ok((
+        StatusCode::CREATED,
+        Json(find_bookmark(&state.db, user.user_id, bookmark_id).await?),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    #[test]
+    fn test_instance_url() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::HOST, HeaderValue::from_static("pp.example.com:8888"));
+        assert_eq!(
+            instance_url(Some("https://bookmarks.example.com"), &headers),
+            "https://bookmarks.example.com"
+        );
+        assert_eq!(instance_url(None, &headers), "http://pp.example.com:8888");
+        headers.insert("x-forwarded-proto", HeaderValue::from_static("https, http"));
+        assert_eq!(instance_url(None, &headers), "https://pp.example.com:8888");
+    }
+
+    #[test]
+    fn test_bookmarklets() {
+        let bookmarklets = Bookmarklets::new("https://pp.example.com");
+        assert!(
+            bookmarklets
+                .add
+                .starts_with("javascript:(function(){window.open(\"https://pp.example.com\"+'/add?url='")
+        );
+        assert!(!bookmarklets.add.contains("save=true"));
+        assert!(bookmarklets.save.contains("'/add?save=true&url='"));
+        assert!(!bookmarklets.save.contains("token"));
+
+        let bookmarklets = Bookmarklets::new("https://pp.example.com/it's");
+        assert!(bookmarklets.add.contains("\"https://pp.example.com/it's\""));
+    }
+}
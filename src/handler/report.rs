@@ -0,0 +1,38 @@
+//! Collection health report handler and template.
+
+use askama::Template;
+use axum::{Extension, extract::State, response::IntoResponse};
+
+use crate::{
+    ApiState,
+    db::{
+        bookmarks::{self, CollectionHealth},
+        users::User,
+    },
+    error::AppResult,
+    handler::{AuthState, HtmlTemplate},
+};
+
+#[derive(Template)]
+#[template(path = "pages/report.html")]
+pub struct ReportTemplate<'a> {
+    pub title: &'a str,
+    pub auth_state: AuthState,
+    pub is_error: bool,
+    pub health: CollectionHealth,
+    /// Whether links are checked on this instance, so broken ones can be found
+    pub link_check_enabled: bool,
+}
+
+/// Handler for the collection health report, listing bookmarks that could use tidying up
+pub async fn report_handler(State(state): ApiState, Extension(user): Extension<User>) -> AppResult<impl IntoResponse> {
+    let health = bookmarks::get_collection_health(&state.db, user.user_id).await?;
+
+    Ok(HtmlTemplate(ReportTemplate {
+        title: "Collection health",
+        auth_state: AuthState::Authenticated,
+        is_error: false,
+        health,
+        link_check_enabled: state.link_check_enabled,
+    }))
+}
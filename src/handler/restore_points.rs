@@ -0,0 +1,111 @@
+//! Restore point handlers and templates, for rolling bookmarks back after a bulk change.
+
+use anyhow::anyhow;
+use askama::Template;
+use axum::{
+    Extension, Form,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use serde::Deserialize;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{
+    ApiState,
+    db::{
+        Db,
+        restore_points::{self, RestorePoint},
+        users::User,
+    },
+    error::{AppError, AppResult},
+    handler::HtmlTemplate,
+    restore::MAX_RESTORE_POINTS,
+};
+
+/// Longest label a restore point made by hand can have, in characters.
+const MAX_LABEL_LENGTH: usize = 100;
+
+/// The list of restore points on the settings page, re-rendered after each change.
+#[derive(Template)]
+#[template(path = "components/restore_point_settings.html")]
+pub struct RestorePointSettingsTemplate {
+    pub restore_points: Vec<RestorePoint>,
+    pub max_restore_points: i64,
+    /// Label of the restore point just rolled back to, if any
+    pub restored: Option<String>,
+}
+
+impl RestorePointSettingsTemplate {
+    pub async fn for_user(db: &Db, user_id: Uuid, restored: Option<String>) -> AppResult<Self> {
+        Ok(Self {
+            restore_points: restore_points::get_restore_points(db, user_id).await?,
+            max_restore_points: MAX_RESTORE_POINTS,
+            restored,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RestorePointForm {
+    #[serde(default)]
+    pub label: String,
+}
+
+fn restore_point_not_found(restore_point_id: Uuid) -> AppError {
+    AppError::with_status(
+        anyhow!("Restore point {restore_point_id} not found for user"),
+        StatusCode::NOT_FOUND,
+    )
+}
+
+/// API handler for saving the current state of the user's bookmarks as a restore point
+pub async fn create_restore_point_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Form(form): Form<RestorePointForm>,
+) -> AppResult<impl IntoResponse> {
+    let label: String = match form.label.trim() {
+        "" => "Saved by hand".to_string(),
+        label => label.chars().take(MAX_LABEL_LENGTH).collect(),
+    };
+    let restore_point_id = restore_points::create_restore_point(&state.db, user.user_id, &label).await?;
+    info!(user = user.username, %restore_point_id, "🛟 Created restore point.");
+
+    Ok(HtmlTemplate(
+        RestorePointSettingsTemplate::for_user(&state.db, user.user_id, None).await?,
+    ))
+}
+
+/// API handler for rolling the user's bookmarks back to a restore point
+pub async fn restore_restore_point_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Path(restore_point_id): Path<Uuid>,
+) -> AppResult<impl IntoResponse> {
+    let Some(label) = restore_points::roll_back_to(&state.db, user.user_id, restore_point_id).await? else {
+        return Err(restore_point_not_found(restore_point_id));
+    };
+    info!(user = user.username, %restore_point_id, "🛟 Rolled back to restore point.");
+
+    Ok(HtmlTemplate(
+        RestorePointSettingsTemplate::for_user(&state.db, user.user_id, Some(label)).await?,
+    ))
+}
+
+/// API handler for deleting a restore point
+pub async fn delete_restore_point_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Path(restore_point_id): Path<Uuid>,
+) -> AppResult<impl IntoResponse> {
+    if !restore_points::delete_restore_point(&state.db, user.user_id, restore_point_id).await? {
+        return Err(restore_point_not_found(restore_point_id));
+    }
+    info!(user = user.username, %restore_point_id, "🛟 Deleted restore point.");
+
+    Ok(HtmlTemplate(
+        RestorePointSettingsTemplate::for_user(&state.db, user.user_id, None).await?,
+    ))
+}
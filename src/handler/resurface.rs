@@ -0,0 +1,43 @@
+//! Old bookmarks resurfaced on the home page, see [`crate::resurface`].
+
+use askama::Template;
+use axum::{Extension, extract::State, response::IntoResponse};
+use chrono::Utc;
+
+use crate::{
+    ApiState,
+    db::{self, bookmarks::BookmarkItem, users::User},
+    error::AppResult,
+    handler::HtmlTemplate,
+    resurface::{self, RESURFACE_COUNT},
+};
+
+/// Most bookmarks loaded to pick the resurfaced ones from.
+const CANDIDATE_LIMIT: i64 = 200;
+
+/// The resurfaced bookmarks for the sidebar, or nothing if there are none.
+#[derive(Template)]
+#[template(path = "components/resurfaced.html")]
+pub struct ResurfacedTemplate {
+    pub bookmarks: Vec<ResurfacedBookmark>,
+}
+
+pub struct ResurfacedBookmark {
+    /// When it was saved, e.g. "1 year ago"
+    pub saved: String,
+    pub bookmark: BookmarkItem,
+}
+
+/// Handler for the fragment of bookmarks saved around this time months or years ago
+pub async fn resurface_handler(State(state): ApiState, Extension(user): Extension<User>) -> AppResult<impl IntoResponse> {
+    let windows = resurface::windows(Utc::now().date_naive());
+    let periods: Vec<(i64, i64)> = windows.iter().map(|window| (window.start, window.end)).collect();
+    let candidates = db::bookmarks::get_saved_between(&state.db, user.user_id, &periods, CANDIDATE_LIMIT).await?;
+
+    Ok(HtmlTemplate(ResurfacedTemplate {
+        bookmarks: resurface::pick(&windows, candidates, RESURFACE_COUNT)
+            .into_iter()
+            .map(|(saved, bookmark)| ResurfacedBookmark { saved, bookmark })
+            .collect(),
+    }))
+}
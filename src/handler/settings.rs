@@ -1,13 +1,59 @@
 //! Settings-related handlers and templates.
 
+use anyhow::anyhow;
 use askama::Template;
-use axum::response::IntoResponse;
+use axum::{
+    Extension, Form, Json,
+    extract::{Path, State},
+    http::{
+        HeaderMap, StatusCode,
+        header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+    },
+    response::IntoResponse,
+};
 use axum_extra::extract::CookieJar;
+use chrono::{DateTime, Utc};
 use cookie::time::Duration;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use uuid::Uuid;
 
-use crate::handler::{AuthState, HtmlTemplate};
+use crate::{
+    ApiState,
+    api_usage::first_usage_day,
+    db::{
+        Db,
+        api_tokens::{self, ApiToken, TokenScope, TokenUsage},
+        blocked_domains::{self, BlockedDomain},
+        bookmarks::{self, SortOrder},
+        collections::{self, SmartCollection},
+        restore_points::{self, RestorePoint},
+        settings::{self, DigestFrequency, ListDensity, ListDisplay, UserSettings},
+        tags::{self, TagColors},
+        url_rules::{self, UrlCleaningRule},
+        users::{self, User},
+    },
+    encryption::generate_api_token,
+    error::{AppError, AppResult},
+    export::{AccountExport, EXPORT_VERSION},
+    features::Feature,
+    handler::{
+        AuthState, HtmlTemplate,
+        bookmarks::parse_tags,
+        guest::{GUEST_LINK_DURATIONS, SharedGuestLink, shared_guest_links},
+        public_tags::{PublicTag, public_tags},
+        quick_add::{Bookmarklets, instance_url},
+        shares::{SharedBookmark, shared_bookmarks},
+        subscriptions::{SharedSubscription, shared_subscriptions},
+        two_factor::TwoFactorView,
+    },
+    restore::MAX_RESTORE_POINTS,
+    search::{SearchDefaults, SearchLogic, SearchQuery},
+    translate::normalize_language,
+    vault,
+};
 
+#[allow(clippy::struct_excessive_bools)] // independent flags for the template
 #[derive(Template)]
 #[template(path = "pages/settings.html")]
 pub struct SettingsTemplate<'a> {
@@ -15,6 +61,53 @@ pub struct SettingsTemplate<'a> {
     pub auth_state: AuthState,
     pub is_error: bool,
     pub current_theme: String,
+    /// Whether the instance allows fetching saved URLs at all
+    pub instance_fetch_enabled: bool,
+    /// Whether this user opted out of fetching saved URLs
+    pub disable_fetch: bool,
+    /// Which fields the bookmark list shows
+    pub list: ListDisplay,
+    /// Whether old bookmarks are resurfaced on the home page
+    pub resurface: bool,
+    /// Whether the instance can send email, so digests can be turned on
+    pub digest_enabled: bool,
+    pub digest: DigestFrequency,
+    /// How searches are read when the query doesn't say
+    pub search: SearchDefaults,
+    pub default_sort: SortOrder,
+    pub collections: Vec<SmartCollection>,
+    pub url_rules: Vec<UrlCleaningRule>,
+    pub tag_colors: TagColors,
+    /// The user's tags, to pick one to color
+    pub tag_names: Vec<String>,
+    /// The user's own blocklist, after the instance-wide one
+    pub blocked_domains: Vec<BlockedDomain>,
+    /// Always false here; the blocklist component is shared with the admin dashboard
+    pub instance_blocklist: bool,
+    /// Whether to link to the admin dashboard
+    pub is_admin: bool,
+    /// Whether the instance has a translation service configured
+    pub translation_enabled: bool,
+    pub preferred_language: String,
+    /// The user's email address, or empty
+    pub email: String,
+    pub two_factor: TwoFactorView,
+    pub guest_links: Vec<SharedGuestLink>,
+    /// Choices of how long a new guest link works for, in hours
+    pub guest_link_durations: &'a [(i64, &'a str)],
+    pub public_tags: Vec<PublicTag>,
+    pub shared_bookmarks: Vec<SharedBookmark>,
+    pub subscriptions: Vec<SharedSubscription>,
+    pub api_tokens: Vec<ApiTokenView>,
+    /// Always `None` here; a new token is only shown when it's created
+    pub new_token: Option<String>,
+    pub bookmarklets: Bookmarklets,
+    /// Labels of the features still being rolled out that are enabled for this user
+    pub early_access: Vec<&'static str>,
+    pub restore_points: Vec<RestorePoint>,
+    pub max_restore_points: i64,
+    /// Always `None` here; only set right after rolling back
+    pub restored: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -22,19 +115,358 @@ pub struct ThemeUpdate {
     pub theme: String, // "light" or "dark" or "auto"
 }
 
+#[derive(Deserialize)]
+pub struct FetchSettingUpdate {
+    /// Checkbox value, only sent when checked
+    pub disable_fetch: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ResurfaceSettingUpdate {
+    /// Checkbox value, only sent when checked
+    pub resurface: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct LanguageSettingUpdate {
+    #[serde(default)]
+    pub preferred_language: String,
+}
+
+#[derive(Deserialize)]
+pub struct EmailUpdate {
+    #[serde(default)]
+    pub email: String,
+}
+
+#[derive(Deserialize)]
+pub struct SearchSettingsUpdate {
+    #[serde(default)]
+    pub logic: SearchLogic,
+    #[serde(default)]
+    pub sort: SortOrder,
+    // Checkbox values, only sent when checked
+    pub include_archived: Option<String>,
+    pub fuzzy: Option<String>,
+    pub page_text: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct DigestSettingUpdate {
+    pub digest: DigestFrequency,
+}
+
+#[derive(Deserialize)]
+pub struct ListSettingsUpdate {
+    #[serde(default)]
+    pub density: ListDensity,
+    // Checkbox values, only sent when checked
+    pub show_description: Option<String>,
+    pub show_tags: Option<String>,
+    pub show_domain: Option<String>,
+    pub show_date: Option<String>,
+    pub show_favicon: Option<String>,
+    pub infinite_scroll: Option<String>,
+}
+
 /// Handler for the settings page
-pub async fn settings_handler(jar: CookieJar) -> impl IntoResponse {
+pub async fn settings_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    jar: CookieJar,
+    headers: HeaderMap,
+) -> AppResult<impl IntoResponse> {
     // Get current theme from cookie, default to "auto"
     let current_theme = jar
         .get("theme")
         .map_or_else(|| "auto".to_string(), |cookie| cookie.value().to_string());
+    let user_settings = settings::get_user_settings(&state.db, user.user_id).await?;
+    let collections = collections::get_user_collections(&state.db, user.user_id).await?;
+    let url_rules = url_rules::get_user_rules(&state.db, user.user_id).await?;
+    let tag_colors = tags::get_tag_colors(&state.db, user.user_id).await?;
+    let tag_names = tags::get_user_tags(&state.db, user.user_id)
+        .await?
+        .into_iter()
+        .map(|tag| tag.name)
+        .collect();
+    let blocked_domains = blocked_domains::get_blocklist(&state.db, Some(user.user_id)).await?;
+    let guest_links = shared_guest_links(&state, user.user_id).await?;
+    let public_tags = public_tags(&state, &user).await?;
+    let shared_bookmarks = shared_bookmarks(&state, user.user_id).await?;
+    let subscriptions = shared_subscriptions(&state, user.user_id).await?;
+    let api_tokens = api_token_views(&state.db, user.user_id).await?;
+    let early_access = state.features.enabled_for(&state.db, user.user_id).await?;
+    let email = users::get_email(&state.db, user.user_id).await?;
+    let two_factor = TwoFactorView::for_user(&state, &user).await?;
+    let restore_points = restore_points::get_restore_points(&state.db, user.user_id).await?;
 
-    HtmlTemplate(SettingsTemplate {
+    Ok(HtmlTemplate(SettingsTemplate {
         title: "Settings",
         auth_state: crate::handler::AuthState::Authenticated,
         is_error: false,
         current_theme,
-    })
+        instance_fetch_enabled: state.fetch_config.enabled,
+        disable_fetch: user_settings.disable_fetch,
+        list: user_settings.list,
+        resurface: user_settings.resurface,
+        digest_enabled: state.mailer.is_some(),
+        digest: user_settings.digest,
+        search: user_settings.search,
+        default_sort: user_settings.default_sort,
+        collections,
+        url_rules,
+        tag_colors,
+        tag_names,
+        blocked_domains,
+        instance_blocklist: false,
+        is_admin: user.is_admin,
+        translation_enabled: state.translator.is_some(),
+        preferred_language: user_settings.preferred_language.unwrap_or_default(),
+        email: email.unwrap_or_default(),
+        two_factor,
+        guest_links,
+        guest_link_durations: &GUEST_LINK_DURATIONS,
+        public_tags,
+        shared_bookmarks,
+        subscriptions,
+        api_tokens,
+        new_token: None,
+        bookmarklets: Bookmarklets::new(&instance_url(state.public_url.as_deref(), &headers)),
+        early_access: early_access.into_iter().map(Feature::label).collect(),
+        restore_points,
+        max_restore_points: MAX_RESTORE_POINTS,
+        restored: None,
+    }))
+}
+
+/// API handler for opting out of (or back into) fetching saved URLs
+pub async fn update_fetch_setting_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    axum::extract::Form(update): axum::extract::Form<FetchSettingUpdate>,
+) -> AppResult<impl IntoResponse> {
+    let mut user_settings = settings::get_user_settings(&state.db, user.user_id).await?;
+    user_settings.disable_fetch = update.disable_fetch.is_some();
+    settings::update_user_settings(&state.db, user.user_id, &user_settings).await?;
+    info!(
+        user = user.username,
+        disable_fetch = user_settings.disable_fetch,
+        "🔒 Updated fetch setting."
+    );
+
+    Ok("OK")
+}
+
+/// API handler for turning resurfacing of old bookmarks on the home page on or off
+pub async fn update_resurface_setting_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    axum::extract::Form(update): axum::extract::Form<ResurfaceSettingUpdate>,
+) -> AppResult<impl IntoResponse> {
+    let mut user_settings = settings::get_user_settings(&state.db, user.user_id).await?;
+    user_settings.resurface = update.resurface.is_some();
+    settings::update_user_settings(&state.db, user.user_id, &user_settings).await?;
+    info!(
+        user = user.username,
+        resurface = user_settings.resurface,
+        "🔁 Updated resurfacing setting."
+    );
+
+    Ok("OK")
+}
+
+/// API handler for choosing how often to get the email digest of new bookmarks
+pub async fn update_digest_setting_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    axum::extract::Form(update): axum::extract::Form<DigestSettingUpdate>,
+) -> AppResult<impl IntoResponse> {
+    if state.mailer.is_none() {
+        return Err(AppError::bad_request("This instance can't send email."));
+    }
+    let mut user_settings = settings::get_user_settings(&state.db, user.user_id).await?;
+    user_settings.digest = update.digest;
+    settings::update_user_settings(&state.db, user.user_id, &user_settings).await?;
+    info!(
+        user = user.username,
+        digest = user_settings.digest.as_str(),
+        "📧 Updated digest setting."
+    );
+
+    Ok("OK")
+}
+
+/// API handler for setting or removing the email address the user can log in with
+pub async fn update_email_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    axum::extract::Form(update): axum::extract::Form<EmailUpdate>,
+) -> AppResult<impl IntoResponse> {
+    let email = match update.email.trim() {
+        "" => None,
+        email if users::is_valid_email(email) => Some(email),
+        _ => return Err(AppError::bad_request("That doesn't look like an email address.")),
+    };
+
+    if !users::set_email(&state.db, user.user_id, email).await? {
+        return Err(
+            AppError::with_status(anyhow!("Email address already used by another user"), StatusCode::CONFLICT)
+                .user_message("Another account already uses that email address."),
+        );
+    }
+    info!(user = user.username, has_email = email.is_some(), "📧 Updated email address.");
+
+    Ok("OK")
+}
+
+/// API handler for choosing the language bookmarks are translated into
+pub async fn update_language_setting_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    axum::extract::Form(update): axum::extract::Form<LanguageSettingUpdate>,
+) -> AppResult<impl IntoResponse> {
+    let preferred_language = match update.preferred_language.trim() {
+        "" => None,
+        language => Some(normalize_language(language).ok_or_else(|| AppError::bad_request("Use a language code such as 'en' or 'de'."))?),
+    };
+
+    let mut user_settings = settings::get_user_settings(&state.db, user.user_id).await?;
+    user_settings.preferred_language = preferred_language;
+    settings::update_user_settings(&state.db, user.user_id, &user_settings).await?;
+    info!(
+        user = user.username,
+        preferred_language = user_settings.preferred_language,
+        "🌍 Updated translation language."
+    );
+
+    Ok("OK")
+}
+
+/// API handler for choosing which fields the bookmark list shows
+pub async fn update_list_settings_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    axum::extract::Form(update): axum::extract::Form<ListSettingsUpdate>,
+) -> AppResult<impl IntoResponse> {
+    let mut user_settings = settings::get_user_settings(&state.db, user.user_id).await?;
+    user_settings.list = ListDisplay {
+        density: update.density,
+        show_description: update.show_description.is_some(),
+        show_tags: update.show_tags.is_some(),
+        show_domain: update.show_domain.is_some(),
+        show_date: update.show_date.is_some(),
+        show_favicon: update.show_favicon.is_some(),
+        infinite_scroll: update.infinite_scroll.is_some(),
+    };
+    settings::update_user_settings(&state.db, user.user_id, &user_settings).await?;
+    info!(user = user.username, list = ?user_settings.list, "📋 Updated bookmark list settings.");
+
+    Ok("OK")
+}
+
+/// API handler for changing how the user's searches are read, and the list's default order
+pub async fn update_search_settings_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    axum::extract::Form(update): axum::extract::Form<SearchSettingsUpdate>,
+) -> AppResult<impl IntoResponse> {
+    let mut user_settings = settings::get_user_settings(&state.db, user.user_id).await?;
+    user_settings.search = SearchDefaults {
+        logic: update.logic,
+        include_archived: update.include_archived.is_some(),
+        fuzzy: update.fuzzy.is_some(),
+        page_text: update.page_text.is_some(),
+    };
+    user_settings.default_sort = update.sort;
+    settings::update_user_settings(&state.db, user.user_id, &user_settings).await?;
+    info!(
+        user = user.username,
+        search = ?user_settings.search,
+        sort = user_settings.default_sort.as_str(),
+        "🔎 Updated search settings."
+    );
+
+    Ok("OK")
+}
+
+/// API handler for downloading the user's settings and smart collections as JSON
+pub async fn export_settings_handler(State(state): ApiState, Extension(user): Extension<User>) -> AppResult<impl IntoResponse> {
+    let user_settings = settings::get_user_settings(&state.db, user.user_id).await?;
+    let collections = collections::get_user_collections(&state.db, user.user_id).await?;
+    let export = AccountExport::new(user_settings, collections);
+    info!(user = user.username, "📦 Exported settings.");
+
+    let headers = [(CONTENT_DISPOSITION, "attachment; filename=\"pagepouch-settings.json\"")];
+    Ok((headers, Json(export)))
+}
+
+/// API handler for downloading all bookmarks as a zip of markdown notes, e.g. for an Obsidian vault
+pub async fn export_vault_handler(State(state): ApiState, Extension(user): Extension<User>) -> AppResult<impl IntoResponse> {
+    let bookmarks = bookmarks::get_all_bookmarks(&state.db, user.user_id).await?;
+    let zip = vault::write_vault(&bookmarks)?;
+    info!(
+        user = user.username,
+        bookmarks = bookmarks.len(),
+        "📦 Exported bookmarks as markdown."
+    );
+
+    let headers = [
+        (CONTENT_TYPE, "application/zip"),
+        (CONTENT_DISPOSITION, "attachment; filename=\"pagepouch-markdown.zip\""),
+    ];
+    Ok((headers, zip))
+}
+
+/// Summary of what an import changed
+#[derive(Serialize)]
+pub struct ImportSummary {
+    pub collections_added: usize,
+    /// Collections skipped because one with the same name already exists
+    pub collections_skipped: usize,
+}
+
+/// API handler for restoring settings and smart collections from an export
+///
+/// Settings are replaced; collections are added unless one with the same name already exists.
+pub async fn import_settings_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Json(import): Json<AccountExport>,
+) -> AppResult<impl IntoResponse> {
+    if import.version > EXPORT_VERSION {
+        return Err(AppError::bad_request(format!(
+            "This export is from a newer version of PagePouch (format {}), please upgrade first.",
+            import.version
+        )));
+    }
+
+    let user_settings = UserSettings::from(import.settings);
+    settings::update_user_settings(&state.db, user.user_id, &user_settings).await?;
+
+    let mut summary = ImportSummary {
+        collections_added: 0,
+        collections_skipped: 0,
+    };
+    for collection in import.smart_collections {
+        let name = collection.name.trim();
+        let query = collection.query.trim();
+        if name.is_empty() || SearchQuery::parse_complete(query).is_empty() {
+            summary.collections_skipped += 1;
+            continue;
+        }
+        match collections::create_collection(&state.db, user.user_id, name, query).await? {
+            Some(_) => summary.collections_added += 1,
+            None => summary.collections_skipped += 1,
+        }
+    }
+    info!(
+        user = user.username,
+        added = summary.collections_added,
+        skipped = summary.collections_skipped,
+        "📦 Imported settings."
+    );
+
+    Ok(Json(summary))
 }
 
 /// API handler for updating theme preference
@@ -63,3 +495,123 @@ pub async fn update_theme_handler(
     // Just return success - JavaScript handles the UI update
     (jar, "OK")
 }
+
+/// Longest API token name accepted.
+const MAX_TOKEN_NAME_LENGTH: usize = 100;
+
+/// The list of API tokens on the settings page, re-rendered after each change.
+#[derive(Template)]
+#[template(path = "components/api_token_settings.html")]
+pub struct ApiTokenSettingsTemplate {
+    pub api_tokens: Vec<ApiTokenView>,
+    /// A token just created, shown this once
+    pub new_token: Option<String>,
+}
+
+/// An API token formatted for display.
+pub struct ApiTokenView {
+    pub token: ApiToken,
+    pub created: String,
+    /// When it was last used, or "never"
+    pub last_used: String,
+    /// Requests made with it in the last [`crate::api_usage::USAGE_DAYS`] days, newest day first
+    pub usage: Vec<TokenUsage>,
+    /// Total of `usage`
+    pub requests: i64,
+}
+
+impl ApiTokenView {
+    fn new(token: ApiToken, usage: Vec<TokenUsage>) -> Self {
+        Self {
+            created: format_timestamp(token.created_at, "%Y-%m-%d"),
+            last_used: token.last_used_at.map_or_else(
+                || "never".to_string(),
+                |last_used_at| format_timestamp(last_used_at, "%Y-%m-%d %H:%M UTC"),
+            ),
+            requests: usage.iter().map(|usage| usage.requests).sum(),
+            usage,
+            token,
+        }
+    }
+}
+
+fn format_timestamp(timestamp: i64, format: &str) -> String {
+    DateTime::from_timestamp(timestamp, 0).map_or_else(String::new, |dt| dt.format(format).to_string())
+}
+
+#[derive(Deserialize)]
+pub struct ApiTokenForm {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub scope: String,
+    /// Comma-separated tags to limit the token to; empty for all bookmarks
+    #[serde(default)]
+    pub tags: String,
+}
+
+/// Lists a user's API tokens for display, with their usage.
+pub(crate) async fn api_token_views(db: &Db, user_id: Uuid) -> AppResult<Vec<ApiTokenView>> {
+    let tokens = api_tokens::get_api_tokens(db, user_id).await?;
+    let usage = api_tokens::get_token_usage(db, user_id, &first_usage_day(Utc::now().date_naive())).await?;
+    Ok(tokens
+        .into_iter()
+        .map(|token| {
+            let token_usage = usage.iter().filter(|usage| usage.token_id == token.token_id).cloned().collect();
+            ApiTokenView::new(token, token_usage)
+        })
+        .collect())
+}
+
+/// API handler for creating an API token, which is shown once in the response
+pub async fn create_api_token_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Form(form): Form<ApiTokenForm>,
+) -> AppResult<impl IntoResponse> {
+    let name = form.name.trim();
+    if name.is_empty() {
+        return Err(AppError::bad_request("Give the token a name, e.g. what it's for."));
+    }
+    if name.chars().count() > MAX_TOKEN_NAME_LENGTH {
+        return Err(AppError::bad_request(format!(
+            "Token names can be at most {MAX_TOKEN_NAME_LENGTH} characters."
+        )));
+    }
+
+    let scope: TokenScope = form
+        .scope
+        .parse()
+        .map_err(|_| AppError::bad_request("Choose what the token can do."))?;
+    let tags = parse_tags(&form.tags);
+
+    let token = generate_api_token();
+    let token_hash = state.encryption.hash_api_token(&token)?;
+    let token_id = api_tokens::create_api_token(&state.db, user.user_id, name, &token_hash, scope, &tags).await?;
+    info!(user = user.username, %token_id, scope = scope.as_str(), tags = tags.len(), "🔑 Created API token.");
+
+    Ok(HtmlTemplate(ApiTokenSettingsTemplate {
+        api_tokens: api_token_views(&state.db, user.user_id).await?,
+        new_token: Some(token),
+    }))
+}
+
+/// API handler for revoking an API token
+pub async fn delete_api_token_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Path(token_id): Path<Uuid>,
+) -> AppResult<impl IntoResponse> {
+    if !api_tokens::delete_api_token(&state.db, user.user_id, token_id).await? {
+        return Err(AppError::with_status(
+            anyhow!("API token {token_id} not found"),
+            StatusCode::NOT_FOUND,
+        ));
+    }
+    info!(user = user.username, %token_id, "🔑 Revoked API token.");
+
+    Ok(HtmlTemplate(ApiTokenSettingsTemplate {
+        api_tokens: api_token_views(&state.db, user.user_id).await?,
+        new_token: None,
+    }))
+}
@@ -0,0 +1,163 @@
+//! Bookmark share handlers: public links showing a single bookmark to anyone who has them, until
+//! they're revoked.
+
+use anyhow::anyhow;
+use askama::Template;
+use axum::{
+    Extension,
+    extract::{Path, State},
+    http::{StatusCode, header},
+    response::IntoResponse,
+};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::{
+    ApiState, AppState,
+    db::{
+        bookmark_shares::{self, BookmarkShare, ShareAccess, ShareToken},
+        bookmarks::{self, BookmarkId, BookmarkItem},
+        users::User,
+    },
+    error::{AppError, AppResult},
+    handler::{AuthState, HtmlTemplate, bookmarks::find_bookmark},
+};
+
+/// A shared bookmark on the settings page, with the address to hand out.
+#[derive(Clone, Debug)]
+pub struct SharedBookmark {
+    pub share: BookmarkShare,
+    /// Path of the public page, including the signed token
+    pub path: String,
+}
+
+/// The list of shared bookmarks on the settings page, re-rendered after each change.
+#[derive(Template)]
+#[template(path = "components/shared_bookmark_settings.html")]
+pub struct SharedBookmarkSettingsTemplate {
+    pub shared_bookmarks: Vec<SharedBookmark>,
+}
+
+/// A bookmark's share link, or that it isn't shared, shown under the bookmark in the list.
+#[derive(Template)]
+#[template(path = "components/bookmark_share.html")]
+pub struct BookmarkShareTemplate {
+    pub bookmark_id: BookmarkId,
+    /// Path of the public page, if the bookmark is shared
+    pub path: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "pages/shared_bookmark.html")]
+pub struct SharedBookmarkTemplate<'a> {
+    pub title: &'a str,
+    pub auth_state: AuthState,
+    pub is_error: bool,
+    /// Who shared it
+    pub username: String,
+    pub bookmark: BookmarkItem,
+}
+
+/// The path of a share's public page, with a signed token.
+fn share_path(state: &AppState, share_id: Uuid) -> AppResult<String> {
+    let token = state.encryption.sign_token(ShareToken { share_id })?;
+    Ok(format!("/s/{token}"))
+}
+
+/// Lists a user's shared bookmarks, signing a token for each.
+pub(crate) async fn shared_bookmarks(state: &AppState, user_id: Uuid) -> AppResult<Vec<SharedBookmark>> {
+    bookmark_shares::get_shares(&state.db, user_id)
+        .await?
+        .into_iter()
+        .map(|share| {
+            Ok(SharedBookmark {
+                path: share_path(state, share.share_id)?,
+                share,
+            })
+        })
+        .collect()
+}
+
+/// API handler for sharing a bookmark, showing its public link
+///
+/// Sharing a bookmark that's already shared shows the link it has.
+pub async fn share_bookmark_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Path(bookmark_id): Path<BookmarkId>,
+) -> AppResult<impl IntoResponse> {
+    if find_bookmark(&state.db, user.user_id, bookmark_id).await?.is_private {
+        return Err(AppError::bad_request("Private bookmarks can't be shared."));
+    }
+    let share_id = bookmark_shares::create_share(&state.db, user.user_id, bookmark_id)
+        .await?
+        .ok_or_else(|| AppError::with_status(anyhow!("Bookmark {bookmark_id} not found for user"), StatusCode::NOT_FOUND))?;
+    info!(user = user.username, %bookmark_id, %share_id, "🔗 Shared bookmark.");
+
+    Ok(HtmlTemplate(BookmarkShareTemplate {
+        bookmark_id,
+        path: Some(share_path(&state, share_id)?),
+    }))
+}
+
+/// API handler for no longer sharing a bookmark from the bookmark list, revoking its link
+pub async fn unshare_bookmark_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Path(bookmark_id): Path<BookmarkId>,
+) -> AppResult<impl IntoResponse> {
+    if bookmark_shares::delete_share(&state.db, user.user_id, bookmark_id).await? {
+        info!(user = user.username, %bookmark_id, "🔗 Stopped sharing bookmark.");
+    }
+
+    Ok(HtmlTemplate(BookmarkShareTemplate { bookmark_id, path: None }))
+}
+
+/// API handler for no longer sharing a bookmark from the settings page, revoking its link
+pub async fn delete_share_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Path(bookmark_id): Path<BookmarkId>,
+) -> AppResult<impl IntoResponse> {
+    if !bookmark_shares::delete_share(&state.db, user.user_id, bookmark_id).await? {
+        return Err(AppError::with_status(
+            anyhow!("Bookmark {bookmark_id} isn't shared"),
+            StatusCode::NOT_FOUND,
+        ));
+    }
+    info!(user = user.username, %bookmark_id, "🔗 Stopped sharing bookmark.");
+
+    Ok(HtmlTemplate(SharedBookmarkSettingsTemplate {
+        shared_bookmarks: shared_bookmarks(&state, user.user_id).await?,
+    }))
+}
+
+/// Handler for the public page of a shared bookmark
+///
+/// Only reachable through [`crate::handler::middlewares::share_middleware`], which checks the
+/// token.
+pub async fn shared_bookmark_handler(State(state): ApiState, Extension(access): Extension<ShareAccess>) -> AppResult<impl IntoResponse> {
+    debug!(share_id = %access.share_id, username = access.username, "Shared bookmark opened");
+
+    let Some(bookmark) = bookmarks::get_bookmark(&state.db, access.user_id, access.bookmark_id).await? else {
+        return Err(
+            AppError::with_status(anyhow!("Shared bookmark {} is in the trash", access.bookmark_id), StatusCode::GONE)
+                .user_message("This bookmark is no longer shared."),
+        );
+    };
+    if let Err(err) = bookmark_shares::mark_viewed(&state.db, access.share_id).await {
+        warn!(?err, share_id = %access.share_id, "Could not note when the shared bookmark was opened.");
+    }
+
+    // Keep the token out of the Referer header of the page the bookmark links to
+    Ok((
+        [(header::REFERRER_POLICY, "no-referrer")],
+        HtmlTemplate(SharedBookmarkTemplate {
+            title: "Shared bookmark",
+            auth_state: AuthState::Anonymous,
+            is_error: false,
+            username: access.username,
+            bookmark,
+        }),
+    ))
+}
@@ -0,0 +1,75 @@
+//! Handlers for saved copies of bookmarked pages, see [`crate::snapshot`].
+
+use anyhow::anyhow;
+use axum::{
+    Extension,
+    extract::{Path, State},
+    http::{
+        StatusCode,
+        header::{CACHE_CONTROL, CONTENT_SECURITY_POLICY, CONTENT_TYPE, REFERRER_POLICY, X_CONTENT_TYPE_OPTIONS},
+    },
+    response::IntoResponse,
+};
+use tracing::{info, warn};
+
+use crate::{
+    ApiState,
+    db::{bookmarks::BookmarkId, snapshots, users::User},
+    error::{AppError, AppResult},
+    handler::{
+        HtmlTemplate,
+        bookmarks::{find_bookmark, item_template},
+    },
+    snapshot::{self, SNAPSHOT_CSP},
+};
+
+/// Handler for the saved copy of a bookmarked page
+///
+/// It's served locked down, so nothing in the page can run or reach out, see [`SNAPSHOT_CSP`].
+pub async fn bookmark_snapshot_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Path(bookmark_id): Path<BookmarkId>,
+) -> AppResult<impl IntoResponse> {
+    let stored = snapshots::get_snapshot(&state.db, user.user_id, bookmark_id)
+        .await?
+        .ok_or_else(|| AppError::with_status(anyhow!("No snapshot of bookmark {bookmark_id}"), StatusCode::NOT_FOUND))?;
+    let html = snapshot::decode(&stored.content)?;
+
+    Ok((
+        [
+            (CONTENT_TYPE, "text/html; charset=utf-8"),
+            (CONTENT_SECURITY_POLICY, SNAPSHOT_CSP),
+            (REFERRER_POLICY, "no-referrer"),
+            (X_CONTENT_TYPE_OPTIONS, "nosniff"),
+            (CACHE_CONTROL, "private, no-cache"),
+        ],
+        html,
+    ))
+}
+
+/// API handler saving a copy of a bookmarked page now, replacing any earlier one
+pub async fn save_snapshot_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Path(bookmark_id): Path<BookmarkId>,
+) -> AppResult<impl IntoResponse> {
+    if !snapshot::is_archiving_allowed(&state, user.user_id).await? {
+        return Err(AppError::with_status(
+            anyhow!("Archiving is not enabled for {}", user.username),
+            StatusCode::NOT_FOUND,
+        ));
+    }
+    let bookmark = find_bookmark(&state.db, user.user_id, bookmark_id).await?;
+
+    snapshot::save_snapshot(&state, bookmark_id, &bookmark.url).await.map_err(|err| {
+        warn!(%bookmark_id, url = bookmark.url, %err, "🗄️ Could not save a snapshot of the page.");
+        AppError::with_status(anyhow!("Could not save a snapshot: {err}"), StatusCode::BAD_GATEWAY)
+            .user_message("The page could not be saved.")
+    })?;
+    info!(user = user.username, %bookmark_id, "🗄️ Saved a snapshot on request.");
+
+    // Shown again with a link to the snapshot
+    let bookmark = find_bookmark(&state.db, user.user_id, bookmark_id).await?;
+    Ok(HtmlTemplate(item_template(&state, user.user_id, bookmark).await?))
+}
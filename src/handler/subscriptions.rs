@@ -0,0 +1,141 @@
+//! Subscription handlers: stable links serving the latest export of a smart collection, for other
+//! tools to pull on a schedule.
+
+use anyhow::anyhow;
+use askama::Template;
+use axum::{
+    Extension, Form,
+    extract::{Path, State},
+    http::{
+        StatusCode,
+        header::{CACHE_CONTROL, CONTENT_TYPE, REFERRER_POLICY},
+    },
+    response::IntoResponse,
+};
+use serde::Deserialize;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::{
+    ApiState, AppState,
+    db::{
+        collections, settings,
+        subscriptions::{self, Subscription, SubscriptionAccess, SubscriptionToken},
+        users::User,
+    },
+    error::{AppError, AppResult},
+    handler::{
+        HtmlTemplate,
+        export::{ExportQuery, stream_export},
+        extract::TolerantQuery,
+    },
+    search::SearchQuery,
+};
+
+/// A subscription on the settings page, with the address to hand out.
+#[derive(Clone, Debug)]
+pub struct SharedSubscription {
+    pub subscription: Subscription,
+    /// Path of the export, including the signed token
+    pub path: String,
+}
+
+/// The list of subscriptions on the settings page, re-rendered after each change.
+#[derive(Template)]
+#[template(path = "components/subscription_settings.html")]
+pub struct SubscriptionSettingsTemplate {
+    pub subscriptions: Vec<SharedSubscription>,
+}
+
+#[derive(Deserialize)]
+pub struct SubscriptionForm {
+    /// Smart collection to subscribe to
+    #[serde(default)]
+    pub collection: String,
+}
+
+/// Lists a user's subscriptions, signing a token for each.
+pub(crate) async fn shared_subscriptions(state: &AppState, user_id: Uuid) -> AppResult<Vec<SharedSubscription>> {
+    subscriptions::get_subscriptions(&state.db, user_id)
+        .await?
+        .into_iter()
+        .map(|subscription| {
+            let token = state.encryption.sign_token(SubscriptionToken {
+                subscription_id: subscription.subscription_id,
+            })?;
+            Ok(SharedSubscription {
+                subscription,
+                path: format!("/subscriptions/{token}"),
+            })
+        })
+        .collect()
+}
+
+/// API handler for creating a subscription to a smart collection
+pub async fn create_subscription_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Form(form): Form<SubscriptionForm>,
+) -> AppResult<impl IntoResponse> {
+    let collection = match form.collection.trim().parse() {
+        Ok(collection_id) => collections::get_collection(&state.db, user.user_id, collection_id).await?,
+        Err(_) => None,
+    }
+    .ok_or_else(|| AppError::bad_request("Choose a smart collection to subscribe to."))?;
+
+    let subscription_id = subscriptions::create_subscription(&state.db, user.user_id, collection.collection_id).await?;
+    info!(user = user.username, %subscription_id, collection = collection.name, "🔗 Created subscription.");
+
+    Ok(HtmlTemplate(SubscriptionSettingsTemplate {
+        subscriptions: shared_subscriptions(&state, user.user_id).await?,
+    }))
+}
+
+/// API handler for revoking a subscription
+pub async fn delete_subscription_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Path(subscription_id): Path<Uuid>,
+) -> AppResult<impl IntoResponse> {
+    if !subscriptions::delete_subscription(&state.db, user.user_id, subscription_id).await? {
+        return Err(AppError::with_status(
+            anyhow!("Subscription {subscription_id} not found"),
+            StatusCode::NOT_FOUND,
+        ));
+    }
+    info!(user = user.username, %subscription_id, "🔗 Revoked subscription.");
+
+    Ok(HtmlTemplate(SubscriptionSettingsTemplate {
+        subscriptions: shared_subscriptions(&state, user.user_id).await?,
+    }))
+}
+
+/// Handler for a subscription's export: the collection's bookmarks as they are now, as JSON by
+/// default or in any other export format
+///
+/// Only reachable through [`crate::handler::middlewares::subscription_middleware`], which checks
+/// the token.
+pub async fn collection_subscription_handler(
+    State(state): ApiState,
+    Extension(access): Extension<SubscriptionAccess>,
+    TolerantQuery(params): TolerantQuery<ExportQuery>,
+) -> AppResult<impl IntoResponse> {
+    debug!(subscription_id = %access.subscription_id, username = access.username, "Subscription pulled");
+
+    // Collections are evaluated fresh each time, with the owner's search defaults, leaving out
+    // private bookmarks
+    let user_settings = settings::get_user_settings(&state.db, access.user_id).await?;
+    let query = SearchQuery::parse_complete_with(&access.collection_query, user_settings.search).public_only();
+    if let Err(err) = subscriptions::mark_pulled(&state.db, access.subscription_id).await {
+        warn!(?err, subscription_id = %access.subscription_id, "Could not note when the subscription was pulled.");
+    }
+
+    let format = params.format;
+    // Keep the token out of the Referer header, and have readers always fetch the latest
+    let headers = [
+        (CONTENT_TYPE, format.content_type()),
+        (REFERRER_POLICY, "no-referrer"),
+        (CACHE_CONTROL, "no-cache"),
+    ];
+    Ok((headers, stream_export(state, access.user_id, access.username, format, Some(query))))
+}
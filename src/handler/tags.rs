@@ -1,22 +1,47 @@
 //! Tag-related handlers and templates.
 
+use anyhow::anyhow;
 use askama::Template;
-use axum::{Extension, Json, extract::State, response::IntoResponse};
-use axum_extra::extract::Query;
+use axum::{
+    Extension, Form, Json,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
 use fuzzy_matcher::{FuzzyMatcher, skim::SkimMatcherV2};
 use serde::{Deserialize, Serialize};
+use tracing::info;
 
 use crate::{
-    ApiState,
-    db::{bookmarks::TagInfo, tags, users::User},
-    handler::HtmlTemplate,
+    ApiState, AppState,
+    db::{
+        bookmarks::TagInfo,
+        tags::{self, TagColors, TagCount},
+        users::User,
+    },
+    error::{AppError, AppResult},
+    handler::{HtmlTemplate, HxRequest, extract::TolerantQuery, render_fragment},
+    suggest,
 };
 
+/// Number of tags shown at first and loaded per "show more" click.
+const TAG_PAGE_SIZE: usize = 50;
+
 #[derive(Template)]
 #[template(path = "components/tag_list.html")]
 pub struct TagListTemplate {
-    pub tags: Vec<TagInfo>,
-    pub active_tags: Vec<String>,
+    pub tags: Vec<TagCount>,
+    pub active_tags: Vec<TagInfo>,
+    /// URL for the next page of tags, if there are more
+    pub more_url: Option<String>,
+}
+
+/// A further page of tags, appended in place of the "show more" button.
+#[derive(Template)]
+#[template(path = "components/tag_list_page.html")]
+pub struct TagListPageTemplate {
+    pub tags: Vec<TagCount>,
+    pub more_url: Option<String>,
 }
 
 /// Query parameters for tag list filtering
@@ -25,29 +50,150 @@ pub struct TagListQuery {
     /// Filter by active tags - committed tags that are currently filtering results
     #[serde(default)]
     pub tags: Option<Vec<String>>,
+    /// Only show tags whose name contains this text
+    pub tag_q: Option<String>,
+    /// Number of tags already shown
+    pub offset: Option<usize>,
+}
+
+impl TagListQuery {
+    /// Builds the URL for the page of tags starting at `offset`, keeping the current filters.
+    fn page_url(&self, offset: usize) -> String {
+        let mut url = reqwest::Url::parse("http://localhost/api/tags/more").expect("static URL is valid");
+        {
+            let mut pairs = url.query_pairs_mut();
+            for tag in self.tags.iter().flatten() {
+                pairs.append_pair("tags", tag);
+            }
+            if let Some(tag_q) = self.tag_q.as_deref().filter(|q| !q.trim().is_empty()) {
+                pairs.append_pair("tag_q", tag_q);
+            }
+            pairs.append_pair("offset", &offset.to_string());
+        }
+        format!("{}?{}", url.path(), url.query().unwrap_or_default())
+    }
+}
+
+/// Loads one page of inactive tags for the given filters, most used first.
+///
+/// Returns the page along with the URL of the next page, if any.
+async fn load_tag_page(state: &AppState, user: &User, params: &TagListQuery) -> (Vec<TagCount>, Option<String>) {
+    let active_tags = params.tags.clone().unwrap_or_default();
+    let search = params.tag_q.as_deref().map(str::trim).unwrap_or_default().to_lowercase();
+    let offset = params.offset.unwrap_or(0);
+
+    // Get tags filtered by active tag filters
+    let db_tags = tags::get_tags_for_active_filters(&state.db, user.user_id, &active_tags)
+        .await
+        .unwrap_or_default();
+
+    // Filter out active tags from the inactive list, and anything not matching the tag search
+    let mut matching: Vec<TagCount> = db_tags
+        .into_iter()
+        .filter(|db_tag| !active_tags.contains(&db_tag.name))
+        .filter(|db_tag| search.is_empty() || db_tag.name.to_lowercase().contains(&search))
+        .collect();
+    matching.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+
+    let total = matching.len();
+    let page: Vec<TagCount> = matching.into_iter().skip(offset).take(TAG_PAGE_SIZE).collect();
+    let next_offset = offset + page.len();
+    let more_url = (next_offset < total).then(|| params.page_url(next_offset));
+
+    (page, more_url)
 }
 
 /// API handler for tag list (HTMX lazy loading)
+///
+/// Shows the most used tags first, with the rest loaded on demand via [`tag_list_page_handler`].
 pub async fn tag_list_handler(
     State(state): ApiState,
     Extension(user): Extension<User>,
-    Query(params): Query<TagListQuery>,
+    hx: HxRequest,
+    TolerantQuery(params): TolerantQuery<TagListQuery>,
 ) -> impl IntoResponse {
-    // Extract active tag filters
-    let active_tags = params.tags.unwrap_or_default();
+    let (template_tags, more_url) = load_tag_page(&state, &user, &params).await;
+    let tag_colors = tags::get_tag_colors(&state.db, user.user_id).await.unwrap_or_default();
+    let active_tags = params
+        .tags
+        .unwrap_or_default()
+        .into_iter()
+        .map(|name| TagInfo {
+            color: tag_colors.get(&name).cloned(),
+            name,
+        })
+        .collect();
 
-    // Get tags filtered by active tag filters
-    let db_tags = tags::get_tags_for_active_filters(&state.pool, user.user_id, &active_tags)
-        .await
-        .unwrap_or_default();
+    render_fragment(
+        hx,
+        "Tags",
+        TagListTemplate {
+            tags: template_tags,
+            active_tags,
+            more_url,
+        },
+    )
+}
+
+/// API handler for further pages of the tag list ("show more")
+pub async fn tag_list_page_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    hx: HxRequest,
+    TolerantQuery(params): TolerantQuery<TagListQuery>,
+) -> impl IntoResponse {
+    let (tags, more_url) = load_tag_page(&state, &user, &params).await;
 
-    // Convert database results to template format, filtering out active tags from inactive list
-    let template_tags: Vec<TagInfo> = db_tags.into_iter().filter(|db_tag| !active_tags.contains(&db_tag.name)).collect();
+    render_fragment(hx, "Tags", TagListPageTemplate { tags, more_url })
+}
 
-    HtmlTemplate(TagListTemplate {
-        tags: template_tags,
-        active_tags: active_tags.clone(),
-    })
+/// The tag colors on the settings page, re-rendered after each change.
+#[derive(Template)]
+#[template(path = "components/tag_color_settings.html")]
+pub struct TagColorSettingsTemplate {
+    pub tag_colors: TagColors,
+}
+
+#[derive(Deserialize)]
+pub struct TagColorForm {
+    pub tag: String,
+    /// As sent by a color picker, `#rrggbb`
+    pub color: String,
+}
+
+/// API handler for picking the color of one of the user's tags
+pub async fn set_tag_color_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Form(form): Form<TagColorForm>,
+) -> AppResult<impl IntoResponse> {
+    let tag = form.tag.trim().to_lowercase();
+    let color = tags::parse_color(&form.color).ok_or_else(|| AppError::bad_request("Pick a color for the tag."))?;
+    if !tags::set_tag_color(&state.db, user.user_id, &tag, Some(&color)).await? {
+        return Err(AppError::bad_request(format!("None of your bookmarks are tagged '{tag}'.")));
+    }
+    info!(user = user.username, tag, color, "🎨 Set tag color.");
+
+    let tag_colors = tags::get_tag_colors(&state.db, user.user_id).await?;
+    Ok(HtmlTemplate(TagColorSettingsTemplate { tag_colors }))
+}
+
+/// API handler for removing the color of one of the user's tags
+pub async fn delete_tag_color_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Path(tag): Path<String>,
+) -> AppResult<impl IntoResponse> {
+    if !tags::set_tag_color(&state.db, user.user_id, &tag, None).await? {
+        return Err(AppError::with_status(
+            anyhow!("Tag '{tag}' not found for user"),
+            StatusCode::NOT_FOUND,
+        ));
+    }
+    info!(user = user.username, tag, "🎨 Removed tag color.");
+
+    let tag_colors = tags::get_tag_colors(&state.db, user.user_id).await?;
+    Ok(HtmlTemplate(TagColorSettingsTemplate { tag_colors }))
 }
 
 #[derive(Deserialize)]
@@ -68,13 +214,13 @@ pub struct TagSuggestion {
 pub async fn tag_autocomplete_handler(
     State(state): ApiState,
     Extension(user): Extension<User>,
-    Query(query): Query<TagAutocompleteQuery>,
+    TolerantQuery(query): TolerantQuery<TagAutocompleteQuery>,
 ) -> impl IntoResponse {
     // Extract active tag filters
     let active_tags = query.tags.unwrap_or_default();
 
     // Get tags filtered by active tag filters
-    let db_tags = tags::get_tags_for_active_filters(&state.pool, user.user_id, &active_tags)
+    let db_tags = tags::get_tags_for_active_filters(&state.db, user.user_id, &active_tags)
         .await
         .unwrap_or_default();
 
@@ -102,3 +248,38 @@ pub async fn tag_autocomplete_handler(
 
     Json(suggestions)
 }
+
+#[derive(Deserialize)]
+pub struct TagSuggestQuery {
+    pub url: Option<String>,
+    pub title: Option<String>,
+    /// Tags already entered for the bookmark, which should not be suggested again
+    #[serde(default)]
+    pub tags: Option<Vec<String>>,
+}
+
+/// API handler suggesting tags for a new bookmark based on the user's own bookmark history
+pub async fn tag_suggest_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    TolerantQuery(query): TolerantQuery<TagSuggestQuery>,
+) -> impl IntoResponse {
+    if query.url.is_none() && query.title.is_none() {
+        return Json(Vec::<TagSuggestion>::new());
+    }
+
+    let corpus = tags::get_suggestion_corpus(&state.db, user.user_id).await.unwrap_or_default();
+    let exclude = query.tags.unwrap_or_default();
+
+    let suggestions = suggest::suggest_tags(&corpus, query.url.as_deref(), query.title.as_deref(), &exclude, 10)
+        .into_iter()
+        .map(|tag| TagSuggestion {
+            name: tag.name,
+            // Scale to keep the same integer score shape as autocomplete
+            #[allow(clippy::cast_possible_truncation)]
+            score: (tag.score * 100.0).round() as i64,
+        })
+        .collect();
+
+    Json(suggestions)
+}
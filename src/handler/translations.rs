@@ -0,0 +1,87 @@
+//! Bookmark translation handler and template.
+
+use anyhow::anyhow;
+use askama::Template;
+use axum::{
+    Extension,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use tracing::{error, info};
+
+use crate::{
+    ApiState,
+    db::{
+        bookmarks::BookmarkId,
+        settings,
+        translations::{self, BookmarkText},
+        users::User,
+    },
+    error::{AppError, AppResult},
+    handler::HtmlTemplate,
+};
+
+/// A bookmark's title and description translated, shown under the original.
+#[derive(Template)]
+#[template(path = "components/bookmark_translation.html")]
+pub struct BookmarkTranslationTemplate {
+    pub translation: BookmarkText,
+}
+
+/// API handler translating a bookmark's title and description into the user's preferred language
+///
+/// Translations are cached, so the translation service only sees each bookmark once per language.
+pub async fn translate_bookmark_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Path(bookmark_id): Path<BookmarkId>,
+) -> AppResult<impl IntoResponse> {
+    let Some(translator) = &state.translator else {
+        return Err(AppError::with_status(
+            anyhow!("Translation is not configured"),
+            StatusCode::NOT_FOUND,
+        ));
+    };
+    let Some(target) = settings::get_user_settings(&state.db, user.user_id).await?.preferred_language else {
+        return Err(AppError::bad_request(
+            "Choose a language to translate into on the Settings page first.",
+        ));
+    };
+
+    if let Some(translation) = translations::get_translation(&state.db, user.user_id, bookmark_id, &target).await? {
+        return Ok(HtmlTemplate(BookmarkTranslationTemplate { translation }));
+    }
+
+    let text = translations::get_bookmark_text(&state.db, user.user_id, bookmark_id)
+        .await?
+        .ok_or_else(|| AppError::with_status(anyhow!("Bookmark {bookmark_id} not found for user"), StatusCode::NOT_FOUND))?;
+    let mut texts = vec![text.title];
+    texts.extend(text.description);
+
+    let mut translated = translator
+        .translate(&texts, text.language.as_deref(), &target)
+        .await
+        .map_err(|err| {
+            error!(?err, "🌍 Translation failed.");
+            AppError::with_status(anyhow!("Translation failed: {err}"), StatusCode::BAD_GATEWAY)
+        })?
+        .into_iter();
+    let translation = BookmarkText {
+        title: translated.next().unwrap_or_default(),
+        description: translated.next(),
+        language: Some(target),
+    };
+
+    translations::save_translation(
+        &state.db,
+        bookmark_id,
+        translation.language.as_deref().unwrap_or_default(),
+        &translation.title,
+        translation.description.as_deref(),
+    )
+    .await?;
+    info!(user = user.username, %bookmark_id, language = translation.language, "🌍 Translated bookmark.");
+
+    Ok(HtmlTemplate(BookmarkTranslationTemplate { translation }))
+}
@@ -0,0 +1,96 @@
+//! Trash page handlers and templates, for restoring or permanently deleting bookmarks.
+
+use anyhow::anyhow;
+use askama::Template;
+use axum::{
+    Extension,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use tracing::info;
+
+use crate::{
+    ApiState,
+    db::{
+        Db,
+        bookmarks::{self, BookmarkId, TRASH_RETENTION_DAYS, TrashedBookmark},
+        users::User,
+    },
+    error::{AppError, AppResult},
+    handler::{AuthState, HtmlTemplate},
+};
+
+#[derive(Template)]
+#[template(path = "pages/trash.html")]
+pub struct TrashTemplate<'a> {
+    pub title: &'a str,
+    pub auth_state: AuthState,
+    pub is_error: bool,
+    pub trashed_bookmarks: Vec<TrashedBookmark>,
+    pub retention_days: i64,
+}
+
+/// The list of bookmarks in the trash, re-rendered after each change.
+#[derive(Template)]
+#[template(path = "components/trash_list.html")]
+pub struct TrashListTemplate {
+    pub trashed_bookmarks: Vec<TrashedBookmark>,
+}
+
+async fn trash_list(db: &Db, user: &User) -> AppResult<TrashListTemplate> {
+    Ok(TrashListTemplate {
+        trashed_bookmarks: bookmarks::get_trashed_bookmarks(db, user.user_id).await?,
+    })
+}
+
+fn not_in_trash(bookmark_id: BookmarkId) -> AppError {
+    AppError::with_status(anyhow!("Bookmark {bookmark_id} not in the trash"), StatusCode::NOT_FOUND)
+}
+
+/// Handler for the trash page, listing deleted bookmarks until they're purged
+pub async fn trash_handler(State(state): ApiState, Extension(user): Extension<User>) -> AppResult<impl IntoResponse> {
+    Ok(HtmlTemplate(TrashTemplate {
+        title: "Trash",
+        auth_state: AuthState::Authenticated,
+        is_error: false,
+        trashed_bookmarks: bookmarks::get_trashed_bookmarks(&state.db, user.user_id).await?,
+        retention_days: TRASH_RETENTION_DAYS,
+    }))
+}
+
+/// API handler for taking a bookmark back out of the trash
+pub async fn restore_bookmark_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Path(bookmark_id): Path<BookmarkId>,
+) -> AppResult<impl IntoResponse> {
+    if !bookmarks::restore_bookmark(&state.db, user.user_id, bookmark_id).await? {
+        return Err(not_in_trash(bookmark_id));
+    }
+    info!(user = user.username, %bookmark_id, "♻️ Restored bookmark from the trash.");
+
+    Ok(HtmlTemplate(trash_list(&state.db, &user).await?))
+}
+
+/// API handler for permanently deleting a bookmark from the trash
+pub async fn purge_bookmark_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Path(bookmark_id): Path<BookmarkId>,
+) -> AppResult<impl IntoResponse> {
+    if bookmarks::purge_bookmarks(&state.db, user.user_id, Some(bookmark_id)).await? == 0 {
+        return Err(not_in_trash(bookmark_id));
+    }
+    info!(user = user.username, %bookmark_id, "🗑️ Permanently deleted bookmark.");
+
+    Ok(HtmlTemplate(trash_list(&state.db, &user).await?))
+}
+
+/// API handler for permanently deleting everything in the trash
+pub async fn empty_trash_handler(State(state): ApiState, Extension(user): Extension<User>) -> AppResult<impl IntoResponse> {
+    let purged = bookmarks::purge_bookmarks(&state.db, user.user_id, None).await?;
+    info!(user = user.username, purged, "🗑️ Emptied the trash.");
+
+    Ok(HtmlTemplate(trash_list(&state.db, &user).await?))
+}
@@ -0,0 +1,183 @@
+//! Two-factor authentication handlers and templates, for setting it up and turning it off on the
+//! settings page. The code step of signing in is in [`crate::handler::auth_handler`].
+
+use anyhow::anyhow;
+use askama::Template;
+use axum::{Extension, Form, extract::State, http::StatusCode, response::IntoResponse};
+use serde::Deserialize;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{
+    ApiState, AppState,
+    db::{
+        two_factor::{self, UserTotp},
+        users::User,
+    },
+    error::{AppError, AppResult},
+    handler::HtmlTemplate,
+    totp,
+};
+
+/// Where a user is with two-factor authentication, as shown on the settings page.
+pub enum TwoFactorView {
+    Off,
+    /// Set up, but not confirmed with a code yet
+    SettingUp {
+        /// SVG QR code of the provisioning URI
+        qr_code: String,
+        /// The secret, for typing in instead of scanning
+        secret: String,
+    },
+    On {
+        recovery_codes_left: i64,
+        /// Recovery codes just made, shown this once
+        new_recovery_codes: Vec<String>,
+    },
+}
+
+impl TwoFactorView {
+    /// Where `user` is with two-factor authentication.
+    pub(crate) async fn for_user(state: &AppState, user: &User) -> AppResult<Self> {
+        match two_factor::get_totp(&state.db, user.user_id).await? {
+            None => Ok(Self::Off),
+            Some(user_totp) if user_totp.enabled => Ok(Self::On {
+                recovery_codes_left: two_factor::count_recovery_codes(&state.db, user.user_id).await?,
+                new_recovery_codes: Vec::new(),
+            }),
+            Some(user_totp) => Ok(Self::SettingUp {
+                qr_code: totp::qr_code_svg(&totp::provisioning_uri(&user.username, &user_totp.secret))?,
+                secret: totp::encode_secret(&user_totp.secret),
+            }),
+        }
+    }
+}
+
+/// The two-factor part of the settings page, re-rendered after each change.
+#[derive(Template)]
+#[template(path = "components/two_factor_settings.html")]
+pub struct TwoFactorSettingsTemplate {
+    pub two_factor: TwoFactorView,
+}
+
+#[derive(Deserialize)]
+pub struct TwoFactorCodeForm {
+    #[serde(default)]
+    pub code: String,
+}
+
+fn wrong_code() -> AppError {
+    AppError::bad_request("That code isn't right. Check the time on your device and try the next one.")
+}
+
+/// Checks a code from the authenticator app, or a recovery code, of a user with two-factor
+/// authentication on, using it up so it can't be used again. Returns whether it was right.
+///
+/// # Errors
+///
+/// Returns database errors if recording the code as used fails.
+pub(crate) async fn check_code(state: &AppState, user_id: Uuid, user_totp: &UserTotp, code: &str) -> AppResult<bool> {
+    let now = chrono::Utc::now().timestamp();
+    if let Some(step) = totp::verify_code(&user_totp.secret, code, now, user_totp.last_used_step) {
+        return Ok(two_factor::use_totp_step(&state.db, user_id, step).await?);
+    }
+    let code_hash = state.encryption.hash_recovery_code(code)?;
+    let used = two_factor::use_recovery_code(&state.db, user_id, &code_hash).await?;
+    if used {
+        info!(%user_id, "🔐 Used a recovery code.");
+    }
+    Ok(used)
+}
+
+/// Makes a new set of recovery codes, returning them and their hashes.
+fn new_recovery_codes(state: &AppState) -> AppResult<(Vec<String>, Vec<String>)> {
+    let codes = totp::generate_recovery_codes();
+    let hashes = codes
+        .iter()
+        .map(|code| state.encryption.hash_recovery_code(code))
+        .collect::<AppResult<_>>()?;
+    Ok((codes, hashes))
+}
+
+/// API handler for starting to set up two-factor authentication, showing the QR code to scan
+pub async fn start_two_factor_handler(State(state): ApiState, Extension(user): Extension<User>) -> AppResult<impl IntoResponse> {
+    if !two_factor::start_totp_setup(&state.db, user.user_id, &totp::generate_secret()).await? {
+        return Err(
+            AppError::with_status(anyhow!("Two-factor authentication already on"), StatusCode::CONFLICT)
+                .user_message("Two-factor authentication is already on."),
+        );
+    }
+    info!(user = user.username, "🔐 Started setting up two-factor authentication.");
+
+    Ok(HtmlTemplate(TwoFactorSettingsTemplate {
+        two_factor: TwoFactorView::for_user(&state, &user).await?,
+    }))
+}
+
+/// API handler for confirming two-factor setup with a first code, which turns it on and shows the
+/// recovery codes
+pub async fn confirm_two_factor_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Form(form): Form<TwoFactorCodeForm>,
+) -> AppResult<impl IntoResponse> {
+    let Some(user_totp) = two_factor::get_totp(&state.db, user.user_id).await?.filter(|totp| !totp.enabled) else {
+        return Err(AppError::bad_request("Start setting up two-factor authentication first."));
+    };
+    let now = chrono::Utc::now().timestamp();
+    let step = totp::verify_code(&user_totp.secret, &form.code, now, None).ok_or_else(wrong_code)?;
+
+    let (codes, hashes) = new_recovery_codes(&state)?;
+    if !two_factor::enable_totp(&state.db, user.user_id, step, &hashes).await? {
+        return Err(AppError::bad_request("Start setting up two-factor authentication first."));
+    }
+    info!(user = user.username, "🔐 Turned on two-factor authentication.");
+
+    Ok(HtmlTemplate(TwoFactorSettingsTemplate {
+        two_factor: TwoFactorView::On {
+            recovery_codes_left: i64::try_from(codes.len()).unwrap_or(i64::MAX),
+            new_recovery_codes: codes,
+        },
+    }))
+}
+
+/// API handler for replacing the recovery codes with new ones, shown once
+pub async fn regenerate_recovery_codes_handler(State(state): ApiState, Extension(user): Extension<User>) -> AppResult<impl IntoResponse> {
+    let (codes, hashes) = new_recovery_codes(&state)?;
+    if !two_factor::replace_recovery_codes(&state.db, user.user_id, &hashes).await? {
+        return Err(AppError::bad_request("Two-factor authentication is off."));
+    }
+    info!(user = user.username, "🔐 Made new recovery codes.");
+
+    Ok(HtmlTemplate(TwoFactorSettingsTemplate {
+        two_factor: TwoFactorView::On {
+            recovery_codes_left: i64::try_from(codes.len()).unwrap_or(i64::MAX),
+            new_recovery_codes: codes,
+        },
+    }))
+}
+
+/// API handler for turning two-factor authentication off, or cancelling its setup
+///
+/// Once it's on, turning it off takes a current code or a recovery code, so a session left open
+/// somewhere isn't enough.
+pub async fn disable_two_factor_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Form(form): Form<TwoFactorCodeForm>,
+) -> AppResult<impl IntoResponse> {
+    if let Some(user_totp) = two_factor::get_totp(&state.db, user.user_id).await?
+        && user_totp.enabled
+        && !check_code(&state, user.user_id, &user_totp, &form.code).await?
+    {
+        return Err(wrong_code());
+    }
+
+    if two_factor::disable_totp(&state.db, user.user_id).await? {
+        info!(user = user.username, "🔓 Turned off two-factor authentication.");
+    }
+
+    Ok(HtmlTemplate(TwoFactorSettingsTemplate {
+        two_factor: TwoFactorView::Off,
+    }))
+}
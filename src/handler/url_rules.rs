@@ -0,0 +1,74 @@
+//! URL cleaning rule handlers and templates.
+
+use anyhow::anyhow;
+use askama::Template;
+use axum::{
+    Extension, Form,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use serde::Deserialize;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{
+    ApiState, clean,
+    db::{
+        url_rules::{self, UrlCleaningRule},
+        users::User,
+    },
+    error::{AppError, AppResult},
+    handler::HtmlTemplate,
+};
+
+/// The list of URL cleaning rules on the settings page, re-rendered after each change.
+#[derive(Template)]
+#[template(path = "components/url_rule_settings.html")]
+pub struct UrlRuleSettingsTemplate {
+    pub url_rules: Vec<UrlCleaningRule>,
+}
+
+#[derive(Deserialize)]
+pub struct UrlRuleForm {
+    pub domain: String,
+    /// Regex matched against query parameter names
+    pub pattern: String,
+}
+
+/// API handler for creating a URL cleaning rule
+pub async fn create_url_rule_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Form(form): Form<UrlRuleForm>,
+) -> AppResult<impl IntoResponse> {
+    let domain = clean::normalize_domain(&form.domain).ok_or_else(|| AppError::bad_request("Enter the domain the rule applies to."))?;
+    let pattern = form.pattern.trim();
+    clean::compile_pattern(pattern).map_err(AppError::bad_request)?;
+
+    if url_rules::create_rule(&state.db, user.user_id, &domain, pattern).await?.is_none() {
+        return Err(AppError::bad_request(format!("You already have this rule for {domain}.")));
+    }
+    info!(user = user.username, domain, pattern, "🧽 Created URL cleaning rule.");
+
+    let url_rules = url_rules::get_user_rules(&state.db, user.user_id).await?;
+    Ok(HtmlTemplate(UrlRuleSettingsTemplate { url_rules }))
+}
+
+/// API handler for deleting a URL cleaning rule
+pub async fn delete_url_rule_handler(
+    State(state): ApiState,
+    Extension(user): Extension<User>,
+    Path(rule_id): Path<Uuid>,
+) -> AppResult<impl IntoResponse> {
+    if !url_rules::delete_rule(&state.db, user.user_id, rule_id).await? {
+        return Err(AppError::with_status(
+            anyhow!("URL cleaning rule {rule_id} not found for user"),
+            StatusCode::NOT_FOUND,
+        ));
+    }
+    info!(user = user.username, %rule_id, "🧽 Deleted URL cleaning rule.");
+
+    let url_rules = url_rules::get_user_rules(&state.db, user.user_id).await?;
+    Ok(HtmlTemplate(UrlRuleSettingsTemplate { url_rules }))
+}
@@ -0,0 +1,186 @@
+//! Highlighting of searched-for terms in the bookmark list, so users can see why each result matched.
+//!
+//! Terms are marked the way the search matches them: words regardless of (ASCII) case, like `SQLite`'s
+//! `like`, and quoted phrases exactly. Terms restricted to another field, e.g. `url:github`, aren't
+//! marked in the title or description.
+
+use std::ops::Range;
+
+use crate::search::{FieldTerm, SearchField, SearchQuery, SearchTerm};
+
+/// The terms to mark in each shown field of a bookmark. The default marks nothing.
+#[derive(Clone, Debug, Default)]
+pub struct Highlighter {
+    title: Vec<SearchTerm>,
+    description: Vec<SearchTerm>,
+}
+
+impl Highlighter {
+    #[must_use]
+    pub fn new(query: &SearchQuery) -> Self {
+        let terms_for = |shown: SearchField| {
+            let field_terms = query
+                .field_terms
+                .iter()
+                .filter(|FieldTerm { field, .. }| *field == shown)
+                .map(|FieldTerm { term, .. }| term);
+            query
+                .general_terms
+                .iter()
+                .chain(field_terms)
+                .filter(|term| !term_text(term).trim().is_empty())
+                .cloned()
+                .collect()
+        };
+        Self {
+            title: terms_for(SearchField::Title),
+            description: terms_for(SearchField::Description),
+        }
+    }
+
+    /// The title as HTML, with the terms wrapped in `<mark>`.
+    #[must_use]
+    pub fn title(&self, title: &str) -> String {
+        mark(title, &self.title)
+    }
+
+    /// The description as HTML, with the terms wrapped in `<mark>`.
+    #[must_use]
+    pub fn description(&self, description: &str) -> String {
+        mark(description, &self.description)
+    }
+}
+
+fn term_text(term: &SearchTerm) -> &str {
+    match term {
+        SearchTerm::Word(word) | SearchTerm::Phrase(word) => word,
+    }
+}
+
+/// Escapes `text` as HTML, wrapping every occurrence of the terms in `<mark>`. Overlapping and
+/// adjacent occurrences are marked as one.
+fn mark(text: &str, terms: &[SearchTerm]) -> String {
+    let mut ranges: Vec<Range<usize>> = terms.iter().flat_map(|term| occurrences(text, term)).collect();
+    ranges.sort_by_key(|range| range.start);
+
+    let mut merged: Vec<Range<usize>> = Vec::new();
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+
+    let mut html = String::with_capacity(text.len());
+    let mut shown = 0;
+    for range in merged {
+        escape_into(&mut html, &text[shown..range.start]);
+        html.push_str("<mark>");
+        escape_into(&mut html, &text[range.clone()]);
+        html.push_str("</mark>");
+        shown = range.end;
+    }
+    escape_into(&mut html, &text[shown..]);
+    html
+}
+
+/// Byte ranges of the non-overlapping occurrences of a term in `text`.
+///
+/// ASCII case folding leaves other bytes alone, so a match always starts and ends on a character
+/// boundary of `text`.
+fn occurrences(text: &str, term: &SearchTerm) -> Vec<Range<usize>> {
+    let (needle, ignore_case) = match term {
+        SearchTerm::Word(word) => (word.as_bytes(), true),
+        SearchTerm::Phrase(phrase) => (phrase.as_bytes(), false),
+    };
+    let haystack = text.as_bytes();
+    let mut found = Vec::new();
+    let mut start = 0;
+    while start + needle.len() <= haystack.len() {
+        let candidate = &haystack[start..start + needle.len()];
+        let matches = if ignore_case {
+            candidate.eq_ignore_ascii_case(needle)
+        } else {
+            candidate == needle
+        };
+        if matches {
+            found.push(start..start + needle.len());
+            start += needle.len();
+        } else {
+            start += 1;
+        }
+    }
+    found
+}
+
+fn escape_into(html: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '"' => html.push_str("&quot;"),
+            '\'' => html.push_str("&#x27;"),
+            _ => html.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_marks_words_ignoring_case() {
+        let highlighter = Highlighter::new(&SearchQuery::parse("rust axum"));
+        assert_eq!(
+            highlighter.title("Rust web apps with Axum and rusty tools"),
+            "<mark>Rust</mark> web apps with <mark>Axum</mark> and <mark>rust</mark>y tools"
+        );
+        assert_eq!(highlighter.description("Nothing here"), "Nothing here");
+    }
+
+    #[test]
+    fn test_marks_phrases_exactly() {
+        let highlighter = Highlighter::new(&SearchQuery::parse("\"Memory cache\""));
+        assert_eq!(
+            highlighter.title("Memory cache, not memory cache"),
+            "<mark>Memory cache</mark>, not memory cache"
+        );
+    }
+
+    #[test]
+    fn test_escapes_html() {
+        let highlighter = Highlighter::new(&SearchQuery::parse("b"));
+        assert_eq!(
+            highlighter.title("<b>bold</b> & \"quoted\""),
+            "&lt;<mark>b</mark>&gt;<mark>b</mark>old&lt;/<mark>b</mark>&gt; &amp; &quot;quoted&quot;"
+        );
+        // Terms that look like markup are escaped too
+        let highlighter = Highlighter::new(&SearchQuery::parse("<script>"));
+        assert_eq!(highlighter.title("a <script> tag"), "a <mark>&lt;script&gt;</mark> tag");
+        assert_eq!(Highlighter::default().title("<i>it's</i>"), "&lt;i&gt;it&#x27;s&lt;/i&gt;");
+    }
+
+    #[test]
+    fn test_merges_overlapping_terms() {
+        let highlighter = Highlighter::new(&SearchQuery::parse("data base database"));
+        assert_eq!(highlighter.title("A database"), "A <mark>database</mark>");
+    }
+
+    #[test]
+    fn test_field_terms_only_mark_their_field() {
+        let highlighter = Highlighter::new(&SearchQuery::parse("title:axum desc:tokio url:github"));
+        assert_eq!(
+            highlighter.title("Axum on tokio, from github"),
+            "<mark>Axum</mark> on tokio, from github"
+        );
+        assert_eq!(highlighter.description("Axum on tokio"), "Axum on <mark>tokio</mark>");
+    }
+
+    #[test]
+    fn test_non_ascii_text() {
+        let highlighter = Highlighter::new(&SearchQuery::parse("café"));
+        assert_eq!(highlighter.title("Le CAFé, ça va"), "Le <mark>CAFé</mark>, ça va");
+    }
+}
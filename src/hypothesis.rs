@@ -0,0 +1,259 @@
+//! Import of annotations from Hypothes.is.
+//!
+//! Uses a personal API token (from <https://hypothes.is/account/developer>) to download all of a
+//! user's annotations. Each annotated page becomes a bookmark, tagged with the annotations' tags,
+//! with the highlighted passages and notes gathered in its description.
+
+use std::time::Duration;
+
+use anyhow::{Context as _, Result, bail};
+use chrono::DateTime;
+use reqwest::{Client, Url, header::AUTHORIZATION};
+use serde::{Deserialize, de::DeserializeOwned};
+
+use crate::db::bookmarks::NewBookmark;
+
+/// Base URL of the Hypothes.is API.
+const API_URL: &str = "https://api.hypothes.is/api/";
+/// Annotations requested per page, the most the API allows.
+const PAGE_SIZE: usize = 200;
+/// Most annotations downloaded in one import.
+pub const MAX_ANNOTATIONS: usize = 10_000;
+/// Maximum time to wait for each API request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// Tag added to every imported bookmark, so they can be found together.
+pub const IMPORT_TAG: &str = "hypothesis";
+
+#[derive(Deserialize)]
+struct Profile {
+    /// e.g. `acct:alice@hypothes.is`; missing if the token wasn't accepted
+    userid: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SearchResponse {
+    rows: Vec<Annotation>,
+}
+
+/// An annotation as returned by the API, with only the fields the import uses.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct Annotation {
+    pub uri: String,
+    /// RFC 3339 timestamp
+    pub created: String,
+    #[serde(default)]
+    pub text: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub document: Document,
+    #[serde(default)]
+    pub target: Vec<Target>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct Document {
+    #[serde(default)]
+    pub title: Vec<String>,
+}
+
+/// The part of the page an annotation is attached to.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct Target {
+    #[serde(default)]
+    pub selector: Vec<Selector>,
+}
+
+/// One way of locating the annotated passage; only text quote selectors carry the passage itself.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct Selector {
+    pub exact: Option<String>,
+}
+
+impl Annotation {
+    /// The highlighted passage, if the annotation is on one rather than on the page as a whole.
+    fn quote(&self) -> Option<&str> {
+        self.target
+            .iter()
+            .flat_map(|target| &target.selector)
+            .find_map(|selector| selector.exact.as_deref())
+            .map(str::trim)
+            .filter(|quote| !quote.is_empty())
+    }
+}
+
+/// Downloads a user's annotations, oldest first, up to [`MAX_ANNOTATIONS`].
+///
+/// # Errors
+///
+/// Returns an error if the token isn't accepted or the API can't be reached.
+pub async fn fetch_annotations(api_token: &str) -> Result<Vec<Annotation>> {
+    let client = Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .context("Could not build the Hypothes.is HTTP client")?;
+    let api_url = Url::parse(API_URL)?;
+
+    let profile: Profile = get_json(&client, api_url.join("profile")?, api_token).await?;
+    let Some(userid) = profile.userid else {
+        bail!("Hypothes.is didn't accept the API token");
+    };
+
+    let mut annotations: Vec<Annotation> = Vec::new();
+    loop {
+        let mut url = api_url.join("search")?;
+        url.query_pairs_mut()
+            .append_pair("user", &userid)
+            .append_pair("limit", &PAGE_SIZE.to_string())
+            .append_pair("sort", "created")
+            .append_pair("order", "asc");
+        if let Some(last) = annotations.last() {
+            url.query_pairs_mut().append_pair("search_after", &last.created);
+        }
+
+        let page: SearchResponse = get_json(&client, url, api_token).await?;
+        let is_last_page = page.rows.len() < PAGE_SIZE;
+        annotations.extend(page.rows);
+        if is_last_page || annotations.len() >= MAX_ANNOTATIONS {
+            break;
+        }
+    }
+    annotations.truncate(MAX_ANNOTATIONS);
+
+    Ok(annotations)
+}
+
+#[tracing::instrument(level = "trace", name = "http.hypothesis", skip_all, fields(path = url.path()))]
+async fn get_json<T: DeserializeOwned>(client: &Client, url: Url, api_token: &str) -> Result<T> {
+    let response = client
+        .get(url)
+        .header(AUTHORIZATION, format!("Bearer {api_token}"))
+        .send()
+        .await
+        .context("Could not reach Hypothes.is")?;
+    if !response.status().is_success() {
+        bail!("Hypothes.is returned {}", response.status());
+    }
+    serde_json::from_slice(&response.bytes().await?).context("Unexpected response from Hypothes.is")
+}
+
+/// Turns annotations into one bookmark per annotated web page, in the order the pages were first
+/// annotated. Annotations on anything but web pages (such as local PDFs) are skipped.
+pub fn to_bookmarks(annotations: &[Annotation]) -> Vec<NewBookmark> {
+    let mut pages: Vec<(&str, Vec<&Annotation>)> = Vec::new();
+    for annotation in annotations {
+        if !Url::parse(&annotation.uri).is_ok_and(|url| matches!(url.scheme(), "http" | "https")) {
+            continue;
+        }
+        match pages.iter_mut().find(|(uri, _)| *uri == annotation.uri) {
+            Some((_, page)) => page.push(annotation),
+            None => pages.push((&annotation.uri, vec![annotation])),
+        }
+    }
+
+    pages.into_iter().map(|(uri, page)| to_bookmark(uri, &page)).collect()
+}
+
+fn to_bookmark(uri: &str, annotations: &[&Annotation]) -> NewBookmark {
+    let title = annotations
+        .iter()
+        .flat_map(|annotation| &annotation.document.title)
+        .map(|title| title.trim())
+        .find(|title| !title.is_empty())
+        .unwrap_or(uri);
+
+    let mut tag_names = vec![IMPORT_TAG.to_string()];
+    for tag in annotations.iter().flat_map(|annotation| &annotation.tags) {
+        let tag = tag.split_whitespace().collect::<Vec<_>>().join("-").to_lowercase();
+        if !tag.is_empty() && !tag_names.contains(&tag) {
+            tag_names.push(tag);
+        }
+    }
+
+    let notes: Vec<String> = annotations
+        .iter()
+        .filter_map(|annotation| {
+            let quote = annotation
+                .quote()
+                .map(|quote| quote.lines().map(|line| format!("> {line}")).collect::<Vec<_>>().join("\n"));
+            let text = Some(annotation.text.trim()).filter(|text| !text.is_empty());
+            match (quote, text) {
+                (Some(quote), Some(text)) => Some(format!("{quote}\n\n{text}")),
+                (Some(quote), None) => Some(quote),
+                (None, text) => text.map(str::to_string),
+            }
+        })
+        .collect();
+
+    NewBookmark {
+        url: uri.to_string(),
+        title: title.to_string(),
+        description: (!notes.is_empty()).then(|| notes.join("\n\n")),
+        tag_names,
+        created_at: annotations
+            .iter()
+            .filter_map(|annotation| DateTime::parse_from_rfc3339(&annotation.created).ok())
+            .map(|created| created.timestamp())
+            .min(),
+        ..NewBookmark::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEARCH_RESPONSE: &str = r#"{
+        "total": 3,
+        "rows": [
+            {
+                "id": "a1", "uri": "https://example.com/post", "created": "2024-03-01T10:00:00.000000+00:00",
+                "text": "Key point.", "tags": ["Reading List", "rust"],
+                "document": {"title": ["An Example Post"]},
+                "target": [{"source": "https://example.com/post", "selector": [
+                    {"type": "RangeSelector", "startContainer": "/p[1]"},
+                    {"type": "TextQuoteSelector", "exact": "Quoted line one\nline two", "prefix": "", "suffix": ""}
+                ]}]
+            },
+            {
+                "id": "a2", "uri": "urn:x-pdf:abc", "created": "2024-03-02T10:00:00+00:00",
+                "text": "On a local PDF", "tags": [], "document": {}, "target": [{"source": "urn:x-pdf:abc"}]
+            },
+            {
+                "id": "a3", "uri": "https://example.com/post", "created": "2024-02-01T10:00:00+00:00",
+                "text": "A page note", "tags": ["rust"], "document": {"title": []}, "target": [{"source": "https://example.com/post"}]
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn test_to_bookmarks() {
+        let response: SearchResponse = serde_json::from_str(SEARCH_RESPONSE).unwrap();
+        let bookmarks = to_bookmarks(&response.rows);
+
+        assert_eq!(bookmarks.len(), 1);
+        let bookmark = &bookmarks[0];
+        assert_eq!(bookmark.url, "https://example.com/post");
+        assert_eq!(bookmark.title, "An Example Post");
+        assert_eq!(bookmark.tag_names, ["hypothesis", "reading-list", "rust"]);
+        assert_eq!(
+            bookmark.description.as_deref(),
+            Some("> Quoted line one\n> line two\n\nKey point.\n\nA page note")
+        );
+        // 2024-02-01T10:00:00Z, the earliest annotation
+        assert_eq!(bookmark.created_at, Some(1_706_781_600));
+    }
+
+    #[test]
+    fn test_untitled_page_uses_url() {
+        let annotation = Annotation {
+            uri: "https://example.com/".to_string(),
+            created: "not a date".to_string(),
+            ..Annotation::default()
+        };
+        let bookmarks = to_bookmarks(&[annotation]);
+        assert_eq!(bookmarks[0].title, "https://example.com/");
+        assert_eq!(bookmarks[0].description, None);
+        assert_eq!(bookmarks[0].created_at, None);
+    }
+}
@@ -0,0 +1,385 @@
+//! Scheduled background jobs.
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, TimeDelta, Timelike as _, Utc};
+use reqwest::Client;
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    api_usage::{self, ApiUsage},
+    config::{FetchConfig, WatchConfig},
+    db::{self, Db, maintenance::MaintenanceRun, settings::DigestFrequency, user_session::SessionStore},
+    digest::{self, DIGEST_LIMIT, DigestEntry},
+    fetch_retry, link_check, lockout,
+    mail::Mailer,
+    metrics::{Alerter, Metrics},
+    update_check::{CURRENT_VERSION, UpdateCheck},
+    watch, wayback,
+};
+
+/// Time between checks for a new release.
+const UPDATE_CHECK_INTERVAL: Duration = Duration::from_secs(24 * 3600);
+/// Time between writes of the API token usage counted in memory.
+const API_USAGE_FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+/// Time between checks of the watched directory for dropped files.
+const WATCH_INTERVAL: Duration = Duration::from_secs(5);
+/// Time between batches of link checks.
+const LINK_CHECK_INTERVAL: Duration = Duration::from_secs(3600);
+/// Most links checked in one batch, one after another.
+const LINK_CHECK_BATCH: i64 = 200;
+/// Time between looks for pages due to be fetched again.
+const FETCH_RETRY_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// Most pages fetched again in one batch, one after another.
+const FETCH_RETRY_BATCH: i64 = 50;
+/// Time between looks for email digests that are due.
+const DIGEST_INTERVAL: Duration = Duration::from_secs(3600);
+/// Seconds a digest counts as due before it is, so one due a moment after a look isn't an hour late.
+const DIGEST_SLACK: i64 = 30 * 60;
+
+/// Names the jobs' runs are counted under, see [`Metrics::record_job`].
+const MAINTENANCE_JOB: &str = "maintenance";
+const UPDATE_CHECK_JOB: &str = "update check";
+const API_USAGE_FLUSH_JOB: &str = "API usage flush";
+const DIRECTORY_WATCH_JOB: &str = "directory watch";
+const LINK_CHECK_JOB: &str = "link check";
+const FETCH_RETRY_JOB: &str = "fetch retry";
+const DIGEST_JOB: &str = "email digest";
+
+/// Starts the nightly maintenance job, running every day at `hour` (UTC).
+pub fn spawn_nightly_maintenance(db: Db, sessions: Arc<dyn SessionStore>, hour: u32, metrics: Arc<Metrics>) {
+    tokio::spawn(async move {
+        loop {
+            let wait = until_next_run(Utc::now(), hour);
+            info!(hours = wait.as_secs() / 3600, "🧹 Next database maintenance scheduled.");
+            tokio::time::sleep(wait).await;
+            let run = run_maintenance(&db, sessions.as_ref()).await;
+            metrics.record_job(MAINTENANCE_JOB, run.error.is_none());
+        }
+    });
+}
+
+/// Starts the daily check for a new release, the first one right away.
+pub fn spawn_update_check(update_check: Arc<UpdateCheck>, metrics: Arc<Metrics>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(UPDATE_CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            let result = update_check.check().await;
+            metrics.record_job(UPDATE_CHECK_JOB, result.is_ok());
+            match result {
+                Ok(Some(release)) => info!(
+                    current = CURRENT_VERSION,
+                    latest = release.version,
+                    "🆕 A new release of PagePouch is available."
+                ),
+                Ok(None) => debug!(current = CURRENT_VERSION, "PagePouch is up to date"),
+                Err(err) => warn!(?err, "Could not check for a new release."),
+            }
+        }
+    });
+}
+
+/// Starts writing the API token usage counted in memory to the database, once a minute.
+pub fn spawn_api_usage_flush(db: Db, api_usage: Arc<ApiUsage>, metrics: Arc<Metrics>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(API_USAGE_FLUSH_INTERVAL);
+        loop {
+            interval.tick().await;
+            let usage = api_usage.take();
+            if usage.is_empty() {
+                continue;
+            }
+            let result = db::api_tokens::record_token_usage(&db, &usage).await;
+            metrics.record_job(API_USAGE_FLUSH_JOB, result.is_ok());
+            if let Err(err) = result {
+                warn!(?err, "Could not record API token usage, trying again with the next.");
+                api_usage.restore(usage);
+            }
+        }
+    });
+}
+
+/// Starts saving the links in files dropped into the watched directory, checking every few seconds.
+pub fn spawn_directory_watch(db: Db, watch: WatchConfig, metrics: Arc<Metrics>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(WATCH_INTERVAL);
+        loop {
+            interval.tick().await;
+            let result = watch::import_dropped_files(&db, &watch).await;
+            metrics.record_job(DIRECTORY_WATCH_JOB, result.is_ok());
+            if let Err(err) = result {
+                warn!(
+                    ?err,
+                    "📂 Could not save the links in dropped files, trying again with the next check."
+                );
+            }
+        }
+    });
+}
+
+/// Starts checking bookmarked links for ones that have rotted, a batch every hour, each link again
+/// after `days` days.
+pub fn spawn_link_check(db: Db, client: Client, fetch_config: FetchConfig, days: u32, wayback: bool, metrics: Arc<Metrics>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(LINK_CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            let checked_before = Utc::now().timestamp() - i64::from(days) * 86400;
+            let result = check_due_links(&db, &client, &fetch_config, checked_before, wayback).await;
+            metrics.record_job(LINK_CHECK_JOB, result.is_ok());
+            match result {
+                Ok((0, _)) => {}
+                Ok((checked, broken)) => info!(checked, broken, "🔗 Checked bookmarked links."),
+                Err(err) => warn!(?err, "🔗 Could not check bookmarked links, trying again with the next batch."),
+            }
+        }
+    });
+}
+
+/// Checks a batch of links that are due, returning how many were checked and found broken. With
+/// `wayback`, copies of the broken ones are looked up in the Wayback Machine.
+async fn check_due_links(
+    db: &Db,
+    client: &Client,
+    fetch_config: &FetchConfig,
+    checked_before: i64,
+    wayback: bool,
+) -> anyhow::Result<(usize, usize)> {
+    let due = db::link_checks::get_due_links(db, checked_before, LINK_CHECK_BATCH).await?;
+    let mut broken = 0;
+    for link in &due {
+        let mut check = link_check::check_link(client, fetch_config, &link.url).await;
+        if check.is_broken {
+            broken += 1;
+            if wayback {
+                check.archived_url = wayback::find_copy(client, &link.url).await.unwrap_or_else(|err| {
+                    debug!(url = link.url, %err, "🏛️ Could not look up a copy in the Wayback Machine.");
+                    None
+                });
+            }
+            debug!(bookmark_id = %link.bookmark_id, url = link.url, ?check, "🔗 Link is broken.");
+        }
+        db::link_checks::set_link_check(db, link.bookmark_id, &check).await?;
+    }
+    Ok((due.len(), broken))
+}
+
+/// Starts fetching again the pages of bookmarks that couldn't be fetched when they were saved, see
+/// [`fetch_retry`], looking for ones that are due every few minutes.
+pub fn spawn_fetch_retry(db: Db, client: Client, fetch_config: FetchConfig, metrics: Arc<Metrics>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(FETCH_RETRY_INTERVAL);
+        loop {
+            interval.tick().await;
+            let result = retry_due_fetches(&db, &client, &fetch_config).await;
+            metrics.record_job(FETCH_RETRY_JOB, result.is_ok());
+            match result {
+                Ok((0, _)) => {}
+                Ok((tried, fetched)) => info!(tried, fetched, "🌐 Tried fetching pages again."),
+                Err(err) => warn!(?err, "🌐 Could not fetch pages again, trying again with the next batch."),
+            }
+        }
+    });
+}
+
+/// Fetches a batch of pages that are due to be tried again, returning how many were tried and
+/// fetched.
+async fn retry_due_fetches(db: &Db, client: &Client, fetch_config: &FetchConfig) -> anyhow::Result<(usize, usize)> {
+    let due = db::fetch_failures::get_due_fetches(db, Utc::now().timestamp(), FETCH_RETRY_BATCH).await?;
+    let mut fetched = 0;
+    for page in &due {
+        match fetch_retry::fetch_details(client, fetch_config, &page.url).await {
+            Ok(details) => {
+                fetched += 1;
+                db::fetch_failures::complete_fetch(db, page.bookmark_id, &details).await?;
+            }
+            Err(error) => {
+                debug!(bookmark_id = %page.bookmark_id, url = page.url, error, "🌐 Page still can't be fetched.");
+                let failure = fetch_retry::failure_after(page.attempts + 1, error, Utc::now().timestamp());
+                db::fetch_failures::set_fetch_failure(db, page.bookmark_id, &failure).await?;
+            }
+        }
+    }
+    Ok((due.len(), fetched))
+}
+
+/// Starts emailing the digests of new bookmarks users opted into, see [`digest`], looking for
+/// ones that are due every hour.
+pub fn spawn_email_digest(db: Db, mailer: Arc<dyn Mailer>, public_url: String, metrics: Arc<Metrics>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(DIGEST_INTERVAL);
+        loop {
+            interval.tick().await;
+            let result = send_due_digests(&db, mailer.as_ref(), &public_url).await;
+            metrics.record_job(DIGEST_JOB, result.is_ok());
+            match result {
+                Ok(0) => {}
+                Ok(sent) => info!(sent, "📧 Sent bookmark digests."),
+                Err(err) => warn!(?err, "📧 Could not send bookmark digests, trying again in an hour."),
+            }
+        }
+    });
+}
+
+/// Sends the digests that are due, returning how many were sent. A digest that fails to send is
+/// tried again an hour later; the others still go out.
+async fn send_due_digests(db: &Db, mailer: &dyn Mailer, public_url: &str) -> anyhow::Result<usize> {
+    let now = Utc::now().timestamp();
+    let before = |frequency: DigestFrequency| now + DIGEST_SLACK - frequency.period_secs().unwrap_or_default();
+    let due = db::settings::get_due_digests(db, before(DigestFrequency::Daily), before(DigestFrequency::Weekly)).await?;
+    let mut sent = 0;
+    let mut failed = None;
+    for user in due {
+        let since = now - user.frequency.period_secs().unwrap_or_default();
+        let mut bookmarks = db::bookmarks::get_saved_between(db, user.user_id, &[(since, now + 1)], DIGEST_LIMIT + 1).await?;
+        let more = i64::try_from(bookmarks.len()).unwrap_or(i64::MAX) > DIGEST_LIMIT;
+        if more {
+            bookmarks.pop();
+        }
+        let entries: Vec<DigestEntry> = bookmarks.into_iter().map(|(_, bookmark)| bookmark.into()).collect();
+        if let Some(email) = digest::digest_email(&user.email, &user.username, user.frequency, &entries, more, public_url) {
+            if let Err(err) = mailer.send(email).await {
+                debug!(user = user.username, ?err, "📧 Could not send bookmark digest.");
+                failed = Some(err);
+                continue;
+            }
+            sent += 1;
+        }
+        db::settings::mark_digest_sent(db, user.user_id, now).await?;
+    }
+    match failed {
+        Some(err) => Err(err.context(format!("{sent} digests were sent before"))),
+        None => Ok(sent),
+    }
+}
+
+/// Starts checking the routes' and jobs' failure rates at the end of every alert window, posting
+/// the ones that started failing to the webhook.
+pub fn spawn_alert_check(metrics: Arc<Metrics>, alerter: Arc<Alerter>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(alerter.config.window);
+        // The first tick is right away, before anything's been counted
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            let alerts = metrics.check_alerts(&alerter.config);
+            if alerts.is_empty() {
+                continue;
+            }
+            let names: Vec<&str> = alerts.iter().map(|alert| alert.name.as_str()).collect();
+            warn!(failing = names.join(", "), "🚨 Failure rates over the alert thresholds.");
+            if let Err(err) = alerter.send(&alerts).await {
+                error!(?err, "🚨 Could not post alerts, trying again after the next window.");
+                metrics.forget_alerts(&alerts);
+            }
+        }
+    });
+}
+
+/// Runs every maintenance step, logs and records the outcome, and returns it.
+///
+/// A failing step doesn't stop the others; its error is kept in the result.
+pub async fn run_maintenance(db: &Db, sessions: &dyn SessionStore) -> MaintenanceRun {
+    let started = Instant::now();
+    let mut run = MaintenanceRun {
+        started_at: Utc::now().timestamp(),
+        ..MaintenanceRun::default()
+    };
+    let mut errors = Vec::new();
+
+    match db::user_session::clean_expired_sessions(sessions).await {
+        Ok(deleted) => run.sessions_deleted = i64::try_from(deleted).unwrap_or(i64::MAX),
+        Err(err) => errors.push(format!("expired sessions: {err:?}")),
+    }
+    // Before the orphan tags, so tags only the expired and purged bookmarks used go too
+    match db::maintenance::delete_expired_bookmarks(db).await {
+        Ok(deleted) => run.bookmarks_expired = deleted,
+        Err(err) => errors.push(format!("expired bookmarks: {err:#}")),
+    }
+    match db::maintenance::purge_trashed_bookmarks(db).await {
+        Ok(purged) => run.bookmarks_purged = purged,
+        Err(err) => errors.push(format!("trashed bookmarks: {err:#}")),
+    }
+    match db::maintenance::delete_orphan_tags(db).await {
+        Ok(deleted) => run.tags_deleted = deleted,
+        Err(err) => errors.push(format!("orphan tags: {err:#}")),
+    }
+    if let Err(err) = db::api_tokens::delete_old_token_usage(db, &api_usage::first_usage_day(Utc::now().date_naive())).await {
+        errors.push(format!("API token usage: {err:#}"));
+    }
+    if let Err(err) = db::password_resets::delete_finished_resets(db).await {
+        errors.push(format!("password resets: {err:#}"));
+    }
+    if let Err(err) = db::login_failures::delete_stale_failures(db, lockout::FAILURE_WINDOW_SECS).await {
+        errors.push(format!("failed sign-ins: {err:#}"));
+    }
+    if let Err(err) = db::maintenance::merge_fts_indexes(db).await {
+        errors.push(format!("FTS merge: {err:#}"));
+    }
+    match db::maintenance::incremental_vacuum(db).await {
+        Ok(freed) => run.pages_freed = freed,
+        Err(err) => errors.push(format!("incremental vacuum: {err:#}")),
+    }
+    // Last, so the statistics reflect the cleaned-up tables
+    if let Err(err) = db::maintenance::optimize(db).await {
+        errors.push(format!("optimize: {err:#}"));
+    }
+
+    run.duration_ms = i64::try_from(started.elapsed().as_millis()).unwrap_or(i64::MAX);
+    if errors.is_empty() {
+        info!(
+            duration_ms = run.duration_ms,
+            sessions_deleted = run.sessions_deleted,
+            bookmarks_expired = run.bookmarks_expired,
+            bookmarks_purged = run.bookmarks_purged,
+            tags_deleted = run.tags_deleted,
+            pages_freed = run.pages_freed,
+            "🧹 Database maintenance finished."
+        );
+    } else {
+        let message = errors.join("; ");
+        error!(errors = message, "🚨 Database maintenance finished with errors.");
+        run.error = Some(message);
+    }
+
+    if let Err(err) = db::maintenance::record_run(db, &run).await {
+        error!(?err, "🚨 Could not record database maintenance run.");
+    }
+
+    run
+}
+
+/// Time from `now` until the next `hour:00` UTC.
+fn until_next_run(now: DateTime<Utc>, hour: u32) -> Duration {
+    let today = now
+        .with_hour(hour)
+        .and_then(|t| t.with_minute(0))
+        .and_then(|t| t.with_second(0))
+        .and_then(|t| t.with_nanosecond(0))
+        .unwrap_or(now);
+    let next = if today > now { today } else { today + TimeDelta::days(1) };
+    (next - now).to_std().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone as _;
+
+    use super::*;
+
+    #[test]
+    fn test_until_next_run() {
+        let now = Utc.with_ymd_and_hms(2025, 3, 1, 1, 30, 0).unwrap();
+        assert_eq!(until_next_run(now, 3), Duration::from_secs(90 * 60));
+
+        // Already past today's run, so wait for tomorrow's
+        let now = Utc.with_ymd_and_hms(2025, 3, 1, 3, 0, 0).unwrap();
+        assert_eq!(until_next_run(now, 3), Duration::from_secs(24 * 3600));
+        let now = Utc.with_ymd_and_hms(2025, 3, 1, 23, 0, 0).unwrap();
+        assert_eq!(until_next_run(now, 3), Duration::from_secs(4 * 3600));
+    }
+}
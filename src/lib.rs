@@ -0,0 +1,209 @@
+//! `PagePouch`, a self-hosted bookmark manager.
+//!
+//! The server is run by the `pagepouch-rs` binary; the library is what it and the benchmarks in
+//! `benches/` share.
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use axum::extract::State;
+use reqwest::Client;
+
+use crate::{
+    api_usage::ApiUsage,
+    config::{AlertConfig, Config, FetchConfig},
+    db::{Db, user_session::SessionStore},
+    encryption::EncryptionProvider,
+    features::FeatureFlags,
+    mail::Mailer,
+    metrics::Metrics,
+    report::ErrorReporter,
+    translate::Translator,
+    update_check::UpdateCheck,
+};
+
+mod api_usage;
+mod assets;
+mod blocklist;
+mod bookmark_export;
+mod browser_import;
+mod clean;
+mod config;
+pub mod db;
+mod digest;
+mod encryption;
+mod error;
+mod export;
+mod features;
+mod fetch;
+mod fetch_retry;
+mod handler;
+mod highlight;
+mod hypothesis;
+mod jobs;
+mod link_check;
+#[cfg(feature = "loadtest")]
+mod loadtest;
+mod lockout;
+mod mail;
+mod metrics;
+mod onboarding;
+mod page_text;
+mod report;
+mod restore;
+mod resurface;
+mod route;
+pub mod search;
+mod snapshot;
+mod suggest;
+mod thumbnail;
+mod totp;
+mod trace;
+mod translate;
+mod update_check;
+mod vault;
+mod watch;
+mod wayback;
+
+/// Shared application state accessible across all request handlers.
+#[allow(clippy::struct_excessive_bools)] // independent switches of the instance
+pub struct AppState {
+    /// Encryption provider for password hashing and token generation.
+    pub encryption: EncryptionProvider,
+    /// Reader and writer connection pools for `SQLite`.
+    pub db: Db,
+    /// Where user sessions are kept, the database unless Redis is configured.
+    pub sessions: Arc<dyn SessionStore>,
+    /// Shared HTTP client for external requests.
+    pub http_client: Client,
+    /// Instance-wide settings for fetching saved URLs.
+    pub fetch_config: FetchConfig,
+    /// Where handler panics and internal errors are reported, if error reporting is configured.
+    pub reporter: Option<Arc<dyn ErrorReporter>>,
+    /// Whether the admin can search all users' bookmarks, see [`handler::admin::admin_search_handler`].
+    pub admin_search_enabled: bool,
+    /// Whether anyone can sign up for an account, see [`handler::auth_handler::register_handler`].
+    pub registration_enabled: bool,
+    /// Where bookmark titles and descriptions are translated, if translation is configured.
+    pub translator: Option<Arc<dyn Translator>>,
+    /// Which features are enabled for the instance, consulted together with per-user overrides.
+    pub features: FeatureFlags,
+    /// The daily check for a new release, if it's turned on.
+    pub update_check: Option<Arc<UpdateCheck>>,
+    /// Requests made with API tokens that haven't been written to the database yet.
+    pub api_usage: Arc<ApiUsage>,
+    /// Requests and scheduled job runs counted, with how many failed.
+    pub metrics: Arc<Metrics>,
+    /// When failures are posted to the admin's webhook, if alerting is configured.
+    pub alerts: Option<AlertConfig>,
+    /// Whether bookmarked links are checked for ones that have rotted, see [`link_check`].
+    pub link_check_enabled: bool,
+    /// Whether new bookmarks are sent to the Wayback Machine, see [`wayback`].
+    pub wayback_submit: bool,
+    /// Where email is sent through, if an SMTP server is configured, see [`mail`].
+    pub mailer: Option<Arc<dyn Mailer>>,
+    /// Address the instance is reached at, if it's configured, for links sent out of it.
+    pub public_url: Option<String>,
+}
+
+/// Type alias for extracting the application state in request handlers.
+pub type ApiState = State<Arc<AppState>>;
+
+/// Runs the server until it's stopped.
+///
+/// 1. Initializes configuration from the environment
+/// 2. Establishes database connection
+/// 3. Sets up encryption provider
+/// 4. Starts the web server
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - Database connection fails
+/// - Server fails to bind to the configured address
+/// - Server encounters an unrecoverable error during operation
+pub async fn run() -> Result<()> {
+    let config = Config::try_init()?;
+    db::timing::set_slow_query_threshold(config.slow_query_threshold);
+    let db = db::connect(&config.database_url, config.database_key.as_deref()).await?;
+    let sessions = db::user_session::session_store_from_config(&db, config.redis_url.as_deref()).await?;
+    let metrics = Arc::new(Metrics::default());
+    jobs::spawn_nightly_maintenance(db.clone(), sessions.clone(), config.maintenance_hour, metrics.clone());
+    let encryption = EncryptionProvider::new(config.root_key);
+    #[cfg(feature = "loadtest")]
+    loadtest::prepare(&db, &encryption)
+        .await
+        .map_err(|err| anyhow::anyhow!("🔥 Could not prepare the load testing fixtures: {err:?}"))?;
+
+    // Create shared HTTP client with the configured settings for title fetching
+    let http_client = fetch::build_client(&config.fetch)?;
+    let reporter = report::reporter_from_dsn(config.error_reporting_dsn.as_deref())?;
+    let translator = translate::translator_from_config(config.translate_url.as_deref(), config.translate_api_key)?;
+    let mailer = mail::mailer_from_config(config.mail.as_ref())?;
+    let update_check = if config.update_check {
+        let update_check = Arc::new(UpdateCheck::new()?);
+        jobs::spawn_update_check(update_check.clone(), metrics.clone());
+        Some(update_check)
+    } else {
+        None
+    };
+    let api_usage = Arc::new(ApiUsage::default());
+    jobs::spawn_api_usage_flush(db.clone(), api_usage.clone(), metrics.clone());
+    if let Some(watch) = config.watch {
+        jobs::spawn_directory_watch(db.clone(), watch, metrics.clone());
+    }
+    let link_check_days = config.link_check_days.filter(|_| config.fetch.enabled);
+    if let Some(days) = link_check_days {
+        jobs::spawn_link_check(
+            db.clone(),
+            http_client.clone(),
+            config.fetch.clone(),
+            days,
+            config.wayback.lookup,
+            metrics.clone(),
+        );
+    }
+    if config.fetch.enabled {
+        jobs::spawn_fetch_retry(db.clone(), http_client.clone(), config.fetch.clone(), metrics.clone());
+    }
+    if let (Some(mailer), Some(public_url)) = (&mailer, &config.public_url) {
+        jobs::spawn_email_digest(db.clone(), mailer.clone(), public_url.clone(), metrics.clone());
+    }
+    let wayback_submit = config.wayback.submit && config.fetch.enabled;
+    let alerts = config.alerts.clone();
+    if let Some(alerter) = metrics::alerter_from_config(config.alerts)? {
+        jobs::spawn_alert_check(metrics.clone(), alerter);
+    }
+
+    let app_state = Arc::new(AppState {
+        encryption,
+        db,
+        sessions,
+        http_client,
+        fetch_config: config.fetch,
+        reporter,
+        admin_search_enabled: config.admin_search_enabled,
+        registration_enabled: config.registration_enabled,
+        translator,
+        features: FeatureFlags::new(config.features),
+        update_check,
+        api_usage,
+        metrics,
+        alerts,
+        link_check_enabled: link_check_days.is_some(),
+        wayback_submit,
+        mailer,
+        public_url: config.public_url,
+    });
+
+    route::serve(
+        app_state,
+        &config.rate_limit,
+        config.areas,
+        &config.api_cors_origins,
+        config.otlp_endpoint.as_deref(),
+    )
+    .await?;
+
+    Ok(())
+}
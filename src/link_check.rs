@@ -0,0 +1,79 @@
+//! Scheduled checking of bookmarked links, to find the ones that have rotted.
+//!
+//! When turned on with `PAGEPOUCH_LINK_CHECK_DAYS`, a batch of bookmarks is checked every hour
+//! (see [`crate::jobs::spawn_link_check`]), each one again once that many days have passed. A
+//! link is checked with a `HEAD` request, and a `GET` if that fails, since some servers don't
+//! answer `HEAD` properly. It's broken if the page is gone (404 or 410), or if the site can't be
+//! reached or doesn't answer in time; other errors, such as 403s from sites turning away bots or
+//! 5xx that may well pass, don't count. Broken bookmarks are found with `is:broken`, and a copy of
+//! them may be looked up in the Wayback Machine, see [`crate::wayback`].
+
+use reqwest::{Client, StatusCode};
+
+use crate::{config::FetchConfig, db::link_checks::LinkCheck, fetch::check_destination};
+
+/// Checks whether a link still works.
+#[tracing::instrument(level = "trace", name = "http.link_check", skip_all, fields(url = url))]
+pub async fn check_link(client: &Client, config: &FetchConfig, url: &str) -> LinkCheck {
+    if let Err(err) = check_destination(config, url) {
+        return LinkCheck {
+            error: Some(format!("Not checked: {err}")),
+            ..LinkCheck::default()
+        };
+    }
+    let response = match client.head(url).send().await {
+        Ok(response) if response.status().is_success() => Ok(response),
+        _ => client.get(url).send().await,
+    };
+    match response {
+        Ok(response) => {
+            let status = response.status();
+            LinkCheck {
+                status: Some(status.as_u16()),
+                error: None,
+                is_broken: is_gone(status),
+                archived_url: None,
+            }
+        }
+        Err(err) => LinkCheck {
+            status: None,
+            error: Some(describe_error(&err).to_string()),
+            is_broken: err.is_timeout() || err.is_connect(),
+            archived_url: None,
+        },
+    }
+}
+
+/// Whether a status says the page is no more.
+fn is_gone(status: StatusCode) -> bool {
+    matches!(status, StatusCode::NOT_FOUND | StatusCode::GONE)
+}
+
+/// Why a request got no response, briefly.
+fn describe_error(err: &reqwest::Error) -> &'static str {
+    if err.is_timeout() {
+        "Timed out"
+    } else if err.is_connect() {
+        "Could not connect"
+    } else if err.is_redirect() {
+        "Too many redirects"
+    } else {
+        "Request failed"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_gone() {
+        assert!(is_gone(StatusCode::NOT_FOUND));
+        assert!(is_gone(StatusCode::GONE));
+        assert!(!is_gone(StatusCode::OK));
+        // Sites turning away bots, and errors that may well pass
+        assert!(!is_gone(StatusCode::FORBIDDEN));
+        assert!(!is_gone(StatusCode::TOO_MANY_REQUESTS));
+        assert!(!is_gone(StatusCode::SERVICE_UNAVAILABLE));
+    }
+}
@@ -0,0 +1,132 @@
+//! Load testing fixtures, for builds with the `loadtest` feature.
+//!
+//! Such a build is for measuring request handling with tools like `oha`, and must never be
+//! deployed:
+//! - rate limiting is off, so the limits don't end up being what's measured
+//! - an empty database is seeded on startup, in release builds too, with the same repeatable data
+//!   as the benchmarks (`PAGEPOUCH_SEED`, `large` by default)
+//! - every user gets a fixed API token, `pp_loadtest_<username>`
+//! - `GET /loadtest/fixtures` answers, without signing in, with what a load test needs: a session
+//!   cookie and API token for each user, and bookmark IDs, tags and searches that exist for them
+//!
+//! For example:
+//!
+//! ```sh
+//! cargo run --release --features loadtest
+//! COOKIE=$(curl -s localhost:8888/loadtest/fixtures | jq -r '.users[0].cookie')
+//! oha -z 30s -H "Cookie: $COOKIE" 'localhost:8888/api/bookmarks?q=rust'
+//! ```
+
+use std::sync::Arc;
+
+use axum::{Json, Router, extract::State, response::IntoResponse, routing::get};
+use serde::Serialize;
+use tracing::info;
+
+use crate::{
+    ApiState, AppState,
+    db::{
+        self, Db,
+        api_tokens::{self, TokenScope},
+        bookmarks::{self, BookmarkId, SortOrder},
+        seed::SeedProfile,
+        tags,
+        user_session::make_user_session,
+        users,
+    },
+    encryption::{API_TOKEN_PREFIX, EncryptionProvider},
+    error::AppResult,
+    handler::auth_handler::SESSION_COOKIE,
+};
+
+/// Name of the API token every user is given.
+const TOKEN_NAME: &str = "loadtest";
+/// Bookmark IDs listed for each user, newest first.
+const FIXTURE_BOOKMARKS: i64 = 100;
+/// Tags listed for each user, most used first.
+const FIXTURE_TAGS: usize = 20;
+/// Searches that find something in the seeded data, by what they exercise.
+const FIXTURE_SEARCHES: [&str; 5] = [
+    "rust",
+    "rust database",
+    "rust AND async AND server",
+    "\"memory cache\"",
+    "search #rust",
+];
+
+/// What a load test can use, as answered by `GET /loadtest/fixtures`.
+#[derive(Serialize)]
+struct Fixtures {
+    users: Vec<FixtureUser>,
+    searches: &'static [&'static str],
+}
+
+#[derive(Serialize)]
+struct FixtureUser {
+    username: String,
+    /// A `Cookie` header value with a new session
+    cookie: String,
+    /// Sent as `Authorization: Bearer <token>` for the JSON API
+    api_token: String,
+    bookmark_ids: Vec<BookmarkId>,
+    tags: Vec<String>,
+}
+
+/// The fixed API token of a user.
+fn api_token(username: &str) -> String {
+    format!("{API_TOKEN_PREFIX}{TOKEN_NAME}_{username}")
+}
+
+/// Seeds an empty database and gives every user their API token.
+///
+/// # Errors
+///
+/// Returns an error if database operations fail.
+pub async fn prepare(db: &Db, encryption: &EncryptionProvider) -> AppResult<()> {
+    println!("⚠️ Load testing build: rate limiting is off and /loadtest/fixtures hands out sessions. Never deploy it.");
+    db::init_for_dev(&db.writer, SeedProfile::BENCH).await?;
+
+    for user in users::get_active_users(db).await? {
+        let token_hash = encryption.hash_api_token(&api_token(&user.username))?;
+        if api_tokens::get_token_user(db, &token_hash).await?.is_none() {
+            api_tokens::create_api_token(db, user.user_id, TOKEN_NAME, &token_hash, TokenScope::Full, &[]).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Routes serving the fixtures, outside the auth layer.
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new().route("/loadtest/fixtures", get(fixtures_handler))
+}
+
+/// Handler listing what a load test can use, with a new session for every user
+async fn fixtures_handler(State(state): ApiState) -> AppResult<impl IntoResponse> {
+    let mut fixture_users = Vec::new();
+    for user in users::get_active_users(&state.db).await? {
+        let session = make_user_session(state.sessions.as_ref(), &user).await?;
+        let signed_token = state.encryption.sign_token(session.session_token())?;
+        let bookmark_ids = bookmarks::get_user_bookmarks(&state.db, user.user_id, SortOrder::Newest, FIXTURE_BOOKMARKS, 0)
+            .await?
+            .into_iter()
+            .map(|bookmark| bookmark.id)
+            .collect();
+        let mut user_tags = tags::get_user_tags(&state.db, user.user_id).await?;
+        user_tags.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+
+        fixture_users.push(FixtureUser {
+            cookie: format!("{SESSION_COOKIE}={signed_token}"),
+            api_token: api_token(&user.username),
+            bookmark_ids,
+            tags: user_tags.into_iter().take(FIXTURE_TAGS).map(|tag| tag.name).collect(),
+            username: user.username,
+        });
+    }
+    info!(users = fixture_users.len(), "🏋️ Handed out load testing fixtures.");
+
+    Ok(Json(Fixtures {
+        users: fixture_users,
+        searches: &FIXTURE_SEARCHES,
+    }))
+}
@@ -0,0 +1,119 @@
+//! Locking out password guessing, on top of the per-IP rate limit of the sign-in routes.
+//!
+//! Failed sign-ins, wrong passwords as well as wrong two-factor codes, are counted per login name
+//! and per IP address. After a few in a row, further attempts are turned away without checking the
+//! password for a minute, then for twice as long after each further failure, up to an hour. A login
+//! name that doesn't exist is counted the same, so lockouts don't tell which accounts exist. The
+//! count starts over a day after the last failure, and a successful sign-in clears the login name's
+//! (but not the IP address's, or guessing could be spread over an account of one's own).
+
+use anyhow::anyhow;
+use axum::http::StatusCode;
+use chrono::Utc;
+use tracing::{error, warn};
+
+use crate::{
+    db::{Db, login_failures},
+    error::{AppError, AppResult},
+};
+
+/// Failures in a row before a login name is locked out.
+const LOGIN_FAILURES_BEFORE_LOCKOUT: i64 = 5;
+/// Failures in a row before an IP address is locked out; more, as it may be shared by many people.
+const IP_FAILURES_BEFORE_LOCKOUT: i64 = 20;
+/// How long the first lockout lasts, in seconds; each further failure doubles it.
+const FIRST_LOCKOUT_SECS: i64 = 60;
+/// Longest a lockout lasts, in seconds.
+const MAX_LOCKOUT_SECS: i64 = 3600;
+/// Failures further apart than this, in seconds, aren't counted as in a row.
+pub const FAILURE_WINDOW_SECS: i64 = 86400;
+
+/// How long to lock out after `failures` in a row, if that many call for it.
+fn lockout_secs(failures: i64, threshold: i64) -> Option<i64> {
+    let doublings = u32::try_from(failures - threshold).ok()?;
+    Some(
+        FIRST_LOCKOUT_SECS
+            .saturating_mul(2_i64.saturating_pow(doublings))
+            .min(MAX_LOCKOUT_SECS),
+    )
+}
+
+/// The login name failures are counted under: as typed in, trimmed and in lower case, as
+/// usernames and emails are matched regardless of case.
+#[must_use]
+pub fn login_key(login: &str) -> String {
+    login.trim().to_lowercase()
+}
+
+/// Turns a sign-in away with a 429 error if its login name or IP address is locked out.
+///
+/// # Errors
+///
+/// Returns the 429 error, or an error if the database query fails.
+pub async fn check(db: &Db, login: &str, ip: Option<&str>) -> AppResult<()> {
+    let Some(locked_until) = login_failures::get_locked_until(db, &login_key(login), ip).await? else {
+        return Ok(());
+    };
+    let minutes = ((locked_until - Utc::now().timestamp()).max(1) + 59) / 60;
+    Err(AppError::with_status(
+        anyhow!("Sign-in for '{login}' from {ip:?} is locked out"),
+        StatusCode::TOO_MANY_REQUESTS,
+    )
+    .user_message(format!(
+        "Too many failed attempts. Try again in {minutes} minute{}.",
+        if minutes == 1 { "" } else { "s" }
+    )))
+}
+
+/// Counts a failed sign-in against its login name and IP address, locking out whichever had too
+/// many. Failing to record it is logged rather than returned, so the sign-in's own error is shown.
+pub async fn record_failure(db: &Db, login: &str, ip: Option<&str>) {
+    let login = login_key(login);
+    let keys = [
+        Some(("login", login.as_str(), LOGIN_FAILURES_BEFORE_LOCKOUT)),
+        ip.map(|ip| ("ip", ip, IP_FAILURES_BEFORE_LOCKOUT)),
+    ];
+    for (kind, key, threshold) in keys.into_iter().flatten() {
+        let result = async {
+            let failures = login_failures::record_failure(db, kind, key, FAILURE_WINDOW_SECS).await?;
+            if let Some(secs) = lockout_secs(failures, threshold) {
+                login_failures::lock(db, kind, key, Utc::now().timestamp() + secs).await?;
+                warn!(kind, key, failures, secs, "🔒 Locked out sign-ins after repeated failures.");
+            }
+            anyhow::Ok(())
+        }
+        .await;
+        if let Err(err) = result {
+            error!(?err, kind, key, "🚨 Could not record a failed sign-in.");
+        }
+    }
+}
+
+/// Forgets the failed sign-ins of a login name once it signed in.
+pub async fn record_success(db: &Db, login: &str) {
+    if let Err(err) = login_failures::clear_login(db, &login_key(login)).await {
+        error!(?err, "🚨 Could not clear failed sign-ins.");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lockout_secs() {
+        assert_eq!(lockout_secs(0, 5), None);
+        assert_eq!(lockout_secs(4, 5), None);
+        assert_eq!(lockout_secs(5, 5), Some(60));
+        assert_eq!(lockout_secs(6, 5), Some(120));
+        assert_eq!(lockout_secs(8, 5), Some(480));
+        assert_eq!(lockout_secs(11, 5), Some(MAX_LOCKOUT_SECS));
+        assert_eq!(lockout_secs(500, 5), Some(MAX_LOCKOUT_SECS));
+    }
+
+    #[test]
+    fn test_login_key() {
+        assert_eq!(login_key("  Ann@Example.com "), "ann@example.com");
+        assert_eq!(login_key("ann"), login_key("ANN"));
+    }
+}
@@ -1,73 +1,30 @@
-use std::{sync::Arc, time::Duration};
+use std::path::PathBuf;
 
-use anyhow::Result;
-use axum::extract::State;
+use anyhow::{Context as _, Result};
 use dotenvy::dotenv;
-use reqwest::Client;
-use sqlx::SqlitePool;
-
-use crate::{config::Config, encryption::EncryptionProvider};
-
-mod assets;
-mod config;
-mod db;
-mod encryption;
-mod error;
-mod handler;
-mod route;
-mod search;
-mod trace;
-
-/// Shared application state accessible across all request handlers.
-pub struct AppState {
-    /// Encryption provider for password hashing and token generation.
-    pub encryption: EncryptionProvider,
-    /// Database connection pool for `SQLite`.
-    pub pool: SqlitePool,
-    /// Shared HTTP client for external requests.
-    pub http_client: Client,
-}
-
-/// Type alias for extracting the application state in request handlers.
-pub type ApiState = State<Arc<AppState>>;
+use pagepouch_rs::db::seed;
 
 /// Main entry point for the application.
 ///
-/// Initializes the application by:
-/// 1. Loading environment variables from `.env` file
-/// 2. Initializing configuration
-/// 3. Establishing database connection
-/// 4. Setting up encryption provider
-/// 5. Starting the web server
+/// Loads environment variables from the `.env` file, then runs the server (see
+/// [`pagepouch_rs::run`]). With `--bench-db <path>`, it instead writes a database for the
+/// benchmarks to `path` and exits; see [`seed::create_fixture`].
 ///
 /// # Errors
 ///
-/// Returns an error if:
-/// - Database connection fails
-/// - Server fails to bind to the configured address
-/// - Server encounters an unrecoverable error during operation
+/// Returns an error if the arguments aren't understood, or the server or fixture generation fails.
 #[tokio::main]
 async fn main() -> Result<()> {
     // Load environment variables from the .env file
     dotenv().ok();
-    let config = Config::try_init()?;
-    let pool = db::connect(&config.database_url).await?;
-    let encryption = EncryptionProvider::new(config.root_key);
-
-    // Create shared HTTP client with optimized settings for title fetching
-    let http_client = Client::builder()
-        .timeout(Duration::from_millis(1000))
-        .connect_timeout(Duration::from_millis(500))
-        .user_agent("PagePouch/1.0")
-        .build()?;
-
-    let app_state = Arc::new(AppState {
-        encryption,
-        pool,
-        http_client,
-    });
-
-    route::serve(app_state).await?;
 
-    Ok(())
+    let mut args = std::env::args().skip(1);
+    match args.next().as_deref() {
+        None => pagepouch_rs::run().await,
+        Some("--bench-db") => {
+            let path = args.next().map(PathBuf::from).context("🔥 Usage: pagepouch-rs --bench-db <path>")?;
+            seed::create_fixture(&path, seed::SeedProfile::from_env(seed::SeedProfile::BENCH)?).await
+        }
+        Some(arg) => anyhow::bail!("🔥 Unknown argument '{arg}'. Run without arguments to start the server, or with --bench-db <path>."),
+    }
 }
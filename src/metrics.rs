@@ -0,0 +1,367 @@
+//! Error rates of routes and scheduled jobs, and alerts when they get too high.
+//!
+//! Every response and every run of a scheduled job is counted in memory twice: in totals since the
+//! server started, shown on the admin dashboard and at `/admin/metrics`, and in counts for the
+//! current alert window. When an alert webhook is configured, the window's counts are checked at
+//! its end (see [`crate::jobs::spawn_alert_check`]) and any route answering with server errors, or
+//! job failing, more often than its threshold is posted to the webhook. A route or job is only
+//! posted about when it starts failing, not again for every window it keeps failing in.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
+
+use anyhow::{Context as _, Result};
+use axum::{
+    extract::{MatchedPath, Request, State},
+    middleware::Next,
+    response::Response,
+};
+use reqwest::{Client, header::CONTENT_TYPE};
+use serde::Serialize;
+use serde_json::json;
+
+use crate::{ApiState, api_usage::UNKNOWN_ENDPOINT, config::AlertConfig};
+
+/// Maximum time to spend posting alerts.
+const ALERT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Requests or runs counted, and how many of them failed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Tally {
+    pub total: u64,
+    pub failed: u64,
+}
+
+impl Tally {
+    fn add(&mut self, failed: bool) {
+        self.total += 1;
+        if failed {
+            self.failed += 1;
+        }
+    }
+
+    /// Share of failures, in whole percent.
+    #[must_use]
+    pub fn failure_percent(self) -> u64 {
+        (self.failed * 100).checked_div(self.total).unwrap_or(0)
+    }
+
+    /// Whether at least `percent` percent failed.
+    fn exceeds(self, percent: u64) -> bool {
+        self.failed > 0 && self.failed * 100 >= percent * self.total
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct Counts {
+    /// By endpoint, like `GET /api/v1/tags`
+    routes: HashMap<String, Tally>,
+    /// By job name
+    jobs: HashMap<&'static str, Tally>,
+}
+
+impl Counts {
+    fn record_request(&mut self, endpoint: &str, failed: bool) {
+        match self.routes.get_mut(endpoint) {
+            Some(tally) => tally.add(failed),
+            None => self.routes.entry(endpoint.to_string()).or_default().add(failed),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Inner {
+    /// Since the server started
+    totals: Counts,
+    /// Since the last alert check
+    window: Counts,
+    /// Routes and jobs alerted about that haven't recovered yet
+    firing: HashSet<String>,
+}
+
+/// Request and job counts, see the [module docs](self).
+#[derive(Default)]
+pub struct Metrics {
+    inner: Mutex<Inner>,
+}
+
+/// A route's or job's counts, for the dashboard and `/admin/metrics`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct RateView {
+    pub name: String,
+    #[serde(flatten)]
+    pub tally: Tally,
+    pub failure_percent: u64,
+}
+
+/// Everything counted since the server started.
+#[derive(Clone, Debug, Serialize)]
+pub struct MetricsSummary {
+    /// Routes by endpoint, the ones with most server errors first
+    pub routes: Vec<RateView>,
+    /// Scheduled jobs by name
+    pub jobs: Vec<RateView>,
+}
+
+/// A route or job failing more often than its threshold.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Alert {
+    /// `route` or `job`
+    pub kind: &'static str,
+    pub name: String,
+    #[serde(flatten)]
+    pub tally: Tally,
+    pub failure_percent: u64,
+}
+
+impl Alert {
+    fn new(kind: &'static str, name: &str, tally: Tally) -> Self {
+        Self {
+            kind,
+            name: name.to_string(),
+            tally,
+            failure_percent: tally.failure_percent(),
+        }
+    }
+
+    /// Identifies the route or job, to tell whether it was already alerted about.
+    fn key(&self) -> String {
+        format!("{} {}", self.kind, self.name)
+    }
+
+    /// The alert as a line of text, e.g. "GET /api/v1/tags failed 12 of 40 requests (30%)".
+    fn describe(&self) -> String {
+        let what = if self.kind == "job" { "runs" } else { "requests" };
+        format!(
+            "{} failed {} of {} {what} ({}%)",
+            self.name, self.tally.failed, self.tally.total, self.failure_percent
+        )
+    }
+}
+
+impl Metrics {
+    /// The counts, recovered if a thread panicked holding them.
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Counts a response to a request to `endpoint`; server errors count as failures.
+    pub fn record_request(&self, endpoint: &str, failed: bool) {
+        let mut inner = self.lock();
+        inner.totals.record_request(endpoint, failed);
+        inner.window.record_request(endpoint, failed);
+    }
+
+    /// Counts a run of a scheduled job.
+    pub fn record_job(&self, job: &'static str, ok: bool) {
+        let mut inner = self.lock();
+        inner.totals.jobs.entry(job).or_default().add(!ok);
+        inner.window.jobs.entry(job).or_default().add(!ok);
+    }
+
+    /// Everything counted since the server started.
+    pub fn summary(&self) -> MetricsSummary {
+        let totals = self.lock().totals.clone();
+        let view = |name: &str, tally: Tally| RateView {
+            name: name.to_string(),
+            tally,
+            failure_percent: tally.failure_percent(),
+        };
+        let mut routes: Vec<RateView> = totals.routes.iter().map(|(name, tally)| view(name, *tally)).collect();
+        routes.sort_by(|a, b| b.tally.failed.cmp(&a.tally.failed).then_with(|| a.name.cmp(&b.name)));
+        let mut jobs: Vec<RateView> = totals.jobs.iter().map(|(name, tally)| view(name, *tally)).collect();
+        jobs.sort_by(|a, b| a.name.cmp(&b.name));
+        MetricsSummary { routes, jobs }
+    }
+
+    /// Ends the current alert window, returning the routes and jobs that started failing in it.
+    pub fn check_alerts(&self, config: &AlertConfig) -> Vec<Alert> {
+        let mut inner = self.lock();
+        let window = std::mem::take(&mut inner.window);
+        let alerts = over_thresholds(&window, config);
+        let firing: HashSet<String> = alerts.iter().map(Alert::key).collect();
+        let new = alerts.into_iter().filter(|alert| !inner.firing.contains(&alert.key())).collect();
+        inner.firing = firing;
+        new
+    }
+
+    /// Forgets that alerts were sent, so they're sent again after the next window if still failing.
+    pub fn forget_alerts(&self, alerts: &[Alert]) {
+        let mut inner = self.lock();
+        for alert in alerts {
+            inner.firing.remove(&alert.key());
+        }
+    }
+}
+
+/// The routes and jobs whose failures in `window` are over their thresholds. Routes with fewer
+/// than the minimum requests are left out, so one error on a quiet route doesn't alert.
+fn over_thresholds(window: &Counts, config: &AlertConfig) -> Vec<Alert> {
+    let mut alerts: Vec<Alert> = window
+        .routes
+        .iter()
+        .filter(|(_, tally)| tally.total >= config.min_requests && tally.exceeds(config.error_percent))
+        .map(|(name, tally)| Alert::new("route", name, *tally))
+        .chain(
+            window
+                .jobs
+                .iter()
+                .filter(|(_, tally)| tally.exceeds(config.job_failure_percent))
+                .map(|(name, tally)| Alert::new("job", name, *tally)),
+        )
+        .collect();
+    alerts.sort_by(|a, b| a.kind.cmp(b.kind).then_with(|| a.name.cmp(&b.name)));
+    alerts
+}
+
+/// Middleware counting every response by endpoint, see [`Metrics::record_request`].
+///
+/// Applied around the whole app, so requests to routes behind the login are counted once.
+pub async fn count_requests_middleware(State(state): ApiState, req: Request, next: Next) -> Response {
+    let endpoint = req.extensions().get::<MatchedPath>().map_or_else(
+        || UNKNOWN_ENDPOINT.to_string(),
+        |path| format!("{} {}", req.method(), path.as_str()),
+    );
+
+    let response = next.run(req).await;
+
+    state.metrics.record_request(&endpoint, response.status().is_server_error());
+    response
+}
+
+/// Posts alerts to the configured webhook.
+pub struct Alerter {
+    client: Client,
+    pub config: AlertConfig,
+}
+
+impl Alerter {
+    /// Posts alerts as JSON, with a `text` summary that chat webhooks (Slack, Mattermost and the
+    /// like) show as a message, and the `alerts` themselves for anything else.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the webhook can't be reached or doesn't accept the alerts.
+    pub async fn send(&self, alerts: &[Alert]) -> Result<()> {
+        let minutes = self.config.window.as_secs() / 60;
+        let lines: Vec<String> = alerts.iter().map(|alert| format!("• {}", alert.describe())).collect();
+        let text = format!(
+            "⚠️ PagePouch is failing more than it should, over the last {minutes}-minute window:\n{}",
+            lines.join("\n")
+        );
+        self.client
+            .post(&self.config.webhook_url)
+            .header(CONTENT_TYPE, "application/json")
+            .body(json!({ "text": text, "alerts": alerts }).to_string())
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Builds the alerter for the configured webhook, if any.
+///
+/// Alerts use their own client rather than the one for fetching saved URLs, so they aren't sent
+/// through the fetch proxy or cut short by its timeouts.
+///
+/// # Errors
+///
+/// Returns an error if the HTTP client cannot be built.
+pub fn alerter_from_config(config: Option<AlertConfig>) -> Result<Option<Arc<Alerter>>> {
+    let Some(config) = config else {
+        return Ok(None);
+    };
+    let client = Client::builder()
+        .timeout(ALERT_TIMEOUT)
+        .build()
+        .with_context(|| "🔥 Could not build the alerting HTTP client")?;
+    println!(
+        "🚨 Alerts are posted when {}% of a route's requests or {}% of a job's runs fail in a {}-minute window.",
+        config.error_percent,
+        config.job_failure_percent,
+        config.window.as_secs() / 60
+    );
+    Ok(Some(Arc::new(Alerter { client, config })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> AlertConfig {
+        AlertConfig {
+            webhook_url: "https://hooks.example.com/alerts".to_string(),
+            error_percent: 10,
+            job_failure_percent: 50,
+            min_requests: 5,
+            window: Duration::from_secs(15 * 60),
+        }
+    }
+
+    fn record_requests(metrics: &Metrics, endpoint: &str, total: u64, failed: u64) {
+        for i in 0..total {
+            metrics.record_request(endpoint, i < failed);
+        }
+    }
+
+    #[test]
+    fn test_failure_percent() {
+        assert_eq!(Tally { total: 3, failed: 1 }.failure_percent(), 33);
+        assert_eq!(Tally::default().failure_percent(), 0);
+        assert!(Tally { total: 10, failed: 1 }.exceeds(10));
+        assert!(!Tally { total: 11, failed: 1 }.exceeds(10));
+        assert!(!Tally { total: 10, failed: 0 }.exceeds(0));
+    }
+
+    #[test]
+    fn test_alert_thresholds() {
+        let metrics = Metrics::default();
+        record_requests(&metrics, "GET /api/bookmarks", 20, 4);
+        // Too few requests to tell
+        record_requests(&metrics, "GET /quiet", 2, 2);
+        record_requests(&metrics, "GET /fine", 100, 1);
+        metrics.record_job("maintenance", false);
+        metrics.record_job("directory watch", true);
+
+        let alerts = metrics.check_alerts(&config());
+        assert_eq!(
+            alerts.iter().map(|alert| (alert.kind, alert.name.as_str())).collect::<Vec<_>>(),
+            [("job", "maintenance"), ("route", "GET /api/bookmarks")]
+        );
+        assert_eq!(alerts[1].describe(), "GET /api/bookmarks failed 4 of 20 requests (20%)");
+
+        // The window starts over, but the totals are kept
+        assert!(metrics.check_alerts(&config()).is_empty());
+        let summary = metrics.summary();
+        assert_eq!(summary.routes[0].name, "GET /api/bookmarks");
+        assert_eq!(summary.routes[0].tally, Tally { total: 20, failed: 4 });
+        assert_eq!(summary.jobs.len(), 2);
+    }
+
+    #[test]
+    fn test_alerts_only_when_starting_to_fail() {
+        let metrics = Metrics::default();
+        record_requests(&metrics, "POST /bookmarks", 10, 10);
+        assert_eq!(metrics.check_alerts(&config()).len(), 1);
+
+        // Still failing: already alerted about
+        record_requests(&metrics, "POST /bookmarks", 10, 10);
+        assert!(metrics.check_alerts(&config()).is_empty());
+
+        // Recovered, then failing again
+        record_requests(&metrics, "POST /bookmarks", 10, 0);
+        assert!(metrics.check_alerts(&config()).is_empty());
+        record_requests(&metrics, "POST /bookmarks", 10, 10);
+        let alerts = metrics.check_alerts(&config());
+        assert_eq!(alerts.len(), 1);
+
+        // Alerts that couldn't be sent are sent again
+        metrics.forget_alerts(&alerts);
+        record_requests(&metrics, "POST /bookmarks", 10, 10);
+        assert_eq!(metrics.check_alerts(&config()).len(), 1);
+    }
+}
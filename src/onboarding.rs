@@ -0,0 +1,115 @@
+//! The starter set new users can be given, so their first look at `PagePouch` isn't an empty list: a
+//! bookmark on how to use it and one more to try searching on, the tags on them, and a smart
+//! collection.
+//!
+//! The admin turns it on for the instance on the dashboard. Users get it at their first sign-in,
+//! once, however their account was made; accounts that existed before onboarding did count as
+//! signed in already. Starter bookmarks are saved with the `starter` source, so `source:starter`
+//! finds them to delete. Unlike the development data in [`crate::db::seed`], this is for real
+//! accounts.
+
+use tracing::{error, info};
+
+use crate::db::{self, Db, bookmarks::NewBookmark, users::User};
+
+/// A smart collection in the starter set.
+struct StarterCollection {
+    name: &'static str,
+    /// Rule in search syntax
+    query: &'static str,
+}
+
+/// A bookmark in the starter set.
+struct StarterBookmark {
+    url: &'static str,
+    title: &'static str,
+    description: &'static str,
+    tags: &'static [&'static str],
+    is_unread: bool,
+}
+
+const STARTER_BOOKMARKS: [StarterBookmark; 2] = [
+    StarterBookmark {
+        url: "https://github.com/anson-vandoren/pagepouch-rs#readme",
+        title: "How to use PagePouch",
+        description: "Save a link with Add Link at the top of the page, and tag it to find it again. Search by words, \
+                      #tags, site:example.com or is:unread, and keep searches you use often as smart collections, like \
+                      the Read later one you were given. When you're done with these starter bookmarks, search for \
+                      source:starter to find and delete them.",
+        tags: &["pagepouch", "getting-started"],
+        is_unread: true,
+    },
+    StarterBookmark {
+        url: "https://github.com/anson-vandoren/pagepouch-rs/blob/main/CHANGELOG.md",
+        title: "What's new in PagePouch",
+        description: "The changelog, with what each release added, including every filter the search box understands.",
+        tags: &["pagepouch", "reference"],
+        is_unread: false,
+    },
+];
+
+/// Smart collections in the starter set.
+const STARTER_COLLECTIONS: [StarterCollection; 1] = [StarterCollection {
+    name: "Read later",
+    query: "is:unread",
+}];
+
+/// The starter set's bookmarks, ready to save.
+fn starter_bookmarks() -> Vec<NewBookmark> {
+    STARTER_BOOKMARKS
+        .iter()
+        .map(|bookmark| NewBookmark {
+            url: bookmark.url.to_string(),
+            title: bookmark.title.to_string(),
+            description: Some(bookmark.description.to_string()),
+            tag_names: bookmark.tags.iter().map(ToString::to_string).collect(),
+            is_unread: bookmark.is_unread,
+            ..NewBookmark::default()
+        })
+        .collect()
+}
+
+/// Onboards a user at their first sign-in, giving them the starter set if the instance does.
+/// Failures are only logged, as signing in shouldn't fail over it.
+pub async fn onboard(db: &Db, user: &User) {
+    let give_starter_set = match db::instance_settings::get_starter_set(db).await {
+        Ok(enabled) => enabled,
+        Err(err) => {
+            error!(?err, "🚨 Could not load the starter set setting.");
+            return;
+        }
+    };
+    let (bookmarks, collections) = if give_starter_set {
+        let collections: Vec<(&str, &str)> = STARTER_COLLECTIONS
+            .iter()
+            .map(|collection| (collection.name, collection.query))
+            .collect();
+        (starter_bookmarks(), collections)
+    } else {
+        (Vec::new(), Vec::new())
+    };
+
+    match db::onboarding::onboard_user(db, user.user_id, &bookmarks, &collections).await {
+        Ok(true) if give_starter_set => info!(user = user.username, "🌱 Gave a new user the starter set."),
+        Ok(_) => {}
+        Err(err) => error!(user = user.username, ?err, "🚨 Could not onboard a new user."),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{handler::bookmarks::parse_tags, search::SearchQuery};
+
+    #[test]
+    fn test_starter_set_is_valid() {
+        for bookmark in starter_bookmarks() {
+            assert!(reqwest::Url::parse(&bookmark.url).is_ok(), "{}", bookmark.url);
+            // Tags as they'd be if typed into the form
+            assert_eq!(parse_tags(&bookmark.tag_names.join(",")), bookmark.tag_names);
+        }
+        for collection in STARTER_COLLECTIONS {
+            assert!(SearchQuery::explain(collection.query).warnings.is_empty(), "{}", collection.query);
+        }
+    }
+}
@@ -0,0 +1,298 @@
+//! Readable text of saved pages, for searching their content.
+//!
+//! When a bookmark is added, its page is fetched in the background and its main text kept: like
+//! reader modes do, the article is found (an `<article>` or `<main>` element, or else the element
+//! with the most paragraph text), and navigation, headers, footers, forms and anything whose class
+//! or ID marks it as a sidebar, menu, comment section or the like is left out. The text is indexed
+//! for `content:` searches, and for all searches of users who turned that on.
+
+use std::sync::{Arc, LazyLock};
+
+use anyhow::{Result, bail};
+use regex::Regex;
+use reqwest::{Client, header::CONTENT_TYPE};
+use tl::{HTMLTag, Node, NodeHandle, Parser, ParserOptions};
+use tracing::{debug, error, info};
+use uuid::Uuid;
+
+use crate::{
+    AppState,
+    config::FetchConfig,
+    db::{self, bookmarks::BookmarkId},
+    fetch::{check_destination, decode_html_entities, is_fetch_allowed, read_body},
+};
+
+/// Pages larger than this aren't read.
+const MAX_PAGE_BYTES: usize = 5 * 1024 * 1024;
+/// Text beyond this many characters isn't kept.
+const MAX_TEXT_CHARS: usize = 200_000;
+/// Paragraphs shorter than this don't count towards finding the article, as they're more likely
+/// captions, bylines or buttons.
+const MIN_PARAGRAPH_CHARS: usize = 25;
+/// Elements nested deeper than this are left out, rather than risk the stack.
+const MAX_DEPTH: usize = 256;
+
+/// Elements that never hold the article's text.
+const SKIPPED: [&str; 20] = [
+    "head", "script", "style", "noscript", "template", "svg", "canvas", "iframe", "object", "embed", "nav", "header", "footer", "aside",
+    "form", "button", "select", "textarea", "menu", "dialog",
+];
+/// Elements that start a new line of text.
+const BLOCKS: [&str; 24] = [
+    "p",
+    "div",
+    "section",
+    "article",
+    "main",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "li",
+    "ul",
+    "ol",
+    "dl",
+    "dt",
+    "dd",
+    "blockquote",
+    "pre",
+    "table",
+    "tr",
+    "figcaption",
+    "hr",
+    "br",
+];
+
+/// Scripts and styles end at their first closing tag, whatever they contain, so they're cut out
+/// before the page is parsed.
+static HIDDEN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?is)<script\b.*?</script\s*>|<style\b.*?</style\s*>|<noscript\b.*?</noscript\s*>|<template\b.*?</template\s*>")
+        .expect("valid pattern")
+});
+/// Classes and IDs of page furniture rather than content.
+static UNLIKELY: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)comment|sidebar|footer|header|masthead|\bnav|menu|breadcrumb|share|social|related|promo|sponsor|advert|\bads?\b|banner|cookie|consent|popup|modal|newsletter|subscribe")
+        .expect("valid pattern")
+});
+/// Classes and IDs that may be furniture by the above, but also name the content.
+static MAYBE_CONTENT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)article|main|content|body|post|entry|story").expect("valid pattern"));
+
+/// Fetches a newly bookmarked page and keeps its text, unless the user opted out of fetching.
+/// Failures are only logged, as nobody is waiting on this.
+pub async fn save_page_text(state: Arc<AppState>, user_id: Uuid, bookmark_id: BookmarkId, url: String) {
+    match is_fetch_allowed(&state.fetch_config, &state.db, user_id).await {
+        Ok(true) => {}
+        Ok(false) => return,
+        Err(err) => {
+            error!(?err, "🚨 Could not load fetch setting, not keeping page text.");
+            return;
+        }
+    }
+    let text = match fetch_page_text(&state.http_client, &state.fetch_config, &url).await {
+        Ok(Some(text)) => text,
+        Ok(None) => {
+            debug!(%bookmark_id, url, "📄 No readable text on the page.");
+            return;
+        }
+        Err(err) => {
+            debug!(%bookmark_id, url, %err, "📄 Could not read the page's text.");
+            return;
+        }
+    };
+    match db::page_text::set_page_text(&state.db, bookmark_id, &text).await {
+        Ok(()) => info!(%bookmark_id, chars = text.chars().count(), "📄 Kept the page's text."),
+        Err(err) => error!(%bookmark_id, ?err, "🚨 Could not save page text."),
+    }
+}
+
+/// Fetches a page and extracts its text, see [`extract`].
+///
+/// # Errors
+///
+/// Returns an error if the page may not be fetched, the request fails, or it isn't a web page.
+#[tracing::instrument(level = "trace", name = "http.page_text", skip_all, fields(url = url))]
+pub async fn fetch_page_text(client: &Client, config: &FetchConfig, url: &str) -> Result<Option<String>> {
+    check_destination(config, url)?;
+    let response = client.get(url).send().await?.error_for_status()?;
+    let content_type = response.headers().get(CONTENT_TYPE).and_then(|value| value.to_str().ok());
+    if let Some(content_type) = content_type.filter(|content_type| !content_type.contains("html")) {
+        bail!("Only the text of web pages is kept, not {content_type}");
+    }
+    let body = read_body(response, MAX_PAGE_BYTES).await?;
+    Ok(tokio::task::spawn_blocking(move || extract(&String::from_utf8_lossy(&body))).await?)
+}
+
+/// The main text of a page, a line per paragraph, heading or list item, or `None` if it has none.
+#[must_use]
+pub fn extract(html: &str) -> Option<String> {
+    let html = HIDDEN.replace_all(html, "");
+    let dom = tl::parse(&html, ParserOptions::default()).ok()?;
+    let parser = dom.parser();
+    let root = article_root(&dom.nodes().iter().filter_map(Node::as_tag).collect::<Vec<_>>(), parser);
+
+    let mut raw = String::new();
+    match root {
+        Some(root) => write_children(root, parser, &mut raw, 0),
+        None => {
+            for child in dom.children() {
+                write_text(*child, parser, &mut raw, 0);
+            }
+        }
+    }
+
+    let mut text = String::new();
+    for line in decode_html_entities(&raw.replace("&nbsp;", " ").replace("&#160;", " ")).lines() {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.is_empty() {
+            continue;
+        }
+        if !text.is_empty() {
+            text.push('\n');
+        }
+        text.push_str(&words.join(" "));
+    }
+    if let Some((cut, _)) = text.char_indices().nth(MAX_TEXT_CHARS) {
+        text.truncate(cut);
+    }
+    (!text.is_empty()).then_some(text)
+}
+
+/// The element holding the article: the first `<article>`, `<main>` or `role="main"` element, or
+/// else the one with the most text in paragraphs right inside it, or else `<body>`.
+fn article_root<'a, 'b>(tags: &[&'b HTMLTag<'a>], parser: &Parser<'a>) -> Option<&'b HTMLTag<'a>> {
+    let marked = ["article", "main"]
+        .into_iter()
+        .find_map(|name| tags.iter().find(|tag| is_named(tag, name)))
+        .or_else(|| {
+            tags.iter().find(|tag| {
+                tag.attributes()
+                    .get("role")
+                    .flatten()
+                    .is_some_and(|role| role.as_utf8_str().eq_ignore_ascii_case("main"))
+            })
+        });
+    if let Some(tag) = marked {
+        return Some(*tag);
+    }
+
+    let paragraph_chars = |tag: &HTMLTag<'a>| -> usize {
+        tag.children()
+            .top()
+            .iter()
+            .filter_map(|child| child.get(parser).and_then(Node::as_tag))
+            .filter(|child| is_named(child, "p"))
+            .map(|paragraph| paragraph.inner_text(parser).trim().chars().count())
+            .filter(|chars| *chars >= MIN_PARAGRAPH_CHARS)
+            .sum()
+    };
+    tags.iter()
+        .filter(|tag| !is_unlikely(tag))
+        .map(|tag| (paragraph_chars(tag), *tag))
+        .filter(|(chars, _)| *chars > 0)
+        .max_by_key(|(chars, _)| *chars)
+        .map(|(_, tag)| tag)
+        .or_else(|| tags.iter().find(|tag| is_named(tag, "body")).copied())
+}
+
+/// Writes the text inside an element, leaving out furniture.
+fn write_children(tag: &HTMLTag<'_>, parser: &Parser<'_>, out: &mut String, depth: usize) {
+    for child in tag.children().top().iter() {
+        write_text(*child, parser, out, depth + 1);
+    }
+}
+
+fn write_text(handle: NodeHandle, parser: &Parser<'_>, out: &mut String, depth: usize) {
+    if depth > MAX_DEPTH {
+        return;
+    }
+    match handle.get(parser) {
+        // Line breaks in the source don't end lines of text
+        Some(Node::Raw(text)) => out.extend(text.as_utf8_str().chars().map(|c| if c.is_whitespace() { ' ' } else { c })),
+        Some(Node::Tag(tag)) => {
+            let name = tag.name().as_utf8_str().to_ascii_lowercase();
+            if SKIPPED.contains(&name.as_str()) || is_unlikely(tag) {
+                return;
+            }
+            let is_block = BLOCKS.contains(&name.as_str());
+            if is_block {
+                out.push('\n');
+            } else {
+                // Inline elements' text runs on, but words in neighbouring cells mustn't join up
+                out.push(' ');
+            }
+            write_children(tag, parser, out, depth);
+            if is_block {
+                out.push('\n');
+            }
+        }
+        _ => {}
+    }
+}
+
+fn is_named(tag: &HTMLTag<'_>, name: &str) -> bool {
+    tag.name().as_utf8_str().eq_ignore_ascii_case(name)
+}
+
+/// Whether an element's class or ID marks it as page furniture.
+fn is_unlikely(tag: &HTMLTag<'_>) -> bool {
+    let names = [tag.attributes().class(), tag.attributes().id()]
+        .into_iter()
+        .flatten()
+        .map(|value| value.as_utf8_str())
+        .collect::<Vec<_>>()
+        .join(" ");
+    UNLIKELY.is_match(&names) && !MAYBE_CONTENT.is_match(&names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_article() {
+        let html = r#"<html><head><title>Post</title><style>p { color: red }</style></head><body>
+            <nav><a href="/">Home</a> <a href="/blog">Blog</a></nav>
+            <article>
+              <h1>Async &amp; await</h1>
+              <p>Futures do nothing
+                 until they're polled.</p>
+              <div class="share-buttons">Share on social media</div>
+              <ul><li>One</li><li>Two</li></ul>
+              <script>console.log("</p>")</script>
+            </article>
+            <footer>Copyright</footer>
+        </body></html>"#;
+        assert_eq!(
+            extract(html).as_deref(),
+            Some("Async & await\nFutures do nothing until they're polled.\nOne\nTwo")
+        );
+    }
+
+    #[test]
+    fn test_extract_finds_paragraphs() {
+        let html = r#"<body>
+            <div id="sidebar"><p>Popular posts you might have missed this week</p></div>
+            <div class="layout">
+              <div class="text">
+                <p>The first paragraph of the story is long enough to count.</p>
+                <p>And so is the second one, which follows it&nbsp;closely.</p>
+              </div>
+              <p>A short note</p>
+            </div>
+        </body>"#;
+        assert_eq!(
+            extract(html).as_deref(),
+            Some("The first paragraph of the story is long enough to count.\nAnd so is the second one, which follows it closely.")
+        );
+    }
+
+    #[test]
+    fn test_extract_keeps_content_named_like_furniture() {
+        let html = r#"<body class="has-sidebar"><div class="post-header-content"><p>Kept</p></div><div class="comments">Gone</div></body>"#;
+        assert_eq!(extract(html).as_deref(), Some("Kept"));
+        assert_eq!(extract("<html><body><nav>Only navigation</nav></body></html>"), None);
+    }
+}
@@ -0,0 +1,272 @@
+//! Optional error reporting to a Sentry-compatible endpoint.
+//!
+//! When a DSN is configured, handler panics and internal (5xx) `AppError`s are sent to the
+//! reporter along with request context. Errors mark their response with a [`ReportableError`]
+//! extension, and [`report_errors_middleware`] picks it up on the way out, since that's where the
+//! route, request ID and user are known.
+//!
+//! Reporting goes through the [`ErrorReporter`] trait so other backends can be plugged in.
+
+use std::{any::Any, sync::Arc, time::Duration};
+
+use anyhow::{Context as _, Result};
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::{StatusCode, header::CONTENT_TYPE},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use reqwest::{Client, Url};
+use serde_json::json;
+use sha2::{Digest as _, Sha256};
+use tracing::{Instrument as _, error, trace_span, warn};
+use uuid::Uuid;
+
+use crate::{ApiState, db::users::User};
+
+/// Header used to correlate a request with its error report.
+const REQUEST_ID_HEADER: &str = "x-request-id";
+/// Maximum time to spend sending a single report.
+const REPORT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Marks a response as caused by an error worth reporting.
+#[derive(Clone, Debug)]
+pub struct ReportableError {
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+/// What kind of failure produced a report.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ErrorKind {
+    /// A handler panicked
+    Panic,
+    /// A handler returned an internal server error
+    Internal,
+}
+
+impl ErrorKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            ErrorKind::Panic => "panic",
+            ErrorKind::Internal => "internal",
+        }
+    }
+}
+
+/// An error along with the request it happened in.
+#[derive(Clone, Debug)]
+pub struct ErrorEvent {
+    pub kind: ErrorKind,
+    pub message: String,
+    pub method: String,
+    /// Matched route pattern, e.g. `/api/tags/more`, rather than the concrete path
+    pub route: Option<String>,
+    pub request_id: String,
+    /// Hash of the user's ID, so reports can be grouped by user without identifying them
+    pub user_id_hash: Option<String>,
+}
+
+/// Destination for error reports.
+pub trait ErrorReporter: Send + Sync {
+    /// Sends a report. Must not block; implementations should hand off any I/O.
+    fn report(&self, event: ErrorEvent);
+}
+
+/// Reports errors to a Sentry-compatible `store` endpoint derived from a DSN.
+pub struct SentryReporter {
+    client: Client,
+    store_url: Url,
+    auth_header: String,
+}
+
+impl SentryReporter {
+    /// Creates a reporter from a DSN like `https://<public_key>@<host>/<project_id>`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the DSN is not a valid URL or is missing the key or project ID.
+    pub fn from_dsn(dsn: &str, client: Client) -> Result<Self> {
+        let dsn_url = Url::parse(dsn).with_context(|| "🔥 Error reporting DSN is not a valid URL")?;
+        let public_key = dsn_url.username();
+        if public_key.is_empty() {
+            anyhow::bail!("🔥 Error reporting DSN is missing the public key");
+        }
+        let (prefix, project_id) = dsn_url.path().trim_end_matches('/').rsplit_once('/').unwrap_or_default();
+        if project_id.is_empty() {
+            anyhow::bail!("🔥 Error reporting DSN is missing the project ID");
+        }
+
+        let mut store_url = dsn_url.clone();
+        store_url.set_username("").ok();
+        store_url.set_password(None).ok();
+        store_url.set_path(&format!("{prefix}/api/{project_id}/store/"));
+
+        let auth_header = format!(
+            "Sentry sentry_version=7, sentry_client={}/{}, sentry_key={public_key}",
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION")
+        );
+
+        Ok(Self {
+            client,
+            store_url,
+            auth_header,
+        })
+    }
+}
+
+impl ErrorReporter for SentryReporter {
+    fn report(&self, event: ErrorEvent) {
+        let body = json!({
+            "event_id": Uuid::new_v4().simple().to_string(),
+            "timestamp": chrono::Utc::now().timestamp(),
+            "level": if event.kind == ErrorKind::Panic { "fatal" } else { "error" },
+            "platform": "other",
+            "release": concat!(env!("CARGO_PKG_NAME"), "@", env!("CARGO_PKG_VERSION")),
+            "message": { "formatted": event.message },
+            "transaction": event.route,
+            "tags": {
+                "kind": event.kind.as_str(),
+                "request_id": event.request_id,
+                "method": event.method,
+            },
+            "user": event.user_id_hash.map(|id| json!({ "id": id })),
+        });
+
+        let request = self
+            .client
+            .post(self.store_url.clone())
+            .header("X-Sentry-Auth", &self.auth_header)
+            .header(CONTENT_TYPE, "application/json")
+            .body(body.to_string());
+        tokio::spawn(
+            async move {
+                match request.send().await {
+                    Ok(response) if !response.status().is_success() => {
+                        warn!(status = %response.status(), "📮 Error reporting endpoint rejected a report.");
+                    }
+                    Ok(_) => {}
+                    Err(err) => warn!(%err, "📮 Could not send error report."),
+                }
+            }
+            .instrument(trace_span!("http.report")),
+        );
+    }
+}
+
+/// Sends any [`ReportableError`] in the response to the configured reporter, with request context.
+///
+/// Also echoes (or assigns) an `x-request-id` so users can quote it when something goes wrong.
+/// Applied both inside the auth middleware (so the user is known) and around the whole app; the
+/// marker is removed once reported so nothing is sent twice.
+pub async fn report_errors_middleware(State(state): ApiState, req: Request, next: Next) -> Response {
+    let request_id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map_or_else(|| Uuid::new_v4().to_string(), str::to_string);
+    let method = req.method().to_string();
+    let route = req.extensions().get::<MatchedPath>().map(|path| path.as_str().to_string());
+    let user_id_hash = req.extensions().get::<User>().map(|user| hash_user_id(user.user_id));
+
+    let mut response = next.run(req).await;
+
+    let reportable = response.extensions_mut().remove::<ReportableError>();
+    if let (Some(reportable), Some(reporter)) = (reportable, &state.reporter) {
+        reporter.report(ErrorEvent {
+            kind: reportable.kind,
+            message: reportable.message,
+            method,
+            route,
+            request_id: request_id.clone(),
+            user_id_hash,
+        });
+    }
+
+    if let Ok(value) = request_id.parse() {
+        response.headers_mut().entry(REQUEST_ID_HEADER).or_insert(value);
+    }
+
+    response
+}
+
+/// Turns a handler panic into a 500 response marked for reporting. Used with `CatchPanicLayer`.
+#[allow(clippy::needless_pass_by_value)] // signature required by `CatchPanicLayer::custom`
+pub fn panic_response(panic: Box<dyn Any + Send + 'static>) -> Response {
+    let message = panic
+        .downcast_ref::<String>()
+        .cloned()
+        .or_else(|| panic.downcast_ref::<&str>().map(ToString::to_string))
+        .unwrap_or_else(|| "Handler panicked".to_string());
+    error!(message, "💥 Handler panicked.");
+
+    let mut response = (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "An error occurred while processing your request.",
+    )
+        .into_response();
+    response.extensions_mut().insert(ReportableError {
+        kind: ErrorKind::Panic,
+        message,
+    });
+    response
+}
+
+/// Hashes a user ID so it can be sent to a third party without identifying the user.
+fn hash_user_id(user_id: Uuid) -> String {
+    let mut hash = format!("{:x}", Sha256::digest(user_id.as_bytes()));
+    hash.truncate(16);
+    hash
+}
+
+/// Builds the reporter for the configured DSN, if any.
+///
+/// Reports use their own client rather than the one for fetching saved URLs, so they aren't sent
+/// through the fetch proxy or cut short by its timeouts.
+///
+/// # Errors
+///
+/// Returns an error if the DSN is invalid or the HTTP client cannot be built.
+pub fn reporter_from_dsn(dsn: Option<&str>) -> Result<Option<Arc<dyn ErrorReporter>>> {
+    let Some(dsn) = dsn else {
+        return Ok(None);
+    };
+    let client = Client::builder()
+        .timeout(REPORT_TIMEOUT)
+        .build()
+        .with_context(|| "🔥 Could not build the error reporting HTTP client")?;
+    let reporter = SentryReporter::from_dsn(dsn, client)?;
+    println!("📮 Error reporting is enabled.");
+    Ok(Some(Arc::new(reporter)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_url_from_dsn() {
+        let reporter = SentryReporter::from_dsn("https://abc123@o0.ingest.example.com/42", Client::new()).unwrap();
+        assert_eq!(reporter.store_url.as_str(), "https://o0.ingest.example.com/api/42/store/");
+        assert!(reporter.auth_header.contains("sentry_key=abc123"));
+
+        let reporter = SentryReporter::from_dsn("https://key@errors.example.com/glitchtip/7/", Client::new()).unwrap();
+        assert_eq!(reporter.store_url.as_str(), "https://errors.example.com/glitchtip/api/7/store/");
+    }
+
+    #[test]
+    fn test_invalid_dsn() {
+        assert!(SentryReporter::from_dsn("not a url", Client::new()).is_err());
+        assert!(SentryReporter::from_dsn("https://errors.example.com/42", Client::new()).is_err());
+        assert!(SentryReporter::from_dsn("https://key@errors.example.com/", Client::new()).is_err());
+    }
+
+    #[test]
+    fn test_user_id_hash_is_stable_and_opaque() {
+        let user_id = Uuid::new_v4();
+        assert_eq!(hash_user_id(user_id), hash_user_id(user_id));
+        assert_eq!(hash_user_id(user_id).len(), 16);
+        assert!(!hash_user_id(user_id).contains(&user_id.simple().to_string()));
+    }
+}
@@ -0,0 +1,187 @@
+//! Restore points: snapshots of a user's bookmarks to roll back to.
+//!
+//! A snapshot holds everything the bookmark list is made of: every bookmark (archived and trashed
+//! ones included) with its tags, the smart collections and the tag colors. IDs are kept, so rolling
+//! back puts bookmarks back in place rather than adding copies, and guest links to a collection
+//! keep working. Snapshots are stored as LZ4-compressed JSON; see [`crate::db::restore_points`] for
+//! taking and restoring them.
+
+use std::collections::HashMap;
+
+use anyhow::{Context as _, Result, bail};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::db::{bookmarks::BookmarkId, tags::TagColors};
+
+/// Version written to new snapshots. Newer ones are refused rather than partly restored.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// How many restore points each user keeps; the oldest is dropped when another is made.
+pub const MAX_RESTORE_POINTS: i64 = 5;
+
+/// The state of a user's bookmarks at one point in time.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Snapshot {
+    pub version: u32,
+    pub bookmarks: Vec<SnapshotBookmark>,
+    /// Each bookmark's tags, sorted; bookmarks without tags are left out
+    pub tags: HashMap<BookmarkId, Vec<String>>,
+    pub collections: Vec<SnapshotCollection>,
+    #[serde(default)]
+    pub tag_colors: TagColors,
+}
+
+/// A bookmark as it was, with all of its columns.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct SnapshotBookmark {
+    pub bookmark_id: BookmarkId,
+    pub url: String,
+    pub original_url: Option<String>,
+    pub title: String,
+    pub description: Option<String>,
+    /// How it was saved, as stored, e.g. `web` or `import`
+    pub source: String,
+    pub source_detail: Option<String>,
+    /// UNIX timestamp
+    pub created_at: i64,
+    /// UNIX timestamp
+    pub updated_at: i64,
+    pub is_archived: bool,
+    pub is_private: bool,
+    pub is_unread: bool,
+    pub language: Option<String>,
+    /// UNIX timestamp
+    pub expires_at: Option<i64>,
+    pub author: Option<String>,
+    pub site_name: Option<String>,
+    /// UNIX timestamp
+    pub published_at: Option<i64>,
+    /// UNIX timestamp it was moved to the trash, if it was
+    pub deleted_at: Option<i64>,
+}
+
+/// A smart collection as it was.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct SnapshotCollection {
+    pub collection_id: Uuid,
+    pub name: String,
+    pub query: String,
+}
+
+impl Snapshot {
+    /// How many bookmarks there were, leaving out those in the trash.
+    #[must_use]
+    pub fn bookmark_count(&self) -> usize {
+        self.bookmarks.iter().filter(|bookmark| bookmark.deleted_at.is_none()).count()
+    }
+
+    /// A bookmark's tags.
+    #[must_use]
+    pub fn tags_of(&self, bookmark_id: BookmarkId) -> &[String] {
+        self.tags.get(&bookmark_id).map_or(&[], Vec::as_slice)
+    }
+
+    /// Compresses the snapshot for storage.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the snapshot can't be serialized.
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let json = serde_json::to_vec(self).context("Could not serialize snapshot")?;
+        Ok(lz4_flex::compress_prepend_size(&json))
+    }
+
+    /// Reads a snapshot written by [`Snapshot::encode`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the data is corrupt or from a newer version.
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let json = lz4_flex::decompress_size_prepended(data).context("Could not decompress snapshot")?;
+        let snapshot: Self = serde_json::from_slice(&json).context("Could not read snapshot")?;
+        if snapshot.version > SNAPSHOT_VERSION {
+            bail!(
+                "Snapshot version {} is newer than this server supports ({SNAPSHOT_VERSION})",
+                snapshot.version
+            );
+        }
+        Ok(snapshot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot() -> Snapshot {
+        let bookmark_id = BookmarkId(Uuid::new_v4());
+        Snapshot {
+            version: SNAPSHOT_VERSION,
+            bookmarks: vec![SnapshotBookmark {
+                bookmark_id,
+                url: "https://example.com/".to_string(),
+                original_url: Some("https://example.com/?utm_source=feed".to_string()),
+                title: "Example".to_string(),
+                description: None,
+                source: "web".to_string(),
+                source_detail: None,
+                created_at: 1_700_000_000,
+                updated_at: 1_700_000_100,
+                is_archived: false,
+                is_private: false,
+                is_unread: true,
+                language: Some("en".to_string()),
+                expires_at: None,
+                author: None,
+                site_name: None,
+                published_at: None,
+                deleted_at: Some(1_700_000_200),
+            }],
+            tags: HashMap::from([(bookmark_id, vec!["rust".to_string(), "web".to_string()])]),
+            collections: vec![SnapshotCollection {
+                collection_id: Uuid::new_v4(),
+                name: "Rust".to_string(),
+                query: "#rust".to_string(),
+            }],
+            tag_colors: TagColors::from([("rust".to_string(), "#b7410e".to_string())]),
+        }
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let snapshot = snapshot();
+        let data = snapshot.encode().unwrap();
+        assert_eq!(Snapshot::decode(&data).unwrap(), snapshot);
+    }
+
+    #[test]
+    fn test_snapshot_counts_and_tags() {
+        let mut snapshot = snapshot();
+        let bookmark_id = snapshot.bookmarks[0].bookmark_id;
+        assert_eq!(snapshot.bookmark_count(), 0, "the trash isn't counted");
+        assert_eq!(snapshot.tags_of(bookmark_id), ["rust", "web"]);
+
+        snapshot.bookmarks[0].deleted_at = None;
+        assert_eq!(snapshot.bookmark_count(), 1);
+        assert!(snapshot.tags_of(BookmarkId(Uuid::new_v4())).is_empty());
+    }
+
+    #[test]
+    fn test_snapshot_rejects_newer_version() {
+        let snapshot = Snapshot {
+            version: SNAPSHOT_VERSION + 1,
+            ..Snapshot::default()
+        };
+        let data = snapshot.encode().unwrap();
+        assert!(Snapshot::decode(&data).is_err());
+    }
+
+    #[test]
+    fn test_snapshot_rejects_corrupt_data() {
+        let mut data = snapshot().encode().unwrap();
+        data.truncate(data.len() / 2);
+        assert!(Snapshot::decode(&data).is_err());
+        assert!(Snapshot::decode(b"not a snapshot").is_err());
+    }
+}
@@ -0,0 +1,147 @@
+//! Resurfacing of old bookmarks.
+//!
+//! Users who opt in see a handful of bookmarks on the home page that they saved around this time
+//! one, three or six months ago, or in earlier years, to rediscover forgotten saves. "Around this
+//! time" is the week leading up to the same date, so the selection changes slowly over a week
+//! rather than being empty on most days.
+
+use chrono::{Days, Months, NaiveDate};
+
+/// Most bookmarks resurfaced at once.
+pub const RESURFACE_COUNT: usize = 5;
+/// How many months back to look, besides whole years.
+const MONTHS_AGO: [u32; 3] = [1, 3, 6];
+/// How many years back to look.
+const MAX_YEARS_AGO: u32 = 10;
+/// Days before each anniversary that also count, including the day itself.
+const WINDOW_DAYS: u64 = 7;
+
+/// A period bookmarks are resurfaced from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Window {
+    /// How long ago it was, e.g. "3 months ago"
+    pub label: String,
+    /// UNIX timestamp of the start, inclusive
+    pub start: i64,
+    /// UNIX timestamp of the end, exclusive
+    pub end: i64,
+}
+
+impl Window {
+    fn contains(&self, timestamp: i64) -> bool {
+        (self.start..self.end).contains(&timestamp)
+    }
+}
+
+/// The periods to resurface bookmarks from on `today`, most recent first.
+pub fn windows(today: NaiveDate) -> Vec<Window> {
+    let months = MONTHS_AGO.into_iter().map(|months| {
+        let label = if months == 1 {
+            "1 month ago".to_string()
+        } else {
+            format!("{months} months ago")
+        };
+        (label, today.checked_sub_months(Months::new(months)))
+    });
+    let years = (1..=MAX_YEARS_AGO).map(|years| {
+        let label = if years == 1 {
+            "1 year ago".to_string()
+        } else {
+            format!("{years} years ago")
+        };
+        (label, today.checked_sub_months(Months::new(years * 12)))
+    });
+
+    months
+        .chain(years)
+        .filter_map(|(label, day)| {
+            let start = day?.checked_sub_days(Days::new(WINDOW_DAYS - 1))?;
+            let end = day?.checked_add_days(Days::new(1))?;
+            Some(Window {
+                label,
+                start: start.and_hms_opt(0, 0, 0)?.and_utc().timestamp(),
+                end: end.and_hms_opt(0, 0, 0)?.and_utc().timestamp(),
+            })
+        })
+        .collect()
+}
+
+/// Picks up to `count` bookmarks, taking turns between the windows (most recent first) so one busy
+/// week doesn't crowd out the rest. `candidates` are `(created_at, bookmark)` pairs in the order
+/// they should be picked within a window.
+pub fn pick<T>(windows: &[Window], candidates: Vec<(i64, T)>, count: usize) -> Vec<(String, T)> {
+    let mut buckets: Vec<Vec<T>> = windows.iter().map(|_| Vec::new()).collect();
+    for (created_at, candidate) in candidates {
+        if let Some(index) = windows.iter().position(|window| window.contains(created_at)) {
+            buckets[index].push(candidate);
+        }
+    }
+    let mut buckets: Vec<_> = buckets.into_iter().map(Vec::into_iter).collect();
+
+    let mut picked = Vec::new();
+    while picked.len() < count {
+        let before = picked.len();
+        for (window, bucket) in windows.iter().zip(&mut buckets) {
+            if picked.len() == count {
+                break;
+            }
+            if let Some(candidate) = bucket.next() {
+                picked.push((window.label.clone(), candidate));
+            }
+        }
+        if picked.len() == before {
+            break;
+        }
+    }
+    picked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn timestamp(year: i32, month: u32, day: u32) -> i64 {
+        date(year, month, day).and_hms_opt(12, 0, 0).unwrap().and_utc().timestamp()
+    }
+
+    #[test]
+    fn test_windows() {
+        let windows = windows(date(2025, 3, 31));
+        assert_eq!(windows.len(), 13);
+        assert_eq!(windows[0].label, "1 month ago");
+        // 2025-02-28 is the last day of the month before; the week runs from the 22nd
+        assert!(windows[0].contains(timestamp(2025, 2, 22)));
+        assert!(windows[0].contains(timestamp(2025, 2, 28)));
+        assert!(!windows[0].contains(timestamp(2025, 2, 21)));
+        assert!(!windows[0].contains(timestamp(2025, 3, 1)));
+        assert_eq!(windows[3].label, "1 year ago");
+        assert!(windows[3].contains(timestamp(2024, 3, 31)));
+        assert_eq!(windows[12].label, "10 years ago");
+    }
+
+    #[test]
+    fn test_pick_takes_turns_between_windows() {
+        let windows = windows(date(2025, 6, 15));
+        let candidates = vec![
+            (timestamp(2025, 5, 15), "a"),
+            (timestamp(2025, 5, 14), "b"),
+            (timestamp(2025, 5, 13), "c"),
+            (timestamp(2024, 6, 15), "d"),
+            (timestamp(2020, 1, 1), "not resurfaced"),
+        ];
+        let picked = pick(&windows, candidates, 3);
+        assert_eq!(
+            picked,
+            [
+                ("1 month ago".to_string(), "a"),
+                ("1 year ago".to_string(), "d"),
+                ("1 month ago".to_string(), "b"),
+            ]
+        );
+        assert!(pick::<&str>(&windows, Vec::new(), 3).is_empty());
+    }
+}
@@ -0,0 +1,40 @@
+//! The admin pages under `/admin`, each handler checking that the user is the admin.
+
+use std::sync::Arc;
+
+use axum::{
+    Router,
+    routing::{delete, get, post},
+};
+
+use super::RouteContext;
+use crate::{
+    AppState,
+    handler::{
+        admin::{
+            admin_handler, admin_search_handler, admin_search_page_handler, metrics_handler, run_maintenance_handler, unlock_handler,
+            update_announcement_handler, update_feature_override_handler, update_starter_set_handler,
+        },
+        blocklist::{create_instance_blocked_domain_handler, delete_instance_blocked_domain_handler},
+    },
+};
+
+pub(super) fn router(context: &RouteContext) -> Router<Arc<AppState>> {
+    let pages = Router::new()
+        .route("/admin", get(admin_handler))
+        .route("/admin/search", get(admin_search_page_handler))
+        .route("/admin/metrics", get(metrics_handler));
+
+    let search = Router::new().route("/admin/search", post(admin_search_handler));
+
+    let mutation = Router::new()
+        .route("/admin/maintenance", post(run_maintenance_handler))
+        .route("/admin/lockouts/{failure_id}", delete(unlock_handler))
+        .route("/admin/announcement", post(update_announcement_handler))
+        .route("/admin/features", post(update_feature_override_handler))
+        .route("/admin/starter-set", post(update_starter_set_handler))
+        .route("/admin/blocked-domains", post(create_instance_blocked_domain_handler))
+        .route("/admin/blocked-domains/{block_id}", delete(delete_instance_blocked_domain_handler));
+
+    context.authenticated(pages.merge(context.search_routes(search)).merge(context.mutation_routes(mutation)))
+}
@@ -0,0 +1,74 @@
+//! The JSON API under `/api/v1`, see [`crate::handler::api`].
+
+use std::{sync::Arc, time::Duration};
+
+use axum::{
+    Router,
+    http::{
+        HeaderValue, Method,
+        header::{AUTHORIZATION, CONTENT_TYPE},
+    },
+    routing::{get, post, put},
+};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+use super::RouteContext;
+use crate::{
+    AppState,
+    handler::api::{
+        author_bookmarks_handler, batch_create_handler, check_bookmark_handler, create_bookmark_handler, delete_bookmark_handler,
+        get_bookmark_handler, list_authors_handler, list_bookmark_ids_handler, list_bookmarks_handler, list_tags_handler,
+        quick_save_handler, update_bookmark_handler,
+    },
+};
+
+/// How long browsers may cache the answer to a CORS preflight request.
+const CORS_MAX_AGE: Duration = Duration::from_secs(3600);
+
+pub(super) fn router(context: &RouteContext, cors_origins: &[String]) -> Router<Arc<AppState>> {
+    let search = Router::new()
+        .route("/api/v1/bookmarks", get(list_bookmarks_handler))
+        .route("/api/v1/bookmarks/ids", get(list_bookmark_ids_handler))
+        .route("/api/v1/bookmarks/check", get(check_bookmark_handler))
+        .route("/api/v1/bookmarks/{bookmark_id}", get(get_bookmark_handler))
+        .route("/api/v1/tags", get(list_tags_handler))
+        .route("/api/v1/authors", get(list_authors_handler))
+        .route("/api/v1/authors/{author}", get(author_bookmarks_handler));
+
+    let mutation = Router::new()
+        .route("/api/v1/bookmarks", post(create_bookmark_handler))
+        .route(
+            "/api/v1/bookmarks/{bookmark_id}",
+            put(update_bookmark_handler).delete(delete_bookmark_handler),
+        )
+        .route("/api/v1/bookmarks/batch", post(batch_create_handler))
+        .route("/api/v1/bookmarks/quick", post(quick_save_handler));
+
+    let routes = context.authenticated(context.search_routes(search).merge(context.mutation_routes(mutation)));
+    if cors_origins.is_empty() {
+        routes
+    } else {
+        // Outside the login, so preflight requests are answered without a token
+        routes.layer(cors_layer(cors_origins.to_vec()))
+    }
+}
+
+/// Lets browser extensions and other pages from `origins` call the API with a token. Cookies aren't
+/// allowed, so they can't use the browser session.
+fn cors_layer(origins: Vec<String>) -> CorsLayer {
+    CorsLayer::new()
+        .allow_origin(AllowOrigin::predicate(move |origin: &HeaderValue, _| {
+            origin.to_str().is_ok_and(|origin| is_allowed_origin(&origins, origin))
+        }))
+        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
+        .allow_headers([AUTHORIZATION, CONTENT_TYPE])
+        .max_age(CORS_MAX_AGE)
+}
+
+/// Whether `origin` is one of `allowed`, where `scheme://*` allows every origin with that scheme.
+fn is_allowed_origin(allowed: &[String], origin: &str) -> bool {
+    allowed.iter().any(|allowed| match allowed.strip_suffix('*') {
+        Some(scheme) => origin.starts_with(scheme),
+        None => allowed == origin,
+    })
+}
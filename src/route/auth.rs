@@ -0,0 +1,85 @@
+//! Signing in and out.
+
+use std::{sync::Arc, time::Duration};
+
+use axum::{
+    Router,
+    handler::Handler,
+    routing::{get, post},
+};
+use tokio::time;
+use tower_governor::{GovernorLayer, governor::GovernorConfigBuilder, key_extractor::SmartIpKeyExtractor};
+use tracing::{debug, error};
+
+use super::{RATE_LIMITED, RouteContext};
+use crate::{
+    AppState,
+    handler::auth_handler::{
+        forgot_password_handler, forgot_password_page_handler, login_page_handler, login_user_handler, logout_handler, register_handler,
+        register_page_handler, reset_password_handler, reset_password_page_handler, session_check_handler, two_factor_handler,
+    },
+};
+
+/// The login, two-factor, sign-up, password reset and logout pages, open to everyone, and the
+/// session check for signed in pages.
+///
+/// Login, two-factor code, sign-up and password reset attempts share a strict per-IP limit of their
+/// own instead of the general one.
+pub(super) fn router(context: &RouteContext) -> Router<Arc<AppState>> {
+    let login_route = get(login_page_handler);
+    let register_route = get(register_page_handler);
+    let forgot_route = get(forgot_password_page_handler);
+    let reset_route = get(reset_password_page_handler);
+    let (login_route, two_factor_route, register_route, forgot_route, reset_route) = if RATE_LIMITED {
+        let login_conf = Arc::new(
+            GovernorConfigBuilder::default()
+                .per_second(1)
+                .burst_size(3)
+                .key_extractor(SmartIpKeyExtractor)
+                .finish()
+                .unwrap(),
+        );
+        let login_limiter = login_conf.limiter().clone();
+
+        tokio::spawn(async move {
+            let mut interval = time::interval(Duration::from_secs(60));
+            loop {
+                interval.tick().await;
+                let login_len = login_limiter.len();
+                if login_len > 1 {
+                    debug!(login_len, "Login rate limiter cleanup");
+                }
+                login_limiter.retain_recent();
+            }
+        });
+
+        let login_layer = GovernorLayer::new(login_conf).error_handler(|e| {
+            error!(?e, "Login rate limited");
+            e.into()
+        });
+        (
+            login_route.post(login_user_handler.layer(login_layer.clone())),
+            post(two_factor_handler.layer(login_layer.clone())),
+            register_route.post(register_handler.layer(login_layer.clone())),
+            forgot_route.post(forgot_password_handler.layer(login_layer.clone())),
+            reset_route.post(reset_password_handler.layer(login_layer)),
+        )
+    } else {
+        (
+            login_route.post(login_user_handler),
+            post(two_factor_handler),
+            register_route.post(register_handler),
+            forgot_route.post(forgot_password_handler),
+            reset_route.post(reset_password_handler),
+        )
+    };
+
+    context
+        .authenticated(Router::new().route("/api/session-check", get(session_check_handler)))
+        .route("/login", login_route)
+        .route("/login/two-factor", two_factor_route)
+        .route("/register", register_route)
+        .route("/forgot-password", forgot_route)
+        .route("/reset-password/{token}", reset_route)
+        .route("/logout", post(logout_handler))
+}
@@ -0,0 +1,109 @@
+//! The bookmark list and everything around it: searching, tags, collections, the trash and
+//! read-only guest links, public tags, shared bookmarks and the bookmarklet.
+
+use std::sync::Arc;
+
+use axum::{
+    Router,
+    middleware::{from_fn, from_fn_with_state},
+    routing::{delete, get, post, put},
+};
+
+use super::RouteContext;
+use crate::{
+    AppState,
+    handler::{
+        admin::update_notice_handler,
+        bookmarks::{
+            bookmark_archive_handler, bookmark_content_handler, bookmark_create_handler, bookmark_delete_handler, bookmark_edit_handler,
+            bookmark_handler, bookmark_new_handler, bookmark_read_handler, bookmark_thumbnail_handler, bookmark_unarchive_handler,
+            bookmark_unread_handler, bookmark_update_handler, retry_fetch_handler, scrape_site_handler, search_explain_handler,
+        },
+        collections::{
+            create_collection_handler, delete_collection_handler, reorder_collection_handler, save_search_handler,
+            update_collection_handler,
+        },
+        guest::guest_handler,
+        home_handler,
+        middlewares::{guest_middleware, public_page_middleware, share_middleware},
+        public_tags::public_tag_handler,
+        quick_add::{quick_add_handler, quick_add_save_handler},
+        report::report_handler,
+        resurface::resurface_handler,
+        shares::{share_bookmark_handler, shared_bookmark_handler, unshare_bookmark_handler},
+        snapshots::{bookmark_snapshot_handler, save_snapshot_handler},
+        tags::{tag_autocomplete_handler, tag_list_handler, tag_list_page_handler, tag_suggest_handler},
+        translations::translate_bookmark_handler,
+        trash::{empty_trash_handler, purge_bookmark_handler, restore_bookmark_handler, trash_handler},
+    },
+};
+
+pub(super) fn router(context: &RouteContext) -> Router<Arc<AppState>> {
+    let pages = Router::new()
+        .route("/", get(home_handler))
+        .route("/bookmarks/new", get(bookmark_new_handler))
+        .route("/bookmarks/{bookmark_id}/snapshot", get(bookmark_snapshot_handler))
+        .route("/report", get(report_handler))
+        .route("/trash", get(trash_handler))
+        // Loaded by every page, so it stays when the admin pages are turned off
+        .route("/admin/update-notice", get(update_notice_handler));
+
+    let search = Router::new()
+        .route("/api/bookmarks", get(bookmark_content_handler))
+        .route("/api/bookmarks/{bookmark_id}", get(bookmark_handler))
+        .route("/api/bookmarks/{bookmark_id}/edit", get(bookmark_edit_handler))
+        .route("/api/bookmarks/{bookmark_id}/thumbnail", get(bookmark_thumbnail_handler))
+        .route("/api/search/explain", get(search_explain_handler))
+        .route("/api/tags", get(tag_list_handler))
+        .route("/api/tags/more", get(tag_list_page_handler))
+        .route("/api/tags/autocomplete", get(tag_autocomplete_handler))
+        .route("/api/tags/suggest", get(tag_suggest_handler))
+        .route("/api/resurface", get(resurface_handler));
+
+    let mutation = Router::new()
+        .route("/bookmarks", post(bookmark_create_handler))
+        .route("/api/collections", post(create_collection_handler))
+        .route("/api/collections/saved-search", post(save_search_handler))
+        .route(
+            "/api/collections/{collection_id}",
+            put(update_collection_handler).delete(delete_collection_handler),
+        )
+        .route("/api/collections/{collection_id}/order", put(reorder_collection_handler))
+        .route("/api/fetch-title", post(scrape_site_handler))
+        .route(
+            "/api/bookmarks/{bookmark_id}",
+            put(bookmark_update_handler).delete(bookmark_delete_handler),
+        )
+        .route("/api/bookmarks/{bookmark_id}/archive", post(bookmark_archive_handler))
+        .route("/api/bookmarks/{bookmark_id}/unarchive", post(bookmark_unarchive_handler))
+        .route("/api/bookmarks/{bookmark_id}/read", post(bookmark_read_handler))
+        .route("/api/bookmarks/{bookmark_id}/unread", post(bookmark_unread_handler))
+        .route("/api/bookmarks/{bookmark_id}/translate", post(translate_bookmark_handler))
+        .route("/api/bookmarks/{bookmark_id}/snapshot", post(save_snapshot_handler))
+        .route("/api/bookmarks/{bookmark_id}/fetch", post(retry_fetch_handler))
+        .route(
+            "/api/bookmarks/{bookmark_id}/share",
+            post(share_bookmark_handler).delete(unshare_bookmark_handler),
+        )
+        .route("/api/trash", delete(empty_trash_handler))
+        .route("/api/trash/{bookmark_id}", delete(purge_bookmark_handler))
+        .route("/api/trash/{bookmark_id}/restore", post(restore_bookmark_handler));
+
+    // Guests and visitors of public tags and shared bookmarks have no session, but share the same per-IP budget
+    let guest = Router::new()
+        .route("/guest/{token}", get(guest_handler))
+        .route_layer(from_fn_with_state(context.app_state.clone(), guest_middleware));
+    let shared = Router::new()
+        .route("/s/{token}", get(shared_bookmark_handler))
+        .route_layer(from_fn_with_state(context.app_state.clone(), share_middleware))
+        .route_layer(from_fn(public_page_middleware));
+    let public = Router::new()
+        .route("/u/{username}/tags/{tag}", get(public_tag_handler))
+        .route_layer(from_fn(public_page_middleware));
+    // Checks the session or API token itself, as the bookmarklet that saves right away has no session
+    let quick_add = Router::new().route("/add", get(quick_add_handler).post(quick_add_save_handler));
+
+    context
+        .authenticated(pages.merge(context.search_routes(search)).merge(context.mutation_routes(mutation)))
+        .merge(context.limit_per_ip(guest.merge(shared).merge(public).merge(quick_add)))
+}
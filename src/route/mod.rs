@@ -0,0 +1,238 @@
+//! HTTP routing configuration and server setup.
+//!
+//! Routes are grouped by area, each with its own router and middleware stack: [`auth`] for signing
+//! in and out, [`bookmarks`] for the bookmark list and everything around it, [`settings`], the JSON
+//! API in [`api_v1`] and the [`admin`] pages. The API and admin areas can be left out entirely,
+//! see [`AreaConfig`]. Rate limits are shared between areas, see [`RouteContext`].
+
+mod admin;
+mod api_v1;
+mod auth;
+mod bookmarks;
+mod settings;
+
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+
+use anyhow::Result;
+use axum::{Router, middleware::from_fn_with_state, response::IntoResponse, routing::get};
+use reqwest::StatusCode;
+use tokio::time;
+use tower_governor::{GovernorLayer, governor::GovernorConfigBuilder, key_extractor::SmartIpKeyExtractor};
+use tower_http::{catch_panic::CatchPanicLayer, compression::CompressionLayer};
+use tower_livereload::LiveReloadLayer;
+use tracing::{debug, error};
+
+use crate::{
+    AppState,
+    assets::assets_handler,
+    config::{AreaConfig, RateBudget, RateLimitConfig},
+    handler::{
+        announcement::announcement_handler,
+        handle_404,
+        middlewares::{UserKeyExtractor, auth_user_middleware, json_errors_middleware},
+    },
+    metrics::count_requests_middleware,
+    report::{panic_response, report_errors_middleware},
+    trace::{create_filtered_trace_layer, init_tracing},
+};
+
+/// Whether requests are rate limited; not in load testing builds, where the limits would be what's
+/// measured.
+const RATE_LIMITED: bool = !cfg!(feature = "loadtest");
+
+/// Starts the HTTP server with the configured routes and middleware.
+///
+/// This function:
+/// 1. Initializes the tracing subscriber for logging, exporting traces if an OTLP endpoint is given
+/// 2. Creates the router with the enabled areas and middleware, using the given per-user rate limits
+///    and letting the given origins call the JSON API
+/// 3. Binds to port 8888 and starts serving requests
+///
+/// # Errors
+///
+/// Returns an error if the server fails to bind to the port or encounters
+/// a fatal error during operation.
+pub(crate) async fn serve(
+    app_state: Arc<AppState>,
+    rate_limit: &RateLimitConfig,
+    areas: AreaConfig,
+    api_cors_origins: &[String],
+    otlp_endpoint: Option<&str>,
+) -> Result<()> {
+    let _tracing = init_tracing(otlp_endpoint)?;
+
+    let app = create_router(app_state, rate_limit, areas, api_cors_origins);
+
+    let port = 8888;
+
+    let address = tokio::net::TcpListener::bind(format!("0.0.0.0:{port}"))
+        .await
+        .expect("Could not bind to TCP listener port");
+
+    axum::serve(address, app.into_make_service_with_connect_info::<SocketAddr>()).await?;
+
+    Ok(())
+}
+
+fn create_router(app_state: Arc<AppState>, rate_limit: &RateLimitConfig, areas: AreaConfig, api_cors_origins: &[String]) -> Router {
+    let context = RouteContext::new(app_state.clone(), rate_limit);
+
+    let mut route = auth::router(&context)
+        .merge(bookmarks::router(&context))
+        .merge(settings::router(&context));
+    if areas.api {
+        route = route.merge(api_v1::router(&context, api_cors_origins));
+    }
+    if areas.admin {
+        route = route.merge(admin::router(&context));
+    }
+    #[cfg(feature = "loadtest")]
+    let route = route.merge(crate::loadtest::routes());
+
+    let route = route.route("/announcement", get(announcement_handler)).fallback(handle_404);
+
+    if cfg!(debug_assertions) {
+        // must be before compression layer to successfully inject the script needed
+        route.layer(LiveReloadLayer::new().request_predicate(|req: &axum::http::Request<_>| {
+            // Only inject livereload for full page requests, not HTMX partials
+            !req.headers().contains_key("HX-Request")
+        }))
+    } else {
+        route
+    }
+    .layer(CompressionLayer::new())
+    // rust-embed-for-web automatically handles compression
+    .route("/assets/{*path}", get(assets_handler))
+    .route("/health", get(health_check))
+    .with_state(app_state.clone())
+    // Covers the routes outside the auth layer; errors already reported above aren't sent twice
+    .layer(CatchPanicLayer::custom(panic_response))
+    .layer(from_fn_with_state(app_state.clone(), report_errors_middleware))
+    .layer(from_fn_with_state(app_state, count_requests_middleware))
+    .layer(create_filtered_trace_layer())
+}
+
+/// Rate limits some routes; see [`RouteContext`].
+type RouteLimit = Arc<dyn Fn(Router<Arc<AppState>>) -> Router<Arc<AppState>> + Send + Sync>;
+
+/// What every area's router is built with: the app state for its middleware, and the rate limits.
+///
+/// The limits are shared by all areas, so a client spreading its requests over several of them
+/// still has one per-IP budget, and each user one budget for searching and one for changing data.
+#[derive(Clone)]
+pub(crate) struct RouteContext {
+    app_state: Arc<AppState>,
+    per_ip: RouteLimit,
+    search: RouteLimit,
+    mutation: RouteLimit,
+}
+
+impl RouteContext {
+    fn new(app_state: Arc<AppState>, rate_limit: &RateLimitConfig) -> Self {
+        Self {
+            app_state,
+            per_ip: per_ip_limit(),
+            search: user_limit(rate_limit.search, "search"),
+            mutation: user_limit(rate_limit.mutation, "mutation"),
+        }
+    }
+
+    /// Puts routes behind the login. Error reports (and the per-user rate limits) inside know who
+    /// the user is, and all of it is covered by the per-IP limit.
+    fn authenticated(&self, routes: Router<Arc<AppState>>) -> Router<Arc<AppState>> {
+        self.limit_per_ip(
+            routes
+                .layer(CatchPanicLayer::custom(panic_response))
+                .layer(from_fn_with_state(self.app_state.clone(), report_errors_middleware))
+                .layer(from_fn_with_state(self.app_state.clone(), auth_user_middleware))
+                .layer(axum::middleware::from_fn(json_errors_middleware)),
+        )
+    }
+
+    /// Limits each client's requests to `routes` to the general per-IP budget.
+    fn limit_per_ip(&self, routes: Router<Arc<AppState>>) -> Router<Arc<AppState>> {
+        (self.per_ip)(routes)
+    }
+
+    /// Listing and searching bookmarks and tags, with a per-user budget of their own. The routes
+    /// must be put behind the login with [`RouteContext::authenticated`].
+    fn search_routes(&self, routes: Router<Arc<AppState>>) -> Router<Arc<AppState>> {
+        (self.search)(routes)
+    }
+
+    /// Everything that creates, changes or deletes data, with a per-user budget of their own. The
+    /// routes must be put behind the login with [`RouteContext::authenticated`].
+    fn mutation_routes(&self, routes: Router<Arc<AppState>>) -> Router<Arc<AppState>> {
+        (self.mutation)(routes)
+    }
+}
+
+/// The general limit on each client's requests, by IP address.
+fn per_ip_limit() -> RouteLimit {
+    if !RATE_LIMITED {
+        return Arc::new(|routes| routes);
+    }
+    let conf = Arc::new(
+        GovernorConfigBuilder::default()
+            .per_second(5)
+            .burst_size(100)
+            .key_extractor(SmartIpKeyExtractor)
+            .finish()
+            .unwrap(),
+    );
+    let limiter = conf.limiter().clone();
+
+    tokio::spawn(async move {
+        let mut interval = time::interval(Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            let len = limiter.len();
+            if len > 1 {
+                debug!(len, "General rate limiter cleanup");
+            }
+            limiter.retain_recent();
+        }
+    });
+
+    Arc::new(move |routes| {
+        routes.layer(GovernorLayer::new(conf.clone()).error_handler(|e| {
+            error!(?e, "Rate limited");
+            e.into()
+        }))
+    })
+}
+
+/// Limits each user's requests to the given budget. The routes must be inside the auth layer.
+fn user_limit(budget: RateBudget, name: &'static str) -> RouteLimit {
+    if !RATE_LIMITED {
+        return Arc::new(|routes| routes);
+    }
+    let conf = Arc::new(
+        GovernorConfigBuilder::default()
+            .period(budget.period)
+            .burst_size(budget.burst)
+            .key_extractor(UserKeyExtractor)
+            .finish()
+            .unwrap(),
+    );
+    let limiter = conf.limiter().clone();
+
+    tokio::spawn(async move {
+        let mut interval = time::interval(Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            limiter.retain_recent();
+        }
+    });
+
+    Arc::new(move |routes| {
+        routes.route_layer(GovernorLayer::new(conf.clone()).error_handler(move |e| {
+            error!(?e, limit = name, "User rate limited");
+            e.into()
+        }))
+    })
+}
+
+pub async fn health_check() -> impl IntoResponse {
+    StatusCode::OK
+}
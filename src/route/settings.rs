@@ -0,0 +1,102 @@
+//! The settings page and what's managed from it: imports and exports, URL rules, tag colors, guest
+//! links, public tags, shared bookmarks, collection subscriptions, blocked domains, API tokens,
+//! two-factor authentication and restore points, and the exports subscriptions serve.
+
+use std::sync::Arc;
+
+use axum::{
+    Router,
+    extract::DefaultBodyLimit,
+    middleware::from_fn_with_state,
+    routing::{delete, get, post},
+};
+
+use super::RouteContext;
+use crate::{
+    AppState,
+    handler::{
+        blocklist::{create_blocked_domain_handler, delete_blocked_domain_handler},
+        export::export_bookmarks_handler,
+        guest::{create_guest_link_handler, delete_guest_link_handler},
+        import::{MAX_IMPORT_SIZE, import_browser_handler, import_hypothesis_handler, preview_browser_import_handler},
+        middlewares::subscription_middleware,
+        public_tags::{publish_tag_handler, unpublish_tag_handler},
+        restore_points::{create_restore_point_handler, delete_restore_point_handler, restore_restore_point_handler},
+        settings::{
+            create_api_token_handler, delete_api_token_handler, export_settings_handler, export_vault_handler, import_settings_handler,
+            settings_handler, update_digest_setting_handler, update_email_handler, update_fetch_setting_handler,
+            update_language_setting_handler, update_list_settings_handler, update_resurface_setting_handler,
+            update_search_settings_handler, update_theme_handler,
+        },
+        shares::delete_share_handler,
+        subscriptions::{collection_subscription_handler, create_subscription_handler, delete_subscription_handler},
+        tags::{delete_tag_color_handler, set_tag_color_handler},
+        two_factor::{confirm_two_factor_handler, disable_two_factor_handler, regenerate_recovery_codes_handler, start_two_factor_handler},
+        url_rules::{create_url_rule_handler, delete_url_rule_handler},
+    },
+};
+
+pub(super) fn router(context: &RouteContext) -> Router<Arc<AppState>> {
+    let pages = Router::new()
+        .route("/settings", get(settings_handler))
+        .route("/settings/export", get(export_bookmarks_handler))
+        .route("/api/settings/export", get(export_settings_handler))
+        .route("/api/export/markdown", get(export_vault_handler));
+
+    let mutation = Router::new()
+        .route("/api/settings/theme", post(update_theme_handler))
+        .route("/api/settings/fetch", post(update_fetch_setting_handler))
+        .route("/api/settings/list", post(update_list_settings_handler))
+        .route("/api/settings/language", post(update_language_setting_handler))
+        .route("/api/settings/email", post(update_email_handler))
+        .route("/api/settings/digest", post(update_digest_setting_handler))
+        .route("/api/settings/two-factor", post(start_two_factor_handler))
+        .route("/api/settings/two-factor/confirm", post(confirm_two_factor_handler))
+        .route("/api/settings/two-factor/disable", post(disable_two_factor_handler))
+        .route("/api/settings/two-factor/recovery-codes", post(regenerate_recovery_codes_handler))
+        .route("/api/settings/resurface", post(update_resurface_setting_handler))
+        .route("/api/settings/search", post(update_search_settings_handler))
+        .route("/api/settings/import", post(import_settings_handler))
+        .route(
+            "/api/import/browser",
+            post(import_browser_handler).layer(DefaultBodyLimit::max(MAX_IMPORT_SIZE)),
+        )
+        .route(
+            "/api/import/browser/preview",
+            post(preview_browser_import_handler).layer(DefaultBodyLimit::max(MAX_IMPORT_SIZE)),
+        )
+        .route("/api/import/hypothesis", post(import_hypothesis_handler))
+        .route("/api/settings/url-rules", post(create_url_rule_handler))
+        .route("/api/settings/url-rules/{rule_id}", delete(delete_url_rule_handler))
+        .route("/api/settings/tag-colors", post(set_tag_color_handler))
+        .route("/api/settings/tag-colors/{tag}", delete(delete_tag_color_handler))
+        .route("/api/settings/guest-links", post(create_guest_link_handler))
+        .route("/api/settings/guest-links/{link_id}", delete(delete_guest_link_handler))
+        .route("/api/settings/public-tags", post(publish_tag_handler))
+        .route("/api/settings/public-tags/{tag}", delete(unpublish_tag_handler))
+        .route("/api/settings/shared-bookmarks/{bookmark_id}", delete(delete_share_handler))
+        .route("/api/settings/subscriptions", post(create_subscription_handler))
+        .route("/api/settings/subscriptions/{subscription_id}", delete(delete_subscription_handler))
+        .route("/api/settings/blocked-domains", post(create_blocked_domain_handler))
+        .route("/api/settings/blocked-domains/{block_id}", delete(delete_blocked_domain_handler))
+        .route("/api/settings/api-tokens", post(create_api_token_handler))
+        .route("/api/settings/api-tokens/{token_id}", delete(delete_api_token_handler))
+        .route("/api/settings/restore-points", post(create_restore_point_handler))
+        .route(
+            "/api/settings/restore-points/{restore_point_id}",
+            delete(delete_restore_point_handler),
+        )
+        .route(
+            "/api/settings/restore-points/{restore_point_id}/restore",
+            post(restore_restore_point_handler),
+        );
+
+    // Pulled by other tools without a session, within the same per-IP budget as guests
+    let subscriptions = Router::new()
+        .route("/subscriptions/{token}", get(collection_subscription_handler))
+        .route_layer(from_fn_with_state(context.app_state.clone(), subscription_middleware));
+
+    context
+        .authenticated(pages.merge(context.mutation_routes(mutation)))
+        .merge(context.limit_per_ip(subscriptions))
+}
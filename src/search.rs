@@ -4,16 +4,76 @@
 //! - OR logic by default (space-separated terms)
 //! - AND override with explicit "AND"/"and"
 //! - Quoted strings for exact phrases
-//! - Future: Tag syntax (#tag) and fuzzy matching
+//! - Tag syntax (#tag)
+//! - Site filters (`site:github.com`)
+//! - Source filters (`source:import`)
+//! - Language filters (`lang:de`)
+//! - Author filters (`author:knuth`, `author:"Donald Knuth"`)
+//! - Site name filters (`sitename:guardian`, `sitename:"The Guardian"`)
+//! - Publication date filters (`published:2024-03`, `published:<2020`)
+//! - Saved date filters (`after:2023-06`, `before:2024-01-01`, `since:30d`)
+//! - State filters (`is:untagged`, `is:unread`, `is:broken`, `is:private`)
+//! - Read-later filters (`unread:yes`, `unread:no`)
+//! - Filters for what a bookmark has (`has:notes`, `has:snapshot`)
+//! - Field terms (`title:axum`, `url:github.com`, `desc:"error handling"`, `content:tokio`)
+//!
+//! Each user can change how their searches are read when the query doesn't say, see
+//! [`SearchDefaults`]: AND instead of OR, searching the archive too, fuzzy matching of words, and
+//! matching in the text of saved pages.
 
 use std::fmt;
 
+use chrono::{DateTime, Months, NaiveDate, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+
 /// Represents a parsed search query with different term types and logic operations.
 #[derive(Clone, Debug, PartialEq)]
 pub struct SearchQuery {
     pub general_terms: Vec<SearchTerm>,
+    /// Terms from `title:`, `url:`, `desc:` and `content:`, each only matching in that field; they're combined
+    /// with the general terms by the query's logic
+    pub field_terms: Vec<FieldTerm>,
     pub tag_filters: Vec<String>,
+    /// Domains from `site:` terms; a bookmark matches if its host is any of them (or a `www.` variant)
+    pub site_filters: Vec<String>,
+    /// Sources from `source:` terms; a bookmark matches if it was saved through any of them
+    pub source_filters: Vec<String>,
+    /// Languages from `lang:` terms; a bookmark matches if its content is in any of them
+    pub language_filters: Vec<String>,
+    /// Names from `author:` terms; a bookmark matches if its author contains any of them
+    pub author_filters: Vec<String>,
+    /// Names from `sitename:` terms; a bookmark matches if its site name contains any of them
+    pub site_name_filters: Vec<String>,
+    /// Periods from `published:` terms; a bookmark must have been published in all of them
+    pub published_filters: Vec<DateRange>,
+    /// Periods from `after:`, `before:` and `since:` terms; a bookmark must have been saved in all of them
+    pub created_filters: Vec<DateRange>,
+    /// States from `is:` terms; a bookmark must be in all of them
+    pub state_filters: Vec<StateFilter>,
+    /// What a bookmark must have, from `has:` terms
+    pub has_filters: Vec<HasFilter>,
+    pub logic: SearchLogic,
+    /// Search archived bookmarks along with the others, unless only the archive is searched with
+    /// `is:archived`
+    pub include_archived: bool,
+    /// Match words whose letters appear in order, not necessarily next to each other
+    pub fuzzy: bool,
+    /// Also match terms in the text of saved pages
+    pub page_text: bool,
+}
+
+/// How a user's searches are read when the query doesn't say otherwise.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct SearchDefaults {
+    /// Logic between terms in queries without AND or OR
     pub logic: SearchLogic,
+    /// Search the archive along with the other bookmarks
+    pub include_archived: bool,
+    /// Match words like tag completion does: `rst` finds `rust`
+    pub fuzzy: bool,
+    /// Match terms in the text of saved pages too, not just their titles, descriptions and URLs
+    pub page_text: bool,
 }
 
 /// Individual search terms that can be words or phrases.
@@ -23,13 +83,374 @@ pub enum SearchTerm {
     Phrase(String),
 }
 
+/// A bookmark field that a term can be restricted to.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchField {
+    Title,
+    Url,
+    Description,
+    /// The text of the saved page, see [`crate::page_text`]
+    Content,
+}
+
+impl SearchField {
+    /// The qualifier written before a term, e.g. `title:`.
+    #[must_use]
+    pub fn qualifier(self) -> &'static str {
+        match self {
+            SearchField::Title => "title:",
+            SearchField::Url => "url:",
+            SearchField::Description => "desc:",
+            SearchField::Content => "content:",
+        }
+    }
+
+    /// Splits a word like `Title:axum` into its field and the rest, ignoring the qualifier's case.
+    fn split(word: &str) -> Option<(Self, &str)> {
+        [SearchField::Title, SearchField::Url, SearchField::Description, SearchField::Content]
+            .into_iter()
+            .find_map(|field| {
+                let qualifier = field.qualifier();
+                word.get(..qualifier.len())
+                    .filter(|prefix| prefix.eq_ignore_ascii_case(qualifier))
+                    .map(|_| (field, &word[qualifier.len()..]))
+            })
+    }
+}
+
+/// A word or phrase that only matches in one field.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldTerm {
+    pub field: SearchField,
+    pub term: SearchTerm,
+}
+
+/// A bookmark state that can be searched for with `is:`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StateFilter {
+    /// Has no tags
+    Untagged,
+    /// Has no description
+    Undescribed,
+    /// Another bookmark has the same URL, ignoring the scheme and a trailing slash
+    Duplicate,
+    /// Temporary, and due to be deleted within the next week
+    Expiring,
+    /// Moved out of the main list to the archive
+    Archived,
+    /// Saved to read later and not marked as read yet
+    Unread,
+    /// Not waiting to be read
+    Read,
+    /// The last link check found the page gone or the site unreachable
+    Broken,
+    /// Can be shown to people other than its owner, see [`crate::db::bookmarks::Visibility`]
+    Public,
+    /// Only ever shown to its owner
+    Private,
+}
+
+impl StateFilter {
+    /// The name used after `is:`.
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            StateFilter::Untagged => "untagged",
+            StateFilter::Undescribed => "undescribed",
+            StateFilter::Duplicate => "duplicate",
+            StateFilter::Expiring => "expiring",
+            StateFilter::Archived => "archived",
+            StateFilter::Unread => "unread",
+            StateFilter::Read => "read",
+            StateFilter::Broken => "broken",
+            StateFilter::Public => "public",
+            StateFilter::Private => "private",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        [
+            StateFilter::Untagged,
+            StateFilter::Undescribed,
+            StateFilter::Duplicate,
+            StateFilter::Expiring,
+            StateFilter::Archived,
+            StateFilter::Unread,
+            StateFilter::Read,
+            StateFilter::Broken,
+            StateFilter::Public,
+            StateFilter::Private,
+        ]
+        .into_iter()
+        .find(|state| state.as_str() == name)
+    }
+
+    /// The state named by a lowercase `is:` or `unread:` term, or why it was ignored; `None` for
+    /// any other term.
+    fn from_term(word: &str) -> Option<Result<Self, SearchWarning>> {
+        if let Some(name) = word.strip_prefix("is:").filter(|name| !name.is_empty()) {
+            return Some(Self::from_name(name).ok_or_else(|| SearchWarning::UnknownState(name.to_string())));
+        }
+        let value = word.strip_prefix("unread:").filter(|value| !value.is_empty())?;
+        Some(match value {
+            "yes" | "true" => Ok(StateFilter::Unread),
+            "no" | "false" => Ok(StateFilter::Read),
+            _ => Err(SearchWarning::InvalidUnread(value.to_string())),
+        })
+    }
+}
+
+/// Something a bookmark can be searched for having with `has:`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HasFilter {
+    /// A description, which is where notes go, imported ones included
+    Description,
+    /// A saved copy of the page
+    Snapshot,
+    /// A thumbnail of the page's preview image
+    Thumbnail,
+    /// The page's text, kept for searching
+    Text,
+}
+
+impl HasFilter {
+    /// The name used after `has:`.
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HasFilter::Description => "description",
+            HasFilter::Snapshot => "snapshot",
+            HasFilter::Thumbnail => "thumbnail",
+            HasFilter::Text => "text",
+        }
+    }
+
+    /// What a lowercase `has:` term names, or why it was ignored; `None` for any other term.
+    /// `has:notes` is another name for `has:description`.
+    fn from_term(word: &str) -> Option<Result<Self, SearchWarning>> {
+        let name = word.strip_prefix("has:").filter(|name| !name.is_empty())?;
+        let has = match name {
+            "notes" => Some(HasFilter::Description),
+            _ => [HasFilter::Description, HasFilter::Snapshot, HasFilter::Thumbnail, HasFilter::Text]
+                .into_iter()
+                .find(|has| has.as_str() == name),
+        };
+        Some(has.ok_or_else(|| SearchWarning::UnknownHas(name.to_string())))
+    }
+}
+
+/// A period of time a bookmark was published or saved in.
+///
+/// For `published:`, `published:2024`, `published:2024-03` and `published:2024-03-05` are that year,
+/// month or day; with a leading `>` or `<` they mean any time after or before it.
+///
+/// For the time a bookmark was saved, `after:2023-06` is from the start of that year, month or day
+/// on, and `before:2024-01-01` up to its start, so `after:2023-06 before:2023-07` is June.
+/// `since:` takes a date like `after:`, or a time ago in days, weeks, months or years, like
+/// `since:30d`, `since:2w`, `since:6m` or `since:1y`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct DateRange {
+    /// UNIX timestamp of the start, inclusive
+    pub start: Option<i64>,
+    /// UNIX timestamp of the end, exclusive
+    pub end: Option<i64>,
+}
+
+impl DateRange {
+    /// Parses the value of a `published:` term.
+    fn parse(value: &str) -> Option<Self> {
+        let (after, before, date) = if let Some(date) = value.strip_prefix('>') {
+            (true, false, date)
+        } else if let Some(date) = value.strip_prefix('<') {
+            (false, true, date)
+        } else {
+            (false, false, value)
+        };
+        let (start, end) = Self::period(date)?;
+
+        Some(Self {
+            start: (!before).then_some(if after { end } else { start }),
+            end: (!after).then_some(if before { start } else { end }),
+        })
+    }
+
+    /// Parses the value of an `after:` term.
+    fn after(value: &str) -> Option<Self> {
+        let (start, _) = Self::period(value)?;
+        Some(Self {
+            start: Some(start),
+            end: None,
+        })
+    }
+
+    /// Parses the value of a `before:` term.
+    fn before(value: &str) -> Option<Self> {
+        let (start, _) = Self::period(value)?;
+        Some(Self {
+            start: None,
+            end: Some(start),
+        })
+    }
+
+    /// Parses the value of a `since:` term, counting a time ago back from `now`.
+    fn since(value: &str, now: DateTime<Utc>) -> Option<Self> {
+        let (count, unit) = value.split_at(value.len() - value.chars().last()?.len_utf8());
+        let count: u32 = match unit {
+            "d" | "w" | "m" | "y" => count.parse().ok()?,
+            _ => return Self::after(value),
+        };
+        let start = match unit {
+            "d" => now.checked_sub_signed(TimeDelta::try_days(count.into())?)?,
+            "w" => now.checked_sub_signed(TimeDelta::try_weeks(count.into())?)?,
+            "m" => now.checked_sub_months(Months::new(count))?,
+            _ => now.checked_sub_months(Months::new(count.checked_mul(12)?))?,
+        };
+        Some(Self {
+            start: Some(start.timestamp()),
+            end: None,
+        })
+    }
+
+    /// The UNIX timestamps of the start and end of a year, month or day like `2024-03`.
+    fn period(date: &str) -> Option<(i64, i64)> {
+        let parts = date.split('-').map(str::parse).collect::<Result<Vec<u32>, _>>().ok()?;
+        let (start, end) = match parts[..] {
+            [year] => {
+                let start = NaiveDate::from_ymd_opt(i32::try_from(year).ok()?, 1, 1)?;
+                (start, start.checked_add_months(Months::new(12))?)
+            }
+            [year, month] => {
+                let start = NaiveDate::from_ymd_opt(i32::try_from(year).ok()?, month, 1)?;
+                (start, start.checked_add_months(Months::new(1))?)
+            }
+            [year, month, day] => {
+                let start = NaiveDate::from_ymd_opt(i32::try_from(year).ok()?, month, day)?;
+                (start, start.succ_opt()?)
+            }
+            _ => return None,
+        };
+        let start = start.and_hms_opt(0, 0, 0)?.and_utc().timestamp();
+        let end = end.and_hms_opt(0, 0, 0)?.and_utc().timestamp();
+        Some((start, end))
+    }
+}
+
 /// Logic operation between search terms.
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum SearchLogic {
+    #[default]
     Or,
     And,
 }
 
+impl SearchLogic {
+    /// The value stored in the database and used in forms.
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SearchLogic::Or => "or",
+            SearchLogic::And => "and",
+        }
+    }
+}
+
+/// Something in the raw query that was ignored or interpreted differently than typed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SearchWarning {
+    /// A quote was opened but never closed; the rest of the input was treated as a phrase
+    UnclosedQuote,
+    /// A pair of quotes with nothing in between
+    EmptyPhrase,
+    /// A `#tag` at the end of the input that looks like it is still being typed
+    IncompleteTag(String),
+    /// Both AND and OR were used; AND applies to all terms
+    MixedLogic,
+    /// An `is:` term naming a state that doesn't exist
+    UnknownState(String),
+    /// A `has:` term naming something bookmarks can't have
+    UnknownHas(String),
+    /// A `published:` term that isn't a date
+    InvalidDate(String),
+    /// An `after:`, `before:` or `since:` term that isn't a date or, for `since:`, a time ago
+    InvalidCreatedDate(String),
+    /// An `unread:` term that isn't yes or no
+    InvalidUnread(String),
+}
+
+impl fmt::Display for SearchWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SearchWarning::UnclosedQuote => write!(f, "Unclosed quote, the rest of the query was searched as one phrase"),
+            SearchWarning::EmptyPhrase => write!(f, "Empty quotes were ignored"),
+            SearchWarning::IncompleteTag(tag) => write!(f, "Ignored incomplete tag '#{tag}', add a space after it to filter by it"),
+            SearchWarning::MixedLogic => write!(f, "Both AND and OR were used, so all terms must match"),
+            SearchWarning::UnknownState(state) => {
+                write!(
+                    f,
+                    "Ignored unknown 'is:{state}', try is:untagged, is:undescribed, is:duplicate, is:expiring, is:archived, is:unread, is:read, is:broken, is:public or is:private"
+                )
+            }
+            SearchWarning::UnknownHas(name) => {
+                write!(
+                    f,
+                    "Ignored unknown 'has:{name}', try has:notes, has:description, has:snapshot, has:thumbnail or has:text"
+                )
+            }
+            SearchWarning::InvalidDate(date) => {
+                write!(
+                    f,
+                    "Ignored 'published:{date}', use a year, month or day like published:2024-03, optionally after > or <"
+                )
+            }
+            SearchWarning::InvalidCreatedDate(term) => {
+                write!(
+                    f,
+                    "Ignored '{term}', use a year, month or day like after:2024-03, or a time ago like since:30d, since:2w, since:6m or since:1y"
+                )
+            }
+            SearchWarning::InvalidUnread(value) => write!(f, "Ignored 'unread:{value}', use unread:yes or unread:no"),
+        }
+    }
+}
+
+/// Breakdown of how a raw query was interpreted, for showing users why results matched.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SearchExplanation {
+    /// Single words, matched anywhere in title, description, URL or tags
+    pub terms: Vec<String>,
+    /// Quoted exact phrases
+    pub phrases: Vec<String>,
+    /// `title:`, `url:`, `desc:` and `content:` terms, as written
+    pub field_terms: Vec<String>,
+    /// `#tag` filters
+    pub tags: Vec<String>,
+    /// `site:` filters
+    pub sites: Vec<String>,
+    /// `source:` filters
+    pub sources: Vec<String>,
+    /// `lang:` filters
+    pub languages: Vec<String>,
+    /// `author:` filters
+    pub authors: Vec<String>,
+    /// `sitename:` filters
+    pub site_names: Vec<String>,
+    /// `published:` filters
+    pub published: Vec<DateRange>,
+    /// `after:`, `before:` and `since:` filters
+    pub created: Vec<DateRange>,
+    /// `is:` filters
+    pub states: Vec<StateFilter>,
+    /// `has:` filters
+    pub has: Vec<HasFilter>,
+    pub logic: SearchLogic,
+    /// Human-readable notes about anything ignored or reinterpreted
+    pub warnings: Vec<String>,
+}
+
 impl fmt::Display for SearchTerm {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -39,13 +460,33 @@ impl fmt::Display for SearchTerm {
     }
 }
 
+impl fmt::Display for FieldTerm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.field.qualifier(), self.term)
+    }
+}
+
 impl SearchQuery {
     /// Creates a new empty search query.
+    #[must_use]
     pub fn new() -> Self {
         Self {
             general_terms: Vec::new(),
+            field_terms: Vec::new(),
             tag_filters: Vec::new(),
+            site_filters: Vec::new(),
+            source_filters: Vec::new(),
+            language_filters: Vec::new(),
+            author_filters: Vec::new(),
+            site_name_filters: Vec::new(),
+            published_filters: Vec::new(),
+            created_filters: Vec::new(),
+            state_filters: Vec::new(),
+            has_filters: Vec::new(),
             logic: SearchLogic::Or,
+            include_archived: false,
+            fuzzy: false,
+            page_text: false,
         }
     }
 
@@ -69,48 +510,201 @@ impl SearchQuery {
     /// let query = SearchQuery::parse("\"web development\" rust");
     /// assert!(matches!(query.general_terms[0], SearchTerm::Phrase(_)));
     /// ```
+    #[must_use]
     pub fn parse(input: &str) -> Self {
-        let mut query = Self::new();
+        Self::parse_with(input, SearchDefaults::default())
+    }
+
+    /// Parses a query like [`SearchQuery::parse`], reading it with a user's defaults.
+    #[must_use]
+    pub fn parse_with(input: &str, defaults: SearchDefaults) -> Self {
+        Self::parse_with_warnings(input, defaults, Utc::now()).0
+    }
+
+    /// Parses a query that has been written out in full, such as a saved rule, rather than one
+    /// still being typed. A `#tag` at the very end is always taken as complete.
+    #[must_use]
+    pub fn parse_complete(input: &str) -> Self {
+        Self::parse_complete_with(input, SearchDefaults::default())
+    }
+
+    /// Parses a query like [`SearchQuery::parse_complete`], reading it with a user's defaults.
+    #[must_use]
+    pub fn parse_complete_with(input: &str, defaults: SearchDefaults) -> Self {
+        Self::parse_with(&format!("{input} "), defaults)
+    }
+
+    /// Parses a query like [`SearchQuery::parse`] and explains the result, including warnings
+    /// about anything in the input that was ignored or reinterpreted.
+    #[must_use]
+    pub fn explain(input: &str) -> SearchExplanation {
+        Self::explain_with(input, SearchDefaults::default())
+    }
+
+    /// Explains a query like [`SearchQuery::explain`], reading it with a user's defaults.
+    #[must_use]
+    pub fn explain_with(input: &str, defaults: SearchDefaults) -> SearchExplanation {
+        let (query, warnings) = Self::parse_with_warnings(input, defaults, Utc::now());
+        let mut terms = Vec::new();
+        let mut phrases = Vec::new();
+        for term in query.general_terms {
+            match term {
+                SearchTerm::Word(word) => terms.push(word),
+                SearchTerm::Phrase(phrase) => phrases.push(phrase),
+            }
+        }
+
+        SearchExplanation {
+            terms,
+            phrases,
+            field_terms: query.field_terms.iter().map(ToString::to_string).collect(),
+            tags: query.tag_filters,
+            sites: query.site_filters,
+            sources: query.source_filters,
+            languages: query.language_filters,
+            authors: query.author_filters,
+            site_names: query.site_name_filters,
+            published: query.published_filters,
+            created: query.created_filters,
+            states: query.state_filters,
+            has: query.has_filters,
+            logic: query.logic,
+            warnings: warnings.iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    /// Parses a query, also collecting warnings about input that was ignored or reinterpreted.
+    /// Times ago, like `since:30d`, are counted back from `now`.
+    fn parse_with_warnings(input: &str, defaults: SearchDefaults, now: DateTime<Utc>) -> (Self, Vec<SearchWarning>) {
+        let mut query = Self {
+            logic: defaults.logic,
+            include_archived: defaults.include_archived,
+            fuzzy: defaults.fuzzy,
+            page_text: defaults.page_text,
+            ..Self::new()
+        };
+        let mut warnings = Vec::new();
 
         if input.trim().is_empty() {
-            return query;
+            return (query, warnings);
         }
 
         // Check if query contains logical operators (case-insensitive)
         let lower_input = input.to_lowercase();
         if lower_input.contains(" and ") {
             query.logic = SearchLogic::And;
+            if lower_input.contains(" or ") {
+                warnings.push(SearchWarning::MixedLogic);
+            }
         } else if lower_input.contains(" or ") {
-            query.logic = SearchLogic::Or; // Explicitly set OR (though it's default)
+            query.logic = SearchLogic::Or; // Explicitly set OR, whatever the user's default
         }
 
         // Parse terms, handling quotes and AND keywords
-        let terms = Self::tokenize(input);
+        let terms = Self::tokenize(input, &mut warnings);
 
+        // `author:"Jane Doe"` is tokenized as the bare `author:` followed by the phrase
+        let mut phrase_operator: Option<PhraseOperator> = None;
         for term in terms {
+            let operator = phrase_operator.take();
             match term {
                 Token::Word(word) => {
                     // Skip logical operator keywords when building terms
                     let lower_word = word.to_lowercase();
-                    if lower_word != "and" && lower_word != "or" {
+                    if let Some(site) = lower_word.strip_prefix("site:").filter(|site| !site.is_empty()) {
+                        let site = site.strip_prefix("www.").unwrap_or(site);
+                        query.site_filters.push(site.trim_end_matches('/').to_string());
+                    } else if let Some(source) = lower_word.strip_prefix("source:").filter(|source| !source.is_empty()) {
+                        query.source_filters.push(source.to_string());
+                    } else if let Some(language) = lower_word.strip_prefix("lang:").filter(|language| !language.is_empty()) {
+                        query.language_filters.push(language.to_string());
+                    } else if let Some(author) = lower_word.strip_prefix("author:") {
+                        if author.is_empty() {
+                            phrase_operator = Some(PhraseOperator::Author);
+                        } else {
+                            query.author_filters.push(author.to_string());
+                        }
+                    } else if let Some(site_name) = lower_word.strip_prefix("sitename:") {
+                        if site_name.is_empty() {
+                            phrase_operator = Some(PhraseOperator::SiteName);
+                        } else {
+                            query.site_name_filters.push(site_name.to_string());
+                        }
+                    } else if let Some(date) = lower_word.strip_prefix("published:").filter(|date| !date.is_empty()) {
+                        match DateRange::parse(date) {
+                            Some(published) => query.published_filters.push(published),
+                            None => warnings.push(SearchWarning::InvalidDate(date.to_string())),
+                        }
+                    } else if let Some((operator, date)) = ["after:", "before:", "since:"].into_iter().find_map(|operator| {
+                        lower_word
+                            .strip_prefix(operator)
+                            .filter(|date| !date.is_empty())
+                            .map(|date| (operator, date))
+                    }) {
+                        let created = match operator {
+                            "after:" => DateRange::after(date),
+                            "before:" => DateRange::before(date),
+                            _ => DateRange::since(date, now),
+                        };
+                        match created {
+                            Some(created) => query.created_filters.push(created),
+                            None => warnings.push(SearchWarning::InvalidCreatedDate(lower_word.clone())),
+                        }
+                    } else if let Some(added) = query.add_state_term(&lower_word) {
+                        warnings.extend(added.err());
+                    } else if let Some((field, value)) = SearchField::split(&word) {
+                        if value.is_empty() {
+                            phrase_operator = Some(PhraseOperator::Field(field));
+                        } else {
+                            let term = SearchTerm::Word(value.to_string());
+                            query.field_terms.push(FieldTerm { field, term });
+                        }
+                    } else if lower_word != "and" && lower_word != "or" {
                         query.general_terms.push(SearchTerm::Word(word));
                     }
                 }
-                Token::Phrase(phrase) => {
-                    query.general_terms.push(SearchTerm::Phrase(phrase));
-                }
+                Token::Phrase(phrase) => match operator {
+                    Some(PhraseOperator::Author) => query.author_filters.push(phrase.to_lowercase()),
+                    Some(PhraseOperator::SiteName) => query.site_name_filters.push(phrase.to_lowercase()),
+                    Some(PhraseOperator::Field(field)) => query.field_terms.push(FieldTerm {
+                        field,
+                        term: SearchTerm::Phrase(phrase),
+                    }),
+                    None => query.general_terms.push(SearchTerm::Phrase(phrase)),
+                },
                 Token::Tag(tag) => {
                     query.tag_filters.push(tag);
                 }
             }
         }
 
-        query
+        (query, warnings)
+    }
+
+    /// Adds a state bookmarks must be in, unless it's already there.
+    fn add_state(&mut self, state: StateFilter) {
+        if !self.state_filters.contains(&state) {
+            self.state_filters.push(state);
+        }
+    }
+
+    /// Adds what a lowercase `is:`, `unread:` or `has:` term says bookmarks must be in or have,
+    /// unless it's already there, or says why the term was ignored; `None` for any other term.
+    fn add_state_term(&mut self, word: &str) -> Option<Result<(), SearchWarning>> {
+        if let Some(state) = StateFilter::from_term(word) {
+            return Some(state.map(|state| self.add_state(state)));
+        }
+        let has = HasFilter::from_term(word)?;
+        Some(has.map(|has| {
+            if !self.has_filters.contains(&has) {
+                self.has_filters.push(has);
+            }
+        }))
     }
 
     /// Tokenizes input string, respecting quoted phrases and #tag syntax.
     /// Only treats tags as complete when followed by whitespace or at string end.
-    fn tokenize(input: &str) -> Vec<Token> {
+    fn tokenize(input: &str, warnings: &mut Vec<SearchWarning>) -> Vec<Token> {
         let mut tokens = Vec::new();
         let mut current_token = String::new();
         let mut in_quotes = false;
@@ -138,10 +732,12 @@ impl SearchQuery {
                 }
                 '"' | '\'' if in_quotes && Some(ch) == quote_char => {
                     // End of quoted phrase
-                    if !current_token.is_empty() {
+                    if current_token.trim().is_empty() {
+                        warnings.push(SearchWarning::EmptyPhrase);
+                    } else {
                         tokens.push(Token::Phrase(current_token.trim().to_string()));
-                        current_token.clear();
                     }
+                    current_token.clear();
                     in_quotes = false;
                     quote_char = None;
                 }
@@ -171,6 +767,9 @@ impl SearchQuery {
 
         // Handle final token - only add tags if they appear to be complete
         // (i.e., we're not in the middle of typing)
+        if in_quotes {
+            warnings.push(SearchWarning::UnclosedQuote);
+        }
         if !current_token.is_empty() {
             if in_quotes {
                 // Unclosed quote - treat as phrase anyway
@@ -182,8 +781,10 @@ impl SearchQuery {
                 // unless they're clearly meant to be complete
                 if Self::is_tag_complete(&current_token, input) {
                     tokens.push(Token::Tag(current_token.trim().to_string()));
+                } else {
+                    // Skip incomplete tags entirely - they should not affect search results
+                    warnings.push(SearchWarning::IncompleteTag(current_token.trim().to_string()));
                 }
-                // Skip incomplete tags entirely - they should not affect search results
             } else {
                 tokens.push(Token::Word(current_token.trim().to_string()));
             }
@@ -222,8 +823,30 @@ impl SearchQuery {
     }
 
     /// Checks if the query is empty (no search terms).
+    #[must_use]
     pub fn is_empty(&self) -> bool {
-        self.general_terms.is_empty() && self.tag_filters.is_empty()
+        self.general_terms.is_empty()
+            && self.field_terms.is_empty()
+            && self.tag_filters.is_empty()
+            && self.site_filters.is_empty()
+            && self.source_filters.is_empty()
+            && self.language_filters.is_empty()
+            && self.author_filters.is_empty()
+            && self.site_name_filters.is_empty()
+            && self.published_filters.is_empty()
+            && self.created_filters.is_empty()
+            && self.state_filters.is_empty()
+            && self.has_filters.is_empty()
+    }
+
+    /// Limits the search to bookmarks that can be shown to people other than their owner, as
+    /// everything they're shown must be: guest links, public tags and subscriptions.
+    #[must_use]
+    pub fn public_only(mut self) -> Self {
+        if !self.state_filters.contains(&StateFilter::Public) {
+            self.state_filters.push(StateFilter::Public);
+        }
+        self
     }
 }
 
@@ -233,6 +856,14 @@ impl Default for SearchQuery {
     }
 }
 
+/// An operator written without a value, which applies to the phrase that follows it.
+#[derive(Clone, Copy, Debug)]
+enum PhraseOperator {
+    Author,
+    SiteName,
+    Field(SearchField),
+}
+
 /// Internal token representation during parsing.
 #[derive(Clone, Debug)]
 enum Token {
@@ -404,4 +1035,329 @@ mod tests {
         assert_eq!(query.tag_filters[0], "rust");
         assert_eq!(query.general_terms.len(), 0); // Incomplete tags are completely ignored
     }
+
+    #[test]
+    fn test_site_filters() {
+        let query = SearchQuery::parse("site:www.GitHub.com AND #rust async");
+        assert_eq!(query.site_filters, ["github.com"]);
+        assert_eq!(query.tag_filters, ["rust"]);
+        assert_eq!(query.general_terms, [SearchTerm::Word("async".to_string())]);
+        assert_eq!(query.logic, SearchLogic::And);
+
+        let query = SearchQuery::parse("site:docs.rs site:crates.io/");
+        assert_eq!(query.site_filters, ["docs.rs", "crates.io"]);
+        assert!(query.general_terms.is_empty());
+        assert!(!query.is_empty());
+
+        // A bare `site:` is just a word
+        let query = SearchQuery::parse("site:");
+        assert!(query.site_filters.is_empty());
+        assert_eq!(query.general_terms.len(), 1);
+    }
+
+    #[test]
+    fn test_source_filters() {
+        let query = SearchQuery::parse("source:Import rust");
+        assert_eq!(query.source_filters, ["import"]);
+        assert_eq!(query.general_terms, [SearchTerm::Word("rust".to_string())]);
+        assert!(!SearchQuery::parse("source:web").is_empty());
+        assert_eq!(SearchQuery::explain("source:api site:go.dev").sources, ["api"]);
+    }
+
+    #[test]
+    fn test_language_filters() {
+        let query = SearchQuery::parse("lang:DE lang:fr rust");
+        assert_eq!(query.language_filters, ["de", "fr"]);
+        assert_eq!(query.general_terms, [SearchTerm::Word("rust".to_string())]);
+        assert!(!SearchQuery::parse("lang:en").is_empty());
+        assert_eq!(SearchQuery::explain("lang:ja").languages, ["ja"]);
+    }
+
+    #[test]
+    fn test_author_filters() {
+        let query = SearchQuery::parse("author:Knuth author:\"Leslie Lamport\" \"art of\"");
+        assert_eq!(query.author_filters, ["knuth", "leslie lamport"]);
+        assert_eq!(query.general_terms, [SearchTerm::Phrase("art of".to_string())]);
+        assert!(!SearchQuery::parse("author:knuth").is_empty());
+        assert_eq!(SearchQuery::explain("author:'ann' rust").authors, ["ann"]);
+    }
+
+    #[test]
+    fn test_site_name_filters() {
+        let query = SearchQuery::parse("sitename:Guardian sitename:\"New York Times\" author:\"Ann\"");
+        assert_eq!(query.site_name_filters, ["guardian", "new york times"]);
+        assert_eq!(query.author_filters, ["ann"]);
+        assert!(query.general_terms.is_empty());
+        assert_eq!(SearchQuery::explain("sitename:arxiv").site_names, ["arxiv"]);
+    }
+
+    #[test]
+    fn test_field_terms() {
+        let query = SearchQuery::parse("title:Axum URL:github.com desc:\"error handling\" content:tokio rust");
+        assert_eq!(
+            query.field_terms,
+            [
+                FieldTerm {
+                    field: SearchField::Title,
+                    term: SearchTerm::Word("Axum".to_string()),
+                },
+                FieldTerm {
+                    field: SearchField::Url,
+                    term: SearchTerm::Word("github.com".to_string()),
+                },
+                FieldTerm {
+                    field: SearchField::Description,
+                    term: SearchTerm::Phrase("error handling".to_string()),
+                },
+                FieldTerm {
+                    field: SearchField::Content,
+                    term: SearchTerm::Word("tokio".to_string()),
+                },
+            ]
+        );
+        assert_eq!(query.general_terms, [SearchTerm::Word("rust".to_string())]);
+        assert!(!SearchQuery::parse("url:docs.rs").is_empty());
+
+        // Other words with a colon are still general terms
+        let query = SearchQuery::parse("titles:x https://example.com");
+        assert!(query.field_terms.is_empty());
+        assert_eq!(query.general_terms.len(), 2);
+
+        assert_eq!(
+            SearchQuery::explain("title:'web app' url:crates.io").field_terms,
+            ["title:\"web app\"", "url:crates.io"]
+        );
+    }
+
+    #[test]
+    fn test_published_filters() {
+        let query = SearchQuery::parse("published:2024 published:2024-02 published:2024-02-29");
+        assert_eq!(
+            query.published_filters,
+            [
+                // 2024-01-01 to 2025-01-01
+                DateRange {
+                    start: Some(1_704_067_200),
+                    end: Some(1_735_689_600),
+                },
+                // 2024-02-01 to 2024-03-01
+                DateRange {
+                    start: Some(1_706_745_600),
+                    end: Some(1_709_251_200),
+                },
+                // 2024-02-29 to 2024-03-01
+                DateRange {
+                    start: Some(1_709_164_800),
+                    end: Some(1_709_251_200),
+                },
+            ]
+        );
+        assert!(query.general_terms.is_empty());
+
+        // After the end of 2023, before the start of 2024
+        let query = SearchQuery::parse("published:>2023 published:<2024");
+        assert_eq!(
+            query.published_filters,
+            [
+                DateRange {
+                    start: Some(1_704_067_200),
+                    end: None,
+                },
+                DateRange {
+                    start: None,
+                    end: Some(1_704_067_200),
+                },
+            ]
+        );
+
+        let explanation = SearchQuery::explain("published:2023-02-30 published:lately");
+        assert!(explanation.published.is_empty());
+        assert_eq!(
+            explanation.warnings,
+            [
+                SearchWarning::InvalidDate("2023-02-30".to_string()).to_string(),
+                SearchWarning::InvalidDate("lately".to_string()).to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_created_filters() {
+        let (query, warnings) =
+            SearchQuery::parse_with_warnings("after:2023-06 before:2024-01-01 rust", SearchDefaults::default(), Utc::now());
+        assert_eq!(
+            query.created_filters,
+            [
+                // From 2023-06-01
+                DateRange {
+                    start: Some(1_685_577_600),
+                    end: None,
+                },
+                // Until 2024-01-01
+                DateRange {
+                    start: None,
+                    end: Some(1_704_067_200),
+                },
+            ]
+        );
+        assert_eq!(query.general_terms, [SearchTerm::Word("rust".to_string())]);
+        assert!(warnings.is_empty());
+
+        // 2025-03-31 12:00 UTC
+        let now = DateTime::from_timestamp(1_743_422_400, 0).unwrap();
+        let since = |input: &str| {
+            SearchQuery::parse_with_warnings(input, SearchDefaults::default(), now)
+                .0
+                .created_filters[0]
+                .start
+        };
+        assert_eq!(since("since:30d"), Some(1_743_422_400 - 30 * 86400));
+        assert_eq!(since("since:2w"), Some(1_743_422_400 - 14 * 86400));
+        // 2025-02-28 12:00, the end of the shorter month
+        assert_eq!(since("since:1m"), Some(1_740_744_000));
+        // 2024-03-31 12:00
+        assert_eq!(since("since:1y"), Some(1_711_886_400));
+        assert_eq!(since("since:2023-06"), Some(1_685_577_600));
+
+        let (query, warnings) = SearchQuery::parse_with_warnings("since:30x before:soon after:2023-13", SearchDefaults::default(), now);
+        assert!(query.created_filters.is_empty());
+        assert_eq!(
+            warnings,
+            [
+                SearchWarning::InvalidCreatedDate("since:30x".to_string()),
+                SearchWarning::InvalidCreatedDate("before:soon".to_string()),
+                SearchWarning::InvalidCreatedDate("after:2023-13".to_string()),
+            ]
+        );
+        assert_eq!(SearchQuery::explain("since:1w").created.len(), 1);
+    }
+
+    #[test]
+    fn test_state_filters() {
+        let query = SearchQuery::parse("is:Untagged is:duplicate is:untagged is:expiring is:archived is:broken");
+        assert_eq!(
+            query.state_filters,
+            [
+                StateFilter::Untagged,
+                StateFilter::Duplicate,
+                StateFilter::Expiring,
+                StateFilter::Archived,
+                StateFilter::Broken
+            ]
+        );
+        assert!(query.general_terms.is_empty());
+        assert!(!query.is_empty());
+
+        let explanation = SearchQuery::explain("is:starred rust");
+        assert!(explanation.states.is_empty());
+        assert_eq!(explanation.terms, ["rust"]);
+        assert_eq!(
+            explanation.warnings,
+            [SearchWarning::UnknownState("starred".to_string()).to_string()]
+        );
+    }
+
+    #[test]
+    fn test_visibility_filters() {
+        let query = SearchQuery::parse("is:private rust");
+        assert_eq!(query.state_filters, [StateFilter::Private]);
+
+        // Shown to others: a private search still only finds public bookmarks, so finds nothing
+        let query = query.public_only();
+        assert_eq!(query.state_filters, [StateFilter::Private, StateFilter::Public]);
+        assert_eq!(
+            SearchQuery::default().public_only().public_only().state_filters,
+            [StateFilter::Public]
+        );
+        assert!(!SearchQuery::default().public_only().is_empty());
+    }
+
+    #[test]
+    fn test_has_filters() {
+        let query = SearchQuery::parse("has:Notes has:snapshot has:description is:unread");
+        assert_eq!(query.has_filters, [HasFilter::Description, HasFilter::Snapshot]);
+        assert_eq!(query.state_filters, [StateFilter::Unread]);
+        assert!(query.general_terms.is_empty());
+        assert!(!SearchQuery::parse("has:text").is_empty());
+
+        let explanation = SearchQuery::explain("has:stars rust");
+        assert!(explanation.has.is_empty());
+        assert_eq!(explanation.terms, ["rust"]);
+        assert_eq!(explanation.warnings, [SearchWarning::UnknownHas("stars".to_string()).to_string()]);
+    }
+
+    #[test]
+    fn test_unread_filters() {
+        let query = SearchQuery::parse("is:unread Unread:YES rust");
+        assert_eq!(query.state_filters, [StateFilter::Unread]);
+        assert_eq!(query.general_terms, [SearchTerm::Word("rust".to_string())]);
+
+        let query = SearchQuery::parse("unread:no");
+        assert_eq!(query.state_filters, [StateFilter::Read]);
+        assert!(!query.is_empty());
+
+        let explanation = SearchQuery::explain("unread:maybe rust");
+        assert!(explanation.states.is_empty());
+        assert_eq!(explanation.terms, ["rust"]);
+        assert_eq!(
+            explanation.warnings,
+            [SearchWarning::InvalidUnread("maybe".to_string()).to_string()]
+        );
+    }
+
+    #[test]
+    fn test_parse_complete_keeps_trailing_tag() {
+        assert!(SearchQuery::parse("#go").is_empty());
+        assert_eq!(SearchQuery::parse_complete("#go").tag_filters, ["go"]);
+        assert_eq!(SearchQuery::parse_complete("site:go.dev #go").tag_filters, ["go"]);
+    }
+
+    #[test]
+    fn test_explain_breakdown() {
+        let explanation = SearchQuery::explain("rust and \"web development\" #async ");
+        assert_eq!(explanation.terms, ["rust"]);
+        assert_eq!(explanation.phrases, ["web development"]);
+        assert_eq!(explanation.tags, ["async"]);
+        assert_eq!(explanation.logic, SearchLogic::And);
+        assert!(explanation.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_explain_warnings() {
+        let explanation = SearchQuery::explain("\"web development");
+        assert_eq!(explanation.phrases, ["web development"]);
+        assert_eq!(explanation.warnings.len(), 1);
+        assert!(explanation.warnings[0].contains("Unclosed quote"));
+
+        let explanation = SearchQuery::explain("rust #we");
+        assert!(explanation.tags.is_empty());
+        assert_eq!(explanation.warnings, [SearchWarning::IncompleteTag("we".to_string()).to_string()]);
+
+        let explanation = SearchQuery::explain("rust and web or css");
+        assert_eq!(explanation.warnings, [SearchWarning::MixedLogic.to_string()]);
+
+        let explanation = SearchQuery::explain("rust \"\"");
+        assert_eq!(explanation.terms, ["rust"]);
+        assert_eq!(explanation.warnings, [SearchWarning::EmptyPhrase.to_string()]);
+    }
+
+    #[test]
+    fn test_user_defaults() {
+        let defaults = SearchDefaults {
+            logic: SearchLogic::And,
+            include_archived: true,
+            fuzzy: true,
+            page_text: true,
+        };
+        let query = SearchQuery::parse_with("rust axum", defaults);
+        assert_eq!(query.logic, SearchLogic::And);
+        assert!(query.include_archived && query.fuzzy && query.page_text);
+        // The query still decides when it says
+        assert_eq!(SearchQuery::parse_with("rust or axum", defaults).logic, SearchLogic::Or);
+        assert_eq!(SearchQuery::explain_with("rust axum", defaults).logic, SearchLogic::And);
+
+        let query = SearchQuery::parse("rust axum");
+        assert_eq!(query.logic, SearchLogic::Or);
+        assert!(!query.include_archived && !query.fuzzy && !query.page_text);
+    }
 }
@@ -0,0 +1,576 @@
+//! Snapshots: self-contained copies of saved pages, kept in case the page changes or disappears.
+//!
+//! For users with the [`Feature::Archiving`] feature, a page is fetched when it's bookmarked (or
+//! later, on demand) and made into a single HTML file: linked stylesheets become `<style>`
+//! elements, and images, along with the images and fonts stylesheets use, become `data:` URIs.
+//! Scripts, frames and plugins are left out, and a `<base>` keeps the page's links pointing at the
+//! original site. Stylesheets imported by other stylesheets, video and audio are not kept.
+//!
+//! Snapshots are served from the instance with [`SNAPSHOT_CSP`], so whatever a page contains, its
+//! copy can't run scripts or load anything from elsewhere.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, LazyLock},
+};
+
+use anyhow::{Context as _, Result, bail};
+use base64::{Engine as _, engine::general_purpose::STANDARD};
+use regex::{Captures, Regex};
+use reqwest::{Client, Url, header::CONTENT_TYPE};
+use tl::{HTMLTag, ParserOptions};
+use tokio::{sync::Semaphore, task::JoinSet};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::{
+    AppState,
+    config::FetchConfig,
+    db::{self, bookmarks::BookmarkId},
+    features::Feature,
+    fetch::{check_destination, decode_html_entities, is_fetch_allowed, read_body},
+};
+
+/// Pages larger than this aren't saved.
+const MAX_PAGE_BYTES: usize = 5 * 1024 * 1024;
+/// Stylesheets, images and fonts larger than this are left out.
+const MAX_RESOURCE_BYTES: usize = 2 * 1024 * 1024;
+/// Once this much has been inlined into a snapshot, further images and fonts are left out.
+const MAX_INLINED_BYTES: usize = 20 * 1024 * 1024;
+/// At most this many stylesheets, images and fonts are downloaded for one page.
+const MAX_RESOURCES: usize = 100;
+/// How many of a page's stylesheets, images and fonts are downloaded at once.
+const CONCURRENT_DOWNLOADS: usize = 6;
+
+/// Content Security Policy snapshots are served with: inline styles and `data:` images and fonts
+/// only, no scripts, and links open in a new tab outside the sandbox.
+pub const SNAPSHOT_CSP: &str = "default-src 'none'; img-src data:; font-src data:; style-src 'unsafe-inline'; \
+                                sandbox allow-popups allow-popups-to-escape-sandbox";
+
+/// A script ends at the first `</script>`, whatever it contains, so scripts are cut out before the
+/// page is parsed.
+static SCRIPT: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?is)<script\b.*?</script\s*>").expect("valid pattern"));
+static STYLE_ELEMENT: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?is)^(<style\b[^>]*>)(.*)</style\s*>$").expect("valid pattern"));
+static CSS_URL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?i)url\(\s*(?:"([^"]*)"|'([^']*)'|([^)"'\s]*))\s*\)"#).expect("valid pattern"));
+
+/// A saved page, made self-contained.
+#[derive(Debug)]
+pub struct PageSnapshot {
+    pub html: String,
+    /// Where the page was fetched from, after redirects
+    pub url: String,
+}
+
+/// A stylesheet, image or font downloaded for a snapshot.
+#[derive(Clone, Debug)]
+struct Resource {
+    content_type: String,
+    data: Vec<u8>,
+}
+
+impl Resource {
+    fn data_uri(&self) -> String {
+        format!("data:{};base64,{}", self.content_type, STANDARD.encode(&self.data))
+    }
+}
+
+/// Whether pages may be saved for a user: they need the archiving feature, and fetching must be
+/// allowed.
+///
+/// # Errors
+///
+/// Returns an error if the user's settings or feature overrides cannot be loaded.
+pub async fn is_archiving_allowed(state: &AppState, user_id: Uuid) -> Result<bool> {
+    Ok(state.features.is_enabled(&state.db, user_id, Feature::Archiving).await?
+        && is_fetch_allowed(&state.fetch_config, &state.db, user_id).await?)
+}
+
+/// Saves a copy of a newly bookmarked page, if archiving is allowed for the user. Failures are
+/// only logged, as nobody is waiting on this.
+pub async fn save_new_snapshot(state: Arc<AppState>, user_id: Uuid, bookmark_id: BookmarkId, url: String) {
+    match is_archiving_allowed(&state, user_id).await {
+        Ok(true) => {}
+        Ok(false) => return,
+        Err(err) => {
+            warn!(?err, "Could not load archiving settings, not saving a snapshot.");
+            return;
+        }
+    }
+    if let Err(err) = save_snapshot(&state, bookmark_id, &url).await {
+        warn!(%bookmark_id, url, %err, "🗄️ Could not save a snapshot of the page.");
+    }
+}
+
+/// Saves a copy of a bookmarked page, replacing any earlier one.
+///
+/// # Errors
+///
+/// Returns an error if the page can't be fetched or the snapshot can't be stored.
+pub async fn save_snapshot(state: &AppState, bookmark_id: BookmarkId, url: &str) -> Result<()> {
+    let snapshot = capture(&state.http_client, &state.fetch_config, url).await?;
+    let size = i64::try_from(snapshot.html.len())?;
+    let content = lz4_flex::compress_prepend_size(snapshot.html.as_bytes());
+    db::snapshots::set_snapshot(&state.db, bookmark_id, &content, size, &snapshot.url).await?;
+    info!(%bookmark_id, size, compressed = content.len(), "🗄️ Saved a snapshot of the page.");
+    Ok(())
+}
+
+/// Reads a snapshot's HTML as stored by [`save_snapshot`].
+///
+/// # Errors
+///
+/// Returns an error if the stored snapshot is corrupt.
+pub fn decode(content: &[u8]) -> Result<String> {
+    let html = lz4_flex::decompress_size_prepended(content).context("Could not decompress snapshot")?;
+    Ok(String::from_utf8_lossy(&html).into_owned())
+}
+
+/// Fetches a page with its stylesheets, images and fonts, and puts it all into one HTML file.
+///
+/// # Errors
+///
+/// Returns an error if the page may not be fetched, the request fails, or it isn't a web page.
+#[tracing::instrument(level = "trace", name = "http.snapshot", skip_all, fields(url = url))]
+pub async fn capture(client: &Client, config: &FetchConfig, url: &str) -> Result<PageSnapshot> {
+    check_destination(config, url)?;
+    let response = client.get(url).send().await?.error_for_status()?;
+    let content_type = response.headers().get(CONTENT_TYPE).and_then(|value| value.to_str().ok());
+    if let Some(content_type) = content_type.filter(|content_type| !content_type.contains("html")) {
+        bail!("Only web pages can be saved, not {content_type}");
+    }
+    let page_url = response.url().clone();
+    let body = read_body(response, MAX_PAGE_BYTES).await?;
+    let html = SCRIPT.replace_all(&String::from_utf8_lossy(&body), "").into_owned();
+    let plan = Plan::new(&html, &page_url)?;
+
+    let stylesheet_urls: Vec<Url> = plan.stylesheet_urls().take(MAX_RESOURCES).collect();
+    let stylesheets: Vec<(Url, String)> = download_all(client, config, stylesheet_urls.clone())
+        .await
+        .into_iter()
+        .map(|(url, stylesheet)| (url, String::from_utf8_lossy(&stylesheet.data).into_owned()))
+        .collect();
+
+    let mut urls = plan.resource_urls();
+    for (url, css) in &stylesheets {
+        urls.extend(css_urls(css, url));
+    }
+    urls.sort_unstable();
+    urls.dedup();
+    urls.truncate(MAX_RESOURCES - stylesheet_urls.len());
+    let resources = download_all(client, config, urls).await;
+
+    let stylesheets = stylesheets
+        .into_iter()
+        .map(|(url, css)| {
+            let css = inline_css(&css, &url, &resources);
+            (url, css)
+        })
+        .collect();
+    Ok(PageSnapshot {
+        html: plan.render(&html, &stylesheets, &resources),
+        url: page_url.to_string(),
+    })
+}
+
+/// Downloads a page's stylesheets, images or fonts, a few at a time, leaving out any that fail or
+/// are too large.
+async fn download_all(client: &Client, config: &FetchConfig, urls: Vec<Url>) -> HashMap<Url, Resource> {
+    let permits = Arc::new(Semaphore::new(CONCURRENT_DOWNLOADS));
+    let mut downloads = JoinSet::new();
+    for url in urls {
+        let (client, config, permits) = (client.clone(), config.clone(), permits.clone());
+        downloads.spawn(async move {
+            let _permit = permits.acquire_owned().await;
+            let resource = download(&client, &config, &url).await;
+            (url, resource)
+        });
+    }
+
+    let mut resources = HashMap::new();
+    let mut inlined = 0;
+    while let Some(download) = downloads.join_next().await {
+        match download {
+            Ok((url, Ok(resource))) if inlined + resource.data.len() <= MAX_INLINED_BYTES => {
+                inlined += resource.data.len();
+                resources.insert(url, resource);
+            }
+            Ok((url, Ok(_))) => debug!(%url, "Snapshot is full, leaving out a page resource"),
+            Ok((url, Err(err))) => debug!(%url, %err, "Could not download a page resource"),
+            Err(err) => warn!(%err, "Page resource download failed."),
+        }
+    }
+    resources
+}
+
+async fn download(client: &Client, config: &FetchConfig, url: &Url) -> Result<Resource> {
+    check_destination(config, url.as_str())?;
+    let response = client.get(url.clone()).send().await?.error_for_status()?;
+    // Only the type itself, in case its parameters would break out of the data: URI
+    let content_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .map(|value| value.trim().to_ascii_lowercase())
+        .filter(|value| value.chars().all(|c| c.is_ascii_alphanumeric() || "/+-.".contains(c)))
+        .unwrap_or_else(|| "application/octet-stream".to_string());
+    let data = read_body(response, MAX_RESOURCE_BYTES).await?;
+    Ok(Resource { content_type, data })
+}
+
+/// What is changed in a page's HTML to make it self-contained, found in one pass over it.
+#[derive(Debug)]
+struct Plan {
+    /// Sorted, none overlapping
+    edits: Vec<Edit>,
+    /// What the page's relative URLs are relative to
+    base: Url,
+}
+
+/// The bytes `start..end` of the page replaced.
+#[derive(Debug)]
+struct Edit {
+    start: usize,
+    end: usize,
+    change: Change,
+}
+
+#[derive(Debug)]
+enum Change {
+    Remove,
+    Insert(String),
+    /// A `<link>` to a stylesheet, replaced with the stylesheet
+    Stylesheet {
+        url: Url,
+        media: Option<String>,
+    },
+    /// A `<style>` element, with the URLs in its CSS inlined
+    Style {
+        start_tag: String,
+        css: String,
+    },
+    /// An image, its source inlined; the start tag leaves out the `src` and `>`
+    Image {
+        start_tag: String,
+        url: Url,
+    },
+}
+
+impl Plan {
+    fn new(html: &str, page_url: &Url) -> Result<Self> {
+        let dom = tl::parse(html, ParserOptions::default())?;
+        let parser = dom.parser();
+        let tags = || dom.nodes().iter().filter_map(|node| node.as_tag());
+
+        // The page's own <base> is replaced, but still decides what its links mean
+        let base = tags()
+            .find(|tag| tag.name().as_utf8_str().eq_ignore_ascii_case("base"))
+            .and_then(|tag| attribute(tag, "href"))
+            .and_then(|href| resolve(page_url, &href))
+            .unwrap_or_else(|| page_url.clone());
+
+        let mut edits = Vec::new();
+        for tag in tags() {
+            let (start, end) = tag.boundaries(parser);
+            let end = end + 1;
+            let change = match tag.name().as_utf8_str().to_ascii_lowercase().as_str() {
+                "script" | "noscript" | "iframe" | "frame" | "frameset" | "object" | "embed" | "applet" | "base" | "source" => {
+                    Change::Remove
+                }
+                "meta" if attribute(tag, "http-equiv").is_some_and(|value| value.eq_ignore_ascii_case("refresh")) => Change::Remove,
+                "link" => {
+                    let is_stylesheet = attribute(tag, "rel")
+                        .is_some_and(|rel| rel.split_ascii_whitespace().any(|rel| rel.eq_ignore_ascii_case("stylesheet")));
+                    match attribute(tag, "href").and_then(|href| resolve(&base, &href)) {
+                        Some(url) if is_stylesheet => Change::Stylesheet {
+                            url,
+                            media: attribute(tag, "media"),
+                        },
+                        // Icons, preloads and the like
+                        _ => Change::Remove,
+                    }
+                }
+                "style" => match STYLE_ELEMENT.captures(&html[start..end]) {
+                    Some(style) => Change::Style {
+                        start_tag: style[1].to_string(),
+                        css: style[2].to_string(),
+                    },
+                    None => continue,
+                },
+                "img" => {
+                    // Lazy-loading scripts keep the real source aside
+                    let source = attribute(tag, "data-src")
+                        .or_else(|| attribute(tag, "src"))
+                        .and_then(|src| resolve(&base, &src));
+                    let Some(url) = source else { continue };
+                    Change::Image {
+                        start_tag: start_tag_without(tag, &["src", "srcset", "sizes", "data-src", "data-srcset", "loading"]),
+                        url,
+                    }
+                }
+                _ => continue,
+            };
+            edits.push(Edit { start, end, change });
+        }
+
+        // Right at the start of <head>, where the <base> has to be
+        let head = tags()
+            .find(|tag| tag.name().as_utf8_str().eq_ignore_ascii_case("head"))
+            .or_else(|| tags().find(|tag| tag.name().as_utf8_str().eq_ignore_ascii_case("html")))
+            .map_or(0, |tag| {
+                let start = tag.boundaries(parser).0;
+                html[start..].find('>').map_or(start, |end| start + end + 1)
+            });
+        edits.push(Edit {
+            start: head,
+            end: head,
+            change: Change::Insert(format!("<base href=\"{}\" target=\"_blank\">", escape_attribute(base.as_str()))),
+        });
+
+        // Leave out anything inside what's already changed, e.g. an image in a frame's fallback
+        edits.sort_by_key(|edit| (edit.start, edit.end));
+        let mut changed_until = 0;
+        edits.retain(|edit| {
+            let keep = edit.start >= changed_until;
+            if keep {
+                changed_until = edit.end;
+            }
+            keep
+        });
+        Ok(Self { edits, base })
+    }
+
+    fn stylesheet_urls(&self) -> impl Iterator<Item = Url> + '_ {
+        self.edits.iter().filter_map(|edit| match &edit.change {
+            Change::Stylesheet { url, .. } => Some(url.clone()),
+            _ => None,
+        })
+    }
+
+    /// The images the page shows, and those its `<style>` elements use.
+    fn resource_urls(&self) -> Vec<Url> {
+        let mut urls = Vec::new();
+        for edit in &self.edits {
+            match &edit.change {
+                Change::Image { url, .. } => urls.push(url.clone()),
+                Change::Style { css, .. } => urls.extend(css_urls(css, &self.base)),
+                _ => {}
+            }
+        }
+        urls
+    }
+
+    /// The page with the changes made, given its stylesheets (already inlined) and other resources.
+    fn render(&self, html: &str, stylesheets: &HashMap<Url, String>, resources: &HashMap<Url, Resource>) -> String {
+        let mut snapshot = String::with_capacity(html.len());
+        let mut copied_until = 0;
+        for edit in &self.edits {
+            snapshot.push_str(&html[copied_until..edit.start]);
+            match &edit.change {
+                Change::Remove => {}
+                Change::Insert(text) => snapshot.push_str(text),
+                Change::Stylesheet { url, media } => {
+                    if let Some(css) = stylesheets.get(url) {
+                        match media {
+                            Some(media) => {
+                                snapshot.push_str("<style media=\"");
+                                snapshot.push_str(&escape_attribute(media));
+                                snapshot.push_str("\">");
+                            }
+                            None => snapshot.push_str("<style>"),
+                        }
+                        snapshot.push_str(&escape_style(css));
+                        snapshot.push_str("</style>");
+                    }
+                }
+                Change::Style { start_tag, css } => {
+                    snapshot.push_str(start_tag);
+                    snapshot.push_str(&escape_style(&inline_css(css, &self.base, resources)));
+                    snapshot.push_str("</style>");
+                }
+                Change::Image { start_tag, url } => {
+                    let src = resources.get(url).map_or_else(|| url.to_string(), Resource::data_uri);
+                    snapshot.push_str(start_tag);
+                    snapshot.push_str(" src=\"");
+                    snapshot.push_str(&escape_attribute(&src));
+                    snapshot.push_str("\">");
+                }
+            }
+            copied_until = edit.end;
+        }
+        snapshot.push_str(&html[copied_until..]);
+        snapshot
+    }
+}
+
+/// An attribute's value, with entities decoded.
+fn attribute(tag: &HTMLTag<'_>, name: &str) -> Option<String> {
+    let value = tag.attributes().get(name)??;
+    Some(decode_html_entities(&value.as_utf8_str()))
+}
+
+/// A tag's start, without the given attributes or the closing `>`.
+fn start_tag_without(tag: &HTMLTag<'_>, left_out: &[&str]) -> String {
+    let mut start_tag = format!("<{}", tag.name().as_utf8_str());
+    for (name, value) in tag.attributes().iter() {
+        if left_out.iter().any(|left_out| name.eq_ignore_ascii_case(left_out)) {
+            continue;
+        }
+        start_tag.push(' ');
+        start_tag.push_str(&name);
+        if let Some(value) = value {
+            start_tag.push_str("=\"");
+            start_tag.push_str(&escape_attribute(&value));
+            start_tag.push('"');
+        }
+    }
+    start_tag
+}
+
+/// A URL found in a page or stylesheet, made absolute and without its fragment. Only `http` and
+/// `https` URLs are fetched; inline `data:` ones are already fine.
+fn resolve(base: &Url, url: &str) -> Option<Url> {
+    let mut url = base.join(url.trim()).ok()?;
+    url.set_fragment(None);
+    matches!(url.scheme(), "http" | "https").then_some(url)
+}
+
+/// The images and fonts a stylesheet uses.
+fn css_urls(css: &str, base: &Url) -> Vec<Url> {
+    CSS_URL.captures_iter(css).filter_map(|url| resolve(base, css_url(&url))).collect()
+}
+
+/// The stylesheet with the images and fonts it uses inlined, where they were downloaded.
+fn inline_css(css: &str, base: &Url, resources: &HashMap<Url, Resource>) -> String {
+    CSS_URL
+        .replace_all(css, |url: &Captures<'_>| {
+            match resolve(base, css_url(url)).and_then(|resolved| resources.get(&resolved)) {
+                Some(resource) => format!("url(\"{}\")", resource.data_uri()),
+                None => url[0].to_string(),
+            }
+        })
+        .into_owned()
+}
+
+fn css_url<'a>(url: &Captures<'a>) -> &'a str {
+    url.get(1)
+        .or_else(|| url.get(2))
+        .or_else(|| url.get(3))
+        .map_or("", |url| url.as_str())
+}
+
+fn escape_attribute(value: &str) -> String {
+    value.replace('"', "&quot;")
+}
+
+/// Keeps a stylesheet from ending its `<style>` element early.
+fn escape_style(css: &str) -> String {
+    css.replace("</", "<\\/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resource(content_type: &str, data: &[u8]) -> Resource {
+        Resource {
+            content_type: content_type.to_string(),
+            data: data.to_vec(),
+        }
+    }
+
+    fn snapshot(html: &str, stylesheets: &[(&str, &str)], resources: &[(&str, Resource)]) -> String {
+        let html = SCRIPT.replace_all(html, "").into_owned();
+        let plan = Plan::new(&html, &Url::parse("https://example.com/posts/1").unwrap()).unwrap();
+        let stylesheets = stylesheets
+            .iter()
+            .map(|(url, css)| (Url::parse(url).unwrap(), (*css).to_string()))
+            .collect();
+        let resources = resources
+            .iter()
+            .map(|(url, resource)| (Url::parse(url).unwrap(), resource.clone()))
+            .collect();
+        plan.render(&html, &stylesheets, &resources)
+    }
+
+    #[test]
+    fn test_snapshot_finds_resources() {
+        let html = r#"<html><head><link rel="stylesheet" href="/main.css"><link rel="icon" href="/favicon.ico">
+            <style>body { background: url('bg.png') }</style></head>
+            <body><img src="a.png"><img src="data:image/gif;base64,R0lGOD" data-src="https://cdn.example.com/b.jpg#x"></body></html>"#;
+        let plan = Plan::new(html, &Url::parse("https://example.com/posts/1").unwrap()).unwrap();
+
+        assert_eq!(
+            plan.stylesheet_urls().map(String::from).collect::<Vec<_>>(),
+            ["https://example.com/main.css"]
+        );
+        assert_eq!(
+            plan.resource_urls().into_iter().map(String::from).collect::<Vec<_>>(),
+            [
+                "https://example.com/posts/bg.png",
+                "https://example.com/posts/a.png",
+                "https://cdn.example.com/b.jpg"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_snapshot_inlines_resources() {
+        let html = r#"<html><head><title>Post</title><link rel="stylesheet" href="/main.css" media="screen"></head>
+            <body><img alt="A &quot;picture&quot;" src="/a.png" srcset="/a-2x.png 2x"><img src="/missing.png"></body></html>"#;
+        let snapshot = snapshot(
+            html,
+            &[("https://example.com/main.css", "h1 { color: red }")],
+            &[("https://example.com/a.png", resource("image/png", b"png"))],
+        );
+
+        assert!(snapshot.starts_with(r#"<html><head><base href="https://example.com/posts/1" target="_blank"><title>Post</title>"#));
+        assert!(snapshot.contains(r#"<style media="screen">h1 { color: red }</style>"#));
+        assert!(snapshot.contains(r#"<img alt="A &quot;picture&quot;" src="data:image/png;base64,cG5n">"#));
+        assert!(snapshot.contains(r#"<img src="https://example.com/missing.png">"#), "{snapshot}");
+        assert!(!snapshot.contains("srcset"));
+    }
+
+    #[test]
+    fn test_snapshot_inlines_css_urls() {
+        let base = Url::parse("https://example.com/css/main.css").unwrap();
+        let css = r#"@font-face { src: url("../fonts/a.woff2") } body { background: url(bg.png), url('data:image/gif;base64,R0lGOD') }"#;
+        assert_eq!(
+            css_urls(css, &base).into_iter().map(String::from).collect::<Vec<_>>(),
+            ["https://example.com/fonts/a.woff2", "https://example.com/css/bg.png"]
+        );
+
+        let resources = HashMap::from([(
+            Url::parse("https://example.com/fonts/a.woff2").unwrap(),
+            resource("font/woff2", b"font"),
+        )]);
+        assert_eq!(
+            inline_css(css, &base, &resources),
+            r#"@font-face { src: url("data:font/woff2;base64,Zm9udA==") } body { background: url(bg.png), url('data:image/gif;base64,R0lGOD') }"#
+        );
+    }
+
+    #[test]
+    fn test_snapshot_leaves_out_active_content() {
+        let html = r#"<html><head><meta http-equiv="refresh" content="0; url=/login"><base href="/docs/">
+            <script>document.write("<img src=x></div>")</script></head>
+            <body><iframe src="/ad"><img src="fallback.png"></iframe><p>Text</p><style>p { color: red }</style>
+            <object data="movie.swf"></object></body></html>"#;
+        let snapshot = snapshot(html, &[], &[]);
+
+        assert!(snapshot.contains(r#"<base href="https://example.com/docs/" target="_blank">"#));
+        for left_out in [
+            "<script",
+            "document.write",
+            "refresh",
+            "<iframe",
+            "fallback.png",
+            "<object",
+            "/docs/\">",
+        ] {
+            assert!(!snapshot.contains(left_out), "{left_out} is left out of {snapshot}");
+        }
+        assert!(snapshot.contains("<p>Text</p><style>p { color: red }</style>"));
+    }
+}
@@ -0,0 +1,219 @@
+//! Personal tag suggestions.
+//!
+//! Suggests tags for a new bookmark based on the user's own corpus rather than generic keyword
+//! extraction. Candidate tags are scored from:
+//! - Bookmarks saved from the same domain
+//! - Bookmarks with similar titles (trigram similarity)
+//! - Title words that exactly match an existing tag
+//! - Co-occurrence with the strongest candidates
+
+use std::collections::{HashMap, HashSet};
+
+/// Minimum trigram similarity for a saved title to contribute its tags.
+const MIN_TITLE_SIMILARITY: f64 = 0.2;
+/// Weight applied to the tags of bookmarks from the same domain.
+const DOMAIN_WEIGHT: f64 = 1.0;
+/// Weight applied when a title word matches an existing tag name.
+const KEYWORD_WEIGHT: f64 = 1.0;
+/// Weight applied to tags co-occurring with the top candidates.
+const COOCCURRENCE_WEIGHT: f64 = 0.25;
+/// Number of top candidates used as seeds for co-occurrence scoring.
+const COOCCURRENCE_SEEDS: usize = 3;
+
+/// A previously saved bookmark used as input for suggestions.
+#[derive(Clone, Debug)]
+pub struct CorpusEntry {
+    pub url: String,
+    pub title: String,
+    pub tags: Vec<String>,
+}
+
+/// A suggested tag with its relative score.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScoredTag {
+    pub name: String,
+    pub score: f64,
+}
+
+/// Suggests tags for a bookmark with the given URL and/or title, best matches first.
+///
+/// Tags listed in `exclude` (e.g. ones the user already entered) are never suggested.
+pub fn suggest_tags(corpus: &[CorpusEntry], url: Option<&str>, title: Option<&str>, exclude: &[String], limit: usize) -> Vec<ScoredTag> {
+    let mut scores: HashMap<&str, f64> = HashMap::new();
+
+    // Same domain: spread the weight over all bookmarks from that domain
+    if let Some(domain) = url.and_then(domain_of) {
+        let same_domain: Vec<&CorpusEntry> = corpus
+            .iter()
+            .filter(|entry| domain_of(&entry.url).as_deref() == Some(&domain))
+            .collect();
+        #[allow(clippy::cast_precision_loss)] // corpus sizes are nowhere near 2^52
+        let per_bookmark = DOMAIN_WEIGHT / same_domain.len().max(1) as f64;
+        for entry in same_domain {
+            for tag in &entry.tags {
+                *scores.entry(tag).or_default() += per_bookmark;
+            }
+        }
+    }
+
+    if let Some(title) = title.filter(|t| !t.trim().is_empty()) {
+        // Similar titles
+        let title_grams = trigrams(title);
+        for entry in corpus {
+            let similarity = jaccard(&title_grams, &trigrams(&entry.title));
+            if similarity >= MIN_TITLE_SIMILARITY {
+                for tag in &entry.tags {
+                    *scores.entry(tag).or_default() += similarity;
+                }
+            }
+        }
+
+        // Title words which are already tags in the user's taxonomy
+        let words: HashSet<String> = words(title).collect();
+        let known_tags: HashSet<&str> = corpus.iter().flat_map(|entry| entry.tags.iter().map(String::as_str)).collect();
+        for tag in known_tags {
+            if words.contains(tag) {
+                *scores.entry(tag).or_default() += KEYWORD_WEIGHT;
+            }
+        }
+    }
+
+    // Tags which tend to appear alongside the strongest candidates
+    let mut seeds: Vec<(&str, f64)> = scores.iter().map(|(tag, score)| (*tag, *score)).collect();
+    seeds.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    seeds.truncate(COOCCURRENCE_SEEDS);
+    for (seed, _) in seeds {
+        let with_seed: Vec<&CorpusEntry> = corpus.iter().filter(|entry| entry.tags.iter().any(|t| t == seed)).collect();
+        #[allow(clippy::cast_precision_loss)] // corpus sizes are nowhere near 2^52
+        let per_bookmark = COOCCURRENCE_WEIGHT / with_seed.len().max(1) as f64;
+        for entry in with_seed {
+            for tag in entry.tags.iter().filter(|t| *t != seed) {
+                *scores.entry(tag).or_default() += per_bookmark;
+            }
+        }
+    }
+
+    let mut result: Vec<ScoredTag> = scores
+        .into_iter()
+        .filter(|(name, _)| !exclude.iter().any(|e| e == name))
+        .map(|(name, score)| ScoredTag {
+            name: name.to_string(),
+            score,
+        })
+        .collect();
+    result.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.name.cmp(&b.name)));
+    result.truncate(limit);
+    result
+}
+
+/// Extracts the host of a URL, lowercased and without a leading `www.`.
+pub fn domain_of(url: &str) -> Option<String> {
+    let parsed = reqwest::Url::parse(url)
+        .or_else(|_| reqwest::Url::parse(&format!("http://{url}")))
+        .ok()?;
+    let host = parsed.host_str()?.to_lowercase();
+    Some(host.strip_prefix("www.").map(str::to_string).unwrap_or(host))
+}
+
+/// Lowercased alphanumeric words (hyphens kept, so `web-dev` stays one word).
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric() && c != '-')
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+}
+
+/// Builds the set of character trigrams for each word, padded like `pg_trgm` does.
+fn trigrams(text: &str) -> HashSet<[char; 3]> {
+    let mut grams = HashSet::new();
+    for word in words(text) {
+        let padded: Vec<char> = "  ".chars().chain(word.chars()).chain(" ".chars()).collect();
+        for window in padded.windows(3) {
+            grams.insert([window[0], window[1], window[2]]);
+        }
+    }
+    grams
+}
+
+/// Jaccard similarity of two trigram sets, from 0.0 (disjoint) to 1.0 (identical).
+fn jaccard(a: &HashSet<[char; 3]>, b: &HashSet<[char; 3]>) -> f64 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    #[allow(clippy::cast_precision_loss)] // trigram counts are small
+    let similarity = a.intersection(b).count() as f64 / union as f64;
+    similarity
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(url: &str, title: &str, tags: &[&str]) -> CorpusEntry {
+        CorpusEntry {
+            url: url.to_string(),
+            title: title.to_string(),
+            tags: tags.iter().map(|t| (*t).to_string()).collect(),
+        }
+    }
+
+    fn names(tags: &[ScoredTag]) -> Vec<&str> {
+        tags.iter().map(|t| t.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_domain_of() {
+        assert_eq!(domain_of("https://www.GitHub.com/tokio-rs/axum").as_deref(), Some("github.com"));
+        assert_eq!(domain_of("docs.rs/askama").as_deref(), Some("docs.rs"));
+        assert_eq!(domain_of(""), None);
+    }
+
+    #[test]
+    fn test_trigram_similarity() {
+        let a = trigrams("Axum Web Framework");
+        assert!((jaccard(&a, &a) - 1.0).abs() < f64::EPSILON);
+        assert!(jaccard(&a, &trigrams("Axum web framework for Rust")) > 0.5);
+        assert!(jaccard(&a, &trigrams("Baking sourdough bread")) < 0.1);
+        assert!(jaccard(&HashSet::new(), &HashSet::new()).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_same_domain_suggestions() {
+        let corpus = vec![
+            entry("https://github.com/tokio-rs/axum", "Axum", &["rust", "github"]),
+            entry("https://github.com/launchbadge/sqlx", "SQLx", &["rust", "database", "github"]),
+            entry("https://simplecss.org", "Simple.css", &["css"]),
+        ];
+        let suggested = suggest_tags(&corpus, Some("https://github.com/foo/bar"), None, &[], 10);
+        let names = names(&suggested);
+        assert_eq!(names[..2], ["github", "rust"]);
+        assert!(!names.contains(&"css"));
+    }
+
+    #[test]
+    fn test_similar_title_and_keyword_suggestions() {
+        let corpus = vec![
+            entry("https://a.example", "Async Rust in practice", &["rust", "async"]),
+            entry("https://b.example", "Gardening for beginners", &["garden"]),
+        ];
+        let suggested = suggest_tags(&corpus, None, Some("Practical async Rust"), &[], 10);
+        assert_eq!(names(&suggested)[..2], ["async", "rust"]);
+        assert!(!names(&suggested).contains(&"garden"));
+    }
+
+    #[test]
+    fn test_cooccurrence_and_exclusions() {
+        let corpus = vec![
+            entry("https://a.example", "One", &["rust", "programming"]),
+            entry("https://b.example", "Two", &["rust", "programming"]),
+        ];
+        let suggested = suggest_tags(&corpus, None, Some("rust"), &["rust".to_string()], 10);
+        assert_eq!(names(&suggested), ["programming"]);
+    }
+
+    #[test]
+    fn test_no_input_no_suggestions() {
+        let corpus = vec![entry("https://a.example", "One", &["rust"])];
+        assert!(suggest_tags(&corpus, None, None, &[], 10).is_empty());
+    }
+}
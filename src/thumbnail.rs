@@ -0,0 +1,141 @@
+//! Thumbnails of the preview images pages name for link previews (`og:image`).
+//!
+//! When a bookmark is saved from the add form and fetching its title found a preview image, the
+//! image is downloaded in the background, scaled down to fit [`MAX_WIDTH`]×[`MAX_HEIGHT`] and
+//! stored with the bookmark as a JPEG, for the grid view of the bookmark list. Browsers only ever
+//! load the stored copy, never the image on the saved site.
+
+use std::{io::Cursor, sync::Arc};
+
+use anyhow::{Context as _, Result, bail};
+use image::{ImageReader, Limits, codecs::jpeg::JpegEncoder};
+use reqwest::Client;
+use tracing::{debug, error};
+use uuid::Uuid;
+
+use crate::{
+    AppState,
+    config::FetchConfig,
+    db::{self, bookmarks::BookmarkId},
+    fetch::{check_destination, is_fetch_allowed, read_body},
+};
+
+/// Largest thumbnail width, in pixels.
+pub const MAX_WIDTH: u32 = 400;
+/// Largest thumbnail height, in pixels.
+pub const MAX_HEIGHT: u32 = 300;
+/// Downloads larger than this are abandoned.
+const MAX_DOWNLOAD_BYTES: usize = 5 * 1024 * 1024;
+/// Images larger than this either way are refused before decoding, so a small file can't claim a
+/// huge canvas.
+const MAX_SOURCE_SIZE: u32 = 8192;
+/// Images smaller than this either way are icons or tracking pixels rather than previews.
+const MIN_SOURCE_SIZE: u32 = 32;
+const JPEG_QUALITY: u8 = 80;
+
+/// Downloads a preview image and saves a thumbnail of it for the bookmark, unless the user opted
+/// out of fetching. Failures are only logged, as nobody is waiting on this.
+pub async fn save_thumbnail(state: Arc<AppState>, user_id: Uuid, bookmark_id: BookmarkId, image_url: String) {
+    match is_fetch_allowed(&state.fetch_config, &state.db, user_id).await {
+        Ok(true) => {}
+        Ok(false) => return,
+        Err(err) => {
+            error!(?err, "🚨 Could not load fetch setting, not saving thumbnail.");
+            return;
+        }
+    }
+    let thumbnail = match download_thumbnail(&state.http_client, &state.fetch_config, &image_url).await {
+        Ok(thumbnail) => thumbnail,
+        Err(err) => {
+            debug!(%bookmark_id, image_url, %err, "🖼️ Could not make a thumbnail.");
+            return;
+        }
+    };
+    if let Err(err) = db::thumbnails::set_thumbnail(&state.db, bookmark_id, &thumbnail, &image_url).await {
+        error!(%bookmark_id, ?err, "🚨 Could not save thumbnail.");
+    }
+}
+
+/// Downloads an image and makes a thumbnail of it.
+///
+/// # Errors
+///
+/// Returns an error if the image may not be fetched, the download fails or is too large, or the
+/// image can't be read.
+#[tracing::instrument(level = "trace", name = "http.thumbnail", skip_all, fields(url = image_url))]
+pub async fn download_thumbnail(client: &Client, config: &FetchConfig, image_url: &str) -> Result<Vec<u8>> {
+    check_destination(config, image_url)?;
+    let response = client.get(image_url).send().await?.error_for_status()?;
+    let data = read_body(response, MAX_DOWNLOAD_BYTES).await?;
+    tokio::task::spawn_blocking(move || make_thumbnail(&data)).await?
+}
+
+/// Scales an image in any of the common web formats down to fit [`MAX_WIDTH`]×[`MAX_HEIGHT`],
+/// keeping its proportions, and encodes it as a JPEG. Smaller images keep their size.
+///
+/// # Errors
+///
+/// Returns an error if the image can't be decoded, or is too small or too large.
+pub fn make_thumbnail(data: &[u8]) -> Result<Vec<u8>> {
+    let mut reader = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .context("Could not read image")?;
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_SOURCE_SIZE);
+    limits.max_image_height = Some(MAX_SOURCE_SIZE);
+    reader.limits(limits);
+    let image = reader.decode().context("Could not decode image")?;
+
+    if image.width() < MIN_SOURCE_SIZE || image.height() < MIN_SOURCE_SIZE {
+        bail!("Image is too small for a preview ({}x{})", image.width(), image.height());
+    }
+    let image = if image.width() > MAX_WIDTH || image.height() > MAX_HEIGHT {
+        image.thumbnail(MAX_WIDTH, MAX_HEIGHT)
+    } else {
+        image
+    };
+
+    let mut jpeg = Vec::new();
+    JpegEncoder::new_with_quality(&mut jpeg, JPEG_QUALITY)
+        .encode_image(&image.into_rgb8())
+        .context("Could not encode thumbnail")?;
+    Ok(jpeg)
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{ImageFormat, RgbaImage};
+
+    use super::*;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut data = Vec::new();
+        RgbaImage::new(width, height)
+            .write_to(&mut Cursor::new(&mut data), ImageFormat::Png)
+            .unwrap();
+        data
+    }
+
+    fn dimensions(jpeg: &[u8]) -> (u32, u32) {
+        let image = image::load_from_memory_with_format(jpeg, ImageFormat::Jpeg).unwrap();
+        (image.width(), image.height())
+    }
+
+    #[test]
+    fn test_thumbnails_fit_the_bounds() {
+        assert_eq!(dimensions(&make_thumbnail(&png(1200, 630)).unwrap()), (400, 210));
+        assert_eq!(dimensions(&make_thumbnail(&png(600, 900)).unwrap()), (200, 300));
+    }
+
+    #[test]
+    fn test_small_images_keep_their_size() {
+        assert_eq!(dimensions(&make_thumbnail(&png(120, 80)).unwrap()), (120, 80));
+        assert!(make_thumbnail(&png(16, 16)).is_err(), "icons aren't previews");
+    }
+
+    #[test]
+    fn test_unreadable_images_are_refused() {
+        assert!(make_thumbnail(b"<html>not an image</html>").is_err());
+        assert!(make_thumbnail(&png(MAX_SOURCE_SIZE + 1, 40)).is_err());
+    }
+}
@@ -0,0 +1,233 @@
+//! Time-based one-time passwords (TOTP, RFC 6238), the second step of signing in for users who set
+//! up two-factor authentication.
+//!
+//! Codes are the 6-digit, 30-second, HMAC-SHA1 kind every authenticator app understands. The shared
+//! secret is handed to the app as an `otpauth://` provisioning URI, shown as a QR code on the
+//! settings page. Recovery codes sign in once each when the app is lost; like API tokens, only
+//! keyed hashes of them are stored (see [`crate::encryption::EncryptionProvider::hash_api_token`]).
+
+use aes_gcm::aead::{OsRng, rand_core::RngCore as _};
+use anyhow::Result;
+use hmac::{Hmac, Mac as _};
+use qrcode::{QrCode, render::svg};
+use sha1::Sha1;
+
+/// Shown as the account's issuer in authenticator apps.
+const ISSUER: &str = "PagePouch";
+/// Length of a new secret, in bytes: 160 bits, as RFC 4226 recommends.
+const SECRET_LENGTH: usize = 20;
+/// Seconds each code works for.
+const STEP_SECONDS: i64 = 30;
+/// Digits in a code.
+const DIGITS: u32 = 6;
+/// Codes this many steps before or after the current one are accepted too, for clocks that are a
+/// little off.
+const ALLOWED_DRIFT: i64 = 1;
+/// How many recovery codes a user gets at a time.
+pub const RECOVERY_CODE_COUNT: usize = 10;
+/// Characters in a recovery code, not counting the dash in its middle.
+const RECOVERY_CODE_LENGTH: usize = 10;
+/// Alphabet of base32 (RFC 4648), which secrets are written in; also used for recovery codes, as it
+/// has no characters that are easily mistaken for each other.
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// Generates a new random secret.
+pub fn generate_secret() -> Vec<u8> {
+    let mut secret = vec![0u8; SECRET_LENGTH];
+    OsRng.fill_bytes(&mut secret);
+    secret
+}
+
+/// A secret as base32 without padding, as authenticator apps want it typed in.
+#[must_use]
+pub fn encode_secret(secret: &[u8]) -> String {
+    let mut encoded = String::with_capacity(secret.len().div_ceil(5) * 8);
+    for chunk in secret.chunks(5) {
+        let mut buffer = [0u8; 5];
+        buffer[..chunk.len()].copy_from_slice(chunk);
+        let bits = buffer.iter().fold(0u64, |bits, &byte| bits << 8 | u64::from(byte));
+        let chars = (chunk.len() * 8).div_ceil(5);
+        for i in 0..chars {
+            let index = (bits >> (35 - i * 5)) & 0x1f;
+            encoded.push(char::from(BASE32_ALPHABET[usize::try_from(index).unwrap_or_default()]));
+        }
+    }
+    encoded
+}
+
+/// The `otpauth://` URI that sets up an authenticator app for `username` with `secret`.
+#[must_use]
+pub fn provisioning_uri(username: &str, secret: &[u8]) -> String {
+    format!(
+        "otpauth://totp/{ISSUER}:{}?secret={}&issuer={ISSUER}&algorithm=SHA1&digits={DIGITS}&period={STEP_SECONDS}",
+        percent_encode(username),
+        encode_secret(secret)
+    )
+}
+
+/// Percent-encodes everything but unreserved characters (RFC 3986), for the account name in a
+/// provisioning URI.
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| {
+            if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+                char::from(byte).to_string()
+            } else {
+                format!("%{byte:02X}")
+            }
+        })
+        .collect()
+}
+
+/// A provisioning URI as an SVG QR code, to scan with an authenticator app.
+///
+/// # Errors
+///
+/// Returns an error if the URI is too long for a QR code.
+pub fn qr_code_svg(uri: &str) -> Result<String> {
+    let code = QrCode::new(uri.as_bytes())?;
+    Ok(code
+        .render::<svg::Color<'_>>()
+        .min_dimensions(200, 200)
+        .dark_color(svg::Color("#000000"))
+        .light_color(svg::Color("#ffffff"))
+        .build())
+}
+
+/// The time step a UNIX timestamp falls in.
+#[must_use]
+pub fn time_step(timestamp: i64) -> i64 {
+    timestamp.div_euclid(STEP_SECONDS)
+}
+
+/// The code for `secret` in time step `step` (HOTP, RFC 4226, with the step as counter).
+fn code_at(secret: &[u8], step: i64) -> u32 {
+    let mut hmac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC takes keys of any length");
+    hmac.update(&step.to_be_bytes());
+    let hash = hmac.finalize().into_bytes();
+    let offset = usize::from(hash[hash.len() - 1] & 0x0f);
+    let truncated = u32::from_be_bytes([hash[offset], hash[offset + 1], hash[offset + 2], hash[offset + 3]]) & 0x7fff_ffff;
+    truncated % 10u32.pow(DIGITS)
+}
+
+/// Checks a code typed in at `timestamp` against `secret`, returning the time step it's for.
+///
+/// Codes of steps up to and including `last_used_step` are turned away, so a code can't be used
+/// twice, e.g. by someone who saw it typed in. Spaces in the code are ignored.
+#[must_use]
+pub fn verify_code(secret: &[u8], code: &str, timestamp: i64, last_used_step: Option<i64>) -> Option<i64> {
+    let code: String = code.chars().filter(|c| !c.is_whitespace()).collect();
+    if code.len() != DIGITS as usize || !code.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    let code: u32 = code.parse().ok()?;
+    let current = time_step(timestamp);
+    (current - ALLOWED_DRIFT..=current + ALLOWED_DRIFT)
+        .filter(|&step| last_used_step.is_none_or(|last| step > last))
+        .find(|&step| code_at(secret, step) == code)
+}
+
+/// Generates a new set of recovery codes, e.g. `ABCDE-FGH23`.
+pub fn generate_recovery_codes() -> Vec<String> {
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let mut code = String::with_capacity(RECOVERY_CODE_LENGTH + 1);
+            for i in 0..RECOVERY_CODE_LENGTH {
+                if i == RECOVERY_CODE_LENGTH / 2 {
+                    code.push('-');
+                }
+                let index = usize::try_from(OsRng.next_u32() % 32).unwrap_or_default();
+                code.push(char::from(BASE32_ALPHABET[index]));
+            }
+            code
+        })
+        .collect()
+}
+
+/// A recovery code as typed in, the way it's hashed: upper case, without dashes or spaces.
+#[must_use]
+pub fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The secret of the test vectors in RFC 6238, appendix B.
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn test_codes_match_rfc_6238() {
+        // The RFC's vectors have 8 digits; ours are their last 6
+        for (timestamp, expected) in [
+            (59, 287_082),
+            (1_111_111_109, 81_804),
+            (1_111_111_111, 50_471),
+            (1_234_567_890, 5_924),
+            (2_000_000_000, 279_037),
+        ] {
+            assert_eq!(code_at(RFC_SECRET, time_step(timestamp)), expected, "at {timestamp}");
+        }
+    }
+
+    #[test]
+    fn test_verify_code() {
+        let now = 1_111_111_111;
+        let step = time_step(now);
+        assert_eq!(verify_code(RFC_SECRET, "050471", now, None), Some(step));
+        assert_eq!(verify_code(RFC_SECRET, " 050 471 ", now, None), Some(step));
+        // A step early or late is fine, further off isn't
+        assert_eq!(verify_code(RFC_SECRET, "050471", now + 30, None), Some(step));
+        assert_eq!(verify_code(RFC_SECRET, "050471", now - 30, None), Some(step));
+        assert_eq!(verify_code(RFC_SECRET, "050471", now + 90, None), None);
+        // Used codes can't be used again
+        assert_eq!(verify_code(RFC_SECRET, "050471", now, Some(step)), None);
+        assert_eq!(verify_code(RFC_SECRET, "050471", now, Some(step - 1)), Some(step));
+        // Not a code
+        assert_eq!(verify_code(RFC_SECRET, "50471", now, None), None);
+        assert_eq!(verify_code(RFC_SECRET, "05047a", now, None), None);
+        assert_eq!(verify_code(RFC_SECRET, "+50471", now, None), None);
+    }
+
+    #[test]
+    fn test_encode_secret() {
+        // RFC 4648 test vectors, without padding
+        assert_eq!(encode_secret(b""), "");
+        assert_eq!(encode_secret(b"f"), "MY");
+        assert_eq!(encode_secret(b"foob"), "MZXW6YQ");
+        assert_eq!(encode_secret(b"foobar"), "MZXW6YTBOI");
+        assert_eq!(encode_secret(RFC_SECRET), "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        assert_eq!(encode_secret(&generate_secret()).len(), 32);
+    }
+
+    #[test]
+    fn test_provisioning_uri() {
+        assert_eq!(
+            provisioning_uri("ann lee@home", RFC_SECRET),
+            "otpauth://totp/PagePouch:ann%20lee%40home?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&issuer=PagePouch&algorithm=SHA1&digits=6&period=30"
+        );
+        assert!(
+            qr_code_svg(&provisioning_uri("ann", &generate_secret()))
+                .unwrap()
+                .starts_with("<?xml")
+        );
+    }
+
+    #[test]
+    fn test_recovery_codes() {
+        let codes = generate_recovery_codes();
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+        for code in &codes {
+            assert_eq!(code.len(), RECOVERY_CODE_LENGTH + 1);
+            assert_eq!(code.chars().nth(5), Some('-'));
+            assert_eq!(normalize_recovery_code(code).len(), RECOVERY_CODE_LENGTH);
+        }
+        assert_ne!(codes[0], codes[1]);
+        assert_eq!(normalize_recovery_code(" abcde-fgh23 "), "ABCDEFGH23");
+    }
+}
@@ -1,3 +1,11 @@
+//! Logging and tracing.
+//!
+//! Logs go to stdout. With the `otel` feature and an OTLP endpoint configured, spans are also
+//! exported to an OpenTelemetry collector: one per request from [`create_filtered_trace_layer`],
+//! with the database queries (see [`crate::db::timing::QueryTimer`]) and outbound HTTP requests
+//! made for it nested inside. Those inner spans are at trace level, so they cost nothing unless
+//! they're exported.
+
 use std::time::Duration;
 
 use axum::{
@@ -12,6 +20,112 @@ use tower_http::{
 };
 use tracing::Span;
 
+/// Keeps exporting traces while alive, and sends those not yet exported when dropped.
+#[must_use = "traces are only exported while the guard is alive"]
+pub(crate) struct TracingGuard {
+    #[cfg(feature = "otel")]
+    provider: Option<opentelemetry_sdk::trace::SdkTracerProvider>,
+}
+
+impl Drop for TracingGuard {
+    fn drop(&mut self) {
+        #[cfg(feature = "otel")]
+        if let Some(Err(e)) = self.provider.take().map(|provider| provider.shutdown()) {
+            eprintln!("🔭 Could not export the remaining traces: {e}");
+        }
+    }
+}
+
+/// Initializes the tracing subscriber, exporting spans to `otlp_endpoint` if one is given.
+///
+/// # Errors
+///
+/// Returns an error if the log filters can't be parsed, the exporter can't be set up, or an
+/// endpoint is given to a build without the `otel` feature.
+pub(crate) fn init_tracing(otlp_endpoint: Option<&str>) -> anyhow::Result<TracingGuard> {
+    use tracing::level_filters::LevelFilter;
+    use tracing_subscriber::{EnvFilter, Layer as _, fmt, layer::SubscriberExt as _, util::SubscriberInitExt as _};
+
+    let app_name = env!("CARGO_CRATE_NAME");
+
+    // Treat external crates differently because we almost never care about line numbers and internal module names there.
+    // By default, show tower_http at debug, other external crates at warn, and nothing from ours for this formatter,
+    // but, still respect RUST_LOG env var (tho still filter out ours there, too).
+    let default_external_filter = "warn,tower_http=debug".into();
+    let external_filter = EnvFilter::try_from_default_env()
+        .unwrap_or(default_external_filter)
+        .add_directive(format!("{app_name}=off").parse()?);
+    let external_crates_layer = fmt::layer()
+        .compact()
+        .with_file(false)
+        .with_line_number(false)
+        .with_target(false)
+        .with_filter(external_filter);
+
+    // Now, config for just our logs, where we probably care about things like files & line numbers
+    let our_app_only_filter = EnvFilter::builder()
+        .from_env_lossy()
+        .add_directive(LevelFilter::OFF.into())
+        .add_directive(format!("{app_name}=debug").parse()?);
+    let our_app_layer = fmt::layer()
+        .compact()
+        .with_file(false)
+        .with_line_number(false)
+        .with_target(false)
+        .with_filter(our_app_only_filter);
+
+    let registry = tracing_subscriber::registry().with(external_crates_layer).with(our_app_layer);
+
+    #[cfg(feature = "otel")]
+    {
+        let (otlp_layer, provider) = otlp_endpoint.map(|endpoint| otlp_layer(endpoint, app_name)).transpose()?.unzip();
+        registry.with(otlp_layer).init();
+        Ok(TracingGuard { provider })
+    }
+
+    #[cfg(not(feature = "otel"))]
+    {
+        if otlp_endpoint.is_some() {
+            anyhow::bail!("🔥 An OTLP endpoint is set, but this build has no OpenTelemetry support. Build with `--features otel`.");
+        }
+        registry.init();
+        Ok(TracingGuard {})
+    }
+}
+
+/// A layer exporting our spans and the request spans to the collector at `endpoint`, with the
+/// provider to shut down once done.
+#[cfg(feature = "otel")]
+fn otlp_layer<S>(
+    endpoint: &str,
+    app_name: &'static str,
+) -> anyhow::Result<(impl tracing_subscriber::Layer<S>, opentelemetry_sdk::trace::SdkTracerProvider)>
+where
+    S: tracing::Subscriber + for<'span> tracing_subscriber::registry::LookupSpan<'span>,
+{
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry_otlp::{SpanExporter, WithExportConfig as _};
+    use opentelemetry_sdk::{Resource, trace::SdkTracerProvider};
+    use tracing_subscriber::{EnvFilter, Layer as _};
+
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(format!("{endpoint}/v1/traces"))
+        .build()?;
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(Resource::builder().with_service_name("pagepouch").build())
+        .build();
+
+    // Request spans are at debug level, and the ones for queries and outbound requests at trace
+    let filter = EnvFilter::try_new(format!("tower_http=debug,{app_name}=trace"))?;
+    let layer = tracing_opentelemetry::layer()
+        .with_tracer(provider.tracer(app_name))
+        .with_filter(filter);
+
+    Ok((layer, provider))
+}
+
 pub(super) fn create_filtered_trace_layer()
 -> TraceLayer<SharedClassifier<FilteredStatusAsFailures>, impl Fn(&Request<Body>) -> Span + Clone, FilterOnRequest, FilterOnResponse> {
     TraceLayer::new(
@@ -0,0 +1,162 @@
+//! Optional translation of bookmark titles and descriptions.
+//!
+//! When a translation endpoint is configured, bookmarks whose content language differs from a
+//! user's preferred language can be translated on demand. Results are cached per bookmark, so each
+//! one is only sent out once per language.
+//!
+//! Translation goes through the [`Translator`] trait so other services can be plugged in; the
+//! built-in [`LibreTranslate`] speaks the `LibreTranslate` API, which self-hosted instances and
+//! several hosted services offer.
+
+use std::{future::Future, pin::Pin, sync::Arc, time::Duration};
+
+use anyhow::{Context as _, Result, bail};
+use reqwest::{Client, Url, header::CONTENT_TYPE};
+use serde::Deserialize;
+use serde_json::json;
+use tracing::{Instrument as _, trace_span};
+
+/// Maximum time to wait for a translation.
+const TRANSLATE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Future returned by [`Translator::translate`].
+pub type TranslateFuture<'a> = Pin<Box<dyn Future<Output = Result<Vec<String>>> + Send + 'a>>;
+
+/// A translation service.
+pub trait Translator: Send + Sync {
+    /// Translates each of `texts` into the `target` language, returning them in the same order.
+    /// `source` is the language they're in, if known.
+    fn translate<'a>(&'a self, texts: &'a [String], source: Option<&'a str>, target: &'a str) -> TranslateFuture<'a>;
+}
+
+/// Translates through a `LibreTranslate`-compatible `/translate` endpoint.
+pub struct LibreTranslate {
+    client: Client,
+    translate_url: Url,
+    api_key: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct LibreTranslateResponse {
+    #[serde(rename = "translatedText")]
+    translated_text: Vec<String>,
+}
+
+impl LibreTranslate {
+    /// Creates a translator for the service at `base_url`, e.g. `https://translate.example.com/`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `base_url` is not a valid http(s) URL.
+    pub fn new(base_url: &str, api_key: Option<String>, client: Client) -> Result<Self> {
+        let base_url = Url::parse(base_url).with_context(|| "🔥 Translation URL is not a valid URL")?;
+        if !matches!(base_url.scheme(), "http" | "https") {
+            bail!("🔥 Translation URL must be an http or https URL");
+        }
+        let translate_url = base_url
+            .join(&format!("{}/translate", base_url.path().trim_end_matches('/')))
+            .with_context(|| "🔥 Could not build the translation endpoint URL")?;
+
+        Ok(Self {
+            client,
+            translate_url,
+            api_key,
+        })
+    }
+}
+
+impl Translator for LibreTranslate {
+    fn translate<'a>(&'a self, texts: &'a [String], source: Option<&'a str>, target: &'a str) -> TranslateFuture<'a> {
+        let span = trace_span!("http.translate", target, texts = texts.len());
+        Box::pin(
+            async move {
+                let body = json!({
+                    "q": texts,
+                    "source": source.unwrap_or("auto"),
+                    "target": target,
+                    "format": "text",
+                    "api_key": self.api_key,
+                });
+                let response = self
+                    .client
+                    .post(self.translate_url.clone())
+                    .header(CONTENT_TYPE, "application/json")
+                    .body(body.to_string())
+                    .send()
+                    .await?;
+                if !response.status().is_success() {
+                    bail!("Translation service returned {}", response.status());
+                }
+
+                let response: LibreTranslateResponse =
+                    serde_json::from_slice(&response.bytes().await?).context("Unexpected response from translation service")?;
+                if response.translated_text.len() != texts.len() {
+                    bail!(
+                        "Translation service returned {} texts for {}",
+                        response.translated_text.len(),
+                        texts.len()
+                    );
+                }
+                Ok(response.translated_text)
+            }
+            .instrument(span),
+        )
+    }
+}
+
+/// Builds the translator for the configured endpoint, if any.
+///
+/// Like error reports, translations use their own client rather than the one for fetching saved
+/// URLs, so they aren't cut short by its timeouts.
+///
+/// # Errors
+///
+/// Returns an error if the URL is invalid or the HTTP client cannot be built.
+pub fn translator_from_config(url: Option<&str>, api_key: Option<String>) -> Result<Option<Arc<dyn Translator>>> {
+    let Some(url) = url else {
+        return Ok(None);
+    };
+    let client = Client::builder()
+        .timeout(TRANSLATE_TIMEOUT)
+        .build()
+        .with_context(|| "🔥 Could not build the translation HTTP client")?;
+    let translator = LibreTranslate::new(url, api_key, client)?;
+    println!("🌍 Translation of bookmarks is enabled.");
+    Ok(Some(Arc::new(translator)))
+}
+
+/// Normalizes a language tag like `en-US` or `pt_BR` to its primary language subtag (`en`, `pt`).
+///
+/// Returns `None` for anything that isn't a two or three letter language code.
+pub fn normalize_language(tag: &str) -> Option<String> {
+    let primary = tag.trim().split(['-', '_']).next()?.to_ascii_lowercase();
+    (matches!(primary.len(), 2 | 3) && primary.chars().all(|c| c.is_ascii_lowercase())).then_some(primary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_language() {
+        assert_eq!(normalize_language("en-US"), Some("en".to_string()));
+        assert_eq!(normalize_language(" pt_BR "), Some("pt".to_string()));
+        assert_eq!(normalize_language("DE"), Some("de".to_string()));
+        assert_eq!(normalize_language("fil"), Some("fil".to_string()));
+        assert_eq!(normalize_language(""), None);
+        assert_eq!(normalize_language("english"), None);
+        assert_eq!(normalize_language("e1"), None);
+    }
+
+    #[test]
+    fn test_translate_url() {
+        let translator = LibreTranslate::new("https://translate.example.com", None, Client::new()).unwrap();
+        assert_eq!(translator.translate_url.as_str(), "https://translate.example.com/translate");
+
+        let translator = LibreTranslate::new("https://example.com/libre/", None, Client::new()).unwrap();
+        assert_eq!(translator.translate_url.as_str(), "https://example.com/libre/translate");
+
+        assert!(LibreTranslate::new("not a url", None, Client::new()).is_err());
+        assert!(LibreTranslate::new("ftp://example.com", None, Client::new()).is_err());
+    }
+}
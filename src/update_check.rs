@@ -0,0 +1,131 @@
+//! Checks for new releases of `PagePouch`.
+//!
+//! Opt-in with `PAGEPOUCH_UPDATE_CHECK=true`: once a day the server asks the project's release
+//! feed for the latest release, and admins see a banner when it's newer than the running version.
+//! Nothing is sent besides the request itself, and with the check off (the default) the server
+//! never contacts the feed, so air-gapped instances don't try to.
+
+use std::{sync::RwLock, time::Duration};
+
+use anyhow::{Context, Result};
+use reqwest::Client;
+use serde::Deserialize;
+
+/// The project's release feed; answers with the latest release that isn't a draft or pre-release.
+const RELEASES_URL: &str = "https://api.github.com/repos/anson-vandoren/pagepouch-rs/releases/latest";
+/// Maximum time to wait for the release feed.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+/// The running version.
+pub const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// A published release.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Release {
+    /// e.g. "0.2.0", without the tag's leading "v"
+    pub version: String,
+    /// Release page, with the changelog
+    pub url: String,
+}
+
+/// Release as returned by the release feed.
+#[derive(Deserialize)]
+struct FeedRelease {
+    tag_name: String,
+    html_url: String,
+}
+
+/// The update check and what it last found.
+pub struct UpdateCheck {
+    client: Client,
+    /// Newest release found, if it's newer than the running version
+    available: RwLock<Option<Release>>,
+}
+
+impl UpdateCheck {
+    /// Sets up the update check, which uses its own client rather than the one for fetching saved
+    /// URLs, so it isn't sent through the fetch proxy or cut short by its timeouts.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP client cannot be built.
+    pub fn new() -> Result<Self> {
+        let client = Client::builder()
+            .timeout(CHECK_TIMEOUT)
+            .user_agent(format!("PagePouch/{CURRENT_VERSION}"))
+            .build()
+            .with_context(|| "🔥 Could not build the update check HTTP client")?;
+        println!("🆕 Checking for new releases daily.");
+        Ok(Self {
+            client,
+            available: RwLock::new(None),
+        })
+    }
+
+    /// Asks the release feed for the latest release, remembering it if it's newer than the running
+    /// version, and returns it if so.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the feed can't be reached or answers with something unexpected.
+    #[tracing::instrument(level = "trace", name = "http.update_check", skip_all)]
+    pub async fn check(&self) -> Result<Option<Release>> {
+        let response = self
+            .client
+            .get(RELEASES_URL)
+            .header("Accept", "application/vnd.github+json")
+            .send()
+            .await?
+            .error_for_status()?;
+        let release: FeedRelease = serde_json::from_slice(&response.bytes().await?).context("Unexpected response from the release feed")?;
+        let version = release.tag_name.trim_start_matches('v').to_string();
+        let available = is_newer(&version, CURRENT_VERSION).then_some(Release {
+            version,
+            url: release.html_url,
+        });
+
+        let mut stored = self.available.write().unwrap_or_else(std::sync::PoisonError::into_inner);
+        stored.clone_from(&available);
+        Ok(available)
+    }
+
+    /// The newer release found by the last successful check, if any.
+    pub fn available(&self) -> Option<Release> {
+        self.available.read().unwrap_or_else(std::sync::PoisonError::into_inner).clone()
+    }
+}
+
+/// Splits a version like "1.2.3" into its numbers, ignoring any pre-release or build suffix.
+fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let core = version.split(['-', '+']).next()?;
+    let mut numbers = core.split('.').map(str::parse);
+    let version = (numbers.next()?.ok()?, numbers.next()?.ok()?, numbers.next()?.ok()?);
+    numbers.next().is_none().then_some(version)
+}
+
+/// Whether `latest` is a newer version than `current`. Versions that can't be parsed never are.
+fn is_newer(latest: &str, current: &str) -> bool {
+    match (parse_version(latest), parse_version(current)) {
+        (Some(latest), Some(current)) => latest > current,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_newer() {
+        assert!(is_newer("0.1.5", "0.1.4"));
+        assert!(is_newer("0.10.0", "0.9.9"));
+        assert!(is_newer("1.0.0", "0.99.0"));
+        assert!(!is_newer("0.1.4", "0.1.4"));
+        assert!(!is_newer("0.1.3", "0.1.4"));
+        // Pre-release and build suffixes don't count
+        assert!(!is_newer("0.1.4-rc.1", "0.1.4"));
+        assert!(is_newer("0.2.0+build.5", "0.1.4"));
+        assert!(!is_newer("latest", "0.1.4"));
+        assert!(!is_newer("0.2", "0.1.4"));
+        assert!(!is_newer("0.2.0.1", "0.1.4"));
+    }
+}
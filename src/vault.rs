@@ -0,0 +1,181 @@
+//! Bookmark export as a markdown vault, for Obsidian and other note apps.
+//!
+//! Each bookmark becomes its own markdown file with YAML frontmatter (URL, tags, dates) and the
+//! description as the body, all in one zip file. Files are named after the bookmark's title, so
+//! they can be linked to from other notes as `[[Title]]`.
+
+use std::{
+    collections::HashSet,
+    io::{Cursor, Write as _},
+};
+
+use anyhow::Result;
+use chrono::DateTime;
+use zip::{ZipWriter, write::SimpleFileOptions};
+
+use crate::db::bookmarks::ExportedBookmark;
+
+/// Folder in the zip file the notes are put in.
+const VAULT_FOLDER: &str = "PagePouch";
+/// Longest file name used for a note, in characters, leaving room for a suffix and `.md`.
+const MAX_FILE_NAME_LENGTH: usize = 100;
+
+/// Builds the zip file with one markdown note per bookmark.
+///
+/// # Errors
+///
+/// Returns an error if the zip file can't be written.
+pub fn write_vault(bookmarks: &[ExportedBookmark]) -> Result<Vec<u8>> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default();
+    let mut used_names = HashSet::new();
+
+    for bookmark in bookmarks {
+        let name = unique_file_name(&bookmark.title, &mut used_names);
+        zip.start_file(format!("{VAULT_FOLDER}/{name}.md"), options)?;
+        zip.write_all(note(bookmark).as_bytes())?;
+    }
+
+    Ok(zip.finish()?.into_inner())
+}
+
+/// The markdown note for a bookmark.
+fn note(bookmark: &ExportedBookmark) -> String {
+    let mut lines = vec![
+        "---".to_string(),
+        format!("title: {}", yaml_string(&bookmark.title)),
+        format!("url: {}", yaml_string(&bookmark.url)),
+    ];
+    if bookmark.tags.is_empty() {
+        lines.push("tags: []".to_string());
+    } else {
+        lines.push("tags:".to_string());
+        lines.extend(bookmark.tags.iter().map(|tag| format!("  - {}", yaml_string(tag))));
+    }
+    lines.push(format!("created: {}", format_date(bookmark.created_at)));
+    lines.push(format!("updated: {}", format_date(bookmark.updated_at)));
+    if let Some(language) = &bookmark.language {
+        lines.push(format!("language: {}", yaml_string(language)));
+    }
+    if bookmark.is_archived {
+        lines.push("archived: true".to_string());
+    }
+    lines.push("---".to_string());
+    lines.push(String::new());
+
+    if let Some(description) = &bookmark.description {
+        lines.push(description.trim().to_string());
+        lines.push(String::new());
+    }
+    lines.push(format!("[Open page](<{}>)", bookmark.url.replace('>', "%3E")));
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+/// Quotes a string for YAML. JSON strings are valid double-quoted YAML scalars.
+fn yaml_string(value: &str) -> String {
+    serde_json::Value::from(value).to_string()
+}
+
+fn format_date(timestamp: i64) -> String {
+    DateTime::from_timestamp(timestamp, 0).map_or_else(String::new, |dt| dt.format("%Y-%m-%dT%H:%M:%SZ").to_string())
+}
+
+/// A file name for a note from the bookmark's title, without characters that aren't allowed in file
+/// names or that note apps treat specially in links. Repeated titles get a numbered suffix.
+fn unique_file_name(title: &str, used_names: &mut HashSet<String>) -> String {
+    let cleaned: String = title
+        .chars()
+        .map(|c| {
+            if c.is_control() || matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' | '#' | '^' | '[' | ']') {
+                ' '
+            } else {
+                c
+            }
+        })
+        .collect();
+    let cleaned = cleaned.split_whitespace().collect::<Vec<_>>().join(" ");
+    // A leading dot would hide the file, and Windows drops trailing ones
+    let cleaned: String = cleaned
+        .trim_matches(|c| c == '.' || c == ' ')
+        .chars()
+        .take(MAX_FILE_NAME_LENGTH)
+        .collect();
+    let base = match cleaned.trim() {
+        "" => "Untitled",
+        name => name,
+    };
+
+    let mut name = base.to_string();
+    let mut count = 1;
+    // Compared case-insensitively, as the vault may end up on a case-insensitive file system
+    while !used_names.insert(name.to_lowercase()) {
+        count += 1;
+        name = format!("{base} ({count})");
+    }
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bookmark(title: &str) -> ExportedBookmark {
+        ExportedBookmark {
+            url: "https://example.com/post?a=1".to_string(),
+            title: title.to_string(),
+            description: None,
+            tags: Vec::new(),
+            created_at: 1_700_000_000,
+            updated_at: 1_700_000_000,
+            language: None,
+            is_archived: false,
+        }
+    }
+
+    #[test]
+    fn test_note() {
+        let note = note(&ExportedBookmark {
+            description: Some("Worth a read.\n".to_string()),
+            tags: vec!["rust".to_string(), "reading/async".to_string()],
+            language: Some("en".to_string()),
+            is_archived: true,
+            ..bookmark("Say \"hi\": a post")
+        });
+        assert_eq!(
+            note,
+            "---\n\
+             title: \"Say \\\"hi\\\": a post\"\n\
+             url: \"https://example.com/post?a=1\"\n\
+             tags:\n  - \"rust\"\n  - \"reading/async\"\n\
+             created: 2023-11-14T22:13:20Z\n\
+             updated: 2023-11-14T22:13:20Z\n\
+             language: \"en\"\n\
+             archived: true\n\
+             ---\n\n\
+             Worth a read.\n\n\
+             [Open page](<https://example.com/post?a=1>)\n"
+        );
+        assert!(super::note(&bookmark("Plain")).contains("tags: []\n"));
+    }
+
+    #[test]
+    fn test_unique_file_name() {
+        let mut used = HashSet::new();
+        assert_eq!(unique_file_name("Rust: a/b [guide]?", &mut used), "Rust a b guide");
+        assert_eq!(unique_file_name("rust a b guide", &mut used), "rust a b guide (2)");
+        assert_eq!(unique_file_name("  ", &mut used), "Untitled");
+        assert_eq!(unique_file_name("../..", &mut used), "Untitled (2)");
+        assert_eq!(unique_file_name(&"x".repeat(300), &mut used).chars().count(), MAX_FILE_NAME_LENGTH);
+    }
+
+    #[test]
+    fn test_write_vault() {
+        let zip = write_vault(&[bookmark("One"), bookmark("One")]).unwrap();
+        let archive = zip::ZipArchive::new(Cursor::new(zip)).unwrap();
+        let names: Vec<&str> = archive.file_names().collect();
+        assert_eq!(names.len(), 2);
+        assert!(names.contains(&"PagePouch/One.md"));
+        assert!(names.contains(&"PagePouch/One (2).md"));
+    }
+}
@@ -0,0 +1,322 @@
+//! Bookmarks dropped into a watched directory, for automations that can write files but can't call
+//! the API: a folder synced from a phone with Syncthing, a scanner's output folder, a shell script.
+//!
+//! With `PAGEPOUCH_WATCH_DIR` set, the directory is checked every few seconds (see
+//! [`crate::jobs::spawn_directory_watch`]) and the links in each of these files are saved for the
+//! user named in `PAGEPOUCH_WATCH_USER`:
+//! - `.url`: Windows internet shortcuts, titled by the file name
+//! - `.webloc`: macOS web locations (XML property lists), titled by the file name
+//! - `.txt`: a URL at the start of each line, optionally followed by a title
+//!
+//! Files are then moved to `imported/`, or to `failed/` if no links could be read from them, so each
+//! is only saved once. Other files, and hidden ones like Syncthing's temporary files, are left alone.
+
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{Context as _, Result, anyhow, bail};
+use reqwest::Url;
+use tracing::{info, warn};
+
+use crate::{
+    blocklist::Blocklist,
+    clean::UrlCleaner,
+    config::WatchConfig,
+    db::{
+        Db,
+        bookmarks::{self, BookmarkOrigin, BookmarkSource, NewBookmark},
+        users,
+    },
+    error::AppResult,
+};
+
+/// Where files are moved once their links are saved.
+pub const IMPORTED_DIR: &str = "imported";
+/// Where files are moved if no links could be read from them.
+pub const FAILED_DIR: &str = "failed";
+/// Largest file read; anything bigger is moved to `failed/`.
+const MAX_FILE_SIZE: u64 = 1024 * 1024;
+/// Files changed more recently than this may still be being written, and are left for the next check.
+const SETTLE_TIME: Duration = Duration::from_secs(2);
+
+/// A link read from a dropped file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DroppedLink {
+    pub url: String,
+    pub title: Option<String>,
+}
+
+/// The kinds of files links are read from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum FileKind {
+    InternetShortcut,
+    Webloc,
+    Text,
+}
+
+impl FileKind {
+    /// The kind of a file by its extension, ignoring case. Hidden files have none.
+    fn of(file_name: &str) -> Option<Self> {
+        if file_name.starts_with('.') {
+            return None;
+        }
+        let (_, extension) = file_name.rsplit_once('.')?;
+        match extension.to_ascii_lowercase().as_str() {
+            "url" => Some(Self::InternetShortcut),
+            "webloc" => Some(Self::Webloc),
+            "txt" => Some(Self::Text),
+            _ => None,
+        }
+    }
+}
+
+/// Reads the links from a dropped file.
+///
+/// # Errors
+///
+/// Returns an error if the file isn't one of the watched kinds or has no web links in it.
+pub fn parse(file_name: &str, contents: &[u8]) -> Result<Vec<DroppedLink>> {
+    let kind = FileKind::of(file_name).ok_or_else(|| anyhow!("Not a .url, .webloc or .txt file"))?;
+    if contents.starts_with(b"bplist") {
+        bail!("Binary property lists aren't supported; save the .webloc as XML");
+    }
+    let contents = String::from_utf8_lossy(contents);
+    let stem = file_name.rsplit_once('.').map_or(file_name, |(stem, _)| stem).trim();
+    let title = (!stem.is_empty()).then(|| stem.to_string());
+
+    let links = match kind {
+        FileKind::InternetShortcut => shortcut_url(&contents).map(|url| DroppedLink { url, title }).into_iter().collect(),
+        FileKind::Webloc => webloc_url(&contents).map(|url| DroppedLink { url, title }).into_iter().collect(),
+        FileKind::Text => text_links(&contents),
+    };
+    let links: Vec<DroppedLink> = links.into_iter().filter(|link| is_web_url(&link.url)).collect();
+    if links.is_empty() {
+        bail!("No http(s) links found");
+    }
+    Ok(links)
+}
+
+/// The `URL=` line of an internet shortcut.
+fn shortcut_url(contents: &str) -> Option<String> {
+    contents.lines().find_map(|line| {
+        let (key, value) = line.trim().split_once('=')?;
+        key.trim().eq_ignore_ascii_case("url").then(|| value.trim().to_string())
+    })
+}
+
+/// The string after the `URL` key of a web location's property list.
+fn webloc_url(contents: &str) -> Option<String> {
+    let (_, after_key) = contents.split_once("<key>URL</key>")?;
+    let (_, after_tag) = after_key.split_once("<string>")?;
+    let (url, _) = after_tag.split_once("</string>")?;
+    Some(unescape_xml(url.trim()))
+}
+
+fn unescape_xml(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Lines starting with a URL, with the rest of the line as the title. Other lines are skipped.
+fn text_links(contents: &str) -> Vec<DroppedLink> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            let (url, title) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let title = title.trim();
+            is_web_url(url).then(|| DroppedLink {
+                url: url.to_string(),
+                title: (!title.is_empty()).then(|| title.to_string()),
+            })
+        })
+        .collect()
+}
+
+fn is_web_url(url: &str) -> bool {
+    Url::parse(url).is_ok_and(|url| matches!(url.scheme(), "http" | "https"))
+}
+
+/// Saves the links in every file that's been dropped into the watched directory, moving each file
+/// out of the way afterwards. Returns how many bookmarks were saved.
+///
+/// Files are left where they are if the user doesn't exist or their bookmarks can't be saved, and
+/// tried again on the next call.
+///
+/// # Errors
+///
+/// Returns an error if the directory can't be read, or the database can't be.
+pub async fn import_dropped_files(db: &Db, watch: &WatchConfig) -> AppResult<usize> {
+    let files = dropped_files(&watch.dir).await?;
+    if files.is_empty() {
+        return Ok(0);
+    }
+    let Some(user) = users::get_active_users(db)
+        .await?
+        .into_iter()
+        .find(|user| user.username.eq_ignore_ascii_case(&watch.username))
+    else {
+        warn!(
+            username = watch.username,
+            files = files.len(),
+            "📂 No active user for dropped files, leaving them for now."
+        );
+        return Ok(0);
+    };
+
+    let cleaner = UrlCleaner::for_user(db, user.user_id).await;
+    let blocklist = Blocklist::for_user(db, user.user_id).await?;
+    let mut saved = 0;
+    for (path, file_name) in files {
+        let links = match read_dropped_file(&path, &file_name).await {
+            Ok(links) => links,
+            Err(err) => {
+                warn!(file_name, error = %err, "📂 Could not read links from a dropped file.");
+                move_to(&watch.dir, FAILED_DIR, &path, &file_name).await?;
+                continue;
+            }
+        };
+
+        let new_bookmarks: Vec<NewBookmark> = links
+            .into_iter()
+            .filter_map(|link| {
+                let bookmark = cleaner.apply(NewBookmark {
+                    title: link.title.unwrap_or_else(|| link.url.clone()),
+                    url: link.url,
+                    ..NewBookmark::default()
+                });
+                blocklist
+                    .apply(bookmark)
+                    .inspect_err(|reason| info!(file_name, reason, "📂 Skipped a dropped link."))
+                    .ok()
+            })
+            .collect();
+        let origin = BookmarkOrigin {
+            source: BookmarkSource::Folder,
+            detail: Some(file_name.clone()),
+        };
+        let ids = bookmarks::create_bookmarks(db, user.user_id, &new_bookmarks, &origin).await?;
+        info!(
+            file_name,
+            saved = ids.len(),
+            username = user.username,
+            "📂 Saved bookmarks from a dropped file."
+        );
+        saved += ids.len();
+        move_to(&watch.dir, IMPORTED_DIR, &path, &file_name).await?;
+    }
+
+    Ok(saved)
+}
+
+/// The files of the watched kinds in `dir` that are done being written, with their names.
+async fn dropped_files(dir: &Path) -> Result<Vec<(PathBuf, String)>> {
+    let mut entries = tokio::fs::read_dir(dir)
+        .await
+        .with_context(|| format!("Could not read the watched directory {}", dir.display()))?;
+    let mut files = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        let Ok(file_name) = entry.file_name().into_string() else {
+            continue;
+        };
+        if FileKind::of(&file_name).is_none() {
+            continue;
+        }
+        let metadata = entry.metadata().await?;
+        let settled = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+            .is_some_and(|age| age >= SETTLE_TIME);
+        if metadata.is_file() && settled {
+            files.push((entry.path(), file_name));
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+async fn read_dropped_file(path: &Path, file_name: &str) -> Result<Vec<DroppedLink>> {
+    let size = tokio::fs::metadata(path).await?.len();
+    if size > MAX_FILE_SIZE {
+        bail!("Larger than {} KiB", MAX_FILE_SIZE / 1024);
+    }
+    parse(file_name, &tokio::fs::read(path).await?)
+}
+
+/// Moves a file into a subdirectory of the watched directory, prefixing its name with the time if
+/// there's already a file by that name.
+async fn move_to(dir: &Path, subdirectory: &str, path: &Path, file_name: &str) -> Result<()> {
+    let target_dir = dir.join(subdirectory);
+    tokio::fs::create_dir_all(&target_dir).await?;
+    let mut target = target_dir.join(file_name);
+    if tokio::fs::try_exists(&target).await? {
+        target = target_dir.join(format!("{}-{file_name}", chrono::Utc::now().timestamp()));
+    }
+    tokio::fs::rename(path, &target)
+        .await
+        .with_context(|| format!("Could not move {} to {}", path.display(), target.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn link(url: &str, title: Option<&str>) -> DroppedLink {
+        DroppedLink {
+            url: url.to_string(),
+            title: title.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_parse_internet_shortcut() {
+        let contents = b"[{000214A0-0000-0000-C000-000000000046}]\r\nProp3=19,11\r\n[InternetShortcut]\r\nIDList=\r\nURL=https://tokio.rs/tokio/tutorial\r\n";
+        assert_eq!(
+            parse("Tokio tutorial.URL", contents).unwrap(),
+            [link("https://tokio.rs/tokio/tutorial", Some("Tokio tutorial"))]
+        );
+        assert!(parse("Local.url", b"[InternetShortcut]\nURL=file:///etc/passwd\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_webloc() {
+        let contents = br#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>URL</key>
+	<string>https://example.com/search?q=rust&amp;page=2</string>
+</dict>
+</plist>"#;
+        assert_eq!(
+            parse("Search.webloc", contents).unwrap(),
+            [link("https://example.com/search?q=rust&page=2", Some("Search"))]
+        );
+        assert!(parse("Binary.webloc", b"bplist00\xd1\x01\x02SURL").is_err());
+    }
+
+    #[test]
+    fn test_parse_text() {
+        let contents = b"Links from the scanner\n\nhttps://axum.rs Axum docs\n  http://example.com\nnot a link\nftp://example.com/file\n";
+        assert_eq!(
+            parse("links.txt", contents).unwrap(),
+            [link("https://axum.rs", Some("Axum docs")), link("http://example.com", None)]
+        );
+        assert!(parse("empty.txt", b"nothing to see\n").is_err());
+    }
+
+    #[test]
+    fn test_watched_files() {
+        assert_eq!(FileKind::of("a.TXT"), Some(FileKind::Text));
+        assert_eq!(FileKind::of(".syncthing.a.url.tmp"), None);
+        assert_eq!(FileKind::of(".hidden.url"), None);
+        assert_eq!(FileKind::of("photo.jpg"), None);
+        assert_eq!(FileKind::of("README"), None);
+    }
+}
@@ -0,0 +1,124 @@
+//! Copies of bookmarked pages in the Internet Archive's Wayback Machine.
+//!
+//! With `PAGEPOUCH_WAYBACK_LOOKUP=true`, when the link checker (see [`crate::link_check`]) finds a
+//! link broken, the closest copy the Wayback Machine has is looked up with its availability API,
+//! and offered on the bookmark as an archived copy to view instead. With
+//! `PAGEPOUCH_WAYBACK_SUBMIT=true`, new bookmarks are also sent to it to be saved, so there's a copy
+//! to find should they break. Both send bookmarked links to archive.org, so they're off by default,
+//! and bookmarks of users who opted out of fetching are left alone.
+
+use std::sync::Arc;
+
+use anyhow::{Context as _, Result};
+use reqwest::{Client, Url};
+use serde::Deserialize;
+use tracing::{debug, error};
+use uuid::Uuid;
+
+use crate::{
+    AppState,
+    fetch::{check_destination, is_fetch_allowed},
+};
+
+/// Answers with the closest copy of a page, if there is one.
+const AVAILABILITY_URL: &str = "https://archive.org/wayback/available";
+/// Saves a copy of the page whose URL follows.
+const SAVE_URL: &str = "https://web.archive.org/save/";
+
+/// Answer of the availability API.
+#[derive(Deserialize)]
+struct Availability {
+    archived_snapshots: ArchivedSnapshots,
+}
+
+#[derive(Deserialize)]
+struct ArchivedSnapshots {
+    closest: Option<Snapshot>,
+}
+
+#[derive(Deserialize)]
+struct Snapshot {
+    available: bool,
+    url: String,
+    /// HTTP status the page was saved with, e.g. "200"
+    status: String,
+}
+
+/// Looks up the closest copy of a page in the Wayback Machine.
+///
+/// # Errors
+///
+/// Returns an error if the availability API can't be reached or answers with something unexpected.
+#[tracing::instrument(level = "trace", name = "http.wayback_lookup", skip_all, fields(url = url))]
+pub async fn find_copy(client: &Client, url: &str) -> Result<Option<String>> {
+    let lookup = Url::parse_with_params(AVAILABILITY_URL, [("url", url)])?;
+    let response = client.get(lookup).send().await?.error_for_status()?;
+    closest_copy(&response.bytes().await?)
+}
+
+/// The closest copy in an answer of the availability API, if it's of the page itself rather than
+/// of an error.
+fn closest_copy(body: &[u8]) -> Result<Option<String>> {
+    let availability: Availability = serde_json::from_slice(body).context("Unexpected response from the Wayback Machine")?;
+    Ok(availability
+        .archived_snapshots
+        .closest
+        .filter(|snapshot| snapshot.available && snapshot.status.starts_with('2'))
+        .map(|snapshot| match snapshot.url.strip_prefix("http://") {
+            Some(rest) => format!("https://{rest}"),
+            None => snapshot.url,
+        }))
+}
+
+/// Sends a new bookmark to the Wayback Machine to be saved, if the instance does and the user
+/// didn't opt out of fetching. Failures are only logged, as nobody is waiting on this.
+pub async fn submit_new_bookmark(state: Arc<AppState>, user_id: Uuid, url: String) {
+    if !state.wayback_submit {
+        return;
+    }
+    match is_fetch_allowed(&state.fetch_config, &state.db, user_id).await {
+        Ok(true) => {}
+        Ok(false) => return,
+        Err(err) => {
+            error!(
+                ?err,
+                "🚨 Could not load fetch setting, not sending the bookmark to the Wayback Machine."
+            );
+            return;
+        }
+    }
+    if !(url.starts_with("http://") || url.starts_with("https://")) || check_destination(&state.fetch_config, &url).is_err() {
+        return;
+    }
+    match state.http_client.get(format!("{SAVE_URL}{url}")).send().await {
+        Ok(response) if response.status().is_success() => debug!(url, "🏛️ Sent a new bookmark to the Wayback Machine."),
+        Ok(response) => debug!(url, status = %response.status(), "🏛️ The Wayback Machine didn't save a new bookmark."),
+        Err(err) => debug!(url, %err, "🏛️ Could not send a new bookmark to the Wayback Machine."),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_closest_copy() {
+        let found = br#"{"url": "example.com/gone", "archived_snapshots": {"closest": {"status": "200", "available": true,
+            "url": "http://web.archive.org/web/20240101000000/https://example.com/gone", "timestamp": "20240101000000"}}}"#;
+        assert_eq!(
+            closest_copy(found).unwrap().as_deref(),
+            Some("https://web.archive.org/web/20240101000000/https://example.com/gone")
+        );
+
+        // Never saved, or only saved as an error page
+        assert_eq!(
+            closest_copy(br#"{"url": "example.com/gone", "archived_snapshots": {}}"#).unwrap(),
+            None
+        );
+        let error_page =
+            br#"{"archived_snapshots": {"closest": {"status": "404", "available": true, "url": "http://web.archive.org/web/2024/x"}}}"#;
+        assert_eq!(closest_copy(error_page).unwrap(), None);
+
+        assert!(closest_copy(b"<html>Too many requests</html>").is_err());
+    }
+}